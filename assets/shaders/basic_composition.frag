@@ -1,15 +1,121 @@
 #version 450
 
+#define MAX_LIGHTS 32
+#define PI 3.14159265359
+
 layout(location = 0) in vec2 in_pos;
 
 layout(input_attachment_index = 0, set = 0, binding = 0) uniform subpassInput inputColor;
 layout(input_attachment_index = 1, set = 0, binding = 1) uniform subpassInput inputNormals;
 layout(input_attachment_index = 2, set = 0, binding = 2) uniform subpassInput inputPositionDepth;
+// Metallic, roughness, ambient occlusion and `1.0` for lit pixels
+layout(input_attachment_index = 3, set = 0, binding = 3) uniform subpassInput inputMaterial;
+
+layout(set = 1, binding = 0) uniform SceneUniform {
+    mat4 view;
+    mat4 projection;
+    vec4 camera_position;
+    vec4 resolution;
+    float time;
+    float delta_time;
+    float near;
+    float far;
+} scene_ubo;
+
+struct Light {
+    // `w` is `1.0` for point lights and `0.0` for directional ones
+    vec4 position;
+    vec4 direction;
+    vec4 radiance;
+};
+
+layout(set = 2, binding = 0) uniform LightUniform {
+    // `w` is the amount of lights
+    vec4 ambient;
+    Light lights[MAX_LIGHTS];
+} light_ubo;
 
 layout(location = 0) out vec4 out_color;
 
+float distribution_ggx(float n_dot_h, float roughness) {
+    float a = roughness * roughness;
+    float a2 = a * a;
+    float denom = n_dot_h * n_dot_h * (a2 - 1.0) + 1.0;
+
+    return a2 / (PI * denom * denom);
+}
+
+float geometry_schlick_ggx(float n_dot_x, float roughness) {
+    float r = roughness + 1.0;
+    float k = r * r / 8.0;
+
+    return n_dot_x / (n_dot_x * (1.0 - k) + k);
+}
+
+vec3 fresnel_schlick(float cos_theta, vec3 f0) {
+    return f0 + (1.0 - f0) * pow(clamp(1.0 - cos_theta, 0.0, 1.0), 5.0);
+}
 
 void main() {
-    out_color = subpassLoad(inputColor);
-    //out_color = vec4(0.5, 0.3, 1.0, 1.0);
+    vec4 albedo = subpassLoad(inputColor);
+    vec4 material = subpassLoad(inputMaterial);
+
+    int light_count = min(int(light_ubo.ambient.w), MAX_LIGHTS);
+
+    // The background, skyboxes and scenes without lights keep their color
+    if (material.w < 0.5 || light_count == 0) {
+        out_color = albedo;
+        return;
+    }
+
+    vec3 normal = normalize(subpassLoad(inputNormals).xyz);
+    vec3 position = subpassLoad(inputPositionDepth).xyz;
+
+    float metallic = material.x;
+    float roughness = clamp(material.y, 0.04, 1.0);
+    float occlusion = material.z;
+
+    vec3 view_dir = normalize(scene_ubo.camera_position.xyz - position);
+    float n_dot_v = max(dot(normal, view_dir), 1e-4);
+
+    vec3 f0 = mix(vec3(0.04), albedo.rgb, metallic);
+
+    vec3 color = light_ubo.ambient.rgb * albedo.rgb * occlusion;
+
+    for (int i = 0; i < light_count; i++) {
+        Light light = light_ubo.lights[i];
+
+        vec3 light_dir;
+        vec3 radiance = light.radiance.rgb;
+
+        if (light.position.w > 0.5) {
+            vec3 to_light = light.position.xyz - position;
+            float distance_sq = max(dot(to_light, to_light), 1e-4);
+
+            light_dir = to_light * inversesqrt(distance_sq);
+            radiance /= distance_sq;
+        } else {
+            light_dir = -light.direction.xyz;
+        }
+
+        float n_dot_l = max(dot(normal, light_dir), 0.0);
+
+        if (n_dot_l <= 0.0) {
+            continue;
+        }
+
+        vec3 halfway = normalize(view_dir + light_dir);
+        float n_dot_h = max(dot(normal, halfway), 0.0);
+
+        float d = distribution_ggx(n_dot_h, roughness);
+        float g = geometry_schlick_ggx(n_dot_v, roughness) * geometry_schlick_ggx(n_dot_l, roughness);
+        vec3 f = fresnel_schlick(max(dot(halfway, view_dir), 0.0), f0);
+
+        vec3 specular = d * g * f / (4.0 * n_dot_v * n_dot_l + 1e-4);
+        vec3 diffuse = (1.0 - f) * (1.0 - metallic) * albedo.rgb / PI;
+
+        color += (diffuse + specular) * radiance * n_dot_l;
+    }
+
+    out_color = vec4(color, albedo.a);
 }
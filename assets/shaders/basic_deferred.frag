@@ -3,13 +3,42 @@
 layout(location = 0) in vec3 in_color;
 layout(location = 1) in vec3 in_normal;
 layout(location = 2) in vec3 in_position;
+layout(location = 3) in vec2 in_uv;
+layout(location = 4) in vec4 in_tangent;
+layout(location = 5) in vec3 in_current_position;
+layout(location = 6) in vec3 in_previous_position;
 
 layout(location = 0) out vec4 out_color;
 layout(location = 1) out vec4 out_normal;
 layout(location = 2) out vec4 out_position;
+layout(location = 3) out vec4 out_material;
+// Motion since the previous frame in pixels
+layout(location = 4) out vec4 out_velocity;
+
+layout(set = 2, binding = 0) uniform sampler2D normal_map;
+
+vec3 surface_normal() {
+    vec3 normal = normalize(in_normal);
+
+    // Meshes without tangents keep the interpolated vertex normal
+    if (dot(in_tangent.xyz, in_tangent.xyz) < 1e-8) {
+        return normal;
+    }
+
+    vec3 tangent = normalize(in_tangent.xyz - normal * dot(normal, in_tangent.xyz));
+    vec3 bitangent = cross(normal, tangent) * (in_tangent.w < 0.0 ? -1.0 : 1.0);
+
+    vec3 tangent_normal = texture(normal_map, in_uv).xyz * 2.0 - 1.0;
+
+    return normalize(mat3(tangent, bitangent, normal) * tangent_normal);
+}
 
 void main() {
      out_color = vec4(in_color, 1.0);
-     out_normal = vec4(in_normal, 0.0);
+     out_normal = vec4(surface_normal(), 0.0);
      out_position = vec4(in_position, 0.0);
-}
\ No newline at end of file
+     // Rough dielectric, lit
+     out_material = vec4(0.0, 0.5, 1.0, 1.0);
+     out_velocity = vec4(in_current_position.xy / in_current_position.z
+         - in_previous_position.xy / in_previous_position.z, 0.0, 0.0);
+}
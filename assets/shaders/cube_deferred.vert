@@ -4,14 +4,30 @@
 
 layout(location = 0) in vec3 in_position;
 layout(location = 1) in vec3 in_normal;
+layout(location = 2) in vec2 in_uv;
+layout(location = 3) in vec4 in_tangent;
 
 layout(location = 0) out vec3 out_color;
 layout(location = 1) out vec3 out_normal;
 layout(location = 2) out vec3 out_position;
+layout(location = 3) out vec2 out_uv;
+layout(location = 4) out vec4 out_tangent;
+// Clip positions scaled to pixels with `+Y` down, the difference of the
+// current and the previous ones after the perspective division is the motion
+layout(location = 5) out vec3 out_current_position;
+layout(location = 6) out vec3 out_previous_position;
 
 layout(set = 0, binding = 0) uniform SceneUniform {
     mat4 view;
     mat4 projection;
+    vec4 camera_position;
+    vec4 resolution;
+    float time;
+    float delta_time;
+    float near;
+    float far;
+    mat4 previous_view_projection;
+    vec4 jitter;
 } scene_ubo;
 
 struct InstanceData {
@@ -23,13 +39,23 @@ layout(set = 1, binding = 0) uniform InstanceUbo {
     InstanceData data[MAX_UNIFORM_LENGTH];
 } instance_ubo;
 
+vec3 to_pixels(vec4 clip_position) {
+    return vec3(clip_position.xy * vec2(0.5, -0.5) * scene_ubo.resolution.xy, clip_position.w);
+}
+
 void main() {
     InstanceData instance_data = instance_ubo.data[gl_InstanceIndex];
 
-    vec4 pos = scene_ubo.projection * scene_ubo.view * instance_data.transform * vec4(in_position, 1.0);
-    gl_Position = pos;
-    out_position = vec3(pos);
+    vec4 world_position = instance_data.transform * vec4(in_position, 1.0);
+    gl_Position = scene_ubo.projection * scene_ubo.view * world_position;
+    out_position = world_position.xyz;
+
+    vec4 previous_world_position = instance_data.transform * vec4(in_position, 1.0);
+    out_current_position = to_pixels(gl_Position - vec4(scene_ubo.jitter.xy * gl_Position.w, 0.0, 0.0));
+    out_previous_position = to_pixels(scene_ubo.previous_view_projection * previous_world_position);
 
     out_color = instance_data.color;
     out_normal = mat3(transpose(inverse(instance_data.transform))) * in_normal;
+    out_uv = in_uv;
+    out_tangent = vec4(mat3(instance_data.transform) * in_tangent.xyz, in_tangent.w);
 }
@@ -0,0 +1,31 @@
+#version 450
+
+layout(location = 0) in vec3 in_direction;
+layout(location = 1) in vec3 in_current_position;
+layout(location = 2) in vec3 in_previous_position;
+
+layout(location = 0) out vec4 out_color;
+layout(location = 1) out vec4 out_normal;
+layout(location = 2) out vec4 out_position;
+layout(location = 3) out vec4 out_material;
+layout(location = 4) out vec4 out_velocity;
+
+const vec3 ZENITH_COLOR = vec3(0.18, 0.36, 0.68);
+const vec3 HORIZON_COLOR = vec3(0.72, 0.80, 0.88);
+const vec3 GROUND_COLOR = vec3(0.22, 0.21, 0.20);
+
+void main() {
+    float height = normalize(in_direction).y;
+
+    vec3 color = height >= 0.0
+        ? mix(HORIZON_COLOR, ZENITH_COLOR, pow(height, 0.5))
+        : mix(HORIZON_COLOR, GROUND_COLOR, pow(-height, 0.3));
+
+    out_color = vec4(color, 1.0);
+    out_normal = vec4(0.0);
+    out_position = vec4(0.0);
+    // Unlit
+    out_material = vec4(0.0);
+    out_velocity = vec4(in_current_position.xy / in_current_position.z
+        - in_previous_position.xy / in_previous_position.z, 0.0, 0.0);
+}
@@ -0,0 +1,37 @@
+#version 450
+
+layout(set = 0, binding = 0) uniform SceneUniform {
+    mat4 view;
+    mat4 projection;
+    vec4 camera_position;
+    vec4 resolution;
+    float time;
+    float delta_time;
+    float near;
+    float far;
+    mat4 previous_view_projection;
+    vec4 jitter;
+} scene_ubo;
+
+layout(location = 0) out vec3 out_direction;
+// Same as in `basic_deferred.vert`, the sky is infinitely far away, so only
+// the rotation of the camera moves it
+layout(location = 1) out vec3 out_current_position;
+layout(location = 2) out vec3 out_previous_position;
+
+vec3 to_pixels(vec4 clip_position) {
+    return vec3(clip_position.xy * vec2(0.5, -0.5) * scene_ubo.resolution.xy, clip_position.w);
+}
+
+void main() {
+    vec2 pos = vec2((gl_VertexIndex << 1) & 2, gl_VertexIndex & 2) * 2.0 - 1.0;
+
+    // Unprojected onto the far plane and rotated into world space
+    vec4 view_pos = inverse(scene_ubo.projection) * vec4(pos, 1.0, 1.0);
+    out_direction = transpose(mat3(scene_ubo.view)) * (view_pos.xyz / view_pos.w);
+
+    out_current_position = to_pixels(vec4(pos - scene_ubo.jitter.xy, 1.0, 1.0));
+    out_previous_position = to_pixels(scene_ubo.previous_view_projection * vec4(out_direction, 0.0));
+
+    gl_Position = vec4(pos, 1.0, 1.0);
+}
@@ -0,0 +1,7 @@
+#version 450
+
+layout(location = 0) out vec4 out_mask;
+
+void main() {
+    out_mask = vec4(1.0);
+}
@@ -0,0 +1,45 @@
+#version 450
+
+layout(location = 0) in vec3 in_position;
+
+layout(set = 0, binding = 0) uniform SceneUniform {
+    mat4 view;
+    mat4 projection;
+    vec4 camera_position;
+    vec4 resolution;
+    float time;
+    float delta_time;
+    float near;
+    float far;
+    mat4 previous_view_projection;
+    vec4 jitter;
+} scene_ubo;
+
+// Instance data of any layout with a `transform` field
+layout(std430, set = 1, binding = 0) readonly buffer InstanceSsbo {
+    uint words[];
+} instance_ssbo;
+
+layout(push_constant) uniform InstanceLayout {
+    // Size of the instance data of one object in 4 byte words
+    uint stride;
+    // Offset of the `transform` field in 4 byte words
+    uint transform_offset;
+} instance_layout;
+
+mat4 instance_transform() {
+    uint base = gl_InstanceIndex * instance_layout.stride + instance_layout.transform_offset;
+    mat4 transform;
+
+    for (int column = 0; column < 4; column++) {
+        for (int row = 0; row < 4; row++) {
+            transform[column][row] = uintBitsToFloat(instance_ssbo.words[base + column * 4 + row]);
+        }
+    }
+
+    return transform;
+}
+
+void main() {
+    gl_Position = scene_ubo.projection * scene_ubo.view * instance_transform() * vec4(in_position, 1.0);
+}
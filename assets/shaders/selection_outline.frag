@@ -0,0 +1,44 @@
+#version 450
+
+layout(location = 0) in vec2 in_pos;
+
+// Silhouettes of the selected objects
+layout(set = 0, binding = 0) uniform sampler2D selection_mask;
+
+layout(push_constant) uniform Outline {
+    vec4 color;
+    // Outline width in pixels
+    int width;
+} outline;
+
+layout(location = 0) out vec4 out_color;
+
+void main() {
+    ivec2 pixel = ivec2(gl_FragCoord.xy);
+    ivec2 max_pixel = textureSize(selection_mask, 0) - 1;
+
+    // The outline goes around the silhouettes, not over them
+    if (texelFetch(selection_mask, pixel, 0).r > 0.0) {
+        discard;
+    }
+
+    // Dilates the mask by `width` pixels within a circle
+    float coverage = 0.0;
+
+    for (int y = -outline.width; y <= outline.width; y++) {
+        for (int x = -outline.width; x <= outline.width; x++) {
+            if (x * x + y * y > outline.width * outline.width) {
+                continue;
+            }
+
+            ivec2 neighbor = clamp(pixel + ivec2(x, y), ivec2(0), max_pixel);
+            coverage = max(coverage, texelFetch(selection_mask, neighbor, 0).r);
+        }
+    }
+
+    if (coverage == 0.0) {
+        discard;
+    }
+
+    out_color = vec4(outline.color.rgb, outline.color.a * coverage);
+}
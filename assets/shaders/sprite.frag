@@ -0,0 +1,17 @@
+#version 450
+#extension GL_EXT_nonuniform_qualifier : require
+
+// Has to match `MAX_SPRITE_TEXTURES`
+#define MAX_SPRITE_TEXTURES 16
+
+layout(location = 0) in vec2 in_uv;
+layout(location = 1) in vec4 in_color;
+layout(location = 2) flat in uint in_texture_index;
+
+layout(set = 1, binding = 0) uniform sampler2D sprite_textures[MAX_SPRITE_TEXTURES];
+
+layout(location = 0) out vec4 out_color;
+
+void main() {
+    out_color = texture(sprite_textures[nonuniformEXT(in_texture_index)], in_uv) * in_color;
+}
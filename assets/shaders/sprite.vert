@@ -0,0 +1,41 @@
+#version 450
+
+struct Sprite {
+    // Top-left corner and size in pixels
+    vec4 rect;
+    // Top-left and bottom-right texture coordinates
+    vec4 uv;
+    vec4 color;
+    // Index into `sprite_textures` of `sprite.frag`
+    uint texture_index;
+};
+
+layout(std430, set = 0, binding = 0) readonly buffer SpriteSsbo {
+    Sprite sprites[];
+} sprite_ssbo;
+
+layout(push_constant) uniform Viewport {
+    // Size of the drawn area in pixels
+    vec2 size;
+} viewport;
+
+layout(location = 0) out vec2 out_uv;
+layout(location = 1) out vec4 out_color;
+layout(location = 2) flat out uint out_texture_index;
+
+vec2 corners[] = {vec2(0.0, 0.0), vec2(1.0, 0.0), vec2(1.0, 1.0), vec2(0.0, 1.0)};
+int indices[] = {0, 1, 2, 0, 2, 3};
+
+void main() {
+    Sprite sprite = sprite_ssbo.sprites[gl_InstanceIndex];
+    vec2 corner = corners[indices[gl_VertexIndex]];
+
+    vec2 pixel = sprite.rect.xy + corner * sprite.rect.zw;
+    // The viewport is flipped, so `+Y` of the clip space is the top of the screen
+    vec2 pos = vec2(pixel.x / viewport.size.x * 2.0 - 1.0, 1.0 - pixel.y / viewport.size.y * 2.0);
+
+    out_uv = mix(sprite.uv.xy, sprite.uv.zw, corner);
+    out_color = sprite.color;
+    out_texture_index = sprite.texture_index;
+    gl_Position = vec4(pos, 0.0, 1.0);
+}
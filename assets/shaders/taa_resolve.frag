@@ -0,0 +1,48 @@
+#version 450
+
+layout(location = 0) in vec2 in_pos;
+
+layout(set = 0, binding = 0) uniform sampler2D current_color;
+layout(set = 1, binding = 0) uniform sampler2D history_color;
+// Motion of every pixel since the previous frame in pixels
+layout(set = 2, binding = 0) uniform sampler2D velocity;
+
+layout(location = 0) out vec4 out_color;
+
+// Weight of the current frame, the rest comes from the history
+const float CURRENT_WEIGHT = 0.1;
+
+void main() {
+    ivec2 pixel = ivec2(gl_FragCoord.xy);
+    ivec2 max_pixel = textureSize(current_color, 0) - 1;
+
+    vec4 current = texelFetch(current_color, pixel, 0);
+
+    // The history is clamped to the colors around the pixel, so disoccluded and
+    // changed pixels don't leave ghosts
+    vec4 neighborhood_min = current;
+    vec4 neighborhood_max = current;
+
+    for (int y = -1; y <= 1; y++) {
+        for (int x = -1; x <= 1; x++) {
+            ivec2 neighbor_pixel = clamp(pixel + ivec2(x, y), ivec2(0), max_pixel);
+            vec4 neighbor = texelFetch(current_color, neighbor_pixel, 0);
+
+            neighborhood_min = min(neighborhood_min, neighbor);
+            neighborhood_max = max(neighborhood_max, neighbor);
+        }
+    }
+
+    vec2 previous_position = gl_FragCoord.xy - texelFetch(velocity, pixel, 0).xy;
+    vec2 history_uv = previous_position / vec2(textureSize(history_color, 0));
+
+    // Pixels coming from outside of the previous frame have no history
+    if (any(lessThan(history_uv, vec2(0.0))) || any(greaterThan(history_uv, vec2(1.0)))) {
+        out_color = current;
+        return;
+    }
+
+    vec4 history = clamp(texture(history_color, history_uv), neighborhood_min, neighborhood_max);
+
+    out_color = mix(history, current, CURRENT_WEIGHT);
+}
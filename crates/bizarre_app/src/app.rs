@@ -1,24 +1,35 @@
 use std::{
+    panic::{self, AssertUnwindSafe},
     sync::mpsc::{self, Receiver, TryRecvError},
     time::{Duration, Instant},
 };
 
 use anyhow::Result;
 use bizarre_ecs::{
-    system::{schedule::Schedule, system_param::ResMut},
+    system::{schedule::Schedule, system_graph::ScheduleStats},
     world::World,
 };
-use bizarre_event::{EventQueue, EventReader};
-use bizarre_log::{core_info, info, shutdown_logging};
-
-use crate::app_event::AppEvent;
+use bizarre_event::{EventQueue, ReaderId};
+use bizarre_log::{core_error, core_info, core_warn, shutdown_logging};
+
+use crate::{
+    app_event::AppEvent,
+    close_request::CloseRequest,
+    diagnostics::{FrameDiagnostics, Hitch},
+    loop_policy::LoopControl,
+};
 
 pub struct App {
     pub(crate) name: String,
     pub(crate) running: bool,
     pub(crate) paused: bool,
     pub(crate) world: World,
-    pub(crate) event_reader: EventReader,
+    pub(crate) event_reader: ReaderId,
+    /// Applies pending `NextState` of every registered state
+    pub(crate) state_transitions: Vec<fn(&mut World)>,
+    pub(crate) frame_index: u64,
+    /// Shortest time between frame starts
+    pub(crate) frame_time: Duration,
 
     #[cfg(target_os = "linux")]
     pub(crate) termination_receiver: Receiver<i32>,
@@ -30,23 +41,27 @@ impl App {
 
         self.running = true;
 
-        const FRAME_TARGET_TIME: Duration = Duration::from_millis(1000 / 60);
-
         while self.running {
-            let frame_start = Instant::now();
+            if self.frame_index > 0 {
+                self.world.resource_mut::<LoopControl>().unwrap().wait();
+            }
 
-            self.world.init_schedule(Schedule::Preupdate);
-            self.world.run_schedule(Schedule::Preupdate);
+            let frame_start = Instant::now();
 
-            self.process_app_events();
-            self.world.init_schedule(Schedule::Update);
-            self.world.run_schedule(Schedule::Update);
+            // The state of the world goes into the log for bug reports
+            if let Err(payload) = panic::catch_unwind(AssertUnwindSafe(|| self.run_frame())) {
+                core_error!("App panicked, world state:\n{}", self.world.debug_dump());
+                shutdown_logging();
+                panic::resume_unwind(payload);
+            }
 
             let frame_end = Instant::now();
             let frame_duration = frame_end - frame_start;
 
-            if frame_duration <= FRAME_TARGET_TIME {
-                std::thread::sleep(FRAME_TARGET_TIME - frame_duration);
+            self.watch_frame(frame_duration);
+
+            if frame_duration <= self.frame_time {
+                std::thread::sleep(self.frame_time - frame_duration);
             }
         }
 
@@ -56,14 +71,58 @@ impl App {
         Ok(())
     }
 
+    /// Runs a single frame right away, without waiting for events or sleeping
+    /// out the frame time. Lets tests drive an app frame by frame.
+    ///
+    /// Returns `false` once the app was closed
+    pub fn update(&mut self) -> bool {
+        let frame_start = Instant::now();
+
+        self.run_frame();
+        self.watch_frame(frame_start.elapsed());
+
+        self.running
+    }
+
+    pub fn world(&self) -> &World {
+        &self.world
+    }
+
+    pub fn world_mut(&mut self) -> &mut World {
+        &mut self.world
+    }
+
+    /// Index of the next frame to run
+    pub fn frame_index(&self) -> u64 {
+        self.frame_index
+    }
+
+    fn run_frame(&mut self) {
+        self.world.init_schedule(Schedule::Preupdate);
+        self.world.run_schedule(Schedule::Preupdate);
+
+        self.process_app_events();
+        self.world.init_schedule(Schedule::Update);
+        self.world.run_schedule(Schedule::Update);
+
+        self.apply_state_transitions();
+        self.resolve_close_request();
+
+        #[cfg(feature = "snapshots")]
+        if let Err(err) = self.world.record_snapshot(self.frame_index) {
+            core_error!("Failed to record a world snapshot: {err}");
+        }
+    }
+
     fn process_app_events(&mut self) {
+        let mut close_requested = false;
+
         let event_queue = self.world.resource_mut::<EventQueue>().unwrap();
 
         while let Some(ev) = event_queue.poll_event::<AppEvent>(&self.event_reader) {
             if let AppEvent::CloseRequested = ev {
                 core_info!("Got AppEvent::CloseRequested!");
-                self.running = false;
-                event_queue.push_event(AppEvent::WillClose);
+                close_requested = true;
             }
         }
 
@@ -77,5 +136,91 @@ impl App {
                 _ => {}
             }
         }
+
+        if close_requested {
+            self.world.resource_mut::<CloseRequest>().unwrap().request();
+        }
+    }
+
+    /// Records and logs the frame if it took longer than the hitch threshold
+    /// of [`FrameDiagnostics`]
+    fn watch_frame(&mut self, frame_duration: Duration) {
+        let frame = self.frame_index;
+        self.frame_index += 1;
+
+        let is_hitch = self
+            .world
+            .resource::<FrameDiagnostics>()
+            .is_some_and(|diagnostics| diagnostics.is_hitch(frame_duration));
+
+        if !is_hitch {
+            return;
+        }
+
+        let slowest_systems = [Schedule::Preupdate, Schedule::Update]
+            .into_iter()
+            .filter_map(|schedule| self.world.schedule_stats(schedule))
+            .fold(ScheduleStats::default(), |mut acc, stats| {
+                acc.total += stats.total;
+                acc.systems.extend_from_slice(&stats.systems);
+                acc
+            })
+            .slowest(FrameDiagnostics::SLOWEST_SYSTEMS);
+
+        let events = self
+            .world
+            .resource::<EventQueue>()
+            .map(EventQueue::event_counts)
+            .unwrap_or_default();
+
+        let systems_report = slowest_systems
+            .iter()
+            .map(|timing| format!("{} ({:?})", timing.name, timing.duration))
+            .collect::<Vec<_>>()
+            .join(", ");
+
+        let events_report = events
+            .iter()
+            .map(|(name, count)| format!("{name} x{count}"))
+            .collect::<Vec<_>>()
+            .join(", ");
+
+        core_warn!(
+            "Frame {frame} took {frame_duration:?}. Slowest systems: [{systems_report}]. Events: [{events_report}]"
+        );
+
+        self.world
+            .resource_mut::<FrameDiagnostics>()
+            .unwrap()
+            .record(Hitch {
+                frame,
+                duration: frame_duration,
+                slowest_systems,
+                events,
+            });
+    }
+
+    fn apply_state_transitions(&mut self) {
+        self.world.flush();
+
+        self.state_transitions
+            .iter()
+            .for_each(|transition| transition(&mut self.world));
+    }
+
+    /// Stops the app if the pending close request was not prevented or the close got confirmed
+    fn resolve_close_request(&mut self) {
+        // Apply `ConfirmClose` commands issued during this frame
+        self.world.flush();
+
+        let should_close = self.world.resource_mut::<CloseRequest>().unwrap().resolve();
+
+        if should_close {
+            self.running = false;
+            self.world
+                .resource_mut::<EventQueue>()
+                .unwrap()
+                .push_event(AppEvent::WillClose);
+        }
     }
 }
@@ -1,29 +1,59 @@
-use std::{marker::PhantomData, mem::MaybeUninit};
+use std::{marker::PhantomData, mem::MaybeUninit, time::Duration};
 
+use bizarre_config::{register_config_section, validate_config, ConfigSection};
 use bizarre_core::builder::BuilderTypeState;
 use bizarre_ecs::{
-    system::{schedule::Schedule, system_param::ResMut},
+    system::{
+        schedule::{Schedule, ScheduleControl},
+        system_param::ResMut,
+    },
     world::{ecs_module::EcsModule, World},
 };
 use bizarre_event::EventQueue;
-use bizarre_log::init_logging;
+use bizarre_log::{core_error, init_logging};
 
 use crate::{
-    app_event::AppEvent, default_app_module::DefaultAppEcsModule,
-    ecs_module_buffer::EcsModuleBuffer, App,
+    app_event::AppEvent,
+    close_request::CloseRequest,
+    default_app_module::DefaultAppEcsModule,
+    diagnostics::FrameDiagnostics,
+    ecs_module_buffer::EcsModuleBuffer,
+    headless::Headless,
+    loop_policy::{LoopControl, LoopPolicy},
+    state::{apply_state_transition, enter_current_state, insert_state, AppState},
+    App,
 };
 
 pub struct AppBuilder<NameValidation: BuilderTypeState> {
     name: Option<String>,
     modules: EcsModuleBuffer,
+    states: Vec<StateRegistration>,
+    loop_policy: LoopPolicy,
+    frame_time: Duration,
+    headless: bool,
+    deterministic: bool,
     _phantom: PhantomData<NameValidation>,
 }
 
+/// Frames shorter than this are padded with sleep, 60 frames per second
+const DEFAULT_FRAME_TIME: Duration = Duration::from_nanos(1_000_000_000 / 60);
+
+struct StateRegistration {
+    insert: Box<dyn FnOnce(&mut World)>,
+    enter: fn(&mut World),
+    transition: fn(&mut World),
+}
+
 impl<T: BuilderTypeState> AppBuilder<T> {
     pub fn new_empty() -> AppBuilder<NoName> {
         AppBuilder::<NoName> {
             name: None,
             modules: EcsModuleBuffer::default(),
+            states: Vec::new(),
+            loop_policy: LoopPolicy::default(),
+            frame_time: DEFAULT_FRAME_TIME,
+            headless: false,
+            deterministic: false,
             _phantom: PhantomData,
         }
     }
@@ -40,6 +70,59 @@ impl<T: BuilderTypeState> AppBuilder<T> {
         self.modules.add_module(module);
         self
     }
+
+    /// Sets whether the main loop runs frames back to back or waits for events,
+    /// can be changed later through the [`LoopControl`] resource
+    pub fn with_loop_policy(mut self, loop_policy: LoopPolicy) -> Self {
+        self.loop_policy = loop_policy;
+        self
+    }
+
+    /// Sets the shortest time between frame starts, the main loop sleeps out the
+    /// rest of shorter frames. 60 frames per second by default
+    pub fn with_frame_time(mut self, frame_time: Duration) -> Self {
+        self.frame_time = frame_time;
+        self
+    }
+
+    /// Builds the app without windows, input or a renderer, for dedicated servers
+    /// and tests of gameplay systems.
+    ///
+    /// The main loop is paced by the frame time alone and the [`Headless`] resource
+    /// is inserted before the modules are applied, so windowed modules can skip
+    /// themselves. Schedules, states and networking run as usual
+    pub fn headless(mut self) -> Self {
+        self.headless = true;
+        self.loop_policy = LoopPolicy::Poll;
+        self
+    }
+
+    /// Runs the world in the deterministic mode, see [`World::set_deterministic`].
+    /// Needed for lockstep networking and replay tests
+    pub fn with_deterministic(mut self, deterministic: bool) -> Self {
+        self.deterministic = deterministic;
+        self
+    }
+
+    /// Makes `build` validate the config section `C` before the modules are
+    /// applied, errors of all registered sections are logged together
+    pub fn with_config_section<C: ConfigSection>(self) -> Self {
+        register_config_section::<C>();
+        self
+    }
+
+    /// Registers the state machine `S` starting in `initial`.
+    ///
+    /// `OnEnter(initial)` runs once after [`Schedule::Init`], transitions requested
+    /// with [`NextState<S>`](crate::state::NextState) are applied after every `Update`
+    pub fn with_state<S: AppState>(mut self, initial: S) -> Self {
+        self.states.push(StateRegistration {
+            insert: Box::new(move |world| insert_state(world, initial)),
+            enter: enter_current_state::<S>,
+            transition: apply_state_transition::<S>,
+        });
+        self
+    }
 }
 
 impl AppBuilder<WithName> {
@@ -47,24 +130,43 @@ impl AppBuilder<WithName> {
     ///
     /// Builds an `App` and inserts all the provided [`EcsModules`][EcsModule] into the [`World`]
     /// belonging to the built `App`. Also, worth mentioning that call to `build` will initialize
-    /// [`Schedule::Init`], [`Schedule::Preupdate`] and [`Schedule::Update`] and run the `Schedule::Init` once
+    /// [`Schedule::Init`], [`Schedule::Preupdate`] and [`Schedule::Update`] and run the `Schedule::Init` once,
+    /// followed by `OnEnter` of the initial value of every registered state
     ///
     pub fn build(self) -> App {
         let AppBuilder {
-            name, mut modules, ..
+            name,
+            mut modules,
+            states,
+            loop_policy,
+            frame_time,
+            headless,
+            deterministic,
+            ..
         } = self;
 
         init_logging(None, None);
 
+        report_config_errors();
+
         let name = name.expect("Cannot build an app without a name");
 
         let mut world = World::new();
+        world.set_deterministic(deterministic);
 
         let mut event_queue = EventQueue::new();
         let event_reader = event_queue.create_reader();
         event_queue.register_reader::<AppEvent>(event_reader);
 
         world.insert_resource(event_queue);
+        world.insert_resource(CloseRequest::default());
+        world.insert_resource(ScheduleControl::default());
+        world.insert_resource(FrameDiagnostics::default());
+        world.insert_resource(LoopControl::new(loop_policy));
+
+        if headless {
+            world.insert_resource(Headless);
+        }
 
         world.add_schedule(Schedule::Init);
         world.add_schedule(Schedule::Update);
@@ -72,20 +174,39 @@ impl AppBuilder<WithName> {
 
         world.add_systems(Schedule::Preupdate, change_event_queue_frames);
 
+        let mut state_transitions = Vec::with_capacity(states.len());
+        let mut state_enters = Vec::with_capacity(states.len());
+
+        for StateRegistration {
+            insert,
+            enter,
+            transition,
+        } in states
+        {
+            insert(&mut world);
+            state_enters.push(enter);
+            state_transitions.push(transition);
+        }
+
         modules.apply(&mut world);
 
         world.init_schedule(Schedule::Init);
         world.run_schedule(Schedule::Init);
 
+        state_enters.into_iter().for_each(|enter| enter(&mut world));
+
         #[cfg(target_os = "linux")]
         let termination_receiver = setup_termination_handler();
 
         App {
             name,
-            running: false,
+            running: true,
             paused: false,
             world,
             event_reader,
+            state_transitions,
+            frame_index: 0,
+            frame_time,
 
             #[cfg(target_os = "linux")]
             termination_receiver,
@@ -102,11 +223,28 @@ impl Default for AppBuilder<NoName> {
         Self {
             name: Default::default(),
             modules,
+            states: Vec::new(),
+            loop_policy: LoopPolicy::default(),
+            frame_time: DEFAULT_FRAME_TIME,
+            headless: false,
+            deterministic: false,
             _phantom: PhantomData,
         }
     }
 }
 
+fn report_config_errors() {
+    let Err(errors) = validate_config() else {
+        return;
+    };
+
+    core_error!("Found {} config error(s):", errors.len());
+
+    for err in errors {
+        core_error!("{err}");
+    }
+}
+
 pub fn change_event_queue_frames(mut eq: ResMut<EventQueue>) {
     eq.change_frames();
 }
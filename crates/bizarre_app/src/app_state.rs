@@ -35,3 +35,178 @@ impl Display for AppRunTime {
         write!(f, "{:?}", self.0)
     }
 }
+
+/// Frame timing information updated by the app loop at the start of every frame
+#[derive(Resource, Debug, Clone)]
+pub struct Time {
+    delta: Duration,
+    unscaled_delta: Duration,
+    max_delta: Duration,
+    elapsed: Duration,
+    unscaled_elapsed: Duration,
+    frame_count: u64,
+    time_scale: f64,
+}
+
+impl Default for Time {
+    fn default() -> Self {
+        Self {
+            delta: Duration::ZERO,
+            unscaled_delta: Duration::ZERO,
+            max_delta: Time::DEFAULT_MAX_DELTA,
+            elapsed: Duration::ZERO,
+            unscaled_elapsed: Duration::ZERO,
+            frame_count: 0,
+            time_scale: 1.0,
+        }
+    }
+}
+
+impl Time {
+    /// Frames longer than this are clamped so a hitch (debugger break, window drag)
+    /// doesn't produce a huge simulation step
+    pub const DEFAULT_MAX_DELTA: Duration = Duration::from_millis(250);
+
+    /// Scaled and clamped time since the previous frame
+    pub fn delta(&self) -> Duration {
+        self.delta
+    }
+
+    pub fn delta_secs(&self) -> f32 {
+        self.delta.as_secs_f32()
+    }
+
+    /// Real time since the previous frame, not affected by clamping or `time_scale`
+    pub fn unscaled_delta(&self) -> Duration {
+        self.unscaled_delta
+    }
+
+    pub fn unscaled_delta_secs(&self) -> f32 {
+        self.unscaled_delta.as_secs_f32()
+    }
+
+    /// Sum of all scaled deltas since startup
+    pub fn elapsed(&self) -> Duration {
+        self.elapsed
+    }
+
+    pub fn elapsed_secs(&self) -> f64 {
+        self.elapsed.as_secs_f64()
+    }
+
+    /// Real time since startup
+    pub fn unscaled_elapsed(&self) -> Duration {
+        self.unscaled_elapsed
+    }
+
+    pub fn frame_count(&self) -> u64 {
+        self.frame_count
+    }
+
+    pub fn time_scale(&self) -> f64 {
+        self.time_scale
+    }
+
+    /// Sets the multiplier applied to the delta. `0.0` effectively pauses everything
+    /// that relies on the scaled delta.
+    ///
+    /// Negative and non-finite values are clamped to `0.0`
+    pub fn set_time_scale(&mut self, time_scale: f64) {
+        self.time_scale = if time_scale.is_finite() {
+            time_scale.max(0.0)
+        } else {
+            0.0
+        };
+    }
+
+    pub fn max_delta(&self) -> Duration {
+        self.max_delta
+    }
+
+    pub fn set_max_delta(&mut self, max_delta: Duration) {
+        self.max_delta = max_delta;
+    }
+
+    /// Starts a new frame which took `unscaled_delta` of real time
+    pub(crate) fn advance(&mut self, unscaled_delta: Duration) {
+        self.unscaled_delta = unscaled_delta;
+        self.unscaled_elapsed += unscaled_delta;
+        self.delta = unscaled_delta.min(self.max_delta).mul_f64(self.time_scale);
+        self.elapsed += self.delta;
+        self.frame_count += 1;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use super::Time;
+
+    const FRAME: Duration = Duration::from_millis(16);
+
+    #[test]
+    fn should_accumulate_deltas() {
+        let mut time = Time::default();
+        time.advance(FRAME);
+        time.advance(FRAME);
+
+        assert_eq!(time.delta(), FRAME);
+        assert_eq!(time.unscaled_delta(), FRAME);
+        assert_eq!(time.elapsed(), FRAME * 2);
+        assert_eq!(time.unscaled_elapsed(), FRAME * 2);
+        assert_eq!(time.frame_count(), 2);
+    }
+
+    #[test]
+    fn should_clamp_delta() {
+        let mut time = Time::default();
+        let hitch = Time::DEFAULT_MAX_DELTA * 4;
+        time.advance(hitch);
+
+        assert_eq!(time.delta(), Time::DEFAULT_MAX_DELTA);
+        assert_eq!(time.elapsed(), Time::DEFAULT_MAX_DELTA);
+        assert_eq!(time.unscaled_delta(), hitch);
+        assert_eq!(time.unscaled_elapsed(), hitch);
+    }
+
+    #[test]
+    fn should_scale_delta() {
+        let mut time = Time::default();
+        time.set_time_scale(0.5);
+        time.advance(FRAME);
+
+        assert_eq!(time.delta(), FRAME / 2);
+        assert_eq!(time.elapsed(), FRAME / 2);
+        assert_eq!(time.unscaled_delta(), FRAME);
+
+        // The clamp applies before the scale
+        time.set_time_scale(2.0);
+        time.advance(Time::DEFAULT_MAX_DELTA * 2);
+        assert_eq!(time.delta(), Time::DEFAULT_MAX_DELTA * 2);
+    }
+
+    #[test]
+    fn should_pause_with_zero_scale() {
+        let mut time = Time::default();
+        time.advance(FRAME);
+        time.set_time_scale(0.0);
+        time.advance(FRAME);
+
+        assert_eq!(time.delta(), Duration::ZERO);
+        assert_eq!(time.elapsed(), FRAME);
+        assert_eq!(time.unscaled_elapsed(), FRAME * 2);
+        assert_eq!(time.frame_count(), 2);
+    }
+
+    #[test]
+    fn should_clamp_invalid_time_scale() {
+        let mut time = Time::default();
+
+        time.set_time_scale(-1.0);
+        assert_eq!(time.time_scale(), 0.0);
+
+        time.set_time_scale(f64::NAN);
+        assert_eq!(time.time_scale(), 0.0);
+    }
+}
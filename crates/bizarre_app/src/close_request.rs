@@ -0,0 +1,119 @@
+use bizarre_ecs::{commands::Command, prelude::*, world::World};
+
+/// State of the current request to close the app.
+///
+/// When an [`AppEvent::CloseRequested`](crate::app_event::AppEvent::CloseRequested) is received
+/// the request becomes pending for one frame. Systems running in [`Schedule::Update`] may call
+/// [`prevent_default`](Self::prevent_default) to keep the app running (e.g. to show an
+/// "unsaved changes" dialog) and later close it with the [`ConfirmClose`] command.
+///
+/// [`Schedule::Update`]: bizarre_ecs::system::schedule::Schedule::Update
+#[derive(Resource, Default, Debug)]
+pub struct CloseRequest {
+    pending: bool,
+    prevented: bool,
+    confirmed: bool,
+}
+
+impl CloseRequest {
+    /// Returns `true` during the frame in which the app got asked to close
+    pub fn is_pending(&self) -> bool {
+        self.pending
+    }
+
+    /// Cancels the pending close request
+    pub fn prevent_default(&mut self) {
+        if self.pending {
+            self.prevented = true;
+        }
+    }
+
+    pub fn is_prevented(&self) -> bool {
+        self.prevented
+    }
+
+    /// Closes the app at the end of the current frame, even if the request was prevented
+    pub fn confirm(&mut self) {
+        self.confirmed = true;
+    }
+
+    pub(crate) fn request(&mut self) {
+        self.pending = true;
+    }
+
+    /// Resolves the request for the current frame, returns `true` if the app must close
+    pub(crate) fn resolve(&mut self) -> bool {
+        let should_close = self.confirmed || (self.pending && !self.prevented);
+
+        self.pending = false;
+        self.prevented = false;
+
+        should_close
+    }
+}
+
+/// Closes the app at the end of the frame regardless of [`CloseRequest::prevent_default`]
+pub struct ConfirmClose;
+
+impl Command for ConfirmClose {
+    fn apply(self, world: &mut World) {
+        world
+            .resource_mut::<CloseRequest>()
+            .expect("`CloseRequest` is not present in the world")
+            .confirm();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::CloseRequest;
+
+    #[test]
+    fn should_close_on_pending_request() {
+        let mut request = CloseRequest::default();
+        request.request();
+
+        assert!(request.is_pending());
+        assert!(request.resolve());
+    }
+
+    #[test]
+    fn should_stay_open_when_prevented() {
+        let mut request = CloseRequest::default();
+        request.request();
+        request.prevent_default();
+
+        assert!(!request.resolve());
+        assert!(!request.resolve());
+    }
+
+    #[test]
+    fn should_close_when_confirmed_after_prevent() {
+        let mut request = CloseRequest::default();
+        request.request();
+        request.prevent_default();
+        assert!(!request.resolve());
+
+        request.confirm();
+        assert!(request.resolve());
+    }
+
+    #[test]
+    fn should_reset_prevented_on_next_frame() {
+        let mut request = CloseRequest::default();
+        request.request();
+        request.prevent_default();
+        assert!(request.is_prevented());
+        assert!(!request.resolve());
+
+        assert!(!request.is_pending());
+        assert!(!request.is_prevented());
+
+        // Preventing without a pending request does nothing
+        request.prevent_default();
+        assert!(!request.is_prevented());
+
+        request.request();
+        assert!(request.resolve());
+    }
+}
@@ -6,7 +6,7 @@ use bizarre_ecs::prelude::*;
 use bizarre_ecs::system::schedule::Schedule;
 use bizarre_ecs::world::ecs_module::EcsModule;
 
-use crate::app_state::{AppRunTime, DeltaTime};
+use crate::app_state::{AppRunTime, DeltaTime, Time};
 
 pub struct DefaultAppEcsModule;
 
@@ -14,6 +14,7 @@ impl EcsModule for DefaultAppEcsModule {
     fn apply(self, world: &mut bizarre_ecs::world::World) {
         world.insert_resource(DeltaTime(Duration::default()));
         world.insert_resource(AppRunTime(Duration::default()));
+        world.insert_resource(Time::default());
 
         world.add_systems(Schedule::Preupdate, update_timers);
     }
@@ -24,8 +25,11 @@ fn update_timers(
     app_start: Local<Instant>,
     mut delta: ResMut<DeltaTime>,
     mut run_time: ResMut<AppRunTime>,
+    mut time: ResMut<Time>,
 ) {
     delta.0 = last_frame.elapsed();
     *last_frame = Instant::now();
     run_time.0 = app_start.elapsed();
+
+    time.advance(delta.0);
 }
@@ -0,0 +1,101 @@
+use std::{collections::VecDeque, time::Duration};
+
+use bizarre_ecs::{prelude::*, system::system_graph::SystemTiming};
+
+/// Records frames that took longer than the hitch threshold.
+///
+/// The app loop checks every frame and keeps the last [`capacity`](Self::capacity)
+/// hitches, older ones are dropped
+#[derive(Resource, Debug)]
+pub struct FrameDiagnostics {
+    threshold: Duration,
+    capacity: usize,
+    hitches: VecDeque<Hitch>,
+    total_hitches: u64,
+}
+
+/// A single frame that exceeded the hitch threshold
+#[derive(Clone, Debug)]
+pub struct Hitch {
+    /// Index of the frame since the app started
+    pub frame: u64,
+    pub duration: Duration,
+    /// Slowest systems of `Preupdate` and `Update` during the frame, slowest first
+    pub slowest_systems: Vec<SystemTiming>,
+    /// Type name and count of the events that were in flight during the frame
+    pub events: Vec<(&'static str, usize)>,
+}
+
+impl Default for FrameDiagnostics {
+    fn default() -> Self {
+        Self::new(Self::DEFAULT_THRESHOLD, Self::DEFAULT_CAPACITY)
+    }
+}
+
+impl FrameDiagnostics {
+    pub const DEFAULT_THRESHOLD: Duration = Duration::from_millis(50);
+    pub const DEFAULT_CAPACITY: usize = 32;
+    /// Amount of systems recorded for every hitch
+    pub const SLOWEST_SYSTEMS: usize = 5;
+
+    pub fn new(threshold: Duration, capacity: usize) -> Self {
+        Self {
+            threshold,
+            capacity,
+            hitches: VecDeque::with_capacity(capacity),
+            total_hitches: 0,
+        }
+    }
+
+    pub fn threshold(&self) -> Duration {
+        self.threshold
+    }
+
+    /// Frames longer than `threshold` are recorded and logged
+    pub fn set_threshold(&mut self, threshold: Duration) {
+        self.threshold = threshold;
+    }
+
+    pub fn capacity(&self) -> usize {
+        self.capacity
+    }
+
+    pub fn set_capacity(&mut self, capacity: usize) {
+        self.capacity = capacity;
+        self.truncate();
+    }
+
+    /// Recorded hitches, oldest first
+    pub fn hitches(&self) -> impl Iterator<Item = &Hitch> {
+        self.hitches.iter()
+    }
+
+    pub fn last_hitch(&self) -> Option<&Hitch> {
+        self.hitches.back()
+    }
+
+    /// Amount of hitches since startup, including the ones no longer in the buffer
+    pub fn total_hitches(&self) -> u64 {
+        self.total_hitches
+    }
+
+    pub fn clear(&mut self) {
+        self.hitches.clear();
+    }
+
+    pub(crate) fn is_hitch(&self, frame_duration: Duration) -> bool {
+        frame_duration > self.threshold
+    }
+
+    pub(crate) fn record(&mut self, hitch: Hitch) {
+        self.hitches.push_back(hitch);
+        self.total_hitches += 1;
+        self.truncate();
+    }
+
+    fn truncate(&mut self) {
+        while self.hitches.len() > self.capacity {
+            self.hitches.pop_front();
+        }
+    }
+}
@@ -0,0 +1,8 @@
+use bizarre_ecs::prelude::*;
+
+/// Present in apps built with [`AppBuilder::headless`](crate::AppBuilder::headless).
+///
+/// There are no windows, input or renderer in a headless app, modules owning
+/// them should check for this resource and skip their setup
+#[derive(Resource, Default, Debug)]
+pub struct Headless;
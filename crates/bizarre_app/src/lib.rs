@@ -7,6 +7,11 @@ mod ecs_module_buffer;
 pub mod app_builder;
 pub mod app_event;
 pub mod app_state;
+pub mod close_request;
+pub mod diagnostics;
+pub mod headless;
+pub mod loop_policy;
+pub mod state;
 
 pub use app::App;
 pub use app_builder::AppBuilder;
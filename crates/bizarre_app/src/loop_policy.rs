@@ -0,0 +1,90 @@
+use std::time::Duration;
+
+use bizarre_ecs::{commands::Command, prelude::*, world::World};
+
+/// How the main loop behaves when there is nothing new to process
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum LoopPolicy {
+    /// Runs frames back to back, for games and anything animating
+    #[default]
+    Poll,
+    /// Blocks until a window or input event arrives or a redraw is requested,
+    /// for editor-like tools
+    WaitEvents,
+    /// Like [`LoopPolicy::WaitEvents`], but runs a frame at least every `timeout`
+    WaitWithTimeout(Duration),
+}
+
+/// Blocks until the platform has events pending or `timeout` passes, waits
+/// indefinitely when `timeout` is `None`. Must not remove the events it waits for
+pub type EventWait = fn(timeout: Option<Duration>);
+
+/// Main loop policy of the running app and the pending redraw request
+///
+/// The wait policies need an [`EventWait`] installed by the module owning the
+/// platform event queue, the loop doesn't block without one
+#[derive(Resource, Default, Debug)]
+pub struct LoopControl {
+    policy: LoopPolicy,
+    redraw_requested: bool,
+    event_wait: Option<EventWait>,
+}
+
+impl LoopControl {
+    pub fn new(policy: LoopPolicy) -> Self {
+        Self {
+            policy,
+            ..Default::default()
+        }
+    }
+
+    pub fn policy(&self) -> LoopPolicy {
+        self.policy
+    }
+
+    pub fn set_policy(&mut self, policy: LoopPolicy) {
+        self.policy = policy;
+    }
+
+    /// Runs the next frame without waiting for events
+    pub fn request_redraw(&mut self) {
+        self.redraw_requested = true;
+    }
+
+    pub fn is_redraw_requested(&self) -> bool {
+        self.redraw_requested
+    }
+
+    pub fn set_event_wait(&mut self, event_wait: EventWait) {
+        self.event_wait = Some(event_wait);
+    }
+
+    /// Blocks according to the policy, consumes the redraw request
+    pub(crate) fn wait(&mut self) {
+        if std::mem::take(&mut self.redraw_requested) {
+            return;
+        }
+
+        let timeout = match self.policy {
+            LoopPolicy::Poll => return,
+            LoopPolicy::WaitEvents => None,
+            LoopPolicy::WaitWithTimeout(timeout) => Some(timeout),
+        };
+
+        if let Some(event_wait) = self.event_wait {
+            event_wait(timeout);
+        }
+    }
+}
+
+/// Makes the main loop run the next frame even if it waits for events
+pub struct RequestRedraw;
+
+impl Command for RequestRedraw {
+    fn apply(self, world: &mut World) {
+        world
+            .resource_mut::<LoopControl>()
+            .expect("`LoopControl` is not present in the world")
+            .request_redraw();
+    }
+}
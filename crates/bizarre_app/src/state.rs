@@ -0,0 +1,113 @@
+use std::{fmt::Debug, hash::Hash};
+
+use bizarre_ecs::{
+    prelude::*,
+    system::schedule::{Schedule, StateLabel},
+    world::World,
+};
+use bizarre_log::core_info;
+
+/// A state of the app state machine, e.g. main menu, loading and gameplay.
+///
+/// Register it with [`AppBuilder::with_state`](crate::AppBuilder::with_state), the
+/// current value is available as [`State<S>`] and transitions are requested through
+/// [`NextState<S>`]
+pub trait AppState: Copy + Eq + Hash + Debug + Send + Sync + 'static {}
+
+/// Current value of the state `S`
+#[derive(Resource, Debug)]
+pub struct State<S: AppState>(S);
+
+impl<S: AppState> State<S> {
+    pub fn get(&self) -> S {
+        self.0
+    }
+}
+
+/// Requested transition of the state `S`, applied at the end of the frame.
+///
+/// [`OnExit`] of the current state runs first, then [`OnEnter`] of the new one.
+/// Setting the state that is already current does nothing
+#[derive(Resource, Debug)]
+pub struct NextState<S: AppState>(Option<S>);
+
+impl<S: AppState> NextState<S> {
+    pub fn set(&mut self, state: S) {
+        self.0 = Some(state);
+    }
+
+    pub fn pending(&self) -> Option<S> {
+        self.0
+    }
+}
+
+/// Schedule that runs when the app enters the state, use it as
+/// `world.add_systems(OnEnter(GameState::Menu), spawn_menu)`
+#[derive(Clone, Copy, Debug)]
+pub struct OnEnter<S: AppState>(pub S);
+
+/// Schedule that runs when the app leaves the state
+#[derive(Clone, Copy, Debug)]
+pub struct OnExit<S: AppState>(pub S);
+
+impl<S: AppState> From<OnEnter<S>> for Schedule {
+    fn from(value: OnEnter<S>) -> Self {
+        Schedule::OnEnter(StateLabel::new(&value.0))
+    }
+}
+
+impl<S: AppState> From<OnExit<S>> for Schedule {
+    fn from(value: OnExit<S>) -> Self {
+        Schedule::OnExit(StateLabel::new(&value.0))
+    }
+}
+
+pub(crate) fn insert_state<S: AppState>(world: &mut World, initial: S) {
+    world.insert_resource(State(initial));
+    world.insert_resource(NextState::<S>(None));
+}
+
+/// Runs [`OnEnter`] of the current state, used once the app is built
+pub(crate) fn enter_current_state<S: AppState>(world: &mut World) {
+    let current = world
+        .resource::<State<S>>()
+        .expect("State is not registered")
+        .get();
+
+    run_state_schedule(world, OnEnter(current).into());
+}
+
+pub(crate) fn apply_state_transition<S: AppState>(world: &mut World) {
+    let Some(next) = world
+        .resource_mut::<NextState<S>>()
+        .and_then(|next| next.0.take())
+    else {
+        return;
+    };
+
+    let current = world
+        .resource::<State<S>>()
+        .expect("State is not registered")
+        .get();
+
+    if current == next {
+        return;
+    }
+
+    core_info!("State transition: {current:?} -> {next:?}");
+
+    run_state_schedule(world, OnExit(current).into());
+
+    world.resource_mut::<State<S>>().unwrap().0 = next;
+
+    run_state_schedule(world, OnEnter(next).into());
+}
+
+fn run_state_schedule(world: &mut World, schedule: Schedule) {
+    if !world.has_schedule(schedule) {
+        return;
+    }
+
+    world.init_schedule(schedule);
+    world.run_schedule(schedule);
+}
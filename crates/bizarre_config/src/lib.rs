@@ -1,39 +1,323 @@
-use std::{env, sync::LazyLock};
+use std::{
+    env, fmt,
+    marker::PhantomData,
+    sync::{LazyLock, Mutex},
+};
 
-use serde::Deserialize;
+use serde::{
+    de::{DeserializeSeed, IgnoredAny, MapAccess, Visitor},
+    Deserialize, Deserializer,
+};
 use thiserror::Error;
-use toml::Table;
+use toml::{Table, Value};
 
-static CONFIG: LazyLock<Table> = LazyLock::new(init_config);
+pub use toml;
 
-fn init_config() -> Table {
-    let config_path = env::var("BE_CONFIG_PATH").unwrap_or(String::from("be_config.toml"));
-    std::fs::read_to_string(config_path)
-        .unwrap()
-        .parse()
-        .unwrap()
+static CONFIG: LazyLock<LoadedConfig> = LazyLock::new(init_config);
+
+/// Sections checked by [`validate_config`]
+static SECTIONS: Mutex<Vec<RegisteredSection>> = Mutex::new(Vec::new());
+
+/// Values set with [`set_config_value`], merged over the config file
+static OVERRIDES: LazyLock<Mutex<Table>> = LazyLock::new(Default::default);
+
+struct LoadedConfig {
+    path: String,
+    source: String,
+    table: Result<Table, toml::de::Error>,
+}
+
+struct RegisteredSection {
+    name: &'static str,
+    validate: fn() -> ConfigResult<()>,
+}
+
+/// A missing config file is an empty config, every section falls back to its defaults
+fn init_config() -> LoadedConfig {
+    let path = env::var("BE_CONFIG_PATH").unwrap_or(String::from("be_config.toml"));
+    let source = std::fs::read_to_string(&path).unwrap_or_default();
+    let table = source.parse();
+
+    LoadedConfig {
+        path,
+        source,
+        table,
+    }
 }
 
 pub trait ConfigSection: for<'a> Deserialize<'a> + Default {
     fn section_name() -> &'static str;
+
+    /// Required sections are reported as [`ConfigError::MissingSection`] instead
+    /// of falling back to the defaults
+    fn required() -> bool {
+        false
+    }
 }
 
 #[derive(Debug, Clone, Error)]
 pub enum ConfigError {
-    #[error("Failed to parse config: {0}")]
-    FailedToParse(#[from] toml::de::Error),
+    #[error("Failed to parse config `{path}`: {source}")]
+    FailedToParse {
+        path: String,
+        source: Box<toml::de::Error>,
+    },
+    #[error("Required section `[{section}]` is missing in config `{path}`")]
+    MissingSection { path: String, section: &'static str },
+    #[error("Invalid section `[{section}]` in config `{path}`: {source}")]
+    InvalidSection {
+        path: String,
+        section: &'static str,
+        source: Box<toml::de::Error>,
+    },
+    #[error("Invalid config key `{0}`")]
+    InvalidKey(String),
 }
 
-type ConfigResult<T> = Result<T, ConfigError>;
+pub type ConfigResult<T> = Result<T, ConfigError>;
 
+/// The whole config as a table, empty if the config file failed to parse
 pub fn get_config() -> Table {
-    CONFIG.clone()
+    let mut config = Value::Table(CONFIG.table.clone().unwrap_or_default());
+    merge_value(&mut config, Value::Table(OVERRIDES.lock().unwrap().clone()));
+
+    match config {
+        Value::Table(config) => config,
+        _ => unreachable!(),
+    }
+}
+
+/// Value at a dot separated `key` like `renderer.vsync`, overrides included
+pub fn get_config_value(key: &str) -> Option<Value> {
+    let path = key_path(key).ok()?;
+    let config = get_config();
+
+    let mut value = config.get(path[0])?;
+    for name in &path[1..] {
+        value = value.as_table()?.get(*name)?;
+    }
+
+    Some(value.clone())
+}
+
+/// Overrides the value at a dot separated `key` until the process exits, sections
+/// read afterwards see the new value.
+///
+/// If the section of the key is registered with [`register_config_section`] and
+/// fails to deserialize with the new value, the override is reverted
+pub fn set_config_value(key: &str, value: Value) -> ConfigResult<()> {
+    let path = key_path(key)?;
+    let (last, tables) = path.split_last().unwrap();
+
+    let previous = {
+        let mut overrides = OVERRIDES.lock().unwrap();
+        let previous = overrides.clone();
+
+        let mut table = &mut *overrides;
+        for name in tables {
+            let entry = table
+                .entry(name.to_string())
+                .or_insert_with(|| Value::Table(Table::new()));
+            if !entry.is_table() {
+                *entry = Value::Table(Table::new());
+            }
+            table = entry.as_table_mut().unwrap();
+        }
+        table.insert(last.to_string(), value);
+
+        previous
+    };
+
+    let validate = SECTIONS
+        .lock()
+        .unwrap()
+        .iter()
+        .find(|section| section.name == path[0])
+        .map(|section| section.validate);
+
+    if let Some(Err(err)) = validate.map(|validate| validate()) {
+        *OVERRIDES.lock().unwrap() = previous;
+        return Err(err);
+    }
+
+    Ok(())
+}
+
+/// Drops every value set with [`set_config_value`]
+pub fn clear_config_overrides() {
+    OVERRIDES.lock().unwrap().clear();
+}
+
+fn key_path(key: &str) -> ConfigResult<Vec<&str>> {
+    let path = key.split('.').collect::<Vec<_>>();
+
+    if path.iter().any(|name| name.is_empty()) {
+        Err(ConfigError::InvalidKey(key.to_string()))
+    } else {
+        Ok(path)
+    }
+}
+
+/// Tables are merged key by key, any other value is replaced
+fn merge_value(base: &mut Value, value: Value) {
+    match (base, value) {
+        (Value::Table(base), Value::Table(value)) => {
+            for (key, value) in value {
+                match base.get_mut(&key) {
+                    Some(base) => merge_value(base, value),
+                    None => {
+                        base.insert(key, value);
+                    }
+                }
+            }
+        }
+        (base, value) => *base = value,
+    }
 }
 
+/// Reads the section `C`, a missing optional section falls back to its defaults
 pub fn get_config_section<C: ConfigSection>() -> ConfigResult<C> {
-    if let Some(value) = CONFIG.get(C::section_name()) {
-        value.clone().try_into().map_err(|err| err.into())
+    try_get_config_section().map(Option::unwrap_or_default)
+}
+
+/// Reads the section `C`, returns `None` if it's missing and not required.
+///
+/// The section is deserialized from the config source, so type mismatches and
+/// missing keys are reported with the line and column they are at
+pub fn try_get_config_section<C: ConfigSection>() -> ConfigResult<Option<C>> {
+    let LoadedConfig {
+        path,
+        source,
+        table,
+    } = &*CONFIG;
+
+    let table = match table {
+        Ok(table) => table,
+        Err(source) => {
+            return Err(ConfigError::FailedToParse {
+                path: path.clone(),
+                source: Box::new(source.clone()),
+            })
+        }
+    };
+
+    let overrides = OVERRIDES.lock().unwrap().get(C::section_name()).cloned();
+
+    let section = match overrides {
+        // The merged section has no source, so errors lose their locations
+        Some(overrides) => {
+            let mut section = table
+                .get(C::section_name())
+                .cloned()
+                .unwrap_or(Value::Table(Table::new()));
+            merge_value(&mut section, overrides);
+            section.try_into().map(Some)
+        }
+        None => {
+            SectionSeed::<C>::new(C::section_name()).deserialize(toml::Deserializer::new(source))
+        }
+    }
+    .map_err(|source| ConfigError::InvalidSection {
+        path: path.clone(),
+        section: C::section_name(),
+        source: Box::new(source),
+    })?;
+
+    match section {
+        None if C::required() => Err(ConfigError::MissingSection {
+            path: path.clone(),
+            section: C::section_name(),
+        }),
+        section => Ok(section),
+    }
+}
+
+/// Makes [`validate_config`] check the section `C`
+pub fn register_config_section<C: ConfigSection>() {
+    let mut sections = SECTIONS.lock().unwrap();
+
+    if sections
+        .iter()
+        .any(|section| section.name == C::section_name())
+    {
+        return;
+    }
+
+    sections.push(RegisteredSection {
+        name: C::section_name(),
+        validate: || try_get_config_section::<C>().map(|_| ()),
+    });
+}
+
+/// Checks every section registered with [`register_config_section`] and
+/// returns all the errors found instead of stopping at the first one
+pub fn validate_config() -> Result<(), Vec<ConfigError>> {
+    if let Err(source) = &CONFIG.table {
+        return Err(vec![ConfigError::FailedToParse {
+            path: CONFIG.path.clone(),
+            source: Box::new(source.clone()),
+        }]);
+    }
+
+    let errors = SECTIONS
+        .lock()
+        .unwrap()
+        .iter()
+        .filter_map(|section| (section.validate)().err())
+        .collect::<Vec<_>>();
+
+    if errors.is_empty() {
+        Ok(())
     } else {
-        Ok(Default::default())
+        Err(errors)
+    }
+}
+
+/// Deserializes a single top level table of the config and skips the others
+struct SectionSeed<C> {
+    name: &'static str,
+    _phantom: PhantomData<C>,
+}
+
+impl<C> SectionSeed<C> {
+    fn new(name: &'static str) -> Self {
+        Self {
+            name,
+            _phantom: PhantomData,
+        }
+    }
+}
+
+impl<'de, C: Deserialize<'de>> DeserializeSeed<'de> for SectionSeed<C> {
+    type Value = Option<C>;
+
+    fn deserialize<D: Deserializer<'de>>(self, deserializer: D) -> Result<Self::Value, D::Error> {
+        deserializer.deserialize_map(self)
+    }
+}
+
+impl<'de, C: Deserialize<'de>> Visitor<'de> for SectionSeed<C> {
+    type Value = Option<C>;
+
+    fn expecting(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            formatter,
+            "a config with an optional `[{}]` table",
+            self.name
+        )
+    }
+
+    fn visit_map<A: MapAccess<'de>>(self, mut map: A) -> Result<Self::Value, A::Error> {
+        let mut section = None;
+
+        while let Some(key) = map.next_key::<String>()? {
+            if key == self.name {
+                section = Some(map.next_value()?);
+            } else {
+                map.next_value::<IgnoredAny>()?;
+            }
+        }
+
+        Ok(section)
     }
 }
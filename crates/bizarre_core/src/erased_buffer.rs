@@ -197,6 +197,41 @@ impl ErasedSparseArray {
         prev_value
     }
 
+    /// Moves the element at `from` into the empty slot `to`
+    ///
+    /// # Safety
+    ///
+    /// `to` must not contain an element
+    pub unsafe fn move_element(&mut self, from: usize, to: usize) {
+        if from == to || !self.contains(from) {
+            return;
+        }
+
+        debug_assert!(
+            !self.contains(to),
+            "Moving an element into an occupied slot"
+        );
+
+        let src = self.data.add(self.element_stride * from);
+        let dst = self.data.add(self.element_stride * to);
+        std::ptr::copy_nonoverlapping(src, dst, self.element_layout.size());
+
+        self.valid_elements.set(from, false);
+        self.valid_elements.set(to, true);
+    }
+
+    /// Drops the element at `index` in place, if there is one
+    pub fn drop_element(&mut self, index: usize) {
+        if !self.contains(index) {
+            return;
+        }
+
+        let offset = self.element_stride * index;
+        unsafe { (self.drop_fn)(self.data.add(offset).cast()) }
+
+        self.valid_elements.set(index, false);
+    }
+
     pub fn contains(&self, index: usize) -> bool {
         self.valid_elements.get(index).is_some_and(|val| val)
     }
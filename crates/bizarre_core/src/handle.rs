@@ -9,6 +9,8 @@ use std::{
     },
 };
 
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+
 pub struct Handle<T: ?Sized> {
     handle: usize,
     _marker: PhantomData<T>,
@@ -18,10 +20,21 @@ unsafe impl<T> Send for Handle<T> {}
 unsafe impl<T> Sync for Handle<T> {}
 
 impl<T> Handle<T> {
+    #[cfg(not(all(feature = "handle_audit", debug_assertions)))]
     pub const fn as_raw(&self) -> usize {
         self.handle
     }
 
+    /// Remembers the exported value for [`Handle::from_raw`], see the `handle_audit` feature
+    #[cfg(all(feature = "handle_audit", debug_assertions))]
+    pub fn as_raw(&self) -> usize {
+        audit::export::<T>(self.handle);
+        self.handle
+    }
+
+    /// Raw values should come from the storage of `T` itself, handles of related
+    /// types are converted with [`Handle::derived_from`]
+    #[cfg(not(all(feature = "handle_audit", debug_assertions)))]
     pub const fn from_raw<I: ~const IntoHandleRawValue>(raw: I) -> Self {
         Self {
             handle: raw.as_handle_raw_value(),
@@ -29,8 +42,66 @@ impl<T> Handle<T> {
         }
     }
 
+    /// Panics when `raw` was just exported by [`Handle::as_raw`] of another type
+    #[cfg(all(feature = "handle_audit", debug_assertions))]
+    pub fn from_raw<I: IntoHandleRawValue>(raw: I) -> Self {
+        let handle = raw.as_handle_raw_value();
+        audit::import::<T>(handle);
+
+        Self {
+            handle,
+            _marker: PhantomData,
+        }
+    }
+
+    /// Handle of the `T` identified by the handle of `S`, e.g. of a present
+    /// target by the handle of its window
+    pub const fn derived_from<S>(handle: &Handle<S>) -> Self
+    where
+        T: DerivedHandle<S>,
+    {
+        Self {
+            handle: handle.handle,
+            _marker: PhantomData,
+        }
+    }
+
     pub const fn null() -> Self {
-        Self::from_raw(usize::MAX)
+        Self {
+            handle: usize::MAX,
+            _marker: PhantomData,
+        }
+    }
+}
+
+/// Marks handles of `Self` as sharing their raw values with handles of `S`,
+/// allows [`Handle::derived_from`]
+pub trait DerivedHandle<S: ?Sized> {}
+
+/// Catches `Handle::<B>::from_raw(a.as_raw())` with handles of unrelated types.
+/// The last exported raw value of the thread is kept until the next import, so
+/// only conversions done right after the export are caught
+#[cfg(all(feature = "handle_audit", debug_assertions))]
+mod audit {
+    use std::{any::type_name, cell::Cell};
+
+    thread_local! {
+        static LAST_EXPORT: Cell<Option<(usize, &'static str)>> = const { Cell::new(None) };
+    }
+
+    pub fn export<T>(raw: usize) {
+        LAST_EXPORT.set(Some((raw, type_name::<T>())));
+    }
+
+    pub fn import<T>(raw: usize) {
+        if let Some((exported, exported_type)) = LAST_EXPORT.take() {
+            assert!(
+                exported != raw || exported_type == type_name::<T>(),
+                "Raw value {raw:x} of a `Handle<{exported_type}>` reused for a `Handle<{}>`, \
+                 implement `DerivedHandle` and use `Handle::derived_from` instead",
+                type_name::<T>()
+            );
+        }
     }
 }
 
@@ -126,6 +197,24 @@ impl<T> Clone for Handle<T> {
 
 impl<T> Copy for Handle<T> {}
 
+/// Handles are serialized as their raw values
+impl<T> Serialize for Handle<T> {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_u64(self.handle as u64)
+    }
+}
+
+impl<'de, T> Deserialize<'de> for Handle<T> {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let raw = u64::deserialize(deserializer)?;
+
+        Ok(Self {
+            handle: usize::try_from(raw).map_err(serde::de::Error::custom)?,
+            _marker: PhantomData,
+        })
+    }
+}
+
 pub enum HandlePlacement {
     Present,
     NotPresent,
@@ -215,8 +304,9 @@ impl<T> Default for DenseHandleStrategy<T> {
     }
 }
 
-impl<T> HandleStrategy<T> for DenseHandleStrategy<T> {
-    fn new_handle(&mut self, _: &T) -> (Handle<T>, bool) {
+impl<T> DenseHandleStrategy<T> {
+    /// Allocates a handle without an object, returns `true` if the handle was recycled
+    pub fn reserve(&self) -> (Handle<T>, bool) {
         let mut dumpster = self
             .id_dumpster
             .lock()
@@ -229,6 +319,12 @@ impl<T> HandleStrategy<T> for DenseHandleStrategy<T> {
             (dumpster.pop_front().unwrap(), true)
         }
     }
+}
+
+impl<T> HandleStrategy<T> for DenseHandleStrategy<T> {
+    fn new_handle(&mut self, _: &T) -> (Handle<T>, bool) {
+        self.reserve()
+    }
 
     fn mark_deleted(&mut self, handle: Handle<T>) {
         let mut a = self
@@ -254,3 +350,39 @@ impl<T> HandleStrategy<T> for DenseHandleStrategy<T> {
         }
     }
 }
+
+#[cfg(test)]
+mod test {
+    use serde::{de::value::U64Deserializer, Deserialize};
+
+    use super::{DerivedHandle, Handle};
+
+    struct Window;
+    struct Surface;
+
+    impl DerivedHandle<Window> for Surface {}
+
+    #[test]
+    fn should_derive_handle_from_related_type() {
+        let window = Handle::<Window>::from_raw(42usize);
+        let surface = Handle::<Surface>::derived_from(&window);
+
+        assert_eq!(surface.as_raw(), 42);
+    }
+
+    #[test]
+    fn should_deserialize_handle_from_raw_value() {
+        let deserializer = U64Deserializer::<serde::de::value::Error>::new(7);
+        let handle = Handle::<Window>::deserialize(deserializer).unwrap();
+
+        assert_eq!(handle, Handle::from_raw(7usize));
+    }
+
+    #[cfg(all(feature = "handle_audit", debug_assertions))]
+    #[test]
+    #[should_panic]
+    fn should_panic_on_cross_type_raw_reuse() {
+        let window = Handle::<Window>::from_raw(3usize);
+        let _ = Handle::<Surface>::from_raw(window.as_raw());
+    }
+}
@@ -7,4 +7,4 @@ pub mod erased_buffer;
 pub mod handle;
 pub mod utils;
 
-pub use handle::{Handle, IntoHandleRawValue};
+pub use handle::{DerivedHandle, Handle, IntoHandleRawValue};
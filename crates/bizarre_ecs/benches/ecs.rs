@@ -0,0 +1,114 @@
+use bizarre_ecs::{prelude::*, world::World};
+use criterion::{black_box, criterion_group, criterion_main, BatchSize, Criterion};
+
+#[derive(Component, Clone, Copy)]
+struct Position([f32; 3]);
+
+#[derive(Component, Clone, Copy)]
+struct Velocity([f32; 3]);
+
+#[derive(Component, Clone, Copy)]
+struct Health(u32);
+
+const ENTITY_COUNT: usize = 10_000;
+
+fn populated_world() -> World {
+    let mut world = World::new();
+
+    world.register_components::<(Position, Velocity, Health)>();
+
+    for i in 0..ENTITY_COUNT {
+        let entity = world.spawn_entity((Position([0.0; 3]), Velocity([1.0; 3])));
+
+        if i % 2 == 0 {
+            world.insert_component(entity, Health(100));
+        }
+    }
+
+    world
+}
+
+fn spawn_despawn(c: &mut Criterion) {
+    let mut group = c.benchmark_group("spawn_despawn");
+
+    group.bench_function("spawn", |b| {
+        b.iter_batched(
+            || {
+                let mut world = World::new();
+                world.register_components::<(Position, Velocity)>();
+                world
+            },
+            |mut world| {
+                for _ in 0..ENTITY_COUNT {
+                    black_box(world.spawn_entity((Position([0.0; 3]), Velocity([1.0; 3]))));
+                }
+                world
+            },
+            BatchSize::LargeInput,
+        )
+    });
+
+    group.bench_function("spawn_kill_reuse", |b| {
+        let mut world = populated_world();
+
+        b.iter(|| {
+            let entity = world.spawn_entity((Position([0.0; 3]), Velocity([1.0; 3])));
+            world.kill(black_box(entity));
+        })
+    });
+
+    group.finish();
+}
+
+fn iteration(c: &mut Criterion) {
+    let mut group = c.benchmark_group("iteration");
+
+    group.bench_function("query_single", |b| {
+        let world = populated_world();
+
+        b.iter(|| {
+            for position in Query::<&Position>::new(&world) {
+                black_box(position);
+            }
+        })
+    });
+
+    group.bench_function("query_pair_mut", |b| {
+        let world = populated_world();
+
+        b.iter(|| {
+            for (position, velocity) in Query::<(&mut Position, &Velocity)>::new(&world) {
+                position.0[0] += velocity.0[0];
+            }
+        })
+    });
+
+    group.bench_function("query_sparse_match", |b| {
+        let world = populated_world();
+
+        b.iter(|| {
+            for (health, position) in Query::<(&Health, &Position)>::new(&world) {
+                black_box((health.0, position.0));
+            }
+        })
+    });
+
+    group.bench_function("query_after_structural_change", |b| {
+        let mut world = populated_world();
+
+        b.iter(|| {
+            let entity = world.spawn_entity(Position([0.0; 3]));
+
+            for position in Query::<&Position>::new(&world) {
+                black_box(position);
+            }
+
+            world.kill(entity);
+        })
+    });
+
+    group.finish();
+}
+
+criterion_group!(benches, spawn_despawn, iteration);
+criterion_main!(benches);
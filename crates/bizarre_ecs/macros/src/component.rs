@@ -12,6 +12,7 @@ pub fn derive_component_impl(input: DeriveInput) -> proc_macro2::TokenStream {
 
     let mut insert_fn = None;
     let mut remove_fn = None;
+    let mut storage = None;
 
     for attr in attrs {
         let Some(ident) = attr.path().get_ident() else {
@@ -22,6 +23,32 @@ pub fn derive_component_impl(input: DeriveInput) -> proc_macro2::TokenStream {
         match ident_string.as_str() {
             "on_insert_fn" => insert_fn = attr.parse_args::<syn::Path>().ok(),
             "on_remove_fn" => remove_fn = attr.parse_args::<syn::Path>().ok(),
+            "component" => {
+                let result = attr.parse_nested_meta(|meta| {
+                    if !meta.path.is_ident("storage") {
+                        return Err(meta.error("expected `storage = \"sparse\" | \"dense\"`"));
+                    }
+
+                    let kind = meta.value()?.parse::<syn::LitStr>()?;
+
+                    storage = match kind.value().as_str() {
+                        "sparse" => Some(quote! { ::bizarre_ecs::component::StorageKind::Sparse }),
+                        "dense" => Some(quote! { ::bizarre_ecs::component::StorageKind::Dense }),
+                        _ => {
+                            return Err(syn::Error::new_spanned(
+                                kind,
+                                "expected `\"sparse\"` or `\"dense\"` storage",
+                            ))
+                        }
+                    };
+
+                    Ok(())
+                });
+
+                if let Err(err) = result {
+                    return err.to_compile_error();
+                }
+            }
             _ => {}
         }
     }
@@ -46,6 +73,16 @@ pub fn derive_component_impl(input: DeriveInput) -> proc_macro2::TokenStream {
         TokenStream::new()
     };
 
+    let storage_kind_impl = if let Some(storage) = storage {
+        quote! {
+            fn storage_kind() -> ::bizarre_ecs::component::StorageKind {
+                #storage
+            }
+        }
+    } else {
+        TokenStream::new()
+    };
+
     let (impl_generics, type_generics, where_clause) = generics.split_for_impl();
 
     quote! {
@@ -56,6 +93,7 @@ pub fn derive_component_impl(input: DeriveInput) -> proc_macro2::TokenStream {
         impl #impl_generics Component for #ident #type_generics #where_clause {
             #on_insert_impl
             #on_remove_impl
+            #storage_kind_impl
         }
     }
 }
@@ -5,12 +5,16 @@ use component_batch::derive_component_batch_impl;
 use proc_macro::TokenStream;
 use resource::derive_resource_impl;
 use syn::{parse_macro_input, DeriveInput};
+use system_param::derive_system_param_impl;
+use system_set::derive_system_set_impl;
 
 mod component;
 mod component_batch;
 mod resource;
+mod system_param;
+mod system_set;
 
-#[proc_macro_derive(Component, attributes(on_insert_fn, on_remove_fn))]
+#[proc_macro_derive(Component, attributes(component, on_insert_fn, on_remove_fn))]
 pub fn derive_component(input: TokenStream) -> TokenStream {
     derive_component_impl(parse_macro_input!(input as DeriveInput)).into()
 }
@@ -24,3 +28,16 @@ pub fn derive_resource(input: TokenStream) -> TokenStream {
 pub fn derive_component_batch(input: TokenStream) -> TokenStream {
     derive_component_batch_impl(parse_macro_input!(input as DeriveInput)).into()
 }
+
+/// Bundles the fields, each one a `SystemParam`, into a single param. The struct
+/// may only have the `'w` (world) and `'s` (param state) lifetimes
+#[proc_macro_derive(SystemParam)]
+pub fn derive_system_param(input: TokenStream) -> TokenStream {
+    derive_system_param_impl(parse_macro_input!(input as DeriveInput)).into()
+}
+
+/// Names the type after itself for ordering systems relative to each other
+#[proc_macro_derive(SystemSet)]
+pub fn derive_system_set(input: TokenStream) -> TokenStream {
+    derive_system_set_impl(parse_macro_input!(input as DeriveInput)).into()
+}
@@ -13,4 +13,3 @@ pub fn derive_resource_impl(input: DeriveInput) -> proc_macro2::TokenStream {
         impl #impl_generics Resource for #ident #type_generics #where_clause {}
     }
 }
-
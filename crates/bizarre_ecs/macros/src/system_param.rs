@@ -0,0 +1,157 @@
+use proc_macro::Diagnostic;
+use proc_macro2::TokenStream;
+use quote::{format_ident, quote};
+use syn::{spanned::Spanned, visit_mut::VisitMut, DeriveInput, GenericParam, Lifetime, Member};
+
+/// Lifetime of the world borrowed by the params
+const WORLD_LIFETIME: &str = "w";
+/// Lifetime of the param state, borrowed by params like `Local`
+const STATE_LIFETIME: &str = "s";
+
+fn is_param_lifetime(lifetime: &Lifetime) -> bool {
+    lifetime.ident == WORLD_LIFETIME || lifetime.ident == STATE_LIFETIME
+}
+
+/// Replaces `'w` and `'s` with `'static`, so field params can be named outside of
+/// `get_item`
+struct StaticLifetimes;
+
+impl VisitMut for StaticLifetimes {
+    fn visit_lifetime_mut(&mut self, lifetime: &mut Lifetime) {
+        if is_param_lifetime(lifetime) {
+            *lifetime = Lifetime::new("'static", lifetime.span());
+        }
+    }
+}
+
+/// Renames `'w` and `'s` of the impl, so they don't clash with the lifetimes of
+/// `SystemParam::Item`
+struct ImplLifetimes;
+
+impl VisitMut for ImplLifetimes {
+    fn visit_lifetime_mut(&mut self, lifetime: &mut Lifetime) {
+        if is_param_lifetime(lifetime) {
+            *lifetime = Lifetime::new(&format!("'__{}", lifetime.ident), lifetime.span());
+        }
+    }
+}
+
+pub fn derive_system_param_impl(input: DeriveInput) -> TokenStream {
+    let DeriveInput {
+        ident,
+        generics,
+        data,
+        ..
+    } = input;
+
+    let data = match data {
+        syn::Data::Struct(data_struct) => data_struct,
+        _ => {
+            Diagnostic::spanned(
+                ident.span().unwrap(),
+                proc_macro::Level::Error,
+                "`SystemParam` can be derived only for structs",
+            )
+            .emit();
+
+            return TokenStream::new();
+        }
+    };
+
+    let foreign_lifetimes = generics
+        .params
+        .iter()
+        .filter_map(|param| match param {
+            GenericParam::Lifetime(param) if !is_param_lifetime(&param.lifetime) => {
+                Some(param.lifetime.span().unwrap())
+            }
+            _ => None,
+        })
+        .collect::<Vec<_>>();
+
+    if !foreign_lifetimes.is_empty() {
+        Diagnostic::spanned(
+            foreign_lifetimes,
+            proc_macro::Level::Error,
+            "A `SystemParam` can only have the `'w` (world) and `'s` (param state) lifetimes",
+        )
+        .emit();
+
+        return TokenStream::new();
+    }
+
+    let (members, types): (Vec<_>, Vec<_>) = data
+        .fields
+        .iter()
+        .enumerate()
+        .map(|(index, field)| {
+            let member = match &field.ident {
+                Some(ident) => Member::Named(ident.clone()),
+                None => Member::Unnamed(index.into()),
+            };
+
+            let mut ty = field.ty.clone();
+            StaticLifetimes.visit_type_mut(&mut ty);
+
+            (member, ty)
+        })
+        .unzip();
+
+    let states = (0..types.len())
+        .map(|index| format_ident!("state_{index}"))
+        .collect::<Vec<_>>();
+
+    let mut impl_generics = generics.clone();
+    ImplLifetimes.visit_generics_mut(&mut impl_generics);
+
+    let (_, item_generics, _) = generics.split_for_impl();
+    let (impl_generics, type_generics, where_clause) = impl_generics.split_for_impl();
+
+    let derive = quote! { __system_param_derive };
+
+    quote! {
+        #[automatically_derived]
+        impl #impl_generics #derive::SystemParam for #ident #type_generics #where_clause {
+            type Item<'w, 's> = #ident #item_generics;
+
+            type State = (#(<#types as #derive::SystemParam>::State,)*);
+
+            unsafe fn init(world: #derive::UnsafeWorldCell) -> Self::State {
+                (#(<#types as #derive::SystemParam>::init(world),)*)
+            }
+
+            unsafe fn get_item<'w, 's>(
+                world: #derive::UnsafeWorldCell<'w>,
+                param_state: &'s mut Self::State,
+            ) -> Self::Item<'w, 's>
+            where
+                Self: Sized,
+            {
+                let (#(#states,)*) = param_state;
+
+                #ident {
+                    #(#members: <#types as #derive::SystemParam>::get_item(world, #states),)*
+                }
+            }
+
+            fn param_access() -> Vec<#derive::WorldAccess> {
+                let mut access = vec![];
+                #(access.extend(<#types as #derive::SystemParam>::param_access());)*
+                access
+            }
+
+            fn take_deferred(state: &mut Self::State) -> Option<#derive::CommandBuffer> {
+                let (#(#states,)*) = state;
+                let mut cmd = #derive::CommandBuffer::new();
+
+                #(
+                    if let Some(mut deferred) = <#types as #derive::SystemParam>::take_deferred(#states) {
+                        cmd.append(&mut deferred);
+                    }
+                )*
+
+                (!cmd.is_empty()).then_some(cmd)
+            }
+        }
+    }
+}
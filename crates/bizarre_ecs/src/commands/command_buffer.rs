@@ -38,6 +38,11 @@ impl CommandBuffer {
     pub fn is_empty(&self) -> bool {
         self.bytes.is_empty()
     }
+
+    /// Amount of queued commands
+    pub fn len(&self) -> usize {
+        unsafe { command_count(&self.bytes) }
+    }
 }
 
 impl Drop for CommandBuffer {
@@ -49,6 +54,8 @@ impl Drop for CommandBuffer {
 }
 
 pub struct CommandMeta {
+    /// Size of the command following the meta
+    size: usize,
     consume: unsafe fn(command: *mut (), Option<NonNull<World>>, cursor: &mut usize),
 }
 
@@ -72,6 +79,7 @@ impl RawCommandBuffer {
         }
 
         let meta = CommandMeta {
+            size: size_of::<T>(),
             consume: |command, world, cursor| {
                 *cursor += size_of::<T>();
                 let command: T = command.cast::<T>().read_unaligned();
@@ -127,6 +135,37 @@ impl RawCommandBuffer {
     pub unsafe fn is_empty(&self) -> bool {
         self.bytes.as_ref().is_empty()
     }
+
+    /// Amount of queued commands
+    ///
+    /// # Safety
+    ///
+    /// The buffer this was created from must be alive and not mutated while
+    /// the commands are counted
+    pub unsafe fn len(&self) -> usize {
+        command_count(self.bytes.as_ref())
+    }
+}
+
+/// # Safety
+///
+/// `bytes` must hold commands pushed by [`RawCommandBuffer::push`]
+unsafe fn command_count(bytes: &[MaybeUninit<u8>]) -> usize {
+    let mut cursor = 0;
+    let mut len = 0;
+
+    while cursor < bytes.len() {
+        let meta = bytes
+            .as_ptr()
+            .add(cursor)
+            .cast::<CommandMeta>()
+            .read_unaligned();
+
+        cursor += size_of::<CommandMeta>() + meta.size;
+        len += 1;
+    }
+
+    len
 }
 
 impl Default for RawCommandBuffer {
@@ -6,6 +6,7 @@ use crate::{
         entity_commands::{EntityCmdBuilder, SpawnEntityCmd},
         Entity,
     },
+    prefab::SpawnPrefabCmd,
     prelude::Resource,
     resource::resource_commands::{InsertResourceCmd, RemoveResourceCmd},
     system::{
@@ -40,6 +41,21 @@ impl<'s> Commands<'s> {
         self
     }
 
+    /// Spawns an instance of the prefab registered in
+    /// [`Prefabs`](crate::prefab::Prefabs) under `name`, `overrides` replace its
+    /// components of the same type
+    pub fn spawn_prefab(
+        &mut self,
+        name: impl Into<String>,
+        overrides: impl ComponentBatch,
+    ) -> &mut Self {
+        self.buffer.push(SpawnPrefabCmd {
+            name: name.into(),
+            overrides,
+        });
+        self
+    }
+
     pub fn entity(&mut self, entity: Entity) -> EntityCmdBuilder<false> {
         EntityCmdBuilder::new(self.buffer, entity)
     }
@@ -56,11 +72,13 @@ impl<'s> Commands<'s> {
 
     pub fn add_systems<M>(
         &mut self,
-        schedule: Schedule,
+        schedule: impl Into<Schedule>,
         systems: impl IntoSystemConfigs<M>,
     ) -> &mut Self {
-        self.buffer
-            .push(AddSystemsCmd::new(schedule, systems.into_system_configs()));
+        self.buffer.push(AddSystemsCmd::new(
+            schedule.into(),
+            systems.into_system_configs(),
+        ));
         self
     }
 
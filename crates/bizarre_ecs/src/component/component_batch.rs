@@ -33,7 +33,8 @@ impl<T: Component> ComponentBatch for T {
 }
 
 macro_rules! impl_component_batch {
-    ($($comp:tt),+) => {
+    ($(#[$meta:meta])*; $($comp:tt),+) => {
+        $(#[$meta])*
         #[allow(non_snake_case)]
         impl<$($comp: Component),+> ComponentBatch for ($($comp,)+) {
             fn register(components: &mut ComponentRegistry) {
@@ -56,4 +57,4 @@ macro_rules! impl_component_batch {
     };
 }
 
-mass_impl!(impl_component_batch, 16, C);
+mass_impl!(impl_component_batch, 16, C; doc_hidden);
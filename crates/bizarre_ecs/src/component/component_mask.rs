@@ -0,0 +1,106 @@
+const INLINE_WORDS: usize = 2;
+const WORD_BITS: usize = u64::BITS as usize;
+
+/// Set of component indices of a [`ComponentRegistry`](super::ComponentRegistry).
+///
+/// The first 128 components are kept inline, the rest spill into a vector that
+/// never ends with a zero word, so equal sets compare and hash equal
+#[derive(Clone, Default, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct ComponentMask {
+    inline: [u64; INLINE_WORDS],
+    spilled: Vec<u64>,
+}
+
+impl ComponentMask {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn insert(&mut self, index: usize) {
+        let (word, bit) = (index / WORD_BITS, index % WORD_BITS);
+
+        if word >= INLINE_WORDS && self.spilled.len() <= word - INLINE_WORDS {
+            self.spilled.resize(word - INLINE_WORDS + 1, 0);
+        }
+
+        *self.word_mut(word) |= 1 << bit;
+    }
+
+    pub fn remove(&mut self, index: usize) {
+        let (word, bit) = (index / WORD_BITS, index % WORD_BITS);
+
+        if word >= INLINE_WORDS + self.spilled.len() {
+            return;
+        }
+
+        *self.word_mut(word) &= !(1 << bit);
+        self.trim();
+    }
+
+    pub fn contains(&self, index: usize) -> bool {
+        self.word(index / WORD_BITS) & (1 << (index % WORD_BITS)) != 0
+    }
+
+    /// `true` if every index of `other` is in the set
+    pub fn contains_all(&self, other: &Self) -> bool {
+        (0..other.word_count()).all(|word| self.word(word) & other.word(word) == other.word(word))
+    }
+
+    /// `true` if the sets share any index
+    pub fn intersects(&self, other: &Self) -> bool {
+        (0..self.word_count().min(other.word_count()))
+            .any(|word| self.word(word) & other.word(word) != 0)
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.inline.iter().all(|word| *word == 0) && self.spilled.is_empty()
+    }
+
+    pub fn clear(&mut self) {
+        self.inline = [0; INLINE_WORDS];
+        self.spilled.clear();
+    }
+
+    /// Indices in the set in ascending order
+    pub fn iter(&self) -> impl Iterator<Item = usize> + '_ {
+        (0..self.word_count()).flat_map(move |word| {
+            let bits = self.word(word);
+
+            (0..WORD_BITS)
+                .filter(move |bit| bits & (1 << bit) != 0)
+                .map(move |bit| word * WORD_BITS + bit)
+        })
+    }
+
+    fn word_count(&self) -> usize {
+        INLINE_WORDS + self.spilled.len()
+    }
+
+    fn word(&self, word: usize) -> u64 {
+        match word.checked_sub(INLINE_WORDS) {
+            None => self.inline[word],
+            Some(spilled) => self.spilled.get(spilled).copied().unwrap_or(0),
+        }
+    }
+
+    fn word_mut(&mut self, word: usize) -> &mut u64 {
+        match word.checked_sub(INLINE_WORDS) {
+            None => &mut self.inline[word],
+            Some(spilled) => &mut self.spilled[spilled],
+        }
+    }
+
+    fn trim(&mut self) {
+        while self.spilled.last() == Some(&0) {
+            self.spilled.pop();
+        }
+    }
+}
+
+impl FromIterator<usize> for ComponentMask {
+    fn from_iter<T: IntoIterator<Item = usize>>(iter: T) -> Self {
+        let mut mask = Self::new();
+        iter.into_iter().for_each(|index| mask.insert(index));
+        mask
+    }
+}
@@ -1,3 +1,292 @@
 use bizarre_core::erased_buffer::ErasedSparseArray;
 
 use super::Component;
+
+/// How components of a type are laid out in memory, selected with
+/// `#[component(storage = "sparse" | "dense")]`. The attribute names it as
+/// `::bizarre_ecs::component::StorageKind`
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum StorageKind {
+    /// A slot for every entity, cheap to insert and remove
+    #[default]
+    Sparse,
+    /// Components packed next to each other, for components iterated every frame
+    Dense,
+}
+
+const NO_COMPONENT: usize = usize::MAX;
+
+/// Components packed at the start of a buffer with an entity index to
+/// packed index remap. Removal moves the last component into the freed slot
+pub struct ErasedDenseArray {
+    data: ErasedSparseArray,
+    /// Packed index of the component of every entity index
+    sparse: Vec<usize>,
+    /// Entity index of every packed component
+    entities: Vec<usize>,
+}
+
+impl ErasedDenseArray {
+    pub fn with_capacity<T: Component>(capacity: usize) -> Self {
+        Self {
+            data: ErasedSparseArray::with_capacity::<T>(capacity.max(1)),
+            sparse: vec![NO_COMPONENT; capacity],
+            entities: Vec::with_capacity(capacity),
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.entities.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entities.is_empty()
+    }
+
+    /// Entity indices of the packed components, in the storage order
+    pub fn entity_indices(&self) -> &[usize] {
+        &self.entities
+    }
+
+    fn packed_index(&self, index: usize) -> Option<usize> {
+        self.sparse
+            .get(index)
+            .copied()
+            .filter(|packed| *packed != NO_COMPONENT)
+    }
+
+    pub fn contains(&self, index: usize) -> bool {
+        self.packed_index(index).is_some()
+    }
+
+    /// # Safety
+    ///
+    /// `T` must be the component type the storage was created for
+    pub unsafe fn get<T: Component>(&self, index: usize) -> Option<&T> {
+        self.data.get(self.packed_index(index)?)
+    }
+
+    /// # Safety
+    ///
+    /// `T` must be the component type the storage was created for
+    pub unsafe fn get_mut<T: Component>(&self, index: usize) -> Option<&mut T> {
+        self.data.get_mut(self.packed_index(index)?)
+    }
+
+    /// # Safety
+    ///
+    /// `T` must be the component type the storage was created for
+    pub unsafe fn insert<T: Component>(&mut self, index: usize, value: T) -> Option<T> {
+        if let Some(packed) = self.packed_index(index) {
+            return self.data.insert(packed, value);
+        }
+
+        let packed = self.entities.len();
+
+        if packed >= self.data.capacity() {
+            self.data.grow(self.data.capacity() * 2);
+        }
+
+        self.data.insert(packed, value);
+        self.entities.push(index);
+        self.sparse[index] = packed;
+
+        None
+    }
+
+    /// # Safety
+    ///
+    /// `T` must be the component type the storage was created for
+    pub unsafe fn remove<T: Component>(&mut self, index: usize) -> Option<T> {
+        let packed = self.packed_index(index)?;
+        let value = self.data.remove(packed);
+
+        self.fill_hole(index, packed);
+
+        value
+    }
+
+    pub fn drop_element(&mut self, index: usize) {
+        let Some(packed) = self.packed_index(index) else {
+            return;
+        };
+
+        self.data.drop_element(packed);
+        self.fill_hole(index, packed);
+    }
+
+    /// Moves the component of the entity index `from` to the entity index `to`
+    ///
+    /// # Safety
+    ///
+    /// `to` must not have a component
+    pub unsafe fn move_element(&mut self, from: usize, to: usize) {
+        let Some(packed) = self.packed_index(from) else {
+            return;
+        };
+
+        debug_assert!(
+            !self.contains(to),
+            "Moving an element into an occupied slot"
+        );
+
+        self.sparse[from] = NO_COMPONENT;
+        self.sparse[to] = packed;
+        self.entities[packed] = to;
+    }
+
+    pub fn grow(&mut self, new_capacity: usize) {
+        if self.sparse.len() < new_capacity {
+            self.sparse.resize(new_capacity, NO_COMPONENT);
+        }
+    }
+
+    /// # Safety
+    ///
+    /// `T` must be the component type the storage was created for
+    pub unsafe fn iter_mut<T: Component>(&mut self) -> impl Iterator<Item = &mut T> {
+        self.data.iter_mut()
+    }
+
+    /// Moves the last packed component into the emptied `packed` slot of `index`
+    fn fill_hole(&mut self, index: usize, packed: usize) {
+        self.sparse[index] = NO_COMPONENT;
+        self.entities.swap_remove(packed);
+
+        if let Some(&moved) = self.entities.get(packed) {
+            unsafe { self.data.move_element(self.entities.len(), packed) };
+            self.sparse[moved] = packed;
+        }
+    }
+}
+
+/// Type erased storage of a single component type
+pub enum ComponentStorage {
+    Sparse(ErasedSparseArray),
+    Dense(ErasedDenseArray),
+}
+
+impl ComponentStorage {
+    pub fn new<T: Component>(capacity: usize) -> Self {
+        match T::storage_kind() {
+            StorageKind::Sparse => Self::Sparse(ErasedSparseArray::with_capacity::<T>(capacity)),
+            StorageKind::Dense => Self::Dense(ErasedDenseArray::with_capacity::<T>(capacity)),
+        }
+    }
+
+    pub fn kind(&self) -> StorageKind {
+        match self {
+            Self::Sparse(_) => StorageKind::Sparse,
+            Self::Dense(_) => StorageKind::Dense,
+        }
+    }
+
+    pub fn contains(&self, index: usize) -> bool {
+        match self {
+            Self::Sparse(storage) => storage.contains(index),
+            Self::Dense(storage) => storage.contains(index),
+        }
+    }
+
+    /// # Safety
+    ///
+    /// `T` must be the component type the storage was created for
+    pub unsafe fn get<T: Component>(&self, index: usize) -> Option<&T> {
+        match self {
+            Self::Sparse(storage) => storage.get(index),
+            Self::Dense(storage) => storage.get(index),
+        }
+    }
+
+    /// # Safety
+    ///
+    /// `T` must be the component type the storage was created for
+    pub unsafe fn get_mut<T: Component>(&self, index: usize) -> Option<&mut T> {
+        match self {
+            Self::Sparse(storage) => storage.get_mut(index),
+            Self::Dense(storage) => storage.get_mut(index),
+        }
+    }
+
+    /// # Safety
+    ///
+    /// `T` must be the component type the storage was created for
+    pub unsafe fn insert<T: Component>(&mut self, index: usize, value: T) -> Option<T> {
+        match self {
+            Self::Sparse(storage) => storage.insert(index, value),
+            Self::Dense(storage) => storage.insert(index, value),
+        }
+    }
+
+    /// # Safety
+    ///
+    /// `T` must be the component type the storage was created for
+    pub unsafe fn remove<T: Component>(&mut self, index: usize) -> Option<T> {
+        match self {
+            Self::Sparse(storage) => storage.remove(index),
+            Self::Dense(storage) => storage.remove(index),
+        }
+    }
+
+    pub fn drop_element(&mut self, index: usize) {
+        match self {
+            Self::Sparse(storage) => storage.drop_element(index),
+            Self::Dense(storage) => storage.drop_element(index),
+        }
+    }
+
+    /// # Safety
+    ///
+    /// `to` must not have a component
+    pub unsafe fn move_element(&mut self, from: usize, to: usize) {
+        match self {
+            Self::Sparse(storage) => storage.move_element(from, to),
+            Self::Dense(storage) => storage.move_element(from, to),
+        }
+    }
+
+    pub fn grow(&mut self, new_capacity: usize) {
+        match self {
+            Self::Sparse(storage) => {
+                storage.grow(new_capacity);
+            }
+            Self::Dense(storage) => storage.grow(new_capacity),
+        }
+    }
+
+    /// Where the component of the entity `index` is in the storage memory,
+    /// components in consecutive slots lie next to each other.
+    ///
+    /// Dense storages use the packed index, sparse ones the entity index
+    pub fn slot(&self, index: usize) -> Option<usize> {
+        match self {
+            Self::Sparse(storage) => storage.contains(index).then_some(index),
+            Self::Dense(storage) => storage.packed_index(index),
+        }
+    }
+
+    /// # Safety
+    ///
+    /// `T` must be the component type the storage was created for, the slots
+    /// `slot..slot + len` must hold components nothing else borrows mutably
+    pub unsafe fn slots_mut<'a, T: Component>(&self, slot: usize, len: usize) -> &'a mut [T] {
+        let data = match self {
+            Self::Sparse(storage) => storage.as_mut_ptr::<T>(),
+            Self::Dense(storage) => storage.data.as_mut_ptr::<T>(),
+        };
+
+        std::slice::from_raw_parts_mut(data.add(slot), len)
+    }
+
+    /// # Safety
+    ///
+    /// `T` must be the component type the storage was created for
+    pub unsafe fn iter_mut<'a, T: Component>(
+        &'a mut self,
+    ) -> Box<dyn Iterator<Item = &'a mut T> + 'a> {
+        match self {
+            Self::Sparse(storage) => Box::new(storage.iter_mut()),
+            Self::Dense(storage) => Box::new(storage.iter_mut()),
+        }
+    }
+}
@@ -1,18 +1,25 @@
-use std::collections::{BTreeMap, VecDeque};
+use std::{
+    cell::RefCell,
+    collections::{BTreeMap, VecDeque},
+    rc::Rc,
+};
 
-use bizarre_core::erased_buffer::ErasedSparseArray;
 use component_batch::ComponentBatch;
 
 use crate::{
-    entity::Entity,
+    entity::{Entity, EntityRemap},
     resource::{Resource, ResourceId},
     world::World,
 };
 
 pub mod component_batch;
 pub mod component_commands;
+mod component_mask;
 mod component_storage;
 
+pub use component_mask::ComponentMask;
+pub use component_storage::{ComponentStorage, ErasedDenseArray, StorageKind};
+
 pub use bizarre_ecs_proc_macro::Component;
 
 pub trait Component: Resource {
@@ -22,26 +29,54 @@ pub trait Component: Resource {
     fn on_remove(&mut self, world: &mut World) {
         let _ = world;
     }
+    /// Storage the components of this type are kept in
+    fn storage_kind() -> StorageKind {
+        StorageKind::Sparse
+    }
 }
 
+/// Moves the component of an entity in one registry to an entity in another one
+pub(crate) type ComponentMover = fn(&mut ComponentRegistry, Entity, &mut ComponentRegistry, Entity);
+
 pub struct ComponentRegistry {
-    storages: Vec<Option<ErasedSparseArray>>,
+    storages: Vec<Option<ComponentStorage>>,
+    /// Type names of the components in `storages`
+    names: Vec<&'static str>,
     capacity: usize,
     lookup: BTreeMap<ResourceId, usize>,
+    /// Typed movers of the registered components, used to merge worlds
+    movers: BTreeMap<ResourceId, ComponentMover>,
     index_dumpster: VecDeque<usize>,
-    entities: Vec<(Entity, u128)>,
-    component_bitmasks: Vec<u128>,
+    /// Every entity slot with the indices of its components in `storages`
+    entities: Vec<(Entity, ComponentMask)>,
+    /// Entities the components in `storages` were removed from since the last
+    /// [`clear_removed`](Self::clear_removed), including killed entities
+    removed: Vec<Vec<Entity>>,
+    /// Bumped on every change that may alter the result of a query: entity registration
+    /// and removal, component insertion and removal, storage registration and removal
+    structure_version: u64,
+    query_cache: RefCell<BTreeMap<(ComponentMask, ComponentMask), CachedQuery>>,
+}
+
+/// Entity list of a query, reused until the registry structure changes
+struct CachedQuery {
+    structure_version: u64,
+    entities: Rc<[Entity]>,
 }
 
 impl ComponentRegistry {
     pub fn with_capacity(capacity: usize) -> Self {
         Self {
             storages: Default::default(),
+            names: Default::default(),
             capacity,
             lookup: Default::default(),
+            movers: Default::default(),
             index_dumpster: Default::default(),
-            entities: vec![(Entity::from_gen_id(0, 0), 0); capacity],
-            component_bitmasks: vec![0; capacity],
+            entities: vec![(Entity::from_gen_id(0, 0), ComponentMask::new()); capacity],
+            removed: Vec::new(),
+            structure_version: 0,
+            query_cache: Default::default(),
         }
     }
 
@@ -57,33 +92,140 @@ impl ComponentRegistry {
         });
 
         self.entities
-            .extend((0..by).map(|_| (Entity::from_gen_id(0, 0), 0)));
+            .extend((0..by).map(|_| (Entity::from_gen_id(0, 0), ComponentMask::new())));
     }
 
     pub fn expand(&mut self) {
         self.expand_by(1);
     }
 
+    pub fn capacity(&self) -> usize {
+        self.capacity
+    }
+
+    /// Live entities that need to be moved for all of them to occupy the first
+    /// indices, paired with their new indices. Indices for which `is_retired`
+    /// returns `true` are skipped, they have no generations left to hand out.
+    ///
+    /// Also returns the index past the last live entity and the live entity count
+    pub(crate) fn compaction_moves(
+        &self,
+        is_retired: impl Fn(usize) -> bool,
+    ) -> (usize, usize, Vec<(Entity, usize)>) {
+        let mut end = 0;
+        let mut live = 0;
+        let mut moves = Vec::new();
+
+        for (index, (entity, _)) in self.entities.iter().enumerate() {
+            if entity.gen() == 0 {
+                continue;
+            }
+
+            while end < index && is_retired(end) {
+                end += 1;
+            }
+
+            if index != end {
+                moves.push((*entity, end));
+            }
+
+            end += 1;
+            live += 1;
+        }
+
+        (end, live, moves)
+    }
+
+    /// Drops components left in the slots of dead entities and moves live entities
+    /// with their components according to `remap`
+    pub(crate) fn compact(&mut self, remap: &EntityRemap) {
+        for (index, (entity, _)) in self.entities.iter().enumerate() {
+            if entity.gen() == 0 {
+                self.storages
+                    .iter_mut()
+                    .flatten()
+                    .for_each(|storage| storage.drop_element(index));
+            }
+        }
+
+        // Live entities only move down, moving the lowest ones first makes sure
+        // the target slots are already free
+        let mut moves = remap.iter().collect::<Vec<_>>();
+        moves.sort_by_key(|(old, _)| old.index());
+
+        for (old, new) in moves {
+            let (from, to) = (old.index(), new.index());
+
+            for storage in self.storages.iter_mut().flatten() {
+                unsafe { storage.move_element(from, to) };
+            }
+
+            let mask = std::mem::take(&mut self.entities[from].1);
+            self.entities[to] = (new, mask);
+            self.entities[from].0 = Entity::from_gen_id(0, 0);
+        }
+
+        self.structure_version += 1;
+    }
+
+    /// Entities registered and not removed yet
+    pub(crate) fn live_entities(&self) -> Vec<Entity> {
+        self.entities
+            .iter()
+            .map(|(entity, _)| *entity)
+            .filter(|entity| entity.gen() != 0)
+            .collect()
+    }
+
+    /// Name, storage kind and live component count of every registered component
+    pub(crate) fn storage_infos(&self) -> Vec<(&'static str, StorageKind, usize)> {
+        self.storages
+            .iter()
+            .enumerate()
+            .filter_map(|(index, storage)| {
+                let storage = storage.as_ref()?;
+                let count = self
+                    .entities
+                    .iter()
+                    .filter(|(entity, mask)| entity.gen() != 0 && mask.contains(index))
+                    .count();
+
+                Some((self.names[index], storage.kind(), count))
+            })
+            .collect()
+    }
+
+    pub(crate) fn movers(&self) -> Vec<ComponentMover> {
+        self.movers.values().copied().collect()
+    }
+
     pub fn register_entity(&mut self, entity: Entity) {
-        let (stored, bitmask) = &mut self.entities[entity.index()];
-        (*stored, *bitmask) = (entity, 0)
+        let (stored, mask) = &mut self.entities[entity.index()];
+        *stored = entity;
+        mask.clear();
+        self.structure_version += 1;
     }
 
     pub fn remove_entity(&mut self, entity: Entity) {
-        let (stored, bitmask) = &mut self.entities[entity.index()];
+        let (stored, mask) = &mut self.entities[entity.index()];
         if *stored == entity {
+            for index in mask.iter() {
+                self.removed[index].push(entity);
+            }
+
             stored.set_gen(0);
-            *bitmask = 0;
+            mask.clear();
+            self.structure_version += 1;
         }
     }
 
-    pub fn storage<T: Component>(&self) -> Option<&ErasedSparseArray> {
+    pub fn storage<T: Component>(&self) -> Option<&ComponentStorage> {
         let index = self.index::<T>()?;
 
         self.storages[index].as_ref()
     }
 
-    pub fn storage_mut<T: Component>(&mut self) -> Option<&mut ErasedSparseArray> {
+    pub fn storage_mut<T: Component>(&mut self) -> Option<&mut ComponentStorage> {
         let index = self.index::<T>()?;
 
         self.storages[index].as_mut()
@@ -114,19 +256,23 @@ impl ComponentRegistry {
             return;
         }
 
-        let new_storage = ErasedSparseArray::with_capacity::<T>(self.capacity);
+        let new_storage = ComponentStorage::new::<T>(self.capacity);
         let index = if let Some(index) = self.index_dumpster.pop_front() {
             self.storages[index] = Some(new_storage);
-            self.component_bitmasks[index] = 1 << index;
+            self.names[index] = T::resource_name();
+            self.removed[index].clear();
             index
         } else {
             let index = self.storages.len();
             self.storages.push(Some(new_storage));
-            self.component_bitmasks.push(1 << index);
+            self.names.push(T::resource_name());
+            self.removed.push(Vec::new());
             index
         };
 
         self.lookup.insert(T::resource_id(), index);
+        self.movers.insert(T::resource_id(), move_component::<T>);
+        self.structure_version += 1;
     }
 
     pub fn register_batch<T: ComponentBatch>(&mut self) {
@@ -138,9 +284,14 @@ impl ComponentRegistry {
             .index::<T>()
             .unwrap_or_else(|| panic!("Component `{}` is not registered", T::resource_name()));
 
-        let (stored_entity, bitmask) = &mut self.entities[entity.index()];
+        let (stored_entity, mask) = &mut self.entities[entity.index()];
+
+        if *stored_entity != entity || !mask.contains(index) {
+            self.structure_version += 1;
+        }
+
         *stored_entity = entity;
-        *bitmask |= self.component_bitmasks[index];
+        mask.insert(index);
 
         unsafe {
             self.storages[index]
@@ -162,6 +313,14 @@ impl ComponentRegistry {
 
         let index = self.index::<T>()?;
 
+        let mask = &mut self.entities[entity.index()].1;
+
+        if mask.contains(index) {
+            mask.remove(index);
+            self.removed[index].push(entity);
+            self.structure_version += 1;
+        }
+
         unsafe {
             self.storages[index]
                 .as_mut()
@@ -174,30 +333,101 @@ impl ComponentRegistry {
         T::remove(self, entity);
     }
 
-    pub fn remove_storage<T: Component>(&mut self) -> Option<ErasedSparseArray> {
+    pub fn remove_storage<T: Component>(&mut self) -> Option<ComponentStorage> {
         let index = self.index::<T>()?;
 
         let ret = self.storages[index].take();
         self.lookup.remove(&T::resource_id());
+        self.movers.remove(&T::resource_id());
+        self.structure_version += 1;
         ret
     }
 
+    /// Entities `T` was removed from since the removal logs were last cleared,
+    /// see [`RemovedComponents`](crate::system::removed_components::RemovedComponents)
+    pub fn removed<T: Component>(&self) -> &[Entity] {
+        self.index::<T>()
+            .map_or(&[], |index| self.removed[index].as_slice())
+    }
+
+    /// Clears the removal logs of every component
+    pub fn clear_removed(&mut self) {
+        self.removed.iter_mut().for_each(Vec::clear);
+    }
+
     pub fn filter_entities(&self, ids: &[ResourceId]) -> Vec<Entity> {
         if ids.is_empty() {
             return self.entities.iter().map(|(e, _)| *e).collect();
         }
 
-        let query_bitmask = ids.iter().fold(0u128, |acc, curr| {
-            let index = self
-                .index_by_id(curr)
-                .expect("Trying to filter entities using unregistered `ResourceId`");
+        self.collect_entities(&self.query_mask(ids), &ComponentMask::new())
+    }
 
-            acc | self.component_bitmasks[index]
-        });
+    /// Same as [`filter_entities`](Self::filter_entities), but the resulting list is cached
+    /// per set of components and reused until the structure of the registry changes
+    pub fn query_entities(&self, ids: &[ResourceId]) -> Rc<[Entity]> {
+        self.query_entities_filtered(ids, &[])
+    }
+
+    /// Same as [`query_entities`](Self::query_entities), but skips entities having any
+    /// of the `without` components. Unregistered `without` components are ignored
+    pub fn query_entities_filtered(
+        &self,
+        ids: &[ResourceId],
+        without: &[ResourceId],
+    ) -> Rc<[Entity]> {
+        let query_mask = self.query_mask(ids);
+        let exclude_mask = without
+            .iter()
+            .filter_map(|id| self.index_by_id(id))
+            .collect::<ComponentMask>();
+
+        let mut cache = self.query_cache.borrow_mut();
+        let key = (query_mask, exclude_mask);
+
+        match cache.get(&key) {
+            Some(cached) if cached.structure_version == self.structure_version => {
+                cached.entities.clone()
+            }
+            _ => {
+                let entities: Rc<[Entity]> = self.collect_entities(&key.0, &key.1).into();
+
+                cache.insert(
+                    key,
+                    CachedQuery {
+                        structure_version: self.structure_version,
+                        entities: entities.clone(),
+                    },
+                );
+
+                entities
+            }
+        }
+    }
 
+    pub fn structure_version(&self) -> u64 {
+        self.structure_version
+    }
+
+    fn query_mask(&self, ids: &[ResourceId]) -> ComponentMask {
+        ids.iter()
+            .map(|id| {
+                self.index_by_id(id)
+                    .expect("Trying to filter entities using unregistered `ResourceId`")
+            })
+            .collect()
+    }
+
+    fn collect_entities(
+        &self,
+        query_mask: &ComponentMask,
+        exclude_mask: &ComponentMask,
+    ) -> Vec<Entity> {
         self.entities
             .iter()
-            .filter(|(e, b)| e.gen() != 0 && b & query_bitmask == query_bitmask)
+            .filter(|(e, mask)| {
+                e.gen() != 0 && mask.contains_all(query_mask) && !mask.intersects(exclude_mask)
+            })
             .map(|(e, _)| *e)
             .collect()
     }
@@ -255,10 +485,26 @@ impl ComponentRegistry {
 
     pub(crate) fn clear(&mut self) {
         self.storages.clear();
+        self.names.clear();
         self.lookup.clear();
+        self.movers.clear();
         self.index_dumpster.clear();
         self.entities.clear();
-        self.component_bitmasks.clear();
+        self.removed.clear();
+        self.query_cache.borrow_mut().clear();
+        self.structure_version += 1;
+    }
+}
+
+fn move_component<T: Component>(
+    from: &mut ComponentRegistry,
+    entity: Entity,
+    to: &mut ComponentRegistry,
+    target: Entity,
+) {
+    if let Some(component) = from.remove::<T>(entity) {
+        to.register::<T>();
+        to.insert(target, component);
     }
 }
 
@@ -273,7 +519,7 @@ mod test {
     use crate::entity::Entity;
     use crate::prelude::*;
 
-    use super::ComponentRegistry;
+    use super::{ComponentRegistry, ComponentStorage};
 
     #[derive(Component, Debug, Clone, PartialEq, Eq, PartialOrd, Ord)]
     struct Health(pub u32);
@@ -284,6 +530,18 @@ mod test {
     #[derive(Component, Debug, Clone, PartialEq, Eq, PartialOrd, Ord)]
     struct Name(pub &'static str);
 
+    #[derive(Component, Debug, Clone, PartialEq, Eq, PartialOrd, Ord)]
+    #[component(storage = "dense")]
+    struct Position(pub i32);
+
+    mod without_prelude {
+        use crate::{component::Component, resource::Resource};
+
+        #[derive(Component)]
+        #[component(storage = "dense")]
+        pub struct Velocity;
+    }
+
     #[test]
     #[should_panic]
     pub fn should_panic_on_unregistered_insert() {
@@ -320,4 +578,140 @@ mod test {
         assert!(c.has_component_for_entity::<Health>(entity_0));
         assert!(c.component(entity_0) == Some(&Health(100)));
     }
+
+    #[test]
+    pub fn should_invalidate_cached_queries_on_structural_changes() {
+        let mut c = ComponentRegistry::with_capacity(2);
+        let entity_0 = Entity::from_gen_id(1, 0);
+        let entity_1 = Entity::from_gen_id(1, 1);
+
+        c.register::<Health>();
+        c.register::<Mana>();
+
+        let ids = [Health::resource_id()];
+
+        c.insert(entity_0, Health(100));
+        assert_eq!(&*c.query_entities(&ids), &[entity_0]);
+
+        let cached = c.query_entities(&ids);
+        assert!(std::rc::Rc::ptr_eq(&cached, &c.query_entities(&ids)));
+
+        c.insert(entity_0, Health(50));
+        assert!(std::rc::Rc::ptr_eq(&cached, &c.query_entities(&ids)));
+
+        c.insert(entity_1, Health(100));
+        assert_eq!(&*c.query_entities(&ids), &[entity_0, entity_1]);
+
+        c.remove::<Health>(entity_0);
+        assert_eq!(&*c.query_entities(&ids), &[entity_1]);
+
+        c.remove_entity(entity_1);
+        assert!(c.query_entities(&ids).is_empty());
+    }
+
+    #[test]
+    pub fn should_log_removed_components() {
+        let mut c = ComponentRegistry::with_capacity(2);
+        let entity_0 = Entity::from_gen_id(1, 0);
+        let entity_1 = Entity::from_gen_id(1, 1);
+
+        c.register::<Health>();
+        c.register::<Mana>();
+
+        c.insert(entity_0, Health(100));
+        c.insert(entity_1, Health(100));
+        c.insert(entity_1, Mana(50));
+
+        c.remove::<Health>(entity_0);
+        c.remove::<Health>(entity_0);
+        c.remove_entity(entity_1);
+
+        assert_eq!(c.removed::<Health>(), &[entity_0, entity_1]);
+        assert_eq!(c.removed::<Mana>(), &[entity_1]);
+        assert!(c.removed::<Name>().is_empty());
+
+        c.clear_removed();
+        assert!(c.removed::<Health>().is_empty());
+    }
+
+    #[derive(Component)]
+    struct Tag<const ROW: usize, const COL: usize>;
+
+    /// Registers a `Tag` for every pair of `rows` and `cols`
+    macro_rules! register_tags {
+        ($c:ident; [$($row:literal)*] $cols:tt) => {
+            $(register_tags!(@row $c; $row $cols);)*
+        };
+        (@row $c:ident; $row:literal [$($col:literal)*]) => {
+            $($c.register::<Tag<$row, $col>>();)*
+        };
+    }
+
+    #[test]
+    pub fn should_query_past_128_components() {
+        let mut c = ComponentRegistry::with_capacity(2);
+        let entity_0 = Entity::from_gen_id(1, 0);
+        let entity_1 = Entity::from_gen_id(1, 1);
+
+        register_tags!(c; [0 1 2 3 4 5 6 7 8 9 10 11 12] [0 1 2 3 4 5 6 7 8 9]);
+        c.register::<Health>();
+        assert_eq!(c.storages.len(), 131);
+
+        c.insert(entity_0, Tag::<12, 9>);
+        c.insert(entity_0, Health(100));
+        c.insert(entity_1, Tag::<0, 0>);
+        c.insert(entity_1, Health(50));
+
+        let ids = [Tag::<12, 9>::resource_id(), Health::resource_id()];
+        assert_eq!(&*c.query_entities(&ids), &[entity_0]);
+
+        let without = [Tag::<12, 9>::resource_id()];
+        assert_eq!(
+            &*c.query_entities_filtered(&[Health::resource_id()], &without),
+            &[entity_1]
+        );
+
+        c.remove::<Tag<12, 9>>(entity_0);
+        assert!(c.query_entities(&ids).is_empty());
+        assert_eq!(c.removed::<Tag<12, 9>>(), &[entity_0]);
+    }
+
+    #[test]
+    pub fn should_derive_storage_without_the_prelude() {
+        assert_eq!(
+            <without_prelude::Velocity as Component>::storage_kind(),
+            StorageKind::Dense
+        );
+    }
+
+    #[test]
+    pub fn should_pack_dense_components() {
+        let mut c = ComponentRegistry::with_capacity(3);
+        let entities = [0, 1, 2].map(|id| Entity::from_gen_id(1, id));
+
+        c.register::<Position>();
+        assert_eq!(c.storage::<Position>().unwrap().kind(), StorageKind::Dense);
+
+        for (i, entity) in entities.iter().enumerate() {
+            c.insert(*entity, Position(i as i32));
+        }
+
+        assert_eq!(c.remove::<Position>(entities[0]), Some(Position(0)));
+        assert!(!c.has_component_for_entity::<Position>(entities[0]));
+        assert_eq!(c.component(entities[2]), Some(&Position(2)));
+
+        c.expand_by(2);
+        let entity_3 = Entity::from_gen_id(1, 3);
+        c.insert(entity_3, Position(3));
+
+        let ComponentStorage::Dense(storage) = c.storage_mut::<Position>().unwrap() else {
+            unreachable!()
+        };
+        assert_eq!(storage.entity_indices(), &[2, 1, 3]);
+
+        let packed = unsafe { storage.iter_mut::<Position>() }
+            .map(|p| p.0)
+            .collect::<Vec<_>>();
+        assert_eq!(packed, [2, 1, 3]);
+    }
 }
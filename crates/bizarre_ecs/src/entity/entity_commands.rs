@@ -6,7 +6,7 @@ use crate::{
     world::World,
 };
 
-use super::Entity;
+use super::{Disabled, Entity, Parent};
 
 pub struct SpawnEntityCmd<T: ComponentBatch> {
     pub components: T,
@@ -78,6 +78,43 @@ impl Command for KillEntity {
     }
 }
 
+/// Spawns a child of `parent` with a [`Parent`] component
+pub struct SpawnChildCmd<T: ComponentBatch> {
+    pub parent: Entity,
+    pub components: T,
+}
+
+impl<T: ComponentBatch> Command for SpawnChildCmd<T> {
+    fn apply(self, world: &mut World) {
+        if world.components.storage::<Parent>().is_none() {
+            world.register_entity_mapper::<Parent>();
+        }
+
+        let child = world.spawn_entity(self.components);
+        world.insert_components(child, Parent(self.parent));
+    }
+}
+
+/// Spawns children inside of [`EntityCmdBuilder::with_children`]
+pub struct ChildSpawner<'a> {
+    parent: Entity,
+    commands: &'a mut CommandBuffer,
+}
+
+impl ChildSpawner<'_> {
+    pub fn parent(&self) -> Entity {
+        self.parent
+    }
+
+    pub fn spawn(&mut self, components: impl ComponentBatch) -> &mut Self {
+        self.commands.push(SpawnChildCmd {
+            parent: self.parent,
+            components,
+        });
+        self
+    }
+}
+
 #[must_use = "`EntityCmdBuilder` won't have any effect unless `build` is called"]
 #[deny(unused)]
 pub struct EntityCmdBuilder<'a, const IS_KILLING: bool> {
@@ -102,12 +139,47 @@ impl<'a> EntityCmdBuilder<'a, false> {
         self
     }
 
-    pub fn remove_components<T: ComponentBatch>(mut self) -> Self {
+    /// Inserts `components` only when `condition` holds, keeps optional parts of
+    /// the entity in the same chain
+    pub fn insert_if<T: ComponentBatch>(self, condition: bool, components: T) -> Self {
+        if condition {
+            self.insert_components(components)
+        } else {
+            self
+        }
+    }
+
+    pub fn remove_components<T: ComponentBatch>(self) -> Self {
+        self.remove_batch::<T>()
+    }
+
+    /// Removes every component of the batch `T` the entity has
+    pub fn remove_batch<T: ComponentBatch>(mut self) -> Self {
         self.inner_cmd
             .push(RemoveComponentsCmd::<T>::new(self.entity));
         self
     }
 
+    /// Skips the entity in queries until it's enabled again, its components are kept
+    pub fn disable(self) -> Self {
+        self.insert_components(Disabled)
+    }
+
+    /// Undoes [`disable`](Self::disable)
+    pub fn enable(self) -> Self {
+        self.remove_components::<Disabled>()
+    }
+
+    /// Spawns children of the entity with a [`Parent`] pointing to it, after the
+    /// commands recorded so far
+    pub fn with_children(mut self, f: impl FnOnce(&mut ChildSpawner)) -> Self {
+        f(&mut ChildSpawner {
+            parent: self.entity,
+            commands: &mut self.inner_cmd,
+        });
+        self
+    }
+
     /// Kills the entity
     ///
     /// If this function is called, builder will produce only one command: [`KillEntityCmd`]. There
@@ -1,14 +1,17 @@
 use std::{
-    collections::VecDeque,
+    collections::{BTreeMap, VecDeque},
     fmt::Debug,
     sync::atomic::{self, AtomicU64},
 };
 
-use crate::query::query_element::QueryData;
+use serde::{Deserialize, Serialize};
+
+use crate::{component::Component, query::query_element::QueryData, resource::Resource};
 
 pub mod entity_commands;
 
-#[derive(PartialEq, Eq, PartialOrd, Ord, Default, Hash, Clone, Copy)]
+#[derive(PartialEq, Eq, PartialOrd, Ord, Default, Hash, Clone, Copy, Serialize, Deserialize)]
+#[serde(transparent)]
 pub struct Entity {
     ///|gen|id |
     ///|---|---|
@@ -83,6 +86,28 @@ impl QueryData for Entity {
 pub struct EntitySpawner {
     pub(crate) next_id: AtomicU64,
     pub(crate) dead: VecDeque<Entity>,
+    /// Highest generation handed out for every index
+    pub(crate) generations: Vec<u16>,
+    /// Indices that reached [`Entity::GEN_MAX`] and are never reused
+    pub(crate) retired: usize,
+    pub(crate) recycled: u64,
+}
+
+/// Entity index usage of a [`World`](crate::world::World)
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct EntityStats {
+    pub live: u64,
+    /// Indices of dead entities waiting to be reused
+    pub free: usize,
+    /// Indices that ran out of generations
+    pub retired: usize,
+    /// Number of indices ever handed out
+    pub high_water: u64,
+    /// Number of entity slots allocated in the component storages
+    pub capacity: usize,
+    /// How many times an index of a dead entity was reused
+    pub recycled: u64,
+    pub max_generation: u16,
 }
 
 impl EntitySpawner {
@@ -93,10 +118,31 @@ impl EntitySpawner {
     pub fn new_entity(&mut self) -> (Entity, bool) {
         if let Some(mut entity) = self.dead.pop_front() {
             entity.set_gen(entity.gen() + 1);
+            self.generations[entity.index()] = entity.gen();
+            self.recycled += 1;
             (entity, true)
         } else {
-            let id = self.next_id.fetch_add(1, atomic::Ordering::SeqCst);
-            (Entity::from_gen_id(1, id), false)
+            let (id, index) = loop {
+                let id = self.next_id.fetch_add(1, atomic::Ordering::SeqCst);
+                let index = id as usize;
+
+                if index >= self.generations.len() {
+                    self.generations.resize(index + 1, 0);
+                }
+
+                // The index may have been used before compaction, skip it if it
+                // ran out of generations there
+                if self.generations[index] < Entity::GEN_MAX {
+                    break (id, index);
+                }
+
+                self.retired += 1;
+            };
+
+            let gen = self.generations[index] + 1;
+            self.generations[index] = gen;
+
+            (Entity::from_gen_id(gen, id), false)
         }
     }
 
@@ -105,6 +151,120 @@ impl EntitySpawner {
             panic!("Trying to kill an `Entity` which is already dead");
         }
 
-        self.dead.push_back(entity)
+        if entity.gen() == Entity::GEN_MAX {
+            self.retired += 1;
+        } else {
+            self.dead.push_back(entity)
+        }
+    }
+
+    pub fn live_count(&self) -> u64 {
+        self.next_id.load(atomic::Ordering::SeqCst) - (self.dead.len() + self.retired) as u64
+    }
+
+    /// Forgets all dead indices after the `live` entities were moved below `end`,
+    /// skipping the retired indices, which stay retired. Returns entities for the
+    /// moved ones with generations which were never handed out for their new indices
+    pub(crate) fn compact(
+        &mut self,
+        end: usize,
+        live: usize,
+        moved: &[(Entity, usize)],
+    ) -> Vec<Entity> {
+        self.dead.clear();
+        self.retired = end - live;
+        self.next_id.store(end as u64, atomic::Ordering::SeqCst);
+
+        moved
+            .iter()
+            .map(|(_, index)| {
+                let gen = self.generations[*index] + 1;
+                self.generations[*index] = gen;
+                Entity::from_gen_id(gen, *index as u64)
+            })
+            .collect()
+    }
+
+    /// Returns `true` if `index` ran out of generations, whether its last entity
+    /// is alive or not
+    pub(crate) fn is_retired(&self, index: usize) -> bool {
+        self.generations
+            .get(index)
+            .is_some_and(|gen| *gen == Entity::GEN_MAX)
+    }
+
+    pub(crate) fn max_generation(&self) -> u16 {
+        self.generations.iter().copied().max().unwrap_or(0)
+    }
+}
+
+/// Old to new entity mapping produced by [`World::compact_entities`](crate::world::World::compact_entities)
+#[derive(Clone, Debug, Default)]
+pub struct EntityRemap {
+    moved: BTreeMap<Entity, Entity>,
+}
+
+impl EntityRemap {
+    pub fn get(&self, entity: Entity) -> Option<Entity> {
+        self.moved.get(&entity).copied()
+    }
+
+    /// New entity for `entity`, or `entity` itself if it was not moved
+    pub fn map(&self, entity: Entity) -> Entity {
+        self.get(entity).unwrap_or(entity)
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.moved.is_empty()
+    }
+
+    pub fn len(&self) -> usize {
+        self.moved.len()
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = (Entity, Entity)> + '_ {
+        self.moved.iter().map(|(old, new)| (*old, *new))
+    }
+}
+
+impl FromIterator<(Entity, Entity)> for EntityRemap {
+    fn from_iter<T: IntoIterator<Item = (Entity, Entity)>>(iter: T) -> Self {
+        Self {
+            moved: iter.into_iter().collect(),
+        }
+    }
+}
+
+/// Components storing [`Entity`] references implement it to keep them valid
+/// after compaction, see [`World::register_entity_mapper`](crate::world::World::register_entity_mapper)
+pub trait MapEntities {
+    fn map_entities(&mut self, remap: &EntityRemap);
+}
+
+/// Entity a child was spawned under with
+/// [`EntityCmdBuilder::with_children`](entity_commands::EntityCmdBuilder::with_children)
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Parent(pub Entity);
+
+impl Resource for Parent {}
+
+impl Component for Parent {}
+
+/// Marks an entity as disabled without despawning it, queries skip disabled
+/// entities unless they ask for `Disabled` or pass
+/// [`IncludeDisabled`](crate::query::query_filter::IncludeDisabled).
+///
+/// Toggled with [`EntityCmdBuilder::disable`](entity_commands::EntityCmdBuilder::disable)
+/// and [`EntityCmdBuilder::enable`](entity_commands::EntityCmdBuilder::enable)
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct Disabled;
+
+impl Resource for Disabled {}
+
+impl Component for Disabled {}
+
+impl MapEntities for Parent {
+    fn map_entities(&mut self, remap: &EntityRemap) {
+        self.0 = remap.map(self.0);
     }
 }
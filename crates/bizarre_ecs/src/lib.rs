@@ -4,9 +4,13 @@
 #![feature(trait_alias)]
 #![feature(type_changing_struct_update)]
 
+// Lets the derive macros name `::bizarre_ecs` paths inside of this crate
+extern crate self as bizarre_ecs;
+
 pub mod commands;
 pub mod component;
 pub mod entity;
+pub mod prefab;
 pub mod query;
 pub mod resource;
 pub mod system;
@@ -14,14 +18,22 @@ pub mod world;
 
 pub mod prelude {
     pub use crate::{
-        component::{component_batch::ComponentBatch, Component, ComponentRegistry},
-        entity::Entity,
-        query::Query,
-        resource::{Resource, ResourceId},
+        component::{component_batch::ComponentBatch, Component, ComponentRegistry, StorageKind},
+        entity::{Disabled, Entity},
+        query::{
+            query_filter::{IncludeDisabled, With, Without},
+            Query,
+        },
+        resource::{shared::Shared, Resource, ResourceId},
         system::{
             local::{FromWorld, Local},
-            system_param::{Res, ResMut},
+            removed_components::RemovedComponents,
+            system_param::{Res, ResMut, SystemParam},
+            system_set::SystemSet,
             IntoSystem, System,
         },
     };
+
+    #[doc(hidden)]
+    pub use crate::system::system_param::derive as __system_param_derive;
 }
@@ -0,0 +1,191 @@
+use std::collections::HashMap;
+
+use bizarre_log::core_error;
+use serde::de::DeserializeOwned;
+use thiserror::Error;
+
+use crate::{
+    commands::Command,
+    component::{component_batch::ComponentBatch, Component},
+    entity::Entity,
+    resource::{Resource, ResourceId},
+    world::World,
+};
+
+#[derive(Debug, Error)]
+pub enum PrefabError {
+    #[error("Failed to parse prefab RON: {0}")]
+    Ron(#[from] ron::error::SpannedError),
+    #[error("Prefab must be a map of component names to values")]
+    NotAMap,
+    #[error("Component `{0}` is not registered for prefabs")]
+    UnknownComponent(String),
+    #[error("Invalid value of component `{name}`: {source}")]
+    InvalidComponent { name: String, source: ron::Error },
+    #[error("There is no prefab named `{0}`")]
+    UnknownPrefab(String),
+    #[error("There is no `Prefabs` resource in the world")]
+    MissingPrefabs,
+}
+
+pub type PrefabResult<T> = Result<T, PrefabError>;
+
+/// Component value of a [`Prefab`], cloned into every instance
+trait PrefabComponent {
+    /// Inserts a copy unless `entity` already has a component of this type
+    fn insert_missing(&self, world: &mut World, entity: Entity);
+}
+
+impl<C: Component + Clone> PrefabComponent for C {
+    fn insert_missing(&self, world: &mut World, entity: Entity) {
+        if world.component::<C>(entity).is_none() {
+            world.insert_components(entity, self.clone());
+        }
+    }
+}
+
+type ComponentDeserializer = fn(ron::Value) -> Result<PrefabEntry, ron::Error>;
+
+type PrefabEntry = (ResourceId, Box<dyn PrefabComponent>);
+
+/// Entity template, a list of component values
+#[derive(Default)]
+pub struct Prefab {
+    components: Vec<PrefabEntry>,
+}
+
+impl Prefab {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Adds `component` to the template, replacing the value of the same type
+    pub fn with<C: Component + Clone>(mut self, component: C) -> Self {
+        self.insert_entry((C::resource_id(), Box::new(component)));
+        self
+    }
+
+    pub fn len(&self) -> usize {
+        self.components.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.components.is_empty()
+    }
+
+    fn insert_entry(&mut self, entry: PrefabEntry) {
+        match self.components.iter_mut().find(|(id, _)| *id == entry.0) {
+            Some(existing) => *existing = entry,
+            None => self.components.push(entry),
+        }
+    }
+
+    /// Spawns a copy of the template, `overrides` replace components of the same type.
+    ///
+    /// Every component is inserted once, the overridden ones aren't copied
+    pub fn instantiate(&self, world: &mut World, overrides: impl ComponentBatch) -> Entity {
+        let entity = world.spawn_entity(overrides);
+
+        for (_, component) in self.components.iter() {
+            component.insert_missing(world, entity);
+        }
+
+        entity
+    }
+}
+
+/// Named prefabs and the components they can be loaded with from RON
+///
+/// A RON prefab is a map of registered component names to their values:
+/// `{ "Health": (100), "Enemy": () }`
+#[derive(Default)]
+pub struct Prefabs {
+    prefabs: HashMap<String, Prefab>,
+    deserializers: HashMap<String, ComponentDeserializer>,
+}
+
+impl Resource for Prefabs {}
+
+impl Prefabs {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Makes `C` loadable from RON under its type name without the module path
+    pub fn register_component<C: Component + Clone + DeserializeOwned>(&mut self) -> &mut Self {
+        let name = C::resource_name().rsplit("::").next().unwrap_or_default();
+        self.register_component_as::<C>(name)
+    }
+
+    pub fn register_component_as<C: Component + Clone + DeserializeOwned>(
+        &mut self,
+        name: impl Into<String>,
+    ) -> &mut Self {
+        self.deserializers.insert(name.into(), |value| {
+            let component = value.into_rust::<C>()?;
+            Ok((C::resource_id(), Box::new(component)))
+        });
+        self
+    }
+
+    pub fn insert(&mut self, name: impl Into<String>, prefab: Prefab) -> Option<Prefab> {
+        self.prefabs.insert(name.into(), prefab)
+    }
+
+    pub fn remove(&mut self, name: &str) -> Option<Prefab> {
+        self.prefabs.remove(name)
+    }
+
+    pub fn get(&self, name: &str) -> Option<&Prefab> {
+        self.prefabs.get(name)
+    }
+
+    /// Parses a prefab with registered components and stores it under `name`
+    pub fn load_ron(&mut self, name: impl Into<String>, source: &str) -> PrefabResult<()> {
+        let prefab = self.parse_ron(source)?;
+        self.prefabs.insert(name.into(), prefab);
+
+        Ok(())
+    }
+
+    pub fn parse_ron(&self, source: &str) -> PrefabResult<Prefab> {
+        let ron::Value::Map(map) = ron::from_str::<ron::Value>(source)? else {
+            return Err(PrefabError::NotAMap);
+        };
+
+        let mut prefab = Prefab::new();
+
+        for (key, value) in map.into_iter() {
+            let ron::Value::String(name) = key else {
+                return Err(PrefabError::NotAMap);
+            };
+
+            let deserializer = self
+                .deserializers
+                .get(&name)
+                .ok_or_else(|| PrefabError::UnknownComponent(name.clone()))?;
+
+            let entry = deserializer(value)
+                .map_err(|source| PrefabError::InvalidComponent { name, source })?;
+
+            prefab.insert_entry(entry);
+        }
+
+        Ok(prefab)
+    }
+}
+
+/// Spawns an instance of a named prefab from [`Prefabs`], a missing prefab is
+/// logged and nothing is spawned
+pub struct SpawnPrefabCmd<T: ComponentBatch> {
+    pub name: String,
+    pub overrides: T,
+}
+
+impl<T: ComponentBatch> Command for SpawnPrefabCmd<T> {
+    fn apply(self, world: &mut World) {
+        if let Err(err) = world.spawn_prefab(&self.name, self.overrides) {
+            core_error!("Failed to spawn a prefab: {err}");
+        }
+    }
+}
@@ -0,0 +1,168 @@
+use std::marker::PhantomData;
+
+use bizarre_utils::mass_impl;
+
+use crate::{
+    component::{Component, ComponentStorage},
+    entity::Entity,
+    world::unsafe_world_cell::UnsafeWorldCell,
+};
+
+use super::query_element::QueryData;
+
+/// Query data that can be fetched as slices of components lying next to each
+/// other in their storages, see [`Query::iter_chunks`](super::Query::iter_chunks)
+pub trait ChunkData: QueryData {
+    type Chunk<'w>;
+
+    /// Appends the storage slot of every component of `entity` the data reads
+    ///
+    /// # Safety
+    ///
+    /// `entity` must have every component of the data
+    unsafe fn slots(world: UnsafeWorldCell, entity: Entity, slots: &mut Vec<usize>);
+
+    /// Components of `len` entities with consecutive slots, starting at `entity`
+    ///
+    /// # Safety
+    ///
+    /// The slots must hold components of matched entities no other chunk or
+    /// item borrows mutably
+    unsafe fn get_chunk(world: UnsafeWorldCell, entity: Entity, len: usize) -> Self::Chunk<'_>;
+}
+
+unsafe fn storage<T: Component>(world: UnsafeWorldCell) -> &ComponentStorage {
+    unsafe { world.unsafe_world() }
+        .components
+        .storage::<T>()
+        .unwrap_or_else(|| panic!("{} is not registered", T::resource_name()))
+}
+
+unsafe fn slot<T: Component>(world: UnsafeWorldCell, entity: Entity) -> usize {
+    storage::<T>(world)
+        .slot(entity.index())
+        .unwrap_or_else(|| panic!("Failed to get {} for {entity:?}", T::resource_name()))
+}
+
+impl<T> ChunkData for &T
+where
+    T: Component,
+{
+    type Chunk<'w> = &'w [T];
+
+    unsafe fn slots(world: UnsafeWorldCell, entity: Entity, slots: &mut Vec<usize>) {
+        slots.push(slot::<T>(world, entity));
+    }
+
+    unsafe fn get_chunk(world: UnsafeWorldCell, entity: Entity, len: usize) -> Self::Chunk<'_> {
+        storage::<T>(world).slots_mut(slot::<T>(world, entity), len)
+    }
+}
+
+impl<T> ChunkData for &mut T
+where
+    T: Component,
+{
+    type Chunk<'w> = &'w mut [T];
+
+    unsafe fn slots(world: UnsafeWorldCell, entity: Entity, slots: &mut Vec<usize>) {
+        slots.push(slot::<T>(world, entity));
+    }
+
+    unsafe fn get_chunk(world: UnsafeWorldCell, entity: Entity, len: usize) -> Self::Chunk<'_> {
+        storage::<T>(world).slots_mut(slot::<T>(world, entity), len)
+    }
+}
+
+macro_rules! impl_chunk_data {
+    ($(#[$meta:meta])*; $($el:tt),+) => {
+        $(#[$meta])*
+        #[allow(non_snake_case)]
+        impl<$($el),+> ChunkData for ($($el,)+)
+        where
+            $($el: ChunkData),+
+        {
+            type Chunk<'w> = ($($el::Chunk<'w>,)+);
+
+            unsafe fn slots(world: UnsafeWorldCell, entity: Entity, slots: &mut Vec<usize>) {
+                $($el::slots(world, entity, slots);)+
+            }
+
+            unsafe fn get_chunk(
+                world: UnsafeWorldCell,
+                entity: Entity,
+                len: usize,
+            ) -> Self::Chunk<'_> {
+                ($($el::get_chunk(world, entity, len),)+)
+            }
+        }
+    };
+}
+
+mass_impl!(impl_chunk_data, 16, D; doc_hidden);
+
+/// Runs of matched entities whose components are contiguous in every storage,
+/// created with [`Query::iter_chunks`](super::Query::iter_chunks)
+pub struct QueryChunks<'q, D: ChunkData> {
+    world: UnsafeWorldCell<'q>,
+    /// First entity and length of every chunk
+    chunks: Vec<(Entity, usize)>,
+    index: usize,
+    _phantom: PhantomData<D>,
+}
+
+impl<'q, D: ChunkData> QueryChunks<'q, D> {
+    pub(crate) fn new(world: UnsafeWorldCell<'q>, entities: &[Entity]) -> Self {
+        let mut slots = Vec::new();
+        let mut matched = entities
+            .iter()
+            .map(|entity| {
+                slots.clear();
+                unsafe { D::slots(world, *entity, &mut slots) };
+                (slots.clone(), *entity)
+            })
+            .collect::<Vec<_>>();
+
+        matched.sort_unstable();
+
+        let mut chunks: Vec<(Entity, usize)> = Vec::new();
+        let mut previous: Option<&[usize]> = None;
+
+        for (slots, entity) in &matched {
+            let continues = previous.is_some_and(|previous| {
+                previous
+                    .iter()
+                    .zip(slots.iter())
+                    .all(|(previous, slot)| previous + 1 == *slot)
+            });
+
+            match chunks.last_mut() {
+                Some((_, len)) if continues => *len += 1,
+                _ => chunks.push((*entity, 1)),
+            }
+
+            previous = Some(slots);
+        }
+
+        Self {
+            world,
+            chunks,
+            index: 0,
+            _phantom: PhantomData,
+        }
+    }
+
+    pub fn chunk_count(&self) -> usize {
+        self.chunks.len()
+    }
+}
+
+impl<'q, D: ChunkData> Iterator for QueryChunks<'q, D> {
+    type Item = D::Chunk<'q>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let (entity, len) = *self.chunks.get(self.index)?;
+        self.index += 1;
+        Some(unsafe { D::get_chunk(self.world, entity, len) })
+    }
+}
@@ -1,22 +1,29 @@
-use std::marker::PhantomData;
+use std::{marker::PhantomData, rc::Rc};
 
+use chunks::{ChunkData, QueryChunks};
+use par_iter::QueryParIter;
 use query_element::QueryData;
+use query_filter::QueryFilter;
 
 use crate::{
-    entity::Entity,
-    system::{system_param::SystemParam, WorldAccess},
+    entity::{Disabled, Entity},
+    resource::Resource,
+    system::{functional_system::get_internal_conflicts, system_param::SystemParam, WorldAccess},
     world::{unsafe_world_cell::UnsafeWorldCell, World},
 };
 
+pub mod chunks;
+pub mod par_iter;
 pub mod query_element;
+pub mod query_filter;
 
 #[derive(Clone)]
-pub struct Query<'q, D: QueryData> {
+pub struct Query<'q, D: QueryData, F: QueryFilter = ()> {
     world: UnsafeWorldCell<'q>,
-    _phantom: PhantomData<D>,
+    _phantom: PhantomData<(D, F)>,
 }
 
-impl<'q, D: QueryData> Query<'q, D> {
+impl<'q, D: QueryData, F: QueryFilter> Query<'q, D, F> {
     pub fn new(world: &'q World) -> Self {
         let world = unsafe { world.as_unsafe_cell() };
 
@@ -25,10 +32,74 @@ impl<'q, D: QueryData> Query<'q, D> {
             _phantom: PhantomData,
         }
     }
+
+    /// Splits matched entities into batches processed on the thread pool
+    ///
+    /// # Panics
+    ///
+    /// If `D` accesses a component mutably more than once
+    pub fn par_iter(self) -> QueryParIter<'q, D> {
+        assert_no_internal_conflicts::<D>("iterated in parallel");
+
+        QueryParIter::new(self.world, self.entities())
+    }
+
+    /// Iterates runs of matched entities whose components lie next to each other
+    /// in every storage, yielding a slice of each component per run.
+    ///
+    /// Dense storages pack components in their own order, so runs are longest
+    /// when the queried components are inserted and removed together
+    ///
+    /// # Panics
+    ///
+    /// If `D` accesses a component mutably more than once
+    pub fn iter_chunks(self) -> QueryChunks<'q, D>
+    where
+        D: ChunkData,
+    {
+        assert_no_internal_conflicts::<D>("iterated in chunks");
+
+        QueryChunks::new(self.world, &self.entities())
+    }
+
+    fn entities(&self) -> Rc<[Entity]> {
+        matched_entities::<D, F>(self.world)
+    }
 }
 
-impl<'q, D: QueryData> SystemParam for Query<'q, D> {
-    type Item<'w, 's> = Query<'w, D>;
+/// Entities having the components of `D` and passing `F`. Disabled ones are
+/// skipped unless the query asks for [`Disabled`] or includes them with
+/// [`IncludeDisabled`](query_filter::IncludeDisabled)
+fn matched_entities<D: QueryData, F: QueryFilter>(world: UnsafeWorldCell) -> Rc<[Entity]> {
+    let mut ids = D::resource_ids();
+    ids.extend(F::with_ids());
+
+    let mut without = F::without_ids();
+    let disabled = Disabled::resource_id();
+
+    if !F::includes_disabled() && !ids.contains(&disabled) {
+        without.push(disabled);
+    }
+
+    world.query_entities_filtered(&ids, &without)
+}
+
+/// Mutable items of `D` would alias if it accessed a component mutably twice
+pub(crate) fn assert_no_internal_conflicts<D: QueryData>(operation: &str) {
+    let mut access = D::query_access();
+    access.sort();
+
+    if let Some(conflicts) = get_internal_conflicts(&access) {
+        panic!(
+            "Query {} can't be {operation}, it accesses {}",
+            std::any::type_name::<D>(),
+            conflicts.join(", ")
+        );
+    }
+}
+
+impl<'q, D: QueryData, F: QueryFilter> SystemParam for Query<'q, D, F> {
+    type Item<'w, 's> = Query<'w, D, F>;
 
     type State = ();
 
@@ -52,29 +123,35 @@ impl<'q, D: QueryData> SystemParam for Query<'q, D> {
     }
 }
 
-impl<'q, D: QueryData> IntoIterator for Query<'q, D> {
+impl<'q, D: QueryData, F: QueryFilter> IntoIterator for Query<'q, D, F> {
     type Item = D::Item<'q>;
 
     type IntoIter = QueryIterator<'q, D>;
 
     fn into_iter(self) -> Self::IntoIter {
-        QueryIterator {
-            world: self.world,
-            entities: self.world.filter_entities(D::resource_ids().as_slice()),
-            index: 0,
-            _phantom: PhantomData,
-        }
+        QueryIterator::new::<F>(self.world)
     }
 }
 
 #[derive(Clone)]
 pub struct QueryIterator<'q, D: QueryData> {
     world: UnsafeWorldCell<'q>,
-    entities: Vec<Entity>,
+    entities: Rc<[Entity]>,
     index: usize,
     _phantom: PhantomData<D>,
 }
 
+impl<'q, D: QueryData> QueryIterator<'q, D> {
+    pub(crate) fn new<F: QueryFilter>(world: UnsafeWorldCell<'q>) -> Self {
+        Self {
+            world,
+            entities: matched_entities::<D, F>(world),
+            index: 0,
+            _phantom: PhantomData,
+        }
+    }
+}
+
 impl<'a, D: QueryData> Iterator for QueryIterator<'a, D> {
     type Item = D::Item<'a>;
 
@@ -0,0 +1,74 @@
+use std::{marker::PhantomData, rc::Rc};
+
+use rayon::{current_num_threads, prelude::*};
+
+use crate::{entity::Entity, world::unsafe_world_cell::UnsafeWorldCell};
+
+use super::query_element::QueryData;
+
+/// Batches per pool thread when the batch size isn't set, leaves room for
+/// work stealing when items take uneven time
+const BATCHES_PER_THREAD: usize = 4;
+
+/// Runs a closure over entities matched by a [`Query`](super::Query) in batches on
+/// the rayon thread pool, created with [`Query::par_iter`](super::Query::par_iter)
+pub struct QueryParIter<'q, D: QueryData> {
+    world: UnsafeWorldCell<'q>,
+    entities: Rc<[Entity]>,
+    batch_size: Option<usize>,
+    _phantom: PhantomData<D>,
+}
+
+/// Every entity is visited by exactly one batch, so items of different threads
+/// never alias and the query access was checked for internal conflicts
+struct SharedWorld<'q>(UnsafeWorldCell<'q>);
+
+unsafe impl Send for SharedWorld<'_> {}
+unsafe impl Sync for SharedWorld<'_> {}
+
+impl<'q, D: QueryData> QueryParIter<'q, D> {
+    pub(crate) fn new(world: UnsafeWorldCell<'q>, entities: Rc<[Entity]>) -> Self {
+        Self {
+            world,
+            entities,
+            batch_size: None,
+            _phantom: PhantomData,
+        }
+    }
+
+    /// Number of entities processed by a single task
+    pub fn with_batch_size(mut self, batch_size: usize) -> Self {
+        self.batch_size = Some(batch_size.max(1));
+        self
+    }
+
+    pub fn for_each<F>(self, func: F)
+    where
+        F: Fn(D::Item<'q>) + Send + Sync,
+        D::Item<'q>: Send,
+    {
+        let batch_size = self.batch_size.unwrap_or_else(|| {
+            self.entities
+                .len()
+                .div_ceil(current_num_threads() * BATCHES_PER_THREAD)
+                .max(1)
+        });
+
+        if unsafe { self.world.unsafe_world() }.is_deterministic() {
+            for entity in self.entities.iter() {
+                func(unsafe { D::get_item(self.world, *entity) });
+            }
+
+            return;
+        }
+
+        let world = SharedWorld(self.world);
+        let world = &world;
+
+        self.entities.par_chunks(batch_size).for_each(|batch| {
+            for entity in batch {
+                func(unsafe { D::get_item(world.0, *entity) });
+            }
+        });
+    }
+}
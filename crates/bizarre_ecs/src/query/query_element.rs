@@ -66,8 +66,33 @@ where
     }
 }
 
+/// Matches entities with and without `T`, yielding `None` for the latter
+impl<T> QueryData for Option<&T>
+where
+    T: Component,
+{
+    type Item<'w> = Option<&'w T>;
+
+    fn resource_ids() -> Vec<ResourceId> {
+        vec![]
+    }
+
+    unsafe fn get_item(world: UnsafeWorldCell, entity: Entity) -> Self::Item<'_> {
+        world.component(entity)
+    }
+
+    fn query_access() -> Vec<WorldAccess> {
+        vec![WorldAccess {
+            resource_id: T::resource_id(),
+            resource_name: T::resource_name(),
+            access_type: WorldAccessType::CompRead,
+        }]
+    }
+}
+
 macro_rules! impl_query_data {
-    ($($el:tt),+) => {
+    ($(#[$meta:meta])*; $($el:tt),+) => {
+        $(#[$meta])*
         #[allow(non_snake_case)]
         impl<$($el),+> QueryData for ($($el,)+)
         where
@@ -94,4 +119,4 @@ macro_rules! impl_query_data {
     };
 }
 
-mass_impl!(impl_query_data, 16, D);
+mass_impl!(impl_query_data, 16, D; doc_hidden);
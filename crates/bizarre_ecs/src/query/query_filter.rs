@@ -0,0 +1,94 @@
+use std::marker::PhantomData;
+
+use bizarre_utils::mass_impl;
+
+use crate::{component::Component, resource::ResourceId};
+
+/// Narrows down the entities of a query without accessing their components
+pub trait QueryFilter {
+    /// Components an entity must have
+    fn with_ids() -> Vec<ResourceId>;
+    /// Components an entity must not have
+    fn without_ids() -> Vec<ResourceId>;
+    /// Disabled entities are matched too, see [`Disabled`](crate::entity::Disabled)
+    fn includes_disabled() -> bool {
+        false
+    }
+}
+
+/// Matches entities having `T`
+pub struct With<T: Component>(PhantomData<T>);
+
+/// Matches entities not having `T`
+pub struct Without<T: Component>(PhantomData<T>);
+
+/// Matches disabled entities along with the enabled ones
+pub struct IncludeDisabled;
+
+impl QueryFilter for () {
+    fn with_ids() -> Vec<ResourceId> {
+        vec![]
+    }
+
+    fn without_ids() -> Vec<ResourceId> {
+        vec![]
+    }
+}
+
+impl<T: Component> QueryFilter for With<T> {
+    fn with_ids() -> Vec<ResourceId> {
+        vec![T::resource_id()]
+    }
+
+    fn without_ids() -> Vec<ResourceId> {
+        vec![]
+    }
+}
+
+impl<T: Component> QueryFilter for Without<T> {
+    fn with_ids() -> Vec<ResourceId> {
+        vec![]
+    }
+
+    fn without_ids() -> Vec<ResourceId> {
+        vec![T::resource_id()]
+    }
+}
+
+impl QueryFilter for IncludeDisabled {
+    fn with_ids() -> Vec<ResourceId> {
+        vec![]
+    }
+
+    fn without_ids() -> Vec<ResourceId> {
+        vec![]
+    }
+
+    fn includes_disabled() -> bool {
+        true
+    }
+}
+
+macro_rules! impl_query_filter {
+    ($(#[$meta:meta])*; $($el:tt),+) => {
+        $(#[$meta])*
+        impl<$($el),+> QueryFilter for ($($el,)+)
+        where
+            $($el: QueryFilter),+
+        {
+            fn with_ids() -> Vec<ResourceId> {
+                vec![$($el::with_ids()),+].into_iter().flatten().collect()
+            }
+
+            fn without_ids() -> Vec<ResourceId> {
+                vec![$($el::without_ids()),+].into_iter().flatten().collect()
+            }
+
+            fn includes_disabled() -> bool {
+                $($el::includes_disabled())||+
+            }
+        }
+    };
+}
+
+mass_impl!(impl_query_filter, 16, F; doc_hidden);
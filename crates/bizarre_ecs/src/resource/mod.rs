@@ -8,6 +8,7 @@ use std::{
 pub use bizarre_ecs_proc_macro::Resource;
 
 pub mod resource_commands;
+pub mod shared;
 
 #[derive(Clone, Copy, Debug, Hash, PartialEq, Eq, PartialOrd, Ord)]
 pub struct ResourceId(TypeId);
@@ -47,6 +48,7 @@ impl ResourceMeta {
 pub struct StoredResource {
     pub(crate) id: ResourceId,
     pub(crate) name: &'static str,
+    pub(crate) size: usize,
     pub(crate) data: NonNull<u8>,
     pub(crate) drop_fn: unsafe fn(NonNull<u8>),
 }
@@ -58,12 +60,16 @@ impl StoredResource {
 
     pub unsafe fn from_meta_and_data(meta: ResourceMeta, data: NonNull<u8>) -> Self {
         let ResourceMeta {
-            name, id, drop_fn, ..
+            name,
+            id,
+            size,
+            drop_fn,
         } = meta;
 
         Self {
             name,
             id,
+            size,
             data,
             drop_fn,
         }
@@ -105,6 +111,7 @@ impl<T: Resource> IntoStored for T {
         StoredResource {
             id: T::resource_id(),
             name: T::resource_name(),
+            size: size_of::<T>(),
             data: unsafe { NonNull::new_unchecked(Box::into_raw(Box::new(self)).cast()) },
             drop_fn: |ptr| {
                 let ptr = ptr.cast::<T>();
@@ -0,0 +1,64 @@
+use std::{fmt, ops::Deref, sync::Arc};
+
+use super::Resource;
+
+/// Read-only resource backed by an [`Arc`], so the same data can be inserted into
+/// several worlds or handed to other threads without cloning it
+///
+/// Derefs to `T`, systems read it through `Res<Shared<T>>`. Cloning only bumps
+/// the reference count
+pub struct Shared<T: ?Sized + 'static>(Arc<T>);
+
+impl<T: ?Sized + 'static> Resource for Shared<T> {}
+
+impl<T: 'static> Shared<T> {
+    pub fn new(value: T) -> Self {
+        Self(Arc::new(value))
+    }
+}
+
+impl<T: ?Sized + 'static> Shared<T> {
+    pub fn from_arc(arc: Arc<T>) -> Self {
+        Self(arc)
+    }
+
+    /// The shared pointer, for handing the data over to something outside of the world
+    pub fn arc(&self) -> &Arc<T> {
+        &self.0
+    }
+
+    pub fn into_arc(self) -> Arc<T> {
+        self.0
+    }
+
+    /// Whether both point to the same allocation
+    pub fn ptr_eq(&self, other: &Self) -> bool {
+        Arc::ptr_eq(&self.0, &other.0)
+    }
+}
+
+impl<T: ?Sized + 'static> Clone for Shared<T> {
+    fn clone(&self) -> Self {
+        Self(Arc::clone(&self.0))
+    }
+}
+
+impl<T: ?Sized + 'static> Deref for Shared<T> {
+    type Target = T;
+
+    fn deref(&self) -> &Self::Target {
+        &self.0
+    }
+}
+
+impl<T: ?Sized + 'static> From<Arc<T>> for Shared<T> {
+    fn from(arc: Arc<T>) -> Self {
+        Self(arc)
+    }
+}
+
+impl<T: ?Sized + fmt::Debug + 'static> fmt::Debug for Shared<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_tuple("Shared").field(&&*self.0).finish()
+    }
+}
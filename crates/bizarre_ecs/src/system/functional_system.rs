@@ -107,8 +107,7 @@ where
     }
 }
 
-#[cfg(debug_assertions)]
-fn get_internal_conflicts(access: &[WorldAccess]) -> Option<Vec<String>> {
+pub(crate) fn get_internal_conflicts(access: &[WorldAccess]) -> Option<Vec<String>> {
     let internal_conflicts = access
         .chunk_by(|a, b| {
             a.resource_id == b.resource_id
@@ -148,7 +147,8 @@ fn get_internal_conflicts(access: &[WorldAccess]) -> Option<Vec<String>> {
 }
 
 macro_rules! impl_fn_sys {
-    ($($param:tt),+) => {
+    ($(#[$meta:meta])*; $($param:tt),+) => {
+        $(#[$meta])*
         #[allow(non_snake_case)]
         impl<$($param,)+ F> FnSys<fn($($param),+)> for F
         where
@@ -171,19 +171,38 @@ macro_rules! impl_fn_sys {
     };
 }
 
-mass_impl!(impl_fn_sys, 16, F);
+mass_impl!(impl_fn_sys, 16, F; doc_hidden);
 
 #[cfg(test)]
 mod tests {
     use crate::{
         prelude::*,
         query::Query,
-        system::system_param::{Res, ResMut},
+        system::{
+            local::Local,
+            schedule::Schedule,
+            system_param::{Res, ResMut, SystemParam},
+            WorldAccessType,
+        },
+        world::World,
     };
 
     #[derive(Resource)]
     struct Res1;
 
+    #[derive(Resource, Default)]
+    struct Counter(u32);
+
+    #[derive(SystemParam)]
+    struct CounterParams<'w, 's> {
+        counter: ResMut<'w, Counter>,
+        calls: Local<'s, u32>,
+    }
+
+    #[allow(dead_code)]
+    #[derive(SystemParam)]
+    struct MixedParams<'w>(Res<'w, Res1>, ResMut<'w, Counter>);
+
     #[derive(Component)]
     struct Comp1;
 
@@ -195,6 +214,11 @@ mod tests {
 
     fn mut_and_ref_query(_: Query<(&mut Comp1, &Comp1)>) {}
 
+    fn count_calls(mut params: CounterParams) {
+        *params.calls += 1;
+        params.counter.0 = *params.calls;
+    }
+
     #[test]
     #[should_panic]
     fn should_panic_on_multiple_mutable_access() {
@@ -218,4 +242,29 @@ mod tests {
     fn should_panic_on_mut_and_ref_query_access() {
         mut_and_ref_query.into_system();
     }
+
+    #[test]
+    fn should_combine_derived_param_access() {
+        let access = MixedParams::param_access();
+
+        assert_eq!(access.len(), 2);
+        assert_eq!(access[0].resource_id, Res1::resource_id());
+        assert_eq!(access[0].access_type, WorldAccessType::ResRead);
+        assert_eq!(access[1].resource_id, Counter::resource_id());
+        assert_eq!(access[1].access_type, WorldAccessType::ResWrite);
+    }
+
+    #[test]
+    fn should_run_systems_with_derived_params() {
+        let mut world = World::new();
+        world.insert_resource(Counter::default());
+        world.add_schedule(Schedule::Update);
+        world.add_systems(Schedule::Update, count_calls);
+        world.init_schedule(Schedule::Update);
+
+        world.run_schedule(Schedule::Update);
+        world.run_schedule(Schedule::Update);
+
+        assert_eq!(world.resource::<Counter>().unwrap().0, 2);
+    }
 }
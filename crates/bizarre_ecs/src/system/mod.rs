@@ -11,11 +11,13 @@ use crate::{
 
 pub mod functional_system;
 pub mod local;
+pub mod removed_components;
 pub mod schedule;
 pub mod system_commands;
 pub mod system_config;
 pub mod system_graph;
 pub mod system_param;
+pub mod system_set;
 
 bitflags! {
     #[derive(PartialEq, Eq, Clone, Copy, Debug, PartialOrd, Ord)]
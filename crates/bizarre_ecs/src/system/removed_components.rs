@@ -0,0 +1,66 @@
+use std::{marker::PhantomData, slice};
+
+use crate::{component::Component, entity::Entity, world::unsafe_world_cell::UnsafeWorldCell};
+
+use super::{system_param::SystemParam, WorldAccess};
+
+/// Entities the component `T` was removed from, killed entities included.
+///
+/// Removals are logged by the [`World`](crate::world::World) and cleared after
+/// every schedule run, so removals made by commands are seen by the systems of
+/// the next schedule
+pub struct RemovedComponents<'w, T: Component> {
+    entities: &'w [Entity],
+    _phantom: PhantomData<T>,
+}
+
+impl<'w, T: Component> RemovedComponents<'w, T> {
+    pub fn iter(&self) -> slice::Iter<'w, Entity> {
+        self.entities.iter()
+    }
+
+    pub fn len(&self) -> usize {
+        self.entities.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entities.is_empty()
+    }
+}
+
+impl<'w, T: Component> IntoIterator for &RemovedComponents<'w, T> {
+    type Item = &'w Entity;
+
+    type IntoIter = slice::Iter<'w, Entity>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.iter()
+    }
+}
+
+impl<T: Component> SystemParam for RemovedComponents<'_, T> {
+    type Item<'w, 's> = RemovedComponents<'w, T>;
+
+    type State = ();
+
+    unsafe fn init(_: UnsafeWorldCell) -> Self::State {}
+
+    unsafe fn get_item<'w, 's>(
+        world: UnsafeWorldCell<'w>,
+        _: &'s mut Self::State,
+    ) -> Self::Item<'w, 's>
+    where
+        Self: Sized,
+    {
+        RemovedComponents {
+            entities: world.removed::<T>(),
+            _phantom: PhantomData,
+        }
+    }
+
+    // The logs only change on structural changes, which can't happen while
+    // systems are running
+    fn param_access() -> Vec<WorldAccess> {
+        vec![]
+    }
+}
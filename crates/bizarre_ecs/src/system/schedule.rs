@@ -1,3 +1,11 @@
+use std::{
+    any::TypeId,
+    collections::BTreeSet,
+    hash::{DefaultHasher, Hash, Hasher},
+};
+
+use crate::prelude::Resource;
+
 #[derive(Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Debug)]
 pub enum Schedule {
     /// Should be called once before first `Preupdate`
@@ -6,6 +14,83 @@ pub enum Schedule {
     Preupdate,
     /// Should be called every frame
     Update,
+    /// Runs when a state machine enters the state. Created the first time
+    /// systems are added to it
+    OnEnter(StateLabel),
+    /// Runs when a state machine leaves the state. Created the first time
+    /// systems are added to it
+    OnExit(StateLabel),
+}
+
+impl Schedule {
+    /// `OnEnter` and `OnExit` schedules are created on demand
+    pub fn is_state_transition(&self) -> bool {
+        matches!(self, Self::OnEnter(_) | Self::OnExit(_))
+    }
+}
+
+/// Identifies a single value of a state type for [`Schedule::OnEnter`] and [`Schedule::OnExit`]
+#[derive(Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Debug)]
+pub struct StateLabel {
+    type_id: TypeId,
+    value: u64,
+}
+
+impl StateLabel {
+    pub fn new<S: Hash + 'static>(state: &S) -> Self {
+        let mut hasher = DefaultHasher::new();
+        state.hash(&mut hasher);
+
+        Self {
+            type_id: TypeId::of::<S>(),
+            value: hasher.finish(),
+        }
+    }
+}
+
+/// Schedules and system labels paused at runtime, consulted by
+/// [`World::run_schedule`](crate::world::World::run_schedule).
+///
+/// Systems get labels with [`IntoSystemConfigs::label`](super::system_config::IntoSystemConfigs::label),
+/// a system is skipped if any of its labels is paused
+#[derive(Resource, Default, Debug, Clone)]
+pub struct ScheduleControl {
+    paused_schedules: BTreeSet<Schedule>,
+    paused_labels: BTreeSet<&'static str>,
+}
+
+impl ScheduleControl {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn pause(&mut self, schedule: Schedule) {
+        self.paused_schedules.insert(schedule);
+    }
+
+    pub fn resume(&mut self, schedule: Schedule) {
+        self.paused_schedules.remove(&schedule);
+    }
+
+    pub fn is_paused(&self, schedule: Schedule) -> bool {
+        self.paused_schedules.contains(&schedule)
+    }
+
+    pub fn pause_label(&mut self, label: &'static str) {
+        self.paused_labels.insert(label);
+    }
+
+    pub fn resume_label(&mut self, label: &'static str) {
+        self.paused_labels.remove(label);
+    }
+
+    pub fn is_label_paused(&self, label: &str) -> bool {
+        self.paused_labels.contains(label)
+    }
+
+    pub(crate) fn paused_labels(&self) -> &BTreeSet<&'static str> {
+        &self.paused_labels
+    }
 }
 
 pub struct Schedule2 {}
@@ -1,14 +1,23 @@
-use std::{any::type_name, fmt::Debug};
+use std::{any::type_name, fmt::Debug, rc::Rc};
 
 use bizarre_utils::mass_impl;
 
-use super::{IntoSystem, System, WorldAccess};
+use crate::world::World;
+
+use super::{
+    system_set::{RunCondition, SystemSet},
+    IntoSystem, System, WorldAccess,
+};
 
 #[derive(Debug, Clone)]
 pub struct SystemMeta {
     pub(crate) name: &'static str,
     pub(crate) before: Vec<&'static str>,
     pub(crate) after: Vec<&'static str>,
+    /// Labels the system can be paused by with a `ScheduleControl`
+    pub(crate) labels: Vec<&'static str>,
+    /// Names of the [`SystemSet`]s the system is in
+    pub(crate) sets: Vec<&'static str>,
     pub(crate) access: Box<[WorldAccess]>,
 }
 
@@ -19,13 +28,22 @@ impl SystemMeta {
             access: T::System::access(),
             before: Default::default(),
             after: Default::default(),
+            labels: Default::default(),
+            sets: Default::default(),
         }
     }
+
+    /// Whether `name` refers to the system itself or to one of its sets
+    pub(crate) fn is_named(&self, name: &str) -> bool {
+        self.name == name || self.sets.contains(&name)
+    }
 }
 
 pub struct SystemConfig {
     pub meta: SystemMeta,
     pub system: Box<dyn System>,
+    /// The system is skipped unless all of them hold
+    pub conditions: Vec<RunCondition>,
 }
 
 impl Debug for SystemConfig {
@@ -33,6 +51,7 @@ impl Debug for SystemConfig {
         f.debug_struct("SystemConfig")
             .field("meta", &self.meta)
             .field("system", &"BoxedSystem")
+            .field("conditions", &self.conditions.len())
             .finish()
     }
 }
@@ -64,6 +83,29 @@ impl SystemConfigs {
             SystemConfigs::Configs(confs) => confs.iter_mut().for_each(|c| c.before_inner(names)),
         }
     }
+
+    pub fn label_inner(&mut self, label: &'static str) {
+        match self {
+            SystemConfigs::Config(conf) => conf.meta.labels.push(label),
+            SystemConfigs::Configs(confs) => confs.iter_mut().for_each(|c| c.label_inner(label)),
+        }
+    }
+
+    pub fn in_set_inner(&mut self, set: &'static str) {
+        match self {
+            SystemConfigs::Config(conf) => conf.meta.sets.push(set),
+            SystemConfigs::Configs(confs) => confs.iter_mut().for_each(|c| c.in_set_inner(set)),
+        }
+    }
+
+    pub fn run_if_inner(&mut self, condition: &RunCondition) {
+        match self {
+            SystemConfigs::Config(conf) => conf.conditions.push(condition.clone()),
+            SystemConfigs::Configs(confs) => {
+                confs.iter_mut().for_each(|c| c.run_if_inner(condition))
+            }
+        }
+    }
 }
 
 pub trait IntoSystemConfigs<Marker>
@@ -89,6 +131,30 @@ where
     fn before<M>(self, other: impl IntoSystemConfigs<M>) -> SystemConfigs {
         self.into_system_configs().before(other)
     }
+
+    /// Labels the systems so they can be paused together with a
+    /// [`ScheduleControl`](super::schedule::ScheduleControl)
+    fn label(self, label: &'static str) -> SystemConfigs {
+        let mut configs = self.into_system_configs();
+        configs.label_inner(label);
+        configs
+    }
+
+    /// Puts the systems into `set`, they are ordered and run conditionally with
+    /// the set configured by [`World::configure_sets`]
+    fn in_set(self, set: impl SystemSet) -> SystemConfigs {
+        let mut configs = self.into_system_configs();
+        configs.in_set_inner(set.name());
+        configs
+    }
+
+    /// The systems run only while `condition` returns `true`, it's checked before
+    /// every run of each system
+    fn run_if(self, condition: impl Fn(&World) -> bool + 'static) -> SystemConfigs {
+        let mut configs = self.into_system_configs();
+        configs.run_if_inner(&(Rc::new(condition) as RunCondition));
+        configs
+    }
 }
 
 impl IntoSystemConfigs<()> for SystemConfig {
@@ -123,12 +189,14 @@ where
         SystemConfigs::Config(SystemConfig {
             meta: SystemMeta::new::<M, T>(),
             system: Box::new(self.into_system()),
+            conditions: Vec::new(),
         })
     }
 }
 
 macro_rules! impl_into_system_configs {
-    ($(($config:tt, $marker:tt)),+) => {
+    ($(#[$meta:meta])*; $(($config:tt, $marker:tt)),+) => {
+        $(#[$meta])*
         #[allow(non_snake_case)]
         impl<$($config: IntoSystemConfigs<$marker>, $marker),+> IntoSystemConfigs<($($marker,)+)> for ($($config,)+) {
             fn into_system_configs(self) -> SystemConfigs {
@@ -141,4 +209,4 @@ macro_rules! impl_into_system_configs {
     };
 }
 
-mass_impl!(impl_into_system_configs, 16, C, M);
+mass_impl!(impl_into_system_configs, 16, C, M; doc_hidden);
@@ -1,13 +1,24 @@
+use std::{
+    cmp::Reverse,
+    collections::{BTreeMap, BinaryHeap},
+    time::{Duration, Instant},
+};
+
 use petgraph::{
     algo::toposort,
     data::FromElements,
     graph::{DiGraph, NodeIndex},
+    Direction,
 };
 use thiserror::Error;
 
 use crate::{commands::command_buffer::CommandBuffer, world::World};
 
-use super::system_config::{IntoSystemConfigs, SystemConfig, SystemConfigs, SystemMeta};
+use super::{
+    schedule::ScheduleControl,
+    system_config::{IntoSystemConfigs, SystemConfig, SystemConfigs, SystemMeta},
+    system_set::{IntoSystemSetConfigs, SystemSetConfig},
+};
 
 #[derive(Debug, Error)]
 pub enum SystemGraphError {
@@ -28,7 +39,35 @@ pub type DependencyGraph = DiGraph<NodeId, (), usize>;
 
 pub struct SystemGraph {
     systems: Vec<SystemConfig>,
+    sets: BTreeMap<&'static str, SystemSetConfig>,
     cached_toposort: Option<Vec<usize>>,
+    /// The cached order was sorted for a deterministic world
+    deterministic: bool,
+    stats: ScheduleStats,
+}
+
+/// Timings of the last run of a schedule
+#[derive(Clone, Debug, Default)]
+pub struct ScheduleStats {
+    pub total: Duration,
+    /// In the order the systems ran
+    pub systems: Vec<SystemTiming>,
+}
+
+#[derive(Clone, Copy, Debug)]
+pub struct SystemTiming {
+    pub name: &'static str,
+    pub duration: Duration,
+}
+
+impl ScheduleStats {
+    /// Up to `count` systems that took the longest, slowest first
+    pub fn slowest(&self, count: usize) -> Vec<SystemTiming> {
+        let mut systems = self.systems.clone();
+        systems.sort_by(|a, b| b.duration.cmp(&a.duration));
+        systems.truncate(count);
+        systems
+    }
 }
 
 fn root_system() {}
@@ -43,12 +82,16 @@ impl SystemGraph {
             };
         Self {
             systems: vec![root_system_config],
+            sets: BTreeMap::new(),
             cached_toposort: None,
+            deterministic: false,
+            stats: ScheduleStats::default(),
         }
     }
 
     pub fn add_systems<M>(&mut self, systems: impl IntoSystemConfigs<M>) {
         let systems = systems.into_system_configs();
+        self.cached_toposort = None;
 
         match systems {
             SystemConfigs::Config(config) => self.systems.push(config),
@@ -58,48 +101,133 @@ impl SystemGraph {
         }
     }
 
+    /// Orders sets and adds run conditions to them, configuring a set several
+    /// times adds up the configurations
+    pub fn configure_sets<M>(&mut self, sets: impl IntoSystemSetConfigs<M>) {
+        for config in sets.into_set_configs().0 {
+            match self.sets.get_mut(config.name) {
+                Some(existing) => existing.merge(config),
+                None => {
+                    self.sets.insert(config.name, config);
+                }
+            }
+        }
+
+        self.cached_toposort = None;
+    }
+
     pub fn init_systems(&mut self, world: &mut World) {
         self.systems
             .iter_mut()
             .filter(|s| !s.system.is_init())
             .for_each(|s| s.system.init(unsafe { world.as_unsafe_cell() }));
 
-        if self.cached_toposort.is_none() {
-            let toposort = build_dependency_graph(&self.systems)
-                .1
-                .into_iter()
-                .map(|index| index.index())
-                .collect();
+        let deterministic = world.is_deterministic();
 
-            self.cached_toposort = Some(toposort);
+        if self.cached_toposort.is_none() || self.deterministic != deterministic {
+            let (dag, toposort) = build_dependency_graph(&self.systems, &self.sets);
+
+            let toposort = if deterministic {
+                stable_toposort(&dag)
+            } else {
+                toposort
+            };
+
+            self.cached_toposort = Some(toposort.into_iter().map(|index| index.index()).collect());
+            self.deterministic = deterministic;
         }
     }
 
     pub fn run_systems(&mut self, world: &mut World) -> CommandBuffer {
-        if let Some(toposort) = self.cached_toposort.as_ref() {
-            toposort
-                .iter()
-                .map(|i| &raw mut self.systems[*i].system)
-                .filter_map(|s| {
-                    let system = unsafe { &mut **s };
-                    if system.is_init() {
-                        system.run(unsafe { world.as_unsafe_cell() });
-                        system.take_deferred()
-                    } else {
-                        None
-                    }
-                })
-                .fold(CommandBuffer::new(), |mut acc, mut curr| {
-                    acc.append(&mut curr);
-                    acc
-                })
-        } else {
+        let Some(toposort) = self.cached_toposort.as_ref() else {
             panic!("Trying to execute system graph without initializing systems in it!");
+        };
+
+        let paused_labels = world
+            .resource::<ScheduleControl>()
+            .map(|control| control.paused_labels().clone())
+            .unwrap_or_default();
+
+        let run_start = Instant::now();
+        let mut timings = Vec::with_capacity(toposort.len());
+        let mut commands = CommandBuffer::new();
+        // Conditions of sets are checked once per run
+        let mut set_conditions = BTreeMap::<&'static str, bool>::new();
+
+        for &index in toposort {
+            let SystemConfig {
+                meta,
+                system,
+                conditions,
+            } = &mut self.systems[index];
+
+            if !system.is_init() || meta.labels.iter().any(|l| paused_labels.contains(l)) {
+                continue;
+            }
+
+            let sets_hold = meta.sets.iter().all(|set| {
+                *set_conditions.entry(set).or_insert_with(|| {
+                    self.sets.get(set).is_none_or(|config| {
+                        config.conditions.iter().all(|condition| condition(world))
+                    })
+                })
+            });
+
+            if !sets_hold || !conditions.iter().all(|condition| condition(world)) {
+                continue;
+            }
+
+            let system_start = Instant::now();
+            system.run(unsafe { world.as_unsafe_cell() });
+
+            // The root system is only there to anchor the graph
+            if index != 0 {
+                timings.push(SystemTiming {
+                    name: meta.name,
+                    duration: system_start.elapsed(),
+                });
+            }
+
+            if let Some(mut deferred) = system.take_deferred() {
+                commands.append(&mut deferred);
+            }
         }
+
+        self.stats = ScheduleStats {
+            total: run_start.elapsed(),
+            systems: timings,
+        };
+
+        commands
+    }
+
+    /// `false` until the systems added last are initialized
+    pub fn is_initialized(&self) -> bool {
+        self.cached_toposort.is_some()
+    }
+
+    /// Names of the systems in execution order, or in the order they were added
+    /// if the graph isn't initialized
+    pub fn system_names(&self) -> Vec<&'static str> {
+        let order = match &self.cached_toposort {
+            Some(toposort) => toposort.clone(),
+            None => (0..self.systems.len()).collect(),
+        };
+
+        // The root system is only there to anchor the graph
+        order
+            .into_iter()
+            .filter(|index| *index != 0)
+            .map(|index| self.systems[index].meta.name)
+            .collect()
+    }
+
+    pub fn stats(&self) -> &ScheduleStats {
+        &self.stats
     }
 
     pub fn dependency_graph(&self) -> (DependencyGraph, Vec<NodeIndex<usize>>) {
-        build_dependency_graph(&self.systems)
+        build_dependency_graph(&self.systems, &self.sets)
     }
 }
 
@@ -118,14 +246,31 @@ impl From<NodeId> for NodeIndex<usize> {
     }
 }
 
+/// Ordering of the sets of the system becomes its own, `before` and `after` may
+/// name either systems or sets
+fn with_set_dependencies(
+    mut meta: SystemMeta,
+    sets: &BTreeMap<&'static str, SystemSetConfig>,
+) -> SystemMeta {
+    for set in meta.sets.clone() {
+        if let Some(config) = sets.get(set) {
+            meta.before.extend(&config.before);
+            meta.after.extend(&config.after);
+        }
+    }
+
+    meta
+}
+
 fn build_dependency_graph(
     systems: &[SystemConfig],
+    sets: &BTreeMap<&'static str, SystemSetConfig>,
 ) -> (DiGraph<NodeId, (), usize>, Vec<NodeIndex<usize>>) {
     let mut en = systems
         .iter()
         .filter_map(|sys| {
             if sys.system.is_init() {
-                Some(sys.meta.clone())
+                Some(with_set_dependencies(sys.meta.clone(), sets))
             } else {
                 None
             }
@@ -152,9 +297,11 @@ fn build_dependency_graph(
             skipped_root
                 .clone()
                 .filter_map(|(n, dep_meta)| {
-                    if dep_meta.before.contains(&meta.name) {
+                    if n == i {
+                        None
+                    } else if dep_meta.before.iter().any(|name| meta.is_named(name)) {
                         Some((NodeId(n, dep_meta.name), NodeId(i, meta.name)))
-                    } else if dep_meta.after.contains(&meta.name) {
+                    } else if dep_meta.after.iter().any(|name| meta.is_named(name)) {
                         Some((NodeId(i, meta.name), NodeId(n, dep_meta.name)))
                     } else {
                         None
@@ -195,3 +342,36 @@ fn build_dependency_graph(
 
     (dag, toposort)
 }
+
+/// Topological order of `dag` that doesn't depend on the order systems were
+/// added in: out of the systems ready to run the one with the smallest name goes
+/// first, systems with the same name keep the order they were added in
+fn stable_toposort(dag: &DependencyGraph) -> Vec<NodeIndex<usize>> {
+    let mut in_degrees = dag
+        .node_indices()
+        .map(|node| dag.neighbors_directed(node, Direction::Incoming).count())
+        .collect::<Vec<_>>();
+
+    let mut ready = dag
+        .node_indices()
+        .filter(|node| in_degrees[node.index()] == 0)
+        .map(|node| Reverse((dag[node].1, dag[node].0)))
+        .collect::<BinaryHeap<_>>();
+
+    let mut order = Vec::with_capacity(dag.node_count());
+
+    while let Some(Reverse((_, index))) = ready.pop() {
+        let node = NodeIndex::new(index);
+        order.push(node);
+
+        for next in dag.neighbors_directed(node, Direction::Outgoing) {
+            in_degrees[next.index()] -= 1;
+
+            if in_degrees[next.index()] == 0 {
+                ready.push(Reverse((dag[next].1, dag[next].0)));
+            }
+        }
+    }
+
+    order
+}
@@ -14,6 +14,19 @@ use crate::{
 
 use super::WorldAccess;
 
+pub use bizarre_ecs_proc_macro::SystemParam;
+
+/// Items used by `#[derive(SystemParam)]`, re-exported by the prelude
+#[doc(hidden)]
+pub mod derive {
+    pub use crate::{
+        commands::command_buffer::CommandBuffer, system::WorldAccess,
+        world::unsafe_world_cell::UnsafeWorldCell,
+    };
+
+    pub use super::SystemParam;
+}
+
 pub trait SystemParam {
     type Item<'w, 's>;
     type State;
@@ -160,7 +173,8 @@ impl<T: Resource> DerefMut for ResMut<'_, T> {
 }
 
 macro_rules! impl_system_param {
-    ($($param:tt),+) => {
+    ($(#[$meta:meta])*; $($param:tt),+) => {
+        $(#[$meta])*
         #[allow(non_snake_case)]
         impl<$($param),+> SystemParam for ($($param,)+)
         where
@@ -206,4 +220,4 @@ macro_rules! impl_system_param {
     };
 }
 
-mass_impl!(impl_system_param, 16, F);
+mass_impl!(impl_system_param, 16, F; doc_hidden);
@@ -0,0 +1,141 @@
+use std::{any::type_name, fmt::Debug, rc::Rc};
+
+use bizarre_utils::mass_impl;
+
+use crate::world::World;
+
+pub use bizarre_ecs_proc_macro::SystemSet;
+
+/// Named group of systems, usually a unit struct deriving `SystemSet`.
+///
+/// Systems join a set with [`IntoSystemConfigs::in_set`](super::system_config::IntoSystemConfigs::in_set),
+/// sets are ordered and get run conditions with [`World::configure_sets`], so
+/// modules can be ordered against each other without knowing their systems
+pub trait SystemSet: 'static {
+    fn name(&self) -> &'static str {
+        type_name::<Self>()
+    }
+}
+
+/// Skips the system or the set for a run of the schedule when it returns `false`
+pub type RunCondition = Rc<dyn Fn(&World) -> bool>;
+
+/// `true` while the world has the resource `R`
+pub fn resource_exists<R: crate::prelude::Resource>() -> impl Fn(&World) -> bool {
+    |world| world.resource::<R>().is_some()
+}
+
+#[derive(Clone, Default)]
+pub struct SystemSetConfig {
+    pub(crate) name: &'static str,
+    /// Names of the sets the systems of this set run before
+    pub(crate) before: Vec<&'static str>,
+    pub(crate) after: Vec<&'static str>,
+    pub(crate) conditions: Vec<RunCondition>,
+}
+
+impl SystemSetConfig {
+    pub fn new(set: impl SystemSet) -> Self {
+        Self {
+            name: set.name(),
+            ..Default::default()
+        }
+    }
+
+    /// Adds the ordering and the conditions of `other` configuring the same set
+    pub(crate) fn merge(&mut self, other: SystemSetConfig) {
+        self.before.extend(other.before);
+        self.after.extend(other.after);
+        self.conditions.extend(other.conditions);
+    }
+}
+
+impl Debug for SystemSetConfig {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("SystemSetConfig")
+            .field("name", &self.name)
+            .field("before", &self.before)
+            .field("after", &self.after)
+            .field("conditions", &self.conditions.len())
+            .finish()
+    }
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct SystemSetConfigs(pub(crate) Vec<SystemSetConfig>);
+
+pub trait IntoSystemSetConfigs<Marker>
+where
+    Self: Sized,
+{
+    fn into_set_configs(self) -> SystemSetConfigs;
+
+    /// Systems of the sets run before the systems of `set`
+    fn before(self, set: impl SystemSet) -> SystemSetConfigs {
+        let mut configs = self.into_set_configs();
+        let name = set.name();
+        configs.0.iter_mut().for_each(|c| c.before.push(name));
+        configs
+    }
+
+    /// Systems of the sets run after the systems of `set`
+    fn after(self, set: impl SystemSet) -> SystemSetConfigs {
+        let mut configs = self.into_set_configs();
+        let name = set.name();
+        configs.0.iter_mut().for_each(|c| c.after.push(name));
+        configs
+    }
+
+    /// Systems of the sets run only while `condition` returns `true`, it's checked
+    /// once per run of the schedule
+    fn run_if(self, condition: impl Fn(&World) -> bool + 'static) -> SystemSetConfigs {
+        let mut configs = self.into_set_configs();
+        let condition: RunCondition = Rc::new(condition);
+        configs
+            .0
+            .iter_mut()
+            .for_each(|c| c.conditions.push(condition.clone()));
+        configs
+    }
+
+    /// Orders the sets one after another in the order they are listed
+    fn chain(self) -> SystemSetConfigs {
+        let mut configs = self.into_set_configs();
+        let names = configs.0.iter().map(|c| c.name).collect::<Vec<_>>();
+
+        for (config, previous) in configs.0.iter_mut().skip(1).zip(names) {
+            config.after.push(previous);
+        }
+
+        configs
+    }
+}
+
+impl<S: SystemSet> IntoSystemSetConfigs<()> for S {
+    fn into_set_configs(self) -> SystemSetConfigs {
+        SystemSetConfigs(vec![SystemSetConfig::new(self)])
+    }
+}
+
+impl IntoSystemSetConfigs<()> for SystemSetConfigs {
+    fn into_set_configs(self) -> SystemSetConfigs {
+        self
+    }
+}
+
+macro_rules! impl_into_system_set_configs {
+    ($(#[$meta:meta])*; $(($config:tt, $marker:tt)),+) => {
+        $(#[$meta])*
+        #[allow(non_snake_case)]
+        impl<$($config: IntoSystemSetConfigs<$marker>, $marker),+> IntoSystemSetConfigs<($($marker,)+)> for ($($config,)+) {
+            fn into_set_configs(self) -> SystemSetConfigs {
+                let ($($config,)+) = self;
+                SystemSetConfigs(
+                    [$($config.into_set_configs().0),+].concat()
+                )
+            }
+        }
+    };
+}
+
+mass_impl!(impl_into_system_set_configs, 16, C, M; doc_hidden);
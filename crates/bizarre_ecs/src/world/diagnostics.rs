@@ -0,0 +1,193 @@
+use std::fmt::{self, Display};
+
+use crate::{component::StorageKind, entity::EntityStats, system::schedule::Schedule};
+
+use super::World;
+
+/// State of a [`World`] for bug reports, see [`World::debug_dump`]
+#[derive(Clone, Debug)]
+pub struct WorldDump {
+    pub entities: EntityStats,
+    /// Sorted by name
+    pub resources: Vec<ResourceDump>,
+    /// Sorted by name
+    pub components: Vec<ComponentDump>,
+    pub schedules: Vec<ScheduleDump>,
+    /// Commands queued into the world and not applied yet
+    pub pending_commands: usize,
+}
+
+#[derive(Clone, Debug)]
+pub struct ResourceDump {
+    pub name: &'static str,
+    /// Size of the value in bytes, without the memory it owns
+    pub size: usize,
+}
+
+#[derive(Clone, Debug)]
+pub struct ComponentDump {
+    pub name: &'static str,
+    pub storage: StorageKind,
+    /// Live entities with the component
+    pub count: usize,
+    /// Entity slots of the storage
+    pub capacity: usize,
+}
+
+#[derive(Clone, Debug)]
+pub struct ScheduleDump {
+    pub schedule: Schedule,
+    /// Systems added since the last run are not ordered yet
+    pub initialized: bool,
+    /// In execution order if the schedule is initialized
+    pub systems: Vec<&'static str>,
+}
+
+impl World {
+    /// Collects resources, component storages, schedules and pending commands of
+    /// the world. The dump is printable with `{}`
+    pub fn debug_dump(&self) -> WorldDump {
+        let mut resources = self
+            .resources
+            .values()
+            .map(|resource| ResourceDump {
+                name: resource.name,
+                size: resource.size,
+            })
+            .collect::<Vec<_>>();
+        resources.sort_by_key(|resource| resource.name);
+
+        let capacity = self.components.capacity();
+        let mut components = self
+            .components
+            .storage_infos()
+            .into_iter()
+            .map(|(name, storage, count)| ComponentDump {
+                name,
+                storage,
+                count,
+                capacity,
+            })
+            .collect::<Vec<_>>();
+        components.sort_by_key(|component| component.name);
+
+        let mut schedules = self
+            .schedules
+            .iter()
+            .map(|(schedule, graph)| ScheduleDump {
+                schedule: *schedule,
+                initialized: graph.is_initialized(),
+                systems: graph.system_names(),
+            })
+            .collect::<Vec<_>>();
+        schedules.sort_by_key(|schedule| schedule.schedule);
+
+        WorldDump {
+            entities: self.entity_stats(),
+            resources,
+            components,
+            schedules,
+            pending_commands: unsafe { self.deferred_commands.len() },
+        }
+    }
+}
+
+impl Display for WorldDump {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let EntityStats {
+            live,
+            free,
+            capacity,
+            ..
+        } = self.entities;
+
+        writeln!(f, "Entities: {live} live, {free} free, {capacity} slots")?;
+
+        writeln!(f, "Resources ({}):", self.resources.len())?;
+        for ResourceDump { name, size } in &self.resources {
+            writeln!(f, "  {name} ({size} bytes)")?;
+        }
+
+        writeln!(f, "Components ({}):", self.components.len())?;
+        for component in &self.components {
+            writeln!(
+                f,
+                "  {} [{:?}]: {}/{}",
+                component.name, component.storage, component.count, component.capacity
+            )?;
+        }
+
+        writeln!(f, "Schedules ({}):", self.schedules.len())?;
+        for schedule in &self.schedules {
+            let state = if schedule.initialized {
+                ""
+            } else {
+                ", not initialized"
+            };
+
+            writeln!(
+                f,
+                "  {:?} ({} systems{state}):",
+                schedule.schedule,
+                schedule.systems.len()
+            )?;
+
+            for (index, system) in schedule.systems.iter().enumerate() {
+                writeln!(f, "    {index}. {system}")?;
+            }
+        }
+
+        write!(f, "Pending commands: {}", self.pending_commands)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::{prelude::*, system::schedule::Schedule, world::World};
+
+    #[derive(Component)]
+    struct Health;
+
+    #[allow(dead_code)]
+    #[derive(Resource)]
+    struct Score(u64);
+
+    fn tick() {}
+
+    #[test]
+    fn should_dump_world_state() {
+        let mut world = World::new();
+        world.register_component::<Health>();
+        world.spawn_entity(Health);
+        world.create_entity();
+        world.insert_resource(Score(0));
+        world.add_schedule(Schedule::Update);
+        world.add_systems(Schedule::Update, tick);
+
+        let dump = world.debug_dump();
+
+        assert_eq!(dump.entities.live, 2);
+        assert!(dump
+            .resources
+            .iter()
+            .any(|resource| resource.name.ends_with("Score") && resource.size == 8));
+
+        let health = dump
+            .components
+            .iter()
+            .find(|component| component.name.ends_with("Health"))
+            .unwrap();
+        assert_eq!(health.count, 1);
+
+        let update = dump
+            .schedules
+            .iter()
+            .find(|schedule| schedule.schedule == Schedule::Update)
+            .unwrap();
+        assert!(!update.initialized);
+        assert_eq!(update.systems.len(), 1);
+        assert!(update.systems[0].ends_with("tick"));
+
+        assert!(dump.to_string().contains("Pending commands: 0"));
+    }
+}
@@ -0,0 +1,111 @@
+use std::{
+    collections::hash_map::Entry,
+    sync::mpsc::{self, Receiver, TryRecvError},
+};
+
+use crate::entity::EntityRemap;
+
+use super::World;
+
+/// What [`World::merge`] does with a resource present in both worlds
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum ResourceConflict {
+    /// The resource of the merged world is dropped
+    #[default]
+    KeepExisting,
+    /// The resource of the merged world replaces the existing one
+    Replace,
+}
+
+impl World {
+    /// Moves all entities, components and resources of `other` into this world.
+    ///
+    /// Every entity of `other` gets a new entity here, the returned [`EntityRemap`]
+    /// maps the old entities to the new ones. Entity references in components of
+    /// types registered with [`register_entity_mapper`](Self::register_entity_mapper)
+    /// on `other` are remapped. Schedules of `other` are dropped, its teardowns are
+    /// added to this world
+    pub fn merge(&mut self, mut other: World, on_conflict: ResourceConflict) -> EntityRemap {
+        self.flush();
+        other.flush();
+
+        let remap = other
+            .components
+            .live_entities()
+            .into_iter()
+            .map(|entity| (entity, self.create_entity()))
+            .collect::<EntityRemap>();
+
+        for mapper in other.entity_mappers.iter() {
+            mapper(&mut other.components, &remap);
+        }
+
+        let movers = other.components.movers();
+
+        for (entity, target) in remap.iter() {
+            for mover in movers.iter() {
+                mover(&mut other.components, entity, &mut self.components, target);
+            }
+        }
+
+        for (id, resource) in other.resources.drain() {
+            match (self.resources.entry(id), on_conflict) {
+                (Entry::Vacant(entry), _) => {
+                    entry.insert(resource);
+                }
+                (Entry::Occupied(mut entry), ResourceConflict::Replace) => {
+                    entry.insert(resource);
+                }
+                (Entry::Occupied(_), ResourceConflict::KeepExisting) => (),
+            }
+        }
+
+        self.teardowns.append(&mut other.teardowns);
+
+        remap
+    }
+}
+
+/// World wrapper allowed to cross threads, see [`load_world`]
+struct DetachedWorld(World);
+
+unsafe impl Send for DetachedWorld {}
+
+/// World being built by [`load_world`]
+pub struct PendingWorld {
+    receiver: Receiver<DetachedWorld>,
+}
+
+impl PendingWorld {
+    /// Takes the world if it's built. Returns `None` while it's still being built
+    /// and after it was taken
+    pub fn try_take(&self) -> Option<World> {
+        match self.receiver.try_recv() {
+            Ok(DetachedWorld(world)) => Some(world),
+            Err(TryRecvError::Empty | TryRecvError::Disconnected) => None,
+        }
+    }
+
+    /// Blocks until the world is built. Returns `None` if `build` panicked
+    pub fn wait(self) -> Option<World> {
+        self.receiver.recv().ok().map(|DetachedWorld(world)| world)
+    }
+}
+
+/// Builds a new world with `build` on the rayon thread pool, merge it with
+/// [`World::merge`] once it's ready
+///
+/// # Safety
+///
+/// Every component and resource `build` inserts into the world must be `Send`
+pub unsafe fn load_world(build: impl FnOnce(&mut World) + Send + 'static) -> PendingWorld {
+    let (sender, receiver) = mpsc::sync_channel(1);
+
+    rayon::spawn(move || {
+        let mut world = World::new();
+        build(&mut world);
+        let _ = sender.send(DetachedWorld(world));
+    });
+
+    PendingWorld { receiver }
+}
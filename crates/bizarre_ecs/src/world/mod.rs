@@ -1,4 +1,7 @@
-use std::{collections::HashMap, sync::atomic::Ordering};
+use std::{
+    collections::HashMap,
+    sync::{atomic::Ordering, Arc},
+};
 
 use ecs_module::EcsModule;
 use unsafe_world_cell::UnsafeWorldCell;
@@ -6,12 +9,26 @@ use unsafe_world_cell::UnsafeWorldCell;
 use crate::{
     commands::command_buffer::RawCommandBuffer,
     component::{component_batch::ComponentBatch, Component, ComponentRegistry},
-    entity::{Entity, EntitySpawner},
-    resource::{IntoStored, Resource, ResourceId, StoredResource},
-    system::{schedule::Schedule, system_config::IntoSystemConfigs, system_graph::SystemGraph},
+    entity::{Entity, EntityRemap, EntitySpawner, EntityStats, MapEntities},
+    prefab::{PrefabError, PrefabResult, Prefabs},
+    query::{
+        assert_no_internal_conflicts, query_element::QueryData, query_filter::QueryFilter,
+        QueryIterator,
+    },
+    resource::{shared::Shared, IntoStored, Resource, ResourceId, StoredResource},
+    system::{
+        schedule::{Schedule, ScheduleControl},
+        system_config::IntoSystemConfigs,
+        system_graph::{ScheduleStats, SystemGraph},
+        system_set::IntoSystemSetConfigs,
+    },
 };
 
+pub mod diagnostics;
 pub mod ecs_module;
+pub mod merge;
+#[cfg(feature = "snapshots")]
+pub mod snapshot;
 pub mod unsafe_world_cell;
 
 #[derive(Default)]
@@ -21,6 +38,9 @@ pub struct World {
     pub(crate) spawner: EntitySpawner,
     pub(crate) schedules: HashMap<Schedule, SystemGraph>,
     pub(crate) deferred_commands: RawCommandBuffer,
+    pub(crate) entity_mappers: Vec<fn(&mut ComponentRegistry, &EntityRemap)>,
+    pub(crate) teardowns: Vec<fn(&mut World)>,
+    pub(crate) deterministic: bool,
 }
 
 impl World {
@@ -28,10 +48,25 @@ impl World {
         Self::default()
     }
 
+    /// Makes runs of the world repeatable for lockstep networking and replays.
+    ///
+    /// Systems not ordered against each other run sorted by their names instead
+    /// of the order they were added in, and [`par_iter`](crate::query::Query::par_iter)
+    /// visits entities on the calling thread in the order queries iterate them.
+    /// Takes effect on the next [`init_schedule`](Self::init_schedule)
+    pub fn set_deterministic(&mut self, deterministic: bool) {
+        self.deterministic = deterministic;
+    }
+
+    pub fn is_deterministic(&self) -> bool {
+        self.deterministic
+    }
+
     pub fn create_entity(&mut self) -> Entity {
-        let (entity, reused) = self.spawner.new_entity();
-        if !reused {
-            self.components.expand()
+        let (entity, _) = self.spawner.new_entity();
+        let capacity = self.components.capacity();
+        if entity.index() >= capacity {
+            self.components.expand_by(entity.index() + 1 - capacity)
         }
         self.components.register_entity(entity);
         entity
@@ -45,6 +80,22 @@ impl World {
         entity
     }
 
+    /// Spawns an instance of the prefab named `name` from the [`Prefabs`]
+    /// resource, `overrides` replace its components of the same type
+    pub fn spawn_prefab(
+        &mut self,
+        name: &str,
+        overrides: impl ComponentBatch,
+    ) -> PrefabResult<Entity> {
+        self.resource_scope(|world, prefabs: &mut Prefabs| {
+            prefabs
+                .get(name)
+                .map(|prefab| prefab.instantiate(world, overrides))
+                .ok_or_else(|| PrefabError::UnknownPrefab(name.into()))
+        })
+        .unwrap_or(Err(PrefabError::MissingPrefabs))
+    }
+
     pub fn kill(&mut self, entity: Entity) {
         self.spawner.kill(entity);
         self.components.remove_entity(entity);
@@ -55,6 +106,17 @@ impl World {
             .insert(R::resource_id(), resource.into_stored());
     }
 
+    /// Inserts `value` as a [`Shared<T>`] resource without cloning the data, the
+    /// same `Arc` can be inserted into other worlds
+    pub fn insert_shared<T: ?Sized + 'static>(&mut self, value: Arc<T>) {
+        self.insert_resource(Shared::from_arc(value))
+    }
+
+    /// Clones the [`Shared<T>`] resource, cheap since only the reference count changes
+    pub fn shared<T: ?Sized + 'static>(&self) -> Option<Shared<T>> {
+        self.resource::<Shared<T>>().cloned()
+    }
+
     pub fn remove_resource<R: Resource>(&mut self) -> Option<R> {
         self.resources
             .remove(&R::resource_id())
@@ -73,6 +135,24 @@ impl World {
             .map(|r| unsafe { r.as_mut() })
     }
 
+    /// Temporarily removes the resource `R` from the world and runs `f` with
+    /// both of them, so the resource can be mutated while the world is accessed.
+    ///
+    /// The resource is put back after `f` returns, `R` is not available inside of
+    /// `f` and inserting it there is overwritten. Returns `None` if there's no `R`
+    pub fn resource_scope<R: Resource, T>(
+        &mut self,
+        f: impl FnOnce(&mut World, &mut R) -> T,
+    ) -> Option<T> {
+        let mut stored = self.resources.remove(&R::resource_id())?;
+
+        let result = f(self, unsafe { stored.as_mut() });
+
+        self.resources.insert(R::resource_id(), stored);
+
+        Some(result)
+    }
+
     pub fn register_component<C: Component>(&mut self) {
         self.components.register::<C>()
     }
@@ -105,6 +185,27 @@ impl World {
         self.components.remove_batch::<C>(entity)
     }
 
+    /// Entities `C` was removed from since the end of the last schedule run
+    pub fn removed<C: Component>(&self) -> &[Entity] {
+        self.components.removed::<C>()
+    }
+
+    /// Iterates over all entities matching `D`, for use outside of systems
+    pub fn query<D: QueryData>(&mut self) -> QueryIterator<'_, D> {
+        self.query_filtered::<D, ()>()
+    }
+
+    /// Iterates over all entities matching `D` and passing the filter `F`
+    ///
+    /// # Panics
+    ///
+    /// If `D` accesses a component mutably more than once
+    pub fn query_filtered<D: QueryData, F: QueryFilter>(&mut self) -> QueryIterator<'_, D> {
+        assert_no_internal_conflicts::<D>("queried");
+
+        QueryIterator::new::<F>(UnsafeWorldCell::new(self))
+    }
+
     pub fn add_schedule(&mut self, schedule: Schedule) {
         if self.schedules.contains_key(&schedule) {
             panic!("Trying to insert a `Schedule` {schedule:?} while there is already one in this world");
@@ -113,19 +214,40 @@ impl World {
         self.schedules.insert(schedule, SystemGraph::new());
     }
 
+    /// Timings of the last run of `schedule`
+    pub fn schedule_stats(&self, schedule: Schedule) -> Option<&ScheduleStats> {
+        self.schedules.get(&schedule).map(SystemGraph::stats)
+    }
+
+    pub fn has_schedule(&self, schedule: Schedule) -> bool {
+        self.schedules.contains_key(&schedule)
+    }
+
     pub fn init_schedule(&mut self, schedule: Schedule) {
         self.flush();
 
         self.with_schedule(schedule, |world, sg| sg.init_systems(world));
     }
 
+    /// Runs the systems of `schedule` unless it's paused in the [`ScheduleControl`]
+    /// resource, systems with paused labels are skipped. Component removal logs
+    /// are cleared after the run
     pub fn run_schedule(&mut self, schedule: Schedule) {
         self.flush();
 
+        if self
+            .resource::<ScheduleControl>()
+            .is_some_and(|control| control.is_paused(schedule))
+        {
+            return;
+        }
+
         let mut cmd = self.with_schedule(schedule, |world, sg| sg.run_systems(world));
         if !cmd.is_empty() {
             unsafe { self.deferred_commands.append(&mut cmd.as_raw()) }
         }
+
+        self.components.clear_removed();
     }
 
     fn with_schedule<T, F>(&mut self, schedule: Schedule, func: F) -> T
@@ -153,10 +275,36 @@ impl World {
         }
     }
 
-    pub fn add_systems<M>(&mut self, schedule: Schedule, systems: impl IntoSystemConfigs<M>) {
+    /// Adds systems to `schedule`, [`OnEnter`](Schedule::OnEnter) and
+    /// [`OnExit`](Schedule::OnExit) schedules are created if they don't exist yet
+    pub fn add_systems<M>(
+        &mut self,
+        schedule: impl Into<Schedule>,
+        systems: impl IntoSystemConfigs<M>,
+    ) {
+        let schedule = schedule.into();
+
+        if schedule.is_state_transition() && !self.has_schedule(schedule) {
+            self.add_schedule(schedule);
+        }
+
         self.with_schedule(schedule, |_, sg| sg.add_systems(systems));
     }
 
+    pub fn configure_sets<M>(
+        &mut self,
+        schedule: impl Into<Schedule>,
+        sets: impl IntoSystemSetConfigs<M>,
+    ) {
+        let schedule = schedule.into();
+
+        if schedule.is_state_transition() && !self.has_schedule(schedule) {
+            self.add_schedule(schedule);
+        }
+
+        self.with_schedule(schedule, |_, sg| sg.configure_sets(sets));
+    }
+
     pub fn add_module(&mut self, module: impl EcsModule) {
         module.apply(self);
     }
@@ -166,10 +314,81 @@ impl World {
     }
 
     pub fn entity_count(&self) -> u64 {
-        self.spawner.next_id.load(Ordering::SeqCst) - self.spawner.dead.len() as u64
+        self.spawner.live_count()
+    }
+
+    pub fn entity_stats(&self) -> EntityStats {
+        EntityStats {
+            live: self.spawner.live_count(),
+            free: self.spawner.dead.len(),
+            retired: self.spawner.retired,
+            high_water: self.spawner.next_id.load(Ordering::SeqCst),
+            capacity: self.components.capacity(),
+            recycled: self.spawner.recycled,
+            max_generation: self.spawner.max_generation(),
+        }
+    }
+
+    /// Makes [`compact_entities`](Self::compact_entities) update entity references
+    /// stored in `C` components
+    pub fn register_entity_mapper<C: Component + MapEntities>(&mut self) {
+        self.entity_mappers.push(|components, remap| {
+            if let Some(storage) = components.storage_mut::<C>() {
+                unsafe { storage.iter_mut::<C>() }.for_each(|c| c.map_entities(remap));
+            }
+        });
+    }
+
+    /// Moves live entities to the lowest indices so the freed slots at the end get
+    /// reused first, retired indices are never reused. Moved entities get new ids,
+    /// so any `Entity` stored outside of components registered with
+    /// [`register_entity_mapper`](Self::register_entity_mapper) has to be updated
+    /// with the returned [`EntityRemap`].
+    ///
+    /// There is no remap event: events are read a frame later, while mappers fix
+    /// the components before any system sees the moved entities. The event queue
+    /// also lives in a crate depending on this one, callers can push the returned
+    /// remap into it themselves
+    pub fn compact_entities(&mut self) -> EntityRemap {
+        self.flush();
+
+        let spawner = &self.spawner;
+        let (end, live, moves) = self
+            .components
+            .compaction_moves(|index| spawner.is_retired(index));
+        let new_entities = self.spawner.compact(end, live, &moves);
+
+        let remap = moves
+            .iter()
+            .map(|(old, _)| *old)
+            .zip(new_entities)
+            .collect::<EntityRemap>();
+
+        self.components.compact(&remap);
+
+        for mapper in self.entity_mappers.iter() {
+            mapper(&mut self.components, &remap);
+        }
+
+        remap
+    }
+
+    /// Registers `teardown` to run before the resources are dropped by
+    /// [`purge`](Self::purge). Teardowns run in reverse registration order, so a
+    /// module set up after another one is torn down before it
+    pub fn add_teardown(&mut self, teardown: fn(&mut World)) {
+        self.teardowns.push(teardown);
+    }
+
+    /// Runs and unregisters the teardowns added with [`add_teardown`](Self::add_teardown)
+    pub fn teardown(&mut self) {
+        while let Some(teardown) = self.teardowns.pop() {
+            teardown(self);
+        }
     }
 
     pub fn purge(&mut self) {
+        self.teardown();
         self.components.clear();
         self.schedules.clear();
         self.resources.clear();
@@ -182,3 +401,683 @@ impl Drop for World {
         self.purge();
     }
 }
+
+#[cfg(test)]
+mod test {
+    use crate::{
+        commands::{command_buffer::CommandBuffer, Commands},
+        entity::{EntityRemap, MapEntities, Parent},
+        prefab::{Prefab, PrefabError, Prefabs},
+        prelude::*,
+        system::{
+            schedule::{Schedule, ScheduleControl, StateLabel},
+            system_config::IntoSystemConfigs,
+            system_set::IntoSystemSetConfigs,
+        },
+    };
+
+    use super::{
+        merge::{load_world, ResourceConflict},
+        World,
+    };
+
+    #[derive(Component, Debug, Clone, PartialEq, serde::Serialize, serde::Deserialize)]
+    struct Health(pub u32);
+
+    #[derive(Component, Debug, Clone, PartialEq, serde::Deserialize)]
+    struct Dead;
+
+    #[test]
+    pub fn should_query_outside_of_systems() {
+        let mut world = World::new();
+        world.register_components::<(Health, Dead)>();
+
+        let alive = world.spawn_entity(Health(100));
+        world.spawn_entity((Health(0), Dead));
+
+        world.query::<&mut Health>().for_each(|h| h.0 += 1);
+
+        let mut healths = world.query::<&Health>().map(|h| h.0).collect::<Vec<_>>();
+        healths.sort();
+        assert_eq!(healths, [1, 101]);
+
+        let alive_entities = world
+            .query_filtered::<&Health, Without<Dead>>()
+            .collect::<Vec<_>>();
+        assert_eq!(alive_entities, [&Health(101)]);
+
+        assert_eq!(world.query_filtered::<&Health, With<Dead>>().count(), 1);
+
+        let mut optional = world
+            .query::<(&Health, Option<&Dead>)>()
+            .map(|(health, dead)| (health.0, dead.is_some()))
+            .collect::<Vec<_>>();
+        optional.sort();
+        assert_eq!(optional, [(1, true), (101, false)]);
+
+        world.kill(alive);
+        assert_eq!(world.query_filtered::<&Health, Without<Dead>>().count(), 0);
+    }
+
+    #[test]
+    pub fn should_build_entities_with_children() {
+        let mut world = World::new();
+        let parent = world.spawn_entity(Health(10));
+
+        let mut buffer = CommandBuffer::new();
+        Commands::new(&mut buffer)
+            .entity(parent)
+            .insert_if(false, Dead)
+            .remove_batch::<Health>()
+            .insert_if(true, Health(20))
+            .with_children(|children| {
+                children.spawn(Health(1)).spawn((Health(2), Dead));
+            })
+            .build();
+        buffer.apply(&mut world);
+
+        assert_eq!(world.component::<Health>(parent), Some(&Health(20)));
+        assert!(world.component::<Dead>(parent).is_none());
+
+        let mut children = world
+            .query::<(&Health, &Parent)>()
+            .map(|(health, parent)| (health.0, parent.0))
+            .collect::<Vec<_>>();
+        children.sort();
+        assert_eq!(children, [(1, parent), (2, parent)]);
+    }
+
+    #[test]
+    pub fn should_skip_disabled_entities() {
+        let mut world = World::new();
+        world.register_component::<Health>();
+
+        let enabled = world.spawn_entity(Health(1));
+        let disabled = world.spawn_entity(Health(2));
+
+        let mut buffer = CommandBuffer::new();
+        Commands::new(&mut buffer)
+            .entity(disabled)
+            .disable()
+            .build();
+        buffer.apply(&mut world);
+
+        assert_eq!(world.query::<&Health>().collect::<Vec<_>>(), [&Health(1)]);
+        assert_eq!(
+            world.query_filtered::<&Health, IncludeDisabled>().count(),
+            2
+        );
+        assert_eq!(
+            world
+                .query_filtered::<&Health, With<Disabled>>()
+                .collect::<Vec<_>>(),
+            [&Health(2)]
+        );
+        assert_eq!(world.component::<Health>(disabled), Some(&Health(2)));
+
+        Commands::new(&mut buffer).entity(disabled).enable().build();
+        buffer.apply(&mut world);
+
+        assert_eq!(world.query::<&Health>().count(), 2);
+        assert!(world.component::<Disabled>(enabled).is_none());
+    }
+
+    #[test]
+    pub fn should_spawn_prefabs_with_overrides() {
+        let mut prefabs = Prefabs::new();
+        prefabs
+            .register_component::<Health>()
+            .register_component::<Dead>();
+        prefabs
+            .load_ron("corpse", r#"{ "Health": (0), "Dead": () }"#)
+            .unwrap();
+        prefabs.insert("orc", Prefab::new().with(Health(30)));
+
+        assert!(matches!(
+            prefabs.parse_ron(r#"{ "Mana": 5 }"#),
+            Err(PrefabError::UnknownComponent(_))
+        ));
+
+        let mut world = World::new();
+        world.insert_resource(prefabs);
+
+        let mut buffer = CommandBuffer::new();
+        Commands::new(&mut buffer)
+            .spawn_prefab("orc", ())
+            .spawn_prefab("orc", Health(50))
+            .spawn_prefab("corpse", ());
+        buffer.apply(&mut world);
+
+        let mut spawned = world
+            .query::<(&Health, Option<&Dead>)>()
+            .map(|(health, dead)| (health.0, dead.is_some()))
+            .collect::<Vec<_>>();
+        spawned.sort();
+        assert_eq!(spawned, [(0, true), (30, false), (50, false)]);
+
+        assert!(matches!(
+            world.spawn_prefab("goblin", ()),
+            Err(PrefabError::UnknownPrefab(_))
+        ));
+        assert!(matches!(
+            World::new().spawn_prefab("orc", ()),
+            Err(PrefabError::MissingPrefabs)
+        ));
+    }
+
+    #[test]
+    pub fn should_share_resources_between_worlds() {
+        let table = std::sync::Arc::new(vec![1, 2, 3]);
+
+        let mut first = World::new();
+        let mut second = World::new();
+        first.insert_shared(table.clone());
+        second.insert_shared(table);
+
+        let from_first = first.shared::<Vec<i32>>().unwrap();
+        assert!(from_first.ptr_eq(&second.shared::<Vec<i32>>().unwrap()));
+        assert_eq!(first.resource::<Shared<Vec<i32>>>().unwrap().len(), 3);
+    }
+
+    #[test]
+    pub fn should_iterate_queries_in_parallel() {
+        let mut world = World::new();
+        world.register_components::<(Health, Dead)>();
+
+        for i in 0..1000 {
+            world.spawn_entity(Health(i));
+        }
+        world.spawn_entity((Health(0), Dead));
+
+        Query::<&mut Health, Without<Dead>>::new(&world)
+            .par_iter()
+            .with_batch_size(64)
+            .for_each(|health| health.0 *= 2);
+
+        let sum = world.query::<&Health>().map(|h| h.0).sum::<u32>();
+        assert_eq!(sum, (0..1000).sum::<u32>() * 2);
+    }
+
+    #[derive(Component, Debug, Clone, Copy, PartialEq)]
+    #[component(storage = "dense")]
+    struct Position(f32);
+
+    #[derive(Component, Debug, Clone, Copy, PartialEq)]
+    #[component(storage = "dense")]
+    struct Velocity(f32);
+
+    #[test]
+    pub fn should_iterate_queries_in_chunks() {
+        let mut world = World::new();
+        world.register_components::<(Position, Velocity)>();
+
+        for i in 0..4 {
+            world.spawn_entity((Position(i as f32), Velocity(1.0)));
+        }
+        // Splits the packed positions, the velocities stay contiguous
+        world.spawn_entity(Position(-1.0));
+        for i in 4..8 {
+            world.spawn_entity((Position(i as f32), Velocity(1.0)));
+        }
+
+        let chunks = Query::<(&mut Position, &Velocity)>::new(&world).iter_chunks();
+        assert_eq!(chunks.chunk_count(), 2);
+
+        for (positions, velocities) in chunks {
+            assert_eq!(positions.len(), 4);
+
+            for (position, velocity) in positions.iter_mut().zip(velocities) {
+                position.0 += velocity.0;
+            }
+        }
+
+        let mut positions = world.query::<&Position>().map(|p| p.0).collect::<Vec<_>>();
+        positions.sort_by(f32::total_cmp);
+        assert_eq!(positions, [-1.0, 1.0, 2.0, 3.0, 4.0, 5.0, 6.0, 7.0, 8.0]);
+    }
+
+    #[test]
+    #[should_panic]
+    pub fn should_not_query_aliasing_components_outside_of_systems() {
+        let mut world = World::new();
+        world.register_component::<Health>();
+        world.query::<(&mut Health, &Health)>();
+    }
+
+    #[test]
+    #[should_panic]
+    pub fn should_not_iterate_aliasing_queries_in_parallel() {
+        let world = World::new();
+        Query::<(&mut Health, &Health)>::new(&world).par_iter();
+    }
+
+    #[derive(Component, Debug, Clone, PartialEq)]
+    struct Target(Entity);
+
+    impl MapEntities for Target {
+        fn map_entities(&mut self, remap: &EntityRemap) {
+            self.0 = remap.map(self.0);
+        }
+    }
+
+    #[test]
+    pub fn should_compact_entities() {
+        let mut world = World::new();
+        world.register_components::<(Health, Target)>();
+        world.register_entity_mapper::<Target>();
+
+        let entities = (0..4)
+            .map(|i| world.spawn_entity(Health(i)))
+            .collect::<Vec<_>>();
+
+        world.kill(entities[0]);
+        world.kill(entities[2]);
+        world.insert_component(entities[1], Target(entities[3]));
+
+        let stats = world.entity_stats();
+        assert_eq!((stats.live, stats.free, stats.high_water), (2, 2, 4));
+
+        let remap = world.compact_entities();
+        assert_eq!(remap.len(), 2);
+
+        let first = remap.map(entities[1]);
+        let last = remap.map(entities[3]);
+        assert_eq!((first.index(), last.index()), (0, 1));
+        assert_ne!(first.gen(), entities[0].gen());
+
+        assert_eq!(world.component::<Health>(first), Some(&Health(1)));
+        assert_eq!(world.component::<Health>(last), Some(&Health(3)));
+        assert_eq!(world.component::<Target>(first), Some(&Target(last)));
+        assert_eq!(world.component::<Health>(entities[3]), None);
+
+        let stats = world.entity_stats();
+        assert_eq!((stats.live, stats.free, stats.high_water), (2, 0, 2));
+
+        let spawned = world.spawn_entity(Health(10));
+        assert_eq!(spawned.index(), 2);
+        assert_ne!(spawned, entities[2]);
+        assert_eq!(world.entity_stats().capacity, 4);
+    }
+
+    #[test]
+    pub fn should_not_compact_into_retired_indices() {
+        let mut world = World::new();
+        world.register_components::<Health>();
+
+        let mut retired = world.spawn_entity(Health(0));
+        while retired.gen() < Entity::GEN_MAX {
+            world.kill(retired);
+            retired = world.spawn_entity(Health(0));
+        }
+        assert_eq!(retired.index(), 0);
+
+        world.kill(retired);
+        let dead = world.spawn_entity(Health(1));
+        let live = world.spawn_entity(Health(2));
+        world.kill(dead);
+
+        let remap = world.compact_entities();
+        let moved = remap.map(live);
+        assert_eq!(moved.index(), 1);
+        assert_ne!(moved, retired);
+        assert_eq!(world.component::<Health>(moved), Some(&Health(2)));
+        assert_eq!(world.component::<Health>(retired), None);
+
+        let stats = world.entity_stats();
+        assert_eq!((stats.live, stats.free, stats.retired), (1, 0, 1));
+
+        let spawned = world.spawn_entity(Health(3));
+        assert_eq!(spawned.index(), 2);
+    }
+
+    #[derive(Resource, Default)]
+    struct Entered(u32);
+
+    #[derive(Clone, Copy, PartialEq, Eq, Hash, Debug)]
+    enum GameState {
+        Menu,
+        Playing,
+    }
+
+    fn count_enter(mut entered: ResMut<Entered>) {
+        entered.0 += 1;
+    }
+
+    #[test]
+    pub fn should_create_state_schedules_on_demand() {
+        let mut world = World::new();
+        world.insert_resource(Entered::default());
+
+        let menu = Schedule::OnEnter(StateLabel::new(&GameState::Menu));
+        let playing = Schedule::OnEnter(StateLabel::new(&GameState::Playing));
+
+        assert!(!world.has_schedule(menu));
+
+        world.add_systems(menu, count_enter);
+
+        assert!(world.has_schedule(menu));
+        assert!(!world.has_schedule(playing));
+
+        world.init_schedule(menu);
+        world.run_schedule(menu);
+        world.run_schedule(menu);
+
+        assert_eq!(world.resource::<Entered>().unwrap().0, 2);
+    }
+
+    #[test]
+    pub fn should_record_schedule_stats() {
+        let mut world = World::new();
+        world.insert_resource(Entered::default());
+        world.add_schedule(Schedule::Update);
+        world.add_systems(Schedule::Update, count_enter);
+
+        assert!(world.schedule_stats(Schedule::Init).is_none());
+
+        world.init_schedule(Schedule::Update);
+        world.run_schedule(Schedule::Update);
+
+        let stats = world.schedule_stats(Schedule::Update).unwrap();
+        assert_eq!(stats.systems.len(), 1);
+        assert!(stats.systems[0].name.ends_with("count_enter"));
+        assert!(stats.total >= stats.systems[0].duration);
+    }
+
+    fn spawn_wave() {}
+
+    fn apply_damage() {}
+
+    fn update_score() {}
+
+    #[test]
+    pub fn should_run_systems_in_name_order_when_deterministic() {
+        let run_order = |reversed: bool| {
+            let mut world = World::new();
+            world.set_deterministic(true);
+            world.add_schedule(Schedule::Update);
+
+            if reversed {
+                world.add_systems(Schedule::Update, apply_damage.after(spawn_wave));
+                world.add_systems(Schedule::Update, (spawn_wave, update_score));
+            } else {
+                world.add_systems(Schedule::Update, (update_score, spawn_wave));
+                world.add_systems(Schedule::Update, apply_damage.after(spawn_wave));
+            }
+
+            world.init_schedule(Schedule::Update);
+            world.run_schedule(Schedule::Update);
+
+            world
+                .schedule_stats(Schedule::Update)
+                .unwrap()
+                .systems
+                .iter()
+                .map(|timing| timing.name.rsplit("::").next().unwrap())
+                .collect::<Vec<_>>()
+        };
+
+        // `apply_damage` would sort first, but has to wait for `spawn_wave`
+        assert_eq!(
+            run_order(false),
+            ["spawn_wave", "apply_damage", "update_score"]
+        );
+        assert_eq!(run_order(false), run_order(true));
+    }
+
+    #[derive(Resource, Default)]
+    struct Removed(Vec<Entity>);
+
+    fn collect_removed(removed: RemovedComponents<Health>, mut collected: ResMut<Removed>) {
+        collected.0 = removed.iter().copied().collect();
+    }
+
+    #[test]
+    pub fn should_see_removed_components_until_schedule_ends() {
+        let mut world = World::new();
+        world.register_components::<(Health, Dead)>();
+        world.insert_resource(Removed::default());
+        world.add_schedule(Schedule::Update);
+        world.add_systems(Schedule::Update, collect_removed);
+        world.init_schedule(Schedule::Update);
+
+        let healthy = world.spawn_entity(Health(10));
+        let killed = world.spawn_entity(Health(10));
+        world.spawn_entity(Dead);
+
+        world.remove_component::<Health>(healthy);
+        world.kill(killed);
+
+        world.run_schedule(Schedule::Update);
+        assert_eq!(world.resource::<Removed>().unwrap().0, [healthy, killed]);
+
+        world.run_schedule(Schedule::Update);
+        assert!(world.resource::<Removed>().unwrap().0.is_empty());
+    }
+
+    #[test]
+    pub fn should_skip_paused_schedules_and_labels() {
+        let mut world = World::new();
+        world.insert_resource(Entered::default());
+        world.insert_resource(ScheduleControl::new());
+        world.add_schedule(Schedule::Update);
+        world.add_systems(
+            Schedule::Update,
+            (count_enter, count_enter.label("gameplay")),
+        );
+        world.init_schedule(Schedule::Update);
+
+        world.run_schedule(Schedule::Update);
+        assert_eq!(world.resource::<Entered>().unwrap().0, 2);
+
+        world
+            .resource_mut::<ScheduleControl>()
+            .unwrap()
+            .pause_label("gameplay");
+        world.run_schedule(Schedule::Update);
+        assert_eq!(world.resource::<Entered>().unwrap().0, 3);
+
+        let control = world.resource_mut::<ScheduleControl>().unwrap();
+        control.resume_label("gameplay");
+        control.pause(Schedule::Update);
+        world.run_schedule(Schedule::Update);
+        assert_eq!(world.resource::<Entered>().unwrap().0, 3);
+    }
+
+    #[test]
+    pub fn should_scope_resources() {
+        let mut world = World::new();
+        world.register_component::<Health>();
+        world.insert_resource(Entered(5));
+        world.spawn_entity(Health(10));
+
+        let total = world.resource_scope::<Entered, _>(|world, entered| {
+            assert!(world.resource::<Entered>().is_none());
+
+            world.query::<&mut Health>().for_each(|h| h.0 += entered.0);
+            entered.0 += 1;
+
+            world.query::<&Health>().map(|h| h.0).sum::<u32>()
+        });
+
+        assert_eq!(total, Some(15));
+        assert_eq!(world.resource::<Entered>().unwrap().0, 6);
+
+        assert!(world.resource_scope::<Scoped, _>(|_, _| ()).is_none());
+    }
+
+    #[derive(Resource)]
+    struct Scoped;
+
+    #[test]
+    pub fn should_run_teardowns_before_purge_in_reverse_order() {
+        let mut world = World::new();
+        world.insert_resource(Entered(0));
+        world.insert_resource(Scoped);
+
+        world.add_teardown(|world| {
+            assert!(world.remove_resource::<Scoped>().is_none());
+            world.resource_mut::<Entered>().unwrap().0 *= 10;
+        });
+        world.add_teardown(|world| {
+            assert!(world.remove_resource::<Scoped>().is_some());
+            world.resource_mut::<Entered>().unwrap().0 += 1;
+        });
+
+        world.teardown();
+        assert_eq!(world.resource::<Entered>().unwrap().0, 10);
+
+        world.purge();
+        assert!(world.resource::<Entered>().is_none());
+    }
+
+    #[test]
+    pub fn should_merge_worlds_with_remapped_entities() {
+        let mut world = World::new();
+        world.register_component::<Health>();
+        world.insert_resource(Entered(1));
+        let existing = world.spawn_entity(Health(1));
+
+        let mut chunk = World::new();
+        chunk.register_entity_mapper::<Parent>();
+        chunk.insert_resource(Entered(2));
+        chunk.insert_resource(Scoped);
+        let parent = chunk.spawn_entity(Health(2));
+        let child = chunk.spawn_entity((Health(3), Parent(parent)));
+
+        let remap = world.merge(chunk, ResourceConflict::KeepExisting);
+
+        assert_eq!(remap.len(), 2);
+        assert_ne!(remap.map(parent), parent);
+        assert_eq!(world.component::<Health>(existing), Some(&Health(1)));
+        assert_eq!(
+            world.component::<Health>(remap.map(parent)),
+            Some(&Health(2))
+        );
+        assert_eq!(
+            world.component::<Parent>(remap.map(child)),
+            Some(&Parent(remap.map(parent)))
+        );
+        assert_eq!(world.resource::<Entered>().unwrap().0, 1);
+        assert!(world.resource::<Scoped>().is_some());
+
+        let mut chunk = World::new();
+        chunk.insert_resource(Entered(3));
+        world.merge(chunk, ResourceConflict::Replace);
+        assert_eq!(world.resource::<Entered>().unwrap().0, 3);
+    }
+
+    #[test]
+    pub fn should_load_worlds_in_background() {
+        let pending = unsafe {
+            load_world(|world| {
+                world.spawn_entity(Health(7));
+            })
+        };
+
+        let chunk = pending.wait().unwrap();
+
+        let mut world = World::new();
+        let remap = world.merge(chunk, ResourceConflict::KeepExisting);
+        let (_, entity) = remap.iter().next().unwrap();
+
+        assert_eq!(world.component::<Health>(entity), Some(&Health(7)));
+    }
+
+    #[derive(Resource, Default)]
+    struct RunOrder(Vec<&'static str>);
+
+    #[derive(SystemSet)]
+    struct InputSet;
+
+    #[derive(SystemSet)]
+    struct CameraSet;
+
+    #[derive(SystemSet)]
+    struct RenderSet;
+
+    fn read_input(mut order: ResMut<RunOrder>) {
+        order.0.push("input");
+    }
+
+    fn move_camera(mut order: ResMut<RunOrder>) {
+        order.0.push("camera");
+    }
+
+    fn render(mut order: ResMut<RunOrder>) {
+        order.0.push("render");
+    }
+
+    #[test]
+    pub fn should_order_and_skip_system_sets() {
+        let mut world = World::new();
+        world.insert_resource(RunOrder::default());
+        world.add_schedule(Schedule::Update);
+        world.add_systems(Schedule::Update, render.in_set(RenderSet));
+        world.add_systems(Schedule::Update, move_camera.in_set(CameraSet));
+        world.add_systems(Schedule::Update, read_input.in_set(InputSet));
+        world.configure_sets(Schedule::Update, (InputSet, CameraSet, RenderSet).chain());
+        world.configure_sets(
+            Schedule::Update,
+            CameraSet.run_if(|world: &World| world.resource::<Entered>().is_some()),
+        );
+        world.init_schedule(Schedule::Update);
+
+        world.run_schedule(Schedule::Update);
+        assert_eq!(world.resource::<RunOrder>().unwrap().0, ["input", "render"]);
+
+        world.insert_resource(Entered::default());
+        world.resource_mut::<RunOrder>().unwrap().0.clear();
+        world.run_schedule(Schedule::Update);
+        assert_eq!(
+            world.resource::<RunOrder>().unwrap().0,
+            ["input", "camera", "render"]
+        );
+    }
+
+    #[cfg(feature = "snapshots")]
+    #[test]
+    pub fn should_rewind_to_snapshots() {
+        use super::snapshot::{SnapshotError, SnapshotRecorder};
+
+        #[derive(Resource, serde::Serialize, serde::Deserialize)]
+        struct Score(u32);
+
+        let mut world = World::new();
+        world.register_component::<Health>();
+        world.insert_resource(Score(0));
+
+        let mut recorder = SnapshotRecorder::new(2, 4);
+        recorder
+            .register_component::<Health>()
+            .register_resource::<Score>();
+        world.insert_resource(recorder);
+
+        let kept = world.spawn_entity(Health(100));
+        let killed = world.spawn_entity(Health(50));
+        world.record_snapshot(0).unwrap();
+
+        world.kill(killed);
+        world.component_mut::<Health>(kept).unwrap().0 = 10;
+        world.resource_mut::<Score>().unwrap().0 = 7;
+        let spawned = world.spawn_entity(Health(1));
+        world.record_snapshot(1).unwrap();
+        world.record_snapshot(2).unwrap();
+
+        assert_eq!(world.rewind_to(1).unwrap(), 0);
+        assert_eq!(world.component::<Health>(kept), Some(&Health(100)));
+        assert_eq!(world.component::<Health>(killed), Some(&Health(50)));
+        assert_eq!(world.component::<Health>(spawned), None);
+        assert_eq!(world.resource::<Score>().unwrap().0, 0);
+        assert_eq!(world.entity_count(), 2);
+
+        let recorder = world.resource::<SnapshotRecorder>().unwrap();
+        assert_eq!(recorder.snapshots().count(), 1);
+
+        // The index of `killed` is taken again, so a new one is handed out
+        assert_eq!(world.spawn_entity(Health(1)), Entity::from_gen_id(1, 2));
+        assert!(matches!(
+            World::new().rewind_to(0),
+            Err(SnapshotError::NoRecorder)
+        ));
+    }
+}
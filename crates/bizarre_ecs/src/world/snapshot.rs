@@ -0,0 +1,338 @@
+use std::collections::{BTreeSet, VecDeque};
+
+use serde::{de::DeserializeOwned, Serialize};
+use thiserror::Error;
+
+use crate::{component::Component, entity::Entity, resource::Resource};
+
+use super::World;
+
+#[derive(Debug, Error)]
+pub enum SnapshotError {
+    #[error("Failed to serialize `{name}`: {source}")]
+    Serialize {
+        name: &'static str,
+        source: ron::Error,
+    },
+    #[error("Failed to deserialize `{name}`: {source}")]
+    Deserialize {
+        name: &'static str,
+        source: ron::error::SpannedError,
+    },
+    #[error("There is no `SnapshotRecorder` in the world")]
+    NoRecorder,
+    #[error("There is no snapshot taken at or before frame {0}")]
+    NoSnapshot(u64),
+}
+
+pub type SnapshotResult<T> = Result<T, SnapshotError>;
+
+/// Captured values of a single registered type, serialized to RON
+enum SnapshotData {
+    Components(Vec<(Entity, String)>),
+    Resource(Option<String>),
+}
+
+/// Deserialized values waiting to be put into the world
+type Restore = Box<dyn FnOnce(&mut World)>;
+
+struct SnapshotType {
+    name: &'static str,
+    capture: fn(&World) -> Result<SnapshotData, ron::Error>,
+    decode: fn(&SnapshotData) -> Result<Restore, ron::error::SpannedError>,
+}
+
+/// Entity allocation state, restored so entities keep their ids after a rewind
+#[derive(Clone)]
+struct SpawnerState {
+    next_id: u64,
+    dead: VecDeque<Entity>,
+    generations: Vec<u16>,
+    retired: usize,
+    recycled: u64,
+}
+
+/// State of the live entities and of the types registered with a [`SnapshotRecorder`]
+pub struct WorldSnapshot {
+    frame: u64,
+    entities: Vec<Entity>,
+    spawner: SpawnerState,
+    /// Parallel to the types of the recorder that captured it
+    data: Vec<SnapshotData>,
+}
+
+impl WorldSnapshot {
+    pub fn frame(&self) -> u64 {
+        self.frame
+    }
+
+    pub fn entity_count(&self) -> usize {
+        self.entities.len()
+    }
+}
+
+/// Captures the world every `interval` frames and keeps the last `capacity`
+/// snapshots, so the world can be rewound to before a bug showed up.
+///
+/// Only components and resources registered with the recorder are captured.
+/// Entities keep their ids after a rewind, but other components of entities
+/// killed after the snapshot are lost
+pub struct SnapshotRecorder {
+    interval: u64,
+    capacity: usize,
+    types: Vec<SnapshotType>,
+    snapshots: VecDeque<WorldSnapshot>,
+}
+
+impl Resource for SnapshotRecorder {}
+
+impl SnapshotRecorder {
+    pub fn new(interval: u64, capacity: usize) -> Self {
+        Self {
+            interval: interval.max(1),
+            capacity: capacity.max(1),
+            types: Vec::new(),
+            snapshots: VecDeque::new(),
+        }
+    }
+
+    pub fn register_component<C>(&mut self) -> &mut Self
+    where
+        C: Component + Serialize + DeserializeOwned,
+    {
+        self.register(SnapshotType {
+            name: C::resource_name(),
+            capture: |world| {
+                if !world.components.has_storage::<C>() {
+                    return Ok(SnapshotData::Components(Vec::new()));
+                }
+
+                world
+                    .components
+                    .filter_entities(&[C::resource_id()])
+                    .into_iter()
+                    .filter_map(|entity| Some((entity, world.component::<C>(entity)?)))
+                    .map(|(entity, component)| Ok((entity, ron::to_string(component)?)))
+                    .collect::<Result<_, _>>()
+                    .map(SnapshotData::Components)
+            },
+            decode: |data| {
+                let SnapshotData::Components(components) = data else {
+                    unreachable!()
+                };
+
+                let components = components
+                    .iter()
+                    .map(|(entity, value)| Ok((*entity, ron::from_str::<C>(value)?)))
+                    .collect::<Result<Vec<_>, ron::error::SpannedError>>()?;
+
+                Ok(Box::new(move |world: &mut World| {
+                    world.register_component::<C>();
+
+                    let captured = components
+                        .iter()
+                        .map(|(entity, _)| *entity)
+                        .collect::<BTreeSet<_>>();
+
+                    for entity in world.components.filter_entities(&[C::resource_id()]) {
+                        if !captured.contains(&entity) {
+                            world.remove_component::<C>(entity);
+                        }
+                    }
+
+                    for (entity, component) in components {
+                        world.insert_component(entity, component);
+                    }
+                }))
+            },
+        })
+    }
+
+    pub fn register_resource<R>(&mut self) -> &mut Self
+    where
+        R: Resource + Serialize + DeserializeOwned,
+    {
+        self.register(SnapshotType {
+            name: R::resource_name(),
+            capture: |world| {
+                world
+                    .resource::<R>()
+                    .map(ron::to_string)
+                    .transpose()
+                    .map(SnapshotData::Resource)
+            },
+            decode: |data| {
+                let SnapshotData::Resource(resource) = data else {
+                    unreachable!()
+                };
+
+                let resource = resource.as_deref().map(ron::from_str::<R>).transpose()?;
+
+                Ok(Box::new(move |world: &mut World| match resource {
+                    Some(resource) => world.insert_resource(resource),
+                    None => drop(world.remove_resource::<R>()),
+                }))
+            },
+        })
+    }
+
+    fn register(&mut self, ty: SnapshotType) -> &mut Self {
+        if self
+            .types
+            .iter()
+            .all(|registered| registered.name != ty.name)
+        {
+            self.types.push(ty);
+        }
+
+        self
+    }
+
+    pub fn interval(&self) -> u64 {
+        self.interval
+    }
+
+    /// Snapshots from the oldest to the latest
+    pub fn snapshots(&self) -> impl Iterator<Item = &WorldSnapshot> {
+        self.snapshots.iter()
+    }
+
+    pub fn latest(&self) -> Option<&WorldSnapshot> {
+        self.snapshots.back()
+    }
+
+    pub fn clear(&mut self) {
+        self.snapshots.clear();
+    }
+
+    pub fn capture(&self, world: &World, frame: u64) -> SnapshotResult<WorldSnapshot> {
+        let data = self
+            .types
+            .iter()
+            .map(|ty| {
+                (ty.capture)(world).map_err(|source| SnapshotError::Serialize {
+                    name: ty.name,
+                    source,
+                })
+            })
+            .collect::<SnapshotResult<_>>()?;
+
+        let spawner = &world.spawner;
+
+        Ok(WorldSnapshot {
+            frame,
+            entities: world.components.live_entities(),
+            spawner: SpawnerState {
+                next_id: spawner.next_id.load(std::sync::atomic::Ordering::SeqCst),
+                dead: spawner.dead.clone(),
+                generations: spawner.generations.clone(),
+                retired: spawner.retired,
+                recycled: spawner.recycled,
+            },
+            data,
+        })
+    }
+
+    /// Puts the entities and the captured values of `snapshot` back into `world`.
+    ///
+    /// Nothing is changed if any of the values fails to deserialize
+    pub fn restore(&self, world: &mut World, snapshot: &WorldSnapshot) -> SnapshotResult<()> {
+        let restores = self
+            .types
+            .iter()
+            .zip(snapshot.data.iter())
+            .map(|(ty, data)| {
+                (ty.decode)(data).map_err(|source| SnapshotError::Deserialize {
+                    name: ty.name,
+                    source,
+                })
+            })
+            .collect::<SnapshotResult<Vec<_>>>()?;
+
+        world.flush();
+
+        let captured = snapshot.entities.iter().copied().collect::<BTreeSet<_>>();
+
+        for entity in world.components.live_entities() {
+            if !captured.contains(&entity) {
+                world.components.remove_entity(entity);
+            }
+        }
+
+        for entity in snapshot.entities.iter().copied() {
+            if world.components.has_entity(entity) {
+                continue;
+            }
+
+            let capacity = world.components.capacity();
+            if entity.index() >= capacity {
+                world.components.expand_by(entity.index() + 1 - capacity);
+            }
+
+            world.components.register_entity(entity);
+        }
+
+        let SpawnerState {
+            next_id,
+            dead,
+            generations,
+            retired,
+            recycled,
+        } = snapshot.spawner.clone();
+
+        let spawner = &mut world.spawner;
+        spawner
+            .next_id
+            .store(next_id, std::sync::atomic::Ordering::SeqCst);
+        (spawner.dead, spawner.generations) = (dead, generations);
+        (spawner.retired, spawner.recycled) = (retired, recycled);
+
+        for restore in restores {
+            restore(world);
+        }
+
+        Ok(())
+    }
+}
+
+impl World {
+    /// Captures the world into its [`SnapshotRecorder`] if `frame` is a multiple
+    /// of the recorder interval. Does nothing without a recorder
+    pub fn record_snapshot(&mut self, frame: u64) -> SnapshotResult<()> {
+        self.resource_scope(|world, recorder: &mut SnapshotRecorder| {
+            if frame % recorder.interval != 0 {
+                return Ok(());
+            }
+
+            let snapshot = recorder.capture(world, frame)?;
+
+            if recorder.snapshots.len() == recorder.capacity {
+                recorder.snapshots.pop_front();
+            }
+            recorder.snapshots.push_back(snapshot);
+
+            Ok(())
+        })
+        .unwrap_or(Ok(()))
+    }
+
+    /// Restores the latest snapshot taken at or before `frame` and drops the ones
+    /// taken after it. Returns the frame of the restored snapshot
+    pub fn rewind_to(&mut self, frame: u64) -> SnapshotResult<u64> {
+        self.resource_scope(|world, recorder: &mut SnapshotRecorder| {
+            let index = recorder
+                .snapshots
+                .iter()
+                .rposition(|snapshot| snapshot.frame <= frame)
+                .ok_or(SnapshotError::NoSnapshot(frame))?;
+
+            recorder.snapshots.truncate(index + 1);
+
+            let snapshot = &recorder.snapshots[index];
+            recorder.restore(world, snapshot)?;
+
+            Ok(snapshot.frame)
+        })
+        .unwrap_or(Err(SnapshotError::NoRecorder))
+    }
+}
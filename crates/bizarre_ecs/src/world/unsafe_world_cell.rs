@@ -1,6 +1,7 @@
 use std::{
     marker::PhantomData,
     ptr::{self},
+    rc::Rc,
 };
 
 use crate::{
@@ -53,9 +54,29 @@ impl<'w> UnsafeWorldCell<'w> {
         unsafe { self.unsafe_world_mut().component_mut(entity) }
     }
 
+    pub fn removed<C: Component>(self) -> &'w [Entity] {
+        unsafe { self.unsafe_world() }.components.removed::<C>()
+    }
+
     pub fn filter_entities(self, ids: &[ResourceId]) -> Vec<Entity> {
         unsafe { self.unsafe_world() }
             .components
             .filter_entities(ids)
     }
+
+    pub fn query_entities(self, ids: &[ResourceId]) -> Rc<[Entity]> {
+        unsafe { self.unsafe_world() }
+            .components
+            .query_entities(ids)
+    }
+
+    pub fn query_entities_filtered(
+        self,
+        ids: &[ResourceId],
+        without: &[ResourceId],
+    ) -> Rc<[Entity]> {
+        unsafe { self.unsafe_world() }
+            .components
+            .query_entities_filtered(ids, without)
+    }
 }
@@ -0,0 +1,46 @@
+use std::time::Duration;
+
+use bizarre_ecs::{system::schedule::Schedule, world::ecs_module::EcsModule};
+use bizarre_render::{asset_server::AssetServer, ecs::update_asset_server};
+
+/// Inserts the [`AssetServer`] and picks up finished loads every frame.
+///
+/// Requires `RenderAssets` and `EventQueue` resources
+#[derive(Default)]
+pub struct AssetModule {
+    loader_threads: Option<usize>,
+    hot_reload: Option<Duration>,
+}
+
+impl AssetModule {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn with_loader_threads(mut self, loader_threads: usize) -> Self {
+        self.loader_threads = Some(loader_threads);
+        self
+    }
+
+    /// Reloads assets when their files change, checking the files every `poll_interval`
+    pub fn with_hot_reload(mut self, poll_interval: Duration) -> Self {
+        self.hot_reload = Some(poll_interval);
+        self
+    }
+}
+
+impl EcsModule for AssetModule {
+    fn apply(self, world: &mut bizarre_ecs::world::World) {
+        let mut server = match self.loader_threads {
+            Some(threads) => AssetServer::new(threads),
+            None => AssetServer::default(),
+        };
+
+        if let Some(poll_interval) = self.hot_reload {
+            server.watch_for_changes(poll_interval);
+        }
+
+        world.insert_resource(server);
+        world.add_systems(Schedule::Preupdate, update_asset_server);
+    }
+}
@@ -0,0 +1,449 @@
+use std::{f32::consts::FRAC_PI_2, marker::PhantomData};
+
+use bizarre_app::app_state::DeltaTime;
+use bizarre_ecs::{
+    prelude::*,
+    system::schedule::Schedule,
+    world::{ecs_module::EcsModule, World},
+};
+use bizarre_render::camera::Camera;
+use bizarre_sdl::{
+    input::{InputState, MouseButton, Scancode},
+    window::{PlatformWindow, Windows},
+};
+use nalgebra_glm::{look_at, Mat4, Vec3};
+
+/// Pitch is kept just short of straight up and down so the view never flips
+const MAX_PITCH: f32 = FRAC_PI_2 - 0.01;
+
+const UP: Vec3 = Vec3::new(0.0, 1.0, 0.0);
+
+/// A component camera controllers write their view matrix into
+pub trait CameraView: Component {
+    fn set_view(&mut self, view: Mat4);
+}
+
+impl CameraView for Camera {
+    fn set_view(&mut self, view: Mat4) {
+        self.view = view;
+    }
+}
+
+/// Walks on the horizontal plane with WASD and looks around with the mouse
+#[derive(Component, Clone, Debug)]
+pub struct FpsCameraController {
+    pub position: Vec3,
+    /// Radians, `0` looks along `-Z`
+    pub yaw: f32,
+    /// Radians, positive looks up
+    pub pitch: f32,
+    /// Units per second
+    pub move_speed: f32,
+    /// Speed multiplier while left shift is held
+    pub sprint_multiplier: f32,
+    /// Radians per pixel of mouse movement
+    pub sensitivity: f32,
+    /// Mouse button that has to be held to look around, looks around all the time if `None`
+    pub look_button: Option<MouseButton>,
+}
+
+impl Default for FpsCameraController {
+    fn default() -> Self {
+        Self {
+            position: Vec3::zeros(),
+            yaw: 0.0,
+            pitch: 0.0,
+            move_speed: 5.0,
+            sprint_multiplier: 2.0,
+            sensitivity: 0.003,
+            look_button: Some(MouseButton::Right),
+        }
+    }
+}
+
+impl FpsCameraController {
+    pub fn new(position: Vec3) -> Self {
+        Self {
+            position,
+            ..Default::default()
+        }
+    }
+
+    pub fn with_move_speed(mut self, move_speed: f32) -> Self {
+        self.move_speed = move_speed;
+        self
+    }
+
+    pub fn with_sensitivity(mut self, sensitivity: f32) -> Self {
+        self.sensitivity = sensitivity;
+        self
+    }
+
+    pub fn with_look_button(mut self, look_button: Option<MouseButton>) -> Self {
+        self.look_button = look_button;
+        self
+    }
+
+    pub fn forward(&self) -> Vec3 {
+        direction(self.yaw, self.pitch)
+    }
+
+    pub fn view(&self) -> Mat4 {
+        look_at(&self.position, &(self.position + self.forward()), &UP)
+    }
+}
+
+/// Flies along the view direction with WASD, rises with E and sinks with Q
+#[derive(Component, Clone, Debug)]
+pub struct FlyCameraController {
+    pub position: Vec3,
+    /// Radians, `0` looks along `-Z`
+    pub yaw: f32,
+    /// Radians, positive looks up
+    pub pitch: f32,
+    /// Units per second
+    pub move_speed: f32,
+    /// Speed multiplier while left shift is held
+    pub boost_multiplier: f32,
+    /// Radians per pixel of mouse movement
+    pub sensitivity: f32,
+    /// Mouse button that has to be held to look around, looks around all the time if `None`
+    pub look_button: Option<MouseButton>,
+}
+
+impl Default for FlyCameraController {
+    fn default() -> Self {
+        Self {
+            position: Vec3::zeros(),
+            yaw: 0.0,
+            pitch: 0.0,
+            move_speed: 10.0,
+            boost_multiplier: 4.0,
+            sensitivity: 0.003,
+            look_button: Some(MouseButton::Right),
+        }
+    }
+}
+
+impl FlyCameraController {
+    pub fn new(position: Vec3) -> Self {
+        Self {
+            position,
+            ..Default::default()
+        }
+    }
+
+    pub fn with_move_speed(mut self, move_speed: f32) -> Self {
+        self.move_speed = move_speed;
+        self
+    }
+
+    pub fn with_sensitivity(mut self, sensitivity: f32) -> Self {
+        self.sensitivity = sensitivity;
+        self
+    }
+
+    pub fn with_look_button(mut self, look_button: Option<MouseButton>) -> Self {
+        self.look_button = look_button;
+        self
+    }
+
+    pub fn forward(&self) -> Vec3 {
+        direction(self.yaw, self.pitch)
+    }
+
+    pub fn view(&self) -> Mat4 {
+        look_at(&self.position, &(self.position + self.forward()), &UP)
+    }
+}
+
+/// Rotates around `target` while the rotate button is held, zooms with the
+/// mouse wheel and pans with the middle button
+#[derive(Component, Clone, Debug)]
+pub struct OrbitCameraController {
+    pub target: Vec3,
+    pub distance: f32,
+    /// Radians, `0` puts the camera on the `+Z` side of the target
+    pub yaw: f32,
+    /// Radians, positive puts the camera above the target
+    pub pitch: f32,
+    /// Radians per pixel of mouse movement
+    pub sensitivity: f32,
+    /// Share of the distance covered by one wheel step
+    pub zoom_speed: f32,
+    /// Target movement per pixel, multiplied by the distance
+    pub pan_speed: f32,
+    pub min_distance: f32,
+    pub max_distance: f32,
+    pub rotate_button: MouseButton,
+}
+
+impl Default for OrbitCameraController {
+    fn default() -> Self {
+        Self {
+            target: Vec3::zeros(),
+            distance: 10.0,
+            yaw: 0.0,
+            pitch: 0.5,
+            sensitivity: 0.005,
+            zoom_speed: 0.1,
+            pan_speed: 0.001,
+            min_distance: 0.5,
+            max_distance: 500.0,
+            rotate_button: MouseButton::Left,
+        }
+    }
+}
+
+impl OrbitCameraController {
+    pub fn new(target: Vec3, distance: f32) -> Self {
+        Self {
+            target,
+            distance,
+            ..Default::default()
+        }
+    }
+
+    pub fn with_sensitivity(mut self, sensitivity: f32) -> Self {
+        self.sensitivity = sensitivity;
+        self
+    }
+
+    pub fn with_zoom_speed(mut self, zoom_speed: f32) -> Self {
+        self.zoom_speed = zoom_speed;
+        self
+    }
+
+    pub fn with_distance_limits(mut self, min_distance: f32, max_distance: f32) -> Self {
+        self.min_distance = min_distance;
+        self.max_distance = max_distance;
+        self
+    }
+
+    pub fn with_rotate_button(mut self, rotate_button: MouseButton) -> Self {
+        self.rotate_button = rotate_button;
+        self
+    }
+
+    /// Direction from the camera to the target
+    pub fn forward(&self) -> Vec3 {
+        direction(self.yaw, -self.pitch)
+    }
+
+    pub fn position(&self) -> Vec3 {
+        self.target - self.forward() * self.distance
+    }
+
+    pub fn view(&self) -> Mat4 {
+        look_at(&self.position(), &self.target, &UP)
+    }
+}
+
+/// Updates the camera controllers of entities with a `V` view every frame.
+///
+/// Requires `InputState` and `DeltaTime` resources
+pub struct CameraControlsModule<V: CameraView = Camera> {
+    grab_mouse: bool,
+    _marker: PhantomData<V>,
+}
+
+impl CameraControlsModule {
+    pub fn new() -> Self {
+        Self::for_view()
+    }
+}
+
+impl Default for CameraControlsModule {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<V: CameraView> CameraControlsModule<V> {
+    /// Drives views of type `V` instead of [`Camera`]
+    pub fn for_view() -> Self {
+        Self {
+            grab_mouse: false,
+            _marker: PhantomData,
+        }
+    }
+
+    /// Grabs the mouse in the main window while a controller is looking around,
+    /// requires the `Windows` resource
+    pub fn with_mouse_grab(mut self) -> Self {
+        self.grab_mouse = true;
+        self
+    }
+}
+
+impl<V: CameraView> EcsModule for CameraControlsModule<V> {
+    fn apply(self, world: &mut World) {
+        world.add_systems(
+            Schedule::Update,
+            (
+                update_fps_cameras::<V>,
+                update_fly_cameras::<V>,
+                update_orbit_cameras::<V>,
+            ),
+        );
+
+        if self.grab_mouse {
+            world.add_systems(Schedule::Update, grab_mouse_while_looking);
+        }
+    }
+}
+
+pub fn grab_mouse_while_looking(
+    input: Res<InputState>,
+    mut windows: ResMut<Windows>,
+    fps_cameras: Query<&FpsCameraController>,
+    fly_cameras: Query<&FlyCameraController>,
+    orbit_cameras: Query<&OrbitCameraController>,
+) {
+    let Some(window) = windows.get_main_window_mut() else {
+        return;
+    };
+
+    let grab = fps_cameras
+        .into_iter()
+        .any(|controller| looking(&input, controller.look_button))
+        || fly_cameras
+            .into_iter()
+            .any(|controller| looking(&input, controller.look_button))
+        || orbit_cameras
+            .into_iter()
+            .any(|controller| input.is_mouse_pressed(controller.rotate_button));
+
+    if window.is_mouse_grabbed() != grab {
+        PlatformWindow::set_mouse_grab(window, grab);
+    }
+}
+
+pub fn update_fps_cameras<V: CameraView>(
+    input: Res<InputState>,
+    delta: Res<DeltaTime>,
+    cameras: Query<(&mut FpsCameraController, &mut V)>,
+) {
+    let delta = delta.as_secs_f32();
+
+    for (controller, view) in cameras {
+        if looking(&input, controller.look_button) {
+            (controller.yaw, controller.pitch) = look(
+                &input,
+                controller.yaw,
+                controller.pitch,
+                controller.sensitivity,
+            );
+        }
+
+        let forward = direction(controller.yaw, 0.0);
+        let right = forward.cross(&UP);
+        let movement = axis(&input, Scancode::D, Scancode::A) * right
+            + axis(&input, Scancode::W, Scancode::S) * forward;
+
+        let mut speed = controller.move_speed;
+        if input.is_key_pressed(Scancode::LShift) {
+            speed *= controller.sprint_multiplier;
+        }
+
+        controller.position += normalized(movement) * speed * delta;
+        view.set_view(controller.view());
+    }
+}
+
+pub fn update_fly_cameras<V: CameraView>(
+    input: Res<InputState>,
+    delta: Res<DeltaTime>,
+    cameras: Query<(&mut FlyCameraController, &mut V)>,
+) {
+    let delta = delta.as_secs_f32();
+
+    for (controller, view) in cameras {
+        if looking(&input, controller.look_button) {
+            (controller.yaw, controller.pitch) = look(
+                &input,
+                controller.yaw,
+                controller.pitch,
+                controller.sensitivity,
+            );
+        }
+
+        let forward = controller.forward();
+        let right = forward.cross(&UP).normalize();
+        let movement = axis(&input, Scancode::D, Scancode::A) * right
+            + axis(&input, Scancode::W, Scancode::S) * forward
+            + axis(&input, Scancode::E, Scancode::Q) * UP;
+
+        let mut speed = controller.move_speed;
+        if input.is_key_pressed(Scancode::LShift) {
+            speed *= controller.boost_multiplier;
+        }
+
+        controller.position += normalized(movement) * speed * delta;
+        view.set_view(controller.view());
+    }
+}
+
+pub fn update_orbit_cameras<V: CameraView>(
+    input: Res<InputState>,
+    cameras: Query<(&mut OrbitCameraController, &mut V)>,
+) {
+    for (controller, view) in cameras {
+        if input.is_mouse_pressed(controller.rotate_button) {
+            (controller.yaw, controller.pitch) = look(
+                &input,
+                controller.yaw,
+                controller.pitch,
+                controller.sensitivity,
+            );
+        }
+
+        if input.is_mouse_pressed(MouseButton::Middle) {
+            let forward = controller.forward();
+            let right = forward.cross(&UP).normalize();
+            let up = right.cross(&forward);
+            let mouse_delta = input.mouse_delta();
+
+            controller.target += (up * mouse_delta.y as f32 - right * mouse_delta.x as f32)
+                * controller.pan_speed
+                * controller.distance;
+        }
+
+        let scroll = input.mouse_scroll_delta().y;
+        controller.distance = (controller.distance * (1.0 - scroll * controller.zoom_speed))
+            .clamp(controller.min_distance, controller.max_distance);
+
+        view.set_view(controller.view());
+    }
+}
+
+/// Unit vector pointing along `yaw` and `pitch`
+fn direction(yaw: f32, pitch: f32) -> Vec3 {
+    Vec3::new(
+        pitch.cos() * yaw.sin(),
+        pitch.sin(),
+        -pitch.cos() * yaw.cos(),
+    )
+}
+
+fn looking(input: &InputState, look_button: Option<MouseButton>) -> bool {
+    look_button.is_none_or(|button| input.is_mouse_pressed(button))
+}
+
+fn look(input: &InputState, yaw: f32, pitch: f32, sensitivity: f32) -> (f32, f32) {
+    let mouse_delta = input.mouse_delta();
+
+    (
+        yaw + mouse_delta.x as f32 * sensitivity,
+        (pitch - mouse_delta.y as f32 * sensitivity).clamp(-MAX_PITCH, MAX_PITCH),
+    )
+}
+
+/// `1` while only `positive` is pressed, `-1` while only `negative` is
+fn axis(input: &InputState, positive: Scancode, negative: Scancode) -> f32 {
+    input.is_key_pressed(positive) as i32 as f32 - input.is_key_pressed(negative) as i32 as f32
+}
+
+fn normalized(movement: Vec3) -> Vec3 {
+    movement.try_normalize(f32::EPSILON).unwrap_or_default()
+}
@@ -0,0 +1,617 @@
+use std::{
+    collections::{BTreeMap, VecDeque},
+    sync::mpsc::Receiver,
+};
+
+use bizarre_config::{get_config_value, set_config_value, toml};
+use bizarre_ecs::{
+    commands::{Command, Commands},
+    prelude::*,
+    system::schedule::Schedule,
+    world::{ecs_module::EcsModule, World},
+};
+use bizarre_event::Events;
+use bizarre_log::{core_error, core_info};
+use bizarre_render::{
+    render_assets::RenderAssets, render_target::DynamicResolution, renderer::VulkanRenderer,
+};
+use bizarre_sdl::{input::InputEvent, input::Scancode, raw_event::SdlEventHooks, sdl};
+use bizarre_ui::{Dimension, Edges, Style, UiNode, UiNodeId, UiNodeKind, UiTree};
+use nalgebra_glm::Vec4;
+
+use sdl::event::Event as SdlEvent;
+
+/// Output of a console command, errors are printed the same way but logged as errors
+pub type ConsoleResult = Result<String, String>;
+
+type ConsoleHandler = Box<dyn FnMut(&mut World, &[&str]) -> ConsoleResult>;
+
+/// Lines of output kept by the console
+const OUTPUT_CAPACITY: usize = 256;
+/// Lines of output shown by the overlay
+const VISIBLE_LINES: usize = 16;
+const TEXT_SIZE: f32 = 16.0;
+/// Commands handled by the console itself
+const CONSOLE_COMMANDS: [&str; 3] = ["clear", "help", "history"];
+const HISTORY_CAPACITY: usize = 64;
+
+/// Developer console, toggled with the backtick key.
+///
+/// Commands are registered with [`Console::register`] and get the world and the
+/// words typed after their name, quoted words may contain spaces
+#[derive(Resource)]
+pub struct Console {
+    commands: BTreeMap<String, ConsoleHandler>,
+    toggle_key: Scancode,
+    open: bool,
+    input: String,
+    output: VecDeque<String>,
+    history: Vec<String>,
+    /// Entry of the history shown in the input while browsing it
+    history_cursor: Option<usize>,
+    /// Lines submitted since the commands ran last
+    pending: Vec<String>,
+}
+
+impl Default for Console {
+    fn default() -> Self {
+        let mut console = Self {
+            commands: Default::default(),
+            toggle_key: Scancode::Grave,
+            open: false,
+            input: Default::default(),
+            output: Default::default(),
+            history: Default::default(),
+            history_cursor: None,
+            pending: Default::default(),
+        };
+
+        console.register_builtins();
+        console
+    }
+}
+
+impl Console {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn with_toggle_key(mut self, toggle_key: Scancode) -> Self {
+        self.toggle_key = toggle_key;
+        self
+    }
+
+    /// Registers a command, replacing the command with the same name
+    pub fn register(
+        &mut self,
+        name: impl Into<String>,
+        handler: impl FnMut(&mut World, &[&str]) -> ConsoleResult + 'static,
+    ) {
+        self.commands.insert(name.into(), Box::new(handler));
+    }
+
+    pub fn unregister(&mut self, name: &str) -> bool {
+        self.commands.remove(name).is_some()
+    }
+
+    pub fn commands(&self) -> impl Iterator<Item = &str> {
+        self.commands.keys().map(String::as_str)
+    }
+
+    pub fn is_open(&self) -> bool {
+        self.open
+    }
+
+    pub fn set_open(&mut self, open: bool) {
+        self.open = open;
+    }
+
+    pub fn toggle(&mut self) {
+        self.open = !self.open;
+    }
+
+    pub fn input(&self) -> &str {
+        &self.input
+    }
+
+    pub fn output(&self) -> impl Iterator<Item = &str> {
+        self.output.iter().map(String::as_str)
+    }
+
+    pub fn history(&self) -> &[String] {
+        &self.history
+    }
+
+    pub fn print(&mut self, text: impl AsRef<str>) {
+        for line in text.as_ref().lines() {
+            if self.output.len() == OUTPUT_CAPACITY {
+                self.output.pop_front();
+            }
+            self.output.push_back(line.to_string());
+        }
+    }
+
+    pub fn clear_output(&mut self) {
+        self.output.clear();
+    }
+
+    /// Queues `line` to run with the next commands of the console and adds it to
+    /// the history
+    pub fn submit(&mut self, line: impl Into<String>) {
+        let line = line.into();
+        if line.trim().is_empty() {
+            return;
+        }
+
+        if self.history.last() != Some(&line) {
+            if self.history.len() == HISTORY_CAPACITY {
+                self.history.remove(0);
+            }
+            self.history.push(line.clone());
+        }
+
+        self.pending.push(line);
+    }
+
+    /// Runs `line` right away, the output is printed into the console as well.
+    ///
+    /// The console is taken out of `world` while it runs, so call it through
+    /// [`World::resource_scope`]
+    pub fn execute(&mut self, world: &mut World, line: &str) -> ConsoleResult {
+        self.print(format!("> {line}"));
+
+        let words = split_words(line);
+        let result = match words.split_first() {
+            None => Ok(String::new()),
+            Some((name, args)) => match name.as_str() {
+                "help" => {
+                    let mut names = self.commands().chain(CONSOLE_COMMANDS).collect::<Vec<_>>();
+                    names.sort_unstable();
+                    Ok(names.join("\n"))
+                }
+                "history" => Ok(self.history.join("\n")),
+                "clear" => {
+                    self.clear_output();
+                    Ok(String::new())
+                }
+                name => match self.commands.get_mut(name) {
+                    Some(handler) => {
+                        let args = args.iter().map(String::as_str).collect::<Vec<_>>();
+                        handler(world, &args)
+                    }
+                    None => Err(format!(
+                        "Unknown command `{name}`, type `help` for the list"
+                    )),
+                },
+            },
+        };
+
+        match &result {
+            Ok(output) => {
+                if !output.is_empty() {
+                    core_info!("Console `{line}`: {output}");
+                }
+                self.print(output);
+            }
+            Err(err) => {
+                core_error!("Console `{line}` failed: {err}");
+                self.print(format!("error: {err}"));
+            }
+        }
+
+        result
+    }
+
+    /// Runs the lines submitted since the last call
+    pub fn run_pending(&mut self, world: &mut World) {
+        for line in std::mem::take(&mut self.pending) {
+            let _ = self.execute(world, &line);
+        }
+    }
+
+    fn browse_history(&mut self, older: bool) {
+        if self.history.is_empty() {
+            return;
+        }
+
+        let last = self.history.len() - 1;
+        self.history_cursor = match (self.history_cursor, older) {
+            (None, true) => Some(last),
+            (None, false) => None,
+            (Some(cursor), true) => Some(cursor.saturating_sub(1)),
+            (Some(cursor), false) if cursor < last => Some(cursor + 1),
+            (Some(_), false) => None,
+        };
+
+        self.input = self
+            .history_cursor
+            .map(|cursor| self.history[cursor].clone())
+            .unwrap_or_default();
+    }
+
+    /// Text shown by the overlay
+    fn overlay_text(&self) -> String {
+        let skip = self.output.len().saturating_sub(VISIBLE_LINES);
+        let mut text = self
+            .output
+            .iter()
+            .skip(skip)
+            .fold(String::new(), |text, line| text + line + "\n");
+
+        text.push_str("> ");
+        text.push_str(&self.input);
+        text.push('_');
+        text
+    }
+
+    fn register_builtins(&mut self) {
+        self.register("config.get", |_, args| {
+            let [key] = args else {
+                return Err(String::from("Usage: config.get <section.key>"));
+            };
+
+            get_config_value(key)
+                .map(|value| value.to_string())
+                .ok_or_else(|| format!("`{key}` is not set"))
+        });
+
+        self.register("config.set", |_, args| {
+            let [key, value @ ..] = args else {
+                return Err(String::from("Usage: config.set <section.key> <value>"));
+            };
+
+            let value = parse_value(&value.join(" "));
+            let output = format!("{key} = {value}");
+            set_config_value(key, value).map_err(|err| err.to_string())?;
+
+            Ok(output)
+        });
+
+        self.register("world.dump", |world, _| Ok(world.debug_dump().to_string()));
+
+        self.register("render.gpu_culling", |world, args| {
+            let renderer = world
+                .resource_mut::<VulkanRenderer>()
+                .ok_or("The renderer is not running")?;
+            let enabled = parse_toggle(args, renderer.gpu_culling())?;
+
+            renderer
+                .set_gpu_culling(enabled)
+                .map_err(|err| err.to_string())?;
+
+            Ok(format!("GPU culling: {}", on_off(enabled)))
+        });
+
+        self.register("render.occlusion_culling", |world, args| {
+            let renderer = world
+                .resource_mut::<VulkanRenderer>()
+                .ok_or("The renderer is not running")?;
+            let enabled = parse_toggle(args, renderer.occlusion_culling())?;
+
+            renderer.set_occlusion_culling(enabled);
+
+            Ok(format!("Occlusion culling: {}", on_off(enabled)))
+        });
+
+        self.register("render.caps", |world, _| {
+            let renderer = world
+                .resource::<VulkanRenderer>()
+                .ok_or("The renderer is not running")?;
+
+            Ok(renderer.device_capabilities().to_string())
+        });
+
+        self.register("render.secondary_cmd_buffers", |world, args| {
+            let renderer = world
+                .resource_mut::<VulkanRenderer>()
+                .ok_or("The renderer is not running")?;
+            let enabled = parse_toggle(args, renderer.secondary_command_buffers())?;
+
+            renderer
+                .set_secondary_command_buffers(enabled)
+                .map_err(|err| err.to_string())?;
+
+            Ok(format!("Secondary command buffers: {}", on_off(enabled)))
+        });
+
+        self.register("render.depth_prepass", |world, args| {
+            let assets = world
+                .resource_mut::<RenderAssets>()
+                .ok_or("The renderer is not running")?;
+            let current = assets
+                .render_targets
+                .iter()
+                .any(|(_, target)| target.depth_prepass());
+            let enabled = parse_toggle(args, current)?;
+
+            for (_, target) in assets.render_targets.iter_mut() {
+                target.set_depth_prepass(enabled);
+            }
+
+            Ok(format!("Depth pre-pass: {}", on_off(enabled)))
+        });
+
+        self.register("render.taa", |world, args| {
+            let assets = world
+                .resource_mut::<RenderAssets>()
+                .ok_or("The renderer is not running")?;
+            let current = assets
+                .render_targets
+                .iter()
+                .any(|(_, target)| target.temporal_antialiasing());
+            let enabled = parse_toggle(args, current)?;
+
+            for (_, target) in assets.render_targets.iter_mut() {
+                target.set_temporal_antialiasing(enabled);
+            }
+
+            Ok(format!("Temporal antialiasing: {}", on_off(enabled)))
+        });
+
+        self.register("render.scale", |world, args| {
+            let assets = world
+                .resource_mut::<RenderAssets>()
+                .ok_or("The renderer is not running")?;
+
+            match args {
+                [] => (),
+                [scale] => {
+                    let scale = scale
+                        .parse::<f32>()
+                        .map_err(|_| format!("`{scale}` is not a number"))?;
+
+                    for (_, target) in assets.render_targets.iter_mut() {
+                        target.set_render_scale(scale);
+                    }
+                }
+                _ => return Err(String::from("Usage: render.scale [<scale>]")),
+            }
+
+            let scales = assets
+                .render_targets
+                .iter()
+                .map(|(handle, target)| {
+                    let stats = target.stats();
+                    let gpu_frame_time = stats.gpu_frame_time.map_or(String::from("n/a"), |time| {
+                        format!("{:.2}ms", time.as_secs_f64() * 1000.0)
+                    });
+
+                    format!(
+                        "{handle:?}: {:.2} ({}x{}, GPU {gpu_frame_time})",
+                        target.render_scale(),
+                        stats.render_extent.x,
+                        stats.render_extent.y,
+                    )
+                })
+                .collect::<Vec<_>>();
+
+            Ok(format!("Render scale: {scales:?}"))
+        });
+
+        self.register("render.dynamic_resolution", |world, args| {
+            let assets = world
+                .resource_mut::<RenderAssets>()
+                .ok_or("The renderer is not running")?;
+            let current = assets
+                .render_targets
+                .iter()
+                .any(|(_, target)| target.dynamic_resolution().is_some());
+            let enabled = parse_toggle(args, current)?;
+
+            for (_, target) in assets.render_targets.iter_mut() {
+                target.set_dynamic_resolution(enabled.then(DynamicResolution::default));
+            }
+
+            Ok(format!("Dynamic resolution: {}", on_off(enabled)))
+        });
+    }
+}
+
+/// Splits `line` on whitespace, double quoted words may contain whitespace
+fn split_words(line: &str) -> Vec<String> {
+    let mut words = Vec::new();
+    let mut word = None::<String>;
+    let mut quoted = false;
+
+    for c in line.chars() {
+        match c {
+            '"' => {
+                quoted = !quoted;
+                word.get_or_insert_default();
+            }
+            c if c.is_whitespace() && !quoted => words.extend(word.take()),
+            c => word.get_or_insert_default().push(c),
+        }
+    }
+
+    words.extend(word);
+    words
+}
+
+/// Parses `value` as a TOML value, anything that isn't one becomes a string
+fn parse_value(value: &str) -> toml::Value {
+    format!("value = {value}")
+        .parse::<toml::Table>()
+        .ok()
+        .and_then(|mut table| table.remove("value"))
+        .unwrap_or_else(|| toml::Value::String(value.to_string()))
+}
+
+/// `on` or `off` from the arguments, flips `current` without them
+fn parse_toggle(args: &[&str], current: bool) -> Result<bool, String> {
+    match args {
+        [] => Ok(!current),
+        ["on" | "true" | "1"] => Ok(true),
+        ["off" | "false" | "0"] => Ok(false),
+        _ => Err(String::from("Expected `on`, `off` or nothing to toggle")),
+    }
+}
+
+fn on_off(enabled: bool) -> &'static str {
+    if enabled {
+        "on"
+    } else {
+        "off"
+    }
+}
+
+/// Text typed into the focused window, SDL has no input event for it
+#[derive(Resource)]
+struct ConsoleTextInput(Receiver<String>);
+
+/// Nodes of the overlay in the [`UiTree`]
+#[derive(Resource)]
+struct ConsoleOverlay {
+    panel: UiNodeId,
+    text: UiNodeId,
+}
+
+/// Inserts the [`Console`] resource and runs the submitted commands.
+///
+/// The overlay is added to the [`UiTree`] if the
+/// [`UiModule`](super::ui_module::UiModule) was applied before, the output is
+/// only logged otherwise
+#[derive(Default)]
+pub struct ConsoleModule {
+    console: Console,
+}
+
+impl ConsoleModule {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn with_console(mut self, console: Console) -> Self {
+        self.console = console;
+        self
+    }
+}
+
+impl EcsModule for ConsoleModule {
+    fn apply(self, world: &mut World) {
+        let text_input = SdlEventHooks::of_world(world).subscribe(|event| match event {
+            SdlEvent::TextInput { text, .. } => Some(text.clone()),
+            _ => None,
+        });
+
+        world.insert_resource(self.console);
+        world.insert_resource(ConsoleTextInput(text_input));
+        world.add_systems(Schedule::Update, (update_console, run_console_commands));
+
+        if let Some(tree) = world.resource_mut::<UiTree>() {
+            let text = UiNode::text("", TEXT_SIZE).with_color(Vec4::new(0.9, 0.9, 0.9, 1.0));
+            let panel = UiNode::panel(Vec4::new(0.05, 0.05, 0.05, 0.85))
+                .with_style(
+                    Style::default()
+                        .with_size(Dimension::Percent(1.0), Dimension::Auto)
+                        .with_padding(Edges::all(8.0)),
+                )
+                .with_visible(false);
+
+            let panel = tree.add(tree.root(), panel);
+            let text = tree.add(panel, text);
+
+            world.insert_resource(ConsoleOverlay { panel, text });
+            world.add_systems(Schedule::Update, update_console_overlay);
+        }
+    }
+}
+
+fn update_console(
+    mut console: ResMut<Console>,
+    text_input: Res<ConsoleTextInput>,
+    events: Events<InputEvent>,
+) {
+    let was_open = console.open;
+
+    for event in events {
+        let InputEvent::KeyPressed {
+            scancode, repeat, ..
+        } = event
+        else {
+            continue;
+        };
+
+        if scancode == console.toggle_key && !repeat {
+            console.toggle();
+            continue;
+        }
+
+        if !console.open {
+            continue;
+        }
+
+        match scancode {
+            Scancode::Return | Scancode::KpEnter => {
+                let line = std::mem::take(&mut console.input);
+                console.history_cursor = None;
+                console.submit(line);
+            }
+            Scancode::Backspace => {
+                console.input.pop();
+            }
+            Scancode::Escape => console.set_open(false),
+            Scancode::Up => console.browse_history(true),
+            Scancode::Down => console.browse_history(false),
+            _ => (),
+        }
+    }
+
+    // Text typed before the console opened and the toggle key itself are dropped
+    for text in text_input.0.try_iter() {
+        if console.open && was_open {
+            console.input.extend(text.chars().filter(|c| *c != '`'));
+        }
+    }
+}
+
+fn run_console_commands(console: Res<Console>, mut commands: Commands) {
+    if !console.pending.is_empty() {
+        commands.custom_command(RunConsoleCommands);
+    }
+}
+
+fn update_console_overlay(
+    console: Res<Console>,
+    overlay: Res<ConsoleOverlay>,
+    mut tree: ResMut<UiTree>,
+) {
+    let visible = tree.node(overlay.panel).is_some_and(|panel| panel.visible);
+
+    if visible != console.open {
+        if let Some(panel) = tree.node_mut(overlay.panel) {
+            panel.visible = console.open;
+        }
+    }
+
+    if !console.open {
+        return;
+    }
+
+    // Changing the node lays the tree out again, so it's only touched on changes
+    let text = console.overlay_text();
+    let changed = tree
+        .node(overlay.text)
+        .is_some_and(|node| match &node.kind {
+            UiNodeKind::Text { text: current, .. } => *current != text,
+            _ => true,
+        });
+
+    if changed {
+        if let Some(node) = tree.node_mut(overlay.text) {
+            node.kind = UiNodeKind::Text {
+                text,
+                size: TEXT_SIZE,
+            };
+        }
+    }
+}
+
+/// Commands get the whole world, so they run deferred
+struct RunConsoleCommands;
+
+impl Command for RunConsoleCommands {
+    fn apply(self, world: &mut World) {
+        world.resource_scope(|world, console: &mut Console| console.run_pending(world));
+    }
+}
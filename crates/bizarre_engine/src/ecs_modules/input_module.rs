@@ -0,0 +1,135 @@
+use bizarre_app::headless::Headless;
+use bizarre_ecs::{
+    prelude::{Res, ResMut, SystemSet},
+    system::{
+        schedule::Schedule, system_config::IntoSystemConfigs, system_set::IntoSystemSetConfigs,
+    },
+    world::{ecs_module::EcsModule, World},
+};
+use bizarre_event::EventReader;
+use bizarre_log::core_warn;
+use bizarre_sdl::{
+    input::{self, InputEvent, InputState, KeyRepeat, KeyRepeatSynthesizer},
+    replay::{EventCapture, RecordedEvent},
+    window::WindowEvent,
+};
+
+use bizarre_sdl::sdl;
+
+use sdl::event::{Event as SdlEvent, WindowEvent as SdlWindowEvent};
+
+use super::sdl_module::{
+    add_sdl_event_pump, SdlCaptureSet, SdlConvertSet, SdlFrameEvents, SYNTHESIZED_EVENT,
+};
+
+/// Systems of [`InputModule`] updating [`InputState`] with the events of the
+/// frame, run after the events are pushed
+#[derive(SystemSet)]
+pub struct InputSet;
+
+/// Keyboard and mouse input from SDL, inserts the [`InputState`] resource.
+///
+/// Works with windows of any module, focus is tracked from the [`WindowEvent`]s
+/// in the event queue
+#[derive(Default)]
+pub struct InputModule {
+    key_repeat: Option<KeyRepeat>,
+}
+
+impl InputModule {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Synthesizes key repeats when the video driver doesn't deliver them
+    pub fn with_key_repeat(mut self, key_repeat: KeyRepeat) -> Self {
+        self.key_repeat = Some(key_repeat);
+        self
+    }
+}
+
+impl EcsModule for InputModule {
+    fn apply(self, world: &mut World) {
+        if world.resource::<Headless>().is_some() {
+            core_warn!("Skipping `InputModule` in a headless app, no input is read");
+            return;
+        }
+
+        world.insert_resource(InputState::new());
+        world.insert_resource(KeyRepeatSynthesizer::new(self.key_repeat));
+
+        add_sdl_event_pump(world);
+        world.configure_sets(Schedule::Preupdate, InputSet.after(SdlCaptureSet));
+        world.add_systems(
+            Schedule::Preupdate,
+            convert_input_events.in_set(SdlConvertSet),
+        );
+        world.add_systems(
+            Schedule::Preupdate,
+            (update_input_focus, update_input_state).in_set(InputSet),
+        );
+    }
+}
+
+fn convert_input_events(
+    input: Res<InputState>,
+    capture: Res<EventCapture>,
+    mut key_repeat: ResMut<KeyRepeatSynthesizer>,
+    mut frame: ResMut<SdlFrameEvents>,
+) {
+    let frame = &mut *frame;
+
+    // Replays contain the repeats synthesized while recording
+    let synthesize = !capture.is_replaying();
+
+    for (index, event) in frame.raw.iter().enumerate() {
+        if let SdlEvent::Window {
+            win_event: SdlWindowEvent::FocusLost,
+            ..
+        } = event
+        {
+            if synthesize {
+                key_repeat.reset();
+            }
+        }
+
+        if let Some(event) = InputEvent::try_from_sdl(event) {
+            if synthesize {
+                key_repeat.observe(&event);
+            }
+
+            frame.converted.push((index, RecordedEvent::Input(event)));
+        }
+    }
+
+    if !synthesize {
+        return;
+    }
+
+    let repeats = key_repeat.synthesize(input::ticks());
+    frame.converted.extend(
+        repeats
+            .into_iter()
+            .map(|event| (SYNTHESIZED_EVENT, RecordedEvent::Input(event))),
+    );
+
+    // SDL has no event for grabbing the mouse
+    frame.converted.extend(
+        InputEvent::mouse_mode_change(input.mouse_mode())
+            .map(|event| (SYNTHESIZED_EVENT, RecordedEvent::Input(event))),
+    );
+}
+
+fn update_input_state(mut input: ResMut<InputState>, events: EventReader<InputEvent>) {
+    input.swap_frames();
+
+    for event in events {
+        input.process_event(event.clone())
+    }
+}
+
+fn update_input_focus(mut input: ResMut<InputState>, events: EventReader<WindowEvent>) {
+    for event in events {
+        input.process_window_event(event)
+    }
+}
@@ -1,2 +1,9 @@
+pub mod asset_module;
+pub mod camera_controls;
+pub mod console_module;
+pub mod input_module;
+pub mod net_module;
 pub mod render_module;
 pub mod sdl_module;
+pub mod ui_module;
+pub mod window_module;
@@ -0,0 +1,137 @@
+use std::net::SocketAddr;
+
+use bizarre_ecs::{
+    commands::{Command, Commands},
+    prelude::ResMut,
+    system::schedule::Schedule,
+    world::{ecs_module::EcsModule, World},
+};
+use bizarre_event::{EventQueue, Events};
+use bizarre_log::core_error;
+use bizarre_net::{
+    ConnectionId, MessageReceived, MessageRegistry, NetEvent, NetMessage, Network, Replicated,
+    Replication, ReplicationSnapshot,
+};
+
+/// Inserts the [`Network`] and [`Replication`] resources, polls the network every
+/// frame and replicates the registered components
+#[derive(Default)]
+pub struct NetModule {
+    registry: MessageRegistry,
+    replication: Replication,
+    host: Option<SocketAddr>,
+    connect: Option<SocketAddr>,
+}
+
+impl NetModule {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn with_message<M: NetMessage>(mut self) -> Self {
+        self.registry.register::<M>();
+        self
+    }
+
+    /// Replicates the component from the server to the clients
+    pub fn with_replicated<C: Replicated>(mut self) -> Self {
+        self.replication.register::<C>();
+        self
+    }
+
+    /// Starts a server when the module is applied
+    pub fn with_server(mut self, addr: SocketAddr) -> Self {
+        self.host = Some(addr);
+        self
+    }
+
+    /// Connects to a server when the module is applied
+    pub fn with_client(mut self, addr: SocketAddr) -> Self {
+        self.connect = Some(addr);
+        self
+    }
+}
+
+impl EcsModule for NetModule {
+    fn apply(mut self, world: &mut World) {
+        self.registry.register::<ReplicationSnapshot>();
+        let mut network = Network::new(self.registry);
+
+        if let Some(addr) = self.host {
+            if let Err(err) = network.host(addr) {
+                core_error!("Failed to host a server on {addr}: {err}");
+            }
+        }
+
+        if let Some(addr) = self.connect {
+            if let Err(err) = network.connect(addr) {
+                core_error!("Failed to connect to {addr}: {err}");
+            }
+        }
+
+        world.insert_resource(network);
+        world.insert_resource(self.replication);
+        world.add_systems(Schedule::Preupdate, (poll_network, apply_snapshots));
+        world.add_systems(Schedule::Update, replicate_world);
+    }
+}
+
+fn poll_network(mut network: ResMut<Network>, mut event_queue: ResMut<EventQueue>) {
+    network.poll(&mut event_queue);
+}
+
+fn apply_snapshots(
+    snapshots: Events<MessageReceived<ReplicationSnapshot>>,
+    net_events: Events<NetEvent>,
+    mut commands: Commands,
+) {
+    for event in net_events {
+        if let NetEvent::Disconnected {
+            connection: ConnectionId::SERVER,
+            ..
+        } = event
+        {
+            commands.custom_command(ReplicationCmd::Clear);
+        }
+    }
+
+    for snapshot in snapshots {
+        commands.custom_command(ReplicationCmd::Apply(snapshot.message));
+    }
+}
+
+fn replicate_world(network: ResMut<Network>, mut commands: Commands) {
+    if network.server().is_some() {
+        commands.custom_command(ReplicationCmd::Replicate);
+    }
+}
+
+/// Replication needs the whole world, so it runs deferred
+enum ReplicationCmd {
+    Replicate,
+    Apply(ReplicationSnapshot),
+    Clear,
+}
+
+impl Command for ReplicationCmd {
+    fn apply(self, world: &mut World) {
+        world.resource_scope(|world, replication: &mut Replication| {
+            let result = match self {
+                ReplicationCmd::Replicate => world
+                    .resource_scope(|world, network: &mut Network| {
+                        replication.replicate(world, network)
+                    })
+                    .unwrap_or(Ok(())),
+                ReplicationCmd::Apply(snapshot) => replication.apply_snapshot(world, &snapshot),
+                ReplicationCmd::Clear => {
+                    replication.clear_replicated(world);
+                    Ok(())
+                }
+            };
+
+            if let Err(err) = result {
+                core_error!("Replication failed: {err}");
+            }
+        });
+    }
+}
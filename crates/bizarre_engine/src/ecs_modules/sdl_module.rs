@@ -1,87 +1,234 @@
-use bizarre_app::app_event::AppEvent;
-use bizarre_core::Handle;
+use std::{ptr, time::Duration};
+
+use bizarre_app::{app_event::AppEvent, headless::Headless, loop_policy::LoopControl};
 use bizarre_ecs::{
-    prelude::{Res, ResMut},
-    system::schedule::Schedule,
-    world::ecs_module::EcsModule,
+    prelude::{ResMut, Resource, SystemSet},
+    system::{
+        schedule::Schedule, system_config::IntoSystemConfigs, system_set::IntoSystemSetConfigs,
+    },
+    world::{ecs_module::EcsModule, World},
 };
-use bizarre_event::{EventQueue, Events};
-use bizarre_log::core_info;
+use bizarre_event::EventQueue;
+use bizarre_log::{core_error, core_warn};
 use bizarre_sdl::{
     context::{with_sdl_context, with_sdl_events},
-    input::{InputEvent, InputState},
-    window::{try_handle_sdl_event, WindowCreateInfo, WindowEvent, WindowHandle, Windows},
+    input::KeyRepeat,
+    raw_event::SdlEventHooks,
+    replay::{EventCapture, RecordedEvent},
+    window::{WindowCreateInfo, WindowEvent},
 };
 
 use bizarre_sdl::sdl;
 
 use sdl::event::Event as SdlEvent;
 
+use super::{input_module::InputModule, window_module::WindowModule};
+
+/// Polls the SDL events of the frame, runs first
+#[derive(SystemSet)]
+pub struct SdlPollSet;
+
+/// Converts the polled events into window and input events
+#[derive(SystemSet)]
+pub struct SdlConvertSet;
+
+/// Records the converted events or replays a recording instead of them and
+/// pushes them into the event queue
+#[derive(SystemSet)]
+pub struct SdlCaptureSet;
+
+/// SDL events of the current frame, shared by [`WindowModule`] and [`InputModule`]
+#[derive(Resource, Default)]
+pub(crate) struct SdlFrameEvents {
+    /// Events that passed the filters of [`SdlEventHooks`]
+    pub raw: Vec<SdlEvent>,
+    /// Events converted from `raw` with the index of the raw event, events
+    /// synthesized by the modules come after the polled ones
+    pub converted: Vec<(usize, RecordedEvent)>,
+    /// Pushed after the converted events without being recorded
+    pub unrecorded: Vec<RecordedEvent>,
+    /// Every event pushed into the event queue on the frame
+    pub pushed: Vec<RecordedEvent>,
+}
+
+/// Index of events synthesized by the modules instead of converted
+pub(crate) const SYNTHESIZED_EVENT: usize = usize::MAX;
+
+/// Windows and input from SDL, applies [`WindowModule`] and [`InputModule`]
 pub struct SdlModule {
-    windows: Vec<(bool, WindowCreateInfo)>,
+    window: WindowModule,
+    input: InputModule,
+    event_capture: EventCapture,
 }
 
 impl SdlModule {
     pub fn new() -> Self {
         Self {
-            windows: Default::default(),
+            window: WindowModule::new(),
+            input: InputModule::new(),
+            event_capture: Default::default(),
         }
     }
 
+    /// Synthesizes key repeats when the video driver doesn't deliver them
+    pub fn with_key_repeat(mut self, key_repeat: KeyRepeat) -> Self {
+        self.input = self.input.with_key_repeat(key_repeat);
+        self
+    }
+
+    /// Records input and window events into a file or replays a recording
+    /// instead of the live events
+    pub fn with_event_capture(mut self, event_capture: EventCapture) -> Self {
+        self.event_capture = event_capture;
+        self
+    }
+
     pub fn with_window(mut self, create_info: WindowCreateInfo) -> Self {
-        self.windows.push((false, create_info));
+        self.window = self.window.with_window(create_info);
         self
     }
 
+    /// Overrides the main window described by the `[window]` config section
     pub fn with_main_window(mut self, create_info: WindowCreateInfo) -> Self {
-        self.windows.push((true, create_info));
+        self.window = self.window.with_main_window(create_info);
         self
     }
 }
 
 impl EcsModule for SdlModule {
-    fn apply(self, world: &mut bizarre_ecs::world::World) {
-        let mut windows = Windows::new();
-
-        for (main_window, create_info) in self.windows {
-            let handle = windows.create_window(&create_info);
-            if main_window {
-                windows.set_main_window(handle);
-            }
+    fn apply(self, world: &mut World) {
+        if world.resource::<Headless>().is_some() {
+            core_warn!("Skipping `SdlModule` in a headless app, no windows or input are created");
+            return;
         }
 
-        world.insert_resource(windows);
-        world.insert_resource(InputState::new());
-        world.add_systems(Schedule::Preupdate, (push_sdl_events, update_input_state));
+        world.insert_resource(self.event_capture);
+        world.add_module(self.window);
+        world.add_module(self.input);
     }
 }
 
-fn update_input_state(mut input: ResMut<InputState>, events: Events<InputEvent>) {
-    input.swap_frames();
+/// Adds the systems polling SDL and pushing the converted events, once for
+/// every module using them.
+///
+/// An [`EventCapture`] inserted before the first module is kept
+pub(crate) fn add_sdl_event_pump(world: &mut World) {
+    if world.resource::<SdlFrameEvents>().is_some() {
+        return;
+    }
+
+    if world.resource::<EventCapture>().is_none() {
+        world.insert_resource(EventCapture::default());
+    }
 
-    for event in events {
-        input.process_event(event)
+    if let Some(loop_control) = world.resource_mut::<LoopControl>() {
+        loop_control.set_event_wait(wait_sdl_events);
     }
+
+    world.insert_resource(SdlFrameEvents::default());
+    // Modules applied earlier may have registered their hooks already
+    SdlEventHooks::of_world(world);
+
+    world.configure_sets(Schedule::Preupdate, SdlConvertSet.after(SdlPollSet));
+    world.configure_sets(
+        Schedule::Preupdate,
+        SdlCaptureSet.after(SdlPollSet).after(SdlConvertSet),
+    );
+    world.add_systems(Schedule::Preupdate, poll_sdl_events.in_set(SdlPollSet));
+    world.add_systems(
+        Schedule::Preupdate,
+        capture_sdl_events.in_set(SdlCaptureSet),
+    );
 }
 
-fn push_sdl_events(windows: Res<Windows>, mut event_queue: ResMut<EventQueue>) {
+/// Blocks until SDL has events queued, leaves them for [`poll_sdl_events`]
+fn wait_sdl_events(timeout: Option<Duration>) {
+    with_sdl_events(|_| unsafe {
+        match timeout {
+            Some(timeout) => {
+                let timeout = timeout.as_millis().min(i32::MAX as u128) as i32;
+                sdl::sys::SDL_WaitEventTimeout(ptr::null_mut(), timeout);
+            }
+            None => {
+                sdl::sys::SDL_WaitEvent(ptr::null_mut());
+            }
+        }
+    });
+}
+
+fn poll_sdl_events(mut hooks: ResMut<SdlEventHooks>, mut frame: ResMut<SdlFrameEvents>) {
+    frame.raw.clear();
+    frame.converted.clear();
+    frame.unrecorded.clear();
+    frame.pushed.clear();
+
     with_sdl_context(|sdl| {
-        sdl.event_pump()
-            .unwrap()
-            .poll_iter()
-            .for_each(|event| try_push_event(&windows, &mut event_queue, &event));
-    })
+        sdl.event_pump().unwrap().poll_iter().for_each(|event| {
+            if hooks.is_allowed(&event) {
+                hooks.dispatch(&event);
+                frame.raw.push(event);
+            }
+        });
+    });
 }
 
-fn try_push_event(windows: &Windows, event_queue: &mut EventQueue, event: &SdlEvent) {
-    if let Some(event) = try_handle_sdl_event(windows, event) {
-        if let WindowEvent::MainWindowCloseRequested(_) = event {
-            event_queue.push_event(AppEvent::CloseRequested);
+fn capture_sdl_events(
+    mut frame: ResMut<SdlFrameEvents>,
+    mut capture: ResMut<EventCapture>,
+    mut event_queue: ResMut<EventQueue>,
+) {
+    // Window events of a polled event come before its input events
+    frame
+        .converted
+        .sort_by_key(|(index, event)| (*index, matches!(event, RecordedEvent::Input(_))));
+
+    let mut events = std::mem::take(&mut frame.converted)
+        .into_iter()
+        .map(|(_, event)| event)
+        .collect::<Vec<_>>();
+
+    if capture.is_replaying() {
+        // Live close requests are kept so a replay can be interrupted
+        events.retain(|event| {
+            matches!(
+                event,
+                RecordedEvent::Window(
+                    WindowEvent::CloseRequested(_) | WindowEvent::MainWindowCloseRequested(_)
+                )
+            )
+        });
+        events.extend(capture.replayed_events());
+    }
+
+    for event in &events {
+        if let Err(err) = capture.record(event) {
+            core_error!("Failed to record an event: {err}");
         }
-        event_queue.push_event(event)
     }
 
-    if let Some(event) = InputEvent::try_from_sdl(event) {
-        event_queue.push_event(event)
+    // Closing windows is driven by the app, replays close them again instead
+    // of recording it
+    events.append(&mut frame.unrecorded);
+
+    for event in &events {
+        push_event(&mut event_queue, event.clone());
+    }
+
+    frame.pushed = events;
+
+    if let Err(err) = capture.next_frame() {
+        core_error!("Failed to flush the event recording: {err}");
+    }
+}
+
+fn push_event(event_queue: &mut EventQueue, event: RecordedEvent) {
+    match event {
+        RecordedEvent::Window(event) => {
+            if let WindowEvent::MainWindowCloseRequested(_) = event {
+                event_queue.push_event(AppEvent::CloseRequested);
+            }
+            event_queue.push_event(event)
+        }
+        RecordedEvent::Input(event) => event_queue.push_event(event),
     }
 }
@@ -0,0 +1,51 @@
+use bizarre_ecs::{
+    system::{
+        schedule::Schedule, system_config::IntoSystemConfigs, system_set::IntoSystemSetConfigs,
+    },
+    world::{ecs_module::EcsModule, World},
+};
+use bizarre_render::ecs::RenderSet;
+use bizarre_ui::{
+    ecs::{queue_ui, update_ui, UiLayoutSet, UiQueueSet},
+    UiAtlas, UiTree,
+};
+
+/// Inserts the [`UiTree`] and [`UiAtlas`] resources and queues the UI into the
+/// sprite pass every frame.
+///
+/// The UI is laid out and queued in `Update` before [`RenderSet`], systems
+/// rendering the cameras have to be in it
+#[derive(Default)]
+pub struct UiModule {
+    tree: UiTree,
+    atlas: UiAtlas,
+}
+
+impl UiModule {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn with_tree(mut self, tree: UiTree) -> Self {
+        self.tree = tree;
+        self
+    }
+
+    pub fn with_atlas(mut self, atlas: UiAtlas) -> Self {
+        self.atlas = atlas;
+        self
+    }
+}
+
+impl EcsModule for UiModule {
+    fn apply(self, world: &mut World) {
+        world.insert_resource(self.tree);
+        world.insert_resource(self.atlas);
+        world.configure_sets(
+            Schedule::Update,
+            (UiLayoutSet, UiQueueSet, RenderSet).chain(),
+        );
+        world.add_systems(Schedule::Update, update_ui.in_set(UiLayoutSet));
+        world.add_systems(Schedule::Update, queue_ui.in_set(UiQueueSet));
+    }
+}
@@ -0,0 +1,212 @@
+use bizarre_app::headless::Headless;
+use bizarre_config::{get_config_section, ConfigSection};
+use bizarre_ecs::{
+    prelude::{Res, ResMut, SystemSet},
+    system::{
+        schedule::Schedule, system_config::IntoSystemConfigs, system_set::IntoSystemSetConfigs,
+    },
+    world::{ecs_module::EcsModule, World},
+};
+use bizarre_log::core_warn;
+use bizarre_sdl::{
+    replay::RecordedEvent,
+    window::{
+        create_info::FullscreenType, try_handle_sdl_event, WindowCreateInfo, WindowEvent,
+        WindowPosition, Windows,
+    },
+};
+use nalgebra_glm::{IVec2, UVec2};
+use serde::Deserialize;
+
+use super::sdl_module::{add_sdl_event_pump, SdlCaptureSet, SdlConvertSet, SdlFrameEvents};
+
+/// Where the window is placed, `"undefined"`, `"centered"` or `[x, y]`
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(untagged)]
+pub enum WindowConfigPosition {
+    Named(NamedWindowPosition),
+    At([i32; 2]),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum NamedWindowPosition {
+    Undefined,
+    Centered,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum WindowConfigFullscreen {
+    #[default]
+    Off,
+    /// Changes the display mode to the window size
+    Exclusive,
+    /// Borderless window covering the display
+    Desktop,
+}
+
+/// `[window]` section of the engine config, describes the main window unless
+/// one is given with [`WindowModule::with_main_window`]
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default)]
+pub struct WindowConfig {
+    pub title: String,
+    pub size: [u32; 2],
+    pub position: WindowConfigPosition,
+    pub fullscreen: WindowConfigFullscreen,
+    pub resizable: bool,
+    pub vsync: bool,
+}
+
+impl Default for WindowConfig {
+    fn default() -> Self {
+        Self {
+            title: String::from("Bizarre Window"),
+            size: [800, 600],
+            position: WindowConfigPosition::Named(NamedWindowPosition::Undefined),
+            fullscreen: WindowConfigFullscreen::Off,
+            resizable: true,
+            vsync: true,
+        }
+    }
+}
+
+impl ConfigSection for WindowConfig {
+    fn section_name() -> &'static str {
+        "window"
+    }
+}
+
+impl From<WindowConfig> for WindowCreateInfo {
+    fn from(config: WindowConfig) -> Self {
+        let position = match config.position {
+            WindowConfigPosition::Named(NamedWindowPosition::Undefined) => {
+                WindowPosition::Undefined
+            }
+            WindowConfigPosition::Named(NamedWindowPosition::Centered) => WindowPosition::Centered,
+            WindowConfigPosition::At([x, y]) => WindowPosition::Positioned(IVec2::new(x, y)),
+        };
+
+        let fullscreen_type = match config.fullscreen {
+            WindowConfigFullscreen::Off => FullscreenType::Off,
+            WindowConfigFullscreen::Exclusive => FullscreenType::True,
+            WindowConfigFullscreen::Desktop => FullscreenType::Desktop,
+        };
+
+        let [width, height] = config.size;
+
+        WindowCreateInfo {
+            fullscreen_type,
+            resizable: config.resizable,
+            vsync: config.vsync,
+            ..WindowCreateInfo::normal_window(config.title, UVec2::new(width, height), position)
+        }
+    }
+}
+
+/// Systems of [`WindowModule`] routing the events of the frame to their windows,
+/// run after the events are pushed
+#[derive(SystemSet)]
+pub struct WindowSet;
+
+/// SDL windows, inserts the [`Windows`] resource.
+///
+/// Without the `[window]` config section or a main window given with
+/// [`with_main_window`](Self::with_main_window) the main window uses the defaults
+/// of [`WindowConfig`]
+#[derive(Default)]
+pub struct WindowModule {
+    windows: Vec<(bool, WindowCreateInfo)>,
+}
+
+impl WindowModule {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn with_window(mut self, create_info: WindowCreateInfo) -> Self {
+        self.windows.push((false, create_info));
+        self
+    }
+
+    /// Overrides the main window described by the `[window]` config section
+    pub fn with_main_window(mut self, create_info: WindowCreateInfo) -> Self {
+        self.windows.push((true, create_info));
+        self
+    }
+}
+
+impl EcsModule for WindowModule {
+    fn apply(self, world: &mut World) {
+        if world.resource::<Headless>().is_some() {
+            core_warn!("Skipping `WindowModule` in a headless app, no windows are created");
+            return;
+        }
+
+        let mut windows = Windows::new();
+
+        let mut create_infos = self.windows;
+
+        if !create_infos.iter().any(|(main_window, _)| *main_window) {
+            let config = get_config_section::<WindowConfig>().unwrap_or_else(|err| {
+                core_warn!("Invalid `[window]` config, using the defaults: {err}");
+                WindowConfig::default()
+            });
+
+            create_infos.insert(0, (true, config.into()));
+        }
+
+        for (main_window, create_info) in create_infos {
+            let handle = windows.create_window(&create_info);
+            if main_window {
+                windows.set_main_window(handle);
+            }
+        }
+
+        world.insert_resource(windows);
+
+        add_sdl_event_pump(world);
+        world.configure_sets(Schedule::Preupdate, WindowSet.after(SdlCaptureSet));
+        world.add_systems(
+            Schedule::Preupdate,
+            convert_window_events.in_set(SdlConvertSet),
+        );
+        world.add_systems(Schedule::Preupdate, route_window_events.in_set(WindowSet));
+    }
+}
+
+fn convert_window_events(mut windows: ResMut<Windows>, mut frame: ResMut<SdlFrameEvents>) {
+    let must_close = windows.begin_frame();
+
+    let frame = &mut *frame;
+
+    for (index, event) in frame.raw.iter().enumerate() {
+        let Some(event) = try_handle_sdl_event(&windows, event) else {
+            continue;
+        };
+
+        // SDL may still have events queued for windows destroyed since
+        if windows.window(&event.window_handle()).is_none() {
+            continue;
+        }
+
+        if let WindowEvent::Resized { handle, size } = event {
+            windows.constrain_aspect_ratio(handle, size);
+        }
+
+        frame.converted.push((index, RecordedEvent::Window(event)));
+    }
+
+    frame
+        .unrecorded
+        .extend(must_close.into_iter().map(RecordedEvent::Window));
+}
+
+fn route_window_events(mut windows: ResMut<Windows>, frame: Res<SdlFrameEvents>) {
+    for event in &frame.pushed {
+        if let RecordedEvent::Window(event) = event {
+            windows.route_event(event);
+        }
+    }
+}
@@ -1,10 +1,13 @@
 pub use bizarre_app as app;
+pub use bizarre_config as config;
 pub use bizarre_core as core;
 pub use bizarre_ecs as ecs;
 pub use bizarre_event as event;
 pub use bizarre_log as log;
+pub use bizarre_net as net;
 pub use bizarre_render as render;
 pub use bizarre_sdl as sdl;
+pub use bizarre_ui as ui;
 
 pub mod ecs_modules;
 
@@ -1,13 +1,19 @@
-use std::{any::TypeId, collections::HashMap};
+use std::{any::TypeId, collections::HashMap, sync::mpsc::Sender};
 
 use anyhow::{anyhow, Result};
 use bizarre_ecs::prelude::*;
 
-use crate::{event::Event, event_reader::EventReader, typed_event_queue::TypedEventQueue};
+use crate::{
+    event::Event,
+    event_reader::ReaderId,
+    event_sender::{EventSender, RemoteEvents, DEFAULT_SENDER_CAPACITY},
+    typed_event_queue::TypedEventQueue,
+};
 
 #[derive(Resource)]
 pub struct EventQueue {
     queues: HashMap<TypeId, TypedEventQueue>,
+    remote: HashMap<TypeId, RemoteEvents>,
     next_reader_id: usize,
 }
 
@@ -16,6 +22,7 @@ impl Default for EventQueue {
         Self {
             next_reader_id: 1,
             queues: Default::default(),
+            remote: Default::default(),
         }
     }
 }
@@ -25,13 +32,13 @@ impl EventQueue {
         Self::default()
     }
 
-    pub fn create_reader(&mut self) -> EventReader {
+    pub fn create_reader(&mut self) -> ReaderId {
         let id = self.next_reader_id;
         self.next_reader_id += 1;
-        EventReader { id }
+        ReaderId { id }
     }
 
-    pub fn register_reader<E>(&mut self, reader: EventReader) -> Result<()>
+    pub fn register_reader<E>(&mut self, reader: ReaderId) -> Result<()>
     where
         E: Event,
     {
@@ -50,6 +57,16 @@ impl EventQueue {
         Ok(())
     }
 
+    /// Creates the queue of `E` events if it doesn't exist yet
+    pub fn register_event<E>(&mut self)
+    where
+        E: Event,
+    {
+        self.queues
+            .entry(TypeId::of::<E>())
+            .or_insert_with(TypedEventQueue::new::<E>);
+    }
+
     pub fn push_event<E>(&mut self, event: E)
     where
         E: Event,
@@ -63,14 +80,52 @@ impl EventQueue {
         }
     }
 
-    pub fn poll_event<E>(&mut self, reader: &EventReader) -> Option<&E>
+    /// Sender of `E` events for other threads, see [`EventSender`]
+    pub fn sender<E>(&mut self) -> EventSender<E>
+    where
+        E: Event,
+    {
+        self.sender_with_capacity(DEFAULT_SENDER_CAPACITY)
+    }
+
+    /// Same as [`EventQueue::sender`], `capacity` is only used by the first
+    /// sender or [`EventWriter`](crate::EventWriter) of `E`, the rest share its
+    /// buffer
+    pub fn sender_with_capacity<E>(&mut self, capacity: usize) -> EventSender<E>
+    where
+        E: Event,
+    {
+        self.remote_events::<E>(capacity).sender()
+    }
+
+    /// Unbounded sender used by [`EventWriter`](crate::EventWriter), its events
+    /// are flushed along with the ones of [`EventSender`]s
+    pub(crate) fn writer<E>(&mut self) -> Sender<E>
+    where
+        E: Event,
+    {
+        self.remote_events::<E>(DEFAULT_SENDER_CAPACITY).writer()
+    }
+
+    fn remote_events<E>(&mut self, capacity: usize) -> &RemoteEvents
+    where
+        E: Event,
+    {
+        self.register_event::<E>();
+
+        self.remote
+            .entry(TypeId::of::<E>())
+            .or_insert_with(|| RemoteEvents::new::<E>(capacity))
+    }
+
+    pub fn poll_event<E>(&mut self, reader: &ReaderId) -> Option<&E>
     where
         E: Event,
     {
         self.get_queue_mut::<E>()?.poll_event(reader)
     }
 
-    pub fn pull_events<E>(&mut self, reader: &EventReader) -> Vec<E>
+    pub fn pull_events<E>(&mut self, reader: &ReaderId) -> Vec<E>
     where
         E: Event + Clone,
     {
@@ -82,10 +137,33 @@ impl EventQueue {
         }
     }
 
+    /// Type name and amount of events of every type that are readable this
+    /// frame or were pushed during it
+    pub fn event_counts(&self) -> Vec<(&'static str, usize)> {
+        self.queues
+            .values()
+            .map(|queue| (queue.event_name, queue.event_count()))
+            .filter(|(_, count)| *count > 0)
+            .collect()
+    }
+
+    /// Makes the events pushed during the frame and sent from other threads
+    /// until now readable
     pub fn change_frames(&mut self) {
+        for (type_id, remote) in &self.remote {
+            remote.flush(self.queues.get_mut(type_id).unwrap());
+        }
+
         self.queues.values_mut().for_each(|q| q.swap_buffers());
     }
 
+    pub(crate) fn get_queue<E>(&self) -> Option<&TypedEventQueue>
+    where
+        E: Event,
+    {
+        self.queues.get(&TypeId::of::<E>())
+    }
+
     #[inline(always)]
     fn get_queue_mut<E>(&mut self) -> Option<&mut TypedEventQueue>
     where
@@ -98,6 +176,8 @@ impl EventQueue {
 
 #[cfg(test)]
 mod tests {
+    use std::{sync::mpsc::TrySendError, thread};
+
     use anyhow::Result;
 
     use crate::EventQueue;
@@ -134,9 +214,9 @@ mod tests {
             usize_info: 0,
         };
 
-        event_queue.push_event(event)?;
+        event_queue.push_event(event);
         event_queue.change_frames();
-        let polled_event = event_queue.poll_event::<TestEvent1>(&reader)?;
+        let polled_event = event_queue.poll_event::<TestEvent1>(&reader);
 
         assert!(
             polled_event == Some(&event),
@@ -146,4 +226,42 @@ mod tests {
 
         Ok(())
     }
+
+    #[test]
+    fn sent_events_should_be_readable_after_frame_change() -> Result<()> {
+        let mut event_queue = EventQueue::default();
+        let reader = event_queue.create_reader();
+        event_queue.register_reader::<TestEvent1>(reader)?;
+
+        let sender = event_queue.sender_with_capacity::<TestEvent1>(2);
+        let event = |usize_info| TestEvent1 {
+            str_info: "sent",
+            usize_info,
+        };
+
+        let remote = sender.clone();
+        thread::spawn(move || {
+            assert!(remote.send(event(0)).is_ok());
+            assert!(remote.send(event(1)).is_ok());
+            assert!(matches!(remote.send(event(2)), Err(TrySendError::Full(_))));
+        })
+        .join()
+        .unwrap();
+
+        assert_eq!(event_queue.poll_event::<TestEvent1>(&reader), None);
+
+        event_queue.change_frames();
+        assert_eq!(event_queue.poll_event(&reader), Some(&event(0)));
+        assert_eq!(event_queue.poll_event(&reader), Some(&event(1)));
+        assert_eq!(event_queue.poll_event::<TestEvent1>(&reader), None);
+
+        // The flush drained the buffer
+        assert!(sender.send(event(3)).is_ok());
+        assert!(sender.send(event(4)).is_ok());
+
+        event_queue.change_frames();
+        assert_eq!(event_queue.poll_event(&reader), Some(&event(3)));
+
+        Ok(())
+    }
 }
@@ -1,5 +1,5 @@
 use std::{
-    any::type_name,
+    any::{type_name, Any},
     marker::PhantomData,
     ops::{Deref, DerefMut},
 };
@@ -7,15 +7,18 @@ use std::{
 use bizarre_ecs::{
     prelude::Resource,
     system::{system_param::SystemParam, WorldAccess, WorldAccessType},
+    world::{unsafe_world_cell::UnsafeWorldCell, World},
 };
 
-use crate::{Event, EventQueue};
+use crate::{typed_event_queue::TypedEventQueue, Event, EventQueue};
 
 #[derive(Hash, PartialEq, PartialOrd, Eq, Ord, Clone, Copy, Debug)]
-pub struct EventReader {
+pub struct ReaderId {
     pub(crate) id: usize,
 }
 
+/// Clones of the unread `T` events of the previous frame. Borrows the whole
+/// [`EventQueue`] mutably, [`EventReader`] doesn't
 pub struct Events<T: Event> {
     events: Vec<T>,
 }
@@ -41,21 +44,21 @@ impl<T: Event> IntoIterator for Events<T> {
 impl<T: Event> SystemParam for Events<T> {
     type Item<'w, 's> = Events<T>;
 
-    type State = EventReader;
+    type State = ReaderId;
 
     unsafe fn init(world: bizarre_ecs::world::unsafe_world_cell::UnsafeWorldCell) -> Self::State {
         let eq = world
             .unsafe_world_mut()
             .resource_mut::<EventQueue>()
             .unwrap_or_else(|| {
-                panic!("Cannot create an `EventReader` when there is `EventQueue` in the world")
+                panic!("Cannot create a `ReaderId` when there is no `EventQueue` in the world")
             });
 
         let reader = eq.create_reader();
 
         eq.register_reader::<T>(reader)
             .map_err(|err| {
-                panic!("Could not register an `EventReader` for `Events` system param: {err}")
+                panic!("Could not register a `ReaderId` for `Events` system param: {err}")
             })
             .unwrap();
 
@@ -85,3 +88,185 @@ impl<T: Event> SystemParam for Events<T> {
         }]
     }
 }
+
+/// Position of an [`EventReader`] in the events of the current frame, kept in
+/// the state of the system owning the reader
+#[derive(Default, Clone, Copy, Debug)]
+pub struct EventCursor {
+    generation: u64,
+    read: usize,
+}
+
+/// Reads `E` events pushed during the previous frame that this system hasn't
+/// read yet. Every system has its own cursor, so each event is read once per
+/// system, events are marked read as soon as the system runs.
+///
+/// The queue of `E` is registered in the [`EventQueue`] on the first use and the
+/// queue is borrowed immutably, so readers of any events run in parallel
+pub struct EventReader<'w, E: Event> {
+    events: &'w [Box<dyn Any>],
+    _phantom: PhantomData<E>,
+}
+
+impl<'w, E: Event> EventReader<'w, E> {
+    pub fn len(&self) -> usize {
+        self.events.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.events.is_empty()
+    }
+
+    pub fn iter(&self) -> impl ExactSizeIterator<Item = &'w E> {
+        self.events.iter().map(|event| {
+            event.downcast_ref::<E>().unwrap_or_else(|| {
+                panic!(
+                    "Found an event of a wrong type in the queue of `{}`",
+                    type_name::<E>()
+                )
+            })
+        })
+    }
+}
+
+impl<'w, E: Event> IntoIterator for EventReader<'w, E> {
+    type Item = &'w E;
+
+    type IntoIter = Box<dyn ExactSizeIterator<Item = &'w E> + 'w>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        Box::new(self.iter())
+    }
+}
+
+impl<E: Event> SystemParam for EventReader<'_, E> {
+    type Item<'w, 's> = EventReader<'w, E>;
+
+    type State = EventCursor;
+
+    unsafe fn init(world: UnsafeWorldCell) -> Self::State {
+        register_event::<E>(world.unsafe_world_mut());
+
+        EventCursor::default()
+    }
+
+    unsafe fn get_item<'w, 's>(
+        world: UnsafeWorldCell<'w>,
+        param_state: &'s mut Self::State,
+    ) -> Self::Item<'w, 's>
+    where
+        Self: Sized,
+    {
+        let (events, generation) = world
+            .resource::<EventQueue>()
+            .and_then(EventQueue::get_queue::<E>)
+            .map(TypedEventQueue::readable)
+            .unwrap_or((&[], param_state.generation));
+
+        if param_state.generation != generation {
+            *param_state = EventCursor {
+                generation,
+                read: 0,
+            };
+        }
+
+        let unread = &events[param_state.read.min(events.len())..];
+        param_state.read = events.len();
+
+        EventReader {
+            events: unread,
+            _phantom: PhantomData,
+        }
+    }
+
+    fn param_access() -> Vec<WorldAccess> {
+        vec![WorldAccess {
+            resource_id: EventQueue::resource_id(),
+            resource_name: EventQueue::resource_name(),
+            access_type: WorldAccessType::ResRead,
+        }]
+    }
+}
+
+/// Inserts an [`EventQueue`] into `world` if it has none and registers the
+/// queue of `E` events in it
+pub(crate) fn register_event<E: Event>(world: &mut World) {
+    if world.resource::<EventQueue>().is_none() {
+        world.insert_resource(EventQueue::new());
+    }
+
+    world
+        .resource_mut::<EventQueue>()
+        .unwrap()
+        .register_event::<E>();
+}
+
+#[cfg(test)]
+mod tests {
+    use bizarre_ecs::{prelude::*, system::schedule::Schedule, world::World};
+
+    use crate::{EventQueue, EventReader, EventWriter};
+
+    #[derive(Clone, Debug)]
+    struct Ping(u32);
+
+    #[derive(Clone, Debug)]
+    struct Pong(u32);
+
+    #[derive(Resource, Default)]
+    struct Received(Vec<u32>);
+
+    fn send_ping(mut writer: EventWriter<Ping>, mut sent: Local<u32>) {
+        writer.send(Ping(*sent));
+        *sent += 1;
+    }
+
+    fn receive_pings(reader: EventReader<Ping>, mut received: ResMut<Received>) {
+        received.0.extend(reader.iter().map(|ping| ping.0));
+    }
+
+    #[test]
+    fn event_reader_should_read_every_event_once() {
+        let mut world = World::new();
+        world.insert_resource(Received::default());
+        world.add_schedule(Schedule::Update);
+        world.add_systems(Schedule::Update, (send_ping, receive_pings));
+        world.init_schedule(Schedule::Update);
+
+        // The second run within a frame sends an event, but reads nothing new
+        for runs in [1, 2, 1] {
+            for _ in 0..runs {
+                world.run_schedule(Schedule::Update);
+            }
+
+            world.resource_mut::<EventQueue>().unwrap().change_frames();
+        }
+
+        assert_eq!(world.resource::<Received>().unwrap().0, [0, 1, 2]);
+    }
+
+    fn answer_pings(pings: EventReader<Ping>, mut pongs: EventWriter<Pong>) {
+        pongs.send_batch(pings.iter().map(|ping| Pong(ping.0 * 10)));
+    }
+
+    fn receive_pongs(reader: EventReader<Pong>, mut received: ResMut<Received>) {
+        received.0.extend(reader.iter().map(|pong| pong.0));
+    }
+
+    #[test]
+    fn event_reader_and_writer_should_share_a_system() {
+        let mut world = World::new();
+        world.insert_resource(Received::default());
+        world.add_schedule(Schedule::Update);
+        world.add_systems(Schedule::Update, (send_ping, answer_pings, receive_pongs));
+        world.init_schedule(Schedule::Update);
+
+        for _ in 0..3 {
+            world.run_schedule(Schedule::Update);
+            world.resource_mut::<EventQueue>().unwrap().change_frames();
+        }
+
+        // A ping is answered a frame after it is sent and read a frame later
+        assert_eq!(world.resource::<Received>().unwrap().0, [0]);
+    }
+}
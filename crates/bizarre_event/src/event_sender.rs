@@ -0,0 +1,79 @@
+use std::{
+    any::Any,
+    sync::mpsc::{self, Receiver, Sender, SyncSender, TrySendError},
+};
+
+use crate::{event::Event, typed_event_queue::TypedEventQueue};
+
+/// Amount of events an [`EventSender`] buffers between two frames when the
+/// capacity is not specified
+pub const DEFAULT_SENDER_CAPACITY: usize = 1024;
+
+/// Pushes events into an [`EventQueue`](crate::EventQueue) from other threads.
+///
+/// Sent events are buffered and become readable after the next frame change of
+/// the queue, sending fails while the buffer is full
+pub struct EventSender<E: Event> {
+    sender: SyncSender<E>,
+}
+
+impl<E: Event> Clone for EventSender<E> {
+    fn clone(&self) -> Self {
+        Self {
+            sender: self.sender.clone(),
+        }
+    }
+}
+
+impl<E: Event> EventSender<E> {
+    /// Returns the event back if the buffer is full or the queue was dropped
+    pub fn send(&self, event: E) -> Result<(), TrySendError<E>> {
+        self.sender.try_send(event)
+    }
+}
+
+/// Receiving end of the [`EventSender`]s and
+/// [`EventWriter`](crate::EventWriter)s of a single event type
+pub(crate) struct RemoteEvents {
+    sender: Box<dyn Any>,
+    writer: Box<dyn Any>,
+    receiver: Box<dyn Fn(&mut TypedEventQueue)>,
+}
+
+impl RemoteEvents {
+    pub fn new<E: Event>(capacity: usize) -> Self {
+        let (sender, receiver): (_, Receiver<E>) = mpsc::sync_channel(capacity);
+        // Writers are unbounded, systems can't handle a full buffer
+        let (writer, written): (_, Receiver<E>) = mpsc::channel();
+
+        Self {
+            sender: Box::new(EventSender { sender }),
+            writer: Box::new(writer),
+            receiver: Box::new(move |queue| {
+                written
+                    .try_iter()
+                    .chain(receiver.try_iter())
+                    .for_each(|event| queue.push_event(event))
+            }),
+        }
+    }
+
+    pub fn sender<E: Event>(&self) -> EventSender<E> {
+        self.sender
+            .downcast_ref::<EventSender<E>>()
+            .expect("RemoteEvents accessed with a wrong event type")
+            .clone()
+    }
+
+    pub fn writer<E: Event>(&self) -> Sender<E> {
+        self.writer
+            .downcast_ref::<Sender<E>>()
+            .expect("RemoteEvents accessed with a wrong event type")
+            .clone()
+    }
+
+    /// Moves the events sent since the last flush into `queue`
+    pub fn flush(&self, queue: &mut TypedEventQueue) {
+        (self.receiver)(queue)
+    }
+}
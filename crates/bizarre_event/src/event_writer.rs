@@ -0,0 +1,63 @@
+use std::sync::mpsc::Sender;
+
+use bizarre_ecs::{
+    prelude::Resource,
+    system::{system_param::SystemParam, WorldAccess, WorldAccessType},
+    world::unsafe_world_cell::UnsafeWorldCell,
+};
+
+use crate::{event_reader::register_event, Event, EventQueue};
+
+/// Pushes `E` events, they become readable after the next frame change of the
+/// [`EventQueue`].
+///
+/// The queue of `E` is registered on the first use. Events go through a channel
+/// flushed by the frame change, so the [`EventQueue`] is only borrowed
+/// immutably and the writer can share a system with [`EventReader`]s
+///
+/// [`EventReader`]: crate::EventReader
+pub struct EventWriter<'s, E: Event> {
+    sender: &'s Sender<E>,
+}
+
+impl<E: Event> EventWriter<'_, E> {
+    pub fn send(&mut self, event: E) {
+        // Sending only fails once the `EventQueue` was dropped
+        let _ = self.sender.send(event);
+    }
+
+    pub fn send_batch(&mut self, events: impl IntoIterator<Item = E>) {
+        events.into_iter().for_each(|event| self.send(event));
+    }
+}
+
+impl<E: Event> SystemParam for EventWriter<'_, E> {
+    type Item<'w, 's> = EventWriter<'s, E>;
+
+    type State = Sender<E>;
+
+    unsafe fn init(world: UnsafeWorldCell) -> Self::State {
+        let world = world.unsafe_world_mut();
+        register_event::<E>(world);
+
+        world.resource_mut::<EventQueue>().unwrap().writer::<E>()
+    }
+
+    unsafe fn get_item<'w, 's>(
+        _: UnsafeWorldCell<'w>,
+        sender: &'s mut Self::State,
+    ) -> Self::Item<'w, 's>
+    where
+        Self: Sized,
+    {
+        EventWriter { sender }
+    }
+
+    fn param_access() -> Vec<WorldAccess> {
+        vec![WorldAccess {
+            resource_id: EventQueue::resource_id(),
+            resource_name: EventQueue::resource_name(),
+            access_type: WorldAccessType::ResRead,
+        }]
+    }
+}
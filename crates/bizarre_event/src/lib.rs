@@ -4,10 +4,14 @@
 mod event;
 mod event_queue;
 mod event_reader;
+mod event_sender;
+mod event_writer;
 mod typed_event_queue;
 
 pub use {
     event::Event,
     event_queue::EventQueue,
-    event_reader::{EventReader, Events},
+    event_reader::{EventCursor, EventReader, Events, ReaderId},
+    event_sender::{EventSender, DEFAULT_SENDER_CAPACITY},
+    event_writer::EventWriter,
 };
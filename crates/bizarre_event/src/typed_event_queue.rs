@@ -5,7 +5,7 @@ use std::{
 
 use anyhow::{anyhow, Result};
 
-use crate::{event::Event, event_reader::EventReader};
+use crate::{event::Event, event_reader::ReaderId};
 
 type IteratorType<'frame> = std::slice::Iter<'frame, Box<dyn Any>>;
 
@@ -14,7 +14,9 @@ pub struct TypedEventQueue {
     pub(crate) event_name: &'static str,
     front: Vec<Box<dyn Any>>,
     back: Vec<Box<dyn Any>>,
-    readers: HashMap<EventReader, usize>,
+    readers: HashMap<ReaderId, usize>,
+    /// Amount of buffer swaps, tells cursors of readers apart between frames
+    generation: u64,
 }
 
 impl TypedEventQueue {
@@ -28,6 +30,7 @@ impl TypedEventQueue {
             front: Default::default(),
             back: Default::default(),
             readers: Default::default(),
+            generation: 0,
         }
     }
 
@@ -45,14 +48,14 @@ impl TypedEventQueue {
         self.back.push(Box::new(event))
     }
 
-    pub fn poll_event<E>(&mut self, reader: &EventReader) -> Option<&E>
+    pub fn poll_event<E>(&mut self, reader: &ReaderId) -> Option<&E>
     where
         E: Event + 'static,
     {
         let reader_index = self
             .readers
             .get_mut(reader)
-            .unwrap_or_else(|| panic!("Trying to poll event with an unregistered `EventReader`"));
+            .unwrap_or_else(|| panic!("Trying to poll event with an unregistered `ReaderId`"));
 
         if *reader_index >= self.front.len() {
             None
@@ -72,14 +75,14 @@ impl TypedEventQueue {
         }
     }
 
-    pub fn pull_events<E>(&mut self, reader: &EventReader) -> Vec<E>
+    pub fn pull_events<E>(&mut self, reader: &ReaderId) -> Vec<E>
     where
         E: Event + Clone,
     {
         let reader_index = self
             .readers
             .get_mut(reader)
-            .unwrap_or_else(|| panic!("Trying to poll event with an unregistered `EventReader`"));
+            .unwrap_or_else(|| panic!("Trying to poll event with an unregistered `ReaderId`"));
 
         if *reader_index >= self.front.len() {
             return Vec::new();
@@ -106,13 +109,24 @@ impl TypedEventQueue {
         result
     }
 
-    pub fn add_reader(&mut self, reader: EventReader) {
+    /// Events readable this frame with the generation of the buffer holding them
+    pub fn readable(&self) -> (&[Box<dyn Any>], u64) {
+        (&self.front, self.generation)
+    }
+
+    /// Events readable this frame plus the ones pushed for the next frame
+    pub fn event_count(&self) -> usize {
+        self.front.len() + self.back.len()
+    }
+
+    pub fn add_reader(&mut self, reader: ReaderId) {
         self.readers.entry(reader).or_insert(0);
     }
 
     pub fn swap_buffers(&mut self) {
         std::mem::swap(&mut self.front, &mut self.back);
         self.back.clear();
+        self.generation += 1;
         self.readers.iter_mut().for_each(|(_, index)| *index = 0)
     }
 }
@@ -36,6 +36,7 @@ pub const fn bg_color(color: TerminalEscapeCode) -> TerminalEscapeCode {
     color + 10
 }
 
+#[derive(Clone, Debug)]
 pub struct TerminalEscapeSequence(pub HashSet<TerminalEscapeCode>);
 
 impl Display for TerminalEscapeSequence {
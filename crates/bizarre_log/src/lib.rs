@@ -4,14 +4,20 @@
 use std::fmt::Display;
 
 mod log_thread;
-pub use log_thread::{init_logging, register_logger, send_log, shutdown_logging};
+pub use log_thread::{
+    flush_logging, init_logging, init_logging_with, is_logging_initialized, log_stats,
+    register_logger, send_log, shutdown_logging, LogChannelConfig, LogStats, OverflowPolicy,
+};
 
 pub mod escape_code;
+pub mod log_format;
 pub mod log_target;
 pub mod logger;
 pub mod logger_builder;
+pub mod memory_target;
 
 pub mod macros;
+pub mod rate_limit;
 
 #[derive(Debug, Default, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
 pub enum LogLevel {
@@ -37,15 +43,23 @@ pub struct Log {
 
 #[cfg(test)]
 mod test {
+    use std::{sync::Mutex, time::Duration};
+
     use crate::{
+        flush_logging, is_logging_initialized,
         log_target::TerminalTarget,
         log_thread::{init_logging, register_logger, shutdown_logging},
         logger::Logger,
         macros::*,
     };
 
+    /// Tests below rely on the global logging state
+    static LOGGING_TESTS: Mutex<()> = Mutex::new(());
+
     #[test]
     fn should_log() {
+        let _guard = LOGGING_TESTS.lock().unwrap_or_else(|err| err.into_inner());
+
         init_logging(None, None);
 
         register_logger(
@@ -68,6 +82,29 @@ mod test {
         core_error!("Format: {}", "some string");
         core_fatal!("Format: {}", "some string");
 
+        for _ in 0..3 {
+            info_once!("Logged once");
+            core_warn_throttle!(render: Duration::from_secs(1), "Throttled: {}", 42);
+        }
+
+        shutdown_logging();
+    }
+
+    #[test]
+    fn should_reference_count_logging_init() {
+        let _guard = LOGGING_TESTS.lock().unwrap_or_else(|err| err.into_inner());
+
+        init_logging(None, None);
+        init_logging(None, None);
+
+        core_info!("Logging is initialized twice");
+
+        shutdown_logging();
+        assert!(is_logging_initialized());
+        assert!(flush_logging(Duration::from_secs(1)));
+
         shutdown_logging();
+        assert!(!is_logging_initialized());
+        assert!(!flush_logging(Duration::from_secs(1)));
     }
 }
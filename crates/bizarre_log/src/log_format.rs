@@ -0,0 +1,219 @@
+use chrono::{DateTime, Local};
+
+use crate::{
+    escape_code::{TerminalEscapeSequence, RESET},
+    escape_sequence, Log, LogLevel,
+};
+
+/// Colors of the `{level}` placeholder for every log level
+#[derive(Clone)]
+pub struct ColorTheme {
+    trace: TerminalEscapeSequence,
+    info: TerminalEscapeSequence,
+    warn: TerminalEscapeSequence,
+    error: TerminalEscapeSequence,
+    fatal: TerminalEscapeSequence,
+}
+
+impl Default for ColorTheme {
+    fn default() -> Self {
+        Self {
+            trace: TerminalEscapeSequence::from(&LogLevel::Trace),
+            info: TerminalEscapeSequence::from(&LogLevel::Info),
+            warn: TerminalEscapeSequence::from(&LogLevel::Warn),
+            error: TerminalEscapeSequence::from(&LogLevel::Error),
+            fatal: TerminalEscapeSequence::from(&LogLevel::Fatal),
+        }
+    }
+}
+
+impl ColorTheme {
+    pub fn with_level(mut self, level: LogLevel, color: impl Into<TerminalEscapeSequence>) -> Self {
+        *self.level_mut(level) = color.into();
+        self
+    }
+
+    pub fn level(&self, level: LogLevel) -> &TerminalEscapeSequence {
+        match level {
+            LogLevel::Trace => &self.trace,
+            LogLevel::Info => &self.info,
+            LogLevel::Warn => &self.warn,
+            LogLevel::Error => &self.error,
+            LogLevel::Fatal => &self.fatal,
+        }
+    }
+
+    fn level_mut(&mut self, level: LogLevel) -> &mut TerminalEscapeSequence {
+        match level {
+            LogLevel::Trace => &mut self.trace,
+            LogLevel::Info => &mut self.info,
+            LogLevel::Warn => &mut self.warn,
+            LogLevel::Error => &mut self.error,
+            LogLevel::Fatal => &mut self.fatal,
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+enum Segment {
+    Literal(String),
+    Timestamp,
+    Level,
+    Label,
+    Target,
+    Message,
+}
+
+/// Layout of a log line.
+///
+/// The template supports `{timestamp}`, `{level}`, `{label}`, `{target}` and `{message}`
+/// placeholders, `{{` and `}}` are literal braces. Unknown placeholders are kept as is
+#[derive(Clone)]
+pub struct LogFormat {
+    segments: Vec<Segment>,
+    timestamp_format: String,
+    theme: ColorTheme,
+}
+
+impl Default for LogFormat {
+    fn default() -> Self {
+        Self::new(Self::DEFAULT_TEMPLATE)
+    }
+}
+
+impl LogFormat {
+    pub const DEFAULT_TEMPLATE: &str = "{timestamp} [{label}] {level}: {message}";
+    pub const DEFAULT_TIMESTAMP_FORMAT: &str = "%Y-%m-%d %H:%M:%S";
+
+    pub fn new(template: &str) -> Self {
+        Self {
+            segments: parse_template(template),
+            timestamp_format: Self::DEFAULT_TIMESTAMP_FORMAT.into(),
+            theme: ColorTheme::default(),
+        }
+    }
+
+    /// `chrono` format string of the `{timestamp}` placeholder
+    pub fn with_timestamp_format(self, format: impl Into<String>) -> Self {
+        Self {
+            timestamp_format: format.into(),
+            ..self
+        }
+    }
+
+    pub fn with_theme(self, theme: ColorTheme) -> Self {
+        Self { theme, ..self }
+    }
+
+    pub(crate) fn format(
+        &self,
+        log: &Log,
+        label: &str,
+        timestamp: &DateTime<Local>,
+        color: bool,
+    ) -> String {
+        let mut line = String::new();
+
+        for segment in self.segments.iter() {
+            match segment {
+                Segment::Literal(literal) => line.push_str(literal),
+                Segment::Timestamp => {
+                    line.push_str(&timestamp.format(&self.timestamp_format).to_string())
+                }
+                Segment::Level if color => line.push_str(&format!(
+                    "{}{}{}",
+                    self.theme.level(log.level),
+                    log.level,
+                    escape_sequence!(RESET)
+                )),
+                Segment::Level => line.push_str(&log.level.to_string()),
+                Segment::Label => line.push_str(label),
+                Segment::Target => line.push_str(log.target),
+                Segment::Message => line.push_str(&log.message),
+            }
+        }
+
+        line
+    }
+}
+
+fn parse_template(template: &str) -> Vec<Segment> {
+    let mut segments = Vec::new();
+    let mut literal = String::new();
+    let mut rest = template;
+
+    while let Some(ch) = rest.chars().next() {
+        if rest.starts_with("{{") || rest.starts_with("}}") {
+            literal.push(ch);
+            rest = &rest[2..];
+            continue;
+        }
+
+        let placeholder = (ch == '{')
+            .then(|| rest.find('}'))
+            .flatten()
+            .and_then(|end| {
+                let segment = match &rest[1..end] {
+                    "timestamp" => Segment::Timestamp,
+                    "level" => Segment::Level,
+                    "label" => Segment::Label,
+                    "target" => Segment::Target,
+                    "message" => Segment::Message,
+                    _ => return None,
+                };
+
+                Some((segment, end))
+            });
+
+        match placeholder {
+            Some((segment, end)) => {
+                if !literal.is_empty() {
+                    segments.push(Segment::Literal(std::mem::take(&mut literal)));
+                }
+                segments.push(segment);
+                rest = &rest[end + 1..];
+            }
+            None => {
+                literal.push(ch);
+                rest = &rest[ch.len_utf8()..];
+            }
+        }
+    }
+
+    if !literal.is_empty() {
+        segments.push(Segment::Literal(literal));
+    }
+
+    segments
+}
+
+#[cfg(test)]
+mod test {
+    use chrono::{Local, TimeZone};
+
+    use crate::{Log, LogLevel};
+
+    use super::LogFormat;
+
+    #[test]
+    fn should_format_templates() {
+        let log = Log {
+            target: "render",
+            level: LogLevel::Warn,
+            message: String::from("Hello"),
+        };
+        let timestamp = Local.with_ymd_and_hms(2024, 5, 1, 12, 30, 0).unwrap();
+
+        let format =
+            LogFormat::new("{{{timestamp}}} <{target}/{label}> {level} {unknown}: {message}")
+                .with_timestamp_format("%H:%M");
+
+        assert_eq!(
+            format.format(&log, "Render", &timestamp, false),
+            "{12:30} <render/Render> WARN {unknown}: Hello"
+        );
+
+        let colored = LogFormat::new("{level}").format(&log, "Render", &timestamp, true);
+        assert!(colored.starts_with("\x1b[33m") && colored.ends_with("WARN\x1b[0m"));
+    }
+}
@@ -18,6 +18,9 @@ pub trait LogTarget {
     /// stderr if `level` is higher or equals to [`LogLevel::Error`])
     fn write(&mut self, message: String, level: LogLevel, target: &'static str);
     fn supports_color(&self) -> bool;
+
+    /// Make sure everything written so far reached its destination
+    fn flush(&mut self) {}
 }
 
 pub struct TerminalTarget {
@@ -42,6 +45,11 @@ impl LogTarget for TerminalTarget {
             println!("{message}")
         }
     }
+
+    fn flush(&mut self) {
+        let _ = std::io::stdout().flush();
+        let _ = std::io::stderr().flush();
+    }
 }
 
 pub struct FileTarget {
@@ -1,10 +1,14 @@
 use std::{
+    backtrace::Backtrace,
     collections::BTreeMap,
+    panic::{self, PanicHookInfo},
     sync::{
-        mpsc::{channel, Receiver, Sender, TryRecvError},
-        Mutex, Once, OnceLock,
+        atomic::{AtomicU64, Ordering},
+        mpsc::{channel, sync_channel, Receiver, Sender, SyncSender, TrySendError},
+        Mutex, Once, RwLock,
     },
-    thread::{self, JoinHandle},
+    thread::{self, JoinHandle, ThreadId},
+    time::Duration,
 };
 
 use chrono::Local;
@@ -17,78 +21,177 @@ use crate::{
     Log, LogLevel,
 };
 
-static LOGGING_INIT: Once = Once::new();
-static THREAD_HANDLE: Mutex<Option<JoinHandle<()>>> = Mutex::new(None);
+/// How long a panicking thread waits for the log thread to write out pending logs
+const PANIC_FLUSH_TIMEOUT: Duration = Duration::from_secs(1);
 
-static LOG_SENDER: OnceLock<Sender<Log>> = OnceLock::new();
-static LOGGER_REGISTER_SENDER: OnceLock<Sender<(&'static str, Logger)>> = OnceLock::new();
+static PANIC_HOOK_INIT: Once = Once::new();
+
+static LOGGING_STATE: Mutex<LoggingState> = Mutex::new(LoggingState {
+    ref_count: 0,
+    thread: None,
+});
+
+static LOG_SENDER: RwLock<Option<LogSender>> = RwLock::new(None);
+
+static DROPPED_LOGS: AtomicU64 = AtomicU64::new(0);
+static COALESCED_LOGS: AtomicU64 = AtomicU64::new(0);
+
+/// Log that couldn't be sent under [`OverflowPolicy::Coalesce`] and how many
+/// times it was repeated
+static PENDING_LOG: Mutex<Option<(Log, u64)>> = Mutex::new(None);
+
+/// What [`send_log`] does when the log channel is full
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum OverflowPolicy {
+    /// Wait until the log thread catches up
+    #[default]
+    Block,
+    /// Drop the log and count it in [`LogStats::dropped`]
+    Drop,
+    /// Count repeats of the same log and send it once with the amount of
+    /// repeats when there is room again, different logs are dropped
+    Coalesce,
+}
+
+/// Capacity of the channel between the logging threads and the log thread
+#[derive(Clone, Copy, Debug)]
+pub struct LogChannelConfig {
+    pub capacity: usize,
+    pub overflow_policy: OverflowPolicy,
+}
+
+impl Default for LogChannelConfig {
+    fn default() -> Self {
+        Self {
+            capacity: 4096,
+            overflow_policy: OverflowPolicy::default(),
+        }
+    }
+}
+
+/// Logs lost to the overflow policy since the start of the program
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct LogStats {
+    pub dropped: u64,
+    /// Repeated logs merged into a single one
+    pub coalesced: u64,
+}
+
+struct LogSender {
+    sender: SyncSender<LogThreadMessage>,
+    overflow_policy: OverflowPolicy,
+}
+
+struct LoggingState {
+    ref_count: usize,
+    thread: Option<JoinHandle<()>>,
+}
+
+enum LogThreadMessage {
+    Log(Log),
+    Register(&'static str, Logger),
+    Flush(Sender<()>),
+    Shutdown,
+}
 
 struct LogThreadContext {
     loggers: BTreeMap<&'static str, Logger>,
-    log_recv: Receiver<Log>,
-    register_recv: Receiver<(&'static str, Logger)>,
+    recv: Receiver<LogThreadMessage>,
 }
 
+/// Starts the logging thread, or just adds a reference to an already running one.
+///
+/// Every call must be paired with [`shutdown_logging`], the thread stops when the
+/// last reference is released. Loggers passed to the calls other than the first one
+/// are ignored
 pub fn init_logging(engine_logger: Option<Logger>, app_logger: Option<Logger>) {
-    LOGGING_INIT.call_once(|| {
-        let (log_sender, log_recv) = channel();
-        let (register_sender, register_recv) = channel();
-
-        let log_file = format!("log/{}", Local::now().format("log_%Y_%m_%d__%H_%M_%S.log"));
-
-        let engine_logger = match engine_logger {
-            Some(logger) => logger,
-            None => Logger::builder()
-                .with_label("Engine")
-                .with_target(TerminalTarget::default())
-                .with_target(FileTarget::new(log_file.clone()))
-                .build(),
-        };
-
-        let app_logger = match app_logger {
-            Some(logger) => logger,
-            None => Logger::builder()
-                .with_label("App")
-                .with_target(TerminalTarget::default())
-                .with_target(FileTarget::new(log_file))
-                .build(),
-        };
-
-        let mut ctx = LogThreadContext {
-            loggers: BTreeMap::from([("engine", engine_logger), ("app", app_logger)]),
-            log_recv,
-            register_recv,
-        };
-
-        LOG_SENDER.set(log_sender);
-        LOGGER_REGISTER_SENDER.set(register_sender);
-
-        let handle = thread::spawn(move || {
-            thread_body(ctx);
-        });
+    init_logging_with(engine_logger, app_logger, LogChannelConfig::default())
+}
+
+/// [`init_logging`] with a custom log channel, `config` is ignored if the logging
+/// thread is already running
+pub fn init_logging_with(
+    engine_logger: Option<Logger>,
+    app_logger: Option<Logger>,
+    config: LogChannelConfig,
+) {
+    let mut state = LOGGING_STATE.lock().unwrap_or_else(|err| err.into_inner());
+
+    state.ref_count += 1;
+
+    if state.thread.is_some() {
+        return;
+    }
 
-        let _ = THREAD_HANDLE
-            .lock()
-            .unwrap()
-            .replace(handle)
-            .is_none_or(|_| panic!("Somehow logging thread got initialized more than once"));
+    let (sender, recv) = sync_channel(config.capacity.max(1));
+
+    let log_file = format!("log/{}", Local::now().format("log_%Y_%m_%d__%H_%M_%S.log"));
+
+    let engine_logger = match engine_logger {
+        Some(logger) => logger,
+        None => Logger::builder()
+            .with_label("Engine")
+            .with_target(TerminalTarget::default())
+            .with_target(FileTarget::new(log_file.clone()))
+            .build(),
+    };
+
+    let app_logger = match app_logger {
+        Some(logger) => logger,
+        None => Logger::builder()
+            .with_label("App")
+            .with_target(TerminalTarget::default())
+            .with_target(FileTarget::new(log_file))
+            .build(),
+    };
+
+    let ctx = LogThreadContext {
+        loggers: BTreeMap::from([("engine", engine_logger), ("app", app_logger)]),
+        recv,
+    };
+
+    *LOG_SENDER.write().unwrap_or_else(|err| err.into_inner()) = Some(LogSender {
+        sender,
+        overflow_policy: config.overflow_policy,
     });
+
+    let handle = thread::Builder::new()
+        .name("bizarre_log".into())
+        .spawn(move || thread_body(ctx))
+        .unwrap_or_else(|err| panic!("Failed to spawn logging thread: {err}"));
+
+    state.thread = Some(handle);
+
+    PANIC_HOOK_INIT.call_once(install_panic_hook);
 }
 
+/// Releases a reference taken by [`init_logging`]. The last one writes out
+/// all pending logs and stops the logging thread
 pub fn shutdown_logging() {
-    if !LOGGING_INIT.is_completed() {
+    let mut state = LOGGING_STATE.lock().unwrap_or_else(|err| err.into_inner());
+
+    if state.ref_count == 0 {
         return;
     }
 
-    if let Some(handle) = THREAD_HANDLE.lock().unwrap().take() {
-        LOG_SENDER
-            .get()
-            .unwrap()
-            .send(Log {
-                target: "__system",
-                level: LogLevel::Info,
-                message: "__SHUTDOWN".to_string(),
-            })
+    state.ref_count -= 1;
+
+    if state.ref_count > 0 {
+        return;
+    }
+
+    let sender = LOG_SENDER
+        .write()
+        .unwrap_or_else(|err| err.into_inner())
+        .take();
+
+    if let (Some(LogSender { sender, .. }), Some(handle)) = (sender, state.thread.take()) {
+        if let Some(log) = take_pending_log() {
+            let _ = sender.send(LogThreadMessage::Log(log));
+        }
+
+        sender
+            .send(LogThreadMessage::Shutdown)
             .unwrap_or_else(|err| panic!("Failed to send log: {err}"));
 
         handle.join().unwrap_or_else(|err| {
@@ -97,61 +200,312 @@ pub fn shutdown_logging() {
     }
 }
 
-pub fn send_log(log: Log) {
+/// Returns `true` if there is a running logging thread
+pub fn is_logging_initialized() -> bool {
     LOG_SENDER
-        .get()
-        .unwrap()
-        .send(log)
-        .unwrap_or_else(|err| panic!("Could not send log: {err}"));
+        .read()
+        .map(|sender| sender.is_some())
+        .unwrap_or(false)
+}
+
+/// Blocks until all logs sent before this call are written, or `timeout` passes.
+///
+/// Returns `false` if the logs could not be flushed in time
+pub fn flush_logging(timeout: Duration) -> bool {
+    if let Some(log) = take_pending_log() {
+        send_message(LogThreadMessage::Log(log));
+    }
+
+    let (ack_sender, ack_recv) = channel();
+
+    if !send_message(LogThreadMessage::Flush(ack_sender)) {
+        return false;
+    }
+
+    ack_recv.recv_timeout(timeout).is_ok()
+}
+
+/// Sends the log to the logging thread, a full log channel is handled
+/// according to its [`OverflowPolicy`]
+pub fn send_log(log: Log) {
+    let sender = LOG_SENDER.read().unwrap_or_else(|err| err.into_inner());
+
+    let Some(LogSender {
+        sender,
+        overflow_policy,
+    }) = sender.as_ref()
+    else {
+        panic!("Could not send log: logging is not initialized");
+    };
+
+    match overflow_policy {
+        OverflowPolicy::Block => {
+            let _ = sender.send(LogThreadMessage::Log(log));
+        }
+        OverflowPolicy::Drop => send_dropping(sender, log),
+        OverflowPolicy::Coalesce => send_coalesced(sender, log),
+    }
+}
+
+fn send_dropping(sender: &SyncSender<LogThreadMessage>, log: Log) {
+    if let Err(TrySendError::Full(_)) = sender.try_send(LogThreadMessage::Log(log)) {
+        DROPPED_LOGS.fetch_add(1, Ordering::Relaxed);
+    }
+}
+
+fn send_coalesced(sender: &SyncSender<LogThreadMessage>, log: Log) {
+    let mut pending = PENDING_LOG.lock().unwrap_or_else(|err| err.into_inner());
+
+    // The delayed log goes first, so the order of logs is kept
+    if let Some((pending_log, repeats)) = pending.take() {
+        let delayed = with_repeats(&pending_log, repeats);
+
+        if let Err(TrySendError::Full(_)) = sender.try_send(LogThreadMessage::Log(delayed)) {
+            *pending = Some((pending_log, repeats));
+        }
+    }
+
+    let Err(TrySendError::Full(LogThreadMessage::Log(log))) =
+        sender.try_send(LogThreadMessage::Log(log))
+    else {
+        return;
+    };
+
+    match pending.as_mut() {
+        Some((pending_log, repeats))
+            if pending_log.target == log.target
+                && pending_log.level == log.level
+                && pending_log.message == log.message =>
+        {
+            *repeats += 1;
+            COALESCED_LOGS.fetch_add(1, Ordering::Relaxed);
+        }
+        Some(_) => {
+            DROPPED_LOGS.fetch_add(1, Ordering::Relaxed);
+        }
+        None => *pending = Some((log, 1)),
+    }
+}
+
+/// Takes the log delayed by [`OverflowPolicy::Coalesce`] with its repeats
+/// added to the message
+fn take_pending_log() -> Option<Log> {
+    let (log, repeats) = PENDING_LOG
+        .lock()
+        .unwrap_or_else(|err| err.into_inner())
+        .take()?;
+
+    Some(with_repeats(&log, repeats))
+}
+
+fn with_repeats(log: &Log, repeats: u64) -> Log {
+    let message = match repeats {
+        1 => log.message.clone(),
+        repeats => format!("{} (repeated {repeats} times)", log.message),
+    };
+
+    Log { message, ..*log }
+}
+
+/// Amount of logs dropped or coalesced because the log channel was full
+pub fn log_stats() -> LogStats {
+    LogStats {
+        dropped: DROPPED_LOGS.load(Ordering::Relaxed),
+        coalesced: COALESCED_LOGS.load(Ordering::Relaxed),
+    }
 }
 
 pub fn register_logger(name: &'static str, logger: Logger) {
-    LOGGER_REGISTER_SENDER
-        .get()
-        .unwrap()
-        .send((name, logger))
-        .unwrap_or_else(|err| panic!("Could not register logger: {err}"));
+    if !send_message(LogThreadMessage::Register(name, logger)) {
+        panic!("Could not register logger: logging is not initialized");
+    }
 
     core_trace!("Registered logger `{name}`");
     log!(name, LogLevel::Trace, "Start of this log");
 }
 
+fn send_message(message: LogThreadMessage) -> bool {
+    let sender = LOG_SENDER.read().unwrap_or_else(|err| err.into_inner());
+
+    match sender.as_ref() {
+        Some(LogSender { sender, .. }) => sender.send(message).is_ok(),
+        None => false,
+    }
+}
+
+fn log_thread_id() -> Option<ThreadId> {
+    LOGGING_STATE
+        .try_lock()
+        .ok()?
+        .thread
+        .as_ref()
+        .map(|handle| handle.thread().id())
+}
+
+/// Routes panics through `core_fatal!` and waits for the log to be written, so
+/// the crash info ends up in the log files. Falls back to the previous hook when
+/// logging is not running or the log thread itself panicked
+fn install_panic_hook() {
+    let prev_hook = panic::take_hook();
+
+    panic::set_hook(Box::new(move |info| {
+        let on_log_thread = log_thread_id() == Some(thread::current().id());
+
+        if on_log_thread || !is_logging_initialized() {
+            prev_hook(info);
+            return;
+        }
+
+        let backtrace = Backtrace::force_capture();
+
+        // Same as `core_fatal!`, but must not panic inside of the panic hook
+        let logged = send_message(LogThreadMessage::Log(Log {
+            target: "engine",
+            level: LogLevel::Fatal,
+            message: format!("{}\nBacktrace:\n{backtrace}", panic_message(info)),
+        }));
+
+        if !logged || !flush_logging(PANIC_FLUSH_TIMEOUT) {
+            prev_hook(info);
+        }
+    }));
+}
+
+fn panic_message(info: &PanicHookInfo) -> String {
+    let payload = info
+        .payload()
+        .downcast_ref::<&str>()
+        .copied()
+        .or_else(|| info.payload().downcast_ref::<String>().map(String::as_str))
+        .unwrap_or("Box<dyn Any>");
+
+    let thread = thread::current();
+    let thread_name = thread.name().unwrap_or("<unnamed>");
+
+    match info.location() {
+        Some(location) => format!("Thread '{thread_name}' panicked at {location}:\n{payload}"),
+        None => format!("Thread '{thread_name}' panicked:\n{payload}"),
+    }
+}
+
 #[inline]
 fn thread_body(mut ctx: LogThreadContext) {
-    loop {
-        match ctx.log_recv.recv() {
-            Ok(log) => {
-                if log.target == "__system" && log.message == "__SHUTDOWN" {
-                    break;
-                }
-
-                match ctx.register_recv.try_recv() {
-                    Ok((name, logger)) => {
-                        ctx.loggers.insert(name, logger);
-                    }
-                    Err(TryRecvError::Empty) => {}
-                    Err(err) => panic!("Failed to get log register requrest: {err}"),
-                }
-
-                if let Some(logger) = ctx.loggers.get_mut(log.target) {
-                    logger.log(log);
-                } else {
-                    let engine_logger = ctx.loggers.get_mut("engine").unwrap();
-
-                    engine_logger.log(Log {
-                    target: "engine",
-                    level: LogLevel::Error,
-                    message: format!("Could not find a logger named `{}`, here's what ment to be sent to that logger:", log.target),
-                });
-
-                    engine_logger.log(Log {
-                        target: "engine",
-                        level: log.level,
-                        message: log.message,
-                    });
-                }
+    while let Ok(message) = ctx.recv.recv() {
+        match message {
+            LogThreadMessage::Log(log) => ctx.log(log),
+            LogThreadMessage::Register(name, logger) => {
+                ctx.loggers.insert(name, logger);
             }
-            Err(_) => todo!(),
+            LogThreadMessage::Flush(ack) => {
+                ctx.flush();
+                let _ = ack.send(());
+            }
+            LogThreadMessage::Shutdown => break,
+        }
+    }
+
+    ctx.flush();
+}
+
+impl LogThreadContext {
+    fn log(&mut self, log: Log) {
+        if let Some(logger) = self.loggers.get_mut(log.target) {
+            logger.log(log);
+        } else {
+            let engine_logger = self.loggers.get_mut("engine").unwrap();
+
+            engine_logger.log(Log {
+                target: "engine",
+                level: LogLevel::Error,
+                message: format!("Could not find a logger named `{}`, here's what ment to be sent to that logger:", log.target),
+            });
+
+            engine_logger.log(Log {
+                target: "engine",
+                level: log.level,
+                message: log.message,
+            });
+        }
+    }
+
+    fn flush(&mut self) {
+        self.loggers.values_mut().for_each(Logger::flush);
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::sync::{
+        mpsc::{sync_channel, Receiver},
+        Mutex,
+    };
+
+    use crate::{Log, LogLevel};
+
+    use super::{log_stats, send_coalesced, send_dropping, take_pending_log, LogThreadMessage};
+
+    /// Tests below rely on the global overflow counters and the pending log
+    static OVERFLOW_TESTS: Mutex<()> = Mutex::new(());
+
+    fn log(message: &str) -> Log {
+        Log {
+            target: "engine",
+            level: LogLevel::Info,
+            message: message.into(),
+        }
+    }
+
+    fn received(recv: &Receiver<LogThreadMessage>) -> Vec<String> {
+        recv.try_iter()
+            .map(|message| match message {
+                LogThreadMessage::Log(log) => log.message,
+                _ => panic!("Expected only logs in the channel"),
+            })
+            .collect()
+    }
+
+    #[test]
+    fn should_drop_logs_when_full() {
+        let _guard = OVERFLOW_TESTS.lock().unwrap_or_else(|err| err.into_inner());
+
+        let (sender, recv) = sync_channel(2);
+        let before = log_stats();
+
+        for message in ["first", "second", "third"] {
+            send_dropping(&sender, log(message));
         }
+
+        assert_eq!(received(&recv), ["first", "second"]);
+        assert_eq!(log_stats().dropped - before.dropped, 1);
+        assert_eq!(log_stats().coalesced, before.coalesced);
+    }
+
+    #[test]
+    fn should_coalesce_repeated_logs_when_full() {
+        let _guard = OVERFLOW_TESTS.lock().unwrap_or_else(|err| err.into_inner());
+
+        let (sender, recv) = sync_channel(1);
+        let before = log_stats();
+
+        send_coalesced(&sender, log("first"));
+        // Delayed while the channel is full, then counted as a repeat
+        send_coalesced(&sender, log("repeated"));
+        send_coalesced(&sender, log("repeated"));
+        // Different from the delayed log, dropped
+        send_coalesced(&sender, log("dropped"));
+
+        assert_eq!(received(&recv), ["first"]);
+
+        // The delayed log goes before the new one, which is delayed in turn
+        send_coalesced(&sender, log("last"));
+        assert_eq!(received(&recv), ["repeated (repeated 2 times)"]);
+        assert_eq!(
+            take_pending_log().map(|log| log.message).as_deref(),
+            Some("last")
+        );
+
+        let stats = log_stats();
+        assert_eq!(stats.coalesced - before.coalesced, 1);
+        assert_eq!(stats.dropped - before.dropped, 1);
     }
 }
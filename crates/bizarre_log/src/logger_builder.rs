@@ -1,6 +1,7 @@
 use std::marker::PhantomData;
 
-use crate::logger::Logger;
+use crate::log_format::LogFormat;
+use crate::logger::{FormattedTarget, Logger};
 use crate::LogLevel;
 
 use crate::log_target::LogTarget;
@@ -24,7 +25,9 @@ impl BuilderTypeState for HasLabel {}
 impl BuilderTypeState for NoLabel {}
 
 pub struct LoggerBuilder<T: BuilderTypeState, L: BuilderTypeState> {
-    pub(crate) targets: Vec<Box<dyn LogTarget + Send>>,
+    /// Targets with their own format, `None` uses the logger format
+    pub(crate) targets: Vec<(Box<dyn LogTarget + Send>, Option<LogFormat>)>,
+    pub(crate) format: LogFormat,
     pub(crate) label: Option<&'static str>,
     pub(crate) min_level: LogLevel,
     pub(crate) _phantom: PhantomData<(T, L)>,
@@ -34,6 +37,7 @@ impl<T: BuilderTypeState, L: BuilderTypeState> LoggerBuilder<T, L> {
     pub fn new() -> LoggerBuilder<NoTargets, NoLabel> {
         LoggerBuilder {
             targets: Vec::new(),
+            format: LogFormat::default(),
             label: None,
             min_level: LogLevel::default(),
             _phantom: PhantomData,
@@ -52,7 +56,7 @@ impl<T: BuilderTypeState, L: BuilderTypeState> LoggerBuilder<T, L> {
     where
         Target: LogTarget + Send + 'static,
     {
-        self.targets.push(Box::new(target));
+        self.targets.push((Box::new(target), None));
 
         LoggerBuilder {
             _phantom: PhantomData,
@@ -60,6 +64,28 @@ impl<T: BuilderTypeState, L: BuilderTypeState> LoggerBuilder<T, L> {
         }
     }
 
+    /// Adds a target that formats its lines with `format` instead of the logger format
+    pub fn with_formatted_target<Target>(
+        mut self,
+        target: Target,
+        format: LogFormat,
+    ) -> LoggerBuilder<HasTargets, L>
+    where
+        Target: LogTarget + Send + 'static,
+    {
+        self.targets.push((Box::new(target), Some(format)));
+
+        LoggerBuilder {
+            _phantom: PhantomData,
+            ..self
+        }
+    }
+
+    /// Format of the targets added without their own one
+    pub fn with_format(self, format: LogFormat) -> Self {
+        Self { format, ..self }
+    }
+
     pub fn with_min_level(self, level: LogLevel) -> Self {
         Self {
             min_level: level,
@@ -73,7 +99,14 @@ impl LoggerBuilder<HasTargets, HasLabel> {
         Logger {
             label: self.label.unwrap(),
             min_level: self.min_level,
-            targets: self.targets.into_boxed_slice(),
+            targets: self
+                .targets
+                .into_iter()
+                .map(|(target, format)| FormattedTarget {
+                    target,
+                    format: format.unwrap_or_else(|| self.format.clone()),
+                })
+                .collect(),
         }
     }
 }
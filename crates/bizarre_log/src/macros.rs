@@ -16,8 +16,37 @@ macro_rules! log {
     };
 }
 
+/// Logs only the first time the call site is reached
+#[macro_export]
+macro_rules! log_once {
+    ($name: expr, $log_level: expr, $($args:tt)*) => {{
+        static SITE: $crate::rate_limit::LogSite = $crate::rate_limit::LogSite::new();
+
+        if SITE.once() {
+            $crate::log!($name, $log_level, $($args)*)
+        }
+    }};
+}
+
+/// Logs at most once per `period` from the call site, the next log after the
+/// period says how many times the call site was suppressed
+#[macro_export]
+macro_rules! log_throttle {
+    ($name: expr, $log_level: expr, $period: expr, $($args:tt)*) => {{
+        static SITE: $crate::rate_limit::LogSite = $crate::rate_limit::LogSite::new();
+
+        if let Some(suppressed) = SITE.throttle($period) {
+            $crate::send_log($crate::Log {
+                target: $name,
+                level: $log_level,
+                message: $crate::rate_limit::with_repeats(format!($($args)*), suppressed),
+            })
+        }
+    }};
+}
+
 macro_rules! gen_log_macro_impl {
-    ($macro_name: tt, $default_logger: tt, $log_level: tt) => {
+    ($macro_name: tt, $once_name: tt, $throttle_name: tt, $default_logger: tt, $log_level: tt) => {
         #[macro_export]
         macro_rules! $macro_name {
             ($$logger: tt: $$($$args: tt)*) => {
@@ -28,31 +57,52 @@ macro_rules! gen_log_macro_impl {
             }
         }
 
-        pub(crate) use $macro_name;
+        #[macro_export]
+        macro_rules! $once_name {
+            ($$logger: tt: $$($$args: tt)*) => {
+                $crate::log_once!(stringify!($$logger), $crate::LogLevel::$log_level, $$($$args)*)
+            };
+            ($$($$args: tt)*) => {
+                $crate::log_once!(stringify!($default_logger), $crate::LogLevel::$log_level, $$($$args)*)
+            }
+        }
+
+        #[macro_export]
+        macro_rules! $throttle_name {
+            ($$logger: tt: $$period: expr, $$($$args: tt)*) => {
+                $crate::log_throttle!(stringify!($$logger), $crate::LogLevel::$log_level, $$period, $$($$args)*)
+            };
+            ($$period: expr, $$($$args: tt)*) => {
+                $crate::log_throttle!(stringify!($default_logger), $crate::LogLevel::$log_level, $$period, $$($$args)*)
+            }
+        }
+
+        #[allow(unused_imports)]
+        pub(crate) use {$macro_name, $once_name, $throttle_name};
     }
 }
 
 macro_rules! gen_log_macros {
     ($($logger: tt {
-        $($macro_name:tt => $log_level: ident),+ $(,)?
+        $($macro_name:tt, $once_name: tt, $throttle_name: tt => $log_level: ident),+ $(,)?
     });+;) => {
-        $($(gen_log_macro_impl!($macro_name, $logger, $log_level);)+)+
+        $($(gen_log_macro_impl!($macro_name, $once_name, $throttle_name, $logger, $log_level);)+)+
     };
 }
 
 gen_log_macros! {
     engine {
-        core_trace => Trace,
-        core_info => Info,
-        core_warn => Warn,
-        core_error => Error,
-        core_fatal => Fatal,
+        core_trace, core_trace_once, core_trace_throttle => Trace,
+        core_info, core_info_once, core_info_throttle => Info,
+        core_warn, core_warn_once, core_warn_throttle => Warn,
+        core_error, core_error_once, core_error_throttle => Error,
+        core_fatal, core_fatal_once, core_fatal_throttle => Fatal,
     };
     app {
-        trace => Trace,
-        info => Info,
-        warning => Warn,
-        error => Error,
-        fatal => Fatal,
+        trace, trace_once, trace_throttle => Trace,
+        info, info_once, info_throttle => Info,
+        warning, warn_once, warn_throttle => Warn,
+        error, error_once, error_throttle => Error,
+        fatal, fatal_once, fatal_throttle => Fatal,
     };
 }
@@ -0,0 +1,211 @@
+use std::sync::{
+    atomic::{fence, AtomicU64, AtomicU8, AtomicUsize, Ordering},
+    Arc,
+};
+
+use crate::{log_target::LogTarget, LogLevel};
+
+/// Longest message kept by a [`MemoryLog`], longer ones are cut on a char boundary
+pub const MEMORY_RECORD_LEN: usize = 256;
+
+const RECORD_WORDS: usize = MEMORY_RECORD_LEN / size_of::<u64>();
+
+const LEVELS: [LogLevel; 5] = [
+    LogLevel::Trace,
+    LogLevel::Info,
+    LogLevel::Warn,
+    LogLevel::Error,
+    LogLevel::Fatal,
+];
+
+/// Log line read out of a [`MemoryLog`]
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct MemoryRecord {
+    /// Position of the record among all the records ever written to the log
+    pub sequence: u64,
+    pub level: LogLevel,
+    pub message: String,
+}
+
+/// Slot of the ring guarded by a sequence lock. `sequence` is odd while the slot
+/// is written and `2 * (n + 1)` once it holds the record `n`
+struct Slot {
+    sequence: AtomicU64,
+    level: AtomicU8,
+    len: AtomicUsize,
+    words: [AtomicU64; RECORD_WORDS],
+}
+
+impl Slot {
+    fn new() -> Self {
+        Self {
+            sequence: AtomicU64::new(0),
+            level: AtomicU8::new(0),
+            len: AtomicUsize::new(0),
+            words: std::array::from_fn(|_| AtomicU64::new(0)),
+        }
+    }
+}
+
+/// Ring of the last log lines, written by the log thread through [`MemoryTarget`]
+/// and read from any thread without locks.
+///
+/// Readers never block the log thread, a record overwritten while it's read is
+/// skipped instead
+pub struct MemoryLog {
+    slots: Box<[Slot]>,
+    written: AtomicU64,
+}
+
+impl MemoryLog {
+    pub fn new(capacity: usize) -> Arc<Self> {
+        Arc::new(Self {
+            slots: (0..capacity.max(1)).map(|_| Slot::new()).collect(),
+            written: AtomicU64::new(0),
+        })
+    }
+
+    pub fn capacity(&self) -> usize {
+        self.slots.len()
+    }
+
+    /// Amount of records written since the log was created
+    pub fn written(&self) -> u64 {
+        self.written.load(Ordering::Acquire)
+    }
+
+    /// Records still in the ring, oldest first
+    pub fn records(&self) -> Vec<MemoryRecord> {
+        self.records_since(0)
+    }
+
+    /// Records with a sequence of at least `sequence` still in the ring, oldest first.
+    ///
+    /// Pass the sequence after the last record seen to read only the new ones
+    pub fn records_since(&self, sequence: u64) -> Vec<MemoryRecord> {
+        let written = self.written();
+        let first = written.saturating_sub(self.capacity() as u64).max(sequence);
+
+        (first..written)
+            .filter_map(|sequence| self.read(sequence))
+            .collect()
+    }
+
+    fn read(&self, sequence: u64) -> Option<MemoryRecord> {
+        let slot = &self.slots[sequence as usize % self.capacity()];
+        let expected = 2 * (sequence + 1);
+
+        if slot.sequence.load(Ordering::Acquire) != expected {
+            return None;
+        }
+
+        let level = LEVELS[slot.level.load(Ordering::Relaxed) as usize];
+        let len = slot.len.load(Ordering::Relaxed).min(MEMORY_RECORD_LEN);
+        let bytes = slot
+            .words
+            .iter()
+            .flat_map(|word| word.load(Ordering::Relaxed).to_ne_bytes())
+            .take(len)
+            .collect::<Vec<_>>();
+
+        fence(Ordering::Acquire);
+
+        if slot.sequence.load(Ordering::Relaxed) != expected {
+            return None;
+        }
+
+        Some(MemoryRecord {
+            sequence,
+            level,
+            message: String::from_utf8(bytes).ok()?,
+        })
+    }
+
+    /// Must be called from one thread at a time, the log thread does all the writes
+    fn write(&self, message: &str, level: LogLevel) {
+        let sequence = self.written.load(Ordering::Relaxed);
+        let slot = &self.slots[sequence as usize % self.capacity()];
+
+        let mut len = message.len().min(MEMORY_RECORD_LEN);
+        while !message.is_char_boundary(len) {
+            len -= 1;
+        }
+
+        slot.sequence.store(2 * sequence + 1, Ordering::Relaxed);
+        fence(Ordering::Release);
+
+        slot.level.store(level as u8, Ordering::Relaxed);
+        slot.len.store(len, Ordering::Relaxed);
+
+        for (word, chunk) in slot.words.iter().zip(message.as_bytes()[..len].chunks(8)) {
+            let mut bytes = [0; 8];
+            bytes[..chunk.len()].copy_from_slice(chunk);
+            word.store(u64::from_ne_bytes(bytes), Ordering::Relaxed);
+        }
+
+        slot.sequence.store(2 * (sequence + 1), Ordering::Release);
+        self.written.store(sequence + 1, Ordering::Release);
+    }
+}
+
+/// Keeps the last log lines in a [`MemoryLog`], so they can be shown on screen
+/// without reading the log files back.
+///
+/// Targets sharing a log must be written from one thread, which is the case for
+/// the ones registered with loggers
+pub struct MemoryTarget {
+    log: Arc<MemoryLog>,
+}
+
+impl MemoryTarget {
+    pub fn new(log: Arc<MemoryLog>) -> Self {
+        Self { log }
+    }
+
+    pub fn log(&self) -> &Arc<MemoryLog> {
+        &self.log
+    }
+}
+
+impl LogTarget for MemoryTarget {
+    fn supports_color(&self) -> bool {
+        false
+    }
+
+    fn write(&mut self, message: String, level: LogLevel, _: &'static str) {
+        self.log.write(&message, level);
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::{log_target::LogTarget, LogLevel};
+
+    use super::{MemoryLog, MemoryTarget, MEMORY_RECORD_LEN};
+
+    #[test]
+    fn should_keep_last_records() {
+        let log = MemoryLog::new(3);
+        let mut target = MemoryTarget::new(log.clone());
+
+        for i in 0..5 {
+            target.write(format!("Record {i}"), LogLevel::Info, "engine");
+        }
+        target.write("ж".repeat(MEMORY_RECORD_LEN), LogLevel::Error, "engine");
+
+        let records = log.records();
+        let messages = records
+            .iter()
+            .map(|record| record.message.as_str())
+            .collect::<Vec<_>>();
+
+        assert_eq!(log.written(), 6);
+        assert_eq!(messages[..2], ["Record 3", "Record 4"]);
+        assert_eq!(records[2].level, LogLevel::Error);
+        assert_eq!(records[2].message, "ж".repeat(MEMORY_RECORD_LEN / 2));
+
+        let new = log.records_since(5);
+        assert_eq!(new.len(), 1);
+        assert_eq!(new[0].sequence, 5);
+    }
+}
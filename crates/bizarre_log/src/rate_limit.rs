@@ -0,0 +1,88 @@
+use std::{
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Mutex,
+    },
+    time::{Duration, Instant},
+};
+
+/// State of a single call site of the `*_once` and `*_throttle` macros
+pub struct LogSite {
+    logged: AtomicBool,
+    /// Time of the last log and the amount of logs suppressed since then
+    throttle: Mutex<Option<(Instant, u64)>>,
+}
+
+impl Default for LogSite {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl LogSite {
+    pub const fn new() -> Self {
+        Self {
+            logged: AtomicBool::new(false),
+            throttle: Mutex::new(None),
+        }
+    }
+
+    /// Returns `true` only the first time it's called
+    pub fn once(&self) -> bool {
+        !self.logged.swap(true, Ordering::Relaxed)
+    }
+
+    /// Returns the amount of suppressed logs if `period` has passed since the
+    /// last log, `None` if this one has to be suppressed as well
+    pub fn throttle(&self, period: Duration) -> Option<u64> {
+        let mut state = self.throttle.lock().unwrap_or_else(|err| err.into_inner());
+        let now = Instant::now();
+
+        match &mut *state {
+            Some((last, suppressed)) if now.duration_since(*last) < period => {
+                *suppressed += 1;
+                None
+            }
+            state => {
+                let suppressed = state.map_or(0, |(_, suppressed)| suppressed);
+                *state = Some((now, 0));
+                Some(suppressed)
+            }
+        }
+    }
+}
+
+/// Appends the summary of the suppressed repeats to `message`
+pub fn with_repeats(message: String, suppressed: u64) -> String {
+    match suppressed {
+        0 => message,
+        1 => format!("{message} (repeated 1 time)"),
+        n => format!("{message} (repeated {n} times)"),
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::time::Duration;
+
+    use super::{with_repeats, LogSite};
+
+    #[test]
+    fn should_suppress_repeats_within_period() {
+        let site = LogSite::new();
+        assert!(site.once());
+        assert!(!site.once());
+
+        let period = Duration::from_millis(50);
+        assert_eq!(site.throttle(period), Some(0));
+        assert_eq!(site.throttle(period), None);
+        assert_eq!(site.throttle(period), None);
+
+        std::thread::sleep(period);
+        assert_eq!(site.throttle(period), Some(2));
+        assert_eq!(
+            with_repeats(String::from("Suboptimal swapchain"), 2),
+            "Suboptimal swapchain (repeated 2 times)"
+        );
+    }
+}
@@ -0,0 +1,97 @@
+use std::net::{SocketAddr, TcpStream, ToSocketAddrs};
+
+use bizarre_event::EventQueue;
+
+use crate::{
+    connection::{Connection, ConnectionId},
+    error::{NetError, NetResult},
+    message::MessageRegistry,
+    network::NetEvent,
+};
+
+/// Connection to a [`NetServer`](crate::NetServer), received messages come from
+/// [`ConnectionId::SERVER`]
+pub struct NetClient {
+    connection: Option<Connection>,
+    pending_events: Vec<NetEvent>,
+}
+
+impl NetClient {
+    /// Blocks until the server accepts the connection
+    pub fn connect(addr: impl ToSocketAddrs) -> NetResult<Self> {
+        let connection = Connection::new(TcpStream::connect(addr)?)?;
+
+        Ok(Self {
+            connection: Some(connection),
+            pending_events: vec![NetEvent::Connected(ConnectionId::SERVER)],
+        })
+    }
+
+    pub fn is_connected(&self) -> bool {
+        self.connection.is_some()
+    }
+
+    pub fn server_addr(&self) -> Option<SocketAddr> {
+        self.connection.as_ref().map(Connection::peer_addr)
+    }
+
+    /// Closes the connection, [`NetEvent::Disconnected`] is pushed on the next poll
+    pub fn disconnect(&mut self) {
+        if let Some(mut connection) = self.connection.take() {
+            connection.shutdown();
+            self.pending_events.push(NetEvent::Disconnected {
+                connection: ConnectionId::SERVER,
+                error: None,
+            });
+        }
+    }
+
+    pub(crate) fn send_frame(&mut self, frame: &[u8]) -> NetResult<()> {
+        self.connection
+            .as_mut()
+            .ok_or(NetError::UnknownConnection(ConnectionId::SERVER))?
+            .send_frame(frame)
+    }
+
+    pub(crate) fn poll(&mut self, registry: &MessageRegistry, events: &mut EventQueue) {
+        if let Some(connection) = &mut self.connection {
+            let received = connection.flush().and_then(|_| connection.receive());
+
+            let error = match received {
+                Ok(received) => {
+                    let decoded = received
+                        .frames
+                        .iter()
+                        .try_for_each(|frame| registry.decode(ConnectionId::SERVER, frame, events));
+
+                    match decoded {
+                        Ok(()) if received.closed => Some(None),
+                        Ok(()) => None,
+                        Err(err) => Some(Some(err.to_string())),
+                    }
+                }
+                Err(err) => Some(Some(err.to_string())),
+            };
+
+            if let Some(error) = error {
+                self.connection = None;
+                self.pending_events.push(NetEvent::Disconnected {
+                    connection: ConnectionId::SERVER,
+                    error,
+                });
+            }
+        }
+
+        self.pending_events
+            .drain(..)
+            .for_each(|event| events.push_event(event));
+    }
+}
+
+impl Drop for NetClient {
+    fn drop(&mut self) {
+        if let Some(connection) = &mut self.connection {
+            connection.shutdown();
+        }
+    }
+}
@@ -0,0 +1,141 @@
+use std::{
+    io::{self, ErrorKind, Read, Write},
+    net::{Shutdown, SocketAddr, TcpStream},
+};
+
+use crate::error::{NetError, NetResult};
+
+/// Frames larger than that are rejected on both ends
+pub const MAX_FRAME_SIZE: usize = 16 * 1024 * 1024;
+
+/// Size of the length prefix of a frame
+const HEADER_SIZE: usize = size_of::<u32>();
+
+#[derive(Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Debug)]
+pub struct ConnectionId(pub(crate) u64);
+
+impl ConnectionId {
+    /// Connection of a client to its server
+    pub const SERVER: ConnectionId = ConnectionId(0);
+
+    pub fn as_raw(&self) -> u64 {
+        self.0
+    }
+}
+
+/// Frames received since the last poll
+pub(crate) struct Received {
+    pub frames: Vec<Vec<u8>>,
+    /// The peer closed the connection after sending the frames
+    pub closed: bool,
+}
+
+/// Non-blocking TCP stream sending length prefixed frames
+pub(crate) struct Connection {
+    stream: TcpStream,
+    peer_addr: SocketAddr,
+    read_buffer: Vec<u8>,
+    write_buffer: Vec<u8>,
+}
+
+impl Connection {
+    pub fn new(stream: TcpStream) -> io::Result<Self> {
+        stream.set_nonblocking(true)?;
+        stream.set_nodelay(true)?;
+
+        Ok(Self {
+            peer_addr: stream.peer_addr()?,
+            stream,
+            read_buffer: Vec::new(),
+            write_buffer: Vec::new(),
+        })
+    }
+
+    pub fn peer_addr(&self) -> SocketAddr {
+        self.peer_addr
+    }
+
+    /// Queues the frame and writes as much as the socket accepts right away
+    pub fn send_frame(&mut self, frame: &[u8]) -> NetResult<()> {
+        if frame.len() > MAX_FRAME_SIZE {
+            return Err(NetError::FrameTooLarge {
+                size: frame.len(),
+                limit: MAX_FRAME_SIZE,
+            });
+        }
+
+        self.write_buffer
+            .extend_from_slice(&(frame.len() as u32).to_le_bytes());
+        self.write_buffer.extend_from_slice(frame);
+
+        self.flush()
+    }
+
+    /// Writes the queued frames until the socket would block
+    pub fn flush(&mut self) -> NetResult<()> {
+        while !self.write_buffer.is_empty() {
+            match self.stream.write(&self.write_buffer) {
+                Ok(0) => return Err(io::Error::from(ErrorKind::WriteZero).into()),
+                Ok(written) => {
+                    self.write_buffer.drain(..written);
+                }
+                Err(err) if err.kind() == ErrorKind::WouldBlock => break,
+                Err(err) if err.kind() == ErrorKind::Interrupted => continue,
+                Err(err) => return Err(err.into()),
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Reads everything available and returns the frames that arrived completely
+    pub fn receive(&mut self) -> NetResult<Received> {
+        let mut chunk = [0u8; 4096];
+        let mut closed = false;
+
+        loop {
+            match self.stream.read(&mut chunk) {
+                Ok(0) => {
+                    closed = true;
+                    break;
+                }
+                Ok(read) => self.read_buffer.extend_from_slice(&chunk[..read]),
+                Err(err) if err.kind() == ErrorKind::WouldBlock => break,
+                Err(err) if err.kind() == ErrorKind::Interrupted => continue,
+                Err(err) => return Err(err.into()),
+            }
+        }
+
+        let mut frames = Vec::new();
+        let mut offset = 0;
+
+        while self.read_buffer.len() - offset >= HEADER_SIZE {
+            let header = &self.read_buffer[offset..offset + HEADER_SIZE];
+            let size = u32::from_le_bytes(header.try_into().unwrap()) as usize;
+
+            if size > MAX_FRAME_SIZE {
+                return Err(NetError::FrameTooLarge {
+                    size,
+                    limit: MAX_FRAME_SIZE,
+                });
+            }
+
+            let start = offset + HEADER_SIZE;
+            if self.read_buffer.len() - start < size {
+                break;
+            }
+
+            frames.push(self.read_buffer[start..start + size].to_vec());
+            offset = start + size;
+        }
+
+        self.read_buffer.drain(..offset);
+
+        Ok(Received { frames, closed })
+    }
+
+    pub fn shutdown(&mut self) {
+        let _ = self.flush();
+        let _ = self.stream.shutdown(Shutdown::Both);
+    }
+}
@@ -0,0 +1,27 @@
+use std::io;
+
+use thiserror::Error;
+
+use crate::connection::ConnectionId;
+
+#[derive(Debug, Error)]
+pub enum NetError {
+    #[error(transparent)]
+    Io(#[from] io::Error),
+    #[error("Failed to serialize a message: {0}")]
+    Serialization(#[from] bincode::Error),
+    #[error("Frame of {size} bytes exceeds the limit of {limit} bytes")]
+    FrameTooLarge { size: usize, limit: usize },
+    #[error("Message `{0}` is not registered")]
+    UnregisteredMessage(&'static str),
+    #[error("Received a frame too short to contain a message kind")]
+    MalformedFrame,
+    #[error("Received a message of unknown kind {0:#018x}")]
+    UnknownMessageKind(u64),
+    #[error("There is no connection {0:?}")]
+    UnknownConnection(ConnectionId),
+    #[error("The network is not {0}")]
+    NotRunning(&'static str),
+}
+
+pub type NetResult<T> = Result<T, NetError>;
@@ -0,0 +1,18 @@
+mod connection;
+
+pub mod client;
+pub mod error;
+pub mod message;
+pub mod network;
+pub mod replication;
+pub mod server;
+
+pub use {
+    client::NetClient,
+    connection::ConnectionId,
+    error::{NetError, NetResult},
+    message::{MessageReceived, MessageRegistry, NetMessage},
+    network::{NetEvent, Network},
+    replication::{Replicated, Replication, ReplicationSnapshot},
+    server::NetServer,
+};
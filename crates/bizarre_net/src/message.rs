@@ -0,0 +1,111 @@
+use std::{any::type_name, collections::HashMap};
+
+use bizarre_event::{Event, EventQueue};
+use serde::{de::DeserializeOwned, Serialize};
+
+use crate::{
+    connection::ConnectionId,
+    error::{NetError, NetResult},
+};
+
+/// Message sent over the network, both ends have to register it in their
+/// [`MessageRegistry`]
+pub trait NetMessage: Event + Serialize + DeserializeOwned {
+    /// Identifies the message on the wire, must be the same on both ends
+    fn name() -> &'static str {
+        type_name::<Self>()
+    }
+}
+
+/// Event pushed for every received message of type `M`
+#[derive(Clone, Debug)]
+pub struct MessageReceived<M> {
+    pub connection: ConnectionId,
+    pub message: M,
+}
+
+type Decoder = fn(ConnectionId, &[u8], &mut EventQueue) -> NetResult<()>;
+
+/// Message types that can be sent and received, a frame starts with the kind of
+/// the message followed by the message encoded with bincode
+#[derive(Default)]
+pub struct MessageRegistry {
+    decoders: HashMap<u64, (&'static str, Decoder)>,
+}
+
+impl MessageRegistry {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn register<M: NetMessage>(&mut self) {
+        let name = M::name();
+        let previous = self
+            .decoders
+            .insert(message_kind(name), (name, decode_message::<M>));
+
+        if let Some((previous, _)) = previous {
+            assert_eq!(
+                previous, name,
+                "Message `{name}` has the same kind as `{previous}`"
+            );
+        }
+    }
+
+    pub fn is_registered<M: NetMessage>(&self) -> bool {
+        self.decoders.contains_key(&message_kind(M::name()))
+    }
+
+    pub(crate) fn encode<M: NetMessage>(&self, message: &M) -> NetResult<Vec<u8>> {
+        if !self.is_registered::<M>() {
+            return Err(NetError::UnregisteredMessage(M::name()));
+        }
+
+        let mut frame = message_kind(M::name()).to_le_bytes().to_vec();
+        bincode::serialize_into(&mut frame, message)?;
+
+        Ok(frame)
+    }
+
+    /// Pushes the message of `frame` into `events` as [`MessageReceived`]
+    pub(crate) fn decode(
+        &self,
+        connection: ConnectionId,
+        frame: &[u8],
+        events: &mut EventQueue,
+    ) -> NetResult<()> {
+        let Some((kind, payload)) = frame.split_first_chunk::<{ size_of::<u64>() }>() else {
+            return Err(NetError::MalformedFrame);
+        };
+
+        let kind = u64::from_le_bytes(*kind);
+
+        let (_, decode) = self
+            .decoders
+            .get(&kind)
+            .ok_or(NetError::UnknownMessageKind(kind))?;
+
+        decode(connection, payload, events)
+    }
+}
+
+fn decode_message<M: NetMessage>(
+    connection: ConnectionId,
+    payload: &[u8],
+    events: &mut EventQueue,
+) -> NetResult<()> {
+    let message = bincode::deserialize::<M>(payload)?;
+    events.push_event(MessageReceived {
+        connection,
+        message,
+    });
+
+    Ok(())
+}
+
+/// FNV-1a hash of the name, stable between builds unlike `TypeId`
+pub(crate) fn message_kind(name: &str) -> u64 {
+    name.bytes().fold(0xcbf29ce484222325, |hash, byte| {
+        (hash ^ byte as u64).wrapping_mul(0x100000001b3)
+    })
+}
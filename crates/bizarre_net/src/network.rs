@@ -0,0 +1,176 @@
+use std::net::ToSocketAddrs;
+
+use bizarre_ecs::prelude::*;
+use bizarre_event::EventQueue;
+
+use crate::{
+    client::NetClient,
+    connection::ConnectionId,
+    error::{NetError, NetResult},
+    message::{MessageRegistry, NetMessage},
+    server::NetServer,
+};
+
+/// Lifecycle of the connections, pushed by [`Network::poll`]
+#[derive(Clone, Debug)]
+pub enum NetEvent {
+    Connected(ConnectionId),
+    /// `error` is `None` when the connection was closed by either end
+    Disconnected {
+        connection: ConnectionId,
+        error: Option<String>,
+    },
+    AcceptFailed(String),
+}
+
+/// Server and client of the app, both may run at the same time for a listen server
+#[derive(Resource, Default)]
+pub struct Network {
+    registry: MessageRegistry,
+    server: Option<NetServer>,
+    client: Option<NetClient>,
+}
+
+impl Network {
+    pub fn new(registry: MessageRegistry) -> Self {
+        Self {
+            registry,
+            ..Default::default()
+        }
+    }
+
+    pub fn registry(&self) -> &MessageRegistry {
+        &self.registry
+    }
+
+    pub fn register<M: NetMessage>(&mut self) {
+        self.registry.register::<M>();
+    }
+
+    /// Starts accepting clients, replaces the running server
+    pub fn host(&mut self, addr: impl ToSocketAddrs) -> NetResult<()> {
+        self.server = Some(NetServer::bind(addr)?);
+        Ok(())
+    }
+
+    /// Connects to a server, replaces the running client
+    pub fn connect(&mut self, addr: impl ToSocketAddrs) -> NetResult<()> {
+        self.client = Some(NetClient::connect(addr)?);
+        Ok(())
+    }
+
+    pub fn server(&self) -> Option<&NetServer> {
+        self.server.as_ref()
+    }
+
+    pub fn server_mut(&mut self) -> Option<&mut NetServer> {
+        self.server.as_mut()
+    }
+
+    pub fn client(&self) -> Option<&NetClient> {
+        self.client.as_ref()
+    }
+
+    pub fn client_mut(&mut self) -> Option<&mut NetClient> {
+        self.client.as_mut()
+    }
+
+    /// Closes the server and the client with all of their connections
+    pub fn shutdown(&mut self) {
+        self.server = None;
+        self.client = None;
+    }
+
+    /// Sends the message from the server to a client
+    pub fn send<M: NetMessage>(&mut self, connection: ConnectionId, message: &M) -> NetResult<()> {
+        let frame = self.registry.encode(message)?;
+        self.server
+            .as_mut()
+            .ok_or(NetError::NotRunning("hosting"))?
+            .send_frame(connection, &frame)
+    }
+
+    /// Sends the message from the server to every client
+    pub fn broadcast<M: NetMessage>(&mut self, message: &M) -> NetResult<()> {
+        let frame = self.registry.encode(message)?;
+        self.server
+            .as_mut()
+            .ok_or(NetError::NotRunning("hosting"))?
+            .broadcast_frame(&frame)
+    }
+
+    /// Sends the message from the client to the server
+    pub fn send_to_server<M: NetMessage>(&mut self, message: &M) -> NetResult<()> {
+        let frame = self.registry.encode(message)?;
+        self.client
+            .as_mut()
+            .ok_or(NetError::NotRunning("connected"))?
+            .send_frame(&frame)
+    }
+
+    /// Accepts clients, sends the queued frames and pushes [`NetEvent`]s and
+    /// [`MessageReceived`](crate::MessageReceived) events for the received messages
+    pub fn poll(&mut self, events: &mut EventQueue) {
+        if let Some(server) = &mut self.server {
+            server.poll(&self.registry, events);
+        }
+
+        if let Some(client) = &mut self.client {
+            client.poll(&self.registry, events);
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::{thread, time::Duration};
+
+    use bizarre_event::EventQueue;
+    use serde::{Deserialize, Serialize};
+
+    use crate::{ConnectionId, MessageReceived, NetMessage};
+
+    use super::{NetEvent, Network};
+
+    #[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+    struct Ping(u32);
+
+    impl NetMessage for Ping {}
+
+    #[test]
+    fn should_exchange_messages_over_loopback() {
+        let mut events = EventQueue::new();
+        let reader = events.create_reader();
+        events.register_reader::<NetEvent>(reader).unwrap();
+        events
+            .register_reader::<MessageReceived<Ping>>(reader)
+            .unwrap();
+
+        let mut server = Network::default();
+        server.register::<Ping>();
+        server.host("127.0.0.1:0").unwrap();
+        let addr = server.server().unwrap().local_addr().unwrap();
+
+        let mut client = Network::default();
+        client.register::<Ping>();
+        client.connect(addr).unwrap();
+        client.send_to_server(&Ping(7)).unwrap();
+
+        let mut received = Vec::new();
+        for _ in 0..100 {
+            server.poll(&mut events);
+            client.poll(&mut events);
+            events.change_frames();
+            received.extend(events.pull_events::<MessageReceived<Ping>>(&reader));
+
+            if !received.is_empty() {
+                break;
+            }
+            thread::sleep(Duration::from_millis(5));
+        }
+
+        assert_eq!(received.len(), 1);
+        assert_eq!(received[0].message, Ping(7));
+        assert_ne!(received[0].connection, ConnectionId::SERVER);
+    }
+}
@@ -0,0 +1,407 @@
+use std::{
+    any::type_name,
+    collections::{BTreeMap, BTreeSet, HashMap},
+};
+
+use bizarre_ecs::{component::Component, entity::Entity, prelude::*, world::World};
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
+
+pub use bizarre_net_proc_macro::Replicated;
+
+use crate::{
+    connection::ConnectionId,
+    error::{NetError, NetResult},
+    message::{message_kind, NetMessage},
+    network::Network,
+};
+
+/// Component sent from the server to the clients, usually derived with `Replicated`.
+/// Both ends have to register it with [`Replication::register`]
+pub trait Replicated: Component + Serialize + DeserializeOwned {
+    /// Identifies the component on the wire, must be the same on both ends
+    fn replication_name() -> &'static str {
+        type_name::<Self>()
+    }
+}
+
+/// Entity of the server world, generation included
+#[derive(Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Debug, Serialize, Deserialize)]
+pub struct ServerEntity(u64);
+
+impl From<Entity> for ServerEntity {
+    fn from(entity: Entity) -> Self {
+        Self(((entity.gen() as u64) << Entity::GEN_SHIFT) | entity.id())
+    }
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct ComponentUpdate {
+    pub entity: ServerEntity,
+    pub kind: u64,
+    pub data: Vec<u8>,
+}
+
+/// Changes of the replicated entities visible to a client since the last snapshot
+/// it was sent
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+pub struct ReplicationSnapshot {
+    pub updates: Vec<ComponentUpdate>,
+    pub removals: Vec<(ServerEntity, u64)>,
+    pub despawns: Vec<ServerEntity>,
+}
+
+impl ReplicationSnapshot {
+    pub fn is_empty(&self) -> bool {
+        self.updates.is_empty() && self.removals.is_empty() && self.despawns.is_empty()
+    }
+}
+
+impl NetMessage for ReplicationSnapshot {}
+
+/// Decides whether a client sees an entity, entities leaving the interest of a
+/// client are despawned on it
+pub type InterestFn = Box<dyn Fn(&World, ConnectionId, Entity) -> bool>;
+
+/// Serializes a component of every entity having it
+type CollectFn = fn(&mut World) -> Vec<(Entity, Vec<u8>)>;
+
+struct ReplicatedComponent {
+    kind: u64,
+    name: &'static str,
+    collect: CollectFn,
+    apply: fn(&mut World, Entity, &[u8]) -> NetResult<()>,
+    remove: fn(&mut World, Entity),
+}
+
+/// Last state of the entities sent to a client
+#[derive(Default)]
+struct ClientView {
+    components: BTreeMap<(Entity, u64), Vec<u8>>,
+    entities: BTreeSet<Entity>,
+}
+
+/// Diffs the replicated components into [`ReplicationSnapshot`]s on the server and
+/// applies them on the clients
+#[derive(Resource, Default)]
+pub struct Replication {
+    components: Vec<ReplicatedComponent>,
+    interest: Option<InterestFn>,
+    views: HashMap<ConnectionId, ClientView>,
+    /// Local entities spawned for the entities of the server
+    entity_map: HashMap<ServerEntity, Entity>,
+}
+
+impl Replication {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn register<C: Replicated>(&mut self) {
+        let name = C::replication_name();
+        let kind = message_kind(name);
+
+        if let Some(registered) = self.components.iter().find(|c| c.kind == kind) {
+            assert_eq!(
+                registered.name, name,
+                "Replicated component `{name}` has the same kind as `{}`",
+                registered.name
+            );
+            return;
+        }
+
+        self.components.push(ReplicatedComponent {
+            kind,
+            name,
+            collect: collect_component::<C>,
+            apply: apply_component::<C>,
+            remove: remove_component::<C>,
+        });
+    }
+
+    /// Replaces the interest filter, without one every client sees every entity
+    pub fn set_interest(
+        &mut self,
+        interest: impl Fn(&World, ConnectionId, Entity) -> bool + 'static,
+    ) {
+        self.interest = Some(Box::new(interest));
+    }
+
+    /// Local entity spawned for the entity of the server
+    pub fn local_entity(&self, entity: ServerEntity) -> Option<Entity> {
+        self.entity_map.get(&entity).copied()
+    }
+
+    pub fn entity_map(&self) -> impl Iterator<Item = (ServerEntity, Entity)> + '_ {
+        self.entity_map
+            .iter()
+            .map(|(&server, &local)| (server, local))
+    }
+
+    /// Sends a snapshot to every client whose visible entities changed.
+    ///
+    /// The world has no change detection yet, so every replicated component of
+    /// every entity is serialized on each call and diffed against the last state
+    /// sent to the clients. It's meant for small worlds, larger ones should call
+    /// it less often than every frame
+    pub fn replicate(&mut self, world: &mut World, network: &mut Network) -> NetResult<()> {
+        let Some(server) = network.server() else {
+            return Err(NetError::NotRunning("hosting"));
+        };
+
+        let connections = server.connections().collect::<Vec<_>>();
+        self.views
+            .retain(|connection, _| connections.contains(connection));
+
+        let current = self
+            .components
+            .iter()
+            .flat_map(|component| {
+                (component.collect)(world)
+                    .into_iter()
+                    .map(|(entity, data)| ((entity, component.kind), data))
+            })
+            .collect::<BTreeMap<_, _>>();
+
+        let mut result = Ok(());
+
+        for connection in connections {
+            let visible = current
+                .keys()
+                .map(|(entity, _)| *entity)
+                .filter(|&entity| {
+                    self.interest
+                        .as_ref()
+                        .is_none_or(|interest| interest(world, connection, entity))
+                })
+                .collect::<BTreeSet<_>>();
+
+            let view = self.views.entry(connection).or_default();
+            let snapshot = diff_view(view, &current, visible);
+
+            if snapshot.is_empty() {
+                continue;
+            }
+
+            if let Err(err) = network.send(connection, &snapshot) {
+                // The view is rebuilt from scratch so nothing is lost
+                self.views.remove(&connection);
+                result = Err(err);
+            }
+        }
+
+        result
+    }
+
+    /// Spawns, updates and despawns the local copies of the server entities
+    pub fn apply_snapshot(
+        &mut self,
+        world: &mut World,
+        snapshot: &ReplicationSnapshot,
+    ) -> NetResult<()> {
+        for update in &snapshot.updates {
+            let apply = self.component(update.kind)?.apply;
+            let entity = *self
+                .entity_map
+                .entry(update.entity)
+                .or_insert_with(|| world.create_entity());
+
+            apply(world, entity, &update.data)?;
+        }
+
+        for (entity, kind) in &snapshot.removals {
+            let remove = self.component(*kind)?.remove;
+            if let Some(&entity) = self.entity_map.get(entity) {
+                remove(world, entity);
+            }
+        }
+
+        for entity in &snapshot.despawns {
+            if let Some(entity) = self.entity_map.remove(entity) {
+                world.kill(entity);
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Despawns the local copies, used when the connection to the server is lost
+    pub fn clear_replicated(&mut self, world: &mut World) {
+        for (_, entity) in self.entity_map.drain() {
+            world.kill(entity);
+        }
+    }
+
+    fn component(&self, kind: u64) -> NetResult<&ReplicatedComponent> {
+        self.components
+            .iter()
+            .find(|component| component.kind == kind)
+            .ok_or(NetError::UnknownMessageKind(kind))
+    }
+}
+
+/// Brings the view up to date with the visible part of `current`, returns what changed
+fn diff_view(
+    view: &mut ClientView,
+    current: &BTreeMap<(Entity, u64), Vec<u8>>,
+    visible: BTreeSet<Entity>,
+) -> ReplicationSnapshot {
+    let mut snapshot = ReplicationSnapshot {
+        despawns: view
+            .entities
+            .difference(&visible)
+            .map(|&entity| entity.into())
+            .collect(),
+        ..Default::default()
+    };
+
+    view.components.retain(|(entity, kind), _| {
+        if !visible.contains(entity) {
+            return false;
+        }
+
+        let present = current.contains_key(&(*entity, *kind));
+        if !present {
+            snapshot.removals.push(((*entity).into(), *kind));
+        }
+        present
+    });
+
+    for (&(entity, kind), data) in current {
+        if !visible.contains(&entity) || view.components.get(&(entity, kind)) == Some(data) {
+            continue;
+        }
+
+        view.components.insert((entity, kind), data.clone());
+        snapshot.updates.push(ComponentUpdate {
+            entity: entity.into(),
+            kind,
+            data: data.clone(),
+        });
+    }
+
+    view.entities = visible;
+
+    snapshot
+}
+
+fn collect_component<C: Replicated>(world: &mut World) -> Vec<(Entity, Vec<u8>)> {
+    world.register_component::<C>();
+    world
+        .query::<(Entity, &C)>()
+        .filter_map(|(entity, component)| Some((entity, bincode::serialize(component).ok()?)))
+        .collect()
+}
+
+fn apply_component<C: Replicated>(world: &mut World, entity: Entity, data: &[u8]) -> NetResult<()> {
+    let component = bincode::deserialize::<C>(data)?;
+    world.register_component::<C>();
+    world.insert_component(entity, component);
+    Ok(())
+}
+
+fn remove_component<C: Replicated>(world: &mut World, entity: Entity) {
+    world.remove_component::<C>(entity);
+}
+
+#[cfg(test)]
+mod test {
+    use std::{thread, time::Duration};
+
+    use bizarre_ecs::{prelude::*, world::World};
+    use bizarre_event::EventQueue;
+    use serde::{Deserialize, Serialize};
+
+    use crate::{MessageReceived, Network};
+
+    use super::{Replicated, Replication, ReplicationSnapshot};
+
+    #[derive(Component, Replicated, Clone, Debug, PartialEq, Serialize, Deserialize)]
+    struct Position(i32, i32);
+
+    /// Polls both ends until the client applies a snapshot
+    fn sync(
+        server: (&mut World, &mut Network, &mut Replication),
+        client: (&mut World, &mut Network, &mut Replication),
+        events: &mut EventQueue,
+    ) {
+        let reader = events.create_reader();
+        events
+            .register_reader::<MessageReceived<ReplicationSnapshot>>(reader)
+            .unwrap();
+
+        for _ in 0..100 {
+            server.1.poll(events);
+            server.2.replicate(server.0, server.1).unwrap();
+            client.1.poll(events);
+            events.change_frames();
+
+            let snapshots = events.pull_events::<MessageReceived<ReplicationSnapshot>>(&reader);
+            for snapshot in &snapshots {
+                client
+                    .2
+                    .apply_snapshot(client.0, &snapshot.message)
+                    .unwrap();
+            }
+
+            if !snapshots.is_empty() {
+                return;
+            }
+            thread::sleep(Duration::from_millis(5));
+        }
+
+        panic!("No snapshot arrived");
+    }
+
+    #[test]
+    fn should_replicate_component_changes_and_despawns() {
+        let mut events = EventQueue::new();
+
+        let mut server_world = World::new();
+        server_world.register_component::<Position>();
+        let entity = server_world.spawn_entity(Position(1, 2));
+
+        let mut server_net = Network::default();
+        server_net.register::<ReplicationSnapshot>();
+        server_net.host("127.0.0.1:0").unwrap();
+        let addr = server_net.server().unwrap().local_addr().unwrap();
+
+        let mut client_world = World::new();
+        let mut client_net = Network::default();
+        client_net.register::<ReplicationSnapshot>();
+        client_net.connect(addr).unwrap();
+
+        let mut server_replication = Replication::new();
+        server_replication.register::<Position>();
+        let mut client_replication = Replication::new();
+        client_replication.register::<Position>();
+
+        macro_rules! sync {
+            () => {
+                sync(
+                    (&mut server_world, &mut server_net, &mut server_replication),
+                    (&mut client_world, &mut client_net, &mut client_replication),
+                    &mut events,
+                )
+            };
+        }
+
+        sync!();
+        let local = client_replication.local_entity(entity.into()).unwrap();
+        assert_eq!(
+            client_world.component::<Position>(local),
+            Some(&Position(1, 2))
+        );
+
+        server_world.component_mut::<Position>(entity).unwrap().0 = 5;
+        sync!();
+        assert_eq!(
+            client_world.component::<Position>(local),
+            Some(&Position(5, 2))
+        );
+
+        server_world.kill(entity);
+        sync!();
+        assert!(client_replication.local_entity(entity.into()).is_none());
+        assert_eq!(client_world.entity_count(), 0);
+    }
+}
@@ -0,0 +1,154 @@
+use std::{
+    collections::BTreeMap,
+    io::ErrorKind,
+    net::{SocketAddr, TcpListener, ToSocketAddrs},
+};
+
+use bizarre_event::EventQueue;
+
+use crate::{
+    connection::{Connection, ConnectionId},
+    error::{NetError, NetResult},
+    message::MessageRegistry,
+    network::NetEvent,
+};
+
+/// Accepts clients and exchanges frames with them
+pub struct NetServer {
+    listener: TcpListener,
+    connections: BTreeMap<ConnectionId, Connection>,
+    next_id: u64,
+    pending_events: Vec<NetEvent>,
+}
+
+impl NetServer {
+    pub fn bind(addr: impl ToSocketAddrs) -> NetResult<Self> {
+        let listener = TcpListener::bind(addr)?;
+        listener.set_nonblocking(true)?;
+
+        Ok(Self {
+            listener,
+            connections: BTreeMap::new(),
+            // 0 is `ConnectionId::SERVER`
+            next_id: 1,
+            pending_events: Vec::new(),
+        })
+    }
+
+    pub fn local_addr(&self) -> NetResult<SocketAddr> {
+        Ok(self.listener.local_addr()?)
+    }
+
+    pub fn connections(&self) -> impl Iterator<Item = ConnectionId> + '_ {
+        self.connections.keys().copied()
+    }
+
+    pub fn peer_addr(&self, connection: ConnectionId) -> Option<SocketAddr> {
+        self.connections.get(&connection).map(Connection::peer_addr)
+    }
+
+    /// Closes the connection, [`NetEvent::Disconnected`] is pushed on the next poll
+    pub fn disconnect(&mut self, connection: ConnectionId) -> bool {
+        let Some(mut removed) = self.connections.remove(&connection) else {
+            return false;
+        };
+
+        removed.shutdown();
+        self.pending_events.push(NetEvent::Disconnected {
+            connection,
+            error: None,
+        });
+
+        true
+    }
+
+    pub(crate) fn send_frame(&mut self, connection: ConnectionId, frame: &[u8]) -> NetResult<()> {
+        self.connections
+            .get_mut(&connection)
+            .ok_or(NetError::UnknownConnection(connection))?
+            .send_frame(frame)
+    }
+
+    /// Sends the frame to every client, the ones that fail are dropped on the next poll
+    pub(crate) fn broadcast_frame(&mut self, frame: &[u8]) -> NetResult<()> {
+        let mut result = Ok(());
+
+        for connection in self.connections.values_mut() {
+            if let Err(err) = connection.send_frame(frame) {
+                result = Err(err);
+            }
+        }
+
+        result
+    }
+
+    pub(crate) fn poll(&mut self, registry: &MessageRegistry, events: &mut EventQueue) {
+        self.accept_connections();
+
+        let mut dropped = Vec::new();
+
+        for (&id, connection) in &mut self.connections {
+            let received = connection.flush().and_then(|_| connection.receive());
+
+            let error = match received {
+                Ok(received) => {
+                    let decoded = received
+                        .frames
+                        .iter()
+                        .try_for_each(|frame| registry.decode(id, frame, events));
+
+                    match decoded {
+                        Ok(()) if received.closed => None,
+                        Ok(()) => continue,
+                        Err(err) => Some(err.to_string()),
+                    }
+                }
+                Err(err) => Some(err.to_string()),
+            };
+
+            dropped.push(NetEvent::Disconnected {
+                connection: id,
+                error,
+            });
+        }
+
+        for event in &dropped {
+            if let NetEvent::Disconnected { connection, .. } = event {
+                self.connections.remove(connection);
+            }
+        }
+
+        self.pending_events
+            .drain(..)
+            .chain(dropped)
+            .for_each(|event| events.push_event(event));
+    }
+
+    fn accept_connections(&mut self) {
+        loop {
+            let accepted = match self.listener.accept() {
+                Ok((stream, _)) => Connection::new(stream),
+                Err(err) if err.kind() == ErrorKind::WouldBlock => break,
+                Err(err) => Err(err),
+            };
+
+            let event = match accepted {
+                Ok(connection) => {
+                    let id = ConnectionId(self.next_id);
+                    self.next_id += 1;
+                    self.connections.insert(id, connection);
+                    NetEvent::Connected(id)
+                }
+                Err(err) => NetEvent::AcceptFailed(err.to_string()),
+            };
+
+            self.pending_events.push(event);
+        }
+    }
+}
+
+impl Drop for NetServer {
+    fn drop(&mut self) {
+        self.connections.values_mut().for_each(Connection::shutdown);
+    }
+}
@@ -0,0 +1,74 @@
+use proc_macro2::TokenStream;
+use quote::{quote, quote_spanned};
+use syn::{spanned::Spanned, Data, DeriveInput, Fields, LitInt};
+
+pub fn derive_instance_data_impl(input: DeriveInput) -> TokenStream {
+    let DeriveInput {
+        ident,
+        generics,
+        attrs,
+        data,
+        ..
+    } = input;
+
+    if !generics.params.is_empty() {
+        return quote_spanned! {generics.span()=>
+            compile_error!("InstanceData can't be derived for generic types");
+        };
+    }
+
+    let mut repr_c = false;
+    let mut align = None;
+
+    for attr in attrs.iter().filter(|attr| attr.path().is_ident("repr")) {
+        let _ = attr.parse_nested_meta(|meta| {
+            if meta.path.is_ident("C") {
+                repr_c = true;
+            } else if meta.path.is_ident("align") {
+                let content;
+                syn::parenthesized!(content in meta.input);
+                align = Some(content.parse::<LitInt>()?.base10_parse::<usize>()?);
+            }
+            Ok(())
+        });
+    }
+
+    if !repr_c || align.is_none_or(|align| align % 16 != 0) {
+        return quote_spanned! {ident.span()=>
+            compile_error!("InstanceData requires `#[repr(C, align(16))]`");
+        };
+    }
+
+    let fields = match data {
+        Data::Struct(data) => match data.fields {
+            Fields::Named(fields) => fields.named,
+            _ => {
+                return quote_spanned! {ident.span()=>
+                    compile_error!("InstanceData can only be derived for structs with named fields");
+                }
+            }
+        },
+        _ => {
+            return quote_spanned! {ident.span()=>
+                compile_error!("InstanceData can only be derived for structs");
+            }
+        }
+    };
+
+    let field_descs = fields.iter().map(|field| {
+        let name = field.ident.as_ref().unwrap();
+        let name_str = name.to_string();
+        let ty = &field.ty;
+
+        quote! {
+            (#name_str, ::std::mem::offset_of!(#ident, #name), ::std::mem::size_of::<#ty>())
+        }
+    });
+
+    quote! {
+        #[automatically_derived]
+        unsafe impl GpuInstanceData for #ident {
+            const FIELDS: &'static [(&'static str, usize, usize)] = &[#(#field_descs),*];
+        }
+    }
+}
@@ -0,0 +1,11 @@
+use instance_data::derive_instance_data_impl;
+use proc_macro::TokenStream;
+use syn::{parse_macro_input, DeriveInput};
+
+mod instance_data;
+
+/// Implements `GpuInstanceData` for a `#[repr(C, align(16))]` struct with named fields
+#[proc_macro_derive(InstanceData)]
+pub fn derive_instance_data(input: TokenStream) -> TokenStream {
+    derive_instance_data_impl(parse_macro_input!(input as DeriveInput)).into()
+}
@@ -1,10 +1,15 @@
 use ash::vk;
+use nalgebra_glm::{UVec2, Vec2};
 
 #[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
 pub enum Antialiasing {
     None,
     FSAA,
     MSAA(MsaaFactor),
+    /// Temporal antialiasing: the projection is jittered by a sub-pixel offset
+    /// every frame and the result is blended with the history reprojected by
+    /// the motion vectors, clamped to the neighborhood of every pixel
+    Taa,
 }
 
 impl From<Antialiasing> for vk::SampleCountFlags {
@@ -12,6 +17,7 @@ impl From<Antialiasing> for vk::SampleCountFlags {
         match value {
             Antialiasing::None => vk::SampleCountFlags::TYPE_1,
             Antialiasing::FSAA => vk::SampleCountFlags::TYPE_1,
+            Antialiasing::Taa => vk::SampleCountFlags::TYPE_1,
             Antialiasing::MSAA(msaa_factor) => match msaa_factor {
                 MsaaFactor::X2 => vk::SampleCountFlags::TYPE_2,
                 MsaaFactor::X4 => vk::SampleCountFlags::TYPE_4,
@@ -33,3 +39,30 @@ pub enum MsaaFactor {
     X32,
     X64,
 }
+
+/// Amount of sub-pixel offsets [`Antialiasing::Taa`] cycles through
+pub const TAA_JITTER_PHASES: u32 = 8;
+
+/// Offset in NDC the projection is jittered by in the `frame_index`-th frame,
+/// taken from the Halton `(2, 3)` sequence and kept within a pixel
+pub(crate) fn taa_jitter(frame_index: u32, resolution: UVec2) -> Vec2 {
+    let index = frame_index % TAA_JITTER_PHASES + 1;
+
+    Vec2::new(
+        (halton(index, 2) - 0.5) * 2.0 / resolution.x.max(1) as f32,
+        (halton(index, 3) - 0.5) * 2.0 / resolution.y.max(1) as f32,
+    )
+}
+
+fn halton(mut index: u32, base: u32) -> f32 {
+    let mut fraction = 1.0;
+    let mut result = 0.0;
+
+    while index > 0 {
+        fraction /= base as f32;
+        result += fraction * (index % base) as f32;
+        index /= base;
+    }
+
+    result
+}
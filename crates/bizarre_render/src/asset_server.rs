@@ -0,0 +1,479 @@
+use std::{
+    any::{type_name, Any, TypeId},
+    collections::HashMap,
+    path::{Path, PathBuf},
+    sync::{
+        mpsc::{channel, Receiver, Sender},
+        Arc, Mutex,
+    },
+    thread::{self, JoinHandle},
+    time::Duration,
+};
+
+use bizarre_core::Handle;
+use bizarre_ecs::prelude::*;
+use bizarre_event::EventQueue;
+use bizarre_log::{core_error, core_trace};
+use thiserror::Error;
+
+use crate::{
+    asset_watcher::AssetWatcher,
+    mesh::Mesh,
+    render_assets::{DenseAssetStore, RenderAssets},
+};
+
+const MAX_LOADER_THREADS: usize = 4;
+
+#[derive(Error, Debug, Clone)]
+pub enum AssetLoadError {
+    #[error("Failed to read `{0}`: {1}")]
+    Io(PathBuf, String),
+    #[error("Failed to import `{0}`: {1}")]
+    Import(PathBuf, String),
+    #[error("Asset loader thread panicked while loading `{0}`")]
+    LoaderPanicked(PathBuf),
+}
+
+/// An asset which can be loaded from a file by the [`AssetServer`]
+pub trait LoadableAsset: Sized + Send + 'static {
+    /// Reads and imports the asset. Called on a loader thread
+    fn load(path: &Path) -> Result<Self, AssetLoadError>;
+
+    /// Store of [`RenderAssets`] the loaded asset goes to
+    fn store(assets: &RenderAssets) -> &DenseAssetStore<Self>;
+
+    fn store_mut(assets: &mut RenderAssets) -> &mut DenseAssetStore<Self>;
+
+    /// Called after a reloaded asset replaced the old one behind `handle`,
+    /// lets GPU-side copies of the asset be refreshed
+    fn on_reloaded(_assets: &mut RenderAssets, _handle: Handle<Self>) {}
+}
+
+impl LoadableAsset for Mesh {
+    fn load(path: &Path) -> Result<Self, AssetLoadError> {
+        Mesh::try_load_from_obj(path)
+            .map_err(|err| AssetLoadError::Import(path.to_path_buf(), err.to_string()))
+    }
+
+    fn store(assets: &RenderAssets) -> &DenseAssetStore<Self> {
+        &assets.meshes
+    }
+
+    fn store_mut(assets: &mut RenderAssets) -> &mut DenseAssetStore<Self> {
+        &mut assets.meshes
+    }
+
+    fn on_reloaded(assets: &mut RenderAssets, handle: Handle<Self>) {
+        assets.mesh_pool.release(handle);
+
+        assets
+            .scenes
+            .iter_mut()
+            .for_each(|(_, scene)| scene.mesh_changed(handle));
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum LoadState {
+    NotLoaded,
+    Loading,
+    Loaded,
+    Failed,
+}
+
+/// Type-erased handle of a loadable asset
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct UntypedAssetId {
+    type_id: TypeId,
+    raw: usize,
+}
+
+impl UntypedAssetId {
+    pub fn of<T: 'static>(handle: Handle<T>) -> Self {
+        Self {
+            type_id: TypeId::of::<T>(),
+            raw: handle.as_raw(),
+        }
+    }
+
+    pub fn typed<T: 'static>(&self) -> Option<Handle<T>> {
+        (self.type_id == TypeId::of::<T>()).then(|| Handle::from_raw(self.raw))
+    }
+}
+
+impl<T: 'static> From<Handle<T>> for UntypedAssetId {
+    fn from(handle: Handle<T>) -> Self {
+        Self::of(handle)
+    }
+}
+
+/// Pushed into the [`EventQueue`] when a load started by the [`AssetServer`] finishes
+#[derive(Debug)]
+pub enum AssetEvent<T> {
+    Loaded(Handle<T>),
+    /// A new version of an already loaded asset replaced the old one
+    Reloaded(Handle<T>),
+    Failed(Handle<T>, AssetLoadError),
+}
+
+impl<T> Clone for AssetEvent<T> {
+    fn clone(&self) -> Self {
+        match self {
+            Self::Loaded(handle) => Self::Loaded(*handle),
+            Self::Reloaded(handle) => Self::Reloaded(*handle),
+            Self::Failed(handle, err) => Self::Failed(*handle, err.clone()),
+        }
+    }
+}
+
+type LoadJob = Box<dyn FnOnce() + Send>;
+type FinishFn = fn(&mut RenderAssets, &mut EventQueue, usize, LoadOutput, bool) -> bool;
+type ReloadFn = fn(&mut AssetServer, usize) -> bool;
+type LoadOutput = Result<Box<dyn Any + Send>, AssetLoadError>;
+
+struct LoadResult {
+    id: UntypedAssetId,
+    output: LoadOutput,
+    reload: bool,
+    finish: FinishFn,
+}
+
+struct AssetInfo {
+    path: PathBuf,
+    state: LoadState,
+    dependencies: Vec<UntypedAssetId>,
+    reload: Option<ReloadFn>,
+}
+
+/// Loads assets on background threads and tracks their state.
+///
+/// Handles returned by [`load`](Self::load) are valid right away, the assets
+/// appear behind them once [`update_asset_server`][crate::ecs::update_asset_server]
+/// picks up the finished loads
+#[derive(Resource)]
+pub struct AssetServer {
+    assets: HashMap<UntypedAssetId, AssetInfo>,
+    paths: HashMap<(TypeId, PathBuf), UntypedAssetId>,
+
+    job_sender: Option<Sender<LoadJob>>,
+    result_sender: Sender<LoadResult>,
+    result_recv: Receiver<LoadResult>,
+    workers: Vec<JoinHandle<()>>,
+    watcher: Option<AssetWatcher>,
+}
+
+impl Default for AssetServer {
+    fn default() -> Self {
+        let threads = thread::available_parallelism()
+            .map(|n| n.get())
+            .unwrap_or(1)
+            .clamp(1, MAX_LOADER_THREADS);
+
+        Self::new(threads)
+    }
+}
+
+impl AssetServer {
+    pub fn new(loader_threads: usize) -> Self {
+        let (job_sender, job_recv) = channel::<LoadJob>();
+        let (result_sender, result_recv) = channel();
+
+        let job_recv = Arc::new(Mutex::new(job_recv));
+
+        let workers = (0..loader_threads.max(1))
+            .map(|i| {
+                let job_recv = job_recv.clone();
+
+                thread::Builder::new()
+                    .name(format!("asset_loader_{i}"))
+                    .spawn(move || loop {
+                        let job = match job_recv.lock() {
+                            Ok(recv) => recv.recv(),
+                            Err(_) => break,
+                        };
+
+                        match job {
+                            Ok(job) => job(),
+                            Err(_) => break,
+                        }
+                    })
+                    .unwrap_or_else(|err| panic!("Failed to spawn asset loader thread: {err}"))
+            })
+            .collect();
+
+        Self {
+            assets: HashMap::default(),
+            paths: HashMap::default(),
+            job_sender: Some(job_sender),
+            result_sender,
+            result_recv,
+            workers,
+            watcher: None,
+        }
+    }
+
+    /// Starts watching files of loaded assets, changed files are reloaded
+    /// in the background and swapped in behind the same handles
+    pub fn watch_for_changes(&mut self, poll_interval: Duration) {
+        if self.watcher.is_some() {
+            return;
+        }
+
+        let watcher = AssetWatcher::new(poll_interval);
+
+        self.assets
+            .values()
+            .filter(|info| info.reload.is_some())
+            .for_each(|info| watcher.watch(&info.path));
+
+        self.watcher = Some(watcher);
+    }
+
+    pub fn stop_watching(&mut self) {
+        self.watcher.take();
+    }
+
+    pub fn is_watching(&self) -> bool {
+        self.watcher.is_some()
+    }
+
+    /// Starts loading an asset of type `T` from `path`.
+    ///
+    /// Loading the same path twice returns the same handle
+    pub fn load<T: LoadableAsset>(
+        &mut self,
+        assets: &RenderAssets,
+        path: impl AsRef<Path>,
+    ) -> Handle<T> {
+        let path = path.as_ref().to_path_buf();
+
+        if let Some(id) = self.paths.get(&(TypeId::of::<T>(), path.clone())) {
+            return id.typed().unwrap();
+        }
+
+        let handle = T::store(assets).reserve();
+        let id = UntypedAssetId::of(handle);
+
+        self.paths.insert((TypeId::of::<T>(), path.clone()), id);
+        self.assets.insert(
+            id,
+            AssetInfo {
+                path: path.clone(),
+                state: LoadState::Loading,
+                dependencies: Vec::new(),
+                reload: Some(|server, raw| server.reload(Handle::<T>::from_raw(raw))),
+            },
+        );
+
+        if let Some(watcher) = &self.watcher {
+            watcher.watch(&path);
+        }
+
+        core_trace!("Loading {} from {path:?}", type_name::<T>());
+
+        self.start_load::<T>(id, path, false);
+
+        handle
+    }
+
+    /// Loads the asset behind `handle` from its path again, the old asset stays
+    /// in place until the new one is ready and if the reload fails
+    pub fn reload<T: LoadableAsset>(&mut self, handle: Handle<T>) -> bool {
+        let id = UntypedAssetId::of(handle);
+
+        let Some(info) = self.assets.get_mut(&id) else {
+            return false;
+        };
+
+        let path = info.path.clone();
+        let reloading = info.state == LoadState::Loaded;
+
+        if !reloading {
+            info.state = LoadState::Loading;
+        }
+
+        core_trace!("Reloading {} from {path:?}", type_name::<T>());
+
+        self.start_load::<T>(id, path, reloading);
+
+        true
+    }
+
+    fn start_load<T: LoadableAsset>(&mut self, id: UntypedAssetId, path: PathBuf, reload: bool) {
+        let result_sender = self.result_sender.clone();
+
+        let job: LoadJob = Box::new(move || {
+            let output = std::panic::catch_unwind(|| T::load(&path))
+                .unwrap_or_else(|_| Err(AssetLoadError::LoaderPanicked(path.clone())))
+                .map(|asset| Box::new(asset) as Box<dyn Any + Send>);
+
+            let _ = result_sender.send(LoadResult {
+                id,
+                output,
+                reload,
+                finish: finish_load::<T>,
+            });
+        });
+
+        if let Some(sender) = &self.job_sender {
+            sender
+                .send(job)
+                .unwrap_or_else(|err| panic!("Asset loader threads are gone: {err}"));
+        }
+    }
+
+    /// Starts reloads of assets whose files changed, moves finished loads into
+    /// `assets` and pushes [`AssetEvent`]s for them
+    pub fn process_loaded(&mut self, assets: &mut RenderAssets, events: &mut EventQueue) {
+        self.reload_changed();
+
+        while let Ok(LoadResult {
+            id,
+            output,
+            reload,
+            finish,
+        }) = self.result_recv.try_recv()
+        {
+            let loaded = finish(assets, events, id.raw, output, reload);
+
+            if let Some(info) = self.assets.get_mut(&id) {
+                // A failed reload keeps the previously loaded asset
+                info.state = match (loaded, info.state) {
+                    (true, _) | (false, LoadState::Loaded) => LoadState::Loaded,
+                    (false, _) => LoadState::Failed,
+                };
+            }
+        }
+    }
+
+    fn reload_changed(&mut self) {
+        let Some(watcher) = &self.watcher else {
+            return;
+        };
+
+        for path in watcher.changed_paths() {
+            let reloads = self
+                .assets
+                .iter()
+                .filter(|(_, info)| info.path == path)
+                .filter_map(|(id, info)| Some((id.raw, info.reload?)))
+                .collect::<Vec<_>>();
+
+            for (raw, reload) in reloads {
+                reload(self, raw);
+            }
+        }
+    }
+
+    pub fn load_state(&self, id: impl Into<UntypedAssetId>) -> LoadState {
+        self.assets
+            .get(&id.into())
+            .map(|info| info.state)
+            .unwrap_or(LoadState::NotLoaded)
+    }
+
+    /// Load state of the asset combined with the states of all of its dependencies.
+    ///
+    /// `Failed` if anything failed, `Loaded` only if everything is loaded
+    pub fn recursive_load_state(&self, id: impl Into<UntypedAssetId>) -> LoadState {
+        let mut stack = vec![id.into()];
+        let mut visited = Vec::new();
+        let mut state = LoadState::Loaded;
+
+        while let Some(id) = stack.pop() {
+            if visited.contains(&id) {
+                continue;
+            }
+            visited.push(id);
+
+            match self.load_state(id) {
+                LoadState::Failed => return LoadState::Failed,
+                LoadState::Loaded => {}
+                other => state = other,
+            }
+
+            if let Some(info) = self.assets.get(&id) {
+                stack.extend(info.dependencies.iter().copied());
+            }
+        }
+
+        state
+    }
+
+    pub fn is_loaded_with_dependencies(&self, id: impl Into<UntypedAssetId>) -> bool {
+        self.recursive_load_state(id) == LoadState::Loaded
+    }
+
+    /// Records that `asset` can't be used until `dependency` is loaded
+    /// (e.g. a material and its textures)
+    pub fn add_dependency(
+        &mut self,
+        asset: impl Into<UntypedAssetId>,
+        dependency: impl Into<UntypedAssetId>,
+    ) {
+        let asset = asset.into();
+        let dependency = dependency.into();
+
+        let info = self.assets.entry(asset).or_insert_with(|| AssetInfo {
+            path: PathBuf::new(),
+            state: LoadState::Loaded,
+            dependencies: Vec::new(),
+            reload: None,
+        });
+
+        if !info.dependencies.contains(&dependency) {
+            info.dependencies.push(dependency);
+        }
+    }
+
+    pub fn dependencies(&self, id: impl Into<UntypedAssetId>) -> &[UntypedAssetId] {
+        self.assets
+            .get(&id.into())
+            .map(|info| info.dependencies.as_slice())
+            .unwrap_or(&[])
+    }
+
+    pub fn path(&self, id: impl Into<UntypedAssetId>) -> Option<&Path> {
+        self.assets.get(&id.into()).map(|info| info.path.as_path())
+    }
+}
+
+impl Drop for AssetServer {
+    fn drop(&mut self) {
+        self.job_sender.take();
+
+        for worker in self.workers.drain(..) {
+            let _ = worker.join();
+        }
+    }
+}
+
+fn finish_load<T: LoadableAsset>(
+    assets: &mut RenderAssets,
+    events: &mut EventQueue,
+    raw: usize,
+    output: LoadOutput,
+    reload: bool,
+) -> bool {
+    let handle = Handle::<T>::from_raw(raw);
+
+    match output.map(|asset| *asset.downcast::<T>().unwrap()) {
+        Ok(asset) => {
+            T::store_mut(assets).insert_reserved(handle, asset);
+
+            if reload {
+                T::on_reloaded(assets, handle);
+                events.push_event(AssetEvent::Reloaded(handle));
+            } else {
+                events.push_event(AssetEvent::Loaded(handle));
+            }
+
+            true
+        }
+        Err(err) => {
+            core_error!("Failed to load {}: {err}", type_name::<T>());
+            events.push_event(AssetEvent::Failed(handle, err));
+            false
+        }
+    }
+}
@@ -0,0 +1,113 @@
+use std::{
+    collections::HashMap,
+    fs,
+    path::{Path, PathBuf},
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        mpsc::{channel, Receiver},
+        Arc, Mutex,
+    },
+    thread::{self, JoinHandle},
+    time::{Duration, SystemTime},
+};
+
+use bizarre_log::core_trace;
+
+pub const DEFAULT_POLL_INTERVAL: Duration = Duration::from_millis(500);
+
+type WatchList = Arc<Mutex<HashMap<PathBuf, Option<SystemTime>>>>;
+
+/// Polls modification times of watched files on a background thread
+pub struct AssetWatcher {
+    watched: WatchList,
+    changes: Receiver<PathBuf>,
+    running: Arc<AtomicBool>,
+    thread: Option<JoinHandle<()>>,
+}
+
+impl AssetWatcher {
+    pub fn new(poll_interval: Duration) -> Self {
+        let watched: WatchList = Default::default();
+        let running = Arc::new(AtomicBool::new(true));
+        let (sender, changes) = channel();
+
+        let thread = {
+            let watched = watched.clone();
+            let running = running.clone();
+
+            thread::Builder::new()
+                .name("asset_watcher".into())
+                .spawn(move || {
+                    while running.load(Ordering::Relaxed) {
+                        thread::sleep(poll_interval);
+
+                        let mut watched = match watched.lock() {
+                            Ok(watched) => watched,
+                            Err(_) => break,
+                        };
+
+                        for (path, last_modified) in watched.iter_mut() {
+                            let modified = modified_time(path);
+
+                            if modified.is_some() && modified != *last_modified {
+                                *last_modified = modified;
+
+                                if sender.send(path.clone()).is_err() {
+                                    return;
+                                }
+                            }
+                        }
+                    }
+                })
+                .unwrap_or_else(|err| panic!("Failed to spawn asset watcher thread: {err}"))
+        };
+
+        Self {
+            watched,
+            changes,
+            running,
+            thread: Some(thread),
+        }
+    }
+
+    pub fn watch(&self, path: impl AsRef<Path>) {
+        let path = path.as_ref().to_path_buf();
+        let modified = modified_time(&path);
+
+        core_trace!("Watching {path:?} for changes");
+
+        self.watched
+            .lock()
+            .unwrap_or_else(|err| err.into_inner())
+            .insert(path, modified);
+    }
+
+    pub fn unwatch(&self, path: impl AsRef<Path>) {
+        self.watched
+            .lock()
+            .unwrap_or_else(|err| err.into_inner())
+            .remove(path.as_ref());
+    }
+
+    /// Paths changed since the last call, every path is reported once
+    pub fn changed_paths(&self) -> Vec<PathBuf> {
+        let mut paths = self.changes.try_iter().collect::<Vec<_>>();
+        paths.sort();
+        paths.dedup();
+        paths
+    }
+}
+
+impl Drop for AssetWatcher {
+    fn drop(&mut self) {
+        self.running.store(false, Ordering::Relaxed);
+
+        if let Some(thread) = self.thread.take() {
+            let _ = thread.join();
+        }
+    }
+}
+
+fn modified_time(path: &Path) -> Option<SystemTime> {
+    fs::metadata(path).and_then(|meta| meta.modified()).ok()
+}
@@ -0,0 +1,380 @@
+//! CPU decoders of the block-compressed formats, used when the device can't
+//! sample them. Decoded pixels match what sampling the compressed image returns:
+//! BC4 fills the red channel and BC5 the red and green ones
+
+use nalgebra_glm::UVec2;
+
+/// Decodes BC4 `blocks` of a `size` image into 8-bit RGBA
+pub(crate) fn decode_bc4(size: UVec2, blocks: &[u8]) -> Vec<u8> {
+    decode_blocks(size, blocks, 8, |block, pixels| {
+        let red = decode_bc4_channel(block);
+
+        for (pixel, red) in pixels.iter_mut().zip(red) {
+            *pixel = [red, 0, 0, 255];
+        }
+    })
+}
+
+/// Decodes BC5 `blocks` of a `size` image into 8-bit RGBA
+pub(crate) fn decode_bc5(size: UVec2, blocks: &[u8]) -> Vec<u8> {
+    decode_blocks(size, blocks, 16, |block, pixels| {
+        let red = decode_bc4_channel(&block[..8]);
+        let green = decode_bc4_channel(&block[8..]);
+
+        for ((pixel, red), green) in pixels.iter_mut().zip(red).zip(green) {
+            *pixel = [red, green, 0, 255];
+        }
+    })
+}
+
+/// Decodes BC7 `blocks` of a `size` image into 8-bit RGBA
+pub(crate) fn decode_bc7(size: UVec2, blocks: &[u8]) -> Vec<u8> {
+    decode_blocks(size, blocks, 16, decode_bc7_block)
+}
+
+/// Bytes of a block compressed image of `size` with `block_size` bytes per 4x4 block
+pub(crate) fn compressed_len(size: UVec2, block_size: usize) -> usize {
+    size.x.div_ceil(4) as usize * size.y.div_ceil(4) as usize * block_size
+}
+
+/// Runs `decode` for every 4x4 block and copies its pixels into the image,
+/// cropping blocks on the right and bottom edges
+fn decode_blocks(
+    size: UVec2,
+    blocks: &[u8],
+    block_size: usize,
+    mut decode: impl FnMut(&[u8], &mut [[u8; 4]; 16]),
+) -> Vec<u8> {
+    let (width, height) = (size.x as usize, size.y as usize);
+    let blocks_per_row = width.div_ceil(4);
+
+    let mut rgba = vec![0; width * height * 4];
+    let mut pixels = [[0; 4]; 16];
+
+    for (index, block) in blocks.chunks_exact(block_size).enumerate() {
+        let block_x = index % blocks_per_row * 4;
+        let block_y = index / blocks_per_row * 4;
+
+        if block_y >= height {
+            break;
+        }
+
+        decode(block, &mut pixels);
+
+        for (i, pixel) in pixels.iter().enumerate() {
+            let (x, y) = (block_x + i % 4, block_y + i / 4);
+
+            if x < width && y < height {
+                let offset = (y * width + x) * 4;
+                rgba[offset..offset + 4].copy_from_slice(pixel);
+            }
+        }
+    }
+
+    rgba
+}
+
+fn decode_bc4_channel(block: &[u8]) -> [u8; 16] {
+    let (r0, r1) = (block[0] as u32, block[1] as u32);
+
+    let palette: [u32; 8] = if r0 > r1 {
+        std::array::from_fn(|i| match i {
+            0 => r0,
+            1 => r1,
+            i => ((8 - i as u32) * r0 + (i as u32 - 1) * r1 + 3) / 7,
+        })
+    } else {
+        std::array::from_fn(|i| match i {
+            0 => r0,
+            1 => r1,
+            6 => 0,
+            7 => 255,
+            i => ((6 - i as u32) * r0 + (i as u32 - 1) * r1 + 2) / 5,
+        })
+    };
+
+    let indices = block[2..8]
+        .iter()
+        .rev()
+        .fold(0u64, |bits, byte| bits << 8 | *byte as u64);
+
+    std::array::from_fn(|i| palette[(indices >> (i * 3) & 7) as usize] as u8)
+}
+
+/// Layout of a BC7 mode
+struct Bc7Mode {
+    subsets: usize,
+    partition_bits: u32,
+    rotation_bits: u32,
+    index_selection_bits: u32,
+    color_bits: u32,
+    alpha_bits: u32,
+    endpoint_p_bits: bool,
+    shared_p_bits: bool,
+    index_bits: u32,
+    secondary_index_bits: u32,
+}
+
+#[allow(clippy::too_many_arguments)]
+const fn bc7_mode(
+    subsets: usize,
+    partition_bits: u32,
+    rotation_bits: u32,
+    index_selection_bits: u32,
+    color_bits: u32,
+    alpha_bits: u32,
+    endpoint_p_bits: bool,
+    shared_p_bits: bool,
+    index_bits: u32,
+    secondary_index_bits: u32,
+) -> Bc7Mode {
+    Bc7Mode {
+        subsets,
+        partition_bits,
+        rotation_bits,
+        index_selection_bits,
+        color_bits,
+        alpha_bits,
+        endpoint_p_bits,
+        shared_p_bits,
+        index_bits,
+        secondary_index_bits,
+    }
+}
+
+const BC7_MODES: [Bc7Mode; 8] = [
+    bc7_mode(3, 4, 0, 0, 4, 0, true, false, 3, 0),
+    bc7_mode(2, 6, 0, 0, 6, 0, false, true, 3, 0),
+    bc7_mode(3, 6, 0, 0, 5, 0, false, false, 2, 0),
+    bc7_mode(2, 6, 0, 0, 7, 0, true, false, 2, 0),
+    bc7_mode(1, 0, 2, 1, 5, 6, false, false, 2, 3),
+    bc7_mode(1, 0, 2, 0, 7, 8, false, false, 2, 2),
+    bc7_mode(1, 0, 0, 0, 7, 7, true, false, 4, 0),
+    bc7_mode(2, 6, 0, 0, 5, 5, true, false, 2, 0),
+];
+
+/// Subset of every pixel of the two subset partitions, a bit per pixel
+const BC7_PARTITIONS_2: [u16; 64] = [
+    0xcccc, 0x8888, 0xeeee, 0xecc8, 0xc880, 0xfeec, 0xfec8, 0xec80, 0xc800, 0xffec, 0xfe80, 0xe800,
+    0xffe8, 0xff00, 0xfff0, 0xf000, 0xf710, 0x008e, 0x7100, 0x08ce, 0x008c, 0x7310, 0x3100, 0x8cce,
+    0x088c, 0x3110, 0x6666, 0x366c, 0x17e8, 0x0ff0, 0x718e, 0x399c, 0xaaaa, 0xf0f0, 0x5a5a, 0x33cc,
+    0x3c3c, 0x55aa, 0x9696, 0xa55a, 0x73ce, 0x13c8, 0x324c, 0x3bdc, 0x6996, 0xc33c, 0x9966, 0x0660,
+    0x0272, 0x04e4, 0x4e40, 0x2720, 0xc936, 0x936c, 0x39c6, 0x639c, 0x9336, 0x9cc6, 0x817e, 0xe718,
+    0xccf0, 0x0fcc, 0x7744, 0xee22,
+];
+
+/// Subset of every pixel of the three subset partitions, two bits per pixel
+const BC7_PARTITIONS_3: [u32; 64] = [
+    0xaa685050, 0x6a5a5040, 0x5a5a4200, 0x5450a0a8, 0xa5a50000, 0xa0a05050, 0x5555a0a0, 0x5a5a5050,
+    0xaa550000, 0xaa555500, 0xaaaa5500, 0x90909090, 0x94949494, 0xa4a4a4a4, 0xa9a59450, 0x2a0a4250,
+    0xa5945040, 0x0a425054, 0xa5a5a500, 0x55a0a0a0, 0xa8a85454, 0x6a6a4040, 0xa4a45000, 0x1a1a0500,
+    0x0050a4a4, 0xaaa59090, 0x14696914, 0x69691400, 0xa08585a0, 0xaa821414, 0x50a4a450, 0x6a5a0200,
+    0xa9a58000, 0x5090a0a8, 0xa8a09050, 0x24242424, 0x00aa5500, 0x24924924, 0x24499224, 0x50a50a50,
+    0x500aa550, 0xaaaa4444, 0x66660000, 0xa5a0a5a0, 0x50a050a0, 0x69286928, 0x44aaaa44, 0x66666600,
+    0xaa444444, 0x54a854a8, 0x95809580, 0x96969600, 0xa85454a8, 0x80959580, 0xaa141414, 0x96960000,
+    0xaaaa1414, 0xa05050a0, 0xa0a5a5a0, 0x96000000, 0x40804080, 0xa9a8a9a8, 0xaaaaaa44, 0x2a4a5254,
+];
+
+/// Pixel of the second subset whose index has its top bit omitted
+const BC7_ANCHORS_2: [u8; 64] = [
+    15, 15, 15, 15, 15, 15, 15, 15, 15, 15, 15, 15, 15, 15, 15, 15, 15, 2, 8, 2, 2, 8, 8, 15, 2, 8,
+    2, 2, 8, 8, 2, 2, 15, 15, 6, 8, 2, 8, 15, 15, 2, 8, 2, 2, 2, 15, 15, 6, 6, 2, 6, 8, 15, 15, 2,
+    2, 15, 15, 15, 15, 15, 2, 2, 15,
+];
+
+/// Anchor pixels of the second and third subsets of the three subset partitions
+const BC7_ANCHORS_3: [[u8; 64]; 2] = [
+    [
+        3, 3, 15, 15, 8, 3, 15, 15, 8, 8, 6, 6, 6, 5, 3, 3, 3, 3, 8, 15, 3, 3, 6, 10, 5, 8, 8, 6,
+        8, 5, 15, 15, 8, 15, 3, 5, 6, 10, 8, 15, 15, 3, 15, 5, 15, 15, 15, 15, 3, 15, 5, 5, 5, 8,
+        5, 10, 5, 10, 8, 13, 15, 12, 3, 3,
+    ],
+    [
+        15, 8, 8, 3, 15, 15, 3, 8, 15, 15, 15, 15, 15, 15, 15, 8, 15, 8, 15, 3, 15, 8, 15, 8, 3,
+        15, 6, 10, 15, 15, 10, 8, 15, 3, 15, 10, 10, 8, 9, 10, 6, 15, 8, 15, 3, 6, 6, 8, 15, 3, 15,
+        15, 15, 15, 15, 15, 15, 15, 15, 15, 3, 15, 15, 8,
+    ],
+];
+
+const BC7_WEIGHTS_2: [u32; 4] = [0, 21, 43, 64];
+const BC7_WEIGHTS_3: [u32; 8] = [0, 9, 18, 27, 37, 46, 55, 64];
+const BC7_WEIGHTS_4: [u32; 16] = [0, 4, 9, 13, 17, 21, 26, 30, 34, 38, 43, 47, 51, 55, 60, 64];
+
+/// Reads a block from the least significant bit of its first byte on
+struct BitReader(u128);
+
+impl BitReader {
+    fn read(&mut self, bits: u32) -> u32 {
+        let value = (self.0 & ((1 << bits) - 1)) as u32;
+        self.0 >>= bits;
+        value
+    }
+}
+
+fn bc7_weights(index_bits: u32) -> &'static [u32] {
+    match index_bits {
+        2 => &BC7_WEIGHTS_2,
+        3 => &BC7_WEIGHTS_3,
+        _ => &BC7_WEIGHTS_4,
+    }
+}
+
+fn bc7_subset(subsets: usize, partition: usize, pixel: usize) -> usize {
+    match subsets {
+        2 => (BC7_PARTITIONS_2[partition] >> pixel & 1) as usize,
+        3 => (BC7_PARTITIONS_3[partition] >> (pixel * 2) & 3) as usize,
+        _ => 0,
+    }
+}
+
+fn is_bc7_anchor(subsets: usize, partition: usize, pixel: usize) -> bool {
+    pixel == 0
+        || match subsets {
+            2 => BC7_ANCHORS_2[partition] as usize == pixel,
+            3 => BC7_ANCHORS_3
+                .iter()
+                .any(|anchors| anchors[partition] as usize == pixel),
+            _ => false,
+        }
+}
+
+/// Expands a `bits` wide value to 8 bits by repeating its top bits
+fn unquantize(value: u32, bits: u32) -> u32 {
+    let value = value << (8 - bits);
+    value | value >> bits
+}
+
+fn decode_bc7_block(block: &[u8], pixels: &mut [[u8; 4]; 16]) {
+    let mut bits = BitReader(u128::from_le_bytes(block.try_into().unwrap()));
+
+    // Reserved mode, decoded as transparent black
+    if block[0] == 0 {
+        *pixels = [[0; 4]; 16];
+        return;
+    }
+
+    let mode_index = block[0].trailing_zeros();
+    let mode = &BC7_MODES[mode_index as usize];
+    bits.read(mode_index + 1);
+
+    let partition = bits.read(mode.partition_bits) as usize;
+    let rotation = bits.read(mode.rotation_bits);
+    let index_selection = bits.read(mode.index_selection_bits);
+
+    let endpoint_count = mode.subsets * 2;
+    let mut endpoints = [[0u32; 4]; 6];
+
+    for channel in 0..3 {
+        for endpoint in &mut endpoints[..endpoint_count] {
+            endpoint[channel] = bits.read(mode.color_bits);
+        }
+    }
+
+    for endpoint in &mut endpoints[..endpoint_count] {
+        endpoint[3] = match mode.alpha_bits {
+            0 => 255,
+            alpha_bits => bits.read(alpha_bits),
+        };
+    }
+
+    let mut color_bits = mode.color_bits;
+    let mut alpha_bits = mode.alpha_bits;
+
+    if mode.endpoint_p_bits || mode.shared_p_bits {
+        let mut p_bits = [0; 6];
+
+        if mode.endpoint_p_bits {
+            for p_bit in &mut p_bits[..endpoint_count] {
+                *p_bit = bits.read(1);
+            }
+        } else {
+            for subset in 0..mode.subsets {
+                let p_bit = bits.read(1);
+                p_bits[subset * 2] = p_bit;
+                p_bits[subset * 2 + 1] = p_bit;
+            }
+        }
+
+        for (endpoint, p_bit) in endpoints[..endpoint_count].iter_mut().zip(p_bits) {
+            for value in &mut endpoint[..3] {
+                *value = *value << 1 | p_bit;
+            }
+
+            if mode.alpha_bits > 0 {
+                endpoint[3] = endpoint[3] << 1 | p_bit;
+            }
+        }
+
+        color_bits += 1;
+
+        if mode.alpha_bits > 0 {
+            alpha_bits += 1;
+        }
+    }
+
+    for endpoint in &mut endpoints[..endpoint_count] {
+        for value in &mut endpoint[..3] {
+            *value = unquantize(*value, color_bits);
+        }
+
+        if alpha_bits > 0 {
+            endpoint[3] = unquantize(endpoint[3], alpha_bits);
+        }
+    }
+
+    let mut read_indices = |index_bits: u32| -> [u32; 16] {
+        std::array::from_fn(|pixel| match index_bits {
+            0 => 0,
+            index_bits if is_bc7_anchor(mode.subsets, partition, pixel) => {
+                bits.read(index_bits - 1)
+            }
+            index_bits => bits.read(index_bits),
+        })
+    };
+
+    let indices = read_indices(mode.index_bits);
+    let secondary_indices = read_indices(mode.secondary_index_bits);
+
+    let (color_indices, color_index_bits, alpha_indices, alpha_index_bits) =
+        match (mode.secondary_index_bits, index_selection) {
+            (0, _) => (indices, mode.index_bits, indices, mode.index_bits),
+            (_, 0) => (
+                indices,
+                mode.index_bits,
+                secondary_indices,
+                mode.secondary_index_bits,
+            ),
+            _ => (
+                secondary_indices,
+                mode.secondary_index_bits,
+                indices,
+                mode.index_bits,
+            ),
+        };
+
+    let color_weights = bc7_weights(color_index_bits);
+    let alpha_weights = bc7_weights(alpha_index_bits);
+
+    for (pixel_index, pixel) in pixels.iter_mut().enumerate() {
+        let subset = bc7_subset(mode.subsets, partition, pixel_index);
+        let (start, end) = (endpoints[subset * 2], endpoints[subset * 2 + 1]);
+
+        let interpolate = |channel: usize, weight: u32| {
+            (((64 - weight) * start[channel] + weight * end[channel] + 32) >> 6) as u8
+        };
+
+        let color_weight = color_weights[color_indices[pixel_index] as usize];
+        let alpha_weight = alpha_weights[alpha_indices[pixel_index] as usize];
+
+        *pixel = [
+            interpolate(0, color_weight),
+            interpolate(1, color_weight),
+            interpolate(2, color_weight),
+            interpolate(3, alpha_weight),
+        ];
+
+        if rotation > 0 {
+            pixel.swap(3, rotation as usize - 1);
+        }
+    }
+}
@@ -1,4 +1,5 @@
 use std::{
+    any::type_name,
     ops::{Deref, DerefMut, RangeBounds},
     ptr::slice_from_raw_parts_mut,
 };
@@ -7,7 +8,15 @@ use ash::vk;
 use thiserror::Error;
 use vma::Alloc;
 
-use crate::{device::LogicalDevice, vulkan_context::get_device};
+use crate::{
+    device::LogicalDevice,
+    memory_stats::{track_allocation, track_free, AllocationCategory},
+    vulkan_context::get_device,
+};
+
+pub use typed::TypedGpuBuffer;
+
+mod typed;
 
 #[derive(Debug, Error)]
 pub enum BufferError {
@@ -15,6 +24,25 @@ pub enum BufferError {
     VkError(#[from] vk::Result),
     #[error("Could not transfer data between buffers: {0}")]
     TransferError(#[from] BufferTransferError),
+    #[error("Access of {size} bytes at offset {offset} is out of bounds of a buffer of {buffer_size} bytes")]
+    OutOfBounds {
+        offset: vk::DeviceSize,
+        size: vk::DeviceSize,
+        buffer_size: vk::DeviceSize,
+    },
+    #[error("Offset {offset} is not aligned to the {align} byte alignment of `{type_name}`")]
+    Misaligned {
+        offset: usize,
+        align: usize,
+        type_name: &'static str,
+    },
+    #[error("`{type_name}` of {size} bytes can't be a buffer element, its size must be a non-zero multiple of 4")]
+    InvalidElement {
+        type_name: &'static str,
+        size: usize,
+    },
+    #[error("Index {index} is out of bounds of a buffer with capacity {capacity}")]
+    IndexOutOfBounds { index: usize, capacity: usize },
 }
 
 #[derive(Debug, Error)]
@@ -73,12 +101,38 @@ impl GpuBuffer {
         buffer_usage: vk::BufferUsageFlags,
         mem_usage: vma::MemoryUsage,
         alloc_flags: vma::AllocationCreateFlags,
+    ) -> BufferResult<Self> {
+        Self::new_shared(size, buffer_usage, mem_usage, alloc_flags, &[])
+    }
+
+    /// Creates a buffer accessed by queues of all `queue_families` without
+    /// ownership transfers. Duplicates are ignored, the buffer is exclusive
+    /// when there's only one family
+    pub fn new_shared(
+        size: vk::DeviceSize,
+        buffer_usage: vk::BufferUsageFlags,
+        mem_usage: vma::MemoryUsage,
+        alloc_flags: vma::AllocationCreateFlags,
+        queue_families: &[u32],
     ) -> BufferResult<Self> {
         let device = get_device();
+
+        let mut queue_families = queue_families.to_vec();
+        queue_families.sort();
+        queue_families.dedup();
+
         let (buffer, allocation) = {
             let buffer_info = vk::BufferCreateInfo::default()
                 .size(size as vk::DeviceSize)
                 .usage(buffer_usage);
+
+            let buffer_info = if queue_families.len() > 1 {
+                buffer_info
+                    .sharing_mode(vk::SharingMode::CONCURRENT)
+                    .queue_family_indices(&queue_families)
+            } else {
+                buffer_info
+            };
             let allocation_info = vma::AllocationCreateInfo {
                 usage: mem_usage,
                 flags: alloc_flags,
@@ -91,6 +145,8 @@ impl GpuBuffer {
             }
         };
 
+        track_allocation(AllocationCategory::Buffer, &allocation);
+
         Ok(Self {
             buffer,
             allocation,
@@ -316,6 +372,98 @@ impl GpuBuffer {
         Ok(())
     }
 
+    /// Writes `data` at byte `offset` through a staging buffer copied on the
+    /// transfer queue, for buffers without host access. Blocks until the copy is done.
+    ///
+    /// The buffer has to be created with `TRANSFER_DST` and shared with the
+    /// transfer queue family if it's used by another one
+    pub fn upload<T: Copy>(
+        &mut self,
+        device: &LogicalDevice,
+        offset: vk::DeviceSize,
+        data: &[T],
+    ) -> BufferResult<()> {
+        let size = size_of_val(data) as vk::DeviceSize;
+
+        if size == 0 {
+            return Ok(());
+        }
+
+        if !self
+            .buffer_usage
+            .contains(vk::BufferUsageFlags::TRANSFER_DST)
+        {
+            return Err(BufferTransferError::NoTransferFlags {
+                no_transfer_src: false,
+                no_transfer_dst: true,
+            }
+            .into());
+        }
+
+        if offset + size > self.size {
+            return Err(BufferTransferError::TransferDstTooSmall {
+                src_size: size,
+                dst_offset: offset,
+                dst_size: self.size,
+            }
+            .into());
+        }
+
+        let mut staging = Self::staging_buffer(device, size)?;
+
+        let result = (|| {
+            staging.map_as_slice(0, data.len())?.copy_from_slice(data);
+            staging.flush_range(0, size)?;
+
+            unsafe {
+                let allocate_info = vk::CommandBufferAllocateInfo::default()
+                    .command_pool(device.transfer_cmd_pool)
+                    .level(vk::CommandBufferLevel::PRIMARY)
+                    .command_buffer_count(1);
+
+                let cmd_buffer = device.allocate_command_buffers(&allocate_info)?[0];
+
+                let begin_info = vk::CommandBufferBeginInfo::default()
+                    .flags(vk::CommandBufferUsageFlags::ONE_TIME_SUBMIT);
+
+                device.begin_command_buffer(cmd_buffer, &begin_info)?;
+
+                let regions = [vk::BufferCopy2::default()
+                    .src_offset(0)
+                    .dst_offset(offset)
+                    .size(size)];
+
+                let copy_info = vk::CopyBufferInfo2::default()
+                    .src_buffer(staging.buffer)
+                    .dst_buffer(self.buffer)
+                    .regions(&regions);
+
+                device.cmd_copy_buffer2(cmd_buffer, &copy_info);
+                device.end_command_buffer(cmd_buffer)?;
+
+                let cmd_buffers = [cmd_buffer];
+                let submits = [vk::SubmitInfo::default().command_buffers(&cmd_buffers)];
+
+                let fence = device.create_fence(&vk::FenceCreateInfo::default(), None)?;
+
+                let submitted = device
+                    .queue_submit(device.transfer_queue, &submits, fence)
+                    .and_then(|_| device.wait_for_fences(&[fence], true, u64::MAX));
+
+                device.destroy_fence(fence, None);
+                device.free_command_buffers(device.transfer_cmd_pool, &cmd_buffers);
+
+                submitted?;
+            }
+
+            Ok(())
+        })();
+
+        staging.destroy(device);
+
+        result
+    }
+
     pub fn grow(&mut self, device: &LogicalDevice, size: vk::DeviceSize) -> BufferResult<()> {
         if size < self.size {
             return Ok(());
@@ -333,11 +481,19 @@ impl GpuBuffer {
     }
 
     pub fn destroy(&mut self, device: &LogicalDevice) {
+        if self.buffer == vk::Buffer::null() {
+            return;
+        }
+
+        track_free(AllocationCategory::Buffer, &self.allocation);
+
         unsafe {
             device
                 .allocator
                 .destroy_buffer(self.buffer, &mut self.allocation);
         }
+
+        self.buffer = vk::Buffer::null();
     }
 
     pub fn map_as_slice<'a, T>(
@@ -346,15 +502,15 @@ impl GpuBuffer {
         len: usize,
     ) -> BufferResult<MappedAllocation<'a, [T]>> {
         let size = size_of::<T>() * len;
-
-        if offset + size > self.size as usize {
-            return panic!("Buffer is too small!");
-        }
+        self.check_access::<T>(offset, size)?;
 
         let device = get_device();
 
         let slice = unsafe {
-            let ptr = device.allocator.map_memory(&mut self.allocation)? as *mut T;
+            let ptr = device
+                .allocator
+                .map_memory(&mut self.allocation)?
+                .add(offset) as *mut T;
             &mut *slice_from_raw_parts_mut(ptr, len)
         };
 
@@ -371,15 +527,15 @@ impl GpuBuffer {
 
     pub fn map_memory<'a, T>(&'a mut self, offset: usize) -> BufferResult<MappedAllocation<'a, T>> {
         let size = size_of::<T>();
-
-        if offset + size > self.size as usize {
-            return panic!("Buffer is too small!");
-        }
+        self.check_access::<T>(offset, size)?;
 
         let device = get_device();
 
         let data = unsafe {
-            let ptr = device.allocator.map_memory(&mut self.allocation)? as *mut T;
+            let ptr = device
+                .allocator
+                .map_memory(&mut self.allocation)?
+                .add(offset) as *mut T;
             &mut *ptr
         };
 
@@ -393,6 +549,26 @@ impl GpuBuffer {
 
         Ok(mapped_allocation)
     }
+
+    fn check_access<T>(&self, offset: usize, size: usize) -> BufferResult<()> {
+        if offset + size > self.size as usize {
+            return Err(BufferError::OutOfBounds {
+                offset: offset as vk::DeviceSize,
+                size: size as vk::DeviceSize,
+                buffer_size: self.size,
+            });
+        }
+
+        if offset % align_of::<T>() != 0 {
+            return Err(BufferError::Misaligned {
+                offset,
+                align: align_of::<T>(),
+                type_name: type_name::<T>(),
+            });
+        }
+
+        Ok(())
+    }
 }
 
 pub struct MappedAllocation<'a, T: ?Sized> {
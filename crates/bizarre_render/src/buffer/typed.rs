@@ -0,0 +1,164 @@
+use std::{any::type_name, marker::PhantomData, ops::Deref};
+
+use ash::vk;
+
+use crate::device::LogicalDevice;
+
+use super::{BufferError, BufferResult, GpuBuffer};
+
+/// Host visible [`GpuBuffer`] holding `capacity` elements of `T`, of which the
+/// first `len` were written
+pub struct TypedGpuBuffer<T> {
+    buffer: GpuBuffer,
+    len: usize,
+    capacity: usize,
+    _marker: PhantomData<T>,
+}
+
+impl<T> std::fmt::Debug for TypedGpuBuffer<T> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("TypedGpuBuffer")
+            .field("element", &type_name::<T>())
+            .field("buffer", &self.buffer)
+            .field("len", &self.len)
+            .field("capacity", &self.capacity)
+            .finish()
+    }
+}
+
+impl<T: Clone> TypedGpuBuffer<T> {
+    /// Fails with [`BufferError::InvalidElement`] if `T` can't be laid out in GPU
+    /// memory as is, its size has to be a non-zero multiple of 4
+    pub fn new(
+        capacity: usize,
+        buffer_usage: vk::BufferUsageFlags,
+        mem_usage: vma::MemoryUsage,
+        alloc_flags: vma::AllocationCreateFlags,
+    ) -> BufferResult<Self> {
+        Self::validate_element()?;
+
+        let buffer = GpuBuffer::new(
+            (Self::stride() * capacity) as vk::DeviceSize,
+            buffer_usage,
+            mem_usage,
+            alloc_flags,
+        )?;
+
+        Ok(Self {
+            buffer,
+            len: 0,
+            capacity,
+            _marker: PhantomData,
+        })
+    }
+
+    /// Size of an element in bytes
+    pub fn stride() -> usize {
+        size_of::<T>()
+    }
+
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    pub fn capacity(&self) -> usize {
+        self.capacity
+    }
+
+    /// Replaces the contents with `data`
+    pub fn write(&mut self, data: &[T]) -> BufferResult<()> {
+        if data.len() > self.capacity {
+            return Err(BufferError::OutOfBounds {
+                offset: 0,
+                size: size_of_val(data) as vk::DeviceSize,
+                buffer_size: self.buffer.size(),
+            });
+        }
+
+        if !data.is_empty() {
+            self.buffer
+                .map_as_slice::<T>(0, data.len())?
+                .clone_from_slice(data);
+            self.buffer
+                .flush_range(0, size_of_val(data) as vk::DeviceSize)?;
+        }
+
+        self.len = data.len();
+        Ok(())
+    }
+
+    /// Writes a single element, the length grows to include it
+    pub fn write_at(&mut self, index: usize, value: &T) -> BufferResult<()> {
+        if index >= self.capacity {
+            return Err(BufferError::IndexOutOfBounds {
+                index,
+                capacity: self.capacity,
+            });
+        }
+
+        let offset = index * Self::stride();
+
+        *self.buffer.map_memory::<T>(offset)? = value.clone();
+        self.buffer
+            .flush_range(offset as vk::DeviceSize, Self::stride() as vk::DeviceSize)?;
+
+        self.len = self.len.max(index + 1);
+        Ok(())
+    }
+
+    pub fn clear(&mut self) {
+        self.len = 0;
+    }
+
+    /// Recreates the buffer with room for `capacity` elements, the contents are
+    /// discarded. Does nothing if the buffer is already large enough
+    pub fn reserve(&mut self, device: &LogicalDevice, capacity: usize) -> BufferResult<()> {
+        if capacity <= self.capacity {
+            return Ok(());
+        }
+
+        let buffer = GpuBuffer::new(
+            (Self::stride() * capacity) as vk::DeviceSize,
+            *self.buffer.buffer_usage(),
+            *self.buffer.memory_usage(),
+            *self.buffer.allocation_flags(),
+        )?;
+
+        self.buffer.destroy(device);
+        self.buffer = buffer;
+        self.capacity = capacity;
+        self.len = 0;
+
+        Ok(())
+    }
+
+    pub fn destroy(&mut self, device: &LogicalDevice) {
+        self.buffer.destroy(device);
+        self.len = 0;
+    }
+
+    fn validate_element() -> BufferResult<()> {
+        let size = size_of::<T>();
+
+        if size == 0 || size % 4 != 0 {
+            return Err(BufferError::InvalidElement {
+                type_name: type_name::<T>(),
+                size,
+            });
+        }
+
+        Ok(())
+    }
+}
+
+impl<T> Deref for TypedGpuBuffer<T> {
+    type Target = GpuBuffer;
+
+    fn deref(&self) -> &Self::Target {
+        &self.buffer
+    }
+}
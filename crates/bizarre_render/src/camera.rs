@@ -0,0 +1,266 @@
+use ash::vk;
+use bizarre_ecs::prelude::*;
+use nalgebra_glm::{Mat4, UVec2, Vec2, Vec3, Vec4};
+
+use crate::{
+    render_target::RenderTargetHandle,
+    scene::{SceneHandle, SceneUniform},
+    submitter::ClearMode,
+};
+
+/// Normalized rectangle of a render target a camera draws into.
+///
+/// `(0, 0)` is the top left corner of the target, `(1, 1)` is the bottom right one.
+/// Attached to a camera entity it overrides [`Camera::viewport`], so editors can
+/// move the scene view around without touching the camera itself
+#[derive(Component, Clone, Copy, Debug, PartialEq)]
+pub struct CameraViewport {
+    pub x: f32,
+    pub y: f32,
+    pub width: f32,
+    pub height: f32,
+}
+
+impl Default for CameraViewport {
+    fn default() -> Self {
+        Self::FULL
+    }
+}
+
+impl CameraViewport {
+    pub const FULL: Self = Self {
+        x: 0.0,
+        y: 0.0,
+        width: 1.0,
+        height: 1.0,
+    };
+
+    pub fn new(x: f32, y: f32, width: f32, height: f32) -> Self {
+        Self {
+            x,
+            y,
+            width,
+            height,
+        }
+    }
+
+    /// Converts the viewport into a pixel rectangle of a target with `extent` size.
+    ///
+    /// Returns `None` if the resulting rectangle is empty
+    pub fn to_rect(&self, extent: UVec2) -> Option<vk::Rect2D> {
+        let x0 = (self.x.clamp(0.0, 1.0) * extent.x as f32).round() as u32;
+        let y0 = (self.y.clamp(0.0, 1.0) * extent.y as f32).round() as u32;
+        let x1 = ((self.x + self.width).clamp(0.0, 1.0) * extent.x as f32).round() as u32;
+        let y1 = ((self.y + self.height).clamp(0.0, 1.0) * extent.y as f32).round() as u32;
+
+        if x1 <= x0 || y1 <= y0 {
+            return None;
+        }
+
+        Some(vk::Rect2D {
+            offset: vk::Offset2D {
+                x: x0 as i32,
+                y: y0 as i32,
+            },
+            extent: vk::Extent2D {
+                width: x1 - x0,
+                height: y1 - y0,
+            },
+        })
+    }
+}
+
+/// Half-line in world space, `direction` is normalized
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Ray {
+    pub origin: Vec3,
+    pub direction: Vec3,
+}
+
+impl Ray {
+    pub fn new(origin: Vec3, direction: Vec3) -> Self {
+        Self {
+            origin,
+            direction: direction.normalize(),
+        }
+    }
+
+    pub fn at(&self, distance: f32) -> Vec3 {
+        self.origin + self.direction * distance
+    }
+
+    /// Point where the ray crosses the plane, `None` if the plane is parallel to
+    /// the ray or behind it
+    pub fn intersect_plane(&self, point: Vec3, normal: Vec3) -> Option<Vec3> {
+        let denominator = normal.dot(&self.direction);
+        if denominator.abs() <= f32::EPSILON {
+            return None;
+        }
+
+        let distance = normal.dot(&(point - self.origin)) / denominator;
+        (distance >= 0.0).then(|| self.at(distance))
+    }
+}
+
+/// Pixel viewport and scissor of a render into a target with `extent` size.
+///
+/// The scissor is clipped by the viewport and defaults to it, returns `None`
+/// if nothing would be drawn
+pub(crate) fn render_rects(
+    viewport: &CameraViewport,
+    scissor: Option<&CameraViewport>,
+    extent: UVec2,
+) -> Option<(vk::Rect2D, vk::Rect2D)> {
+    let viewport = viewport.to_rect(extent)?;
+
+    let Some(scissor) = scissor else {
+        return Some((viewport, viewport));
+    };
+
+    let scissor = scissor.to_rect(extent)?;
+
+    let x0 = viewport.offset.x.max(scissor.offset.x);
+    let y0 = viewport.offset.y.max(scissor.offset.y);
+    let x1 = (viewport.offset.x + viewport.extent.width as i32)
+        .min(scissor.offset.x + scissor.extent.width as i32);
+    let y1 = (viewport.offset.y + viewport.extent.height as i32)
+        .min(scissor.offset.y + scissor.extent.height as i32);
+
+    if x1 <= x0 || y1 <= y0 {
+        return None;
+    }
+
+    let scissor = vk::Rect2D {
+        offset: vk::Offset2D { x: x0, y: y0 },
+        extent: vk::Extent2D {
+            width: (x1 - x0) as u32,
+            height: (y1 - y0) as u32,
+        },
+    };
+
+    Some((viewport, scissor))
+}
+
+/// Renders `scene` into `render_target` from its own point of view.
+///
+/// Cameras sharing a render target are drawn in ascending `priority` order by
+/// [`render_cameras`][crate::ecs::render_cameras], each one into its own `viewport`
+#[derive(Component, Clone, Debug)]
+pub struct Camera {
+    pub scene: SceneHandle,
+    pub render_target: RenderTargetHandle,
+    pub view: Mat4,
+    pub projection: Mat4,
+    pub viewport: CameraViewport,
+    /// Part of the target the camera is clipped by, the whole viewport if `None`
+    pub scissor: Option<CameraViewport>,
+    pub priority: i32,
+    pub active: bool,
+    pub clear: ClearMode,
+    /// Depth the viewport is cleared to, `None` keeps the depth of cameras drawn before
+    pub clear_depth: Option<f32>,
+}
+
+impl Camera {
+    pub fn new(scene: SceneHandle, render_target: RenderTargetHandle) -> Self {
+        Self {
+            scene,
+            render_target,
+            view: Mat4::identity(),
+            projection: Mat4::identity(),
+            viewport: CameraViewport::FULL,
+            scissor: None,
+            priority: 0,
+            active: true,
+            clear: ClearMode::default(),
+            clear_depth: Some(1.0),
+        }
+    }
+
+    pub fn with_view(mut self, view: Mat4) -> Self {
+        self.view = view;
+        self
+    }
+
+    pub fn with_projection(mut self, projection: Mat4) -> Self {
+        self.projection = projection;
+        self
+    }
+
+    pub fn with_viewport(mut self, viewport: CameraViewport) -> Self {
+        self.viewport = viewport;
+        self
+    }
+
+    pub fn with_scissor(mut self, scissor: CameraViewport) -> Self {
+        self.scissor = Some(scissor);
+        self
+    }
+
+    pub fn with_priority(mut self, priority: i32) -> Self {
+        self.priority = priority;
+        self
+    }
+
+    /// Overlay cameras usually keep the color drawn by lower priorities with
+    /// [`ClearMode::None`] and clear the depth only
+    pub fn with_clear(mut self, clear: ClearMode) -> Self {
+        self.clear = clear;
+        self
+    }
+
+    pub fn with_clear_depth(mut self, clear_depth: Option<f32>) -> Self {
+        self.clear_depth = clear_depth;
+        self
+    }
+
+    pub fn uniform(&self) -> SceneUniform {
+        SceneUniform::new(self.view, self.projection)
+    }
+
+    /// Ray from the near plane through `screen_pos`.
+    ///
+    /// Both `screen_pos` and `viewport` are in pixels of the render target, with the
+    /// origin in its top left corner. Returns `None` if the projection can't be
+    /// inverted
+    pub fn screen_to_world_ray(&self, screen_pos: Vec2, viewport: vk::Rect2D) -> Option<Ray> {
+        let inverse = (self.projection * self.view).try_inverse()?;
+        let (offset, size) = rect_vectors(viewport);
+
+        let ndc = (screen_pos - offset).component_div(&size) * 2.0 - Vec2::repeat(1.0);
+        // The viewport is flipped, so `+Y` of the clip space is the top of the target
+        let ndc = Vec2::new(ndc.x, -ndc.y);
+
+        let unproject = |depth: f32| {
+            let point = inverse * Vec4::new(ndc.x, ndc.y, depth, 1.0);
+            point.xyz() / point.w
+        };
+
+        let near = unproject(-1.0);
+        let far = unproject(1.0);
+
+        Some(Ray::new(near, far - near))
+    }
+
+    /// Pixel of the render target `point` is drawn at, `None` if it's behind the
+    /// camera. The pixel may be outside of the viewport
+    pub fn world_to_screen(&self, point: Vec3, viewport: vk::Rect2D) -> Option<Vec2> {
+        let clip = self.projection * self.view * Vec4::new(point.x, point.y, point.z, 1.0);
+        if clip.w <= f32::EPSILON {
+            return None;
+        }
+
+        let ndc = clip.xy() / clip.w;
+        let (offset, size) = rect_vectors(viewport);
+        let normalized = Vec2::new(ndc.x + 1.0, 1.0 - ndc.y) * 0.5;
+
+        Some(offset + normalized.component_mul(&size))
+    }
+}
+
+fn rect_vectors(rect: vk::Rect2D) -> (Vec2, Vec2) {
+    (
+        Vec2::new(rect.offset.x as f32, rect.offset.y as f32),
+        Vec2::new(rect.extent.width as f32, rect.extent.height as f32),
+    )
+}
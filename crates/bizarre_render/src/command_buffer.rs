@@ -0,0 +1,361 @@
+use ash::vk;
+
+use crate::{
+    culling::draw_indexed_indirect_count, device::LogicalDevice, material::descriptor_buffer,
+    renderer::RenderResult,
+};
+
+/// Single command of a [`CommandRecording`], keeps only what's needed to compare
+/// two recordings and to replay them
+#[derive(Clone, Debug, PartialEq)]
+pub enum RecordedCommand {
+    /// Flipped viewport mapped onto the area, see [`flipped_viewport`]
+    SetViewport(vk::Rect2D),
+    SetScissor(vk::Rect2D),
+    /// Addresses and usages of the bound descriptor buffers
+    BindDescriptorBuffers(Vec<(vk::DeviceAddress, vk::BufferUsageFlags)>),
+    SetDescriptorBufferOffsets {
+        layout: vk::PipelineLayout,
+        first_set: u32,
+        buffer_indices: Vec<u32>,
+        offsets: Vec<vk::DeviceSize>,
+    },
+    BindPipeline(vk::Pipeline),
+    BindVertexBuffer(vk::Buffer),
+    BindIndexBuffer(vk::Buffer),
+    PushConstants {
+        layout: vk::PipelineLayout,
+        stages: vk::ShaderStageFlags,
+        bytes: Vec<u8>,
+    },
+    Draw {
+        vertex_count: u32,
+        instance_count: u32,
+    },
+    DrawIndexedIndirect {
+        buffer: vk::Buffer,
+        offset: vk::DeviceSize,
+        draw_count: u32,
+    },
+    DrawIndexedIndirectCount {
+        buffer: vk::Buffer,
+        offset: vk::DeviceSize,
+        count_buffer: vk::Buffer,
+        count_offset: vk::DeviceSize,
+        max_draw_count: u32,
+    },
+}
+
+/// Graphics commands recorded on the CPU, replayed into a primary command buffer
+/// or kept in a [`SecondaryCommandBuffer`] that's re-recorded only when they change.
+///
+/// Recording is cheap, so draws are recorded every frame and compared with the
+/// previous recording instead of tracking what they depend on
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct CommandRecording {
+    commands: Vec<RecordedCommand>,
+}
+
+impl CommandRecording {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn commands(&self) -> &[RecordedCommand] {
+        &self.commands
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.commands.is_empty()
+    }
+
+    pub fn push(&mut self, command: RecordedCommand) {
+        self.commands.push(command);
+    }
+
+    /// Sets a flipped viewport, so `+Y` points up like in OpenGL
+    pub fn set_viewport_and_scissor(&mut self, viewport: vk::Rect2D, scissor: vk::Rect2D) {
+        self.push(RecordedCommand::SetScissor(scissor));
+        self.push(RecordedCommand::SetViewport(viewport));
+    }
+
+    pub fn bind_descriptor_buffers(&mut self, buffers: &[vk::DescriptorBufferBindingInfoEXT]) {
+        self.push(RecordedCommand::BindDescriptorBuffers(
+            buffers
+                .iter()
+                .map(|buffer| (buffer.address, buffer.usage))
+                .collect(),
+        ));
+    }
+
+    pub fn set_descriptor_buffer_offsets(
+        &mut self,
+        layout: vk::PipelineLayout,
+        first_set: u32,
+        buffer_indices: &[u32],
+        offsets: &[vk::DeviceSize],
+    ) {
+        self.push(RecordedCommand::SetDescriptorBufferOffsets {
+            layout,
+            first_set,
+            buffer_indices: buffer_indices.to_vec(),
+            offsets: offsets.to_vec(),
+        });
+    }
+
+    pub fn bind_pipeline(&mut self, pipeline: vk::Pipeline) {
+        self.push(RecordedCommand::BindPipeline(pipeline));
+    }
+
+    pub fn bind_mesh_buffers(&mut self, vertex_buffer: vk::Buffer, index_buffer: vk::Buffer) {
+        self.push(RecordedCommand::BindVertexBuffer(vertex_buffer));
+        self.push(RecordedCommand::BindIndexBuffer(index_buffer));
+    }
+
+    pub fn push_constants(
+        &mut self,
+        layout: vk::PipelineLayout,
+        stages: vk::ShaderStageFlags,
+        bytes: &[u8],
+    ) {
+        self.push(RecordedCommand::PushConstants {
+            layout,
+            stages,
+            bytes: bytes.to_vec(),
+        });
+    }
+
+    pub fn draw(&mut self, vertex_count: u32, instance_count: u32) {
+        self.push(RecordedCommand::Draw {
+            vertex_count,
+            instance_count,
+        });
+    }
+
+    pub fn draw_indexed_indirect(
+        &mut self,
+        buffer: vk::Buffer,
+        offset: vk::DeviceSize,
+        draw_count: u32,
+    ) {
+        self.push(RecordedCommand::DrawIndexedIndirect {
+            buffer,
+            offset,
+            draw_count,
+        });
+    }
+
+    pub fn draw_indexed_indirect_count(
+        &mut self,
+        buffer: vk::Buffer,
+        offset: vk::DeviceSize,
+        count_buffer: vk::Buffer,
+        count_offset: vk::DeviceSize,
+        max_draw_count: u32,
+    ) {
+        self.push(RecordedCommand::DrawIndexedIndirectCount {
+            buffer,
+            offset,
+            count_buffer,
+            count_offset,
+            max_draw_count,
+        });
+    }
+
+    /// Records the commands into `cmd_buffer`
+    pub fn replay(&self, device: &LogicalDevice, cmd_buffer: vk::CommandBuffer) {
+        let db_device_ext = descriptor_buffer::device_ext();
+        let stride = size_of::<vk::DrawIndexedIndirectCommand>() as u32;
+
+        for command in &self.commands {
+            unsafe {
+                match command {
+                    RecordedCommand::SetViewport(area) => {
+                        device.cmd_set_viewport(cmd_buffer, 0, &[flipped_viewport(*area)])
+                    }
+                    RecordedCommand::SetScissor(scissor) => {
+                        device.cmd_set_scissor(cmd_buffer, 0, &[*scissor])
+                    }
+                    RecordedCommand::BindDescriptorBuffers(buffers) => {
+                        let binding_infos = buffers
+                            .iter()
+                            .map(|(address, usage)| {
+                                vk::DescriptorBufferBindingInfoEXT::default()
+                                    .address(*address)
+                                    .usage(*usage)
+                            })
+                            .collect::<Vec<_>>();
+
+                        db_device_ext.cmd_bind_descriptor_buffers(cmd_buffer, &binding_infos);
+                    }
+                    RecordedCommand::SetDescriptorBufferOffsets {
+                        layout,
+                        first_set,
+                        buffer_indices,
+                        offsets,
+                    } => db_device_ext.cmd_set_descriptor_buffer_offsets(
+                        cmd_buffer,
+                        vk::PipelineBindPoint::GRAPHICS,
+                        *layout,
+                        *first_set,
+                        buffer_indices,
+                        offsets,
+                    ),
+                    RecordedCommand::BindPipeline(pipeline) => device.cmd_bind_pipeline(
+                        cmd_buffer,
+                        vk::PipelineBindPoint::GRAPHICS,
+                        *pipeline,
+                    ),
+                    RecordedCommand::BindVertexBuffer(buffer) => {
+                        device.cmd_bind_vertex_buffers(cmd_buffer, 0, &[*buffer], &[0])
+                    }
+                    RecordedCommand::BindIndexBuffer(buffer) => {
+                        device.cmd_bind_index_buffer(cmd_buffer, *buffer, 0, vk::IndexType::UINT32)
+                    }
+                    RecordedCommand::PushConstants {
+                        layout,
+                        stages,
+                        bytes,
+                    } => device.cmd_push_constants(cmd_buffer, *layout, *stages, 0, bytes),
+                    RecordedCommand::Draw {
+                        vertex_count,
+                        instance_count,
+                    } => device.cmd_draw(cmd_buffer, *vertex_count, *instance_count, 0, 0),
+                    RecordedCommand::DrawIndexedIndirect {
+                        buffer,
+                        offset,
+                        draw_count,
+                    } => device.cmd_draw_indexed_indirect(
+                        cmd_buffer,
+                        *buffer,
+                        *offset,
+                        *draw_count,
+                        stride,
+                    ),
+                    RecordedCommand::DrawIndexedIndirectCount {
+                        buffer,
+                        offset,
+                        count_buffer,
+                        count_offset,
+                        max_draw_count,
+                    } => draw_indexed_indirect_count(
+                        cmd_buffer,
+                        *buffer,
+                        *offset,
+                        *count_buffer,
+                        *count_offset,
+                        *max_draw_count,
+                    ),
+                }
+            }
+        }
+    }
+}
+
+/// Viewport mapped onto `area` with `+Y` pointing up
+pub(crate) fn flipped_viewport(area: vk::Rect2D) -> vk::Viewport {
+    vk::Viewport {
+        height: -(area.extent.height as f32),
+        width: area.extent.width as f32,
+        x: area.offset.x as f32,
+        y: (area.offset.y + area.extent.height as i32) as f32,
+        min_depth: 0.0,
+        max_depth: 1.0,
+    }
+}
+
+/// Attachments of the dynamic rendering a secondary command buffer is executed in
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct RenderingInheritance {
+    pub color_formats: Vec<vk::Format>,
+    pub depth_format: vk::Format,
+    pub samples: vk::SampleCountFlags,
+}
+
+/// Secondary command buffer re-recorded only when its [`CommandRecording`] or the
+/// attachments it's executed with change.
+///
+/// It must not be updated while a submission executing it is pending
+pub struct SecondaryCommandBuffer {
+    cmd_buffer: vk::CommandBuffer,
+    recorded: Option<(RenderingInheritance, CommandRecording)>,
+}
+
+impl SecondaryCommandBuffer {
+    pub fn new(device: &LogicalDevice) -> RenderResult<Self> {
+        let allocate_info = vk::CommandBufferAllocateInfo::default()
+            .command_pool(device.cmd_pool)
+            .command_buffer_count(1)
+            .level(vk::CommandBufferLevel::SECONDARY);
+
+        let cmd_buffer = unsafe { device.allocate_command_buffers(&allocate_info)?[0] };
+        device.set_object_debug_name(cmd_buffer, "SecondaryCommandBuffer");
+
+        Ok(Self {
+            cmd_buffer,
+            recorded: None,
+        })
+    }
+
+    pub fn cmd_buffer(&self) -> vk::CommandBuffer {
+        self.cmd_buffer
+    }
+
+    /// Re-records the buffer if `recording` differs from the recorded one,
+    /// returns whether it was re-recorded
+    pub fn update(
+        &mut self,
+        device: &LogicalDevice,
+        inheritance: &RenderingInheritance,
+        recording: CommandRecording,
+    ) -> RenderResult<bool> {
+        if self
+            .recorded
+            .as_ref()
+            .is_some_and(|recorded| recorded.0 == *inheritance && recorded.1 == recording)
+        {
+            return Ok(false);
+        }
+
+        // A failed recording leaves the buffer invalid
+        self.recorded = None;
+
+        let mut rendering_info = vk::CommandBufferInheritanceRenderingInfo::default()
+            .color_attachment_formats(&inheritance.color_formats)
+            .depth_attachment_format(inheritance.depth_format)
+            .rasterization_samples(inheritance.samples);
+
+        let inheritance_info =
+            vk::CommandBufferInheritanceInfo::default().push_next(&mut rendering_info);
+
+        let begin_info = vk::CommandBufferBeginInfo::default()
+            .flags(vk::CommandBufferUsageFlags::RENDER_PASS_CONTINUE)
+            .inheritance_info(&inheritance_info);
+
+        unsafe {
+            device.begin_command_buffer(self.cmd_buffer, &begin_info)?;
+            recording.replay(device, self.cmd_buffer);
+            device.end_command_buffer(self.cmd_buffer)?;
+        }
+
+        self.recorded = Some((inheritance.clone(), recording));
+
+        Ok(true)
+    }
+
+    /// Executes the buffer inside of a rendering begun with
+    /// [`vk::RenderingFlags::CONTENTS_SECONDARY_COMMAND_BUFFERS`]
+    pub fn execute(&self, device: &LogicalDevice, primary: vk::CommandBuffer) {
+        unsafe { device.cmd_execute_commands(primary, &[self.cmd_buffer]) }
+    }
+
+    /// Makes the next [`update`](Self::update) re-record the buffer
+    pub fn invalidate(&mut self) {
+        self.recorded = None;
+    }
+
+    pub fn destroy(&mut self, device: &LogicalDevice) {
+        unsafe { device.free_command_buffers(device.cmd_pool, &[self.cmd_buffer]) };
+        self.recorded = None;
+    }
+}
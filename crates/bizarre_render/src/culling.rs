@@ -0,0 +1,558 @@
+use std::path::Path;
+
+use ash::vk;
+use nalgebra_glm::{Mat4, UVec2, Vec4};
+
+use crate::{
+    buffer::{BufferResult, GpuBuffer},
+    device::LogicalDevice,
+    image::mip_level_count,
+    material::pipeline::{PipelineError, PipelineResult},
+    renderer::RenderResult,
+    scene::Scene,
+    shader::{load_shader, ShaderStage},
+    vulkan_context::{get_device, get_instance},
+};
+
+const CULL_SHADER_PATH: &str = "assets/shaders/cull.comp";
+const CULL_WORKGROUP_SIZE: u32 = 64;
+
+const HIZ_SHADER_PATH: &str = "assets/shaders/hiz.comp";
+const HIZ_WORKGROUP_SIZE: u32 = 8;
+
+/// Input of the culling shader, one for every instance in the scene
+#[repr(C, align(16))]
+#[derive(Clone, Copy, Debug, Default)]
+pub struct CullObject {
+    /// World space bounding sphere, `xyz` is the center and `w` the radius
+    pub sphere: Vec4,
+    pub first_index: u32,
+    pub index_count: u32,
+    pub vertex_offset: i32,
+    pub first_instance: u32,
+    /// First command of the object's batch in the culled indirect buffer
+    pub command_offset: u32,
+    /// Index of the object's batch, selects its draw count
+    pub batch: u32,
+}
+
+#[repr(C)]
+struct CullPushConstants {
+    planes: [Vec4; 6],
+    objects: vk::DeviceAddress,
+    commands: vk::DeviceAddress,
+    counts: vk::DeviceAddress,
+    /// [`CullParams`] of the dispatch, push constants are full with the above
+    params: vk::DeviceAddress,
+}
+
+/// Parameters of a culling dispatch that don't fit into its push constants,
+/// written into a buffer right before the dispatch
+#[repr(C)]
+#[derive(Clone, Copy)]
+struct CullParams {
+    /// View-projection the depth pyramid was drawn with
+    view_projection: Mat4,
+    /// Depth pyramid objects are tested against, `0` skips the occlusion test
+    hiz: vk::DeviceAddress,
+    hiz_size: [u32; 2],
+    hiz_levels: u32,
+    object_count: u32,
+    /// Pack visible commands at the start of every batch and count them,
+    /// otherwise every object keeps its slot and culled ones get `instance_count = 0`
+    compact: u32,
+    _padding: u32,
+}
+
+#[repr(C)]
+struct HiZPushConstants {
+    src: vk::DeviceAddress,
+    dst: vk::DeviceAddress,
+    src_size: [u32; 2],
+    dst_size: [u32; 2],
+}
+
+/// Hierarchical depth buffer built from the depth pre-pass. Level 0 is a copy of
+/// the depth, every next level keeps the farthest depth of the 2x2 texels under
+/// it, so a texel of any level is never nearer than the depth it covers
+struct HiZPyramid {
+    buffer: GpuBuffer,
+    size: UVec2,
+    levels: u32,
+}
+
+impl HiZPyramid {
+    /// Sizes of every level of a pyramid over `size`, the last one is 1x1
+    fn level_sizes(size: UVec2) -> impl Iterator<Item = UVec2> {
+        (0..mip_level_count(size)).map(move |level| {
+            UVec2::new(
+                size.x.div_ceil(1 << level).max(1),
+                size.y.div_ceil(1 << level).max(1),
+            )
+        })
+    }
+
+    fn byte_size(size: UVec2) -> vk::DeviceSize {
+        Self::level_sizes(size)
+            .map(|level| (level.x * level.y) as vk::DeviceSize * 4)
+            .sum()
+    }
+}
+
+/// Compute pipeline that frustum culls scene objects and writes their
+/// `DrawIndexedIndirectCommand`s on the GPU.
+///
+/// With `VK_KHR_draw_indirect_count` the commands are compacted and drawn with
+/// `cmd_draw_indexed_indirect_count`, without it culled commands are zeroed.
+///
+/// Objects can also be culled against a Hi-Z pyramid of the depth pre-pass,
+/// so the ones hidden behind the pre-pass depth are not drawn in the main pass
+pub struct GpuCulling {
+    pipeline: vk::Pipeline,
+    layout: vk::PipelineLayout,
+    compact: bool,
+    /// Device local [`CullParams`], updated inside of the command buffer
+    params: GpuBuffer,
+    hiz_pipeline: vk::Pipeline,
+    hiz_layout: vk::PipelineLayout,
+    /// Created by the first occlusion culled frame
+    pyramid: Option<HiZPyramid>,
+}
+
+impl GpuCulling {
+    pub fn new(device: &LogicalDevice) -> RenderResult<Self> {
+        let (layout, pipeline) = create_compute_pipeline(
+            device,
+            CULL_SHADER_PATH,
+            size_of::<CullPushConstants>() as u32,
+        )?;
+
+        let (hiz_layout, hiz_pipeline) = match create_compute_pipeline(
+            device,
+            HIZ_SHADER_PATH,
+            size_of::<HiZPushConstants>() as u32,
+        ) {
+            Ok(hiz) => hiz,
+            Err(err) => {
+                destroy_compute_pipeline(device, layout, pipeline);
+                return Err(err.into());
+            }
+        };
+
+        let params = GpuBuffer::new(
+            size_of::<CullParams>() as vk::DeviceSize,
+            vk::BufferUsageFlags::STORAGE_BUFFER
+                | vk::BufferUsageFlags::SHADER_DEVICE_ADDRESS
+                | vk::BufferUsageFlags::TRANSFER_DST,
+            vma::MemoryUsage::AutoPreferDevice,
+            vma::AllocationCreateFlags::empty(),
+        );
+
+        let params = match params {
+            Ok(params) => params,
+            Err(err) => {
+                destroy_compute_pipeline(device, layout, pipeline);
+                destroy_compute_pipeline(device, hiz_layout, hiz_pipeline);
+                return Err(err.into());
+            }
+        };
+
+        Ok(Self {
+            pipeline,
+            layout,
+            compact: device.draw_indirect_count,
+            params,
+            hiz_pipeline,
+            hiz_layout,
+            pyramid: None,
+        })
+    }
+
+    /// Draw counts are written into the scene's count buffer and must be used
+    /// with [`draw_indexed_indirect_count`]
+    pub fn draws_with_count(&self) -> bool {
+        self.compact
+    }
+
+    /// Records culling of the current frame of `scene` against `planes`, and
+    /// against the pyramid of the last [`record_hiz`](Self::record_hiz) drawn
+    /// with `occlusion` as the view-projection if it's `Some`.
+    ///
+    /// Must be recorded outside of rendering, commands are ready for the
+    /// `DRAW_INDIRECT` stage after it
+    pub(crate) fn record(
+        &self,
+        device: &LogicalDevice,
+        cmd: vk::CommandBuffer,
+        scene: &Scene,
+        planes: [Vec4; 6],
+        occlusion: Option<Mat4>,
+    ) {
+        let frame = scene.frame();
+
+        let commands = frame.culled_indirect_buffer.buffer();
+        let counts = frame.draw_count_buffer.buffer();
+
+        let push_constants = CullPushConstants {
+            planes,
+            objects: device.get_buffer_address(frame.cull_object_buffer.buffer()),
+            commands: device.get_buffer_address(commands),
+            counts: device.get_buffer_address(counts),
+            params: device.get_buffer_address(self.params.buffer()),
+        };
+
+        let hiz = occlusion.zip(self.pyramid.as_ref());
+
+        let params = CullParams {
+            view_projection: hiz
+                .map(|(view_projection, _)| view_projection)
+                .unwrap_or_else(Mat4::identity),
+            hiz: hiz
+                .map(|(_, pyramid)| device.get_buffer_address(pyramid.buffer.buffer()))
+                .unwrap_or(0),
+            hiz_size: hiz
+                .map(|(_, pyramid)| pyramid.size.into())
+                .unwrap_or_default(),
+            hiz_levels: hiz.map(|(_, pyramid)| pyramid.levels).unwrap_or(0),
+            object_count: frame.cull_object_count,
+            compact: self.compact as u32,
+            _padding: 0,
+        };
+
+        unsafe {
+            // Previous draws from these buffers and the previous culling reading the
+            // params must be done before they are overwritten
+            memory_barrier(
+                device,
+                cmd,
+                vk::PipelineStageFlags2::DRAW_INDIRECT | vk::PipelineStageFlags2::COMPUTE_SHADER,
+                vk::AccessFlags2::INDIRECT_COMMAND_READ | vk::AccessFlags2::SHADER_STORAGE_READ,
+                vk::PipelineStageFlags2::TRANSFER,
+                vk::AccessFlags2::TRANSFER_WRITE,
+            );
+
+            let params_bytes = std::slice::from_raw_parts(
+                (&raw const params).cast::<u8>(),
+                size_of::<CullParams>(),
+            );
+
+            device.cmd_update_buffer(cmd, self.params.buffer(), 0, params_bytes);
+            device.cmd_fill_buffer(cmd, counts, 0, vk::WHOLE_SIZE, 0);
+
+            if !self.compact {
+                device.cmd_fill_buffer(cmd, commands, 0, vk::WHOLE_SIZE, 0);
+            }
+
+            memory_barrier(
+                device,
+                cmd,
+                vk::PipelineStageFlags2::TRANSFER,
+                vk::AccessFlags2::TRANSFER_WRITE,
+                vk::PipelineStageFlags2::COMPUTE_SHADER,
+                vk::AccessFlags2::SHADER_STORAGE_READ | vk::AccessFlags2::SHADER_STORAGE_WRITE,
+            );
+
+            if frame.cull_object_count > 0 {
+                device.cmd_bind_pipeline(cmd, vk::PipelineBindPoint::COMPUTE, self.pipeline);
+
+                let bytes = std::slice::from_raw_parts(
+                    (&raw const push_constants).cast::<u8>(),
+                    size_of::<CullPushConstants>(),
+                );
+
+                device.cmd_push_constants(
+                    cmd,
+                    self.layout,
+                    vk::ShaderStageFlags::COMPUTE,
+                    0,
+                    bytes,
+                );
+
+                device.cmd_dispatch(
+                    cmd,
+                    frame.cull_object_count.div_ceil(CULL_WORKGROUP_SIZE),
+                    1,
+                    1,
+                );
+            }
+
+            memory_barrier(
+                device,
+                cmd,
+                vk::PipelineStageFlags2::COMPUTE_SHADER,
+                vk::AccessFlags2::SHADER_STORAGE_WRITE,
+                vk::PipelineStageFlags2::DRAW_INDIRECT,
+                vk::AccessFlags2::INDIRECT_COMMAND_READ,
+            );
+        }
+    }
+
+    /// Makes sure the depth pyramid fits depth pre-passes of up to `size` and
+    /// returns the buffer the depth is copied into.
+    ///
+    /// Must be called before the frame is recorded, growing the pyramid waits
+    /// for the device to be idle
+    pub(crate) fn prepare_hiz(
+        &mut self,
+        device: &LogicalDevice,
+        size: UVec2,
+    ) -> BufferResult<vk::Buffer> {
+        let byte_size = HiZPyramid::byte_size(size);
+
+        match &mut self.pyramid {
+            Some(pyramid) if pyramid.buffer.size() >= byte_size => {}
+            Some(pyramid) => {
+                unsafe { device.device_wait_idle()? };
+
+                pyramid.buffer.destroy(device);
+                pyramid.buffer = create_pyramid_buffer(byte_size)?;
+            }
+            None => {
+                self.pyramid = Some(HiZPyramid {
+                    buffer: create_pyramid_buffer(byte_size)?,
+                    size,
+                    levels: mip_level_count(size),
+                });
+            }
+        }
+
+        Ok(self.pyramid.as_ref().unwrap().buffer.buffer())
+    }
+
+    /// Records building of the depth pyramid from the `size` depth copied into the
+    /// buffer of [`prepare_hiz`](Self::prepare_hiz), for the next [`record`](Self::record).
+    ///
+    /// Must be recorded outside of rendering after the copy is made visible to
+    /// `COMPUTE_SHADER`
+    pub(crate) fn record_hiz(
+        &mut self,
+        device: &LogicalDevice,
+        cmd: vk::CommandBuffer,
+        size: UVec2,
+    ) {
+        let Some(pyramid) = &mut self.pyramid else {
+            return;
+        };
+
+        pyramid.size = size;
+        pyramid.levels = mip_level_count(size);
+
+        let address = device.get_buffer_address(pyramid.buffer.buffer());
+        let sizes = HiZPyramid::level_sizes(size).collect::<Vec<_>>();
+
+        unsafe {
+            device.cmd_bind_pipeline(cmd, vk::PipelineBindPoint::COMPUTE, self.hiz_pipeline);
+        }
+
+        let mut src = address;
+
+        for pair in sizes.windows(2) {
+            let (src_size, dst_size) = (pair[0], pair[1]);
+            let dst = src + (src_size.x * src_size.y) as vk::DeviceAddress * 4;
+
+            let push_constants = HiZPushConstants {
+                src,
+                dst,
+                src_size: src_size.into(),
+                dst_size: dst_size.into(),
+            };
+
+            unsafe {
+                let bytes = std::slice::from_raw_parts(
+                    (&raw const push_constants).cast::<u8>(),
+                    size_of::<HiZPushConstants>(),
+                );
+
+                device.cmd_push_constants(
+                    cmd,
+                    self.hiz_layout,
+                    vk::ShaderStageFlags::COMPUTE,
+                    0,
+                    bytes,
+                );
+
+                device.cmd_dispatch(
+                    cmd,
+                    dst_size.x.div_ceil(HIZ_WORKGROUP_SIZE),
+                    dst_size.y.div_ceil(HIZ_WORKGROUP_SIZE),
+                    1,
+                );
+
+                // Every level is read by the next one and by culling
+                memory_barrier(
+                    device,
+                    cmd,
+                    vk::PipelineStageFlags2::COMPUTE_SHADER,
+                    vk::AccessFlags2::SHADER_STORAGE_WRITE,
+                    vk::PipelineStageFlags2::COMPUTE_SHADER,
+                    vk::AccessFlags2::SHADER_STORAGE_READ,
+                );
+            }
+
+            src = dst;
+        }
+    }
+
+    pub fn destroy(&mut self, device: &LogicalDevice) {
+        if self.pipeline == vk::Pipeline::null() {
+            return;
+        }
+
+        destroy_compute_pipeline(device, self.layout, self.pipeline);
+        destroy_compute_pipeline(device, self.hiz_layout, self.hiz_pipeline);
+
+        self.params.destroy(device);
+
+        if let Some(mut pyramid) = self.pyramid.take() {
+            pyramid.buffer.destroy(device);
+        }
+
+        self.pipeline = vk::Pipeline::null();
+        self.layout = vk::PipelineLayout::null();
+        self.hiz_pipeline = vk::Pipeline::null();
+        self.hiz_layout = vk::PipelineLayout::null();
+    }
+}
+
+fn create_pyramid_buffer(size: vk::DeviceSize) -> BufferResult<GpuBuffer> {
+    GpuBuffer::new(
+        size,
+        vk::BufferUsageFlags::STORAGE_BUFFER
+            | vk::BufferUsageFlags::SHADER_DEVICE_ADDRESS
+            | vk::BufferUsageFlags::TRANSFER_DST,
+        vma::MemoryUsage::AutoPreferDevice,
+        vma::AllocationCreateFlags::empty(),
+    )
+}
+
+/// Creates a compute pipeline of the shader at `path` with a layout of
+/// `push_constants_size` bytes of push constants and no descriptor sets
+fn create_compute_pipeline(
+    device: &LogicalDevice,
+    path: &str,
+    push_constants_size: u32,
+) -> PipelineResult<(vk::PipelineLayout, vk::Pipeline)> {
+    let push_constant_ranges = [vk::PushConstantRange::default()
+        .stage_flags(vk::ShaderStageFlags::COMPUTE)
+        .offset(0)
+        .size(push_constants_size)];
+
+    let layout = unsafe {
+        let create_info =
+            vk::PipelineLayoutCreateInfo::default().push_constant_ranges(&push_constant_ranges);
+
+        device.create_pipeline_layout(&create_info, None)?
+    };
+
+    match create_compute_pipeline_with_layout(device, path, layout) {
+        Ok(pipeline) => Ok((layout, pipeline)),
+        Err(err) => {
+            unsafe { device.destroy_pipeline_layout(layout, None) };
+            Err(err)
+        }
+    }
+}
+
+fn destroy_compute_pipeline(
+    device: &LogicalDevice,
+    layout: vk::PipelineLayout,
+    pipeline: vk::Pipeline,
+) {
+    unsafe {
+        device.destroy_pipeline(pipeline, None);
+        device.destroy_pipeline_layout(layout, None);
+    }
+}
+
+fn create_compute_pipeline_with_layout(
+    device: &LogicalDevice,
+    path: &str,
+    layout: vk::PipelineLayout,
+) -> PipelineResult<vk::Pipeline> {
+    let code = load_shader(Path::new(path), ShaderStage::Compute)?;
+
+    let module = unsafe {
+        let create_info = vk::ShaderModuleCreateInfo::default().code(&code);
+        device.create_shader_module(&create_info, None)?
+    };
+
+    let stage = vk::PipelineShaderStageCreateInfo::default()
+        .stage(vk::ShaderStageFlags::COMPUTE)
+        .module(module)
+        .name(c"main");
+
+    let create_info = vk::ComputePipelineCreateInfo::default()
+        .stage(stage)
+        .layout(layout);
+
+    let pipeline = unsafe {
+        device
+            .create_compute_pipelines(device.pipeline_cache, &[create_info], None)
+            .map_err(|(_, err)| PipelineError::from(err))
+    };
+
+    unsafe { device.destroy_shader_module(module, None) };
+
+    Ok(pipeline?[0])
+}
+
+unsafe fn memory_barrier(
+    device: &LogicalDevice,
+    cmd: vk::CommandBuffer,
+    src_stage: vk::PipelineStageFlags2,
+    src_access: vk::AccessFlags2,
+    dst_stage: vk::PipelineStageFlags2,
+    dst_access: vk::AccessFlags2,
+) {
+    let barriers = [vk::MemoryBarrier2::default()
+        .src_stage_mask(src_stage)
+        .src_access_mask(src_access)
+        .dst_stage_mask(dst_stage)
+        .dst_access_mask(dst_access)];
+
+    let dependency_info = vk::DependencyInfo::default().memory_barriers(&barriers);
+
+    device.cmd_pipeline_barrier2(cmd, &dependency_info);
+}
+
+/// Records `cmd_draw_indexed_indirect_count` through `VK_KHR_draw_indirect_count`,
+/// the extension must be enabled on the device
+pub(crate) unsafe fn draw_indexed_indirect_count(
+    cmd: vk::CommandBuffer,
+    buffer: vk::Buffer,
+    offset: vk::DeviceSize,
+    count_buffer: vk::Buffer,
+    count_buffer_offset: vk::DeviceSize,
+    max_draw_count: u32,
+) {
+    let device_ext = ash::khr::draw_indirect_count::Device::new(get_instance(), get_device());
+
+    device_ext.cmd_draw_indexed_indirect_count(
+        cmd,
+        buffer,
+        offset,
+        count_buffer,
+        count_buffer_offset,
+        max_draw_count,
+        size_of::<vk::DrawIndexedIndirectCommand>() as u32,
+    );
+}
+
+/// Normalized frustum planes of `view_projection` as `(normal, distance)`, in
+/// the left, right, bottom, top, near, far order. Points inside of the frustum
+/// are in front of every plane
+pub fn frustum_planes(view_projection: &Mat4) -> [Vec4; 6] {
+    let row = |i: usize| view_projection.row(i).transpose();
+
+    let (x, y, z, w) = (row(0), row(1), row(2), row(3));
+
+    [w + x, w - x, w + y, w - y, w + z, w - z].map(|plane| {
+        let length = plane.xyz().norm();
+
+        if length > 0.0 {
+            plane / length
+        } else {
+            plane
+        }
+    })
+}
@@ -9,11 +9,11 @@ pub struct DebugMessenger {
 }
 
 impl DebugMessenger {
-    pub(crate) fn new(entry: &ash::Entry, instance: &ash::Instance) -> Self {
+    pub(crate) fn new(entry: &ash::Entry, instance: &ash::Instance, shader_printf: bool) -> Self {
         let loader = ash::ext::debug_utils::Instance::new(entry, instance);
 
         let mut create_info = vk::DebugUtilsMessengerCreateInfoEXT::default();
-        populate_debug_messenger_create_info(&mut create_info);
+        populate_debug_messenger_create_info(&mut create_info, shader_printf);
 
         let messenger = unsafe {
             loader
@@ -34,14 +34,20 @@ impl Drop for DebugMessenger {
     }
 }
 
+/// Shader printf output is reported with the `INFO` severity, it's only listened
+/// to when `shader_printf` is set
 pub fn populate_debug_messenger_create_info<'a>(
     create_info: &'a mut vk::DebugUtilsMessengerCreateInfoEXT,
+    shader_printf: bool,
 ) {
     type Severity = vk::DebugUtilsMessageSeverityFlagsEXT;
     type Type = vk::DebugUtilsMessageTypeFlagsEXT;
 
     create_info.pfn_user_callback = Some(messenger_callback);
     create_info.message_severity = Severity::WARNING | Severity::ERROR;
+    if shader_printf {
+        create_info.message_severity |= Severity::INFO;
+    }
     create_info.message_type = Type::GENERAL | Type::PERFORMANCE | Type::VALIDATION;
 }
 
@@ -59,6 +65,17 @@ unsafe extern "system" fn messenger_callback(
         vk::DebugUtilsMessageSeverityFlagsEXT::ERROR => LogLevel::Error,
         _ => unreachable!(),
     };
+    let is_printf = unsafe {
+        (*callback_data)
+            .message_id_name_as_c_str()
+            .is_some_and(|name| name.to_bytes().ends_with(b"DEBUG-PRINTF"))
+    };
+
+    // Other informational messages are noise, only printf output is asked for
+    if level == LogLevel::Info && !is_printf {
+        return 0;
+    }
+
     let msg = unsafe {
         (*callback_data)
             .message_as_c_str()
@@ -66,6 +83,10 @@ unsafe extern "system" fn messenger_callback(
             .unwrap_or_default()
     };
 
-    log!(LOGGER_NAME, level, "Vulkan validation: {msg}");
+    if is_printf {
+        log!(LOGGER_NAME, LogLevel::Info, "Shader printf: {msg}");
+    } else {
+        log!(LOGGER_NAME, level, "Vulkan validation: {msg}");
+    }
     0
 }
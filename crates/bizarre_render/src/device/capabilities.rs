@@ -0,0 +1,299 @@
+use std::{ffi::CStr, fmt::Display};
+
+use ash::vk;
+
+use crate::instance::VulkanInstance;
+
+/// Whether the renderer can run without a capability
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Requirement {
+    Required,
+    /// The renderer falls back to a slower or simpler path without it
+    Optional,
+}
+
+/// Support of a single extension or feature by a physical device
+#[derive(Clone, Debug)]
+pub struct Capability {
+    pub name: String,
+    /// What the renderer uses it for, or what it loses without it
+    pub usage: &'static str,
+    pub requirement: Requirement,
+    pub supported: bool,
+}
+
+/// Extensions and features a physical device supports, probed before the device
+/// is created so missing ones can be reported by name
+#[derive(Clone, Debug)]
+pub struct DeviceCapabilities {
+    pub device_name: String,
+    pub api_version: u32,
+    pub capabilities: Vec<Capability>,
+}
+
+const REQUIRED_API_VERSION: u32 = vk::API_VERSION_1_3;
+
+/// Device extensions the renderer can't work without, there is no path using
+/// classic descriptor sets for them
+pub(crate) const REQUIRED_EXTENSIONS: &[(&CStr, &str)] = &[
+    (
+        ash::ext::descriptor_buffer::NAME,
+        "every material binds its resources through descriptor buffers",
+    ),
+    (
+        ash::khr::dynamic_rendering_local_read::NAME,
+        "the deferred composition reads the G-buffer as input attachments",
+    ),
+    (
+        ash::khr::shader_non_semantic_info::NAME,
+        "shaders may carry non-semantic debug instructions",
+    ),
+];
+
+/// Required unless the instance is headless
+pub(crate) const PRESENT_EXTENSIONS: &[(&CStr, &str)] =
+    &[(ash::khr::swapchain::NAME, "windows are presented to")];
+
+/// Enabled when the physical device supports them
+pub(crate) const OPTIONAL_EXTENSIONS: &[(&CStr, &str)] = &[
+    (
+        ash::khr::draw_indirect_count::NAME,
+        "GPU culling draws with a CPU-side count without it",
+    ),
+    (
+        ash::ext::conservative_rasterization::NAME,
+        "conservative rasterization is ignored without it",
+    ),
+    (
+        ash::khr::pipeline_executable_properties::NAME,
+        "pipeline statistics can't be logged without it",
+    ),
+    // Must be enabled on portability implementations (MoltenVK) that advertise it
+    (
+        ash::khr::portability_subset::NAME,
+        "needed by portability implementations only",
+    ),
+];
+
+impl DeviceCapabilities {
+    pub fn probe(instance: &VulkanInstance, device: vk::PhysicalDevice) -> Self {
+        let props = unsafe { instance.get_physical_device_properties(device) };
+
+        let device_name = props
+            .device_name_as_c_str()
+            .map(|name| name.to_string_lossy().to_string())
+            .unwrap_or_default();
+
+        let extensions =
+            unsafe { instance.enumerate_device_extension_properties(device) }.unwrap_or_default();
+
+        let has_extension = |name: &CStr| {
+            extensions
+                .iter()
+                .any(|ext| ext.extension_name_as_c_str() == Ok(name))
+        };
+
+        let present: &[_] = if instance.is_headless() {
+            &[]
+        } else {
+            PRESENT_EXTENSIONS
+        };
+
+        let mut capabilities = vec![Capability {
+            name: String::from("Vulkan 1.3"),
+            usage: "dynamic rendering and synchronization2 are core in it",
+            requirement: Requirement::Required,
+            supported: props.api_version >= REQUIRED_API_VERSION,
+        }];
+
+        let extension_capabilities = REQUIRED_EXTENSIONS
+            .iter()
+            .chain(present)
+            .map(|ext| (ext, Requirement::Required))
+            .chain(
+                OPTIONAL_EXTENSIONS
+                    .iter()
+                    .map(|ext| (ext, Requirement::Optional)),
+            )
+            .map(|((name, usage), requirement)| Capability {
+                name: name.to_string_lossy().to_string(),
+                usage,
+                requirement,
+                supported: has_extension(name),
+            });
+
+        capabilities.extend(extension_capabilities);
+
+        // Features of extensions the device lacks can't be queried
+        if props.api_version >= REQUIRED_API_VERSION {
+            capabilities.extend(probe_features(instance, device, &has_extension));
+        }
+
+        Self {
+            device_name,
+            api_version: props.api_version,
+            capabilities,
+        }
+    }
+
+    pub fn missing_required(&self) -> impl Iterator<Item = &Capability> {
+        self.capabilities
+            .iter()
+            .filter(|cap| cap.requirement == Requirement::Required && !cap.supported)
+    }
+
+    pub fn is_suitable(&self) -> bool {
+        self.missing_required().next().is_none()
+    }
+
+    pub fn supports(&self, name: &str) -> bool {
+        self.capabilities
+            .iter()
+            .any(|cap| cap.name == name && cap.supported)
+    }
+
+    /// Explains why the renderer can't run on the device, `None` if it can
+    pub fn unsupported_reason(&self) -> Option<String> {
+        let missing = self
+            .missing_required()
+            .map(|cap| format!("{} ({})", cap.name, cap.usage))
+            .collect::<Vec<_>>();
+
+        (!missing.is_empty()).then(|| {
+            format!(
+                "GPU `{}` lacks {}, the renderer requires it",
+                self.device_name,
+                missing.join(", ")
+            )
+        })
+    }
+}
+
+impl Display for DeviceCapabilities {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        writeln!(
+            f,
+            "Capabilities of `{}` (Vulkan {}.{}.{}):",
+            self.device_name,
+            vk::api_version_major(self.api_version),
+            vk::api_version_minor(self.api_version),
+            vk::api_version_patch(self.api_version),
+        )?;
+
+        let width = self
+            .capabilities
+            .iter()
+            .map(|cap| cap.name.len())
+            .max()
+            .unwrap_or(0);
+
+        for cap in &self.capabilities {
+            let status = match (cap.supported, cap.requirement) {
+                (true, _) => "yes",
+                (false, Requirement::Required) => "MISSING",
+                (false, Requirement::Optional) => "no",
+            };
+
+            writeln!(f, "  {:<width$}  {status:<7}  {}", cap.name, cap.usage)?;
+        }
+
+        Ok(())
+    }
+}
+
+fn probe_features(
+    instance: &VulkanInstance,
+    device: vk::PhysicalDevice,
+    has_extension: &impl Fn(&CStr) -> bool,
+) -> Vec<Capability> {
+    let mut vulkan_12 = vk::PhysicalDeviceVulkan12Features::default();
+    let mut vulkan_13 = vk::PhysicalDeviceVulkan13Features::default();
+    let mut descriptor_buffer = vk::PhysicalDeviceDescriptorBufferFeaturesEXT::default();
+    let mut local_read = vk::PhysicalDeviceDynamicRenderingLocalReadFeaturesKHR::default();
+
+    let mut features = vk::PhysicalDeviceFeatures2::default()
+        .push_next(&mut vulkan_12)
+        .push_next(&mut vulkan_13);
+
+    if has_extension(ash::ext::descriptor_buffer::NAME) {
+        features = features.push_next(&mut descriptor_buffer);
+    }
+
+    if has_extension(ash::khr::dynamic_rendering_local_read::NAME) {
+        features = features.push_next(&mut local_read);
+    }
+
+    unsafe { instance.get_physical_device_features2(device, &mut features) };
+
+    let base = features.features;
+
+    let required = |name: &str, usage, supported: vk::Bool32| Capability {
+        name: name.to_string(),
+        usage,
+        requirement: Requirement::Required,
+        supported: supported == vk::TRUE,
+    };
+
+    let optional = |name: &str, usage, supported: vk::Bool32| Capability {
+        requirement: Requirement::Optional,
+        ..required(name, usage, supported)
+    };
+
+    vec![
+        required(
+            "descriptorBuffer",
+            "every material binds its resources through descriptor buffers",
+            descriptor_buffer.descriptor_buffer,
+        ),
+        required(
+            "dynamicRendering",
+            "render passes are begun without render pass objects",
+            vulkan_13.dynamic_rendering,
+        ),
+        required(
+            "dynamicRenderingLocalRead",
+            "the deferred composition reads the G-buffer as input attachments",
+            local_read.dynamic_rendering_local_read,
+        ),
+        required(
+            "synchronization2",
+            "barriers and submissions use synchronization2",
+            vulkan_13.synchronization2,
+        ),
+        required(
+            "bufferDeviceAddress",
+            "descriptor buffers and GPU culling address buffers directly",
+            vulkan_12.buffer_device_address,
+        ),
+        required(
+            "runtimeDescriptorArray",
+            "shaders declare unsized descriptor arrays",
+            vulkan_12.runtime_descriptor_array,
+        ),
+        required(
+            "shaderSampledImageArrayNonUniformIndexing",
+            "sprites index a texture array per instance",
+            vulkan_12.shader_sampled_image_array_non_uniform_indexing,
+        ),
+        optional(
+            "samplerAnisotropy",
+            "textures are sampled without anisotropic filtering without it",
+            base.sampler_anisotropy,
+        ),
+        optional(
+            "wideLines",
+            "lines are drawn one pixel wide without it",
+            base.wide_lines,
+        ),
+        optional(
+            "depthBiasClamp",
+            "depth bias is not clamped without it",
+            base.depth_bias_clamp,
+        ),
+        optional(
+            "textureCompressionBC",
+            "BC textures are decoded to RGBA on load without it",
+            base.texture_compression_bc,
+        ),
+    ]
+}
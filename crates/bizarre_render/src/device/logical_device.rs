@@ -1,27 +1,28 @@
 use std::{
-    ffi::{c_char, CStr, CString},
+    ffi::{CStr, CString},
     ops::Deref,
+    sync::Mutex,
 };
 
 use ash::{
     ext::memory_priority,
     vk::{self, Handle, PhysicalDeviceType},
 };
-use bizarre_log::{core_info, core_trace};
+use bizarre_log::{core_info, core_trace, core_warn};
 use thiserror::Error;
 
 use crate::{
-    instance::VulkanInstance, present_target::SwapchainSupportInfo, vulkan_context::get_instance,
+    instance::VulkanInstance, material::pipeline_layout_cache::PipelineLayoutCache,
+    present_target::SwapchainSupportInfo, sampler::SamplerCache, surface::create_surface,
+    vulkan_context::get_instance,
 };
 
-use super::PhysicalDevice;
-
-const REQUIRED_EXTENSIONS: &'static [*const c_char] = &[
-    ash::khr::swapchain::NAME.as_ptr(),
-    ash::ext::descriptor_buffer::NAME.as_ptr(),
-    ash::khr::dynamic_rendering_local_read::NAME.as_ptr(),
-    ash::khr::shader_non_semantic_info::NAME.as_ptr(),
-];
+use super::{
+    capabilities::{
+        DeviceCapabilities, OPTIONAL_EXTENSIONS, PRESENT_EXTENSIONS, REQUIRED_EXTENSIONS,
+    },
+    PhysicalDevice,
+};
 
 pub struct LogicalDevice {
     pub(crate) logical: ash::Device,
@@ -30,9 +31,34 @@ pub struct LogicalDevice {
     pub(crate) graphics_queue: vk::Queue,
     pub(crate) compute_queue: vk::Queue,
     pub(crate) present_queue: vk::Queue,
+    /// Queue of a transfer-only family if there is one, the graphics queue otherwise
+    pub(crate) transfer_queue: vk::Queue,
     pub(crate) cmd_pool: vk::CommandPool,
+    /// Pool for one-off uploads submitted to `transfer_queue`
+    pub(crate) transfer_cmd_pool: vk::CommandPool,
     pub(crate) descriptor_pool: vk::DescriptorPool,
+    pub(crate) pipeline_cache: vk::PipelineCache,
+    pub(crate) pipeline_layouts: Mutex<PipelineLayoutCache>,
+    pub(crate) samplers: Mutex<SamplerCache>,
     pub(crate) allocator: vma::Allocator,
+    /// `VK_KHR_draw_indirect_count` is enabled
+    pub(crate) draw_indirect_count: bool,
+    /// `VK_EXT_conservative_rasterization` is enabled
+    pub(crate) conservative_rasterization: bool,
+    /// Lines can be wider than one pixel, up to `limits.line_width_range`
+    pub(crate) wide_lines: bool,
+    /// Depth bias can be clamped
+    pub(crate) depth_bias_clamp: bool,
+    /// BC compressed images can be sampled
+    pub(crate) texture_compression_bc: bool,
+    /// Set when pipeline statistics are asked for and `VK_KHR_pipeline_executable_properties`
+    /// is enabled
+    pub(crate) pipeline_executable_properties:
+        Option<ash::khr::pipeline_executable_properties::Device>,
+    /// Nanoseconds per timestamp tick, `None` if the graphics queue can't write
+    /// timestamps
+    pub(crate) timestamp_period: Option<f32>,
+    pub(crate) capabilities: DeviceCapabilities,
 }
 
 #[derive(Error, Debug)]
@@ -43,6 +69,10 @@ pub enum DeviceError {
     CStrConvertFail(#[from] std::ffi::FromBytesUntilNulError),
     #[error("Could not find a suitable physical device")]
     NoSuitablePhysicalDevice,
+    /// No physical device has every required capability, the message names the
+    /// missing ones of each device
+    #[error("{0}")]
+    UnsupportedDevice(String),
     #[error("Could not find suitable memory")]
     NoSuitableMemory,
 }
@@ -51,14 +81,14 @@ pub type DeviceResult<T> = Result<T, DeviceError>;
 
 impl LogicalDevice {
     pub(crate) fn new(instance: &VulkanInstance) -> DeviceResult<Self> {
-        let (physical, queue_families) =
-            find_best_physical_device(instance).ok_or(DeviceError::NoSuitablePhysicalDevice)?;
+        let (physical, queue_families, capabilities) = find_best_physical_device(instance)?;
 
         let physical = PhysicalDevice::new(instance, physical);
 
         let name = get_pdevice_name(instance, *physical);
 
         core_info!("Picked physical device: {name}");
+        core_info!("{capabilities}");
 
         let queue_priorities = [1.0];
 
@@ -89,12 +119,68 @@ impl LogicalDevice {
         let mut descriptor_buffer =
             vk::PhysicalDeviceDescriptorBufferFeaturesEXT::default().descriptor_buffer(true);
 
-        let mut descriptor_indexing =
-            vk::PhysicalDeviceDescriptorIndexingFeatures::default().runtime_descriptor_array(true);
+        let mut descriptor_indexing = vk::PhysicalDeviceDescriptorIndexingFeatures::default()
+            .runtime_descriptor_array(true)
+            .shader_sampled_image_array_non_uniform_indexing(true);
+
+        let supported_extensions =
+            unsafe { instance.enumerate_device_extension_properties(*physical) }?;
+
+        let supported_features = unsafe { instance.get_physical_device_features(*physical) };
+
+        let sampler_anisotropy = supported_features.sampler_anisotropy == vk::TRUE;
+        let wide_lines = supported_features.wide_lines == vk::TRUE;
+        let depth_bias_clamp = supported_features.depth_bias_clamp == vk::TRUE;
+        let texture_compression_bc = supported_features.texture_compression_bc == vk::TRUE;
+
+        let features = vk::PhysicalDeviceFeatures::default()
+            .sampler_anisotropy(sampler_anisotropy)
+            .wide_lines(wide_lines)
+            .depth_bias_clamp(depth_bias_clamp)
+            .texture_compression_bc(texture_compression_bc);
+        let max_anisotropy =
+            sampler_anisotropy.then_some(physical.device_props.limits.max_sampler_anisotropy);
+
+        let limits = &physical.device_props.limits;
+        let timestamp_period = (limits.timestamp_compute_and_graphics == vk::TRUE
+            && limits.timestamp_period > 0.0)
+            .then_some(limits.timestamp_period);
+
+        let optional_extensions = OPTIONAL_EXTENSIONS
+            .iter()
+            .map(|(name, _)| *name)
+            .filter(|name| {
+                supported_extensions
+                    .iter()
+                    .any(|ext| ext.extension_name_as_c_str() == Ok(*name))
+            })
+            .collect::<Vec<_>>();
+
+        let extensions = required_extensions(instance)
+            .chain(optional_extensions.iter().copied())
+            .map(CStr::as_ptr)
+            .collect::<Vec<_>>();
+
+        let draw_indirect_count =
+            optional_extensions.contains(&ash::khr::draw_indirect_count::NAME);
+        let conservative_rasterization =
+            optional_extensions.contains(&ash::ext::conservative_rasterization::NAME);
 
-        let create_info = vk::DeviceCreateInfo::default()
+        let pipeline_statistics = instance.pipeline_statistics()
+            && optional_extensions.contains(&ash::khr::pipeline_executable_properties::NAME);
+
+        if instance.pipeline_statistics() && !pipeline_statistics {
+            core_warn!("Pipeline statistics are not supported by the device and won't be logged");
+        }
+
+        let mut pipeline_executable_info =
+            vk::PhysicalDevicePipelineExecutablePropertiesFeaturesKHR::default()
+                .pipeline_executable_info(true);
+
+        let mut create_info = vk::DeviceCreateInfo::default()
             .queue_create_infos(&queue_create_infos)
-            .enabled_extension_names(&REQUIRED_EXTENSIONS)
+            .enabled_extension_names(&extensions)
+            .enabled_features(&features)
             .push_next(&mut sync2)
             .push_next(&mut dynamic_rendering)
             .push_next(&mut dynamic_rendering_local_read)
@@ -102,11 +188,19 @@ impl LogicalDevice {
             .push_next(&mut descriptor_indexing)
             .push_next(&mut descriptor_buffer);
 
+        if pipeline_statistics {
+            create_info = create_info.push_next(&mut pipeline_executable_info);
+        }
+
         let logical = unsafe { instance.create_device(*physical, &create_info, None)? };
 
         let graphics_queue = unsafe { logical.get_device_queue(queue_families.graphics, 0) };
         let compute_queue = unsafe { logical.get_device_queue(queue_families.compute, 0) };
         let present_queue = unsafe { logical.get_device_queue(queue_families.present, 0) };
+        let transfer_queue = unsafe { logical.get_device_queue(queue_families.transfer, 0) };
+
+        let pipeline_executable_properties = pipeline_statistics
+            .then(|| ash::khr::pipeline_executable_properties::Device::new(instance, &logical));
 
         let cmd_pool = {
             let create_info = vk::CommandPoolCreateInfo::default()
@@ -116,20 +210,27 @@ impl LogicalDevice {
             unsafe { logical.create_command_pool(&create_info, None)? }
         };
 
+        let transfer_cmd_pool = {
+            let create_info = vk::CommandPoolCreateInfo::default()
+                .flags(vk::CommandPoolCreateFlags::TRANSIENT)
+                .queue_family_index(queue_families.transfer);
+
+            unsafe { logical.create_command_pool(&create_info, None)? }
+        };
+
         let allocator = {
-            let create_flags =
-                unsafe { instance.enumerate_device_extension_properties(*physical) }?
-                    .into_iter()
-                    .filter_map(|ext| {
-                        VMA_OPT_EXTENSIONS.into_iter().find_map(|vma_ext| {
-                            if vma_ext.name == ext.extension_name_as_c_str().ok()? {
-                                Some(vma::AllocatorCreateFlags::from_bits(vma_ext.flag.bits())?)
-                            } else {
-                                None
-                            }
-                        })
+            let create_flags = supported_extensions
+                .iter()
+                .filter_map(|ext| {
+                    VMA_OPT_EXTENSIONS.into_iter().find_map(|vma_ext| {
+                        if vma_ext.name == ext.extension_name_as_c_str().ok()? {
+                            Some(vma::AllocatorCreateFlags::from_bits(vma_ext.flag.bits())?)
+                        } else {
+                            None
+                        }
                     })
-                    .fold(vma::AllocatorCreateFlags::empty(), |acc, curr| acc | curr);
+                })
+                .fold(vma::AllocatorCreateFlags::empty(), |acc, curr| acc | curr);
 
             let mut create_info = vma::AllocatorCreateInfo::new(&instance, &logical, *physical);
             create_info.flags = create_flags;
@@ -150,6 +251,11 @@ impl LogicalDevice {
             logical.create_descriptor_pool(&create_info, None)?
         };
 
+        let pipeline_cache = unsafe {
+            let create_info = vk::PipelineCacheCreateInfo::default();
+            logical.create_pipeline_cache(&create_info, None)?
+        };
+
         Ok(Self {
             physical,
             logical,
@@ -157,12 +263,30 @@ impl LogicalDevice {
             graphics_queue,
             compute_queue,
             present_queue,
+            transfer_queue,
             cmd_pool,
+            transfer_cmd_pool,
             descriptor_pool,
+            pipeline_cache,
+            pipeline_layouts: Default::default(),
+            samplers: Mutex::new(SamplerCache::new(max_anisotropy)),
             allocator,
+            draw_indirect_count,
+            conservative_rasterization,
+            wide_lines,
+            depth_bias_clamp,
+            texture_compression_bc,
+            pipeline_executable_properties,
+            timestamp_period,
+            capabilities,
         })
     }
 
+    /// Support matrix of the extensions and features the renderer uses
+    pub fn capabilities(&self) -> &DeviceCapabilities {
+        &self.capabilities
+    }
+
     pub(crate) fn get_buffer_address(&self, buffer: vk::Buffer) -> vk::DeviceAddress {
         let addr_info = vk::BufferDeviceAddressInfo::default().buffer(buffer);
         unsafe { self.get_buffer_device_address(&addr_info) }
@@ -192,8 +316,17 @@ impl Drop for LogicalDevice {
         unsafe {
             self.device_wait_idle();
             self.logical.destroy_command_pool(self.cmd_pool, None);
+            self.logical
+                .destroy_command_pool(self.transfer_cmd_pool, None);
             self.logical
                 .destroy_descriptor_pool(self.descriptor_pool, None);
+            self.pipeline_layouts
+                .get_mut()
+                .unwrap()
+                .destroy(&self.logical);
+            self.samplers.get_mut().unwrap().destroy(&self.logical);
+            self.logical
+                .destroy_pipeline_cache(self.pipeline_cache, None);
             self.logical.destroy_device(None);
         }
     }
@@ -212,6 +345,7 @@ struct QueueFamiliesBuilder {
     graphics: Option<u32>,
     compute: Option<u32>,
     present: Option<u32>,
+    transfer: Option<u32>,
 }
 
 impl QueueFamiliesBuilder {
@@ -221,6 +355,7 @@ impl QueueFamiliesBuilder {
                 graphics: self.graphics.unwrap(),
                 compute: self.compute.unwrap(),
                 present: self.present.unwrap(),
+                transfer: self.transfer.or(self.graphics).unwrap(),
             })
         } else {
             None
@@ -237,68 +372,95 @@ pub struct QueueFamilies {
     pub graphics: u32,
     pub compute: u32,
     pub present: u32,
+    pub transfer: u32,
 }
 
 impl QueueFamilies {
     pub fn unique_indices(&self) -> Vec<u32> {
-        let mut result = vec![self.graphics, self.compute];
+        let mut result = vec![self.graphics, self.compute, self.transfer];
         result.sort();
         result.dedup();
         result
     }
 }
 
+/// Device extensions the renderer can't work without
+fn required_extensions(instance: &VulkanInstance) -> impl Iterator<Item = &'static CStr> {
+    let present: &[_] = if instance.is_headless() {
+        &[]
+    } else {
+        PRESENT_EXTENSIONS
+    };
+
+    REQUIRED_EXTENSIONS
+        .iter()
+        .chain(present)
+        .map(|(name, _)| *name)
+}
+
+/// Picks the best rated device with every required capability, or names the
+/// capabilities each device lacks
 #[inline]
 fn find_best_physical_device(
     instance: &VulkanInstance,
-) -> Option<(vk::PhysicalDevice, QueueFamilies)> {
-    let pdevices = unsafe { instance.enumerate_physical_devices() }.ok()?;
+) -> DeviceResult<(vk::PhysicalDevice, QueueFamilies, DeviceCapabilities)> {
+    let pdevices = unsafe { instance.enumerate_physical_devices() }?;
+
+    let probed = pdevices
+        .iter()
+        .map(|dev| (*dev, DeviceCapabilities::probe(instance, *dev)))
+        .collect::<Vec<_>>();
+
+    let unsupported = probed
+        .iter()
+        .filter_map(|(_, capabilities)| capabilities.unsupported_reason())
+        .inspect(|reason| core_warn!("{reason}"))
+        .collect::<Vec<_>>();
 
     let surface_loader = ash::khr::surface::Instance::new(&instance.entry, &instance.instance);
 
-    let (test_window, test_surface) = {
+    // Headless instances don't present, so there is no surface to check against
+    let test = if instance.is_headless() {
+        None
+    } else {
         let window = bizarre_sdl::window::create_test_window();
-        let surface = window
-            .vulkan_create_surface(instance.handle().as_raw() as usize)
-            .unwrap();
+        let surface =
+            create_surface(instance, &window).map_err(|_| DeviceError::NoSuitablePhysicalDevice)?;
 
-        let surface = vk::SurfaceKHR::from_raw(surface);
-
-        (window, surface)
+        Some((window, surface))
     };
 
-    let mut rating = pdevices
-        .iter()
-        .map(|dev| rate_pdevice(instance, *dev, &surface_loader, test_surface))
-        .filter_map(|rating| {
-            if let Some((rate, ..)) = rating {
-                if rate > 0 {
-                    rating
-                } else {
-                    None
-                }
-            } else {
-                None
-            }
+    let test_surface = test.as_ref().map(|(_, surface)| *surface);
+
+    let mut rating = probed
+        .into_iter()
+        .filter(|(_, capabilities)| capabilities.is_suitable())
+        .filter_map(|(dev, capabilities)| {
+            let (rate, dev, queue_families) =
+                rate_pdevice(instance, dev, &surface_loader, test_surface)?;
+
+            (rate > 0).then_some((rate, dev, queue_families, capabilities))
         })
         .collect::<Vec<_>>();
 
-    unsafe {
-        let surface_loader = ash::khr::surface::Instance::new(&instance.entry, &instance.instance);
-        surface_loader.destroy_surface(test_surface, None);
-    };
-
-    drop(test_window);
+    if let Some((test_window, test_surface)) = test {
+        unsafe { surface_loader.destroy_surface(test_surface, None) };
+        drop(test_window);
+    }
 
     if rating.is_empty() {
-        return None;
+        return Err(if unsupported.is_empty() {
+            DeviceError::NoSuitablePhysicalDevice
+        } else {
+            DeviceError::UnsupportedDevice(unsupported.join("; "))
+        });
     }
 
     rating.sort_by(|(a, ..), (b, ..)| a.cmp(b).reverse());
 
-    let (_, best_device, queue_families) = rating.remove(0);
+    let (_, best_device, queue_families, capabilities) = rating.remove(0);
 
-    Some((best_device, queue_families))
+    Ok((best_device, queue_families, capabilities))
 }
 
 #[inline]
@@ -306,14 +468,9 @@ fn rate_pdevice(
     instance: &VulkanInstance,
     dev: vk::PhysicalDevice,
     surface_loader: &ash::khr::surface::Instance,
-    test_surface: vk::SurfaceKHR,
+    test_surface: Option<vk::SurfaceKHR>,
 ) -> Option<(u64, vk::PhysicalDevice, QueueFamilies)> {
     let props = unsafe { instance.get_physical_device_properties(dev) };
-    let features = unsafe { instance.get_physical_device_features(dev) };
-
-    if features.geometry_shader == 0 {
-        return None;
-    }
 
     let queue_families = if let Some(queue_famies) =
         find_queue_families(instance, dev, test_surface, surface_loader).try_build()
@@ -323,9 +480,11 @@ fn rate_pdevice(
         return None;
     };
 
-    let (support, swapchain_adequate) = swapchain_support(instance, dev, test_surface);
+    let swapchain_adequate = test_surface
+        .map(|surface| swapchain_support(instance, dev, surface).1)
+        .unwrap_or(true);
 
-    if !check_pdevice_extensions(instance, dev) || !swapchain_adequate {
+    if !swapchain_adequate {
         return None;
     }
 
@@ -333,6 +492,8 @@ fn rate_pdevice(
 
     match props.device_type {
         PhysicalDeviceType::DISCRETE_GPU => rating += 100000,
+        // Portability implementations like MoltenVK mostly run on integrated GPUs
+        PhysicalDeviceType::INTEGRATED_GPU => rating += 10000,
         _ => return None,
     }
 
@@ -341,28 +502,6 @@ fn rate_pdevice(
     Some((rating, dev, queue_families))
 }
 
-#[inline]
-fn check_pdevice_extensions(instance: &VulkanInstance, dev: vk::PhysicalDevice) -> bool {
-    let mut required = REQUIRED_EXTENSIONS
-        .iter()
-        .map(|name| unsafe { CStr::from_ptr(*name) })
-        .collect::<Vec<_>>();
-
-    unsafe { instance.enumerate_device_extension_properties(dev) }
-        .unwrap()
-        .into_iter()
-        .for_each(|ext| {
-            let name = if let Ok(name) = ext.extension_name_as_c_str() {
-                name
-            } else {
-                return;
-            };
-            required.retain(|required| *required != name);
-        });
-
-    required.is_empty()
-}
-
 /// Returns SwapchainSupportInfo for a device and if it's adequate
 #[inline]
 fn swapchain_support(
@@ -393,13 +532,24 @@ fn get_pdevice_name(instance: &VulkanInstance, dev: vk::PhysicalDevice) -> Strin
 fn find_queue_families(
     instance: &VulkanInstance,
     dev: vk::PhysicalDevice,
-    test_surface: vk::SurfaceKHR,
+    test_surface: Option<vk::SurfaceKHR>,
     surface_loader: &ash::khr::surface::Instance,
 ) -> QueueFamiliesBuilder {
     let families = unsafe { instance.get_physical_device_queue_family_properties(dev) };
 
     let mut result = QueueFamiliesBuilder::default();
 
+    // Transfer-only families are served by the copy engines and don't stall rendering
+    result.transfer = families
+        .iter()
+        .position(|family| {
+            family.queue_flags.contains(vk::QueueFlags::TRANSFER)
+                && !family
+                    .queue_flags
+                    .intersects(vk::QueueFlags::GRAPHICS | vk::QueueFlags::COMPUTE)
+        })
+        .map(|index| index as u32);
+
     for (i, family) in families.into_iter().enumerate() {
         if family.queue_flags.intersects(vk::QueueFlags::GRAPHICS) {
             result.graphics = Some(i as u32);
@@ -408,7 +558,13 @@ fn find_queue_families(
             result.compute = Some(i as u32);
         }
 
-        if query_present_support(surface_loader, test_surface, dev, i as u32) {
+        let can_present = match test_surface {
+            Some(surface) => query_present_support(surface_loader, surface, dev, i as u32),
+            // Nothing is presented, the present queue is just the graphics one
+            None => result.graphics == Some(i as u32),
+        };
+
+        if can_present {
             result.present = Some(i as u32);
         }
 
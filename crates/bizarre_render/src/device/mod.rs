@@ -1,5 +1,7 @@
+pub mod capabilities;
 pub mod logical_device;
 pub mod physical_device;
 
+pub use capabilities::DeviceCapabilities;
 pub use logical_device::LogicalDevice;
 pub use physical_device::PhysicalDevice;
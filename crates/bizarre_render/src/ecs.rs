@@ -1 +1,334 @@
-use bizarre_ecs::prelude::*;
+use std::time::Instant;
+
+use bizarre_core::Handle;
+use bizarre_ecs::{prelude::*, world::World};
+use bizarre_event::{EventQueue, Events};
+use bizarre_log::{core_error, core_warn};
+use bizarre_sdl::{input::InputState, window::WindowEvent};
+use nalgebra_glm::Vec2;
+
+use crate::{
+    asset_server::AssetServer,
+    camera::{Camera, CameraViewport, Ray},
+    material::material_instance::MaterialInstanceHandle,
+    memory_stats::GpuMemoryStats,
+    render_assets::{AssetStore, RenderAssets},
+    renderer::{DeviceEvent, RenderError, VulkanRenderer},
+    scene::{
+        object_pass::SceneObjectPass, render_object::RenderObjectMaterials, RenderObjectId,
+        SceneHandle,
+    },
+    submitter::RenderPackage,
+};
+
+/// Systems drawing the frame, like [`render_cameras`]. Systems queuing sprites
+/// with [`VulkanRenderer::queue_sprites`] have to run before it
+#[derive(SystemSet)]
+pub struct RenderSet;
+
+/// Ray under the mouse cursor, updated by [`update_cursor_ray`]
+#[derive(Resource, Clone, Copy, Debug, Default)]
+pub struct CursorRay {
+    /// Cursor position in pixels of the focused window
+    pub screen_position: Vec2,
+    /// Camera whose viewport the cursor is over
+    pub camera: Option<Entity>,
+    /// `None` if the cursor is not over any camera
+    pub ray: Option<Ray>,
+}
+
+/// Overrides materials of a scene object for specific passes.
+///
+/// Applied to the scene by [`sync_material_overrides`]. Passes left unset keep
+/// the materials the object was added with
+#[derive(Component, Debug, Clone)]
+pub struct MaterialOverride {
+    scene: SceneHandle,
+    materials: RenderObjectMaterials,
+    applied: bool,
+}
+
+impl MaterialOverride {
+    pub fn new(scene: SceneHandle, materials: RenderObjectMaterials) -> Self {
+        Self {
+            scene,
+            materials,
+            applied: false,
+        }
+    }
+
+    pub fn scene(&self) -> SceneHandle {
+        self.scene
+    }
+
+    pub fn materials(&self) -> &RenderObjectMaterials {
+        &self.materials
+    }
+
+    pub fn set(&mut self, pass: SceneObjectPass, material: MaterialInstanceHandle) {
+        self.materials[pass] = Some(material);
+        self.applied = false;
+    }
+
+    pub fn set_materials(&mut self, materials: RenderObjectMaterials) {
+        self.materials = materials;
+        self.applied = false;
+    }
+}
+
+/// Pushes changed [`MaterialOverride`]s to the scenes of their objects
+pub fn sync_material_overrides(
+    mut assets: ResMut<RenderAssets>,
+    overrides: Query<(&RenderObjectId, &mut MaterialOverride)>,
+) {
+    for (object_id, material_override) in overrides {
+        if material_override.applied {
+            continue;
+        }
+
+        let Some(scene) = assets.scene_mut(&material_override.scene) else {
+            continue;
+        };
+
+        scene.override_object_materials(*object_id, material_override.materials.clone());
+        material_override.applied = true;
+    }
+}
+
+/// Outlines the scene object of the entity, see
+/// [`VulkanRenderer::set_selection_outline`].
+///
+/// Applied to the scene by [`sync_selection`], removing the component clears the selection
+#[derive(Component, Debug, Clone, Copy)]
+pub struct Selected {
+    scene: SceneHandle,
+}
+
+impl Selected {
+    pub fn new(scene: SceneHandle) -> Self {
+        Self { scene }
+    }
+
+    pub fn scene(&self) -> SceneHandle {
+        self.scene
+    }
+}
+
+/// Selects the scene objects of entities with [`Selected`] and deselects the ones
+/// that lost it since the last run
+pub fn sync_selection(
+    mut assets: ResMut<RenderAssets>,
+    selected: Query<(&RenderObjectId, &Selected)>,
+    mut previous: Local<Vec<(SceneHandle, RenderObjectId)>>,
+) {
+    let current = selected
+        .into_iter()
+        .map(|(object_id, selected)| (selected.scene, *object_id))
+        .collect::<Vec<_>>();
+
+    for (scene, object_id) in previous.iter() {
+        if current.contains(&(*scene, *object_id)) {
+            continue;
+        }
+
+        if let Some(scene) = assets.scene_mut(scene) {
+            scene.set_object_selected(*object_id, false);
+        }
+    }
+
+    for (scene, object_id) in current.iter() {
+        if previous.contains(&(*scene, *object_id)) {
+            continue;
+        }
+
+        if let Some(scene) = assets.scene_mut(scene) {
+            scene.set_object_selected(*object_id, true);
+        }
+    }
+
+    *previous = current;
+}
+
+/// Renders every active [`Camera`] into its render target.
+///
+/// Cameras are grouped by render target and drawn in ascending priority order,
+/// every target is rendered with its last used extent. A [`CameraViewport`]
+/// component on the camera entity replaces the camera's own viewport. Sprites
+/// queued with [`VulkanRenderer::queue_sprites`] are drawn over the last camera
+pub fn render_cameras(
+    mut renderer: ResMut<VulkanRenderer>,
+    mut assets: ResMut<RenderAssets>,
+    mut events: ResMut<EventQueue>,
+    cameras: Query<(&Camera, Option<&CameraViewport>)>,
+) {
+    let mut cameras = cameras
+        .into_iter()
+        .filter(|(camera, _)| camera.active)
+        .collect::<Vec<_>>();
+
+    if cameras.is_empty() {
+        return;
+    }
+
+    cameras.sort_by_key(|(camera, _)| (camera.render_target.as_raw(), camera.priority));
+
+    for target_cameras in cameras.chunk_by(|(a, _), (b, _)| a.render_target == b.render_target) {
+        let render_target = target_cameras[0].0.render_target;
+
+        let Some(extent) = assets
+            .render_targets
+            .get(&render_target)
+            .map(|target| target.extent())
+        else {
+            core_warn!(
+                "render_cameras: camera refers to a missing render target {render_target:?}"
+            );
+            continue;
+        };
+
+        let mut packages = target_cameras
+            .iter()
+            .map(|(camera, viewport)| RenderPackage {
+                camera: Some(camera.uniform()),
+                viewport: viewport.copied().unwrap_or(camera.viewport),
+                scissor: camera.scissor,
+                clear: camera.clear,
+                clear_depth: camera.clear_depth,
+                ..RenderPackage::new(camera.scene)
+            })
+            .collect::<Vec<_>>();
+
+        if let Some(last) = packages.last_mut() {
+            last.sprites = renderer.take_queued_sprites(render_target);
+        }
+
+        match renderer.render_packages_to_target(&mut assets, render_target, extent, &packages) {
+            Ok(()) | Err(RenderError::RenderSkipped) => (),
+            Err(RenderError::DeviceLost) => {
+                recover_device(&mut renderer, &mut assets, &mut events);
+                return;
+            }
+            Err(err) => core_error!("render_cameras: failed to render {render_target:?}: {err}"),
+        }
+    }
+}
+
+/// Hides the present targets of minimized, hidden and zero sized windows, so
+/// rendering and presenting to them is skipped until they're shown again
+pub fn update_present_target_visibility(
+    mut assets: ResMut<RenderAssets>,
+    events: Events<WindowEvent>,
+) {
+    for event in events {
+        let (window, visible) = match event {
+            WindowEvent::Hidden(window) | WindowEvent::Minimized(window) => (window, false),
+            WindowEvent::Shown(window)
+            | WindowEvent::Exposed(window)
+            | WindowEvent::Restored(window)
+            | WindowEvent::Maximized(window) => (window, true),
+            WindowEvent::Resized { handle, size } => (handle, size.min() > 0),
+            _ => continue,
+        };
+
+        if let Some(present_target) = assets
+            .present_targets
+            .get_mut(&Handle::derived_from(&window))
+        {
+            present_target.set_visible(visible);
+        }
+    }
+}
+
+/// Destroys the present targets of windows that must close, before the windows
+/// themselves are destroyed on the next frame
+pub fn release_closed_present_targets(
+    mut assets: ResMut<RenderAssets>,
+    events: Events<WindowEvent>,
+) {
+    for event in events {
+        if let WindowEvent::WindowMustClose(window) | WindowEvent::MainWindowMustClose(window) =
+            event
+        {
+            assets.destroy_present_target(&Handle::derived_from(&window));
+        }
+    }
+}
+
+/// Destroys [`RenderAssets`] and then the [`VulkanRenderer`], register it with
+/// [`World::add_teardown`] so they go before the rest of the resources
+pub fn teardown_render(world: &mut World) {
+    if let Some(assets) = world.remove_resource::<RenderAssets>() {
+        assets.destroy();
+    }
+
+    drop(world.remove_resource::<VulkanRenderer>());
+}
+
+/// Recreates the device after a [`RenderError::DeviceLost`] and reports it with
+/// [`DeviceEvent`]s
+pub fn recover_device(
+    renderer: &mut VulkanRenderer,
+    assets: &mut RenderAssets,
+    events: &mut EventQueue,
+) {
+    events.push_event(DeviceEvent::Lost);
+
+    match renderer.recover_device(assets) {
+        Ok(()) => events.push_event(DeviceEvent::Restored),
+        Err(err) => {
+            core_error!("Failed to recover from a device loss: {err}");
+            events.push_event(DeviceEvent::RecoveryFailed(err.to_string()));
+        }
+    }
+}
+
+/// Refreshes [`GpuMemoryStats`] once per its update interval
+/// Casts the [`CursorRay`] from the topmost active camera under the cursor.
+///
+/// The mouse position is taken relative to the focused window, which is expected
+/// to present the render targets of the cameras
+pub fn update_cursor_ray(
+    mut cursor: ResMut<CursorRay>,
+    input: Res<InputState>,
+    assets: Res<RenderAssets>,
+    cameras: Query<(Entity, &Camera, Option<&CameraViewport>)>,
+) {
+    let screen_position: Vec2 = input.mouse_position().cast();
+
+    let hit = cameras
+        .into_iter()
+        .filter(|(_, camera, _)| camera.active)
+        .filter_map(|(entity, camera, viewport)| {
+            let extent = assets.render_targets.get(&camera.render_target)?.extent();
+            let rect = viewport.unwrap_or(&camera.viewport).to_rect(extent)?;
+
+            let min = Vec2::new(rect.offset.x as f32, rect.offset.y as f32);
+            let max = min + Vec2::new(rect.extent.width as f32, rect.extent.height as f32);
+            let inside = screen_position.x >= min.x
+                && screen_position.y >= min.y
+                && screen_position.x < max.x
+                && screen_position.y < max.y;
+
+            inside.then_some((entity, camera, rect))
+        })
+        .max_by_key(|(_, camera, _)| camera.priority);
+
+    *cursor = CursorRay {
+        screen_position,
+        camera: hit.map(|(entity, ..)| entity),
+        ray: hit.and_then(|(_, camera, rect)| camera.screen_to_world_ray(screen_position, rect)),
+    };
+}
+
+pub fn update_gpu_memory_stats(mut stats: ResMut<GpuMemoryStats>) {
+    stats.update(Instant::now());
+}
+
+/// Moves assets finished by the [`AssetServer`] loader threads into [`RenderAssets`]
+pub fn update_asset_server(
+    mut server: ResMut<AssetServer>,
+    mut assets: ResMut<RenderAssets>,
+    mut events: ResMut<EventQueue>,
+) {
+    server.process_loaded(&mut assets, &mut events);
+}
@@ -4,7 +4,11 @@ use ash::vk::{self, Handle};
 use nalgebra_glm::UVec2;
 use vma::Alloc;
 
-use crate::{vulkan_context::get_device, COLOR_FORMAT, DEPTH_FORMAT};
+use crate::{
+    memory_stats::{track_allocation, track_free, AllocationCategory},
+    vulkan_context::{get_device, get_instance},
+    COLOR_FORMAT, DEPTH_FORMAT,
+};
 
 pub struct VulkanImage {
     pub image: vk::Image,
@@ -40,7 +44,9 @@ impl VulkanImage {
         Self::new(
             size,
             COLOR_FORMAT,
-            vk::ImageUsageFlags::TRANSFER_SRC | vk::ImageUsageFlags::COLOR_ATTACHMENT,
+            vk::ImageUsageFlags::TRANSFER_SRC
+                | vk::ImageUsageFlags::COLOR_ATTACHMENT
+                | vk::ImageUsageFlags::SAMPLED,
             vk::ImageAspectFlags::COLOR,
             vk::SampleCountFlags::TYPE_1,
             1,
@@ -52,7 +58,9 @@ impl VulkanImage {
         Self::new(
             size,
             DEPTH_FORMAT,
-            vk::ImageUsageFlags::DEPTH_STENCIL_ATTACHMENT | vk::ImageUsageFlags::SAMPLED,
+            vk::ImageUsageFlags::DEPTH_STENCIL_ATTACHMENT
+                | vk::ImageUsageFlags::SAMPLED
+                | vk::ImageUsageFlags::TRANSFER_SRC,
             vk::ImageAspectFlags::DEPTH,
             samples,
             1,
@@ -60,6 +68,40 @@ impl VulkanImage {
         )
     }
 
+    /// Sampled color image with a full mip chain, filled with a transfer and
+    /// finished with [`generate_mipmaps`](Self::generate_mipmaps)
+    pub fn texture_image(size: UVec2, format: vk::Format) -> Result<Self, vk::Result> {
+        Self::new(
+            size,
+            format,
+            vk::ImageUsageFlags::TRANSFER_SRC
+                | vk::ImageUsageFlags::TRANSFER_DST
+                | vk::ImageUsageFlags::SAMPLED,
+            vk::ImageAspectFlags::COLOR,
+            vk::SampleCountFlags::TYPE_1,
+            mip_level_count(size),
+            1,
+        )
+    }
+
+    /// Sampled block-compressed image, every one of its `level_count` levels is
+    /// filled with a transfer, they can't be blitted
+    pub fn compressed_texture_image(
+        size: UVec2,
+        format: vk::Format,
+        level_count: u32,
+    ) -> Result<Self, vk::Result> {
+        Self::new(
+            size,
+            format,
+            vk::ImageUsageFlags::TRANSFER_DST | vk::ImageUsageFlags::SAMPLED,
+            vk::ImageAspectFlags::COLOR,
+            vk::SampleCountFlags::TYPE_1,
+            level_count,
+            1,
+        )
+    }
+
     pub fn new(
         size: UVec2,
         format: vk::Format,
@@ -83,8 +125,8 @@ impl VulkanImage {
                 .format(format)
                 .initial_layout(vk::ImageLayout::UNDEFINED)
                 .usage(usage)
-                .mip_levels(layer_count)
-                .array_layers(level_count);
+                .mip_levels(level_count)
+                .array_layers(layer_count);
 
             let create_info = vma::AllocationCreateInfo {
                 usage: vma::MemoryUsage::Auto,
@@ -94,6 +136,8 @@ impl VulkanImage {
             unsafe { device.allocator.create_image(&image_info, &create_info)? }
         };
 
+        track_allocation(AllocationCategory::Image, &allocation);
+
         let image_view = {
             let create_info = vk::ImageViewCreateInfo::default()
                 .image(image)
@@ -102,7 +146,7 @@ impl VulkanImage {
                 .subresource_range(vk::ImageSubresourceRange {
                     aspect_mask,
                     base_mip_level: 0,
-                    level_count: 1,
+                    level_count,
                     base_array_layer: 0,
                     layer_count: 1,
                 });
@@ -131,6 +175,8 @@ impl VulkanImage {
 
         let device = get_device();
 
+        track_free(AllocationCategory::Image, &self.allocation);
+
         unsafe {
             device.destroy_image_view(self.image_view, None);
             device
@@ -150,8 +196,8 @@ impl VulkanImage {
                 .format(self.format)
                 .initial_layout(vk::ImageLayout::UNDEFINED)
                 .usage(self.usage)
-                .mip_levels(self.layer_count)
-                .array_layers(self.level_count);
+                .mip_levels(self.level_count)
+                .array_layers(self.layer_count);
 
             let create_info = vma::AllocationCreateInfo {
                 usage: vma::MemoryUsage::Auto,
@@ -161,6 +207,8 @@ impl VulkanImage {
             unsafe { device.allocator.create_image(&image_info, &create_info)? }
         };
 
+        track_allocation(AllocationCategory::Image, &allocation);
+
         let image_view = {
             let create_info = vk::ImageViewCreateInfo::default()
                 .image(image)
@@ -169,7 +217,7 @@ impl VulkanImage {
                 .subresource_range(vk::ImageSubresourceRange {
                     aspect_mask: self.aspect_mask,
                     base_mip_level: 0,
-                    level_count: 1,
+                    level_count: self.level_count,
                     base_array_layer: 0,
                     layer_count: 1,
                 });
@@ -227,7 +275,7 @@ impl VulkanImage {
             .subresource_range(vk::ImageSubresourceRange {
                 aspect_mask: self.aspect_mask,
                 base_mip_level: 0,
-                level_count: 1,
+                level_count: self.level_count,
                 base_array_layer: 0,
                 layer_count: 1,
             });
@@ -237,6 +285,138 @@ impl VulkanImage {
         barrier
     }
 
+    /// Records blits filling every mip level from the previous one. Level 0 has to be
+    /// in `TRANSFER_DST_OPTIMAL`, the whole chain ends up in `SHADER_READ_ONLY_OPTIMAL`.
+    ///
+    /// Formats without linear filter support are downsampled with the nearest filter
+    pub fn generate_mipmaps(&mut self, cmd: vk::CommandBuffer) {
+        let device = get_device();
+
+        let format_features = unsafe {
+            get_instance()
+                .get_physical_device_format_properties(*device.physical, self.format)
+                .optimal_tiling_features
+        };
+
+        let filter =
+            if format_features.contains(vk::FormatFeatureFlags::SAMPLED_IMAGE_FILTER_LINEAR) {
+                vk::Filter::LINEAR
+            } else {
+                vk::Filter::NEAREST
+            };
+
+        let level_barrier = |level: u32,
+                             old_layout: vk::ImageLayout,
+                             new_layout: vk::ImageLayout,
+                             src_access_mask: vk::AccessFlags2,
+                             dst_access_mask: vk::AccessFlags2,
+                             dst_stage_mask: vk::PipelineStageFlags2| {
+            vk::ImageMemoryBarrier2::default()
+                .image(self.image)
+                .src_stage_mask(vk::PipelineStageFlags2::TRANSFER)
+                .src_access_mask(src_access_mask)
+                .dst_stage_mask(dst_stage_mask)
+                .dst_access_mask(dst_access_mask)
+                .old_layout(old_layout)
+                .new_layout(new_layout)
+                .subresource_range(vk::ImageSubresourceRange {
+                    aspect_mask: self.aspect_mask,
+                    base_mip_level: level,
+                    level_count: 1,
+                    base_array_layer: 0,
+                    layer_count: self.layer_count,
+                })
+        };
+
+        let level_size = |level: u32| vk::Offset3D {
+            x: (self.size.x >> level).max(1) as i32,
+            y: (self.size.y >> level).max(1) as i32,
+            z: 1,
+        };
+
+        let layers = |level: u32| vk::ImageSubresourceLayers {
+            aspect_mask: self.aspect_mask,
+            mip_level: level,
+            base_array_layer: 0,
+            layer_count: self.layer_count,
+        };
+
+        for level in 1..self.level_count {
+            let barriers = [
+                level_barrier(
+                    level - 1,
+                    vk::ImageLayout::TRANSFER_DST_OPTIMAL,
+                    vk::ImageLayout::TRANSFER_SRC_OPTIMAL,
+                    vk::AccessFlags2::TRANSFER_WRITE,
+                    vk::AccessFlags2::TRANSFER_READ,
+                    vk::PipelineStageFlags2::TRANSFER,
+                ),
+                level_barrier(
+                    level,
+                    vk::ImageLayout::UNDEFINED,
+                    vk::ImageLayout::TRANSFER_DST_OPTIMAL,
+                    vk::AccessFlags2::NONE,
+                    vk::AccessFlags2::TRANSFER_WRITE,
+                    vk::PipelineStageFlags2::TRANSFER,
+                ),
+            ];
+
+            let blit = vk::ImageBlit {
+                src_subresource: layers(level - 1),
+                src_offsets: [vk::Offset3D::default(), level_size(level - 1)],
+                dst_subresource: layers(level),
+                dst_offsets: [vk::Offset3D::default(), level_size(level)],
+            };
+
+            unsafe {
+                device.cmd_pipeline_barrier2(
+                    cmd,
+                    &vk::DependencyInfo::default().image_memory_barriers(&barriers),
+                );
+
+                device.cmd_blit_image(
+                    cmd,
+                    self.image,
+                    vk::ImageLayout::TRANSFER_SRC_OPTIMAL,
+                    self.image,
+                    vk::ImageLayout::TRANSFER_DST_OPTIMAL,
+                    &[blit],
+                    filter,
+                );
+            }
+        }
+
+        let last_level = self.level_count - 1;
+
+        let barriers = (0..self.level_count)
+            .map(|level| {
+                let old_layout = if level == last_level {
+                    vk::ImageLayout::TRANSFER_DST_OPTIMAL
+                } else {
+                    vk::ImageLayout::TRANSFER_SRC_OPTIMAL
+                };
+
+                level_barrier(
+                    level,
+                    old_layout,
+                    vk::ImageLayout::SHADER_READ_ONLY_OPTIMAL,
+                    vk::AccessFlags2::TRANSFER_WRITE,
+                    vk::AccessFlags2::SHADER_SAMPLED_READ,
+                    vk::PipelineStageFlags2::FRAGMENT_SHADER,
+                )
+            })
+            .collect::<Vec<_>>();
+
+        unsafe {
+            device.cmd_pipeline_barrier2(
+                cmd,
+                &vk::DependencyInfo::default().image_memory_barriers(&barriers),
+            );
+        }
+
+        self.image_layout = vk::ImageLayout::SHADER_READ_ONLY_OPTIMAL;
+    }
+
     pub fn destroy(&mut self) {
         if self.image.is_null() {
             return;
@@ -244,6 +424,8 @@ impl VulkanImage {
 
         let device = get_device();
 
+        track_free(AllocationCategory::Image, &self.allocation);
+
         unsafe {
             device
                 .allocator
@@ -254,6 +436,11 @@ impl VulkanImage {
     }
 }
 
+/// Amount of mip levels down to 1x1 for an image of `size`
+pub fn mip_level_count(size: UVec2) -> u32 {
+    u32::BITS - size.x.max(size.y).max(1).leading_zeros()
+}
+
 impl Drop for VulkanImage {
     fn drop(&mut self) {
         self.destroy();
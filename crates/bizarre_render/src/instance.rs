@@ -1,71 +1,218 @@
-use std::ops::{Deref, DerefMut};
+use std::{
+    ffi::{c_char, CStr},
+    ops::{Deref, DerefMut},
+};
 
 use ash::vk;
-use bizarre_log::core_trace;
+use bizarre_config::{get_config_section, ConfigSection};
+use bizarre_log::{core_info, core_trace, core_warn};
+use serde::Deserialize;
 use thiserror::Error;
 
 use crate::{
     debug_messenger::{populate_debug_messenger_create_info, DebugMessenger},
     device::{logical_device::DeviceResult, LogicalDevice},
+    surface::surface_extensions,
 };
 
 #[derive(Error, Debug)]
 pub enum InstanceError {
     #[error("Failed to create a Vulkan instance: {0}")]
     CreationError(#[from] vk::Result),
+    #[error("Required instance extension `{0}` is not available")]
+    MissingExtension(String),
 }
 
 pub type InstanceResult<T> = Result<T, InstanceError>;
 
+/// Window system the instance creates surfaces for
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum SurfaceBackend {
+    /// Picked from the windowing backend SDL runs on
+    #[default]
+    Auto,
+    Wayland,
+    Xcb,
+    Xlib,
+    Win32,
+    /// No surfaces, rendering only into render targets
+    Headless,
+}
+
+/// `[instance]` section of the engine config
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default)]
+pub struct InstanceConfig {
+    pub backend: SurfaceBackend,
+    /// Enumerates portability implementations like MoltenVK when the loader
+    /// supports `VK_KHR_portability_enumeration`
+    pub portability_enumeration: bool,
+    /// Enables `debugPrintfEXT` in shaders through the validation layer settings,
+    /// the printed lines go to the engine log. Debug builds only
+    pub shader_printf: bool,
+    /// Logs register counts, spills and other driver statistics of every material
+    /// pipeline when the device supports `VK_KHR_pipeline_executable_properties`
+    pub pipeline_statistics: bool,
+}
+
+impl Default for InstanceConfig {
+    fn default() -> Self {
+        Self {
+            backend: SurfaceBackend::Auto,
+            portability_enumeration: true,
+            shader_printf: false,
+            pipeline_statistics: false,
+        }
+    }
+}
+
+impl ConfigSection for InstanceConfig {
+    fn section_name() -> &'static str {
+        "instance"
+    }
+}
+
 pub struct VulkanInstance {
     pub(crate) entry: ash::Entry,
     pub(crate) instance: ash::Instance,
     pub(crate) debug_messenger: Option<DebugMessenger>,
+    /// Never [`SurfaceBackend::Auto`], it's resolved on creation
+    backend: SurfaceBackend,
+    enabled_extensions: Vec<&'static CStr>,
+    shader_printf: bool,
+    pipeline_statistics: bool,
 }
 
 impl VulkanInstance {
-    pub fn new() -> Self {
-        let entry = ash::Entry::linked();
+    /// Creates an instance configured by the `[instance]` config section
+    pub fn new() -> InstanceResult<Self> {
+        let config = get_config_section::<InstanceConfig>().unwrap_or_else(|err| {
+            core_warn!("Invalid `[instance]` config, using the defaults: {err}");
+            InstanceConfig::default()
+        });
 
-        let instance = 'create_instance: {
-            unsafe {
-                let mut extentions = PLATFORM_EXTENSIONS.to_vec();
-                extentions.extend_from_slice(ADDITIONAL_EXTENSIONS);
+        Self::with_config(&config)
+    }
 
-                let application_info =
-                    vk::ApplicationInfo::default().api_version(vk::API_VERSION_1_3);
+    pub fn with_config(config: &InstanceConfig) -> InstanceResult<Self> {
+        let entry = ash::Entry::linked();
 
-                let create_info = vk::InstanceCreateInfo::default()
-                    .application_info(&application_info)
-                    .enabled_extension_names(&extentions)
-                    .enabled_layer_names(&LAYERS);
+        let available =
+            unsafe { entry.enumerate_instance_extension_properties(None) }.unwrap_or_default();
 
-                #[cfg(debug_assertions)]
-                {
-                    let mut debug_utils = vk::DebugUtilsMessengerCreateInfoEXT::default();
+        let is_available = |name: &CStr| {
+            available
+                .iter()
+                .any(|ext| ext.extension_name_as_c_str() == Ok(name))
+        };
 
-                    populate_debug_messenger_create_info(&mut debug_utils);
+        let (backend, required, optional) = surface_extensions(config.backend);
 
-                    let create_info = create_info.push_next(&mut debug_utils);
+        core_info!("Surface backend: {backend:?}");
 
-                    break 'create_instance entry.create_instance(&create_info, None).unwrap();
-                }
+        let mut enabled_extensions = Vec::new();
 
-                entry.create_instance(&create_info, None).unwrap()
+        for name in required.iter().chain(ADDITIONAL_EXTENSIONS) {
+            if !is_available(name) {
+                return Err(InstanceError::MissingExtension(
+                    name.to_string_lossy().into_owned(),
+                ));
             }
-        };
+
+            enabled_extensions.push(*name);
+        }
+
+        enabled_extensions.extend(optional.iter().copied().filter(|name| is_available(name)));
+
+        let mut flags = vk::InstanceCreateFlags::empty();
+
+        if config.portability_enumeration && is_available(ash::khr::portability_enumeration::NAME) {
+            enabled_extensions.push(ash::khr::portability_enumeration::NAME);
+            flags |= vk::InstanceCreateFlags::ENUMERATE_PORTABILITY_KHR;
+        }
+
+        let shader_printf = config.shader_printf && shader_printf_available(&entry);
+
+        if shader_printf {
+            enabled_extensions.push(vk::EXT_LAYER_SETTINGS_NAME);
+        }
+
+        core_trace!("Instance extensions: {enabled_extensions:?}");
+
+        let extension_names = enabled_extensions
+            .iter()
+            .map(|name| name.as_ptr())
+            .collect::<Vec<_>>();
+
+        let application_info = vk::ApplicationInfo::default().api_version(vk::API_VERSION_1_3);
+
+        #[allow(unused_mut)]
+        let mut create_info = vk::InstanceCreateInfo::default()
+            .flags(flags)
+            .application_info(&application_info)
+            .enabled_extension_names(&extension_names)
+            .enabled_layer_names(LAYERS);
+
+        #[cfg(debug_assertions)]
+        let mut debug_utils = vk::DebugUtilsMessengerCreateInfoEXT::default();
 
         #[cfg(debug_assertions)]
-        let debug_messenger = Some(DebugMessenger::new(&entry, &instance));
+        {
+            populate_debug_messenger_create_info(&mut debug_utils, shader_printf);
+            create_info = create_info.push_next(&mut debug_utils);
+        }
+
+        let printf_enables = [c"VK_VALIDATION_FEATURE_ENABLE_DEBUG_PRINTF_EXT".as_ptr()];
+        let printf_settings = shader_printf_settings(&printf_enables, &vk::FALSE);
+        let mut layer_settings =
+            vk::LayerSettingsCreateInfoEXT::default().settings(&printf_settings);
+
+        if shader_printf {
+            core_info!("Shader printf is enabled");
+            create_info = create_info.push_next(&mut layer_settings);
+        }
+
+        let instance = unsafe { entry.create_instance(&create_info, None)? };
+
+        #[cfg(debug_assertions)]
+        let debug_messenger = Some(DebugMessenger::new(&entry, &instance, shader_printf));
 
         #[cfg(not(debug_assertions))]
         let debug_messenger = None;
 
-        Self {
+        Ok(Self {
             entry,
             instance,
             debug_messenger,
-        }
+            backend,
+            enabled_extensions,
+            shader_printf,
+            pipeline_statistics: config.pipeline_statistics,
+        })
+    }
+
+    /// Shader `debugPrintfEXT` calls are logged
+    pub fn shader_printf(&self) -> bool {
+        self.shader_printf
+    }
+
+    /// Devices created from the instance log statistics of material pipelines
+    pub fn pipeline_statistics(&self) -> bool {
+        self.pipeline_statistics
+    }
+
+    pub fn backend(&self) -> SurfaceBackend {
+        self.backend
+    }
+
+    /// The instance has no surface extensions, windows can't be presented to
+    pub fn is_headless(&self) -> bool {
+        self.backend == SurfaceBackend::Headless
+    }
+
+    pub fn has_extension(&self, name: &CStr) -> bool {
+        self.enabled_extensions.contains(&name)
     }
 
     pub fn create_device_ext(&self) -> DeviceResult<LogicalDevice> {
@@ -102,21 +249,64 @@ impl Drop for VulkanInstance {
     }
 }
 
-#[cfg(target_os = "linux")]
-const PLATFORM_EXTENSIONS: &'static [*const std::ffi::c_char] = &[
-    vk::KHR_SURFACE_NAME.as_ptr(),
-    vk::KHR_WAYLAND_SURFACE_NAME.as_ptr(),
-    vk::KHR_XLIB_SURFACE_NAME.as_ptr(),
-];
-
 #[cfg(debug_assertions)]
-const ADDITIONAL_EXTENSIONS: &'static [*const std::ffi::c_char] =
-    &[vk::EXT_DEBUG_UTILS_NAME.as_ptr()];
+const ADDITIONAL_EXTENSIONS: &[&CStr] = &[vk::EXT_DEBUG_UTILS_NAME];
+
+#[cfg(not(debug_assertions))]
+const ADDITIONAL_EXTENSIONS: &[&CStr] = &[];
+
+const VALIDATION_LAYER: &CStr = c"VK_LAYER_KHRONOS_validation";
+
+/// Shader printf needs the validation layer and its `VK_EXT_layer_settings`
+fn shader_printf_available(entry: &ash::Entry) -> bool {
+    if !cfg!(debug_assertions) {
+        core_warn!("Shader printf needs the validation layer, only debug builds enable it");
+        return false;
+    }
+
+    let layer_extensions =
+        unsafe { entry.enumerate_instance_extension_properties(Some(VALIDATION_LAYER)) }
+            .unwrap_or_default();
+
+    let available = layer_extensions
+        .iter()
+        .any(|ext| ext.extension_name_as_c_str() == Ok(vk::EXT_LAYER_SETTINGS_NAME));
+
+    if !available {
+        core_warn!("Shader printf is disabled, the validation layer lacks `VK_EXT_layer_settings`");
+    }
+
+    available
+}
+
+/// Validation layer settings enabling debug printf and sending its output to the
+/// debug messenger instead of stdout
+fn shader_printf_settings<'a>(
+    enables: &'a [*const c_char],
+    to_stdout: &'a vk::Bool32,
+) -> [vk::LayerSettingEXT<'a>; 2] {
+    [
+        vk::LayerSettingEXT {
+            p_layer_name: VALIDATION_LAYER.as_ptr(),
+            p_setting_name: c"enables".as_ptr(),
+            ty: vk::LayerSettingTypeEXT::STRING,
+            value_count: enables.len() as u32,
+            p_values: enables.as_ptr().cast(),
+            ..Default::default()
+        },
+        vk::LayerSettingEXT {
+            p_layer_name: VALIDATION_LAYER.as_ptr(),
+            p_setting_name: c"printf_to_stdout".as_ptr(),
+            ty: vk::LayerSettingTypeEXT::BOOL32,
+            value_count: 1,
+            p_values: (to_stdout as *const vk::Bool32).cast(),
+            ..Default::default()
+        },
+    ]
+}
 
 #[cfg(debug_assertions)]
-const LAYERS: &'static [*const std::ffi::c_char] = unsafe {
-    &[std::ffi::CStr::from_bytes_with_nul_unchecked(b"VK_LAYER_KHRONOS_validation\0").as_ptr()]
-};
+const LAYERS: &'static [*const c_char] = &[VALIDATION_LAYER.as_ptr()];
 
 #[cfg(not(debug_assertions))]
-const LAYERS: &'static [*const std::ffi::c_char] = &[];
+const LAYERS: &'static [*const c_char] = &[];
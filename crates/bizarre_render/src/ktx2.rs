@@ -0,0 +1,99 @@
+//! Reader of KTX2 containers holding a single 2D image
+
+use ash::vk;
+use nalgebra_glm::UVec2;
+
+use crate::texture::{TextureError, TextureResult};
+
+const IDENTIFIER: [u8; 12] = [
+    0xab, 0x4b, 0x54, 0x58, 0x20, 0x32, 0x30, 0xbb, 0x0d, 0x0a, 0x1a, 0x0a,
+];
+
+/// Identifier, 9 header fields and the index of the data blocks
+const LEVEL_INDEX_OFFSET: usize = 80;
+const LEVEL_INDEX_ENTRY_LEN: usize = 24;
+const SUPERCOMPRESSION_BASIS_LZ: u32 = 1;
+
+/// Image of a KTX2 file, borrowing the level data from it
+pub(crate) struct Ktx2Image<'a> {
+    pub format: vk::Format,
+    pub size: UVec2,
+    /// Mip levels from the largest
+    pub levels: Vec<&'a [u8]>,
+}
+
+fn invalid(reason: &'static str) -> TextureError {
+    TextureError::InvalidKtx2(reason)
+}
+
+fn read_u32(bytes: &[u8], offset: usize) -> TextureResult<u32> {
+    bytes
+        .get(offset..offset + 4)
+        .map(|bytes| u32::from_le_bytes(bytes.try_into().unwrap()))
+        .ok_or(invalid("the header is truncated"))
+}
+
+fn read_u64(bytes: &[u8], offset: usize) -> TextureResult<u64> {
+    bytes
+        .get(offset..offset + 8)
+        .map(|bytes| u64::from_le_bytes(bytes.try_into().unwrap()))
+        .ok_or(invalid("the level index is truncated"))
+}
+
+impl<'a> Ktx2Image<'a> {
+    /// Parses the header and the level index of `bytes`.
+    ///
+    /// Supercompressed files, including every Basis Universal one, are rejected,
+    /// there is no transcoder for them
+    pub fn parse(bytes: &'a [u8]) -> TextureResult<Self> {
+        if !bytes.starts_with(&IDENTIFIER) {
+            return Err(invalid("the file identifier doesn't match"));
+        }
+
+        let field = |index: usize| read_u32(bytes, IDENTIFIER.len() + index * 4);
+
+        let format = vk::Format::from_raw(field(0)? as i32);
+        let size = UVec2::new(field(2)?, field(3)?);
+        let depth = field(4)?;
+        let layer_count = field(5)?;
+        let face_count = field(6)?;
+        let level_count = field(7)?.max(1);
+        let supercompression = field(8)?;
+
+        // UASTC images aren't supercompressed, but have no Vulkan format either
+        if supercompression == SUPERCOMPRESSION_BASIS_LZ || format == vk::Format::UNDEFINED {
+            return Err(TextureError::BasisUniversal);
+        }
+
+        if supercompression != 0 {
+            return Err(TextureError::UnsupportedSupercompression(supercompression));
+        }
+
+        if size.x == 0 || size.y == 0 {
+            return Err(TextureError::ZeroSize);
+        }
+
+        if depth > 1 || layer_count > 1 || face_count != 1 {
+            return Err(invalid("only single 2D images are supported"));
+        }
+
+        let levels = (0..level_count as usize)
+            .map(|level| {
+                let entry = LEVEL_INDEX_OFFSET + level * LEVEL_INDEX_ENTRY_LEN;
+                let offset = read_u64(bytes, entry)? as usize;
+                let len = read_u64(bytes, entry + 8)? as usize;
+
+                offset
+                    .checked_add(len)
+                    .and_then(|end| bytes.get(offset..end))
+                    .ok_or(invalid("a level is out of the file bounds"))
+            })
+            .collect::<TextureResult<Vec<_>>>()?;
+
+        Ok(Self {
+            format,
+            size,
+            levels,
+        })
+    }
+}
@@ -11,24 +11,42 @@ pub const DEPTH_FORMAT: vk::Format = vk::Format::D32_SFLOAT;
 pub const OUTPUT_FORMAT: vk::Format = vk::Format::R8G8B8A8_SRGB;
 pub const TMP_SAMPLES: vk::SampleCountFlags = vk::SampleCountFlags::TYPE_1;
 
+mod bcn;
 mod debug_messenger;
 mod device;
 mod image;
 mod instance;
+mod ktx2;
 mod macros;
+mod surface;
 mod vulkan_context;
 
 pub mod antialiasing;
+pub mod asset_server;
+pub mod asset_watcher;
 pub mod buffer;
+pub mod camera;
+pub mod command_buffer;
+pub mod culling;
 pub mod ecs;
 pub mod material;
+pub mod memory_stats;
 pub mod mesh;
+pub mod mesh_pool;
 pub mod present_target;
 pub mod render_assets;
 pub mod render_pass;
 pub mod render_target;
+pub mod render_texture;
 pub mod renderer;
+pub mod sampler;
 pub mod scene;
 pub mod shader;
+pub mod shader_reflection;
+pub mod sprite;
 pub mod submitter;
+pub mod texture;
 pub mod vertex;
+
+pub use device::capabilities;
+pub use instance::{InstanceConfig, InstanceError, SurfaceBackend};
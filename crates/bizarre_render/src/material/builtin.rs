@@ -3,31 +3,106 @@ use ash::vk;
 use crate::{
     device::LogicalDevice,
     shader::{ShaderStage, ShaderStageFlags, ShaderStages},
+    sprite::MAX_SPRITE_TEXTURES,
     vertex::Vertex,
-    vulkan_context::get_device,
     COLOR_FORMAT, DEPTH_FORMAT,
 };
 
 use super::{
-    material_binding::{base_scene_bindings, MaterialBinding, MaterialBindingRate},
-    pipeline::{ShaderStageDefinition, VulkanPipeline, VulkanPipelineRequirements},
+    material_binding::{
+        base_scene_bindings, base_scene_bindings_with, MaterialBinding, MaterialBindingRate,
+    },
+    pipeline::{PipelineResult, ShaderStageDefinition, VulkanPipelineRequirements},
     pipeline_features::{CullMode, PipelineFeatureFlags, PolygonMode, VulkanPipelineFeatures},
-    Material,
+    Material, FLAT_NORMAL_TEXTURE,
 };
 
+/// Set of the tangent-space normal map of [`basic_deferred`] and [`pbr_deferred`],
+/// bound with [`MaterialInstance::set_texture`](super::material_instance::MaterialInstance::set_texture).
+/// Without one the vertex normals are used as they are
+pub const BASIC_DEFERRED_NORMAL_MAP_SET: u32 = 2;
+/// Set of the base color texture of [`pbr_deferred`], white without one
+pub const PBR_ALBEDO_SET: u32 = 3;
+/// Set of the occlusion (`R`), roughness (`G`) and metallic (`B`) texture of
+/// [`pbr_deferred`]. Without one the surface is a rough dielectric
+pub const PBR_OCCLUSION_ROUGHNESS_METALLIC_SET: u32 = 4;
+
+/// Occlusion-roughness-metallic of [`pbr_deferred`] without a texture bound
+const PBR_DEFAULT_ORM: [u8; 4] = [255, 128, 0, 255];
+
+/// Amount of color attachments of the deferred pass: color, normals, position,
+/// material parameters and velocity
+pub const GBUFFER_ATTACHMENT_COUNT: usize = 5;
+/// G-buffer attachments read by the composition pass, all but the velocity
+pub const COMPOSITION_INPUT_COUNT: usize = 4;
+
+/// Falls back to a storage buffer for batches with too much instance data and
+/// can be drawn in a depth pre-pass
 pub fn basic_deferred() -> Material {
     with_basic_deferred(|_| {})
+        .with_storage_fallback("assets/shaders/basic_deferred_ssbo.vert")
+        .and_then(Material::with_depth_prepass)
+        .unwrap()
 }
 
 pub fn with_basic_deferred<'a, F>(f: F) -> Material
 where
     F: Fn(&mut VulkanPipelineRequirements),
 {
-    let device = get_device();
+    let samplers = [sampler_binding(BASIC_DEFERRED_NORMAL_MAP_SET)];
+
+    let mut req = deferred_requirements("assets/shaders/basic_deferred.frag", &samplers);
+
+    f(&mut req);
 
-    let bindings = base_scene_bindings();
+    Material::from_requirements(&req, &samplers)
+        .unwrap()
+        .with_fallback_texture(BASIC_DEFERRED_NORMAL_MAP_SET, FLAT_NORMAL_TEXTURE)
+}
+
+/// Metallic-roughness material shaded with Cook-Torrance in the composition
+/// pass, the default material of the engine. Textures are bound to
+/// [`PBR_ALBEDO_SET`], [`PBR_OCCLUSION_ROUGHNESS_METALLIC_SET`] and
+/// [`BASIC_DEFERRED_NORMAL_MAP_SET`]
+pub fn pbr_deferred() -> Material {
+    let samplers = [
+        BASIC_DEFERRED_NORMAL_MAP_SET,
+        PBR_ALBEDO_SET,
+        PBR_OCCLUSION_ROUGHNESS_METALLIC_SET,
+    ]
+    .map(sampler_binding);
+
+    let req = deferred_requirements("assets/shaders/pbr_deferred.frag", &samplers);
+
+    Material::from_requirements(&req, &samplers)
+        .unwrap()
+        .with_fallback_texture(BASIC_DEFERRED_NORMAL_MAP_SET, FLAT_NORMAL_TEXTURE)
+        .with_fallback_texture(PBR_OCCLUSION_ROUGHNESS_METALLIC_SET, PBR_DEFAULT_ORM)
+        .with_storage_fallback("assets/shaders/basic_deferred_ssbo.vert")
+        .and_then(Material::with_depth_prepass)
+        .unwrap()
+}
 
-    let mut req = VulkanPipelineRequirements {
+fn sampler_binding(set: u32) -> MaterialBinding {
+    MaterialBinding {
+        set,
+        binding: 0,
+        descriptor_count: 1,
+        descriptor_type: vk::DescriptorType::COMBINED_IMAGE_SAMPLER,
+        binding_rate: MaterialBindingRate::Single,
+        shader_stage_flags: ShaderStageFlags::FRAGMENT,
+    }
+}
+
+/// Requirements of a material drawn into the G-buffer by `basic_deferred.vert`
+fn deferred_requirements(
+    fragment_shader: &str,
+    samplers: &[MaterialBinding],
+) -> VulkanPipelineRequirements<'static> {
+    let mut bindings = base_scene_bindings();
+    bindings.extend_from_slice(samplers);
+
+    VulkanPipelineRequirements {
         features: VulkanPipelineFeatures {
             flags: PipelineFeatureFlags::DEPTH_TEST | PipelineFeatureFlags::DEPTH_WRITE,
             culling: CullMode::Back,
@@ -41,7 +116,7 @@ where
                 stage: ShaderStage::Vertex,
             },
             ShaderStageDefinition {
-                path: String::from("assets/shaders/basic_deferred.frag"),
+                path: String::from(fragment_shader),
                 stage: ShaderStage::Fragment,
             },
         ],
@@ -49,24 +124,13 @@ where
         vertex_bindings: Vertex::bindings().to_vec(),
         vertex_attributes: Vertex::attributes().to_vec(),
         samples: vk::SampleCountFlags::TYPE_1,
-        color_attachment_formats: vec![COLOR_FORMAT, COLOR_FORMAT, COLOR_FORMAT],
-        input_attachment_indices: vec![
-            vk::ATTACHMENT_UNUSED,
-            vk::ATTACHMENT_UNUSED,
-            vk::ATTACHMENT_UNUSED,
-        ],
+        color_attachment_formats: vec![COLOR_FORMAT; GBUFFER_ATTACHMENT_COUNT],
+        input_attachment_indices: vec![vk::ATTACHMENT_UNUSED; GBUFFER_ATTACHMENT_COUNT],
         depth_attachment_format: DEPTH_FORMAT,
-    };
-
-    f(&mut req);
-
-    let pipeline = VulkanPipeline::from_requirements(&req, None, device).unwrap();
-    Material::new(pipeline, &[])
+    }
 }
 
 pub fn basic_composition() -> Material {
-    let device = get_device();
-
     let req = VulkanPipelineRequirements {
         features: Default::default(),
         bindings: vec![
@@ -94,6 +158,32 @@ pub fn basic_composition() -> Material {
                 descriptor_type: vk::DescriptorType::INPUT_ATTACHMENT,
                 shader_stage_flags: ShaderStageFlags::FRAGMENT,
             },
+            MaterialBinding {
+                binding: 3,
+                set: 0,
+                binding_rate: MaterialBindingRate::PerFrame,
+                descriptor_count: 1,
+                descriptor_type: vk::DescriptorType::INPUT_ATTACHMENT,
+                shader_stage_flags: ShaderStageFlags::FRAGMENT,
+            },
+            // Scene uniform, for the camera position
+            MaterialBinding {
+                binding: 0,
+                set: 1,
+                binding_rate: MaterialBindingRate::PerFrame,
+                descriptor_count: 1,
+                descriptor_type: vk::DescriptorType::UNIFORM_BUFFER,
+                shader_stage_flags: ShaderStageFlags::FRAGMENT,
+            },
+            // Scene lights
+            MaterialBinding {
+                binding: 0,
+                set: 2,
+                binding_rate: MaterialBindingRate::PerFrame,
+                descriptor_count: 1,
+                descriptor_type: vk::DescriptorType::UNIFORM_BUFFER,
+                shader_stage_flags: ShaderStageFlags::FRAGMENT,
+            },
         ],
         stage_definitions: vec![
             ShaderStageDefinition {
@@ -109,12 +199,226 @@ pub fn basic_composition() -> Material {
         vertex_bindings: Default::default(),
         vertex_attributes: Default::default(),
         samples: vk::SampleCountFlags::TYPE_1,
-        color_attachment_formats: vec![COLOR_FORMAT, COLOR_FORMAT, COLOR_FORMAT, COLOR_FORMAT],
-        input_attachment_indices: vec![0, 1, 2, vk::ATTACHMENT_UNUSED],
+        color_attachment_formats: vec![COLOR_FORMAT; COMPOSITION_INPUT_COUNT + 1],
+        input_attachment_indices: vec![0, 1, 2, 3, vk::ATTACHMENT_UNUSED],
+        depth_attachment_format: DEPTH_FORMAT,
+    };
+
+    Material::from_requirements(&req, &[]).unwrap()
+}
+
+/// Composition material drawing a fullscreen triangle pair with `fragment_shader`,
+/// assigned with [`SwapchainRenderTarget::with_composition_material`](crate::render_target::SwapchainRenderTarget::with_composition_material).
+///
+/// Bindings are reflected from the shader: input attachments of `set = 0` read the
+/// G-buffer attachment of their `input_attachment_index` (color, normals,
+/// position-depth, material), `set = 1` is the scene uniform and `set = 2` the lights
+pub fn composition_material(fragment_shader: impl Into<String>) -> PipelineResult<Material> {
+    let req = VulkanPipelineRequirements {
+        features: Default::default(),
+        bindings: Vec::new(),
+        stage_definitions: vec![
+            ShaderStageDefinition {
+                path: String::from("assets/shaders/basic_composition.vert"),
+                stage: ShaderStage::Vertex,
+            },
+            ShaderStageDefinition {
+                path: fragment_shader.into(),
+                stage: ShaderStage::Fragment,
+            },
+        ],
+        base_pipeline: None,
+        vertex_bindings: Default::default(),
+        vertex_attributes: Default::default(),
+        samples: vk::SampleCountFlags::TYPE_1,
+        color_attachment_formats: vec![COLOR_FORMAT; COMPOSITION_INPUT_COUNT + 1],
+        input_attachment_indices: Vec::new(),
         depth_attachment_format: DEPTH_FORMAT,
     };
 
-    let pipeline = VulkanPipeline::from_requirements(&req, None, device).unwrap();
+    Material::from_requirements(&req, &[])
+}
+
+/// Blends the composed frame with the reprojected history for
+/// [`Antialiasing::Taa`](crate::antialiasing::Antialiasing::Taa). Samples the
+/// composed frame at `set = 0`, the history at `set = 1` and the velocity at `set = 2`
+pub fn taa_resolve() -> Material {
+    let req = VulkanPipelineRequirements {
+        features: Default::default(),
+        bindings: (0..3).map(sampler_binding).collect(),
+        stage_definitions: vec![
+            ShaderStageDefinition {
+                path: String::from("assets/shaders/basic_composition.vert"),
+                stage: ShaderStage::Vertex,
+            },
+            ShaderStageDefinition {
+                path: String::from("assets/shaders/taa_resolve.frag"),
+                stage: ShaderStage::Fragment,
+            },
+        ],
+        base_pipeline: None,
+        vertex_bindings: Default::default(),
+        vertex_attributes: Default::default(),
+        samples: vk::SampleCountFlags::TYPE_1,
+        color_attachment_formats: vec![COLOR_FORMAT],
+        input_attachment_indices: vec![vk::ATTACHMENT_UNUSED],
+        depth_attachment_format: vk::Format::UNDEFINED,
+    };
+
+    Material::from_requirements(&req, &[]).unwrap()
+}
+
+/// Draws silhouettes of selected objects into the selection mask. Reads the
+/// `transform` of instance data of any layout from a storage buffer, the layout
+/// is passed in push constants
+pub fn selection_mask() -> Material {
+    let req = VulkanPipelineRequirements {
+        features: VulkanPipelineFeatures {
+            culling: CullMode::None,
+            polygon_mode: PolygonMode::Fill,
+            ..Default::default()
+        },
+        bindings: base_scene_bindings_with(vk::DescriptorType::STORAGE_BUFFER),
+        stage_definitions: vec![
+            ShaderStageDefinition {
+                path: String::from("assets/shaders/selection_mask.vert"),
+                stage: ShaderStage::Vertex,
+            },
+            ShaderStageDefinition {
+                path: String::from("assets/shaders/selection_mask.frag"),
+                stage: ShaderStage::Fragment,
+            },
+        ],
+        base_pipeline: None,
+        vertex_bindings: Vertex::bindings().to_vec(),
+        vertex_attributes: Vertex::attributes().to_vec(),
+        samples: vk::SampleCountFlags::TYPE_1,
+        color_attachment_formats: vec![COLOR_FORMAT],
+        input_attachment_indices: vec![vk::ATTACHMENT_UNUSED],
+        depth_attachment_format: vk::Format::UNDEFINED,
+    };
+
+    Material::from_requirements(&req, &[]).unwrap()
+}
+
+/// Blends an outline around the selection mask sampled at `set = 0` over the
+/// composed frame, the color and width are passed in push constants
+pub fn selection_outline() -> Material {
+    let req = VulkanPipelineRequirements {
+        features: VulkanPipelineFeatures {
+            flags: PipelineFeatureFlags::BLEND_COLOR,
+            ..Default::default()
+        },
+        bindings: vec![sampler_binding(0)],
+        stage_definitions: vec![
+            ShaderStageDefinition {
+                path: String::from("assets/shaders/basic_composition.vert"),
+                stage: ShaderStage::Vertex,
+            },
+            ShaderStageDefinition {
+                path: String::from("assets/shaders/selection_outline.frag"),
+                stage: ShaderStage::Fragment,
+            },
+        ],
+        base_pipeline: None,
+        vertex_bindings: Default::default(),
+        vertex_attributes: Default::default(),
+        samples: vk::SampleCountFlags::TYPE_1,
+        color_attachment_formats: vec![COLOR_FORMAT],
+        input_attachment_indices: vec![vk::ATTACHMENT_UNUSED],
+        depth_attachment_format: vk::Format::UNDEFINED,
+    };
+
+    Material::from_requirements(&req, &[]).unwrap()
+}
+
+/// Screen space quads of [`SpriteBatch`](crate::sprite::SpriteBatch)es blended
+/// over the composed frame. Sprites are read from a storage buffer at `set = 0`,
+/// their texture is sampled at `set = 1` and the viewport size is passed in push
+/// constants
+pub fn sprites() -> Material {
+    let bindings = vec![
+        MaterialBinding {
+            set: 0,
+            binding: 0,
+            descriptor_count: 1,
+            descriptor_type: vk::DescriptorType::STORAGE_BUFFER,
+            binding_rate: MaterialBindingRate::PerFrame,
+            shader_stage_flags: ShaderStageFlags::VERTEX,
+        },
+        MaterialBinding {
+            descriptor_count: MAX_SPRITE_TEXTURES as u32,
+            ..sampler_binding(1)
+        },
+    ];
+
+    let req = VulkanPipelineRequirements {
+        features: VulkanPipelineFeatures {
+            flags: PipelineFeatureFlags::BLEND_COLOR,
+            culling: CullMode::None,
+            polygon_mode: PolygonMode::Fill,
+            ..Default::default()
+        },
+        bindings,
+        stage_definitions: vec![
+            ShaderStageDefinition {
+                path: String::from("assets/shaders/sprite.vert"),
+                stage: ShaderStage::Vertex,
+            },
+            ShaderStageDefinition {
+                path: String::from("assets/shaders/sprite.frag"),
+                stage: ShaderStage::Fragment,
+            },
+        ],
+        base_pipeline: None,
+        vertex_bindings: Default::default(),
+        vertex_attributes: Default::default(),
+        samples: vk::SampleCountFlags::TYPE_1,
+        color_attachment_formats: vec![COLOR_FORMAT],
+        input_attachment_indices: vec![vk::ATTACHMENT_UNUSED],
+        depth_attachment_format: vk::Format::UNDEFINED,
+    };
+
+    Material::from_requirements(&req, &[]).unwrap()
+}
+
+/// Sky gradient for [`ClearMode::Skybox`](crate::submitter::ClearMode::Skybox),
+/// drawn as a full screen triangle without vertex input
+pub fn gradient_skybox() -> Material {
+    let bindings = vec![MaterialBinding {
+        set: 0,
+        binding: 0,
+        descriptor_count: 1,
+        descriptor_type: vk::DescriptorType::UNIFORM_BUFFER,
+        binding_rate: MaterialBindingRate::PerFrame,
+        shader_stage_flags: ShaderStageFlags::VERTEX,
+    }];
+
+    let req = VulkanPipelineRequirements {
+        features: VulkanPipelineFeatures {
+            culling: CullMode::None,
+            polygon_mode: PolygonMode::Fill,
+            ..Default::default()
+        },
+        bindings,
+        stage_definitions: vec![
+            ShaderStageDefinition {
+                path: String::from("assets/shaders/gradient_skybox.vert"),
+                stage: ShaderStage::Vertex,
+            },
+            ShaderStageDefinition {
+                path: String::from("assets/shaders/gradient_skybox.frag"),
+                stage: ShaderStage::Fragment,
+            },
+        ],
+        base_pipeline: None,
+        vertex_bindings: Default::default(),
+        vertex_attributes: Default::default(),
+        samples: vk::SampleCountFlags::TYPE_1,
+        color_attachment_formats: vec![COLOR_FORMAT; GBUFFER_ATTACHMENT_COUNT],
+        input_attachment_indices: vec![vk::ATTACHMENT_UNUSED; GBUFFER_ATTACHMENT_COUNT],
+        depth_attachment_format: DEPTH_FORMAT,
+    };
 
-    Material::new(pipeline, &[])
+    Material::from_requirements(&req, &req.bindings).unwrap()
 }
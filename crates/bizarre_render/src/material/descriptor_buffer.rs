@@ -8,6 +8,7 @@ use vma::Alloc;
 use crate::{
     buffer::GpuBuffer,
     image::VulkanImage,
+    memory_stats::{track_allocation, track_free, AllocationCategory},
     vulkan_context::{get_context, get_device, get_instance},
 };
 
@@ -31,8 +32,15 @@ macro_rules! trace_sleep {
 }
 
 impl DescriptorBuffer {
+    /// Buffer of uniform and storage buffer descriptors, every element is big
+    /// enough to hold either of them
     pub fn uniform_buffers(len: usize) -> Result<Self, vk::Result> {
-        Self::new(
+        let storage_descriptor_size = get_device()
+            .physical
+            .descriptor_buffer_props
+            .storage_buffer_descriptor_size;
+
+        Self::with_min_stride(
             len,
             &[vk::DescriptorSetLayoutBinding {
                 binding: 0,
@@ -41,6 +49,7 @@ impl DescriptorBuffer {
                 ..Default::default()
             }],
             vk::BufferUsageFlags::empty(),
+            storage_descriptor_size as vk::DeviceSize,
         )
     }
 
@@ -61,6 +70,16 @@ impl DescriptorBuffer {
         len: usize,
         bindings: &[vk::DescriptorSetLayoutBinding],
         additional_usage_flags: vk::BufferUsageFlags,
+    ) -> Result<Self, vk::Result> {
+        Self::with_min_stride(len, bindings, additional_usage_flags, 0)
+    }
+
+    /// Same as [`new`](Self::new), but every element takes at least `min_stride` bytes
+    pub fn with_min_stride(
+        len: usize,
+        bindings: &[vk::DescriptorSetLayoutBinding],
+        additional_usage_flags: vk::BufferUsageFlags,
+        min_stride: vk::DeviceSize,
     ) -> Result<Self, vk::Result> {
         let device = get_device();
 
@@ -81,7 +100,7 @@ impl DescriptorBuffer {
 
         let (element_stride, element_offset) = unsafe {
             let size = device_ext.get_descriptor_set_layout_size(layout);
-            let size = aligned_size(size, min_buffer_alignment);
+            let size = aligned_size(size.max(min_stride), min_buffer_alignment);
 
             let offset = device_ext.get_descriptor_set_layout_binding_offset(layout, 0);
 
@@ -109,6 +128,8 @@ impl DescriptorBuffer {
             )?
         };
 
+        track_allocation(AllocationCategory::Buffer, &allocation);
+
         Ok(Self {
             len,
             layout,
@@ -134,6 +155,41 @@ impl DescriptorBuffer {
         buffer_offset: vk::DeviceSize,
         buffer_range: vk::DeviceSize,
         descriptor_index: usize,
+    ) -> vk::DeviceSize {
+        self.set_buffer_unchecked(
+            vk::DescriptorType::UNIFORM_BUFFER,
+            buffer,
+            buffer_offset,
+            buffer_range,
+            descriptor_index,
+        )
+    }
+
+    /// Writes a storage buffer descriptor, the buffer must be created with
+    /// [`uniform_buffers`](Self::uniform_buffers) or have a stride big enough for it
+    pub unsafe fn set_storage_buffer_unchecked(
+        &mut self,
+        buffer: &GpuBuffer,
+        buffer_offset: vk::DeviceSize,
+        buffer_range: vk::DeviceSize,
+        descriptor_index: usize,
+    ) -> vk::DeviceSize {
+        self.set_buffer_unchecked(
+            vk::DescriptorType::STORAGE_BUFFER,
+            buffer,
+            buffer_offset,
+            buffer_range,
+            descriptor_index,
+        )
+    }
+
+    unsafe fn set_buffer_unchecked(
+        &mut self,
+        descriptor_type: vk::DescriptorType,
+        buffer: &GpuBuffer,
+        buffer_offset: vk::DeviceSize,
+        buffer_range: vk::DeviceSize,
+        descriptor_index: usize,
     ) -> vk::DeviceSize {
         let device = get_device();
 
@@ -144,24 +200,33 @@ impl DescriptorBuffer {
             .range(buffer_range)
             .format(vk::Format::UNDEFINED);
 
+        let props = &device.physical.descriptor_buffer_props;
+
+        let (data, descriptor_size) = match descriptor_type {
+            vk::DescriptorType::STORAGE_BUFFER => (
+                vk::DescriptorDataEXT {
+                    p_storage_buffer: &addr_info,
+                },
+                props.storage_buffer_descriptor_size,
+            ),
+            _ => (
+                vk::DescriptorDataEXT {
+                    p_uniform_buffer: &addr_info,
+                },
+                props.uniform_buffer_descriptor_size,
+            ),
+        };
+
         let descriptor_info = vk::DescriptorGetInfoEXT::default()
-            .ty(vk::DescriptorType::UNIFORM_BUFFER)
-            .data(vk::DescriptorDataEXT {
-                p_uniform_buffer: &addr_info,
-            });
+            .ty(descriptor_type)
+            .data(data);
 
         let offset = self.element_offset as usize + descriptor_index * self.element_stride as usize;
 
         let descriptor = unsafe {
             let ptr = self.map_ptr::<u8>().unwrap();
 
-            slice::from_raw_parts_mut(
-                ptr.add(offset),
-                device
-                    .physical
-                    .descriptor_buffer_props
-                    .uniform_buffer_descriptor_size,
-            )
+            slice::from_raw_parts_mut(ptr.add(offset), descriptor_size)
         };
 
         self.get_descriptor(&descriptor_info, descriptor);
@@ -239,6 +304,56 @@ impl DescriptorBuffer {
         offset as vk::DeviceSize
     }
 
+    /// Writes the descriptors of `textures` one after another, as an array bound at
+    /// `binding = 0` of a set starting at element `index`. Returns the offset of
+    /// the set, the array takes [`array_elements`](Self::array_elements) elements
+    pub unsafe fn set_texture_array_unchecked(
+        &mut self,
+        textures: &[(&VulkanImage, vk::Sampler)],
+        index: usize,
+    ) -> vk::DeviceSize {
+        let descriptor_size = get_device()
+            .physical
+            .descriptor_buffer_props
+            .combined_image_sampler_descriptor_size;
+
+        let offset = self.element_offset as usize + index * self.element_stride as usize;
+
+        let ptr = self.map_ptr::<u8>().unwrap();
+
+        for (i, (texture, sampler)) in textures.iter().enumerate() {
+            let image_info = vk::DescriptorImageInfo::default()
+                .image_layout(texture.image_layout)
+                .image_view(texture.image_view)
+                .sampler(*sampler);
+
+            let descriptor_info = vk::DescriptorGetInfoEXT::default()
+                .ty(vk::DescriptorType::COMBINED_IMAGE_SAMPLER)
+                .data(vk::DescriptorDataEXT {
+                    p_combined_image_sampler: &image_info,
+                });
+
+            let descriptor =
+                slice::from_raw_parts_mut(ptr.add(offset + i * descriptor_size), descriptor_size);
+
+            self.get_descriptor(&descriptor_info, descriptor);
+        }
+
+        self.unmap_ptr();
+
+        offset as vk::DeviceSize
+    }
+
+    /// Amount of elements an array of `len` texture descriptors takes
+    pub fn array_elements(&self, len: usize) -> usize {
+        let descriptor_size = get_device()
+            .physical
+            .descriptor_buffer_props
+            .combined_image_sampler_descriptor_size;
+
+        (len * descriptor_size).div_ceil(self.element_stride as usize)
+    }
+
     pub fn get_descriptor(
         &self,
         descriptor_info: &vk::DescriptorGetInfoEXT,
@@ -272,14 +387,29 @@ impl DescriptorBuffer {
     }
 }
 
-impl Drop for DescriptorBuffer {
-    fn drop(&mut self) {
+impl DescriptorBuffer {
+    pub fn destroy(&mut self) {
+        if self.buffer == vk::Buffer::null() {
+            return;
+        }
+
+        track_free(AllocationCategory::Buffer, &self.allocation);
+
         unsafe {
             get_device()
                 .allocator
                 .destroy_buffer(self.buffer, &mut self.allocation);
             get_device().destroy_descriptor_set_layout(self.layout, None);
         }
+
+        self.buffer = vk::Buffer::null();
+        self.layout = vk::DescriptorSetLayout::null();
+    }
+}
+
+impl Drop for DescriptorBuffer {
+    fn drop(&mut self) {
+        self.destroy()
     }
 }
 
@@ -2,18 +2,57 @@ use std::collections::BTreeMap;
 
 use ash::vk;
 
-use crate::{buffer::GpuBuffer, shader::ShaderStage};
+use crate::{
+    buffer::GpuBuffer, render_texture::RenderTextureHandle, shader::ShaderStage,
+    texture::TextureHandle,
+};
 
-use super::material_binding::{MaterialBinding, MaterialBindingSet};
+use super::{
+    material_binding::{MaterialBinding, MaterialBindingSet},
+    MaterialError, MaterialResult,
+};
 
 pub enum InstanceBinding {
     UniformBuffer(Option<GpuBuffer>),
+    StorageBuffer(Option<GpuBuffer>),
+    /// `COMBINED_IMAGE_SAMPLER` reading the output of a render target
+    RenderTexture(Option<RenderTextureHandle>),
+    /// `COMBINED_IMAGE_SAMPLER` reading an uploaded texture, samplers start with
+    /// `Texture(None)` which samples the fallback of the material
+    Texture(Option<TextureHandle>),
+    /// Array of `COMBINED_IMAGE_SAMPLER`s indexed in the shader, unset elements
+    /// sample the fallback of the material
+    TextureArray(Vec<Option<TextureHandle>>),
+}
+
+impl InstanceBinding {
+    pub fn descriptor_type(&self) -> vk::DescriptorType {
+        match self {
+            Self::UniformBuffer(_) => vk::DescriptorType::UNIFORM_BUFFER,
+            Self::StorageBuffer(_) => vk::DescriptorType::STORAGE_BUFFER,
+            Self::RenderTexture(_) | Self::Texture(_) | Self::TextureArray(_) => {
+                vk::DescriptorType::COMBINED_IMAGE_SAMPLER
+            }
+        }
+    }
+
+    pub fn descriptor_count(&self) -> u32 {
+        match self {
+            Self::TextureArray(textures) => textures.len() as u32,
+            _ => 1,
+        }
+    }
 }
 
 impl From<&MaterialBinding> for InstanceBinding {
     fn from(value: &MaterialBinding) -> Self {
         match value.descriptor_type {
             vk::DescriptorType::UNIFORM_BUFFER => Self::UniformBuffer(None),
+            vk::DescriptorType::STORAGE_BUFFER => Self::StorageBuffer(None),
+            vk::DescriptorType::COMBINED_IMAGE_SAMPLER if value.descriptor_count > 1 => {
+                Self::TextureArray(vec![None; value.descriptor_count as usize])
+            }
+            vk::DescriptorType::COMBINED_IMAGE_SAMPLER => Self::Texture(None),
             _ => panic!(
                 "InstanceBinding: unsupported descriptor type: `${:?}`",
                 value.descriptor_type
@@ -28,6 +67,8 @@ type SetIndexLocal = usize;
 pub struct MaterialInstanceBindingMap {
     min_set: usize,
     bindings: Vec<InstanceBinding>,
+    /// `(set, binding)` of every element of `bindings`
+    locations: Vec<(u32, u32)>,
     stage_map: BTreeMap<ShaderStage, Vec<Option<Vec<usize>>>>,
     type_map: BTreeMap<vk::DescriptorType, Vec<(SetIndexLocal, Vec<usize>)>>,
 }
@@ -47,6 +88,61 @@ impl MaterialInstanceBindingMap {
         self.bindings[index] = object;
     }
 
+    /// Replaces the object bound at `binding` of `set`, it has to be of the same
+    /// descriptor type as the material binding
+    pub fn set_binding_at(
+        &mut self,
+        set: u32,
+        binding: u32,
+        object: InstanceBinding,
+    ) -> MaterialResult<()> {
+        let index = self.index_at(set, binding)?;
+
+        let actual = self.bindings[index].descriptor_type();
+        let provided = object.descriptor_type();
+
+        if actual != provided {
+            return Err(MaterialError::WrongBindingObjectType {
+                index,
+                provided,
+                actual,
+            });
+        }
+
+        let actual = self.bindings[index].descriptor_count();
+        let provided = object.descriptor_count();
+
+        if actual != provided {
+            return Err(MaterialError::WrongDescriptorCount {
+                set,
+                binding,
+                provided,
+                actual,
+            });
+        }
+
+        self.bindings[index] = object;
+
+        Ok(())
+    }
+
+    pub fn binding_at_mut(
+        &mut self,
+        set: u32,
+        binding: u32,
+    ) -> MaterialResult<&mut InstanceBinding> {
+        let index = self.index_at(set, binding)?;
+
+        Ok(&mut self.bindings[index])
+    }
+
+    fn index_at(&self, set: u32, binding: u32) -> MaterialResult<usize> {
+        self.locations
+            .iter()
+            .position(|location| *location == (set, binding))
+            .ok_or(MaterialError::NoSuchBinding { set, binding })
+    }
+
     pub fn sets_of_type(
         &self,
         descriptor_type: vk::DescriptorType,
@@ -82,6 +178,12 @@ impl From<&MaterialBindingSet> for MaterialInstanceBindingMap {
             .map(InstanceBinding::from)
             .collect::<Vec<_>>();
 
+        let locations = value
+            .bindings
+            .iter()
+            .map(|binding| (binding.set, binding.binding))
+            .collect();
+
         let (min_set, max_set) = value
             .bindings
             .iter()
@@ -94,7 +196,7 @@ impl From<&MaterialBindingSet> for MaterialInstanceBindingMap {
         let mut type_map = BTreeMap::new();
         let mut stage_map = BTreeMap::new();
 
-        let sets_count = max_set - min_set;
+        let sets_count = max_set - min_set + 1;
 
         for (i, binding) in enumerated_bindings {
             binding
@@ -104,7 +206,7 @@ impl From<&MaterialBindingSet> for MaterialInstanceBindingMap {
                     let stage = ShaderStage::from(stage_flag);
                     let stage_sets = stage_map.entry(stage).or_insert(vec![None; sets_count]);
                     let set = stage_sets
-                        .get_mut(binding.set as usize)
+                        .get_mut(binding.set as usize - min_set)
                         .unwrap()
                         .get_or_insert(Vec::new());
 
@@ -131,6 +233,7 @@ impl From<&MaterialBindingSet> for MaterialInstanceBindingMap {
         Self {
             min_set,
             bindings,
+            locations,
             stage_map,
             type_map,
         }
@@ -29,7 +29,16 @@ impl From<&MaterialBinding> for vk::DescriptorSetLayoutBinding<'_> {
     }
 }
 
+/// Sets below are bound by the renderer: the scene uniform and the instance data
+pub const FIRST_INSTANCE_SET: u32 = 2;
+
 pub fn base_scene_bindings() -> Vec<MaterialBinding> {
+    base_scene_bindings_with(vk::DescriptorType::UNIFORM_BUFFER)
+}
+
+/// Scene bindings with instance data (`set = 1`) bound as `instance_data_type`,
+/// either `UNIFORM_BUFFER` or `STORAGE_BUFFER`
+pub fn base_scene_bindings_with(instance_data_type: vk::DescriptorType) -> Vec<MaterialBinding> {
     [
         MaterialBinding {
             set: 0,
@@ -43,7 +52,7 @@ pub fn base_scene_bindings() -> Vec<MaterialBinding> {
             set: 1,
             binding: 0,
             descriptor_count: 1,
-            descriptor_type: vk::DescriptorType::UNIFORM_BUFFER,
+            descriptor_type: instance_data_type,
             binding_rate: MaterialBindingRate::PerFrame,
             shader_stage_flags: ShaderStageFlags::VERTEX,
         },
@@ -1,8 +1,12 @@
 use ash::vk;
 use bizarre_core::Handle;
 
+use crate::{buffer::GpuBuffer, render_texture::RenderTextureHandle, texture::TextureHandle};
+
 use super::{
-    instance_binding::MaterialInstanceBindingMap, Material, MaterialHandle, MaterialResult,
+    instance_binding::{InstanceBinding, MaterialInstanceBindingMap},
+    material_binding::FIRST_INSTANCE_SET,
+    Material, MaterialError, MaterialHandle, MaterialResult,
 };
 
 pub type MaterialInstanceHandle = Handle<MaterialInstance>;
@@ -28,4 +32,128 @@ impl MaterialInstance {
     pub fn material_handle(&self) -> MaterialHandle {
         self.material_handle
     }
+
+    /// Binds a render texture to a `sampler2D` of the material. Every sampler
+    /// has to be alone in its set at `binding = 0`, batches with a `None` render
+    /// texture aren't drawn
+    pub fn set_render_texture(
+        &mut self,
+        set: u32,
+        texture: Option<RenderTextureHandle>,
+    ) -> MaterialResult<()> {
+        self.bind_map
+            .set_binding_at(set, 0, InstanceBinding::RenderTexture(texture))
+    }
+
+    /// Binds a texture to a `sampler2D` of the material, at `binding = 0` of
+    /// `set` like [`set_render_texture`](Self::set_render_texture). `None` samples
+    /// the [fallback](Material::with_fallback_texture) of the material
+    pub fn set_texture(&mut self, set: u32, texture: Option<TextureHandle>) -> MaterialResult<()> {
+        self.bind_map
+            .set_binding_at(set, 0, InstanceBinding::Texture(texture))
+    }
+
+    /// Binds `textures` to a `sampler2D` array of the material, at `binding = 0`
+    /// of `set`. Elements past the end of `textures` are unset and sample the
+    /// fallback of the material like [`set_texture`](Self::set_texture) with `None`
+    pub fn set_texture_array(
+        &mut self,
+        set: u32,
+        textures: impl IntoIterator<Item = Option<TextureHandle>>,
+    ) -> MaterialResult<()> {
+        let textures = textures.into_iter().collect::<Vec<_>>();
+        let array = self.texture_array_mut(set)?;
+        let len = array.len();
+
+        if textures.len() > len {
+            return Err(MaterialError::BindingOutOfBounds {
+                len,
+                index: textures.len() - 1,
+            });
+        }
+
+        array.fill(None);
+        array[..textures.len()].copy_from_slice(&textures);
+
+        Ok(())
+    }
+
+    /// Binds a texture to the element `index` of a `sampler2D` array, see
+    /// [`set_texture_array`](Self::set_texture_array)
+    pub fn set_array_texture(
+        &mut self,
+        set: u32,
+        index: usize,
+        texture: Option<TextureHandle>,
+    ) -> MaterialResult<()> {
+        let array = self.texture_array_mut(set)?;
+        let len = array.len();
+
+        *array
+            .get_mut(index)
+            .ok_or(MaterialError::BindingOutOfBounds { len, index })? = texture;
+
+        Ok(())
+    }
+
+    fn texture_array_mut(&mut self, set: u32) -> MaterialResult<&mut Vec<Option<TextureHandle>>> {
+        match self.bind_map.binding_at_mut(set, 0)? {
+            InstanceBinding::TextureArray(textures) => Ok(textures),
+            _ => Err(MaterialError::NotATextureArray { set, binding: 0 }),
+        }
+    }
+
+    /// Binds a buffer to a `uniform` block of the material, alone in its `set` at
+    /// `binding = 0`. Batches with a `None` buffer aren't drawn
+    pub fn set_uniform_buffer(
+        &mut self,
+        set: u32,
+        buffer: Option<GpuBuffer>,
+    ) -> MaterialResult<()> {
+        self.bind_map
+            .set_binding_at(set, 0, InstanceBinding::UniformBuffer(buffer))
+    }
+
+    /// Binds a buffer to a `buffer` block of the material like
+    /// [`set_uniform_buffer`](Self::set_uniform_buffer)
+    pub fn set_storage_buffer(
+        &mut self,
+        set: u32,
+        buffer: Option<GpuBuffer>,
+    ) -> MaterialResult<()> {
+        self.bind_map
+            .set_binding_at(set, 0, InstanceBinding::StorageBuffer(buffer))
+    }
+
+    /// Textures and render textures bound to the instance with their sets, in set order
+    pub(crate) fn textures(&self) -> Vec<(u32, &InstanceBinding)> {
+        self.first_bindings_of_type(vk::DescriptorType::COMBINED_IMAGE_SAMPLER)
+    }
+
+    /// Uniform and storage buffers bound to the instance with their sets, in set
+    /// order. The scene sets bound by the renderer are left out
+    pub(crate) fn buffers(&self) -> Vec<(u32, &InstanceBinding)> {
+        let mut buffers = [
+            vk::DescriptorType::UNIFORM_BUFFER,
+            vk::DescriptorType::STORAGE_BUFFER,
+        ]
+        .into_iter()
+        .flat_map(|descriptor_type| self.first_bindings_of_type(descriptor_type))
+        .filter(|(set, _)| *set >= FIRST_INSTANCE_SET)
+        .collect::<Vec<_>>();
+
+        buffers.sort_by_key(|(set, _)| *set);
+        buffers
+    }
+
+    fn first_bindings_of_type(
+        &self,
+        descriptor_type: vk::DescriptorType,
+    ) -> Vec<(u32, &InstanceBinding)> {
+        self.bind_map
+            .sets_of_type(descriptor_type)
+            .into_iter()
+            .filter_map(|(set, bindings)| Some((set as u32, *bindings.first()?)))
+            .collect()
+    }
 }
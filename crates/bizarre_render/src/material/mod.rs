@@ -1,9 +1,13 @@
 use ash::vk;
 use bizarre_core::Handle;
+use bizarre_log::core_warn;
 use material_binding::{MaterialBinding, MaterialBindingSet};
-use pipeline::VulkanPipeline;
+use pipeline::{PipelineResult, ShaderStageDefinition, VulkanPipeline, VulkanPipelineRequirements};
+use pipeline_features::PipelineFeatureFlags;
 use thiserror::Error;
 
+use crate::{device::LogicalDevice, shader::ShaderStage, vulkan_context::get_device};
+
 pub mod builtin;
 pub mod descriptor_buffer;
 pub mod instance_binding;
@@ -11,6 +15,7 @@ pub mod material_binding;
 pub mod material_instance;
 pub mod pipeline;
 pub mod pipeline_features;
+pub mod pipeline_layout_cache;
 
 #[derive(Debug, Error)]
 pub enum MaterialError {
@@ -24,6 +29,17 @@ pub enum MaterialError {
         provided: vk::DescriptorType,
         actual: vk::DescriptorType,
     },
+    #[error("Material has no binding {binding} in set {set}")]
+    NoSuchBinding { set: u32, binding: u32 },
+    #[error("Trying to bind {provided} descriptors to binding {binding} in set {set} while it has {actual}")]
+    WrongDescriptorCount {
+        set: u32,
+        binding: u32,
+        provided: u32,
+        actual: u32,
+    },
+    #[error("Binding {binding} in set {set} is not a texture array")]
+    NotATextureArray { set: u32, binding: u32 },
     #[error("Incomplete bindning set")]
     IncompleteBindingSet,
 }
@@ -32,9 +48,71 @@ pub type MaterialResult<T> = Result<T, MaterialError>;
 
 pub type MaterialHandle = Handle<Material>;
 
+/// Size of the instance data arrays the built-in shaders declare in uniform buffers
+pub const MAX_UNIFORM_INSTANCE_DATA_RANGE: u32 = 64 * 1024;
+
+/// Fallback of samplers without a texture, see [`Material::with_fallback_texture`]
+pub const WHITE_TEXTURE: [u8; 4] = [255, 255, 255, 255];
+/// Tangent-space normal map keeping the surface normal
+pub const FLAT_NORMAL_TEXTURE: [u8; 4] = [128, 128, 255, 255];
+
 pub struct Material {
     pipeline: VulkanPipeline,
     bindings: MaterialBindingSet,
+    /// Description the pipeline was built from, used to rebuild it after a device loss
+    requirements: Option<VulkanPipelineRequirements<'static>>,
+    /// How the pipeline expects scene instance data (`set = 1, binding = 0`)
+    instance_data_type: vk::DescriptorType,
+    /// Pipeline reading instance data from a storage buffer, used when a batch
+    /// doesn't fit into a uniform buffer
+    storage_fallback: Option<(VulkanPipeline, VulkanPipelineRequirements<'static>)>,
+    /// Pre-pass pipelines of the main pipeline, set by [`with_depth_prepass`](Self::with_depth_prepass)
+    depth_prepass: Option<DepthPrepassPipelines>,
+    /// Pre-pass pipelines of the storage fallback
+    storage_depth_prepass: Option<DepthPrepassPipelines>,
+    /// RGBA color sampled by samplers of a set without a texture bound
+    fallback_textures: Vec<(u32, [u8; 4])>,
+}
+
+/// Pipelines drawing a material into a render target with a depth pre-pass
+pub(crate) struct DepthPrepassPipelines {
+    /// Writes depth only, without color attachments and a fragment stage
+    pub depth_only: VulkanPipeline,
+    /// The material pipeline testing for `EQUAL` depth without writing it
+    pub depth_equal: VulkanPipeline,
+}
+
+impl DepthPrepassPipelines {
+    fn new(requirements: &VulkanPipelineRequirements) -> PipelineResult<Self> {
+        let device = get_device();
+        let flags = requirements.features.flags;
+
+        let mut depth_only = requirements.clone();
+        depth_only.features.flags = flags
+            .difference(PipelineFeatureFlags::DEPTH_MASK | PipelineFeatureFlags::BLEND_MASK)
+            | PipelineFeatureFlags::DEPTH_TEST
+            | PipelineFeatureFlags::DEPTH_WRITE;
+        depth_only
+            .stage_definitions
+            .retain(|stage| stage.stage == ShaderStage::Vertex);
+        depth_only.color_attachment_formats.clear();
+        depth_only.input_attachment_indices.clear();
+
+        let mut depth_equal = requirements.clone();
+        depth_equal.features.flags = flags.difference(PipelineFeatureFlags::DEPTH_WRITE)
+            | PipelineFeatureFlags::DEPTH_TEST
+            | PipelineFeatureFlags::DEPTH_EQUAL;
+
+        Ok(Self {
+            depth_only: VulkanPipeline::from_requirements(&depth_only, None, device)?,
+            depth_equal: VulkanPipeline::from_requirements(&depth_equal, None, device)?,
+        })
+    }
+
+    fn destroy(&mut self, device: &LogicalDevice) {
+        self.depth_only.destroy(device);
+        self.depth_equal.destroy(device);
+    }
 }
 
 pub struct MaterialCreateInfo {}
@@ -43,10 +121,209 @@ impl Material {
     pub fn new(pipeline: VulkanPipeline, bindings: &[MaterialBinding]) -> Self {
         let bindings = MaterialBindingSet::from(bindings.to_vec());
 
-        Self { pipeline, bindings }
+        Self {
+            pipeline,
+            bindings,
+            requirements: None,
+            instance_data_type: vk::DescriptorType::UNIFORM_BUFFER,
+            storage_fallback: None,
+            depth_prepass: None,
+            storage_depth_prepass: None,
+            fallback_textures: Vec::new(),
+        }
+    }
+
+    /// Builds the pipeline from `requirements` and keeps a copy of them, so the
+    /// material can be restored with [`restore`](Self::restore)
+    pub fn from_requirements(
+        requirements: &VulkanPipelineRequirements,
+        bindings: &[MaterialBinding],
+    ) -> PipelineResult<Self> {
+        let pipeline = VulkanPipeline::from_requirements(requirements, None, get_device())?;
+
+        let instance_data_type = instance_data_binding(&requirements.bindings)
+            .map(|binding| binding.descriptor_type)
+            .unwrap_or(vk::DescriptorType::UNIFORM_BUFFER);
+
+        Ok(Self {
+            requirements: Some(VulkanPipelineRequirements {
+                base_pipeline: None,
+                ..requirements.clone()
+            }),
+            instance_data_type,
+            ..Self::new(pipeline, bindings)
+        })
+    }
+
+    /// Adds a pipeline for batches with more instance data than a uniform buffer
+    /// can hold. It's built from the material requirements with instance data
+    /// bound as a storage buffer and `vertex_shader` as the vertex stage
+    pub fn with_storage_fallback(
+        mut self,
+        vertex_shader: impl Into<String>,
+    ) -> PipelineResult<Self> {
+        let Some(requirements) = &self.requirements else {
+            core_warn!("Material::with_storage_fallback: material has no pipeline description");
+            return Ok(self);
+        };
+
+        let mut requirements = requirements.clone();
+
+        if let Some(binding) = instance_data_binding_mut(&mut requirements.bindings) {
+            binding.descriptor_type = vk::DescriptorType::STORAGE_BUFFER;
+        }
+
+        requirements
+            .stage_definitions
+            .retain(|stage| stage.stage != ShaderStage::Vertex);
+        requirements.stage_definitions.push(ShaderStageDefinition {
+            path: vertex_shader.into(),
+            stage: ShaderStage::Vertex,
+        });
+
+        let pipeline = VulkanPipeline::from_requirements(&requirements, None, get_device())?;
+
+        if self.depth_prepass.is_some() {
+            self.storage_depth_prepass = Some(DepthPrepassPipelines::new(&requirements)?);
+        }
+
+        self.storage_fallback = Some((pipeline, requirements));
+
+        Ok(self)
+    }
+
+    /// Adds pipelines for render targets with a depth pre-pass: a depth-only one
+    /// built from the vertex stage and one drawing with an `EQUAL` depth test.
+    /// Without them the material is drawn with its own pipeline in the main pass
+    pub fn with_depth_prepass(mut self) -> PipelineResult<Self> {
+        let Some(requirements) = &self.requirements else {
+            core_warn!("Material::with_depth_prepass: material has no pipeline description");
+            return Ok(self);
+        };
+
+        self.depth_prepass = Some(DepthPrepassPipelines::new(requirements)?);
+
+        if let Some((_, requirements)) = &self.storage_fallback {
+            self.storage_depth_prepass = Some(DepthPrepassPipelines::new(requirements)?);
+        }
+
+        Ok(self)
+    }
+
+    /// Samplers of `set` read `rgba` while no texture is bound to them, instead of white
+    pub fn with_fallback_texture(mut self, set: u32, rgba: [u8; 4]) -> Self {
+        self.fallback_textures
+            .retain(|(fallback_set, _)| *fallback_set != set);
+        self.fallback_textures.push((set, rgba));
+        self
+    }
+
+    pub fn fallback_texture(&self, set: u32) -> [u8; 4] {
+        self.fallback_textures
+            .iter()
+            .find(|(fallback_set, _)| *fallback_set == set)
+            .map(|(_, rgba)| *rgba)
+            .unwrap_or(WHITE_TEXTURE)
+    }
+
+    pub fn has_depth_prepass(&self) -> bool {
+        self.depth_prepass.is_some()
+    }
+
+    /// Pipeline able to draw a batch with `instance_data_range` bytes of instance
+    /// data and the descriptor type it expects the instance data in
+    pub(crate) fn pipeline_for(
+        &self,
+        instance_data_range: vk::DeviceSize,
+    ) -> Option<(&VulkanPipeline, vk::DescriptorType)> {
+        if !self.needs_storage_fallback(instance_data_range) {
+            return Some((&self.pipeline, self.instance_data_type));
+        }
+
+        self.storage_fallback
+            .as_ref()
+            .map(|(pipeline, _)| (pipeline, vk::DescriptorType::STORAGE_BUFFER))
+    }
+
+    /// Pre-pass counterparts of the pipeline returned by [`pipeline_for`](Self::pipeline_for)
+    pub(crate) fn depth_prepass_for(
+        &self,
+        instance_data_range: vk::DeviceSize,
+    ) -> Option<&DepthPrepassPipelines> {
+        if self.needs_storage_fallback(instance_data_range) {
+            self.storage_depth_prepass.as_ref()
+        } else {
+            self.depth_prepass.as_ref()
+        }
+    }
+
+    fn needs_storage_fallback(&self, instance_data_range: vk::DeviceSize) -> bool {
+        let max_uniform_range = get_device()
+            .physical
+            .device_props
+            .limits
+            .max_uniform_buffer_range
+            .min(MAX_UNIFORM_INSTANCE_DATA_RANGE) as vk::DeviceSize;
+
+        self.instance_data_type == vk::DescriptorType::UNIFORM_BUFFER
+            && instance_data_range > max_uniform_range
+    }
+
+    /// Destroys the pipeline, the material can't be used until it's restored
+    pub(crate) fn release(&mut self, device: &LogicalDevice) {
+        self.pipeline.destroy(device);
+
+        if let Some((pipeline, _)) = &mut self.storage_fallback {
+            pipeline.destroy(device);
+        }
+
+        [&mut self.depth_prepass, &mut self.storage_depth_prepass]
+            .into_iter()
+            .flatten()
+            .for_each(|prepass| prepass.destroy(device));
+    }
+
+    /// Rebuilds the pipeline on the current device. Returns `false` if the
+    /// material was created from a raw pipeline and can't be rebuilt
+    pub(crate) fn restore(&mut self) -> PipelineResult<bool> {
+        let Some(requirements) = &self.requirements else {
+            return Ok(false);
+        };
+
+        self.pipeline = VulkanPipeline::from_requirements(requirements, None, get_device())?;
+
+        if let Some((pipeline, requirements)) = &mut self.storage_fallback {
+            *pipeline = VulkanPipeline::from_requirements(requirements, None, get_device())?;
+        }
+
+        if let Some(prepass) = &mut self.depth_prepass {
+            *prepass = DepthPrepassPipelines::new(requirements)?;
+        }
+
+        if let Some(((_, requirements), prepass)) = self
+            .storage_fallback
+            .as_ref()
+            .zip(self.storage_depth_prepass.as_mut())
+        {
+            *prepass = DepthPrepassPipelines::new(requirements)?;
+        }
+
+        Ok(true)
     }
 
     pub(crate) fn pipeline(&self) -> &VulkanPipeline {
         &self.pipeline
     }
 }
+
+fn instance_data_binding(bindings: &[MaterialBinding]) -> Option<&MaterialBinding> {
+    bindings
+        .iter()
+        .find(|binding| binding.set == 1 && binding.binding == 0)
+}
+
+fn instance_data_binding_mut(bindings: &mut [MaterialBinding]) -> Option<&mut MaterialBinding> {
+    bindings
+        .iter_mut()
+        .find(|binding| binding.set == 1 && binding.binding == 0)
+}
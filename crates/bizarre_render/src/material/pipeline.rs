@@ -3,17 +3,18 @@ use std::{ffi::CStr, fs::File, io::Read, ops::Deref, path::Path};
 
 use ash::vk;
 use bizarre_core::Handle;
-use bizarre_log::core_warn;
+use bizarre_log::{core_info, core_warn};
 use thiserror::Error;
 
 use crate::{
     device::LogicalDevice,
     shader::{load_shader, ShaderError, ShaderStage},
+    shader_reflection::{PipelineReflection, ReflectedBinding, ReflectionError, ShaderReflection},
 };
 
 use super::{
-    material_binding::{bindings_into_layouts, MaterialBinding},
-    pipeline_features::{PipelineFeatureFlags, VulkanPipelineFeatures},
+    material_binding::{MaterialBinding, MaterialBindingSet},
+    pipeline_features::{DepthBias, FrontFace, PipelineFeatureFlags, VulkanPipelineFeatures},
 };
 
 #[derive(Error, Debug)]
@@ -22,6 +23,8 @@ pub enum PipelineError {
     ShaderError(#[from] ShaderError),
     #[error(transparent)]
     VkError(#[from] vk::Result),
+    #[error("Pipeline doesn't match its shaders: {0}")]
+    ReflectionError(#[from] ReflectionError),
 }
 
 pub type PipelineResult<T> = Result<T, PipelineError>;
@@ -48,19 +51,70 @@ pub struct VulkanPipelineRequirements<'a> {
     pub depth_attachment_format: vk::Format,
 }
 
+impl VulkanPipelineRequirements<'_> {
+    pub fn with_line_width(mut self, line_width: f32) -> Self {
+        self.features.line_width = line_width;
+        self
+    }
+
+    pub fn with_depth_bias(mut self, depth_bias: DepthBias) -> Self {
+        self.features.depth_bias = Some(depth_bias);
+        self
+    }
+
+    pub fn with_front_face(mut self, front_face: FrontFace) -> Self {
+        self.features.front_face = front_face;
+        self
+    }
+
+    pub fn with_primitive_restart(mut self) -> Self {
+        self.features.flags |= PipelineFeatureFlags::PRIMITIVE_RESTART;
+        self
+    }
+
+    pub fn with_conservative_rasterization(mut self) -> Self {
+        self.features.flags |= PipelineFeatureFlags::CONSERVATIVE_RASTER;
+        self
+    }
+}
+
 #[derive(Debug)]
 pub struct VulkanPipeline {
     pub pipeline: vk::Pipeline,
     pub layout: vk::PipelineLayout,
     pub set_layouts: Vec<vk::DescriptorSetLayout>,
+    /// Input attachments reflected from the shaders, with their attachment indices
+    pub input_attachments: Vec<ReflectedBinding>,
 }
 
 impl VulkanPipeline {
+    /// Builds the pipeline described by `requirements`.
+    ///
+    /// The shaders are reflected first: bindings, vertex input and input attachment
+    /// indices left empty are derived from them, push constant ranges always are,
+    /// and the provided ones are validated against the shaders
     pub fn from_requirements(
         requirements: &VulkanPipelineRequirements,
         base_pipeline: Option<vk::Pipeline>,
         device: &LogicalDevice,
     ) -> PipelineResult<Self> {
+        let stage_codes = requirements
+            .stage_definitions
+            .iter()
+            .map(|ShaderStageDefinition { path, stage }| {
+                load_shader(Path::new(path), *stage).map(|code| (code, *stage))
+            })
+            .collect::<Result<Vec<_>, ShaderError>>()?;
+
+        let reflection = PipelineReflection::new(
+            &stage_codes
+                .iter()
+                .map(|(code, stage)| ShaderReflection::from_spirv(code, *stage))
+                .collect::<Result<Vec<_>, _>>()?,
+        );
+
+        let requirements = &reflection.complete(requirements)?;
+
         let dynamic_states = [vk::DynamicState::VIEWPORT, vk::DynamicState::SCISSOR];
 
         let dynamic_state_info =
@@ -73,9 +127,13 @@ impl VulkanPipeline {
             .vertex_binding_descriptions(&vertex_binding_descriptions)
             .vertex_attribute_descriptions(&vertex_input_attributes);
 
+        let feature_flags = requirements.features.flags;
+
         let input_assembly_info = vk::PipelineInputAssemblyStateCreateInfo::default()
             .topology(requirements.features.primitive_topology.into())
-            .primitive_restart_enable(false);
+            .primitive_restart_enable(
+                feature_flags.contains(PipelineFeatureFlags::PRIMITIVE_RESTART),
+            );
 
         let scissors = [vk::Rect2D::default()];
         let viewports = [vk::Viewport::default()];
@@ -84,15 +142,48 @@ impl VulkanPipeline {
             .viewports(&viewports)
             .scissors(&scissors);
 
-        let rasterizer_info = vk::PipelineRasterizationStateCreateInfo::default()
+        let mut rasterizer_info = vk::PipelineRasterizationStateCreateInfo::default()
             .depth_clamp_enable(false)
             .rasterizer_discard_enable(false)
             .polygon_mode(requirements.features.polygon_mode.into())
-            .line_width(1.0)
+            .line_width(line_width(requirements.features.line_width, device))
             .cull_mode(requirements.features.culling.into())
-            .front_face(vk::FrontFace::COUNTER_CLOCKWISE)
+            .front_face(requirements.features.front_face.into())
             .depth_bias_enable(false);
 
+        if let Some(depth_bias) = requirements.features.depth_bias {
+            let clamp = if device.depth_bias_clamp {
+                depth_bias.clamp
+            } else {
+                if depth_bias.clamp != 0.0 {
+                    core_warn!("Depth bias clamp is not supported by the device, ignoring it");
+                }
+                0.0
+            };
+
+            rasterizer_info = rasterizer_info
+                .depth_bias_enable(true)
+                .depth_bias_constant_factor(depth_bias.constant_factor)
+                .depth_bias_slope_factor(depth_bias.slope_factor)
+                .depth_bias_clamp(clamp);
+        }
+
+        let mut conservative_info =
+            vk::PipelineRasterizationConservativeStateCreateInfoEXT::default()
+                .conservative_rasterization_mode(
+                    vk::ConservativeRasterizationModeEXT::OVERESTIMATE,
+                );
+
+        if feature_flags.contains(PipelineFeatureFlags::CONSERVATIVE_RASTER) {
+            if device.conservative_rasterization {
+                rasterizer_info = rasterizer_info.push_next(&mut conservative_info);
+            } else {
+                core_warn!(
+                    "Conservative rasterization is not supported by the device, ignoring it"
+                );
+            }
+        }
+
         let multisampling_info = vk::PipelineMultisampleStateCreateInfo::default()
             .sample_shading_enable(false)
             .rasterization_samples(requirements.samples);
@@ -102,8 +193,6 @@ impl VulkanPipeline {
             let mut blend_state = vk::PipelineColorBlendAttachmentState::default()
                 .color_write_mask(vk::ColorComponentFlags::RGBA);
 
-            let feature_flags = requirements.features.flags;
-
             if feature_flags.intersects(PipelineFeatureFlags::BLEND_MASK) {
                 blend_state = blend_state.blend_enable(true);
 
@@ -162,22 +251,18 @@ impl VulkanPipeline {
             .logic_op_enable(false)
             .attachments(&color_blend_attachments);
 
-        let bindings = requirements.bindings.to_vec().into();
+        let bindings = MaterialBindingSet::from(requirements.bindings.to_vec());
 
-        let set_layouts = bindings_into_layouts(&bindings)?;
+        let (layout, set_layouts) = device.pipeline_layouts.lock().unwrap().acquire(
+            device,
+            &bindings,
+            &reflection.push_constant_ranges(),
+        )?;
 
-        let layout = {
-            let layout_info = vk::PipelineLayoutCreateInfo::default().set_layouts(&set_layouts);
-            unsafe { device.create_pipeline_layout(&layout_info, None)? }
-        };
-
-        let (modules, stages): (Vec<_>, Vec<_>) = requirements
-            .stage_definitions
+        let (modules, stages): (Vec<_>, Vec<_>) = stage_codes
             .iter()
-            .map(|ShaderStageDefinition { path, stage }| {
-                let code = load_shader(Path::new(path), *stage)?;
-
-                let create_info = vk::ShaderModuleCreateInfo::default().code(&code);
+            .map(|(code, stage)| {
+                let create_info = vk::ShaderModuleCreateInfo::default().code(code);
 
                 let module = unsafe { device.create_shader_module(&create_info, None)? };
 
@@ -188,7 +273,7 @@ impl VulkanPipeline {
 
                 Ok((module, stage))
             })
-            .collect::<Result<Vec<(_, _)>, ShaderError>>()?
+            .collect::<Result<Vec<(_, _)>, vk::Result>>()?
             .into_iter()
             .unzip();
 
@@ -198,7 +283,17 @@ impl VulkanPipeline {
         if requirements.features.flags & PipelineFeatureFlags::DEPTH_MASK
             != PipelineFeatureFlags::empty()
         {
-            depth_stencil_info = depth_stencil_info.depth_compare_op(vk::CompareOp::LESS);
+            let compare_op = if requirements
+                .features
+                .flags
+                .contains(PipelineFeatureFlags::DEPTH_EQUAL)
+            {
+                vk::CompareOp::EQUAL
+            } else {
+                vk::CompareOp::LESS
+            };
+
+            depth_stencil_info = depth_stencil_info.depth_compare_op(compare_op);
 
             if requirements
                 .features
@@ -230,6 +325,22 @@ impl VulkanPipeline {
         let mut attachment_index_info = vk::RenderingInputAttachmentIndexInfoKHR::default()
             .color_attachment_input_indices(&requirements.input_attachment_indices);
 
+        let base_pipeline = requirements
+            .base_pipeline
+            .map(|base| base.pipeline)
+            .or(base_pipeline);
+
+        let mut create_flags = vk::PipelineCreateFlags::DESCRIPTOR_BUFFER_EXT
+            | vk::PipelineCreateFlags::ALLOW_DERIVATIVES;
+
+        if base_pipeline.is_some() {
+            create_flags |= vk::PipelineCreateFlags::DERIVATIVE;
+        }
+
+        if device.pipeline_executable_properties.is_some() {
+            create_flags |= vk::PipelineCreateFlags::CAPTURE_STATISTICS_KHR;
+        }
+
         let pipeline_create_info = vk::GraphicsPipelineCreateInfo::default()
             .stages(&stages)
             .vertex_input_state(&vertex_input_info)
@@ -240,21 +351,23 @@ impl VulkanPipeline {
             .multisample_state(&multisampling_info)
             .color_blend_state(&color_blend_info)
             .dynamic_state(&dynamic_state_info)
-            .flags(vk::PipelineCreateFlags::DESCRIPTOR_BUFFER_EXT)
+            .flags(create_flags)
             .layout(layout)
             .push_next(&mut pipeline_rendering_info)
             .push_next(&mut attachment_index_info);
 
         let pipeline_create_info = if let Some(pipeline) = base_pipeline {
-            pipeline_create_info.base_pipeline_handle(pipeline)
+            pipeline_create_info
+                .base_pipeline_handle(pipeline)
+                .base_pipeline_index(-1)
         } else {
             pipeline_create_info
         };
 
         let pipeline = unsafe {
             device
-                .create_graphics_pipelines(vk::PipelineCache::null(), &[pipeline_create_info], None)
-                .map_err(|(_, e)| e)?
+                .create_graphics_pipelines(device.pipeline_cache, &[pipeline_create_info], None)
+                .map_err(|(_, e)| e)
         };
 
         for module in modules {
@@ -263,25 +376,141 @@ impl VulkanPipeline {
             }
         }
 
+        let pipeline = match pipeline {
+            Ok(pipeline) => pipeline,
+            Err(err) => {
+                device
+                    .pipeline_layouts
+                    .lock()
+                    .unwrap()
+                    .release(device, layout);
+                return Err(err.into());
+            }
+        };
+
+        if let Some(loader) = &device.pipeline_executable_properties {
+            log_pipeline_statistics(loader, pipeline[0], &requirements.stage_definitions);
+        }
+
         Ok(VulkanPipeline {
             pipeline: pipeline[0],
             layout,
             set_layouts,
+            input_attachments: reflection.input_attachments(),
         })
     }
 
-    pub fn destroy(&mut self, device: &ash::Device) {
+    /// Creates a derivative of this pipeline. Layouts are shared with the parent when
+    /// the bindings match
+    pub fn derive(
+        &self,
+        requirements: &VulkanPipelineRequirements,
+        device: &LogicalDevice,
+    ) -> PipelineResult<Self> {
+        Self::from_requirements(requirements, Some(self.pipeline), device)
+    }
+
+    pub fn destroy(&mut self, device: &LogicalDevice) {
         unsafe {
-            device.destroy_pipeline_layout(self.layout, None);
+            device
+                .pipeline_layouts
+                .lock()
+                .unwrap()
+                .release(device, self.layout);
             self.layout = vk::PipelineLayout::null();
-
-            for layout in self.set_layouts.iter_mut() {
-                device.destroy_descriptor_set_layout(*layout, None);
-                *layout = vk::DescriptorSetLayout::null();
-            }
+            self.set_layouts.clear();
 
             device.destroy_pipeline(self.pipeline, None);
             self.pipeline = vk::Pipeline::null();
         }
     }
 }
+
+/// Logs the statistics the driver reports for every executable of `pipeline`,
+/// like register counts and spills. The pipeline is named by its shaders
+fn log_pipeline_statistics(
+    loader: &ash::khr::pipeline_executable_properties::Device,
+    pipeline: vk::Pipeline,
+    stages: &[ShaderStageDefinition],
+) {
+    let shaders = stages
+        .iter()
+        .map(|stage| stage.path.as_str())
+        .collect::<Vec<_>>()
+        .join(", ");
+
+    let pipeline_info = vk::PipelineInfoKHR::default().pipeline(pipeline);
+
+    let executables = match unsafe { loader.get_pipeline_executable_properties(&pipeline_info) } {
+        Ok(executables) => executables,
+        Err(err) => {
+            core_warn!("Failed to query executables of the pipeline of `{shaders}`: {err}");
+            return;
+        }
+    };
+
+    let mut report = format!("Pipeline statistics of `{shaders}`:");
+
+    for (index, executable) in executables.iter().enumerate() {
+        let name = executable
+            .name_as_c_str()
+            .map(|name| name.to_string_lossy().into_owned())
+            .unwrap_or_default();
+
+        report.push_str(&format!(
+            "\n  {name} ({:?}, subgroup size {})",
+            executable.stages, executable.subgroup_size
+        ));
+
+        let executable_info = vk::PipelineExecutableInfoKHR::default()
+            .pipeline(pipeline)
+            .executable_index(index as u32);
+
+        let statistics = unsafe { loader.get_pipeline_executable_statistics(&executable_info) }
+            .unwrap_or_default();
+
+        for statistic in statistics {
+            let name = statistic
+                .name_as_c_str()
+                .map(|name| name.to_string_lossy().into_owned())
+                .unwrap_or_default();
+
+            let value = unsafe {
+                match statistic.format {
+                    vk::PipelineExecutableStatisticFormatKHR::BOOL32 => {
+                        (statistic.value.b32 == vk::TRUE).to_string()
+                    }
+                    vk::PipelineExecutableStatisticFormatKHR::INT64 => {
+                        statistic.value.i64.to_string()
+                    }
+                    vk::PipelineExecutableStatisticFormatKHR::UINT64 => {
+                        statistic.value.u64.to_string()
+                    }
+                    vk::PipelineExecutableStatisticFormatKHR::FLOAT64 => {
+                        statistic.value.f64.to_string()
+                    }
+                    _ => String::from("?"),
+                }
+            };
+
+            report.push_str(&format!("\n    {name}: {value}"));
+        }
+    }
+
+    core_info!("{report}");
+}
+
+/// Clamps `width` to what the device can rasterize
+fn line_width(width: f32, device: &LogicalDevice) -> f32 {
+    if !device.wide_lines {
+        if width != 1.0 {
+            core_warn!(
+                "Wide lines are not supported by the device, drawing {width} wide lines as 1.0"
+            );
+        }
+        return 1.0;
+    }
+
+    let [min, max] = device.physical.device_props.limits.line_width_range;
+    width.clamp(min, max)
+}
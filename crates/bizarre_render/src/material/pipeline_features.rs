@@ -1,12 +1,56 @@
 use ash::vk;
 use bitflags::bitflags;
 
-#[derive(Clone, Default, Debug)]
+#[derive(Clone, Debug)]
 pub struct VulkanPipelineFeatures {
     pub flags: PipelineFeatureFlags,
     pub culling: CullMode,
     pub primitive_topology: PrimitiveTopology,
     pub polygon_mode: PolygonMode,
+    pub front_face: FrontFace,
+    /// Widths other than `1.0` need the `wideLines` device feature and are
+    /// clamped to the device line width range
+    pub line_width: f32,
+    pub depth_bias: Option<DepthBias>,
+}
+
+impl Default for VulkanPipelineFeatures {
+    fn default() -> Self {
+        Self {
+            flags: Default::default(),
+            culling: Default::default(),
+            primitive_topology: Default::default(),
+            polygon_mode: Default::default(),
+            front_face: Default::default(),
+            line_width: 1.0,
+            depth_bias: None,
+        }
+    }
+}
+
+/// Offsets the depth of rasterized fragments, the result is
+/// `constant_factor * r + slope_factor * max_slope` clamped to `clamp` if it's not `0.0`
+#[derive(Clone, Copy, Default, Debug, PartialEq)]
+pub struct DepthBias {
+    pub constant_factor: f32,
+    pub slope_factor: f32,
+    /// Needs the `depthBiasClamp` device feature, ignored without it
+    pub clamp: f32,
+}
+
+impl DepthBias {
+    pub fn new(constant_factor: f32, slope_factor: f32) -> Self {
+        Self {
+            constant_factor,
+            slope_factor,
+            clamp: 0.0,
+        }
+    }
+
+    pub fn with_clamp(mut self, clamp: f32) -> Self {
+        self.clamp = clamp;
+        self
+    }
 }
 
 bitflags! {
@@ -26,11 +70,23 @@ bitflags! {
         const DEPTH_MASK = 0xf << Self::DEPTH_SHIFT.bits();
         const DEPTH_TEST = 0b0001 << Self::DEPTH_SHIFT.bits();
         const DEPTH_WRITE = 0b0010 << Self::DEPTH_SHIFT.bits();
+        /// Passes only fragments with the depth already in the depth buffer,
+        /// for drawing over a depth pre-pass
+        const DEPTH_EQUAL = 0b0100 << Self::DEPTH_SHIFT.bits();
 
         const STENCIL_SHIFT = Self::DEPTH_SHIFT.bits() + Self::DEPTH_FIELD_WIDTH.bits();
         const STENCIL_FIELD_WIDTH = 4;
         const STENCIL_MASK = 0xf << Self::STENCIL_SHIFT.bits();
         const STENCIL_TEST = 0b0001 << Self::STENCIL_SHIFT.bits();
+
+        const RASTER_SHIFT = Self::STENCIL_SHIFT.bits() + Self::STENCIL_FIELD_WIDTH.bits();
+        const RASTER_FIELD_WIDTH = 4;
+        const RASTER_MASK = 0xf << Self::RASTER_SHIFT.bits();
+        /// Lets the maximum index value restart strip and fan topologies
+        const PRIMITIVE_RESTART = 0b0001 << Self::RASTER_SHIFT.bits();
+        /// Rasterizes every pixel a primitive touches, needs
+        /// `VK_EXT_conservative_rasterization` and is ignored without it
+        const CONSERVATIVE_RASTER = 0b0010 << Self::RASTER_SHIFT.bits();
     }
 }
 
@@ -99,3 +155,17 @@ impl From<PolygonMode> for vk::PolygonMode {
         Self::from_raw(value as i32)
     }
 }
+
+#[repr(i32)]
+#[derive(Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Debug, Default)]
+pub enum FrontFace {
+    #[default]
+    CounterClockwise = vk::FrontFace::COUNTER_CLOCKWISE.as_raw(),
+    Clockwise = vk::FrontFace::CLOCKWISE.as_raw(),
+}
+
+impl From<FrontFace> for vk::FrontFace {
+    fn from(value: FrontFace) -> Self {
+        Self::from_raw(value as i32)
+    }
+}
@@ -0,0 +1,129 @@
+use std::collections::BTreeMap;
+
+use ash::vk;
+
+use crate::shader::ShaderStageFlags;
+
+use super::material_binding::{bindings_into_layouts, MaterialBindingSet};
+
+/// Part of a `MaterialBinding` that affects the descriptor set layout
+type LayoutBindingKey = (u32, u32, vk::DescriptorType, u32, ShaderStageFlags);
+
+/// Stage flags, offset and size of a push constant range
+type PushConstantKey = (u32, u32, u32);
+
+type LayoutKey = (Vec<LayoutBindingKey>, Vec<PushConstantKey>);
+
+struct CachedPipelineLayout {
+    layout: vk::PipelineLayout,
+    set_layouts: Vec<vk::DescriptorSetLayout>,
+    ref_count: usize,
+}
+
+/// Shares pipeline layouts and descriptor set layouts between pipelines with identical
+/// binding sets and push constant ranges.
+///
+/// Layouts are reference counted and destroyed when the last pipeline using them is destroyed
+#[derive(Default)]
+pub struct PipelineLayoutCache {
+    layouts: BTreeMap<LayoutKey, CachedPipelineLayout>,
+}
+
+impl PipelineLayoutCache {
+    /// Returns a layout for the binding set and push constant ranges, creating it if
+    /// there is no matching one yet
+    pub(crate) fn acquire(
+        &mut self,
+        device: &ash::Device,
+        bindings: &MaterialBindingSet,
+        push_constant_ranges: &[vk::PushConstantRange],
+    ) -> Result<(vk::PipelineLayout, Vec<vk::DescriptorSetLayout>), vk::Result> {
+        let binding_keys = bindings
+            .bindings
+            .iter()
+            .map(|binding| {
+                (
+                    binding.set,
+                    binding.binding,
+                    binding.descriptor_type,
+                    binding.descriptor_count,
+                    binding.shader_stage_flags,
+                )
+            })
+            .collect::<Vec<_>>();
+
+        let push_constant_keys = push_constant_ranges
+            .iter()
+            .map(|range| (range.stage_flags.as_raw(), range.offset, range.size))
+            .collect::<Vec<_>>();
+
+        let key = (binding_keys, push_constant_keys);
+
+        if let Some(cached) = self.layouts.get_mut(&key) {
+            cached.ref_count += 1;
+            return Ok((cached.layout, cached.set_layouts.clone()));
+        }
+
+        let set_layouts = bindings_into_layouts(bindings)?;
+
+        let layout = {
+            let layout_info = vk::PipelineLayoutCreateInfo::default()
+                .set_layouts(&set_layouts)
+                .push_constant_ranges(push_constant_ranges);
+            unsafe { device.create_pipeline_layout(&layout_info, None)? }
+        };
+
+        self.layouts.insert(
+            key,
+            CachedPipelineLayout {
+                layout,
+                set_layouts: set_layouts.clone(),
+                ref_count: 1,
+            },
+        );
+
+        Ok((layout, set_layouts))
+    }
+
+    /// Releases a layout acquired with [`acquire`](Self::acquire)
+    pub(crate) fn release(&mut self, device: &ash::Device, layout: vk::PipelineLayout) {
+        let Some(key) = self
+            .layouts
+            .iter()
+            .find_map(|(key, cached)| (cached.layout == layout).then(|| key.clone()))
+        else {
+            return;
+        };
+
+        let cached = self.layouts.get_mut(&key).unwrap();
+        cached.ref_count -= 1;
+
+        if cached.ref_count == 0 {
+            let cached = self.layouts.remove(&key).unwrap();
+            unsafe { destroy_layout(device, cached) };
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.layouts.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.layouts.is_empty()
+    }
+
+    pub(crate) fn destroy(&mut self, device: &ash::Device) {
+        std::mem::take(&mut self.layouts)
+            .into_values()
+            .for_each(|cached| unsafe { destroy_layout(device, cached) });
+    }
+}
+
+unsafe fn destroy_layout(device: &ash::Device, cached: CachedPipelineLayout) {
+    device.destroy_pipeline_layout(cached.layout, None);
+
+    cached
+        .set_layouts
+        .into_iter()
+        .for_each(|layout| device.destroy_descriptor_set_layout(layout, None));
+}
@@ -0,0 +1,284 @@
+use std::{
+    sync::atomic::{AtomicU64, Ordering},
+    time::{Duration, Instant},
+};
+
+use ash::vk;
+use bizarre_ecs::prelude::*;
+use bizarre_log::core_warn;
+
+use crate::vulkan_context::get_device;
+
+pub const DEFAULT_UPDATE_INTERVAL: Duration = Duration::from_secs(1);
+pub const DEFAULT_WARNING_THRESHOLD: f32 = 0.9;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum AllocationCategory {
+    Buffer,
+    Image,
+}
+
+struct CategoryCounter {
+    bytes: AtomicU64,
+    count: AtomicU64,
+}
+
+impl CategoryCounter {
+    const fn new() -> Self {
+        Self {
+            bytes: AtomicU64::new(0),
+            count: AtomicU64::new(0),
+        }
+    }
+}
+
+static CATEGORY_COUNTERS: [CategoryCounter; 2] = [CategoryCounter::new(), CategoryCounter::new()];
+
+/// Block-compressed textures and the bytes they would take as 8-bit RGBA
+static COMPRESSED_TEXTURES: CategoryCounter = CategoryCounter::new();
+static UNCOMPRESSED_TEXTURE_BYTES: AtomicU64 = AtomicU64::new(0);
+
+fn counter(category: AllocationCategory) -> &'static CategoryCounter {
+    &CATEGORY_COUNTERS[category as usize]
+}
+
+/// Accounts a freshly created allocation in its category
+pub(crate) fn track_allocation(category: AllocationCategory, allocation: &vma::Allocation) {
+    let size = get_device().allocator.get_allocation_info(allocation).size;
+
+    let counter = counter(category);
+    counter.bytes.fetch_add(size, Ordering::Relaxed);
+    counter.count.fetch_add(1, Ordering::Relaxed);
+}
+
+/// Removes an allocation from its category, must be called before the allocation is freed
+pub(crate) fn track_free(category: AllocationCategory, allocation: &vma::Allocation) {
+    let size = get_device().allocator.get_allocation_info(allocation).size;
+
+    let counter = counter(category);
+    counter.bytes.fetch_sub(size, Ordering::Relaxed);
+    counter.count.fetch_sub(1, Ordering::Relaxed);
+}
+
+/// Accounts a block-compressed texture image of `bytes`, which would take
+/// `uncompressed_bytes` as 8-bit RGBA
+pub(crate) fn track_compressed_texture(bytes: u64, uncompressed_bytes: u64) {
+    COMPRESSED_TEXTURES
+        .bytes
+        .fetch_add(bytes, Ordering::Relaxed);
+    COMPRESSED_TEXTURES.count.fetch_add(1, Ordering::Relaxed);
+    UNCOMPRESSED_TEXTURE_BYTES.fetch_add(uncompressed_bytes, Ordering::Relaxed);
+}
+
+/// Removes a texture accounted by [`track_compressed_texture`]
+pub(crate) fn track_compressed_texture_free(bytes: u64, uncompressed_bytes: u64) {
+    COMPRESSED_TEXTURES
+        .bytes
+        .fetch_sub(bytes, Ordering::Relaxed);
+    COMPRESSED_TEXTURES.count.fetch_sub(1, Ordering::Relaxed);
+    UNCOMPRESSED_TEXTURE_BYTES.fetch_sub(uncompressed_bytes, Ordering::Relaxed);
+}
+
+#[derive(Clone, Copy, Debug, Default)]
+pub struct CategoryUsage {
+    pub bytes: u64,
+    pub allocation_count: u64,
+}
+
+/// Memory saved by block-compressed textures
+#[derive(Clone, Copy, Debug, Default)]
+pub struct TextureCompressionUsage {
+    pub texture_count: u64,
+    pub compressed_bytes: u64,
+    /// Bytes the textures would take as 8-bit RGBA
+    pub uncompressed_bytes: u64,
+}
+
+impl TextureCompressionUsage {
+    pub fn saved_bytes(&self) -> u64 {
+        self.uncompressed_bytes
+            .saturating_sub(self.compressed_bytes)
+    }
+}
+
+#[derive(Clone, Copy, Debug, Default)]
+pub struct HeapBudget {
+    pub heap_index: usize,
+    pub flags: vk::MemoryHeapFlags,
+    /// Memory available to the program, as estimated by the driver
+    pub budget: u64,
+    /// Memory used by the program, including allocations made outside of vma
+    pub usage: u64,
+    pub block_bytes: u64,
+    pub allocation_bytes: u64,
+    pub allocation_count: u32,
+}
+
+impl HeapBudget {
+    pub fn usage_ratio(&self) -> f32 {
+        if self.budget == 0 {
+            return 0.0;
+        }
+
+        self.usage as f32 / self.budget as f32
+    }
+}
+
+/// GPU memory usage and budgets, refreshed by
+/// [`update_gpu_memory_stats`][crate::ecs::update_gpu_memory_stats]
+#[derive(Resource, Debug)]
+pub struct GpuMemoryStats {
+    heaps: Vec<HeapBudget>,
+    buffers: CategoryUsage,
+    images: CategoryUsage,
+    texture_compression: TextureCompressionUsage,
+    total_allocated: u64,
+
+    update_interval: Duration,
+    warning_threshold: f32,
+    last_update: Option<Instant>,
+    over_threshold: Vec<bool>,
+}
+
+impl Default for GpuMemoryStats {
+    fn default() -> Self {
+        Self {
+            heaps: Vec::new(),
+            buffers: CategoryUsage::default(),
+            images: CategoryUsage::default(),
+            texture_compression: TextureCompressionUsage::default(),
+            total_allocated: 0,
+            update_interval: DEFAULT_UPDATE_INTERVAL,
+            warning_threshold: DEFAULT_WARNING_THRESHOLD,
+            last_update: None,
+            over_threshold: Vec::new(),
+        }
+    }
+}
+
+impl GpuMemoryStats {
+    pub fn heaps(&self) -> &[HeapBudget] {
+        &self.heaps
+    }
+
+    pub fn buffers(&self) -> CategoryUsage {
+        self.buffers
+    }
+
+    pub fn images(&self) -> CategoryUsage {
+        self.images
+    }
+
+    pub fn texture_compression(&self) -> TextureCompressionUsage {
+        self.texture_compression
+    }
+
+    /// Bytes occupied by all vma allocations
+    pub fn total_allocated(&self) -> u64 {
+        self.total_allocated
+    }
+
+    pub fn update_interval(&self) -> Duration {
+        self.update_interval
+    }
+
+    pub fn set_update_interval(&mut self, interval: Duration) {
+        self.update_interval = interval;
+    }
+
+    pub fn warning_threshold(&self) -> f32 {
+        self.warning_threshold
+    }
+
+    /// Sets the fraction of a heap budget after which a warning is logged
+    pub fn set_warning_threshold(&mut self, threshold: f32) {
+        self.warning_threshold = threshold.clamp(0.0, 1.0);
+    }
+
+    /// Refreshes the stats if `update_interval` has passed since the last update.
+    ///
+    /// Returns `true` if the stats were refreshed
+    pub fn update(&mut self, now: Instant) -> bool {
+        if let Some(last_update) = self.last_update {
+            if now.duration_since(last_update) < self.update_interval {
+                return false;
+            }
+        }
+
+        self.last_update = Some(now);
+        self.collect();
+        self.check_budgets();
+
+        true
+    }
+
+    /// Refreshes the stats unconditionally
+    pub fn collect(&mut self) {
+        let allocator = &get_device().allocator;
+
+        let memory_heaps = unsafe { allocator.get_memory_properties() }.memory_heaps;
+
+        let budgets = match allocator.get_heap_budgets() {
+            Ok(budgets) => budgets,
+            Err(err) => {
+                core_warn!("GpuMemoryStats: failed to get heap budgets: {err}");
+                return;
+            }
+        };
+
+        self.heaps = budgets
+            .iter()
+            .enumerate()
+            .map(|(heap_index, budget)| HeapBudget {
+                heap_index,
+                flags: memory_heaps[heap_index].flags,
+                budget: budget.budget,
+                usage: budget.usage,
+                block_bytes: budget.statistics.blockBytes,
+                allocation_bytes: budget.statistics.allocationBytes,
+                allocation_count: budget.statistics.allocationCount,
+            })
+            .collect();
+
+        self.total_allocated = self.heaps.iter().map(|heap| heap.allocation_bytes).sum();
+
+        let load = |category| {
+            let counter = counter(category);
+            CategoryUsage {
+                bytes: counter.bytes.load(Ordering::Relaxed),
+                allocation_count: counter.count.load(Ordering::Relaxed),
+            }
+        };
+
+        self.buffers = load(AllocationCategory::Buffer);
+        self.images = load(AllocationCategory::Image);
+
+        self.texture_compression = TextureCompressionUsage {
+            texture_count: COMPRESSED_TEXTURES.count.load(Ordering::Relaxed),
+            compressed_bytes: COMPRESSED_TEXTURES.bytes.load(Ordering::Relaxed),
+            uncompressed_bytes: UNCOMPRESSED_TEXTURE_BYTES.load(Ordering::Relaxed),
+        };
+    }
+
+    fn check_budgets(&mut self) {
+        self.over_threshold.resize(self.heaps.len(), false);
+
+        for (heap, over_threshold) in self.heaps.iter().zip(self.over_threshold.iter_mut()) {
+            let over = heap.usage_ratio() >= self.warning_threshold;
+
+            if over && !*over_threshold {
+                core_warn!(
+                    "GPU memory heap {} is at {:.1}% of its budget ({} / {} bytes, buffers: {} bytes, images: {} bytes)",
+                    heap.heap_index,
+                    heap.usage_ratio() * 100.0,
+                    heap.usage,
+                    heap.budget,
+                    self.buffers.bytes,
+                    self.images.bytes,
+                );
+            }
+
+            *over_threshold = over;
+        }
+    }
+}
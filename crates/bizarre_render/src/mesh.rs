@@ -1,31 +1,324 @@
-use std::{fmt::Debug, fs::File, io::Read, path::Path};
+use std::{collections::HashMap, fmt::Debug, fs::File, io::Read, path::Path};
 
 use bizarre_core::Handle;
-use nalgebra_glm::Vec3;
+use bizarre_log::core_warn;
+use nalgebra_glm::{Vec2, Vec3, Vec4};
 use tobj::LoadOptions;
 
 use crate::{render_assets::DenseAssetStore, vertex::Vertex};
 
 pub type MeshHandle = Handle<Mesh>;
 
+/// How often the data of a mesh changes, picks the memory it's kept in on the GPU
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+pub enum MeshUsage {
+    /// Uploaded once into device-local memory through a staging buffer
+    #[default]
+    Static,
+    /// Written directly into host-visible memory, for meshes replaced often
+    Dynamic,
+}
+
+/// Problems found by [`Mesh::validate`]
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct MeshValidation {
+    /// Triangles with zero area, they are not drawn and get no normal
+    pub degenerate_triangles: usize,
+    /// Vertices with NaN or infinite positions
+    pub non_finite_positions: usize,
+    /// Indices pointing past the vertices
+    pub invalid_indices: usize,
+    /// Indices not forming a whole triangle at the end of the index list
+    pub trailing_indices: usize,
+}
+
+impl MeshValidation {
+    pub fn is_valid(&self) -> bool {
+        *self == Self::default()
+    }
+}
+
+/// Axis-aligned bounding box
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct Aabb {
+    pub min: Vec3,
+    pub max: Vec3,
+}
+
+impl Aabb {
+    pub fn new(min: Vec3, max: Vec3) -> Self {
+        Self { min, max }
+    }
+
+    pub fn center(&self) -> Vec3 {
+        (self.min + self.max) * 0.5
+    }
+
+    pub fn extent(&self) -> Vec3 {
+        self.max - self.min
+    }
+
+    pub fn contains(&self, point: &Vec3) -> bool {
+        (0..3).all(|axis| self.min[axis] <= point[axis] && point[axis] <= self.max[axis])
+    }
+
+    /// Distance along `ray` to where it enters the box, `0.0` if it starts inside.
+    /// `None` if the ray misses the box or the box is behind it
+    pub fn intersect_ray(&self, ray: &Ray) -> Option<f32> {
+        let mut near = 0.0f32;
+        let mut far = f32::INFINITY;
+
+        for axis in 0..3 {
+            let inverse = 1.0 / ray.direction[axis];
+            let t_min = (self.min[axis] - ray.origin[axis]) * inverse;
+            let t_max = (self.max[axis] - ray.origin[axis]) * inverse;
+
+            // NaN comes from a ray parallel to the slab starting on its plane, it
+            // doesn't restrict the range
+            near = near.max(t_min.min(t_max));
+            far = far.min(t_max.max(t_min));
+        }
+
+        (near <= far).then_some(near)
+    }
+}
+
+/// Half-line starting at `origin`, `direction` doesn't have to be normalized.
+/// Distances of [`Hit`]s are measured in lengths of `direction`
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Ray {
+    pub origin: Vec3,
+    pub direction: Vec3,
+}
+
+impl Ray {
+    pub fn new(origin: Vec3, direction: Vec3) -> Self {
+        Self { origin, direction }
+    }
+
+    pub fn at(&self, distance: f32) -> Vec3 {
+        self.origin + self.direction * distance
+    }
+}
+
+/// Closest intersection of a [`Ray`] with a [`Mesh`]
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Hit {
+    /// Distance along the ray in lengths of its `direction`
+    pub distance: f32,
+    pub position: Vec3,
+    /// Normal of the hit triangle facing the side the ray came from
+    pub normal: Vec3,
+    /// Index of the triangle, its vertices are `indices[triangle * 3..triangle * 3 + 3]`
+    pub triangle: usize,
+    /// Weights of the second and the third vertex of the triangle at the hit
+    pub barycentric: Vec2,
+}
+
 #[derive(Debug, Default)]
 pub struct Mesh {
     pub vertices: Vec<Vertex>,
     pub indices: Vec<u32>,
+    pub usage: MeshUsage,
 }
 
 impl Mesh {
     pub fn from_vertices(vertices: Vec<Vertex>) -> Self {
         let indices = vertices.iter().enumerate().map(|(i, _)| i as u32).collect();
 
-        Self { vertices, indices }
+        Self::from_vertices_and_indices(vertices, indices)
     }
 
     pub fn from_vertices_and_indices(vertices: Vec<Vertex>, indices: Vec<u32>) -> Self {
-        Self { vertices, indices }
+        Self {
+            vertices,
+            indices,
+            usage: MeshUsage::default(),
+        }
+    }
+
+    pub fn with_usage(mut self, usage: MeshUsage) -> Self {
+        self.usage = usage;
+        self
+    }
+
+    /// Object-space box around all vertices, empty at the origin for a mesh
+    /// without vertices
+    pub fn aabb(&self) -> Aabb {
+        let Some(first) = self.vertices.first() else {
+            return Aabb::default();
+        };
+
+        let (min, max) = self
+            .vertices
+            .iter()
+            .fold((first.position, first.position), |(min, max), vertex| {
+                (min.inf(&vertex.position), max.sup(&vertex.position))
+            });
+
+        Aabb::new(min, max)
+    }
+
+    /// Sphere around all vertices, `xyz` is the center and `w` the radius
+    pub fn bounding_sphere(&self) -> Vec4 {
+        if self.vertices.is_empty() {
+            return Vec4::zeros();
+        }
+
+        let center = self.aabb().center();
+
+        let radius = self
+            .vertices
+            .iter()
+            .map(|vertex| (vertex.position - center).norm())
+            .fold(0.0, f32::max);
+
+        Vec4::new(center.x, center.y, center.z, radius)
+    }
+
+    /// Closest triangle hit by an object-space `ray`, both sides of triangles are
+    /// hit. Rays missing the [`Mesh::aabb`] are rejected without testing triangles
+    pub fn raycast(&self, ray: &Ray) -> Option<Hit> {
+        self.aabb().intersect_ray(ray)?;
+
+        self.indices
+            .chunks_exact(3)
+            .enumerate()
+            .filter_map(|(triangle, indices)| {
+                let corners = [0, 1, 2].map(|corner| self.vertices.get(indices[corner] as usize));
+                let [Some(a), Some(b), Some(c)] = corners else {
+                    return None;
+                };
+
+                intersect_triangle(ray, [a.position, b.position, c.position]).map(
+                    |(distance, barycentric, normal)| Hit {
+                        distance,
+                        position: ray.at(distance),
+                        normal,
+                        triangle,
+                        barycentric,
+                    },
+                )
+            })
+            .min_by(|a, b| a.distance.total_cmp(&b.distance))
+    }
+
+    fn triangles(&self) -> impl Iterator<Item = [usize; 3]> + '_ {
+        self.indices
+            .chunks_exact(3)
+            .map(|triangle| [0, 1, 2].map(|corner| triangle[corner] as usize))
+            .filter(|triangle| triangle.iter().all(|index| *index < self.vertices.len()))
+    }
+
+    pub fn validate(&self) -> MeshValidation {
+        let degenerate_triangles = self
+            .triangles()
+            .filter(|[a, b, c]| {
+                let [a, b, c] = [a, b, c].map(|index| self.vertices[*index].position);
+                (b - a).cross(&(c - a)).norm_squared() <= f32::EPSILON * f32::EPSILON
+            })
+            .count();
+
+        MeshValidation {
+            degenerate_triangles,
+            non_finite_positions: self
+                .vertices
+                .iter()
+                .filter(|vertex| vertex.position.iter().any(|value| !value.is_finite()))
+                .count(),
+            invalid_indices: self
+                .indices
+                .iter()
+                .filter(|index| **index as usize >= self.vertices.len())
+                .count(),
+            trailing_indices: self.indices.len() % 3,
+        }
+    }
+
+    /// Sets every normal to the area weighted average of the faces around its
+    /// position, so vertices split only by their UVs don't leave a seam
+    pub fn generate_smooth_normals(&mut self) {
+        let mut position_normals = HashMap::<[u32; 3], Vec3>::new();
+
+        let position_key = |position: &Vec3| position.map(f32::to_bits).into();
+
+        for triangle in self.triangles().collect::<Vec<_>>() {
+            let [a, b, c] = triangle.map(|index| self.vertices[index].position);
+            // Length is twice the area of the triangle
+            let face_normal = (b - a).cross(&(c - a));
+
+            for index in triangle {
+                *position_normals
+                    .entry(position_key(&self.vertices[index].position))
+                    .or_insert_with(Vec3::zeros) += face_normal;
+            }
+        }
+
+        for vertex in self.vertices.iter_mut() {
+            vertex.normal = position_normals
+                .get(&position_key(&vertex.position))
+                .and_then(|normal| normal.try_normalize(f32::EPSILON))
+                .unwrap_or_else(Vec3::y);
+        }
+    }
+
+    /// Generates per-vertex tangents from the UVs, orthogonalized against the
+    /// normals. Follows the MikkTSpace conventions of the bitangent sign and of
+    /// weighting faces by their area, without its vertex welding
+    pub fn generate_tangents(&mut self) {
+        let mut tangents = vec![Vec3::zeros(); self.vertices.len()];
+        let mut bitangents = vec![Vec3::zeros(); self.vertices.len()];
+
+        for triangle in self.triangles().collect::<Vec<_>>() {
+            let [a, b, c] = triangle.map(|index| &self.vertices[index]);
+
+            let edge_1 = b.position - a.position;
+            let edge_2 = c.position - a.position;
+            let delta_uv_1 = b.uv - a.uv;
+            let delta_uv_2 = c.uv - a.uv;
+
+            let det = delta_uv_1.x * delta_uv_2.y - delta_uv_2.x * delta_uv_1.y;
+            if det.abs() <= f32::EPSILON {
+                continue;
+            }
+
+            // Both are scaled by the triangle area, which weights the faces
+            let tangent = (edge_1 * delta_uv_2.y - edge_2 * delta_uv_1.y) * det.signum();
+            let bitangent = (edge_2 * delta_uv_1.x - edge_1 * delta_uv_2.x) * det.signum();
+
+            for index in triangle {
+                tangents[index] += tangent;
+                bitangents[index] += bitangent;
+            }
+        }
+
+        for ((vertex, tangent), bitangent) in self.vertices.iter_mut().zip(tangents).zip(bitangents)
+        {
+            let normal = vertex.normal;
+
+            let tangent = (tangent - normal * normal.dot(&tangent))
+                .try_normalize(f32::EPSILON)
+                .unwrap_or_else(|| any_orthogonal(&normal));
+
+            let handedness = if normal.cross(&tangent).dot(&bitangent) < 0.0 {
+                -1.0
+            } else {
+                1.0
+            };
+
+            vertex.tangent = Vec4::new(tangent.x, tangent.y, tangent.z, handedness);
+        }
     }
 
     pub fn load_from_obj<P: AsRef<Path> + Debug>(file_path: P) -> Self {
+        Self::try_load_from_obj(file_path).unwrap()
+    }
+
+    pub fn try_load_from_obj<P: AsRef<Path> + Debug>(
+        file_path: P,
+    ) -> Result<Self, tobj::LoadError> {
+        let file_path = file_path.as_ref();
+
         let (models, _) = tobj::load_obj(
             file_path,
             &LoadOptions {
@@ -33,25 +326,123 @@ impl Mesh {
                 triangulate: true,
                 ..Default::default()
             },
-        )
-        .unwrap();
-
-        let model = &models[0];
+        )?;
 
-        let positions = model.mesh.positions.chunks(3).map(Vec3::from_column_slice);
-        let normals = model.mesh.normals.chunks(3).map(Vec3::from_column_slice);
+        let model = models.first().ok_or(tobj::LoadError::GenericFailure)?;
+        let mesh = &model.mesh;
 
-        let vertices = positions
-            .zip(normals)
-            .map(|(position, normal)| Vertex {
-                position,
-                normal,
+        let vertices = mesh
+            .positions
+            .chunks(3)
+            .enumerate()
+            .map(|(index, position)| Vertex {
+                position: Vec3::from_column_slice(position),
+                normal: mesh
+                    .normals
+                    .get(index * 3..index * 3 + 3)
+                    .map(Vec3::from_column_slice)
+                    .unwrap_or_default(),
+                // OBJ has the origin of UVs in the bottom left corner
+                uv: mesh
+                    .texcoords
+                    .get(index * 2..index * 2 + 2)
+                    .map(|uv| Vec2::new(uv[0], 1.0 - uv[1]))
+                    .unwrap_or_default(),
                 ..Default::default()
             })
             .collect::<Vec<_>>();
 
-        let indices = model.mesh.indices.clone();
+        let mut result = Self::from_vertices_and_indices(vertices, mesh.indices.clone());
+
+        let validation = result.validate();
+        if validation.degenerate_triangles > 0 {
+            core_warn!(
+                "{file_path:?}: {} degenerate triangles",
+                validation.degenerate_triangles
+            );
+        }
+        if validation.non_finite_positions > 0 {
+            core_warn!(
+                "{file_path:?}: {} vertices with NaN or infinite positions",
+                validation.non_finite_positions
+            );
+        }
+        if validation.invalid_indices > 0 || validation.trailing_indices > 0 {
+            core_warn!(
+                "{file_path:?}: {} out of range and {} trailing indices",
+                validation.invalid_indices,
+                validation.trailing_indices
+            );
+        }
+
+        if mesh.normals.len() != mesh.positions.len() {
+            core_warn!("{file_path:?}: no normals, generating smooth ones");
+            result.generate_smooth_normals();
+        }
+
+        if mesh.texcoords.len() * 3 == mesh.positions.len() * 2 {
+            result.generate_tangents();
+        } else {
+            // Without UVs any tangent orthogonal to the normal will do
+            for vertex in result.vertices.iter_mut() {
+                let tangent = any_orthogonal(&vertex.normal);
+                vertex.tangent = Vec4::new(tangent.x, tangent.y, tangent.z, 1.0);
+            }
+        }
+
+        Ok(result)
+    }
+}
+
+/// Möller–Trumbore ray-triangle intersection, returns the distance along the ray,
+/// the barycentric coordinates of the hit and the normal facing the ray
+fn intersect_triangle(ray: &Ray, [a, b, c]: [Vec3; 3]) -> Option<(f32, Vec2, Vec3)> {
+    let edge_1 = b - a;
+    let edge_2 = c - a;
+
+    let p = ray.direction.cross(&edge_2);
+    let det = edge_1.dot(&p);
+
+    // The ray is parallel to the triangle or the triangle is degenerate
+    if det.abs() <= f32::EPSILON {
+        return None;
+    }
+
+    let inverse_det = 1.0 / det;
+    let to_origin = ray.origin - a;
+
+    let u = to_origin.dot(&p) * inverse_det;
+    if !(0.0..=1.0).contains(&u) {
+        return None;
+    }
+
+    let q = to_origin.cross(&edge_1);
+
+    let v = ray.direction.dot(&q) * inverse_det;
+    if v < 0.0 || u + v > 1.0 {
+        return None;
+    }
 
-        Self { vertices, indices }
+    let distance = edge_2.dot(&q) * inverse_det;
+    if distance < 0.0 {
+        return None;
     }
+
+    let normal = edge_1.cross(&edge_2).normalize() * -det.signum();
+
+    Some((distance, Vec2::new(u, v), normal))
+}
+
+/// Unit vector orthogonal to `normal`
+fn any_orthogonal(normal: &Vec3) -> Vec3 {
+    let axis = if normal.x.abs() < 0.9 {
+        Vec3::x()
+    } else {
+        Vec3::y()
+    };
+
+    normal
+        .cross(&axis)
+        .try_normalize(f32::EPSILON)
+        .unwrap_or_else(Vec3::z)
 }
@@ -0,0 +1,512 @@
+use std::{
+    collections::{HashMap, VecDeque},
+    iter,
+    ops::Range,
+};
+
+use ash::vk;
+use bizarre_core::handle::HandleStrategy;
+use bizarre_log::{core_info, core_warn};
+use nalgebra_glm::Vec4;
+
+use crate::{
+    buffer::{BufferResult, GpuBuffer},
+    device::LogicalDevice,
+    mesh::{Mesh, MeshHandle, MeshUsage},
+    render_assets::AssetStore,
+    vertex::Vertex,
+    vulkan_context::get_device,
+};
+
+const INITIAL_VERTEX_LEN: u32 = 10_000;
+const INITIAL_INDEX_LEN: u32 = 50_000;
+
+/// Location of a mesh inside of the [`MeshPool`] buffers
+#[derive(Debug, Clone)]
+pub struct MeshMapping {
+    pub(crate) index_offset: u32,
+    pub(crate) index_count: u32,
+    pub(crate) vertex_offset: u32,
+    /// Bounding sphere of the mesh in model space
+    pub(crate) bounds: Vec4,
+    /// Buffers of the pool the offsets point into
+    pub(crate) usage: MeshUsage,
+}
+
+#[derive(Debug, Clone)]
+struct MeshAllocation {
+    vertices: Range<u32>,
+    indices: Range<u32>,
+    bounds: Vec4,
+    usage: MeshUsage,
+}
+
+impl MeshAllocation {
+    fn mapping(&self) -> MeshMapping {
+        MeshMapping {
+            index_offset: self.indices.start,
+            index_count: self.indices.len() as u32,
+            vertex_offset: self.vertices.start,
+            bounds: self.bounds,
+            usage: self.usage,
+        }
+    }
+}
+
+/// Ranges of a released mesh, free once the submissions before `submission`
+/// are done drawing
+#[derive(Debug)]
+struct ReleasedRanges {
+    submission: u64,
+    usage: MeshUsage,
+    vertices: Range<u32>,
+    indices: Range<u32>,
+}
+
+/// First-fit allocator of element ranges, freed ranges are merged with their neighbours
+#[derive(Debug)]
+struct RangeAllocator {
+    capacity: u32,
+    /// Sorted and non-adjacent free ranges
+    free: Vec<Range<u32>>,
+}
+
+impl RangeAllocator {
+    fn new(capacity: u32) -> Self {
+        Self {
+            capacity,
+            free: iter::once(0..capacity).collect(),
+        }
+    }
+
+    fn allocate(&mut self, len: u32) -> Option<Range<u32>> {
+        let index = self
+            .free
+            .iter()
+            .position(|range| range.len() as u32 >= len)?;
+
+        let range = &mut self.free[index];
+        let allocated = range.start..range.start + len;
+        range.start += len;
+
+        if range.start == range.end {
+            self.free.remove(index);
+        }
+
+        Some(allocated)
+    }
+
+    fn free(&mut self, range: Range<u32>) {
+        if range.is_empty() {
+            return;
+        }
+
+        let index = self.free.partition_point(|free| free.start < range.start);
+
+        self.free.insert(index, range);
+
+        if index + 1 < self.free.len() && self.free[index].end == self.free[index + 1].start {
+            self.free[index].end = self.free.remove(index + 1).end;
+        }
+
+        if index > 0 && self.free[index - 1].end == self.free[index].start {
+            self.free[index - 1].end = self.free.remove(index).end;
+        }
+    }
+
+    fn grow(&mut self, capacity: u32) {
+        let old_capacity = self.capacity;
+        self.capacity = capacity;
+        self.free(old_capacity..capacity);
+    }
+}
+
+/// Buffers holding the meshes of one [`MeshUsage`]
+#[derive(Debug)]
+struct MeshBuffers {
+    usage: MeshUsage,
+    vertex_buffer: GpuBuffer,
+    index_buffer: GpuBuffer,
+    vertices: RangeAllocator,
+    indices: RangeAllocator,
+}
+
+impl MeshBuffers {
+    fn new(usage: MeshUsage, vertex_capacity: u32, index_capacity: u32) -> BufferResult<Self> {
+        let vertex_buffer = Self::create_buffer(
+            usage,
+            (size_of::<Vertex>() * vertex_capacity as usize) as vk::DeviceSize,
+            vk::BufferUsageFlags::VERTEX_BUFFER,
+        )?;
+        let index_buffer = Self::create_buffer(
+            usage,
+            (size_of::<u32>() * index_capacity as usize) as vk::DeviceSize,
+            vk::BufferUsageFlags::INDEX_BUFFER,
+        )?;
+
+        Ok(Self {
+            usage,
+            vertex_buffer,
+            index_buffer,
+            vertices: RangeAllocator::new(vertex_capacity),
+            indices: RangeAllocator::new(index_capacity),
+        })
+    }
+
+    fn create_buffer(
+        usage: MeshUsage,
+        size: vk::DeviceSize,
+        buffer_usage: vk::BufferUsageFlags,
+    ) -> BufferResult<GpuBuffer> {
+        let buffer_usage = buffer_usage | vk::BufferUsageFlags::TRANSFER_DST;
+
+        match usage {
+            MeshUsage::Static => {
+                let queue_families = &get_device().queue_families;
+
+                GpuBuffer::new_shared(
+                    size,
+                    buffer_usage,
+                    vma::MemoryUsage::AutoPreferDevice,
+                    vma::AllocationCreateFlags::empty(),
+                    &[queue_families.graphics, queue_families.transfer],
+                )
+            }
+            MeshUsage::Dynamic => GpuBuffer::new(
+                size,
+                buffer_usage,
+                vma::MemoryUsage::Auto,
+                vma::AllocationCreateFlags::HOST_ACCESS_SEQUENTIAL_WRITE,
+            ),
+        }
+    }
+
+    fn write(&mut self, allocation: &MeshAllocation, mesh: &Mesh) -> BufferResult<()> {
+        let vertex_offset = allocation.vertices.start as usize * size_of::<Vertex>();
+        let index_offset = allocation.indices.start as usize * size_of::<u32>();
+
+        if self.usage == MeshUsage::Static {
+            let device = get_device();
+
+            self.vertex_buffer
+                .upload(device, vertex_offset as vk::DeviceSize, &mesh.vertices)?;
+            self.index_buffer
+                .upload(device, index_offset as vk::DeviceSize, &mesh.indices)?;
+
+            return Ok(());
+        }
+
+        {
+            let mut mapped = self
+                .vertex_buffer
+                .map_as_slice(vertex_offset, mesh.vertices.len())?;
+            mapped.copy_from_slice(&mesh.vertices);
+        }
+
+        {
+            let mut mapped = self
+                .index_buffer
+                .map_as_slice(index_offset, mesh.indices.len())?;
+            mapped.copy_from_slice(&mesh.indices);
+        }
+
+        self.vertex_buffer.flush_range(
+            vertex_offset as vk::DeviceSize,
+            size_of_val(mesh.vertices.as_slice()) as vk::DeviceSize,
+        )?;
+        self.index_buffer.flush_range(
+            index_offset as vk::DeviceSize,
+            size_of_val(mesh.indices.as_slice()) as vk::DeviceSize,
+        )?;
+
+        Ok(())
+    }
+
+    fn destroy(&mut self, device: &LogicalDevice) {
+        self.vertex_buffer.destroy(device);
+        self.index_buffer.destroy(device);
+    }
+}
+
+/// Vertices and indices of every mesh drawn by any scene, in one pair of buffers
+/// per [`MeshUsage`].
+///
+/// A mesh is uploaded the first time a scene draws it and stays until it's
+/// [released](Self::release), scenes and their frames in flight only keep its offsets
+#[derive(Debug, Default)]
+pub struct MeshPool {
+    static_buffers: Option<MeshBuffers>,
+    dynamic_buffers: Option<MeshBuffers>,
+    allocations: HashMap<MeshHandle, MeshAllocation>,
+    /// Index of the next submission drawing from the pool
+    next_submission: u64,
+    /// Submissions which may still be drawing, with their fences
+    in_flight: VecDeque<(u64, vk::Fence)>,
+    released: Vec<ReleasedRanges>,
+}
+
+impl MeshPool {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Vertex and index buffers of meshes with `usage`, `None` until the first
+    /// such mesh is uploaded
+    pub fn buffers(&self, usage: MeshUsage) -> Option<(vk::Buffer, vk::Buffer)> {
+        self.region(usage).as_ref().map(|buffers| {
+            (
+                buffers.vertex_buffer.buffer(),
+                buffers.index_buffer.buffer(),
+            )
+        })
+    }
+
+    pub fn contains(&self, mesh: &MeshHandle) -> bool {
+        self.allocations.contains_key(mesh)
+    }
+
+    /// Location of `mesh` in the pool, uploading it from `mesh_store` if it
+    /// isn't there yet
+    pub fn acquire<S, A>(&mut self, mesh: MeshHandle, mesh_store: &A) -> Option<MeshMapping>
+    where
+        S: HandleStrategy<Mesh>,
+        A: AssetStore<Mesh, S>,
+    {
+        if let Some(allocation) = self.allocations.get(&mesh) {
+            return Some(allocation.mapping());
+        }
+
+        let data = mesh_store.get(&mesh)?;
+
+        match self.upload(mesh, data, mesh_store) {
+            Ok(mapping) => Some(mapping),
+            Err(err) => {
+                core_warn!("Failed to upload {mesh:?} into the mesh pool: {err}");
+                None
+            }
+        }
+    }
+
+    /// Frees the space of `mesh`, so the next [`acquire`](Self::acquire) uploads
+    /// it again. Frames in flight might still draw the mesh, its space is reused
+    /// once their fences signal, see [`submitted`](Self::submitted)
+    pub fn release(&mut self, mesh: MeshHandle) {
+        let Some(allocation) = self.allocations.remove(&mesh) else {
+            return;
+        };
+
+        self.released.push(ReleasedRanges {
+            submission: self.next_submission,
+            usage: allocation.usage,
+            vertices: allocation.vertices,
+            indices: allocation.indices,
+        });
+    }
+
+    /// Tracks a submission drawing from the pool until `fence` signals and frees
+    /// the space of released meshes no submission draws anymore
+    pub(crate) fn submitted(&mut self, device: &LogicalDevice, fence: vk::Fence) {
+        self.in_flight.push_back((self.next_submission, fence));
+        self.next_submission += 1;
+
+        // Fences are waited for before they are reset, so a submission is also
+        // done when its fence was submitted again since
+        while let Some(&(_, fence)) = self.in_flight.front() {
+            let reused = self
+                .in_flight
+                .iter()
+                .skip(1)
+                .any(|(_, other)| *other == fence);
+            let signaled = unsafe { device.get_fence_status(fence) }.unwrap_or(false);
+
+            if !reused && !signaled {
+                break;
+            }
+
+            self.in_flight.pop_front();
+        }
+
+        let pending = self
+            .in_flight
+            .front()
+            .map_or(self.next_submission, |(submission, _)| *submission);
+
+        let (done, released) = std::mem::take(&mut self.released)
+            .into_iter()
+            .partition::<Vec<_>, _>(|released| released.submission <= pending);
+        self.released = released;
+
+        for released in done {
+            if let Some(buffers) = self.region_mut(released.usage) {
+                buffers.vertices.free(released.vertices);
+                buffers.indices.free(released.indices);
+            }
+        }
+    }
+
+    /// Destroys the buffers, every mesh is uploaded again on the next acquire
+    pub(crate) fn release_gpu_resources(&mut self, device: &LogicalDevice) {
+        [self.static_buffers.take(), self.dynamic_buffers.take()]
+            .into_iter()
+            .flatten()
+            .for_each(|mut buffers| buffers.destroy(device));
+
+        self.allocations.clear();
+        self.in_flight.clear();
+        self.released.clear();
+    }
+
+    fn region(&self, usage: MeshUsage) -> &Option<MeshBuffers> {
+        match usage {
+            MeshUsage::Static => &self.static_buffers,
+            MeshUsage::Dynamic => &self.dynamic_buffers,
+        }
+    }
+
+    fn region_mut(&mut self, usage: MeshUsage) -> &mut Option<MeshBuffers> {
+        match usage {
+            MeshUsage::Static => &mut self.static_buffers,
+            MeshUsage::Dynamic => &mut self.dynamic_buffers,
+        }
+    }
+
+    fn upload<S, A>(
+        &mut self,
+        handle: MeshHandle,
+        mesh: &Mesh,
+        mesh_store: &A,
+    ) -> BufferResult<MeshMapping>
+    where
+        S: HandleStrategy<Mesh>,
+        A: AssetStore<Mesh, S>,
+    {
+        let usage = mesh.usage;
+        let vertex_len = mesh.vertices.len() as u32;
+        let index_len = mesh.indices.len() as u32;
+
+        let region = self.region_mut(usage);
+        let buffers = match region {
+            Some(buffers) => buffers,
+            None => region.insert(MeshBuffers::new(
+                usage,
+                INITIAL_VERTEX_LEN.max(vertex_len),
+                INITIAL_INDEX_LEN.max(index_len),
+            )?),
+        };
+
+        let vertices = buffers.vertices.allocate(vertex_len);
+        let indices = buffers.indices.allocate(index_len);
+
+        let (vertices, indices) = match (vertices, indices) {
+            (Some(vertices), Some(indices)) => (vertices, indices),
+            (vertices, indices) => {
+                vertices
+                    .into_iter()
+                    .for_each(|range| buffers.vertices.free(range));
+                indices
+                    .into_iter()
+                    .for_each(|range| buffers.indices.free(range));
+
+                self.grow(usage, vertex_len, index_len, mesh_store)?;
+
+                let buffers = self.region_mut(usage).as_mut().unwrap();
+                (
+                    buffers.vertices.allocate(vertex_len).unwrap(),
+                    buffers.indices.allocate(index_len).unwrap(),
+                )
+            }
+        };
+
+        let allocation = MeshAllocation {
+            vertices,
+            indices,
+            bounds: mesh.bounding_sphere(),
+            usage,
+        };
+
+        self.region_mut(usage)
+            .as_mut()
+            .unwrap()
+            .write(&allocation, mesh)?;
+
+        let mapping = allocation.mapping();
+        self.allocations.insert(handle, allocation);
+
+        Ok(mapping)
+    }
+
+    /// Recreates the buffers of `usage` with room for at least `vertex_len` and
+    /// `index_len` more elements and uploads their meshes again at the old locations
+    fn grow<S, A>(
+        &mut self,
+        usage: MeshUsage,
+        vertex_len: u32,
+        index_len: u32,
+        mesh_store: &A,
+    ) -> BufferResult<()>
+    where
+        S: HandleStrategy<Mesh>,
+        A: AssetStore<Mesh, S>,
+    {
+        let device = get_device();
+
+        let MeshBuffers {
+            mut vertex_buffer,
+            mut index_buffer,
+            mut vertices,
+            mut indices,
+            ..
+        } = self.region_mut(usage).take().unwrap();
+
+        let vertex_capacity = (vertices.capacity * 2).max(vertices.capacity + vertex_len);
+        let index_capacity = (indices.capacity * 2).max(indices.capacity + index_len);
+
+        core_info!(
+            "Growing the {usage:?} mesh pool to {vertex_capacity} vertices and {index_capacity} indices"
+        );
+
+        vertices.grow(vertex_capacity);
+        indices.grow(index_capacity);
+
+        let mut buffers = MeshBuffers {
+            vertices,
+            indices,
+            ..MeshBuffers::new(usage, vertex_capacity, index_capacity)?
+        };
+
+        unsafe { device.device_wait_idle()? };
+
+        vertex_buffer.destroy(device);
+        index_buffer.destroy(device);
+
+        let mut lost = Vec::new();
+
+        for (handle, allocation) in self.allocations.iter() {
+            if allocation.usage != usage {
+                continue;
+            }
+
+            match mesh_store.get(handle) {
+                Some(mesh)
+                    if mesh.vertices.len() == allocation.vertices.len()
+                        && mesh.indices.len() == allocation.indices.len() =>
+                {
+                    buffers.write(allocation, mesh)?
+                }
+                _ => lost.push(*handle),
+            }
+        }
+
+        for handle in lost {
+            core_warn!("{handle:?} was replaced without being released from the mesh pool");
+
+            let allocation = self.allocations.remove(&handle).unwrap();
+            buffers.vertices.free(allocation.vertices);
+            buffers.indices.free(allocation.indices);
+        }
+
+        *self.region_mut(usage) = Some(buffers);
+
+        Ok(())
+    }
+}
@@ -1,8 +1,9 @@
-use std::ffi::c_void;
+use std::time::Duration;
 
 use ash::{nv::shader_subgroup_partitioned, vk};
-use bizarre_core::{handle::IntoHandle, Handle};
-use bizarre_log::{core_error, core_info, core_trace, core_warn};
+use bizarre_core::{handle::IntoHandle, DerivedHandle, Handle};
+use bizarre_log::{core_error, core_info, core_trace, core_warn_throttle};
+use bizarre_sdl::window::{Window, WindowHandle};
 use nalgebra_glm::UVec2;
 use thiserror::Error;
 
@@ -11,6 +12,7 @@ use crate::{
     image::VulkanImage,
     instance::VulkanInstance,
     render_target::{ImageRenderTarget, RenderData},
+    surface::create_surface,
     vulkan_context::{get_device, get_instance},
 };
 
@@ -23,11 +25,22 @@ pub enum PresentError {
     #[error("Invalid present target")]
     InvalidPresentTarget,
     #[error(transparent)]
-    VulkanError(#[from] vk::Result),
+    VulkanError(vk::Result),
+    #[error("Vulkan device lost")]
+    DeviceLost,
     #[error("Present must be skipped")]
     PresentSkipped,
 }
 
+impl From<vk::Result> for PresentError {
+    fn from(value: vk::Result) -> Self {
+        match value {
+            vk::Result::ERROR_DEVICE_LOST => Self::DeviceLost,
+            value => Self::VulkanError(value),
+        }
+    }
+}
+
 pub type PresentResult<T> = Result<T, PresentError>;
 
 #[derive(Clone, Debug)]
@@ -81,7 +94,7 @@ impl SwapchainSupportInfo {
 }
 
 pub struct PresentTarget {
-    window_id: usize,
+    window: WindowHandle,
     surface_loader: ash::khr::surface::Instance,
     surface: vk::SurfaceKHR,
     swapchain_loader: ash::khr::swapchain::Device,
@@ -89,6 +102,8 @@ pub struct PresentTarget {
     size: UVec2,
     surface_format: vk::SurfaceFormatKHR,
     present_mode: vk::PresentModeKHR,
+    /// Picks a present mode waiting for the display refresh, see [`PresentTarget::set_vsync`]
+    vsync: bool,
     images: Vec<vk::Image>,
     image_views: Vec<vk::ImageView>,
     present_cmd_buffers: Vec<vk::CommandBuffer>,
@@ -96,13 +111,18 @@ pub struct PresentTarget {
     image_acquired_fences: Vec<vk::Fence>,
     image_ready: Vec<vk::Semaphore>,
     image_ready_fences: Vec<vk::Fence>,
+    /// The window is shown and not minimized, see [`PresentTarget::set_visible`]
+    visible: bool,
 
     next_image_index: u32,
 }
 
+/// Present targets share the handles of their windows
+impl DerivedHandle<Window> for PresentTarget {}
+
 impl IntoHandle for PresentTarget {
     fn into_handle(&self) -> Handle<Self> {
-        Handle::from_raw(self.window_id)
+        Handle::derived_from(&self.window)
     }
 }
 
@@ -111,7 +131,7 @@ impl PresentTarget {
         cmd_pool: vk::CommandPool,
         image_count: u32,
         surface: vk::SurfaceKHR,
-        window_id: usize,
+        window: WindowHandle,
     ) -> Result<Self, vk::Result> {
         let instance = get_instance();
         let device = get_device();
@@ -121,7 +141,7 @@ impl PresentTarget {
 
         let support = SwapchainSupportInfo::query_support_info(instance, *device.physical, surface);
 
-        let present_mode = choose_present_mode(&support.present_modes);
+        let present_mode = choose_present_mode(&support.present_modes, true);
         let format = choose_surface_format(&support.formats);
 
         let (extent, swapchain, images, image_views) = create_swapchain(
@@ -207,6 +227,7 @@ impl PresentTarget {
             swapchain,
             surface_format: *format,
             present_mode,
+            vsync: true,
             images,
             size: UVec2::new(extent.width, extent.height),
             image_views,
@@ -215,35 +236,30 @@ impl PresentTarget {
             image_acquired_fences,
             image_ready,
             image_ready_fences,
-            window_id,
+            window,
 
+            visible: true,
             next_image_index: 0,
         };
 
         Ok(present_target)
     }
 
-    pub(crate) unsafe fn new(
+    /// Creates a surface for `window` on its windowing backend and a swapchain
+    /// presenting to it
+    pub(crate) fn new(
         cmd_pool: vk::CommandPool,
         image_count: u32,
-        extent: UVec2,
-        display: *mut vk::wl_display,
-        surface: *mut c_void,
-        window_id: usize,
+        window: &bizarre_sdl::window::Window,
     ) -> Result<Self, vk::Result> {
-        let instance = get_instance();
-        let device = get_device();
-
-        let wl_surface_loader =
-            ash::khr::wayland_surface::Instance::new(&instance.entry, &instance.instance);
-
-        let create_info = vk::WaylandSurfaceCreateInfoKHR::default()
-            .display(display)
-            .surface(surface);
+        let surface = create_surface(get_instance(), window)?;
 
-        let surface = wl_surface_loader.create_wayland_surface(&create_info, None)?;
-
-        Self::new2(cmd_pool, image_count, surface, window_id)
+        Self::new2(
+            cmd_pool,
+            image_count,
+            surface,
+            WindowHandle::from_raw(window.id() as usize),
+        )
     }
 
     pub fn image_count(&self) -> u32 {
@@ -277,7 +293,10 @@ impl PresentTarget {
             unsafe {
                 device.destroy_semaphore(image_acquired, None);
             }
-            core_warn!("Swapchain is suboptimal! Recreating...");
+            core_warn_throttle!(
+                Duration::from_secs(1),
+                "Swapchain is suboptimal! Recreating..."
+            );
             self.recreate_swapchain().unwrap();
             self.acquire_or_recreate(true)
         } else {
@@ -285,10 +304,13 @@ impl PresentTarget {
         }
     }
 
+    /// Records the blit of the `render_extent` part of `render_image` onto the
+    /// whole next swapchain image
     pub fn record_present(
         &mut self,
         device: &LogicalDevice,
         render_image: &VulkanImage,
+        render_extent: UVec2,
     ) -> PresentResult<PresentData> {
         let (image_index, image_acquired, image_acquired_fence) = self.acquire_or_recreate(false);
 
@@ -311,7 +333,7 @@ impl PresentTarget {
         let image_ready = self.image_ready[image_index as usize];
         let image_ready_fence = self.image_ready_fences[image_index as usize];
 
-        self.record_present_cmd(device, cmd, image, render_image)?;
+        self.record_present_cmd(device, cmd, image, render_image, render_extent)?;
 
         Ok(PresentData {
             cmd_buffer: Some(cmd),
@@ -329,12 +351,46 @@ impl PresentTarget {
         cmd: vk::CommandBuffer,
         present_image: vk::Image,
         render_image: &VulkanImage,
+        render_extent: UVec2,
     ) -> PresentResult<()> {
         let begin_info = vk::CommandBufferBeginInfo::default();
 
         unsafe { device.begin_command_buffer(cmd, &begin_info) }?;
 
+        // Outputs of sampled render targets are left readable by shaders
+        let render_image_layout = render_image.image_layout;
+        let render_image_barrier = |old_layout, new_layout| {
+            vk::ImageMemoryBarrier2::default()
+                .src_stage_mask(vk::PipelineStageFlags2::ALL_COMMANDS)
+                .dst_stage_mask(vk::PipelineStageFlags2::ALL_COMMANDS)
+                .src_queue_family_index(vk::QUEUE_FAMILY_IGNORED)
+                .dst_queue_family_index(vk::QUEUE_FAMILY_IGNORED)
+                .image(render_image.image)
+                .subresource_range(
+                    vk::ImageSubresourceRange::default()
+                        .aspect_mask(vk::ImageAspectFlags::COLOR)
+                        .layer_count(1)
+                        .level_count(1),
+                )
+                .old_layout(old_layout)
+                .new_layout(new_layout)
+        };
+        let needs_render_image_transition =
+            render_image_layout != vk::ImageLayout::TRANSFER_SRC_OPTIMAL;
+
         unsafe {
+            if needs_render_image_transition {
+                let barriers = [render_image_barrier(
+                    render_image_layout,
+                    vk::ImageLayout::TRANSFER_SRC_OPTIMAL,
+                )];
+
+                device.cmd_pipeline_barrier2(
+                    cmd,
+                    &vk::DependencyInfo::default().image_memory_barriers(&barriers),
+                );
+            }
+
             let to_transfer_barriers = [vk::ImageMemoryBarrier2::default()
                 .src_queue_family_index(vk::QUEUE_FAMILY_IGNORED)
                 .dst_queue_family_index(vk::QUEUE_FAMILY_IGNORED)
@@ -364,8 +420,8 @@ impl PresentTarget {
             let src_offsets = [
                 vk::Offset3D::default(),
                 vk::Offset3D {
-                    x: render_image.size.x as i32,
-                    y: render_image.size.y as i32,
+                    x: render_extent.x as i32,
+                    y: render_extent.y as i32,
                     z: 1,
                 },
             ];
@@ -378,6 +434,13 @@ impl PresentTarget {
                     z: 1,
                 },
             ];
+            // Scaled renders are filtered to the window size
+            let filter = if render_extent == self.size {
+                vk::Filter::NEAREST
+            } else {
+                vk::Filter::LINEAR
+            };
+
             let regions = [vk::ImageBlit2::default()
                 .src_offsets(src_offsets.clone())
                 .dst_offsets(dst_offsets.clone())
@@ -389,7 +452,7 @@ impl PresentTarget {
                 .src_image_layout(vk::ImageLayout::TRANSFER_SRC_OPTIMAL)
                 .dst_image(present_image)
                 .dst_image_layout(vk::ImageLayout::TRANSFER_DST_OPTIMAL)
-                .filter(vk::Filter::NEAREST)
+                .filter(filter)
                 .regions(&regions);
 
             device.cmd_blit_image2(cmd, &blit_info);
@@ -416,6 +479,18 @@ impl PresentTarget {
 
             device.cmd_pipeline_barrier2(cmd, &dependency_info);
 
+            if needs_render_image_transition {
+                let barriers = [render_image_barrier(
+                    vk::ImageLayout::TRANSFER_SRC_OPTIMAL,
+                    render_image_layout,
+                )];
+
+                device.cmd_pipeline_barrier2(
+                    cmd,
+                    &vk::DependencyInfo::default().image_memory_barriers(&barriers),
+                );
+            }
+
             device.end_command_buffer(cmd)?;
         }
 
@@ -426,6 +501,45 @@ impl PresentTarget {
         self.size
     }
 
+    /// Hidden targets skip presenting and the render targets presented to them
+    /// skip rendering, kept up to date by
+    /// [`update_present_target_visibility`](crate::ecs::update_present_target_visibility)
+    pub fn set_visible(&mut self, visible: bool) {
+        self.visible = visible;
+    }
+
+    pub fn is_visible(&self) -> bool {
+        self.visible
+    }
+
+    /// Without vsync frames are presented immediately if the surface supports it,
+    /// tearing included. The swapchain is recreated when the present mode changes
+    pub fn set_vsync(&mut self, vsync: bool) -> PresentResult<()> {
+        if self.vsync == vsync {
+            return Ok(());
+        }
+
+        self.vsync = vsync;
+
+        let support = SwapchainSupportInfo::query_support_info(
+            get_instance(),
+            *get_device().physical,
+            self.surface,
+        );
+        let present_mode = choose_present_mode(&support.present_modes, vsync);
+
+        if present_mode == self.present_mode {
+            return Ok(());
+        }
+
+        self.present_mode = present_mode;
+        self.recreate_swapchain()
+    }
+
+    pub fn vsync(&self) -> bool {
+        self.vsync
+    }
+
     fn recreate_swapchain(&mut self) -> PresentResult<()> {
         let device = get_device();
 
@@ -470,7 +584,9 @@ impl PresentTarget {
         self.recreate_swapchain()
     }
 
-    pub fn destroy(&mut self) {
+    /// Destroys the swapchain and everything created for it on the device,
+    /// the surface is kept
+    pub(crate) fn release_swapchain(&mut self) {
         let device = get_device();
 
         let fences = [
@@ -479,8 +595,10 @@ impl PresentTarget {
         ]
         .concat();
 
-        if let Err(err) = unsafe { device.wait_for_fences(&fences, true, u64::MAX) } {
-            core_error!("PresentTarget::recreate_swapchain: failed to wait for fences: {err:?}",)
+        if !fences.is_empty() {
+            if let Err(err) = unsafe { device.wait_for_fences(&fences, true, u64::MAX) } {
+                core_error!("PresentTarget::release_swapchain: failed to wait for fences: {err:?}",)
+            }
         }
 
         self.image_views
@@ -495,11 +613,42 @@ impl PresentTarget {
             .drain(..)
             .for_each(|semaphore| unsafe { device.destroy_semaphore(semaphore, None) });
 
-        unsafe {
-            self.swapchain_loader
-                .destroy_swapchain(self.swapchain, None)
+        self.image_acquired_fences
+            .drain(..)
+            .chain(self.image_ready_fences.drain(..))
+            .for_each(|fence| unsafe { device.destroy_fence(fence, None) });
+
+        self.present_cmd_buffers.clear();
+
+        if self.swapchain != vk::SwapchainKHR::null() {
+            unsafe {
+                self.swapchain_loader
+                    .destroy_swapchain(self.swapchain, None)
+            }
+            self.swapchain = vk::SwapchainKHR::null();
+        }
+    }
+
+    /// Creates a new swapchain for the same surface on the current device, must
+    /// be called after [`release_swapchain`](Self::release_swapchain)
+    pub(crate) fn restore_swapchain(&mut self, cmd_pool: vk::CommandPool) -> PresentResult<()> {
+        let mut restored = Self::new2(cmd_pool, self.image_count(), self.surface, self.window)?;
+
+        std::mem::swap(self, &mut restored);
+
+        // `restored` holds the released target now, the surface belongs to `self`
+        restored.surface = vk::SurfaceKHR::null();
+
+        self.set_vsync(restored.vsync)
+    }
+
+    pub fn destroy(&mut self) {
+        self.release_swapchain();
+
+        if self.surface != vk::SurfaceKHR::null() {
+            unsafe { self.surface_loader.destroy_surface(self.surface, None) }
+            self.surface = vk::SurfaceKHR::null();
         }
-        unsafe { self.surface_loader.destroy_surface(self.surface, None) }
     }
 }
 
@@ -523,7 +672,11 @@ fn choose_surface_format(formats: &Vec<vk::SurfaceFormatKHR>) -> &vk::SurfaceFor
 }
 
 #[inline]
-fn choose_present_mode(modes: &Vec<vk::PresentModeKHR>) -> vk::PresentModeKHR {
+fn choose_present_mode(modes: &Vec<vk::PresentModeKHR>, vsync: bool) -> vk::PresentModeKHR {
+    if !vsync && modes.contains(&vk::PresentModeKHR::IMMEDIATE) {
+        return vk::PresentModeKHR::IMMEDIATE;
+    }
+
     for mode in modes {
         if mode == &vk::PresentModeKHR::MAILBOX {
             return *mode;
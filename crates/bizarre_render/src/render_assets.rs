@@ -2,18 +2,18 @@ use std::{collections::HashMap, ffi::c_void, path::Path};
 
 use std::fmt::Debug;
 
-use ash::vk::{self, Handle as _};
+use ash::vk;
 use bizarre_core::handle::IntoHandle;
 use bizarre_core::{
     handle::{DenseHandleStrategy, HandlePlacement, HandleStrategy, SparseHandleStrategy},
     Handle,
 };
 use bizarre_ecs::prelude::Resource;
-use bizarre_log::core_info;
+use bizarre_log::core_error;
 use nalgebra_glm::UVec2;
 
 use crate::antialiasing::Antialiasing;
-use crate::material::pipeline::{VulkanPipeline, VulkanPipelineRequirements};
+use crate::material::pipeline::VulkanPipelineRequirements;
 use crate::scene::SceneHandle;
 use crate::{
     material::{
@@ -21,10 +21,15 @@ use crate::{
         Material, MaterialHandle,
     },
     mesh::{Mesh, MeshHandle},
+    mesh_pool::MeshPool,
     present_target::{PresentTarget, PresentTargetHandle},
-    render_target::{RenderTargetHandle, SwapchainRenderTarget},
+    render_target::{DynamicResolution, RenderTargetHandle, SwapchainRenderTarget},
+    render_texture::{RenderTexture, RenderTextureHandle},
+    renderer::RenderResult,
+    sampler::SamplerDesc,
     scene::Scene,
-    vulkan_context::{get_device, get_instance},
+    texture::{Texture, TextureHandle, TextureResult},
+    vulkan_context::get_device,
 };
 
 pub trait AssetStore<A, S: HandleStrategy<A>> {
@@ -57,6 +62,42 @@ impl<A> SparseAssetStore<A> {
     pub fn new() -> Self {
         Default::default()
     }
+
+    pub fn iter(&self) -> impl Iterator<Item = (Handle<A>, &A)> {
+        self.data.iter().map(|(handle, asset)| (*handle, asset))
+    }
+
+    pub fn iter_mut(&mut self) -> impl Iterator<Item = (Handle<A>, &mut A)> {
+        self.data.iter_mut().map(|(handle, asset)| (*handle, asset))
+    }
+
+    pub fn handles(&self) -> impl Iterator<Item = Handle<A>> + '_ {
+        self.data.keys().copied()
+    }
+
+    pub fn len(&self) -> usize {
+        self.data.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.data.is_empty()
+    }
+
+    /// Removes every asset `f` returns `false` for
+    pub fn retain(&mut self, mut f: impl FnMut(Handle<A>, &mut A) -> bool)
+    where
+        A: IntoHandle,
+    {
+        let handle_strategy = &mut self.handle_strategy;
+
+        self.data.retain(|handle, asset| {
+            let keep = f(*handle, asset);
+            if !keep {
+                handle_strategy.mark_deleted(*handle);
+            }
+            keep
+        });
+    }
 }
 
 impl<A: IntoHandle> AssetStore<A, SparseHandleStrategy<A>> for SparseAssetStore<A> {
@@ -98,15 +139,82 @@ impl<T> Default for DenseAssetStore<T> {
     }
 }
 
+impl<T> DenseAssetStore<T> {
+    /// Allocates a handle for an asset which will be inserted later with
+    /// [`insert_reserved`](Self::insert_reserved)
+    pub fn reserve(&self) -> Handle<T> {
+        self.handle_strategy.reserve().0
+    }
+
+    /// Puts `asset` behind a handle from [`reserve`](Self::reserve), returns the
+    /// asset previously stored there
+    pub fn insert_reserved(&mut self, handle: Handle<T>, asset: T) -> Option<T> {
+        let index = handle.as_raw();
+
+        if index >= self.data.len() {
+            self.data.resize_with(index + 1, || None);
+        }
+
+        self.data[index].replace(asset)
+    }
+
+    /// Stored assets in handle order, reserved handles without an asset are skipped
+    pub fn iter(&self) -> impl Iterator<Item = (Handle<T>, &T)> {
+        self.data
+            .iter()
+            .enumerate()
+            .filter_map(|(index, asset)| Some((Handle::from_raw(index), asset.as_ref()?)))
+    }
+
+    pub fn iter_mut(&mut self) -> impl Iterator<Item = (Handle<T>, &mut T)> {
+        self.data
+            .iter_mut()
+            .enumerate()
+            .filter_map(|(index, asset)| Some((Handle::from_raw(index), asset.as_mut()?)))
+    }
+
+    /// Handles of the stored assets, in order
+    pub fn handles(&self) -> impl Iterator<Item = Handle<T>> + '_ {
+        self.iter().map(|(handle, _)| handle)
+    }
+
+    pub fn len(&self) -> usize {
+        self.data.iter().filter(|asset| asset.is_some()).count()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.data.iter().all(Option::is_none)
+    }
+
+    /// Removes every asset `f` returns `false` for, their handles get recycled
+    /// the same way as with [`remove`](AssetStore::remove)
+    pub fn retain(&mut self, mut f: impl FnMut(Handle<T>, &mut T) -> bool) {
+        for (index, slot) in self.data.iter_mut().enumerate() {
+            let Some(asset) = slot else {
+                continue;
+            };
+
+            let handle = Handle::from_raw(index);
+
+            if !f(handle, asset) {
+                *slot = None;
+                self.handle_strategy.mark_deleted(handle);
+            }
+        }
+    }
+
+    /// Moves the asset out while keeping its handle alive, put it back with
+    /// [`insert_reserved`](Self::insert_reserved)
+    pub fn take(&mut self, handle: &Handle<T>) -> Option<T> {
+        self.data.get_mut(handle.as_raw())?.take()
+    }
+}
+
 impl<T> AssetStore<T, DenseHandleStrategy<T>> for DenseAssetStore<T> {
     fn insert(&mut self, asset: T) -> Handle<T> {
-        let (handle, reused) = self.handle_strategy.new_handle(&asset);
+        let (handle, _) = self.handle_strategy.new_handle(&asset);
 
-        if reused {
-            self.data[handle.as_raw()] = Some(asset);
-        } else {
-            self.data.push(Some(asset))
-        }
+        self.insert_reserved(handle, asset);
 
         handle
     }
@@ -133,14 +241,41 @@ impl<T> AssetStore<T, DenseHandleStrategy<T>> for DenseAssetStore<T> {
     }
 }
 
+/// What's left of GPU assets after [`RenderAssets::release_gpu_resources`]
+pub(crate) struct ReleasedGpuAssets {
+    render_targets: Vec<ReleasedRenderTarget>,
+}
+
+/// Settings a render target is recreated with
+struct ReleasedRenderTarget {
+    handle: RenderTargetHandle,
+    extent: UVec2,
+    samples: vk::SampleCountFlags,
+    image_count: u32,
+    depth_prepass: bool,
+    sampled: bool,
+    temporal_antialiasing: bool,
+    render_scale: f32,
+    dynamic_resolution: Option<DynamicResolution>,
+    composition_material: Option<MaterialHandle>,
+}
+
 #[derive(Default, Resource)]
 pub struct RenderAssets {
     pub render_targets: DenseAssetStore<SwapchainRenderTarget>,
     pub present_targets: SparseAssetStore<PresentTarget>,
     pub meshes: DenseAssetStore<Mesh>,
+    /// GPU copies of the meshes drawn by scenes
+    pub mesh_pool: MeshPool,
     pub materials: DenseAssetStore<Material>,
     pub material_instances: DenseAssetStore<MaterialInstance>,
+    /// Instances handed out by [`shared_material_instance`](Self::shared_material_instance)
+    shared_material_instances: HashMap<MaterialHandle, MaterialInstanceHandle>,
     pub scenes: DenseAssetStore<Scene>,
+    pub render_textures: DenseAssetStore<RenderTexture>,
+    pub textures: DenseAssetStore<Texture>,
+    /// Present targets every render target was presented to
+    presentations: HashMap<RenderTargetHandle, Vec<PresentTargetHandle>>,
 }
 
 impl RenderAssets {
@@ -152,10 +287,9 @@ impl RenderAssets {
         &mut self,
         pipeline_requirements: &VulkanPipelineRequirements,
     ) -> MaterialHandle {
-        let pipeline =
-            VulkanPipeline::from_requirements(pipeline_requirements, None, get_device()).unwrap();
-
-        let material = Material::new(pipeline, &pipeline_requirements.bindings);
+        let material =
+            Material::from_requirements(pipeline_requirements, &pipeline_requirements.bindings)
+                .unwrap();
 
         let handle = self.materials.insert(material);
 
@@ -179,6 +313,23 @@ impl RenderAssets {
         Some((handle, instance))
     }
 
+    /// Instance of the material shared by every object that doesn't bind anything
+    /// of its own. Objects using the same instance and mesh end up in one batch
+    pub fn shared_material_instance(
+        &mut self,
+        material_handle: MaterialHandle,
+    ) -> Option<MaterialInstanceHandle> {
+        if let Some(handle) = self.shared_material_instances.get(&material_handle) {
+            return Some(*handle);
+        }
+
+        let (handle, _) = self.create_material_instance(material_handle)?;
+        self.shared_material_instances
+            .insert(material_handle, handle);
+
+        Some(handle)
+    }
+
     pub fn material_with_instance(
         &self,
         instance_handle: &MaterialInstanceHandle,
@@ -202,17 +353,8 @@ impl RenderAssets {
         window: &bizarre_sdl::window::Window,
         image_count: u32,
     ) -> PresentTargetHandle {
-        let instance_handle = get_instance().handle().as_raw() as usize;
-        let surface = window.vulkan_create_surface(instance_handle).unwrap();
-        let surface = vk::SurfaceKHR::from_raw(surface);
-
-        let present_target = PresentTarget::new2(
-            get_device().cmd_pool,
-            image_count,
-            surface,
-            window.id() as usize,
-        )
-        .unwrap();
+        let present_target =
+            PresentTarget::new(get_device().cmd_pool, image_count, window).unwrap();
 
         self.present_targets.insert(present_target)
     }
@@ -224,6 +366,46 @@ impl RenderAssets {
         self.present_targets.get_mut(handle)
     }
 
+    /// Destroys the present target, render targets presented only to it count
+    /// as hidden afterwards
+    pub fn destroy_present_target(&mut self, handle: &PresentTargetHandle) {
+        let Some(mut present_target) = self.present_targets.remove(*handle) else {
+            return;
+        };
+
+        present_target.destroy();
+
+        for present_targets in self.presentations.values_mut() {
+            present_targets.retain(|present_target| present_target != handle);
+        }
+    }
+
+    /// Whether anything shows the output of the render target. Render targets
+    /// that were never presented count as visible, the others are visible while
+    /// any of their present targets is
+    pub fn is_render_target_visible(&self, handle: &RenderTargetHandle) -> bool {
+        let Some(present_targets) = self.presentations.get(handle) else {
+            return true;
+        };
+
+        present_targets.iter().any(|present_target| {
+            self.present_targets
+                .get(present_target)
+                .is_none_or(|present_target| present_target.is_visible())
+        })
+    }
+
+    pub(crate) fn record_presentation(
+        &mut self,
+        render_target: RenderTargetHandle,
+        present_target: PresentTargetHandle,
+    ) {
+        let present_targets = self.presentations.entry(render_target).or_default();
+        if !present_targets.contains(&present_target) {
+            present_targets.push(present_target);
+        }
+    }
+
     pub fn create_swapchain_render_target(
         &mut self,
         extent: UVec2,
@@ -239,13 +421,52 @@ impl RenderAssets {
             antialiasing.into(),
             image_count,
         )
-        .unwrap();
+        .unwrap()
+        .with_temporal_antialiasing(antialiasing == Antialiasing::Taa);
 
         let handle = self.render_targets.insert(render_target);
 
         handle
     }
 
+    /// Makes the output of `render_target` sampleable by materials through the
+    /// returned handle. Returns `None` if the render target doesn't exist
+    pub fn create_render_texture(
+        &mut self,
+        render_target: RenderTargetHandle,
+        sampler: SamplerDesc,
+    ) -> Option<RenderTextureHandle> {
+        self.render_targets
+            .get_mut(&render_target)?
+            .set_sampled(true);
+
+        Some(
+            self.render_textures
+                .insert(RenderTexture::new(render_target, sampler)),
+        )
+    }
+
+    /// Uploads 8-bit RGBA `pixels`, see [`Texture::from_rgba8`]
+    pub fn create_texture(
+        &mut self,
+        size: UVec2,
+        format: vk::Format,
+        pixels: Vec<u8>,
+        sampler: SamplerDesc,
+    ) -> TextureResult<TextureHandle> {
+        let texture = Texture::from_rgba8(size, format, pixels, sampler)?;
+        Ok(self.textures.insert(texture))
+    }
+
+    /// Reads and uploads a KTX2 texture, see [`Texture::from_ktx2`]
+    pub fn load_texture<P>(&mut self, path: P, sampler: SamplerDesc) -> TextureResult<TextureHandle>
+    where
+        P: AsRef<Path>,
+    {
+        let texture = Texture::load_ktx2(path, sampler)?;
+        Ok(self.textures.insert(texture))
+    }
+
     pub fn create_scene(&mut self, image_count: u32) -> SceneHandle {
         self.scenes
             .insert(Scene::new(image_count as usize).unwrap())
@@ -254,4 +475,136 @@ impl RenderAssets {
     pub fn scene_mut(&mut self, handle: &SceneHandle) -> Option<&mut Scene> {
         self.scenes.get_mut(handle)
     }
+
+    /// Destroys every asset after the device goes idle: scenes first, then render
+    /// targets, materials, textures and meshes. Run by [`teardown_render`](crate::ecs::teardown_render)
+    /// on shutdown, before the renderer is dropped
+    pub fn destroy(mut self) {
+        let device = get_device();
+
+        if let Err(err) = unsafe { device.device_wait_idle() } {
+            core_error!("RenderAssets::destroy: failed to wait for the device: {err}");
+        }
+
+        self.scenes.retain(|_, scene| {
+            scene.release_gpu_resources(device);
+            false
+        });
+
+        self.render_textures.retain(|_, _| false);
+        self.render_targets.retain(|_, _| false);
+        self.present_targets.retain(|_, target| {
+            target.release_swapchain();
+            false
+        });
+
+        self.shared_material_instances.clear();
+        self.material_instances.retain(|_, _| false);
+        self.materials.retain(|_, material| {
+            material.release(device);
+            false
+        });
+
+        self.textures.retain(|_, texture| {
+            texture.release();
+            false
+        });
+
+        self.mesh_pool.release_gpu_resources(device);
+    }
+
+    /// Destroys every asset object living on the current device. Handles stay
+    /// valid, the objects are rebuilt by [`restore_gpu_resources`](Self::restore_gpu_resources)
+    pub(crate) fn release_gpu_resources(&mut self) -> ReleasedGpuAssets {
+        let device = get_device();
+
+        self.scenes
+            .iter_mut()
+            .for_each(|(_, scene)| scene.release_gpu_resources(device));
+
+        self.mesh_pool.release_gpu_resources(device);
+
+        self.materials
+            .iter_mut()
+            .for_each(|(_, material)| material.release(device));
+
+        self.textures
+            .iter_mut()
+            .for_each(|(_, texture)| texture.release());
+
+        self.present_targets
+            .iter_mut()
+            .for_each(|(_, target)| target.release_swapchain());
+
+        let render_targets = self
+            .render_targets
+            .iter_mut()
+            .map(|(handle, target)| ReleasedRenderTarget {
+                handle,
+                extent: target.extent(),
+                samples: target.samples(),
+                image_count: target.image_count(),
+                depth_prepass: target.depth_prepass(),
+                sampled: target.is_sampled(),
+                temporal_antialiasing: target.temporal_antialiasing(),
+                render_scale: target.render_scale(),
+                dynamic_resolution: target.dynamic_resolution(),
+                composition_material: target.composition_material(),
+            })
+            .collect::<Vec<_>>();
+
+        for released in render_targets.iter() {
+            drop(self.render_targets.take(&released.handle));
+        }
+
+        ReleasedGpuAssets { render_targets }
+    }
+
+    /// Rebuilds assets released by [`release_gpu_resources`](Self::release_gpu_resources)
+    /// on the current device
+    pub(crate) fn restore_gpu_resources(
+        &mut self,
+        released: ReleasedGpuAssets,
+    ) -> RenderResult<()> {
+        let device = get_device();
+
+        for released in released.render_targets {
+            let target = SwapchainRenderTarget::new(
+                device,
+                released.extent,
+                device.cmd_pool,
+                released.samples,
+                released.image_count,
+            )?
+            .with_depth_prepass(released.depth_prepass)
+            .with_sampled(released.sampled)
+            .with_temporal_antialiasing(released.temporal_antialiasing)
+            .with_render_scale(released.render_scale)
+            .with_dynamic_resolution(released.dynamic_resolution)
+            .with_composition_material(released.composition_material);
+            self.render_targets.insert_reserved(released.handle, target);
+        }
+
+        for (_, target) in self.present_targets.iter_mut() {
+            target.restore_swapchain(device.cmd_pool)?;
+        }
+
+        for (_, texture) in self.textures.iter_mut() {
+            texture.restore()?;
+        }
+
+        for (handle, material) in self.materials.iter_mut() {
+            if !material.restore()? {
+                core_error!(
+                    "Material {handle:?} has no pipeline description and can't be restored"
+                );
+            }
+        }
+
+        for (_, scene) in self.scenes.iter_mut() {
+            scene.restore_gpu_resources()?;
+        }
+
+        Ok(())
+    }
 }
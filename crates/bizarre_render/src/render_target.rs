@@ -1,15 +1,18 @@
 use core::slice;
 use std::ptr::addr_of;
+use std::time::Duration;
 
 use ash::vk::{self};
 use bizarre_core::Handle;
 use bizarre_log::{core_error, core_fatal, core_info};
-use nalgebra_glm::UVec2;
+use nalgebra_glm::{UVec2, Vec2, Vec4};
 
 use crate::{
+    command_buffer::{flipped_viewport, RenderingInheritance},
     device::LogicalDevice,
     image::{VulkanImage, VulkanImageView},
     material::descriptor_buffer::DescriptorBuffer,
+    material::MaterialHandle,
     vulkan_context::{get_device, get_instance},
 };
 
@@ -36,10 +39,126 @@ pub struct RenderData2 {
 pub struct SwapchainRenderTarget {
     targets: Vec<ImageRenderTarget>,
     curr_image_index: usize,
+    extent: UVec2,
+    samples: vk::SampleCountFlags,
+    depth_prepass: bool,
+    sampled: bool,
+    /// Index of the target submitted last, its output is the one materials sample
+    last_rendered: usize,
+    temporal_antialiasing: bool,
+    /// Created by the first temporal resolve, shared by all targets
+    history: Option<TemporalHistory>,
+    /// Extent the images are drawn at, `extent` times `render_scale`
+    render_extent: UVec2,
+    render_scale: f32,
+    dynamic_resolution: Option<DynamicResolution>,
+    /// Timings of the last render whose results are available
+    stats: RenderStats,
+    /// Material of the composition pass, the built-in one if `None`
+    composition_material: Option<MaterialHandle>,
+}
+
+pub const MIN_RENDER_SCALE: f32 = 0.5;
+pub const MAX_RENDER_SCALE: f32 = 2.0;
+
+/// Frame times within this fraction of the target one keep the render scale
+const DYNAMIC_RESOLUTION_TOLERANCE: f32 = 0.1;
+/// Largest change of the render scale after a single frame, as a fraction of it
+const DYNAMIC_RESOLUTION_STEP: f32 = 0.1;
+
+/// Adapts the render scale of a target to keep its GPU frame time close to
+/// `target_frame_time`, see [`SwapchainRenderTarget::with_dynamic_resolution`]
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct DynamicResolution {
+    pub target_frame_time: Duration,
+    pub min_scale: f32,
+    pub max_scale: f32,
+}
+
+impl Default for DynamicResolution {
+    fn default() -> Self {
+        Self {
+            target_frame_time: Duration::from_micros(16_667),
+            min_scale: MIN_RENDER_SCALE,
+            max_scale: 1.0,
+        }
+    }
+}
+
+/// Timings of a render into a [`SwapchainRenderTarget`]
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct RenderStats {
+    /// Time the GPU spent on the render, `None` if the device can't write timestamps
+    pub gpu_frame_time: Option<Duration>,
+    /// Extent the render was for
+    pub extent: UVec2,
+    /// Extent the render was drawn at
+    pub render_extent: UVec2,
+}
+
+/// Outputs of temporal antialiasing, every frame blends the one resolved last
+/// into the other
+struct TemporalHistory {
+    images: [VulkanImage; 2],
+    /// Index of the image resolved last
+    current: usize,
+    /// Extent of the last resolve, `None` while there is nothing to blend with
+    extent: Option<UVec2>,
+}
+
+impl TemporalHistory {
+    fn new(size: UVec2) -> RenderingResult<Self> {
+        Ok(Self {
+            images: [
+                VulkanImage::output_image(size)?,
+                VulkanImage::output_image(size)?,
+            ],
+            current: 0,
+            extent: None,
+        })
+    }
+
+    /// Grown images lose their content, so the history starts over
+    fn resize(&mut self, size: UVec2) -> RenderingResult<()> {
+        if size <= self.images[0].size {
+            return Ok(());
+        }
+
+        for image in self.images.iter_mut() {
+            image.resize(size)?;
+        }
+
+        self.extent = None;
+
+        Ok(())
+    }
+
+    fn resolved(&self) -> Option<&VulkanImage> {
+        self.extent.map(|_| &self.images[self.current])
+    }
 }
 
 type RenderingResult<T> = Result<T, vk::Result>;
 
+const CLEAR_DEPTH: f32 = 1.0;
+
+/// Load op and clear value of a depth attachment, `None` loads the depth
+fn depth_clear(clear_depth: Option<f32>) -> (vk::AttachmentLoadOp, vk::ClearValue) {
+    let load_op = match clear_depth {
+        Some(_) => vk::AttachmentLoadOp::CLEAR,
+        None => vk::AttachmentLoadOp::LOAD,
+    };
+
+    let clear_value = vk::ClearValue {
+        depth_stencil: vk::ClearDepthStencilValue {
+            depth: clear_depth.unwrap_or(CLEAR_DEPTH),
+            stencil: 0,
+        },
+    };
+
+    (load_op, clear_value)
+}
+
 impl SwapchainRenderTarget {
     pub fn new(
         device: &LogicalDevice,
@@ -55,15 +174,226 @@ impl SwapchainRenderTarget {
         Ok(Self {
             targets,
             curr_image_index: 0,
+            extent: size,
+            samples,
+            depth_prepass: false,
+            sampled: false,
+            last_rendered: 0,
+            temporal_antialiasing: false,
+            history: None,
+            render_extent: size,
+            render_scale: 1.0,
+            dynamic_resolution: None,
+            stats: RenderStats::default(),
+            composition_material: None,
         })
     }
 
+    /// Draws depth of the scene before the deferred pass, so materials with
+    /// pre-pass pipelines shade every pixel only once
+    pub fn with_depth_prepass(mut self, enabled: bool) -> Self {
+        self.depth_prepass = enabled;
+        self
+    }
+
+    pub fn set_depth_prepass(&mut self, enabled: bool) {
+        self.depth_prepass = enabled;
+    }
+
+    pub fn depth_prepass(&self) -> bool {
+        self.depth_prepass
+    }
+
+    /// Leaves the output in `SHADER_READ_ONLY_OPTIMAL` after every render, so
+    /// materials can sample it as a [`RenderTexture`](crate::render_texture::RenderTexture)
+    pub fn with_sampled(mut self, sampled: bool) -> Self {
+        self.sampled = sampled;
+        self
+    }
+
+    pub fn set_sampled(&mut self, sampled: bool) {
+        self.sampled = sampled;
+    }
+
+    pub fn is_sampled(&self) -> bool {
+        self.sampled
+    }
+
+    /// Resolves every render with [`Antialiasing::Taa`](crate::antialiasing::Antialiasing::Taa),
+    /// the output becomes the resolved history
+    pub fn with_temporal_antialiasing(mut self, enabled: bool) -> Self {
+        self.temporal_antialiasing = enabled;
+        self
+    }
+
+    pub fn set_temporal_antialiasing(&mut self, enabled: bool) {
+        self.temporal_antialiasing = enabled;
+
+        if let Some(history) = &mut self.history {
+            history.extent = None;
+        }
+    }
+
+    pub fn temporal_antialiasing(&self) -> bool {
+        self.temporal_antialiasing
+    }
+
+    /// Sets the extent the target is rendered for, its images are drawn at the
+    /// extent scaled by the render scale
     pub fn resize(&mut self, size: UVec2) -> RenderingResult<()> {
-        self.current_target_mut().resize(size)
+        self.extent = size;
+        self.render_extent = self.scaled_extent(size);
+
+        let render_extent = self.render_extent;
+        self.current_target_mut().resize(render_extent)
+    }
+
+    /// Extent of the last render into this target
+    pub fn extent(&self) -> UVec2 {
+        self.extent
+    }
+
+    /// Extent the last render was drawn at, see [`set_render_scale`](Self::set_render_scale)
+    pub fn render_extent(&self) -> UVec2 {
+        self.render_extent
+    }
+
+    /// `size` scaled by the render scale, at least a pixel on non-empty sides
+    pub fn scaled_extent(&self, size: UVec2) -> UVec2 {
+        size.map(|side| match side {
+            0 => 0,
+            side => ((side as f32 * self.render_scale).round() as u32).max(1),
+        })
+    }
+
+    /// Draws the target at its extent times `scale`, presenting scales the output
+    /// back. Clamped to [`MIN_RENDER_SCALE`]..=[`MAX_RENDER_SCALE`]
+    pub fn with_render_scale(mut self, scale: f32) -> Self {
+        self.set_render_scale(scale);
+        self
+    }
+
+    /// Takes effect with the next render
+    pub fn set_render_scale(&mut self, scale: f32) {
+        self.render_scale = scale.clamp(MIN_RENDER_SCALE, MAX_RENDER_SCALE);
+    }
+
+    pub fn render_scale(&self) -> f32 {
+        self.render_scale
+    }
+
+    /// Adjusts the render scale after every render with a GPU frame time, `None`
+    /// keeps the current scale
+    pub fn with_dynamic_resolution(
+        mut self,
+        dynamic_resolution: Option<DynamicResolution>,
+    ) -> Self {
+        self.set_dynamic_resolution(dynamic_resolution);
+        self
+    }
+
+    pub fn set_dynamic_resolution(&mut self, dynamic_resolution: Option<DynamicResolution>) {
+        self.dynamic_resolution = dynamic_resolution.map(|dynamic| {
+            let min_scale = dynamic.min_scale.clamp(MIN_RENDER_SCALE, MAX_RENDER_SCALE);
+
+            DynamicResolution {
+                min_scale,
+                max_scale: dynamic.max_scale.clamp(min_scale, MAX_RENDER_SCALE),
+                ..dynamic
+            }
+        });
+    }
+
+    pub fn dynamic_resolution(&self) -> Option<DynamicResolution> {
+        self.dynamic_resolution
+    }
+
+    /// Timings of the latest render the GPU has finished
+    pub fn stats(&self) -> RenderStats {
+        self.stats
+    }
+
+    /// Composes the G-buffer with `material` instead of the built-in composition,
+    /// see [`composition_material`](crate::material::builtin::composition_material)
+    pub fn with_composition_material(mut self, material: Option<MaterialHandle>) -> Self {
+        self.composition_material = material;
+        self
+    }
+
+    pub fn set_composition_material(&mut self, material: Option<MaterialHandle>) {
+        self.composition_material = material;
+    }
+
+    pub fn composition_material(&self) -> Option<MaterialHandle> {
+        self.composition_material
+    }
+
+    /// Moves the render scale toward the one drawing in the target frame time.
+    /// Frames drawn at another scale are ignored, so the scale isn't corrected
+    /// again for renders still in flight
+    fn adapt_render_scale(&mut self) {
+        let Some(dynamic) = self.dynamic_resolution else {
+            return;
+        };
+
+        let Some(gpu_frame_time) = self.stats.gpu_frame_time else {
+            return;
+        };
+
+        if self.stats.render_extent != self.scaled_extent(self.stats.extent) {
+            return;
+        }
+
+        let ratio = dynamic.target_frame_time.as_secs_f32()
+            / gpu_frame_time.as_secs_f32().max(f32::EPSILON);
+
+        if (ratio - 1.0).abs() <= DYNAMIC_RESOLUTION_TOLERANCE {
+            return;
+        }
+
+        // The frame time grows with the pixel count, the square of the scale
+        let step = ratio
+            .sqrt()
+            .clamp(1.0 - DYNAMIC_RESOLUTION_STEP, 1.0 + DYNAMIC_RESOLUTION_STEP);
+
+        self.render_scale = (self.render_scale * step).clamp(dynamic.min_scale, dynamic.max_scale);
+    }
+
+    pub fn samples(&self) -> vk::SampleCountFlags {
+        self.samples
+    }
+
+    pub fn image_count(&self) -> u32 {
+        self.targets.len() as u32
     }
 
     pub fn output_image(&self) -> &VulkanImage {
-        self.current_target().output_image()
+        self.resolved_history()
+            .unwrap_or_else(|| self.current_target().output_image())
+    }
+
+    /// Output of the last submitted render
+    pub fn sampled_image(&self) -> &VulkanImage {
+        self.resolved_history()
+            .unwrap_or_else(|| self.targets[self.last_rendered].output_image())
+    }
+
+    fn resolved_history(&self) -> Option<&VulkanImage> {
+        self.history
+            .as_ref()
+            .filter(|_| self.temporal_antialiasing)
+            .and_then(TemporalHistory::resolved)
+    }
+
+    /// Part of [`sampled_image`](Self::sampled_image) covered by the last render,
+    /// images only grow on resize, so it can be less than `1.0`
+    pub fn sampled_uv_scale(&self) -> Vec2 {
+        let image_size = self.sampled_image().size;
+
+        Vec2::new(
+            self.render_extent.x as f32 / image_size.x as f32,
+            self.render_extent.y as f32 / image_size.y as f32,
+        )
     }
 
     pub fn composition_attachments(&self) -> Vec<&VulkanImage> {
@@ -106,7 +436,7 @@ impl SwapchainRenderTarget {
 
         self.end_rendering(device);
 
-        self.prepare_transfer(device);
+        self.prepare_output(device);
 
         self.submit_render(device)?;
 
@@ -125,6 +455,151 @@ impl SwapchainRenderTarget {
         self.current_target_mut().start_composition_pass(device)
     }
 
+    pub fn begin_frame(&mut self, device: &LogicalDevice) -> RenderingResult<()> {
+        let target = &mut self.targets[self.curr_image_index];
+        target.begin_frame(device)?;
+
+        if let Some(stats) = target.take_stats(device) {
+            self.stats = stats;
+            self.adapt_render_scale();
+        }
+
+        Ok(())
+    }
+
+    pub fn begin_deferred_pass(
+        &mut self,
+        device: &LogicalDevice,
+        viewport: vk::Rect2D,
+        scissor: vk::Rect2D,
+        clear_color: Option<Vec4>,
+        clear_depth: Option<f32>,
+        flags: vk::RenderingFlags,
+    ) {
+        self.current_target_mut().begin_deferred_pass(
+            device,
+            viewport,
+            scissor,
+            clear_color,
+            clear_depth,
+            flags,
+        )
+    }
+
+    pub fn begin_depth_prepass(
+        &mut self,
+        device: &LogicalDevice,
+        viewport: vk::Rect2D,
+        scissor: vk::Rect2D,
+        clear_depth: Option<f32>,
+        flags: vk::RenderingFlags,
+    ) {
+        self.current_target_mut()
+            .begin_depth_prepass(device, viewport, scissor, clear_depth, flags)
+    }
+
+    pub fn end_depth_prepass_into(
+        &mut self,
+        device: &LogicalDevice,
+        area: vk::Rect2D,
+        buffer: vk::Buffer,
+    ) {
+        self.current_target_mut()
+            .end_depth_prepass_into(device, area, buffer)
+    }
+
+    pub fn start_deferred_pass_after_prepass(
+        &mut self,
+        device: &LogicalDevice,
+        viewport: vk::Rect2D,
+        scissor: vk::Rect2D,
+        clear_color: Option<Vec4>,
+        flags: vk::RenderingFlags,
+    ) {
+        self.current_target_mut().start_deferred_pass_after_prepass(
+            device,
+            viewport,
+            scissor,
+            clear_color,
+            flags,
+        )
+    }
+
+    /// Index of the image being recorded, every image has its own command buffer
+    pub fn image_index(&self) -> usize {
+        self.curr_image_index
+    }
+
+    /// Attachments of the deferred pass, for the secondary command buffers
+    /// executed in it
+    pub fn deferred_inheritance(&self) -> RenderingInheritance {
+        let target = self.current_target();
+
+        RenderingInheritance {
+            color_formats: target
+                .deferred_attachments()
+                .iter()
+                .map(|image| image.format)
+                .collect(),
+            depth_format: target.depth_image.format,
+            samples: self.samples,
+        }
+    }
+
+    /// Attachments of the depth pre-pass, for the secondary command buffers
+    /// executed in it
+    pub fn prepass_inheritance(&self) -> RenderingInheritance {
+        RenderingInheritance {
+            color_formats: Vec::new(),
+            depth_format: self.current_target().depth_image.format,
+            samples: self.samples,
+        }
+    }
+
+    pub fn start_composition_pass_in(
+        &mut self,
+        device: &LogicalDevice,
+        viewport: vk::Rect2D,
+        scissor: vk::Rect2D,
+    ) -> RenderingResult<()> {
+        self.current_target_mut()
+            .start_composition_pass_in(device, viewport, scissor)
+    }
+
+    pub fn begin_selection_mask_pass(
+        &mut self,
+        device: &LogicalDevice,
+        viewport: vk::Rect2D,
+        scissor: vk::Rect2D,
+    ) {
+        self.current_target_mut()
+            .begin_selection_mask_pass(device, viewport, scissor)
+    }
+
+    pub fn start_selection_outline_pass(
+        &mut self,
+        device: &LogicalDevice,
+        viewport: vk::Rect2D,
+        scissor: vk::Rect2D,
+    ) {
+        self.current_target_mut()
+            .start_selection_outline_pass(device, viewport, scissor)
+    }
+
+    pub fn selection_mask(&self) -> &VulkanImage {
+        &self.current_target().selection_mask
+    }
+
+    pub fn start_overlay_pass(
+        &mut self,
+        device: &LogicalDevice,
+        viewport: vk::Rect2D,
+        scissor: vk::Rect2D,
+    ) {
+        self.current_target_mut()
+            .start_overlay_pass(device, viewport, scissor)
+    }
+
     pub fn end_rendering(&mut self, device: &LogicalDevice) {
         self.current_target_mut().end_rendering(device)
     }
@@ -133,8 +608,166 @@ impl SwapchainRenderTarget {
         self.current_target_mut().prepare_transfer(device)
     }
 
+    /// Moves the output into the layout it's consumed in: sampling for sampled
+    /// targets, transfer to a present target otherwise
+    pub fn prepare_output(&mut self, device: &LogicalDevice) {
+        let (temporal_antialiasing, sampled) = (self.temporal_antialiasing, self.sampled);
+
+        let history = self
+            .history
+            .as_mut()
+            .filter(|history| temporal_antialiasing && history.extent.is_some());
+
+        if let Some(history) = history {
+            let cmd_buffer = self.targets[self.curr_image_index].render_cmd_buffer;
+            let image = &mut history.images[history.current];
+
+            record_output_barrier(device, cmd_buffer, image, sampled);
+        } else if self.sampled {
+            self.current_target_mut().prepare_sampling(device)
+        } else {
+            self.current_target_mut().prepare_transfer(device)
+        }
+    }
+
+    /// Begins the pass blending the composed frame with the history, see
+    /// [`temporal_resolve_inputs`](Self::temporal_resolve_inputs). Has to be
+    /// recorded after the last composition pass of the frame
+    pub fn begin_temporal_resolve(&mut self, device: &LogicalDevice) -> RenderingResult<()> {
+        let extent = self.render_extent;
+
+        let history = match &mut self.history {
+            Some(history) => history,
+            None => self.history.insert(TemporalHistory::new(extent)?),
+        };
+
+        history.resize(extent)?;
+
+        let target = &mut self.targets[self.curr_image_index];
+
+        let [first, second] = &mut history.images;
+        let (previous, next) = if history.current == 0 {
+            (first, second)
+        } else {
+            (second, first)
+        };
+
+        let next_view = next.image_view;
+
+        let barriers = unsafe {
+            let sampled = [
+                &mut target.output_attachment,
+                &mut target.velocity_attachment,
+            ]
+            .map(|image| {
+                image.image_barrier(
+                    vk::PipelineStageFlags2::COLOR_ATTACHMENT_OUTPUT,
+                    vk::AccessFlags2::COLOR_ATTACHMENT_WRITE,
+                    vk::PipelineStageFlags2::FRAGMENT_SHADER,
+                    vk::AccessFlags2::SHADER_SAMPLED_READ,
+                    vk::ImageLayout::SHADER_READ_ONLY_OPTIMAL,
+                )
+            });
+
+            // The previous resolve may still be blitted or sampled by earlier submissions
+            let previous = previous.image_barrier(
+                vk::PipelineStageFlags2::COLOR_ATTACHMENT_OUTPUT
+                    | vk::PipelineStageFlags2::FRAGMENT_SHADER
+                    | vk::PipelineStageFlags2::TRANSFER,
+                vk::AccessFlags2::COLOR_ATTACHMENT_WRITE,
+                vk::PipelineStageFlags2::FRAGMENT_SHADER,
+                vk::AccessFlags2::SHADER_SAMPLED_READ,
+                vk::ImageLayout::SHADER_READ_ONLY_OPTIMAL,
+            );
+
+            let next_barrier = next.image_barrier(
+                vk::PipelineStageFlags2::COLOR_ATTACHMENT_OUTPUT
+                    | vk::PipelineStageFlags2::FRAGMENT_SHADER
+                    | vk::PipelineStageFlags2::TRANSFER,
+                vk::AccessFlags2::empty(),
+                vk::PipelineStageFlags2::COLOR_ATTACHMENT_OUTPUT,
+                vk::AccessFlags2::COLOR_ATTACHMENT_WRITE,
+                vk::ImageLayout::COLOR_ATTACHMENT_OPTIMAL,
+            );
+
+            [&sampled[..], &[previous, next_barrier][..]].concat()
+        };
+
+        let area = vk::Rect2D {
+            extent: vk::Extent2D {
+                width: extent.x,
+                height: extent.y,
+            },
+            offset: vk::Offset2D::default(),
+        };
+
+        let color_attachments = [vk::RenderingAttachmentInfo::default()
+            .image_view(next_view)
+            .image_layout(vk::ImageLayout::COLOR_ATTACHMENT_OPTIMAL)
+            .load_op(vk::AttachmentLoadOp::DONT_CARE)
+            .store_op(vk::AttachmentStoreOp::STORE)];
+
+        let rendering_info = vk::RenderingInfo::default()
+            .render_area(area)
+            .color_attachments(&color_attachments)
+            .layer_count(1);
+
+        unsafe {
+            let dep_info = vk::DependencyInfo::default().image_memory_barriers(&barriers);
+            device.cmd_pipeline_barrier2(target.render_cmd_buffer, &dep_info);
+
+            target.set_viewport_and_scissor(device, area, area);
+
+            device.cmd_begin_rendering(target.render_cmd_buffer, &rendering_info);
+        }
+
+        Ok(())
+    }
+
+    /// Composed frame, history and motion vectors sampled by the temporal resolve.
+    /// The composed frame stands in for the history when there is nothing to blend with
+    pub fn temporal_resolve_inputs(&self) -> Option<[&VulkanImage; 3]> {
+        let history = self.history.as_ref()?;
+        let target = self.current_target();
+
+        let previous = match history.extent {
+            Some(extent) if extent == self.render_extent => &history.images[history.current],
+            _ => &target.output_attachment,
+        };
+
+        Some([
+            &target.output_attachment,
+            previous,
+            &target.velocity_attachment,
+        ])
+    }
+
+    pub fn end_temporal_resolve(&mut self, device: &LogicalDevice) {
+        let extent = self.render_extent;
+        let cmd_buffer = self.current_target().render_cmd_buffer;
+
+        unsafe { device.cmd_end_rendering(cmd_buffer) };
+
+        if let Some(history) = &mut self.history {
+            history.current = 1 - history.current;
+            history.extent = Some(extent);
+        }
+    }
+
     pub fn submit_render(&mut self, device: &LogicalDevice) -> RenderingResult<()> {
-        self.current_target_mut().submit_render(device)
+        let stats = RenderStats {
+            gpu_frame_time: None,
+            extent: self.extent,
+            render_extent: self.render_extent,
+        };
+
+        let target = self.current_target_mut();
+        target.submit_render(device)?;
+        target.submitted = Some(stats);
+
+        self.last_rendered = self.curr_image_index;
+
+        Ok(())
     }
 
     fn current_target_mut(&mut self) -> &mut ImageRenderTarget {
@@ -154,11 +787,24 @@ pub struct ImageRenderTarget {
     pub color_attachment: VulkanImage,
     pub normals_attachment: VulkanImage,
     pub position_depth_attachment: VulkanImage,
+    /// Metallic, roughness and ambient occlusion, `w` is `1.0` for lit pixels
+    pub material_attachment: VulkanImage,
+    /// Motion of every pixel since the previous frame in pixels, drawn for
+    /// temporal antialiasing
+    pub velocity_attachment: VulkanImage,
     pub depth_image: VulkanImage,
+    /// Silhouettes of selected objects, dilated into outlines over the output
+    pub selection_mask: VulkanImage,
 
     pub output_attachment: VulkanImage,
     pub resolve_attachment: Option<VulkanImage>,
     pub size: UVec2,
+
+    /// Written at the start and the end of every frame, `None` if the device
+    /// can't write timestamps
+    timestamps: Option<vk::QueryPool>,
+    /// Render submitted last, its GPU time is read once its fence is waited on
+    submitted: Option<RenderStats>,
 }
 
 impl ImageRenderTarget {
@@ -195,7 +841,10 @@ impl ImageRenderTarget {
         let color_attachment = VulkanImage::attachment_image(size, samples)?;
         let normals_attachment = VulkanImage::attachment_image(size, samples)?;
         let position_depth_attachment = VulkanImage::attachment_image(size, samples)?;
+        let material_attachment = VulkanImage::attachment_image(size, samples)?;
+        let velocity_attachment = VulkanImage::attachment_image(size, samples)?;
         let depth_attachment = VulkanImage::depth_image(size, samples)?;
+        let selection_mask = VulkanImage::attachment_image(size, vk::SampleCountFlags::TYPE_1)?;
 
         let (output_attachment, resolve_image) = if samples != vk::SampleCountFlags::TYPE_1 {
             todo!("Multisampling is yet to be implemented")
@@ -210,17 +859,33 @@ impl ImageRenderTarget {
 
         device.set_object_debug_name(render_ready, "ImageRenderTarget::render_complete");
 
+        let timestamps = device
+            .timestamp_period
+            .map(|_| {
+                let create_info = vk::QueryPoolCreateInfo::default()
+                    .query_type(vk::QueryType::TIMESTAMP)
+                    .query_count(2);
+
+                unsafe { device.create_query_pool(&create_info, None) }
+            })
+            .transpose()?;
+
         Ok(Self {
             render_cmd_buffer: cmd_buffer,
             in_flight_fence,
             color_attachment,
             normals_attachment,
             position_depth_attachment,
+            material_attachment,
+            velocity_attachment,
             depth_image: depth_attachment,
+            selection_mask,
             resolve_attachment: resolve_image,
             size,
             output_attachment,
             render_complete: render_ready,
+            timestamps,
+            submitted: None,
         })
     }
 
@@ -229,6 +894,7 @@ impl ImageRenderTarget {
             &self.color_attachment,
             &self.normals_attachment,
             &self.position_depth_attachment,
+            &self.material_attachment,
         ]
         .to_vec()
     }
@@ -254,8 +920,11 @@ impl ImageRenderTarget {
             &mut self.color_attachment,
             &mut self.normals_attachment,
             &mut self.position_depth_attachment,
+            &mut self.material_attachment,
+            &mut self.velocity_attachment,
             &mut self.output_attachment,
             &mut self.depth_image,
+            &mut self.selection_mask,
         ]
         .iter_mut()
         .map(|image| image.resize(size))
@@ -271,97 +940,340 @@ impl ImageRenderTarget {
     }
 
     pub fn begin_rendering(&mut self, device: &LogicalDevice) -> RenderingResult<RenderData2> {
+        self.begin_frame(device)?;
+        self.begin_deferred_pass(
+            device,
+            self.full_area(),
+            self.full_area(),
+            Some(Vec4::zeros()),
+            Some(CLEAR_DEPTH),
+            vk::RenderingFlags::empty(),
+        );
+
+        let render_data = RenderData2 {
+            in_flight_fence: self.in_flight_fence,
+            render_complete: self.render_complete,
+            cmd_buffer: self.render_cmd_buffer,
+            size: self.size,
+        };
+
+        Ok(render_data)
+    }
+
+    /// Waits for the previous submission of this target and starts recording
+    /// its command buffer
+    pub fn begin_frame(&mut self, device: &LogicalDevice) -> RenderingResult<()> {
         unsafe {
             device.wait_for_fences(&[self.in_flight_fence], true, u64::MAX)?;
 
             device.begin_command_buffer(self.render_cmd_buffer, &Default::default())?;
 
-            self.transition_images_to_deferred(device);
+            if let Some(timestamps) = self.timestamps {
+                device.cmd_reset_query_pool(self.render_cmd_buffer, timestamps, 0, 2);
+                device.cmd_write_timestamp2(
+                    self.render_cmd_buffer,
+                    vk::PipelineStageFlags2::TOP_OF_PIPE,
+                    timestamps,
+                    0,
+                );
+            }
+        }
 
-            device.cmd_set_scissor(
-                self.render_cmd_buffer,
-                0,
-                &[vk::Rect2D {
-                    extent: vk::Extent2D {
-                        width: self.size.x,
-                        height: self.size.y,
-                    },
-                    ..Default::default()
-                }],
+        Ok(())
+    }
+
+    /// Timings of the last submitted render, its fence has to be waited on
+    fn take_stats(&mut self, device: &LogicalDevice) -> Option<RenderStats> {
+        let mut stats = self.submitted.take()?;
+
+        if let (Some(timestamps), Some(period)) = (self.timestamps, device.timestamp_period) {
+            let mut ticks = [0u64; 2];
+
+            let result = unsafe {
+                device.get_query_pool_results(
+                    timestamps,
+                    0,
+                    &mut ticks,
+                    vk::QueryResultFlags::TYPE_64,
+                )
+            };
+
+            stats.gpu_frame_time = result.ok().map(|_| {
+                let [start, end] = ticks;
+                Duration::from_nanos((end.saturating_sub(start) as f64 * period as f64) as u64)
+            });
+        }
+
+        Some(stats)
+    }
+
+    /// Begins the deferred pass mapped onto `viewport` and clipped by `scissor`.
+    ///
+    /// Attachments are cleared only inside of `scissor`, so several passes with
+    /// different areas can be recorded within one frame. `None` keeps the color
+    /// or the depth left by the previous pass
+    pub fn begin_deferred_pass(
+        &mut self,
+        device: &LogicalDevice,
+        viewport: vk::Rect2D,
+        scissor: vk::Rect2D,
+        clear_color: Option<Vec4>,
+        clear_depth: Option<f32>,
+        flags: vk::RenderingFlags,
+    ) {
+        self.transition_images_to_deferred(device);
+
+        self.begin_deferred_rendering(device, viewport, scissor, clear_color, clear_depth, flags);
+    }
+
+    /// Begins a depth-only pass mapped onto `viewport` and clipped by `scissor`.
+    /// The depth is cleared inside of `scissor` unless `clear_depth` is `None` and
+    /// kept for the deferred pass started with
+    /// [`start_deferred_pass_after_prepass`](Self::start_deferred_pass_after_prepass)
+    pub fn begin_depth_prepass(
+        &mut self,
+        device: &LogicalDevice,
+        viewport: vk::Rect2D,
+        scissor: vk::Rect2D,
+        clear_depth: Option<f32>,
+        flags: vk::RenderingFlags,
+    ) {
+        self.transition_images_to_deferred(device);
+
+        self.set_viewport_and_scissor(device, viewport, scissor);
+
+        let (depth_load_op, depth_clear_value) = depth_clear(clear_depth);
+
+        let depth_attachment = vk::RenderingAttachmentInfo::default()
+            .image_view(self.depth_image.image_view)
+            .image_layout(vk::ImageLayout::DEPTH_ATTACHMENT_OPTIMAL)
+            .clear_value(depth_clear_value)
+            .load_op(depth_load_op)
+            .store_op(vk::AttachmentStoreOp::STORE);
+
+        let rendering_info = vk::RenderingInfo::default()
+            .flags(flags)
+            .render_area(scissor)
+            .depth_attachment(&depth_attachment)
+            .layer_count(1);
+
+        unsafe { device.cmd_begin_rendering(self.render_cmd_buffer, &rendering_info) };
+    }
+
+    /// Ends the depth pre-pass and copies the depth inside of `area` into `buffer`
+    /// as tightly packed floats, ready for `COMPUTE_SHADER` reads.
+    ///
+    /// The deferred pass is begun afterwards with
+    /// [`begin_deferred_pass`](Self::begin_deferred_pass) without clearing the depth.
+    /// Only single sampled depth can be copied
+    pub fn end_depth_prepass_into(
+        &mut self,
+        device: &LogicalDevice,
+        area: vk::Rect2D,
+        buffer: vk::Buffer,
+    ) {
+        unsafe {
+            device.cmd_end_rendering(self.render_cmd_buffer);
+
+            let depth_barrier = self.depth_image.image_barrier(
+                vk::PipelineStageFlags2::EARLY_FRAGMENT_TESTS
+                    | vk::PipelineStageFlags2::LATE_FRAGMENT_TESTS,
+                vk::AccessFlags2::DEPTH_STENCIL_ATTACHMENT_WRITE,
+                vk::PipelineStageFlags2::TRANSFER,
+                vk::AccessFlags2::TRANSFER_READ,
+                vk::ImageLayout::TRANSFER_SRC_OPTIMAL,
             );
 
-            device.cmd_set_viewport(
+            // The previous copy into the buffer may still be read by culling
+            let buffer_barrier = vk::MemoryBarrier2::default()
+                .src_stage_mask(vk::PipelineStageFlags2::COMPUTE_SHADER)
+                .src_access_mask(vk::AccessFlags2::SHADER_STORAGE_READ)
+                .dst_stage_mask(vk::PipelineStageFlags2::TRANSFER)
+                .dst_access_mask(vk::AccessFlags2::TRANSFER_WRITE);
+
+            let image_barriers = [depth_barrier];
+            let memory_barriers = [buffer_barrier];
+            let dep_info = vk::DependencyInfo::default()
+                .image_memory_barriers(&image_barriers)
+                .memory_barriers(&memory_barriers);
+
+            device.cmd_pipeline_barrier2(self.render_cmd_buffer, &dep_info);
+
+            let region = vk::BufferImageCopy::default()
+                .image_subresource(vk::ImageSubresourceLayers {
+                    aspect_mask: vk::ImageAspectFlags::DEPTH,
+                    mip_level: 0,
+                    base_array_layer: 0,
+                    layer_count: 1,
+                })
+                .image_offset(vk::Offset3D {
+                    x: area.offset.x,
+                    y: area.offset.y,
+                    z: 0,
+                })
+                .image_extent(vk::Extent3D {
+                    width: area.extent.width,
+                    height: area.extent.height,
+                    depth: 1,
+                });
+
+            device.cmd_copy_image_to_buffer(
                 self.render_cmd_buffer,
-                0,
-                &[vk::Viewport {
-                    height: -(self.size.y as f32),
-                    width: self.size.x as f32,
-                    x: 0.0,
-                    y: self.size.y as f32,
-                    min_depth: 0.0,
-                    max_depth: 1.0,
-                }],
+                self.depth_image.image,
+                vk::ImageLayout::TRANSFER_SRC_OPTIMAL,
+                buffer,
+                &[region],
             );
 
-            let clear_depth_value = vk::ClearValue {
-                depth_stencil: vk::ClearDepthStencilValue {
-                    depth: 1.0,
-                    stencil: 0,
-                },
+            let depth_barrier = self.depth_image.image_barrier(
+                vk::PipelineStageFlags2::TRANSFER,
+                vk::AccessFlags2::empty(),
+                vk::PipelineStageFlags2::EARLY_FRAGMENT_TESTS
+                    | vk::PipelineStageFlags2::LATE_FRAGMENT_TESTS,
+                vk::AccessFlags2::DEPTH_STENCIL_ATTACHMENT_READ
+                    | vk::AccessFlags2::DEPTH_STENCIL_ATTACHMENT_WRITE,
+                vk::ImageLayout::DEPTH_ATTACHMENT_OPTIMAL,
+            );
+
+            let buffer_barrier = vk::MemoryBarrier2::default()
+                .src_stage_mask(vk::PipelineStageFlags2::TRANSFER)
+                .src_access_mask(vk::AccessFlags2::TRANSFER_WRITE)
+                .dst_stage_mask(vk::PipelineStageFlags2::COMPUTE_SHADER)
+                .dst_access_mask(
+                    vk::AccessFlags2::SHADER_STORAGE_READ | vk::AccessFlags2::SHADER_STORAGE_WRITE,
+                );
+
+            let image_barriers = [depth_barrier];
+            let memory_barriers = [buffer_barrier];
+            let dep_info = vk::DependencyInfo::default()
+                .image_memory_barriers(&image_barriers)
+                .memory_barriers(&memory_barriers);
+
+            device.cmd_pipeline_barrier2(self.render_cmd_buffer, &dep_info);
+        }
+    }
+
+    /// Ends the depth pre-pass and begins the deferred pass over its depth
+    pub fn start_deferred_pass_after_prepass(
+        &mut self,
+        device: &LogicalDevice,
+        viewport: vk::Rect2D,
+        scissor: vk::Rect2D,
+        clear_color: Option<Vec4>,
+        flags: vk::RenderingFlags,
+    ) {
+        unsafe {
+            device.cmd_end_rendering(self.render_cmd_buffer);
+
+            let depth_barrier = self.depth_image.image_barrier(
+                vk::PipelineStageFlags2::EARLY_FRAGMENT_TESTS
+                    | vk::PipelineStageFlags2::LATE_FRAGMENT_TESTS,
+                vk::AccessFlags2::DEPTH_STENCIL_ATTACHMENT_WRITE,
+                vk::PipelineStageFlags2::EARLY_FRAGMENT_TESTS
+                    | vk::PipelineStageFlags2::LATE_FRAGMENT_TESTS,
+                vk::AccessFlags2::DEPTH_STENCIL_ATTACHMENT_READ
+                    | vk::AccessFlags2::DEPTH_STENCIL_ATTACHMENT_WRITE,
+                vk::ImageLayout::DEPTH_ATTACHMENT_OPTIMAL,
+            );
+
+            let barriers = [depth_barrier];
+            let dep_info = vk::DependencyInfo::default().image_memory_barriers(&barriers);
+
+            device.cmd_pipeline_barrier2(self.render_cmd_buffer, &dep_info);
+        }
+
+        self.begin_deferred_rendering(device, viewport, scissor, clear_color, None, flags);
+    }
+
+    fn begin_deferred_rendering(
+        &mut self,
+        device: &LogicalDevice,
+        viewport: vk::Rect2D,
+        scissor: vk::Rect2D,
+        clear_color: Option<Vec4>,
+        clear_depth: Option<f32>,
+        flags: vk::RenderingFlags,
+    ) {
+        unsafe {
+            self.set_viewport_and_scissor(device, viewport, scissor);
+
+            let color_load_op = match clear_color {
+                Some(_) => vk::AttachmentLoadOp::CLEAR,
+                None => vk::AttachmentLoadOp::LOAD,
             };
 
-            let color_attachments = [
-                &self.color_attachment,
-                &self.normals_attachment,
-                &self.position_depth_attachment,
-            ]
-            .map(|image| {
-                vk::RenderingAttachmentInfo::default()
-                    .image_view(image.image_view)
-                    .image_layout(image.image_layout)
-                    .clear_value(
-                        (vk::ClearValue {
-                            color: vk::ClearColorValue { float32: [0.0; 4] },
+            // Only the color attachment gets the clear color, the composition
+            // pass shows it where nothing was drawn
+            let clear_values = [
+                clear_color.unwrap_or_default(),
+                Vec4::zeros(),
+                Vec4::zeros(),
+                Vec4::zeros(),
+                Vec4::zeros(),
+            ];
+
+            let color_attachments = self
+                .deferred_attachments()
+                .into_iter()
+                .zip(clear_values)
+                .map(|(image, clear_value)| {
+                    vk::RenderingAttachmentInfo::default()
+                        .image_view(image.image_view)
+                        .image_layout(image.image_layout)
+                        .clear_value(vk::ClearValue {
+                            color: vk::ClearColorValue {
+                                float32: clear_value.into(),
+                            },
                         })
-                        .clone(),
-                    )
-                    .load_op(vk::AttachmentLoadOp::CLEAR)
-                    .store_op(vk::AttachmentStoreOp::STORE)
-            });
+                        .load_op(color_load_op)
+                        .store_op(vk::AttachmentStoreOp::STORE)
+                })
+                .collect::<Vec<_>>();
 
+            let (depth_load_op, depth_clear_value) = depth_clear(clear_depth);
+
+            // Stored for the following packages keeping the depth
             let depth_attachment = vk::RenderingAttachmentInfo::default()
                 .image_view(self.depth_image.image_view)
                 .image_layout(vk::ImageLayout::DEPTH_ATTACHMENT_OPTIMAL)
-                .clear_value(clear_depth_value)
-                .load_op(vk::AttachmentLoadOp::CLEAR)
-                .store_op(vk::AttachmentStoreOp::DONT_CARE);
+                .clear_value(depth_clear_value)
+                .load_op(depth_load_op)
+                .store_op(vk::AttachmentStoreOp::STORE);
 
             let rendering_info = vk::RenderingInfo::default()
-                .render_area(vk::Rect2D {
-                    extent: vk::Extent2D {
-                        width: self.size.x,
-                        height: self.size.y,
-                    },
-                    offset: vk::Offset2D::default(),
-                })
+                .flags(flags)
+                .render_area(scissor)
                 .color_attachments(&color_attachments)
                 .depth_attachment(&depth_attachment)
                 .layer_count(1);
 
             device.cmd_begin_rendering(self.render_cmd_buffer, &rendering_info);
         }
+    }
 
-        let render_data = RenderData2 {
-            in_flight_fence: self.in_flight_fence,
-            render_complete: self.render_complete,
-            cmd_buffer: self.render_cmd_buffer,
-            size: self.size,
-        };
-
-        Ok(render_data)
+    /// Color attachments of the deferred pass in their location order
+    fn deferred_attachments(&self) -> [&VulkanImage; 5] {
+        [
+            &self.color_attachment,
+            &self.normals_attachment,
+            &self.position_depth_attachment,
+            &self.material_attachment,
+            &self.velocity_attachment,
+        ]
     }
 
     pub fn start_composition_pass(&mut self, device: &LogicalDevice) -> RenderingResult<()> {
+        self.start_composition_pass_in(device, self.full_area(), self.full_area())
+    }
+
+    /// Ends the deferred pass and begins the composition pass mapped onto
+    /// `viewport` and clipped by `scissor`
+    pub fn start_composition_pass_in(
+        &mut self,
+        device: &LogicalDevice,
+        viewport: vk::Rect2D,
+        scissor: vk::Rect2D,
+    ) -> RenderingResult<()> {
         unsafe {
             device.cmd_end_rendering(self.render_cmd_buffer);
 
@@ -394,6 +1306,13 @@ impl ImageRenderTarget {
                 .store_op(vk::AttachmentStoreOp::DONT_CARE)
                 .clear_value(clear_color);
 
+            let material_input_attachment = vk::RenderingAttachmentInfo::default()
+                .image_view(self.material_attachment.image_view)
+                .image_layout(vk::ImageLayout::COLOR_ATTACHMENT_OPTIMAL)
+                .load_op(vk::AttachmentLoadOp::LOAD)
+                .store_op(vk::AttachmentStoreOp::DONT_CARE)
+                .clear_value(clear_color);
+
             let color_attachment = vk::RenderingAttachmentInfo::default()
                 .image_view(self.output_attachment.image_view)
                 .image_layout(vk::ImageLayout::COLOR_ATTACHMENT_OPTIMAL)
@@ -405,19 +1324,16 @@ impl ImageRenderTarget {
                 color_input_attachment,
                 normals_input_attachment,
                 position_depth_input_attachment,
+                material_input_attachment,
                 color_attachment,
             ];
 
             let rendering_info = vk::RenderingInfo::default()
                 .color_attachments(&color_attachments)
                 .layer_count(1)
-                .render_area(vk::Rect2D {
-                    extent: vk::Extent2D {
-                        width: self.size.x,
-                        height: self.size.y,
-                    },
-                    offset: vk::Offset2D { x: 0, y: 0 },
-                });
+                .render_area(scissor);
+
+            self.set_viewport_and_scissor(device, viewport, scissor);
 
             device.cmd_begin_rendering(self.render_cmd_buffer, &rendering_info);
         }
@@ -425,32 +1341,188 @@ impl ImageRenderTarget {
         Ok(())
     }
 
-    pub fn end_rendering(&mut self, device: &LogicalDevice) {
-        unsafe { device.cmd_end_rendering(self.render_cmd_buffer) }
-    }
-
-    pub fn prepare_transfer(&mut self, device: &LogicalDevice) {
+    /// Ends the current pass and begins drawing selected objects into the
+    /// selection mask, which is cleared inside of `scissor`
+    pub fn begin_selection_mask_pass(
+        &mut self,
+        device: &LogicalDevice,
+        viewport: vk::Rect2D,
+        scissor: vk::Rect2D,
+    ) {
         unsafe {
-            let cmd = self.render_cmd_buffer;
+            device.cmd_end_rendering(self.render_cmd_buffer);
 
-            let image_barrier = self.output_image_mut().image_barrier(
+            // Earlier packages of the frame may have sampled the mask in their outline pass
+            let mask_barrier = self.selection_mask.image_barrier(
+                vk::PipelineStageFlags2::FRAGMENT_SHADER,
+                vk::AccessFlags2::empty(),
                 vk::PipelineStageFlags2::COLOR_ATTACHMENT_OUTPUT,
                 vk::AccessFlags2::COLOR_ATTACHMENT_WRITE,
-                vk::PipelineStageFlags2::TRANSFER,
-                vk::AccessFlags2::TRANSFER_READ,
-                vk::ImageLayout::TRANSFER_SRC_OPTIMAL,
+                vk::ImageLayout::COLOR_ATTACHMENT_OPTIMAL,
             );
 
-            let barriers = [image_barrier];
+            let barriers = [mask_barrier];
+            let dep_info = vk::DependencyInfo::default().image_memory_barriers(&barriers);
+            device.cmd_pipeline_barrier2(self.render_cmd_buffer, &dep_info);
+
+            let mask_attachment = [vk::RenderingAttachmentInfo::default()
+                .image_view(self.selection_mask.image_view)
+                .image_layout(vk::ImageLayout::COLOR_ATTACHMENT_OPTIMAL)
+                .load_op(vk::AttachmentLoadOp::CLEAR)
+                .store_op(vk::AttachmentStoreOp::STORE)
+                .clear_value(vk::ClearValue {
+                    color: vk::ClearColorValue {
+                        float32: [0.0, 0.0, 0.0, 0.0],
+                    },
+                })];
+
+            let rendering_info = vk::RenderingInfo::default()
+                .color_attachments(&mask_attachment)
+                .layer_count(1)
+                .render_area(scissor);
+
+            self.set_viewport_and_scissor(device, viewport, scissor);
+
+            device.cmd_begin_rendering(self.render_cmd_buffer, &rendering_info);
+        }
+    }
+
+    /// Ends the selection mask pass and begins drawing outlines over the
+    /// composed output, the mask can be sampled by fragment shaders
+    pub fn start_selection_outline_pass(
+        &mut self,
+        device: &LogicalDevice,
+        viewport: vk::Rect2D,
+        scissor: vk::Rect2D,
+    ) {
+        unsafe {
+            device.cmd_end_rendering(self.render_cmd_buffer);
+
+            let barriers = [
+                self.selection_mask.image_barrier(
+                    vk::PipelineStageFlags2::COLOR_ATTACHMENT_OUTPUT,
+                    vk::AccessFlags2::COLOR_ATTACHMENT_WRITE,
+                    vk::PipelineStageFlags2::FRAGMENT_SHADER,
+                    vk::AccessFlags2::SHADER_SAMPLED_READ,
+                    vk::ImageLayout::SHADER_READ_ONLY_OPTIMAL,
+                ),
+                self.output_attachment.image_barrier(
+                    vk::PipelineStageFlags2::COLOR_ATTACHMENT_OUTPUT,
+                    vk::AccessFlags2::COLOR_ATTACHMENT_WRITE,
+                    vk::PipelineStageFlags2::COLOR_ATTACHMENT_OUTPUT,
+                    vk::AccessFlags2::COLOR_ATTACHMENT_READ
+                        | vk::AccessFlags2::COLOR_ATTACHMENT_WRITE,
+                    vk::ImageLayout::COLOR_ATTACHMENT_OPTIMAL,
+                ),
+            ];
 
             let dep_info = vk::DependencyInfo::default().image_memory_barriers(&barriers);
+            device.cmd_pipeline_barrier2(self.render_cmd_buffer, &dep_info);
+        }
 
-            device.cmd_pipeline_barrier2(cmd, &dep_info);
+        self.begin_output_rendering(device, viewport, scissor);
+    }
+
+    /// Ends the current pass and begins drawing over the composed output only
+    pub fn start_overlay_pass(
+        &mut self,
+        device: &LogicalDevice,
+        viewport: vk::Rect2D,
+        scissor: vk::Rect2D,
+    ) {
+        unsafe {
+            device.cmd_end_rendering(self.render_cmd_buffer);
+
+            let barriers = [self.output_attachment.image_barrier(
+                vk::PipelineStageFlags2::COLOR_ATTACHMENT_OUTPUT,
+                vk::AccessFlags2::COLOR_ATTACHMENT_WRITE,
+                vk::PipelineStageFlags2::COLOR_ATTACHMENT_OUTPUT,
+                vk::AccessFlags2::COLOR_ATTACHMENT_READ | vk::AccessFlags2::COLOR_ATTACHMENT_WRITE,
+                vk::ImageLayout::COLOR_ATTACHMENT_OPTIMAL,
+            )];
+
+            let dep_info = vk::DependencyInfo::default().image_memory_barriers(&barriers);
+            device.cmd_pipeline_barrier2(self.render_cmd_buffer, &dep_info);
         }
+
+        self.begin_output_rendering(device, viewport, scissor);
+    }
+
+    fn begin_output_rendering(
+        &mut self,
+        device: &LogicalDevice,
+        viewport: vk::Rect2D,
+        scissor: vk::Rect2D,
+    ) {
+        let output_attachment = [vk::RenderingAttachmentInfo::default()
+            .image_view(self.output_attachment.image_view)
+            .image_layout(vk::ImageLayout::COLOR_ATTACHMENT_OPTIMAL)
+            .load_op(vk::AttachmentLoadOp::LOAD)
+            .store_op(vk::AttachmentStoreOp::STORE)];
+
+        let rendering_info = vk::RenderingInfo::default()
+            .color_attachments(&output_attachment)
+            .layer_count(1)
+            .render_area(scissor);
+
+        self.set_viewport_and_scissor(device, viewport, scissor);
+
+        unsafe { device.cmd_begin_rendering(self.render_cmd_buffer, &rendering_info) };
+    }
+
+    pub fn full_area(&self) -> vk::Rect2D {
+        vk::Rect2D {
+            extent: vk::Extent2D {
+                width: self.size.x,
+                height: self.size.y,
+            },
+            offset: vk::Offset2D::default(),
+        }
+    }
+
+    /// Sets a flipped viewport, so `+Y` points up like in OpenGL
+    fn set_viewport_and_scissor(
+        &self,
+        device: &LogicalDevice,
+        viewport: vk::Rect2D,
+        scissor: vk::Rect2D,
+    ) {
+        unsafe {
+            device.cmd_set_scissor(self.render_cmd_buffer, 0, &[scissor]);
+
+            device.cmd_set_viewport(self.render_cmd_buffer, 0, &[flipped_viewport(viewport)]);
+        }
+    }
+
+    pub fn end_rendering(&mut self, device: &LogicalDevice) {
+        unsafe { device.cmd_end_rendering(self.render_cmd_buffer) }
+    }
+
+    pub fn prepare_transfer(&mut self, device: &LogicalDevice) {
+        let cmd = self.render_cmd_buffer;
+        record_output_barrier(device, cmd, self.output_image_mut(), false);
+    }
+
+    /// Makes the output readable by fragment shaders of later submissions on
+    /// the graphics queue
+    pub fn prepare_sampling(&mut self, device: &LogicalDevice) {
+        let cmd = self.render_cmd_buffer;
+        record_output_barrier(device, cmd, self.output_image_mut(), true);
     }
 
     pub fn submit_render(&self, device: &LogicalDevice) -> RenderingResult<()> {
-        unsafe { device.end_command_buffer(self.render_cmd_buffer) };
+        unsafe {
+            if let Some(timestamps) = self.timestamps {
+                device.cmd_write_timestamp2(
+                    self.render_cmd_buffer,
+                    vk::PipelineStageFlags2::ALL_COMMANDS,
+                    timestamps,
+                    1,
+                );
+            }
+
+            device.end_command_buffer(self.render_cmd_buffer)
+        };
 
         let cmd = [self.render_cmd_buffer];
         let signal_semaphores = [self.render_complete];
@@ -467,18 +1539,23 @@ impl ImageRenderTarget {
         Ok(())
     }
 
+    /// Earlier packages of the frame may have read the attachments in their
+    /// composition pass and left the depth for this one
     fn transition_images_to_deferred(&mut self, device: &LogicalDevice) {
         let attachment_barriers = [
             &mut self.color_attachment,
             &mut self.normals_attachment,
             &mut self.position_depth_attachment,
+            &mut self.material_attachment,
+            &mut self.velocity_attachment,
         ]
         .map(|image| unsafe {
             image.image_barrier(
-                vk::PipelineStageFlags2::COLOR_ATTACHMENT_OUTPUT,
+                vk::PipelineStageFlags2::COLOR_ATTACHMENT_OUTPUT
+                    | vk::PipelineStageFlags2::FRAGMENT_SHADER,
                 vk::AccessFlags2::empty(),
                 vk::PipelineStageFlags2::COLOR_ATTACHMENT_OUTPUT,
-                vk::AccessFlags2::COLOR_ATTACHMENT_WRITE,
+                vk::AccessFlags2::COLOR_ATTACHMENT_READ | vk::AccessFlags2::COLOR_ATTACHMENT_WRITE,
                 vk::ImageLayout::COLOR_ATTACHMENT_OPTIMAL,
             )
             // image.image_barrier(
@@ -492,10 +1569,13 @@ impl ImageRenderTarget {
 
         let depth_barrier = unsafe {
             self.depth_image.image_barrier(
-                vk::PipelineStageFlags2::TOP_OF_PIPE,
-                vk::AccessFlags2::empty(),
-                vk::PipelineStageFlags2::EARLY_FRAGMENT_TESTS,
+                vk::PipelineStageFlags2::EARLY_FRAGMENT_TESTS
+                    | vk::PipelineStageFlags2::LATE_FRAGMENT_TESTS,
                 vk::AccessFlags2::DEPTH_STENCIL_ATTACHMENT_WRITE,
+                vk::PipelineStageFlags2::EARLY_FRAGMENT_TESTS
+                    | vk::PipelineStageFlags2::LATE_FRAGMENT_TESTS,
+                vk::AccessFlags2::DEPTH_STENCIL_ATTACHMENT_READ
+                    | vk::AccessFlags2::DEPTH_STENCIL_ATTACHMENT_WRITE,
                 vk::ImageLayout::DEPTH_ATTACHMENT_OPTIMAL,
             )
         };
@@ -508,10 +1588,12 @@ impl ImageRenderTarget {
     }
 
     fn transition_images_to_composition(&mut self, device: &LogicalDevice) {
-        let image_barriers = [
+        let attachment_barriers = [
             &mut self.color_attachment,
             &mut self.normals_attachment,
             &mut self.position_depth_attachment,
+            &mut self.material_attachment,
+            &mut self.velocity_attachment,
         ]
         .map(|image| unsafe {
             image.image_barrier(
@@ -523,12 +1605,67 @@ impl ImageRenderTarget {
             )
         });
 
+        // The output may still be read by a blit or by materials sampling it in
+        // earlier submissions, earlier packages of this frame are kept
+        let output_barrier = unsafe {
+            self.output_attachment.image_barrier(
+                vk::PipelineStageFlags2::COLOR_ATTACHMENT_OUTPUT
+                    | vk::PipelineStageFlags2::FRAGMENT_SHADER
+                    | vk::PipelineStageFlags2::TRANSFER,
+                vk::AccessFlags2::COLOR_ATTACHMENT_WRITE,
+                vk::PipelineStageFlags2::COLOR_ATTACHMENT_OUTPUT,
+                vk::AccessFlags2::COLOR_ATTACHMENT_READ | vk::AccessFlags2::COLOR_ATTACHMENT_WRITE,
+                vk::ImageLayout::COLOR_ATTACHMENT_OPTIMAL,
+            )
+        };
+
+        let image_barriers = [&attachment_barriers[..], &[output_barrier][..]].concat();
+
         let dependency_info = vk::DependencyInfo::default().image_memory_barriers(&image_barriers);
 
         unsafe { device.cmd_pipeline_barrier2(self.render_cmd_buffer, &dependency_info) };
     }
 }
 
+/// Moves a freshly drawn `image` into `SHADER_READ_ONLY_OPTIMAL` if it's `sampled`
+/// and into `TRANSFER_SRC_OPTIMAL` otherwise
+fn record_output_barrier(
+    device: &LogicalDevice,
+    cmd: vk::CommandBuffer,
+    image: &mut VulkanImage,
+    sampled: bool,
+) {
+    let (dst_stage_mask, dst_access_mask, layout) = if sampled {
+        (
+            vk::PipelineStageFlags2::FRAGMENT_SHADER,
+            vk::AccessFlags2::SHADER_SAMPLED_READ,
+            vk::ImageLayout::SHADER_READ_ONLY_OPTIMAL,
+        )
+    } else {
+        (
+            vk::PipelineStageFlags2::TRANSFER,
+            vk::AccessFlags2::TRANSFER_READ,
+            vk::ImageLayout::TRANSFER_SRC_OPTIMAL,
+        )
+    };
+
+    unsafe {
+        let image_barrier = image.image_barrier(
+            vk::PipelineStageFlags2::COLOR_ATTACHMENT_OUTPUT,
+            vk::AccessFlags2::COLOR_ATTACHMENT_WRITE,
+            dst_stage_mask,
+            dst_access_mask,
+            layout,
+        );
+
+        let barriers = [image_barrier];
+
+        let dep_info = vk::DependencyInfo::default().image_memory_barriers(&barriers);
+
+        device.cmd_pipeline_barrier2(cmd, &dep_info);
+    }
+}
+
 impl Drop for ImageRenderTarget {
     fn drop(&mut self) {
         let device = get_device();
@@ -539,6 +1676,10 @@ impl Drop for ImageRenderTarget {
             }
             device.destroy_fence(self.in_flight_fence, None);
             device.destroy_semaphore(self.render_complete, None);
+
+            if let Some(timestamps) = self.timestamps {
+                device.destroy_query_pool(timestamps, None);
+            }
         }
     }
 }
@@ -0,0 +1,45 @@
+use ash::vk;
+use bizarre_core::Handle;
+
+use crate::{render_target::RenderTargetHandle, sampler::SamplerDesc};
+
+pub type RenderTextureHandle = Handle<RenderTexture>;
+
+/// Output of a render target sampled by materials, for mirrors, portals,
+/// in-game screens and editor viewport previews
+///
+/// Registered with [`RenderAssets::create_render_texture`](crate::render_assets::RenderAssets::create_render_texture)
+/// and bound with [`MaterialInstance::set_render_texture`](crate::material::material_instance::MaterialInstance::set_render_texture).
+/// Materials see the last render submitted before them, so the target should be
+/// rendered first to avoid a frame of latency. The image can be bigger than the
+/// rendered area, see [`SwapchainRenderTarget::sampled_uv_scale`](crate::render_target::SwapchainRenderTarget::sampled_uv_scale)
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RenderTexture {
+    render_target: RenderTargetHandle,
+    sampler: SamplerDesc,
+}
+
+impl RenderTexture {
+    pub(crate) fn new(render_target: RenderTargetHandle, sampler: SamplerDesc) -> Self {
+        Self {
+            render_target,
+            sampler,
+        }
+    }
+
+    pub fn render_target(&self) -> RenderTargetHandle {
+        self.render_target
+    }
+
+    pub fn sampler(&self) -> &SamplerDesc {
+        &self.sampler
+    }
+}
+
+/// Sampler the render textures get by default, the output has no mips
+pub fn render_texture_sampler() -> SamplerDesc {
+    SamplerDesc {
+        mipmap_mode: vk::SamplerMipmapMode::NEAREST,
+        ..SamplerDesc::linear(vk::SamplerAddressMode::CLAMP_TO_EDGE)
+    }
+}
@@ -1,33 +1,51 @@
 use core::fmt::Debug;
-use std::ffi::{CStr, CString};
+use std::{
+    collections::{hash_map::Entry, HashMap},
+    ffi::{CStr, CString},
+    time::Instant,
+};
 
 use ash::vk;
-use bizarre_log::{core_info, core_trace};
-use nalgebra_glm::UVec2;
+use bizarre_log::{core_info, core_trace, core_warn};
+use nalgebra_glm::{Mat4, UVec2, Vec2, Vec4};
 use thiserror::Error;
 
-use bizarre_core::Handle;
 use bizarre_ecs::prelude::Resource;
 
 use crate::{
-    antialiasing::Antialiasing,
-    buffer::GpuBuffer,
-    device::logical_device::DeviceError,
+    antialiasing::{taa_jitter, Antialiasing},
+    buffer::{BufferError, GpuBuffer},
+    camera::render_rects,
+    command_buffer::{CommandRecording, SecondaryCommandBuffer},
+    culling::{frustum_planes, GpuCulling},
+    device::{logical_device::DeviceError, DeviceCapabilities, LogicalDevice},
     image::VulkanImage,
     instance::InstanceError,
     material::{
-        builtin::basic_composition,
+        builtin::{
+            basic_composition, selection_mask, selection_outline, sprites, taa_resolve,
+            COMPOSITION_INPUT_COUNT,
+        },
         descriptor_buffer::{self, DescriptorBuffer},
+        instance_binding::InstanceBinding,
         material_instance::{MaterialInstance, MaterialInstanceHandle},
         pipeline::PipelineError,
         Material, MaterialHandle,
     },
-    present_target::{PresentData, PresentResult, PresentTargetHandle},
-    render_assets::{AssetStore, RenderAssets},
-    render_target::RenderTargetHandle,
-    scene::{object_pass::SceneObjectPass, IndirectIterItem, Scene, SceneUniform},
-    submitter::RenderPackage,
-    vulkan_context::{get_device, get_instance},
+    mesh::MeshUsage,
+    present_target::{PresentData, PresentError, PresentResult, PresentTargetHandle},
+    render_assets::{AssetStore, DenseAssetStore, RenderAssets},
+    render_target::{RenderTargetHandle, SwapchainRenderTarget},
+    render_texture::RenderTextureHandle,
+    sampler::{get_sampler, SamplerDesc},
+    scene::{
+        light::LightUniform, object_pass::SceneObjectPass, IndirectIterItem, Scene, SceneError,
+        SceneUniform,
+    },
+    sprite::{Sprite, SpriteBatch, MAX_SPRITES_PER_FRAME, MAX_SPRITE_TEXTURES},
+    submitter::{ClearMode, RenderPackage},
+    texture::{Texture, TextureError, TextureHandle},
+    vulkan_context::{get_device, get_instance, recreate_device},
 };
 
 #[derive(Resource)]
@@ -41,28 +59,182 @@ pub struct VulkanRenderer {
     curr_uniform_index: usize,
 
     textures: DescriptorBuffer,
+    /// Single color textures sampled by samplers without a texture bound
+    fallback_textures: HashMap<[u8; 4], Texture>,
     curr_texture_index: usize,
 
     input_attachments: DescriptorBuffer,
     curr_input_index: usize,
 
+    camera_uniforms: Vec<GpuBuffer>,
+    /// Scene lights of every render package, per frame
+    light_uniforms: Vec<GpuBuffer>,
+
     basic_composition: Material,
     basic_composition_instance: MaterialInstance,
+    /// Composition material of every target replaced by the built-in one,
+    /// warned about once
+    rejected_compositions: HashMap<RenderTargetHandle, MaterialHandle>,
+    taa_resolve: Material,
+
+    selection_mask: Material,
+    selection_outline_material: Material,
+    selection_outline: SelectionOutline,
+
+    sprite_material: Material,
+    /// Sprites of every render package, per frame
+    sprite_buffers: Vec<GpuBuffer>,
+    curr_sprite_index: usize,
+    /// Drawn over the last camera of the target by `render_cameras`
+    queued_sprites: HashMap<RenderTargetHandle, Vec<SpriteBatch>>,
+
+    gpu_culling: Option<GpuCulling>,
+    /// Culls objects hidden behind the depth pre-pass, needs GPU culling
+    occlusion_culling: bool,
+
+    secondary_command_buffers: bool,
+    /// Scene draws of every render package, re-recorded only when they change
+    secondary_draws: HashMap<SecondaryDrawKey, SecondaryCommandBuffer>,
+
+    start_time: Instant,
+    last_renders: HashMap<RenderTargetHandle, Instant>,
+    /// Keyed by the render target and the index of the package in the frame
+    camera_history: HashMap<(RenderTargetHandle, usize), CameraHistory>,
+}
+
+/// Pass of a render package drawn from a secondary command buffer
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+enum DrawPass {
+    Prepass,
+    Deferred,
+}
+
+/// Render target, index of its image, index of the package in the frame and the pass
+type SecondaryDrawKey = (RenderTargetHandle, usize, usize, DrawPass);
+
+/// Outline drawn around objects selected with [`Scene::set_object_selected`]
+#[derive(Clone, Copy, Debug)]
+pub struct SelectionOutline {
+    pub color: Vec4,
+    /// Width in pixels
+    pub width: u32,
+}
+
+impl Default for SelectionOutline {
+    fn default() -> Self {
+        Self {
+            color: Vec4::new(1.0, 0.6, 0.1, 1.0),
+            width: 2,
+        }
+    }
+}
+
+/// Descriptor of a set bound to a material instance
+#[derive(Clone, Copy, Debug)]
+struct InstanceSet {
+    set: u32,
+    /// Index of the descriptor buffer among the bound ones, see
+    /// [`UNIFORM_BUFFER_INDEX`] and [`TEXTURE_BUFFER_INDEX`]
+    buffer_index: u32,
+    offset: vk::DeviceSize,
+}
+
+/// Index of the uniform descriptor buffer when drawing scene objects, it holds
+/// storage buffer descriptors too
+const UNIFORM_BUFFER_INDEX: u32 = 0;
+/// Index of the texture descriptor buffer when drawing scene objects
+const TEXTURE_BUFFER_INDEX: u32 = 1;
+
+/// Composition pipeline of a render target
+struct CompositionPass {
+    pipeline: vk::Pipeline,
+    layout: vk::PipelineLayout,
+    /// Sets bound by the renderer: input attachments, scene uniform and lights
+    set_count: usize,
+    /// G-buffer attachment read by every input attachment descriptor of `set = 0`
+    inputs: Vec<usize>,
+}
+
+/// Sets of a composition pipeline the renderer binds
+const COMPOSITION_SET_COUNT: usize = 3;
+
+/// Push constants of `sprite.vert`
+#[repr(C)]
+struct SpritePushConstants {
+    viewport_size: [f32; 2],
+}
+
+/// Push constants of `selection_mask.vert`
+#[repr(C)]
+struct SelectionMaskPushConstants {
+    stride: u32,
+    transform_offset: u32,
+}
+
+/// Push constants of `selection_outline.frag`
+#[repr(C)]
+struct SelectionOutlinePushConstants {
+    color: [f32; 4],
+    width: i32,
+}
+
+/// What a camera rendered with in the previous frame
+struct CameraHistory {
+    /// Without the jitter
+    view_projection: Mat4,
+    jitter: Vec2,
+    frame_index: u32,
 }
 
 #[derive(Error, Debug)]
 pub enum RenderError {
     #[error(transparent)]
-    VulkanError(#[from] vk::Result),
+    VulkanError(vk::Result),
+    /// The device was lost, the renderer must be recovered with
+    /// [`VulkanRenderer::recover_device`] before it can render again
+    #[error("Vulkan device lost")]
+    DeviceLost,
     #[error("Failed to create a `VulkanRenderer`: {0}")]
     CreateError(#[from] RendererCreateError),
     #[error(transparent)]
     PipelineError(#[from] PipelineError),
+    #[error(transparent)]
+    BufferError(#[from] BufferError),
+    #[error(transparent)]
+    SceneError(#[from] SceneError),
+    #[error(transparent)]
+    PresentError(#[from] PresentError),
+    #[error(transparent)]
+    TextureError(#[from] TextureError),
     #[error("Invalid render target")]
     InvalidRenderTarget,
+    #[error("Invalid scene")]
+    InvalidScene,
+    #[error("Too many render packages for one target: {0} (max {MAX_CAMERAS_PER_FRAME})")]
+    TooManyPackages(usize),
     #[error("Render must be skipped")]
     RenderSkipped,
 }
+impl From<vk::Result> for RenderError {
+    fn from(value: vk::Result) -> Self {
+        match value {
+            vk::Result::ERROR_DEVICE_LOST => Self::DeviceLost,
+            value => Self::VulkanError(value),
+        }
+    }
+}
+
+/// Pushed into the `EventQueue` when the renderer loses its device, so the game
+/// can tell the player the graphics were restarted
+#[derive(Clone, Debug)]
+pub enum DeviceEvent {
+    Lost,
+    /// The device and every restorable GPU resource were recreated
+    Restored,
+    /// The device couldn't be recreated, nothing will be rendered anymore
+    RecoveryFailed(String),
+}
+
 #[derive(Error, Debug)]
 pub enum RendererCreateError {
     #[error(transparent)]
@@ -73,9 +245,18 @@ pub enum RendererCreateError {
 
 const IMAGE_COUNT: usize = 4;
 const UNIFORM_DESCRIPTOR_BUFFER_LEN: usize = 32;
-const TEXTURE_DESCRIPTOR_BUFFER_LEN: usize = 32;
+/// Texture arrays take several elements, see [`DescriptorBuffer::array_elements`]
+const TEXTURE_DESCRIPTOR_BUFFER_LEN: usize = 256;
 const INPUT_ATTACHMENT_BUFFER_LEN: usize = 32;
 
+/// Max amount of render packages with their own camera in a single frame
+pub const MAX_CAMERAS_PER_FRAME: usize = 16;
+/// Offset between camera uniforms, satisfies any `minUniformBufferOffsetAlignment`
+const CAMERA_UNIFORM_STRIDE: usize = 256;
+/// Offset between light uniforms, aligned like [`CAMERA_UNIFORM_STRIDE`]
+const LIGHT_UNIFORM_STRIDE: usize =
+    size_of::<LightUniform>().next_multiple_of(CAMERA_UNIFORM_STRIDE);
+
 const fn descriptor_buffer_len(descriptor_type: vk::DescriptorType) -> usize {
     match descriptor_type {
         vk::DescriptorType::UNIFORM_BUFFER => UNIFORM_DESCRIPTOR_BUFFER_LEN,
@@ -117,6 +298,35 @@ impl VulkanRenderer {
 
         device.set_object_debug_name(textures.buffer(), "renderer_input_attachments");
 
+        let per_package_uniforms = |stride: usize| {
+            (0..IMAGE_COUNT)
+                .map(|_| {
+                    GpuBuffer::new(
+                        (stride * MAX_CAMERAS_PER_FRAME) as vk::DeviceSize,
+                        vk::BufferUsageFlags::UNIFORM_BUFFER
+                            | vk::BufferUsageFlags::SHADER_DEVICE_ADDRESS,
+                        vma::MemoryUsage::Auto,
+                        vma::AllocationCreateFlags::HOST_ACCESS_RANDOM,
+                    )
+                })
+                .collect::<Result<Vec<_>, _>>()
+        };
+
+        let camera_uniforms = per_package_uniforms(CAMERA_UNIFORM_STRIDE)?;
+        let light_uniforms = per_package_uniforms(LIGHT_UNIFORM_STRIDE)?;
+
+        let sprite_buffers = (0..IMAGE_COUNT)
+            .map(|_| {
+                GpuBuffer::new(
+                    (size_of::<Sprite>() * MAX_SPRITES_PER_FRAME) as vk::DeviceSize,
+                    vk::BufferUsageFlags::STORAGE_BUFFER
+                        | vk::BufferUsageFlags::SHADER_DEVICE_ADDRESS,
+                    vma::MemoryUsage::Auto,
+                    vma::AllocationCreateFlags::HOST_ACCESS_RANDOM,
+                )
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+
         let basic_composition_mat = basic_composition();
         let basic_composition_instance =
             MaterialInstance::new(MaterialHandle::from_raw(0usize), &basic_composition_mat)
@@ -133,20 +343,245 @@ impl VulkanRenderer {
             swapchain_loader,
             uniform_buffers,
             textures,
+            fallback_textures: HashMap::new(),
             input_attachments,
+            camera_uniforms,
+            light_uniforms,
 
             basic_composition: basic_composition_mat,
             basic_composition_instance,
+            rejected_compositions: HashMap::new(),
+            taa_resolve: taa_resolve(),
+
+            selection_mask: selection_mask(),
+            selection_outline_material: selection_outline(),
+            selection_outline: SelectionOutline::default(),
+
+            sprite_material: sprites(),
+            sprite_buffers,
+            curr_sprite_index: 0,
+            queued_sprites: HashMap::new(),
+
+            gpu_culling: None,
+            occlusion_culling: false,
+
+            secondary_command_buffers: true,
+            secondary_draws: HashMap::new(),
+
+            start_time: Instant::now(),
+            last_renders: HashMap::new(),
+            camera_history: HashMap::new(),
         })
     }
 
+    /// Recreates the device after [`RenderError::DeviceLost`].
+    ///
+    /// Everything in `assets` is rebuilt from its CPU-side description, materials
+    /// created from a raw pipeline with [`Material::new`] can't be restored
+    pub fn recover_device(&mut self, assets: &mut RenderAssets) -> RenderResult<()> {
+        core_warn!("Vulkan device lost, recreating it");
+
+        self.release_gpu_resources();
+        let released = assets.release_gpu_resources();
+
+        // SAFETY: everything created with the lost device was destroyed above
+        unsafe { recreate_device() }.map_err(RendererCreateError::from)?;
+
+        assets.restore_gpu_resources(released)?;
+
+        let antialiasing = self.antialiasing;
+        let start_time = self.start_time;
+        let gpu_culling = self.gpu_culling.is_some();
+        let occlusion_culling = self.occlusion_culling;
+        let selection_outline = self.selection_outline;
+        let secondary_command_buffers = self.secondary_command_buffers;
+        *self = Self::new()?;
+        self.antialiasing = antialiasing;
+        self.secondary_command_buffers = secondary_command_buffers;
+        self.selection_outline = selection_outline;
+        self.start_time = start_time;
+        self.occlusion_culling = occlusion_culling;
+        self.set_gpu_culling(gpu_culling)?;
+
+        core_info!("Vulkan device recreated");
+
+        Ok(())
+    }
+
+    fn release_gpu_resources(&mut self) {
+        let device = get_device();
+
+        // The wait fails on a lost device, but the work is gone in that case anyway
+        let _ = unsafe { device.device_wait_idle() };
+
+        self.uniform_buffers.destroy();
+        self.textures.destroy();
+        self.fallback_textures.clear();
+        self.input_attachments.destroy();
+
+        self.camera_uniforms
+            .iter_mut()
+            .chain(self.light_uniforms.iter_mut())
+            .chain(self.sprite_buffers.iter_mut())
+            .for_each(|buffer| buffer.destroy(device));
+
+        self.basic_composition.release(device);
+        self.taa_resolve.release(device);
+        self.selection_mask.release(device);
+        self.selection_outline_material.release(device);
+        self.sprite_material.release(device);
+
+        if let Some(gpu_culling) = &mut self.gpu_culling {
+            gpu_culling.destroy(device);
+        }
+
+        self.destroy_secondary_draws(device);
+    }
+
+    /// Switches between frustum culling on the GPU and drawing every object with
+    /// the indirect commands built on the CPU
+    pub fn set_gpu_culling(&mut self, enabled: bool) -> RenderResult<()> {
+        let device = get_device();
+
+        match (enabled, &mut self.gpu_culling) {
+            (true, None) => self.gpu_culling = Some(GpuCulling::new(device)?),
+            (false, Some(gpu_culling)) => {
+                unsafe { device.device_wait_idle()? };
+
+                gpu_culling.destroy(device);
+                self.gpu_culling = None;
+            }
+            _ => (),
+        }
+
+        Ok(())
+    }
+
+    pub fn gpu_culling(&self) -> bool {
+        self.gpu_culling.is_some()
+    }
+
+    /// Culls objects hidden behind the depth pre-pass against a Hi-Z pyramid of
+    /// it before the deferred pass.
+    ///
+    /// Takes effect only with GPU culling, on single sampled targets with a
+    /// depth pre-pass, for packages that clear the depth of their whole viewport
+    pub fn set_occlusion_culling(&mut self, enabled: bool) {
+        self.occlusion_culling = enabled;
+    }
+
+    pub fn occlusion_culling(&self) -> bool {
+        self.occlusion_culling
+    }
+
+    /// Support matrix of the device the renderer runs on
+    pub fn device_capabilities(&self) -> &'static DeviceCapabilities {
+        get_device().capabilities()
+    }
+
+    /// Switches between drawing scene objects from secondary command buffers,
+    /// re-recorded only when the draws change, and recording them into the
+    /// primary command buffer every frame
+    pub fn set_secondary_command_buffers(&mut self, enabled: bool) -> RenderResult<()> {
+        if !enabled && self.secondary_command_buffers {
+            let device = get_device();
+            unsafe { device.device_wait_idle()? };
+
+            self.destroy_secondary_draws(device);
+        }
+
+        self.secondary_command_buffers = enabled;
+
+        Ok(())
+    }
+
+    pub fn secondary_command_buffers(&self) -> bool {
+        self.secondary_command_buffers
+    }
+
+    fn destroy_secondary_draws(&mut self, device: &LogicalDevice) {
+        self.secondary_draws
+            .drain()
+            .for_each(|(_, mut secondary)| secondary.destroy(device));
+    }
+
+    /// Executes `commands` in the pass begun on `render_target`, from the cached
+    /// secondary command buffer of `key` when they are enabled
+    fn execute_draws(
+        &mut self,
+        device: &LogicalDevice,
+        render_target: &SwapchainRenderTarget,
+        (render_target_handle, package_index, pass): (RenderTargetHandle, usize, DrawPass),
+        commands: CommandRecording,
+    ) -> RenderResult<()> {
+        if !self.secondary_command_buffers {
+            commands.replay(device, render_target.cmd_buffer());
+            return Ok(());
+        }
+
+        let inheritance = match pass {
+            DrawPass::Prepass => render_target.prepass_inheritance(),
+            DrawPass::Deferred => render_target.deferred_inheritance(),
+        };
+
+        let key = (
+            render_target_handle,
+            render_target.image_index(),
+            package_index,
+            pass,
+        );
+
+        let secondary = match self.secondary_draws.entry(key) {
+            Entry::Occupied(entry) => entry.into_mut(),
+            Entry::Vacant(entry) => entry.insert(SecondaryCommandBuffer::new(device)?),
+        };
+
+        secondary.update(device, &inheritance, commands)?;
+        secondary.execute(device, render_target.cmd_buffer());
+
+        Ok(())
+    }
+
+    pub fn set_selection_outline(&mut self, selection_outline: SelectionOutline) {
+        self.selection_outline = selection_outline;
+    }
+
+    pub fn selection_outline(&self) -> SelectionOutline {
+        self.selection_outline
+    }
+
+    /// Queues sprites for the next [`render_cameras`](crate::ecs::render_cameras)
+    /// of the render target, they are drawn over its last camera. Replaces the
+    /// sprites queued for the target before, so they don't pile up while the
+    /// target isn't rendered
+    pub fn queue_sprites(
+        &mut self,
+        render_target: RenderTargetHandle,
+        batches: impl IntoIterator<Item = SpriteBatch>,
+    ) {
+        self.queued_sprites
+            .insert(render_target, batches.into_iter().collect());
+    }
+
+    pub fn take_queued_sprites(&mut self, render_target: RenderTargetHandle) -> Vec<SpriteBatch> {
+        self.queued_sprites
+            .remove(&render_target)
+            .unwrap_or_default()
+    }
+
     pub fn next_frame(&mut self) {
         self.current_frame = (self.current_frame + 1) % self.image_count as usize;
         self.curr_uniform_index = 0;
         self.curr_texture_index = 0;
         self.curr_input_index = 0;
+        self.curr_sprite_index = 0;
     }
 
+    /// Records a single package into `render_target`.
+    ///
+    /// The camera of the package is written into the uniform buffer of the current
+    /// frame while recording, so it's always drawn with the matrices it was
+    /// submitted with, see [`RenderPackage::with_camera`]
     pub fn render_to_target(
         &mut self,
         assets: &mut RenderAssets,
@@ -154,190 +589,1164 @@ impl VulkanRenderer {
         render_extent: UVec2,
         render_package: RenderPackage,
     ) -> RenderResult<()> {
-        if render_extent.x == 0 || render_extent.y == 0 {
+        self.render_packages_to_target(assets, render_target, render_extent, &[render_package])
+    }
+
+    /// Records all `packages` into a single submission of `render_target`.
+    ///
+    /// Packages are drawn in the given order, each one limited to its viewport,
+    /// so later packages are drawn over the earlier ones. Skipped while the target
+    /// is only presented to hidden windows, see [`RenderAssets::is_render_target_visible`]
+    pub fn render_packages_to_target(
+        &mut self,
+        assets: &mut RenderAssets,
+        render_target: RenderTargetHandle,
+        render_extent: UVec2,
+        packages: &[RenderPackage],
+    ) -> RenderResult<()> {
+        if render_extent.x == 0
+            || render_extent.y == 0
+            || packages.is_empty()
+            || !assets.is_render_target_visible(&render_target)
+        {
             return Err(RenderError::RenderSkipped);
         }
 
-        let RenderPackage {
-            scene: scene_handle,
-            pov,
-        } = render_package;
-
-        let device = get_device();
-
-        // TODO: I'm really sorry for what I've done. But it's safe, I'm promise. I'll fix that later
-        let scene = unsafe { &mut *(assets.scenes.get_mut(&scene_handle).unwrap() as *mut Scene) };
+        if packages.len() > MAX_CAMERAS_PER_FRAME {
+            return Err(RenderError::TooManyPackages(packages.len()));
+        }
 
-        scene.sync_frame_data(&assets.meshes);
+        // Drawn at the render scale of the target, presenting scales it back
+        let output_extent = render_extent;
+        let render_extent = assets
+            .render_targets
+            .get(&render_target)
+            .ok_or(RenderError::InvalidRenderTarget)?
+            .scaled_extent(output_extent);
 
-        let (indirect_buffer, indirect_iter) = scene.indirect_draw_iterator();
-        let (_, scene_ubo_offset) =
-            self.add_uniform(scene.scene_ubo(), 0, scene.scene_ubo().size());
+        let device = get_device();
 
-        let vertex_buffer = scene.vertex_buffer();
-        let index_buffer = scene.index_buffer();
+        let now = Instant::now();
+        let time = (now - self.start_time).as_secs_f32();
+        let delta_time = self
+            .last_renders
+            .insert(render_target, now)
+            .map_or(0.0, |last| (now - last).as_secs_f32());
 
         #[derive(Debug)]
         struct DrawItem {
-            mat_handle: MaterialHandle,
-            inst_handle: MaterialInstanceHandle,
             pipeline: vk::Pipeline,
             pipeline_layout: vk::PipelineLayout,
+            /// Depth-only pipeline and its layout when the batch is drawn in the pre-pass
+            prepass: Option<(vk::Pipeline, vk::PipelineLayout)>,
+            mesh_usage: MeshUsage,
+            instance_data_type: vk::DescriptorType,
             indirect_offset: u64,
             batch_offset: u64,
             batch_range: u64,
             count: u32,
+            culled_offset: u64,
+            draw_count_offset: u64,
+            max_count: u32,
+            instance: MaterialInstanceHandle,
+            /// Sets bound to the material instance with their descriptor offsets
+            instance_sets: Vec<InstanceSet>,
+            /// Objects of the batch are drawn into the selection mask
+            selected: bool,
+            /// Size of the instance data of one object in 4 byte words
+            instance_stride: u32,
+            /// Offset of the `transform` in the instance data in 4 byte words,
+            /// batches without one can't be outlined
+            transform_offset: Option<u32>,
         }
 
-        let deferred_indirects = indirect_iter
-            .clone()
-            .filter_map(
-                |IndirectIterItem {
-                     materials,
-                     indirect_offset,
-                     count,
-                     batch_offset,
-                     batch_range,
-                 }| {
-                    let instance_handle = materials[SceneObjectPass::Deferred]?;
-                    let (material, instance) = assets.material_with_instance(&instance_handle)?;
-
-                    let pipeline = material.pipeline();
-
-                    Some(DrawItem {
-                        mat_handle: instance.material_handle(),
-                        inst_handle: instance_handle,
-                        pipeline: pipeline.pipeline,
-                        pipeline_layout: pipeline.layout,
-                        indirect_offset,
-                        count,
-                        batch_offset,
-                        batch_range,
-                    })
-                },
-            )
+        struct SkyboxDraw {
+            pipeline: vk::Pipeline,
+            pipeline_layout: vk::PipelineLayout,
+            instance_sets: Vec<InstanceSet>,
+        }
+
+        struct PackageDraw<'a> {
+            scene: &'a Scene,
+            area: vk::Rect2D,
+            scissor: vk::Rect2D,
+            scene_ubo_offset: vk::DeviceSize,
+            light_ubo_offset: vk::DeviceSize,
+            /// Frustum to cull the scene against, `None` draws everything
+            frustum: Option<[Vec4; 6]>,
+            /// View-projection to cull the scene against the depth pre-pass with
+            occlusion: Option<Mat4>,
+            clear_color: Option<Vec4>,
+            clear_depth: Option<f32>,
+            skybox: Option<SkyboxDraw>,
+            items: Vec<DrawItem>,
+            sprites: &'a [SpriteBatch],
+        }
+
+        let visible_packages = packages
+            .iter()
+            .enumerate()
+            .filter_map(|(camera_index, package)| {
+                render_rects(&package.viewport, package.scissor.as_ref(), render_extent)
+                    .map(|rects| (camera_index, package, rects))
+            })
             .collect::<Vec<_>>();
 
+        // Scenes are synced up front, so the draws below only need shared borrows
+        let mut synced_scenes = Vec::with_capacity(packages.len());
+
+        for (_, package, _) in visible_packages.iter() {
+            if synced_scenes.contains(&package.scene) {
+                continue;
+            }
+
+            let scene = assets
+                .scenes
+                .get_mut(&package.scene)
+                .ok_or(RenderError::InvalidScene)?;
+
+            scene.sync_frame_data(&mut assets.mesh_pool, &assets.meshes);
+            scene.interpolate_transforms(package.interpolation);
+            scene.write_previous_transforms();
+
+            synced_scenes.push(package.scene);
+        }
+
+        let (depth_prepass, temporal_antialiasing, samples) = assets
+            .render_targets
+            .get(&render_target)
+            .map(|target| {
+                (
+                    target.depth_prepass(),
+                    target.temporal_antialiasing(),
+                    target.samples(),
+                )
+            })
+            .ok_or(RenderError::InvalidRenderTarget)?;
+
+        // Multisampled depth can't be copied into the pyramid
+        let occlusion_culling = self.occlusion_culling
+            && self.gpu_culling.is_some()
+            && depth_prepass
+            && samples == vk::SampleCountFlags::TYPE_1;
+
+        let mut package_draws = Vec::with_capacity(packages.len());
+        // Batches sharing a material instance share its descriptors, `None` if
+        // the instance can't be drawn
+        let mut instance_sets = HashMap::<MaterialInstanceHandle, Option<Vec<InstanceSet>>>::new();
+
+        for (camera_index, package, (area, scissor)) in visible_packages {
+            let scene = assets
+                .scenes
+                .get(&package.scene)
+                .ok_or(RenderError::InvalidScene)?;
+
+            let uniform = package
+                .camera
+                .clone()
+                .or_else(|| scene.scene_uniform().cloned());
+
+            let view_projection = uniform
+                .as_ref()
+                .map(|uniform| uniform.projection * uniform.view);
+
+            let frustum = self
+                .gpu_culling
+                .as_ref()
+                .and(view_projection.as_ref())
+                .map(frustum_planes);
+
+            // Depth outside of the scissor or left by earlier packages isn't
+            // drawn by this package's pre-pass
+            let occlusion = view_projection
+                .filter(|_| occlusion_culling && package.clear_depth.is_some() && area == scissor);
+
+            let scene_ubo_offset = match uniform {
+                Some(uniform) => {
+                    let resolution = UVec2::new(area.extent.width, area.extent.height);
+                    let uniform = self.with_camera_history(
+                        (render_target, camera_index),
+                        uniform.with_frame_info(resolution, time, delta_time),
+                        temporal_antialiasing.then_some(resolution),
+                    );
+
+                    self.add_camera_uniform(camera_index, uniform)?
+                }
+                None => {
+                    self.add_uniform(scene.scene_ubo(), 0, scene.scene_ubo().size())
+                        .1
+                }
+            };
+
+            let skybox = match package.clear {
+                ClearMode::Skybox(instance_handle) => {
+                    let skybox = assets.material_with_instance(&instance_handle).and_then(
+                        |(material, instance)| {
+                            Some(SkyboxDraw {
+                                pipeline: material.pipeline().pipeline,
+                                pipeline_layout: material.pipeline().layout,
+                                instance_sets: instance_sets
+                                    .entry(instance_handle)
+                                    .or_insert_with(|| {
+                                        self.add_instance_sets(
+                                            assets,
+                                            render_target,
+                                            material,
+                                            instance,
+                                        )
+                                    })
+                                    .clone()?,
+                            })
+                        },
+                    );
+
+                    if skybox.is_none() {
+                        core_warn!(
+                            "Skybox material instance {instance_handle:?} can't be drawn, clearing instead"
+                        );
+                    }
+
+                    skybox
+                }
+                _ => None,
+            };
+
+            let clear_color = match package.clear {
+                ClearMode::Color(color) => Some(color),
+                ClearMode::Skybox(_) => Some(Vec4::zeros()),
+                ClearMode::None => None,
+            };
+
+            let (_, indirect_iter) = scene.indirect_draw_iterator();
+
+            let items = indirect_iter
+                .filter_map(
+                    |IndirectIterItem {
+                         materials,
+                         indirect_offset,
+                         count,
+                         batch_offset,
+                         batch_range,
+                         culled_offset,
+                         draw_count_offset,
+                         max_count,
+                         mesh_usage,
+                         selected,
+                         instance_stride,
+                         instance_layout,
+                     }| {
+                        let mesh_usage = mesh_usage?;
+                        let instance_handle = materials[SceneObjectPass::Deferred]?;
+                        let (material, instance) =
+                            assets.material_with_instance(&instance_handle)?;
+
+                        let Some((pipeline, instance_data_type)) =
+                            material.pipeline_for(batch_range)
+                        else {
+                            core_warn!(
+                                "Skipping a batch of {count} draws: {batch_range} bytes of instance data don't fit into a uniform buffer and {:?} has no storage fallback",
+                                instance.material_handle()
+                            );
+                            return None;
+                        };
+
+                        let instance_sets = instance_sets
+                            .entry(instance_handle)
+                            .or_insert_with(|| {
+                                self.add_instance_sets(assets, render_target, material, instance)
+                            })
+                            .clone()?;
+
+                        let (pipeline, prepass) = match depth_prepass
+                            .then(|| material.depth_prepass_for(batch_range))
+                            .flatten()
+                        {
+                            Some(prepass) => (
+                                &prepass.depth_equal,
+                                Some((prepass.depth_only.pipeline, prepass.depth_only.layout)),
+                            ),
+                            None => (pipeline, None),
+                        };
+
+                        Some(DrawItem {
+                            pipeline: pipeline.pipeline,
+                            pipeline_layout: pipeline.layout,
+                            prepass,
+                            mesh_usage,
+                            instance_data_type,
+                            indirect_offset,
+                            count,
+                            batch_offset,
+                            batch_range,
+                            culled_offset,
+                            draw_count_offset,
+                            max_count,
+                            instance: instance_handle,
+                            instance_sets,
+                            selected,
+                            instance_stride: (instance_stride / 4) as u32,
+                            transform_offset: instance_layout
+                                .field("transform")
+                                .filter(|(_, size)| *size == size_of::<Mat4>())
+                                .map(|(offset, _)| (offset / 4) as u32),
+                        })
+                    },
+                )
+                .collect::<Vec<_>>();
+
+            let light_ubo_offset = self.add_light_uniform(camera_index, scene)?;
+
+            package_draws.push(PackageDraw {
+                scene,
+                area,
+                scissor,
+                scene_ubo_offset,
+                light_ubo_offset,
+                frustum,
+                occlusion,
+                clear_color,
+                clear_depth: package.clear_depth,
+                skybox,
+                items,
+                sprites: &package.sprites,
+            });
+        }
+
+        if package_draws.is_empty() {
+            return Err(RenderError::RenderSkipped);
+        }
+
+        let mesh_pool = &assets.mesh_pool;
+        let textures = &assets.textures;
+        let materials = &assets.materials;
+
+        let render_target_handle = render_target;
         let render_target = assets
             .render_targets
             .get_mut(&render_target)
             .ok_or(RenderError::InvalidRenderTarget)?;
 
-        render_target.resize(render_extent);
+        render_target.resize(output_extent)?;
 
-        render_target.begin_rendering(device)?;
+        let composition = self.composition_pass(
+            materials,
+            render_target_handle,
+            render_target.composition_material(),
+        );
 
-        let cmd_buffer = render_target.cmd_buffer();
+        let hiz_buffer = match &mut self.gpu_culling {
+            Some(gpu_culling) if package_draws.iter().any(|draw| draw.occlusion.is_some()) => {
+                Some(gpu_culling.prepare_hiz(device, render_extent)?)
+            }
+            _ => None,
+        };
 
-        unsafe {
-            device.cmd_bind_vertex_buffers(cmd_buffer, 0, &[vertex_buffer], &[0]);
-            device.cmd_bind_index_buffer(cmd_buffer, index_buffer, 0, vk::IndexType::UINT32);
-        }
+        render_target.begin_frame(device)?;
 
-        let mut bound_mat = Handle::null();
-        let mut bound_inst = Handle::null();
+        let cmd_buffer = render_target.cmd_buffer();
 
         let db_device_ext = descriptor_buffer::device_ext();
 
-        let bind_info = [self.uniform_buffers.binding_info()];
-        unsafe { db_device_ext.cmd_bind_descriptor_buffers(cmd_buffer, &bind_info) };
-
-        for DrawItem {
-            mat_handle,
-            inst_handle,
-            pipeline,
-            pipeline_layout,
-            indirect_offset,
-            count,
-            batch_offset,
-            batch_range,
-        } in deferred_indirects
+        for (
+            package_index,
+            PackageDraw {
+                scene,
+                area,
+                scissor,
+                scene_ubo_offset,
+                light_ubo_offset,
+                frustum,
+                occlusion,
+                clear_color,
+                clear_depth,
+                skybox,
+                items,
+                sprites,
+            },
+        ) in package_draws.into_iter().enumerate()
         {
-            let (_, instance_data_offset) =
-                self.add_uniform(scene.instance_data_ubo(), batch_offset, batch_range);
+            // `Some(draws_with_count)` when the package was culled on the GPU
+            let culled = self
+                .gpu_culling
+                .as_ref()
+                .zip(frustum)
+                .map(|(gpu_culling, planes)| {
+                    gpu_culling.record(device, cmd_buffer, scene, planes, None);
+                    gpu_culling.draws_with_count()
+                });
+
+            let (indirect_buffer, _) = scene.indirect_draw_iterator();
+            let frame = scene.frame();
+
+            let instance_data_offsets = items
+                .iter()
+                .map(|item| {
+                    let (_, offset) = match item.instance_data_type {
+                        vk::DescriptorType::STORAGE_BUFFER => self.add_storage(
+                            scene.instance_data_ubo(),
+                            item.batch_offset,
+                            item.batch_range,
+                        ),
+                        _ => self.add_uniform(
+                            scene.instance_data_ubo(),
+                            item.batch_offset,
+                            item.batch_range,
+                        ),
+                    };
+
+                    offset
+                })
+                .collect::<Vec<_>>();
+
+            // Selected batches with their storage buffer descriptor offsets, the
+            // mask shader reads instance data of any size from a storage buffer
+            let selection_draws = items
+                .iter()
+                .zip(&instance_data_offsets)
+                .filter(|(item, _)| item.selected)
+                .filter_map(|(item, instance_data_offset)| {
+                    let transform_offset = item.transform_offset?;
+
+                    let offset = match item.instance_data_type {
+                        vk::DescriptorType::STORAGE_BUFFER => *instance_data_offset,
+                        _ => {
+                            self.add_storage(
+                                scene.instance_data_ubo(),
+                                item.batch_offset,
+                                item.batch_range,
+                            )
+                            .1
+                        }
+                    };
+
+                    let push_constants = SelectionMaskPushConstants {
+                        stride: item.instance_stride,
+                        transform_offset,
+                    };
+
+                    Some((item, offset, push_constants))
+                })
+                .collect::<Vec<_>>();
+
+            let bind_info = [
+                self.uniform_buffers.binding_info(),
+                self.textures.binding_info(),
+            ];
+
+            let bind_instance_sets =
+                |commands: &mut CommandRecording,
+                 pipeline_layout: vk::PipelineLayout,
+                 instance_sets: &[InstanceSet]| {
+                    for instance_set in instance_sets {
+                        commands.set_descriptor_buffer_offsets(
+                            pipeline_layout,
+                            instance_set.set,
+                            &[instance_set.buffer_index],
+                            &[instance_set.offset],
+                        );
+                    }
+                };
+
+            let draw = |commands: &mut CommandRecording,
+                        item: &DrawItem,
+                        pipeline: vk::Pipeline,
+                        pipeline_layout: vk::PipelineLayout,
+                        instance_data_offset: vk::DeviceSize,
+                        instance: Option<(MaterialInstanceHandle, &[InstanceSet])>,
+                        bound_pipeline: &mut vk::Pipeline,
+                        bound_meshes: &mut Option<MeshUsage>,
+                        bound_instance: &mut Option<(
+                vk::PipelineLayout,
+                MaterialInstanceHandle,
+            )>| {
+                if *bound_meshes != Some(item.mesh_usage) {
+                    if let Some((vertex_buffer, index_buffer)) = mesh_pool.buffers(item.mesh_usage)
+                    {
+                        commands.bind_mesh_buffers(vertex_buffer, index_buffer);
+                    }
+
+                    *bound_meshes = Some(item.mesh_usage);
+                }
 
-            unsafe {
-                db_device_ext.cmd_set_descriptor_buffer_offsets(
-                    cmd_buffer,
-                    vk::PipelineBindPoint::GRAPHICS,
+                commands.set_descriptor_buffer_offsets(
                     pipeline_layout,
                     0,
                     &[0, 0],
                     &[scene_ubo_offset, instance_data_offset],
                 );
-            }
 
-            let mat_rebind = bound_mat != mat_handle;
-            let inst_rebind = mat_rebind || bound_inst != inst_handle;
+                // Sets of the instance stay bound while batches of the same
+                // instance are drawn with the same layout
+                if let Some((instance, instance_sets)) = instance {
+                    if *bound_instance != Some((pipeline_layout, instance)) {
+                        bind_instance_sets(commands, pipeline_layout, instance_sets);
+                        *bound_instance = Some((pipeline_layout, instance));
+                    }
+                }
 
-            if mat_rebind {
-                unsafe {
-                    device.cmd_bind_pipeline(cmd_buffer, vk::PipelineBindPoint::GRAPHICS, pipeline);
+                if *bound_pipeline != pipeline {
+                    commands.bind_pipeline(pipeline);
+                    *bound_pipeline = pipeline;
+                }
+
+                match culled {
+                    Some(true) => commands.draw_indexed_indirect_count(
+                        frame.culled_indirect_buffer.buffer(),
+                        item.culled_offset,
+                        frame.draw_count_buffer.buffer(),
+                        item.draw_count_offset,
+                        item.max_count,
+                    ),
+                    Some(false) => commands.draw_indexed_indirect(
+                        frame.culled_indirect_buffer.buffer(),
+                        item.culled_offset,
+                        item.max_count,
+                    ),
+                    None => commands.draw_indexed_indirect(
+                        indirect_buffer.buffer(),
+                        item.indirect_offset,
+                        item.count,
+                    ),
+                }
+            };
+
+            let new_recording = || {
+                let mut commands = CommandRecording::new();
+                commands.set_viewport_and_scissor(area, scissor);
+                commands.bind_descriptor_buffers(&bind_info);
+                commands
+            };
+
+            let contents = if self.secondary_command_buffers {
+                vk::RenderingFlags::CONTENTS_SECONDARY_COMMAND_BUFFERS
+            } else {
+                vk::RenderingFlags::empty()
+            };
+
+            if items.iter().any(|item| item.prepass.is_some()) {
+                let mut commands = new_recording();
+                let mut bound_pipeline = vk::Pipeline::null();
+                let mut bound_meshes = None;
+                let mut bound_instance = None;
+
+                for (item, instance_data_offset) in items.iter().zip(&instance_data_offsets) {
+                    if let Some((pipeline, pipeline_layout)) = item.prepass {
+                        draw(
+                            &mut commands,
+                            item,
+                            pipeline,
+                            pipeline_layout,
+                            *instance_data_offset,
+                            Some((item.instance, &item.instance_sets)),
+                            &mut bound_pipeline,
+                            &mut bound_meshes,
+                            &mut bound_instance,
+                        );
+                    }
                 }
 
-                bound_mat = mat_handle;
+                render_target.begin_depth_prepass(device, area, scissor, clear_depth, contents);
+                self.execute_draws(
+                    device,
+                    render_target,
+                    (render_target_handle, package_index, DrawPass::Prepass),
+                    commands,
+                )?;
+
+                // Objects hidden behind the pre-pass depth are culled again before
+                // the deferred pass, the pre-pass itself draws all of them
+                match (&mut self.gpu_culling, frustum, occlusion, hiz_buffer) {
+                    (Some(gpu_culling), Some(planes), Some(view_projection), Some(hiz_buffer)) => {
+                        render_target.end_depth_prepass_into(device, area, hiz_buffer);
+
+                        let size = UVec2::new(area.extent.width, area.extent.height);
+                        gpu_culling.record_hiz(device, cmd_buffer, size);
+                        gpu_culling.record(
+                            device,
+                            cmd_buffer,
+                            scene,
+                            planes,
+                            Some(view_projection),
+                        );
+
+                        render_target.begin_deferred_pass(
+                            device,
+                            area,
+                            scissor,
+                            clear_color,
+                            None,
+                            contents,
+                        );
+                    }
+                    _ => render_target.start_deferred_pass_after_prepass(
+                        device,
+                        area,
+                        scissor,
+                        clear_color,
+                        contents,
+                    ),
+                }
+            } else {
+                render_target.begin_deferred_pass(
+                    device,
+                    area,
+                    scissor,
+                    clear_color,
+                    clear_depth,
+                    contents,
+                );
             }
 
-            if inst_rebind {
-                bound_inst = inst_handle;
+            let mut commands = new_recording();
+            let mut bound_pipeline = vk::Pipeline::null();
+            let mut bound_meshes = None;
+            let mut bound_instance = None;
+
+            if let Some(skybox) = &skybox {
+                commands.set_descriptor_buffer_offsets(
+                    skybox.pipeline_layout,
+                    0,
+                    &[0],
+                    &[scene_ubo_offset],
+                );
+                bind_instance_sets(&mut commands, skybox.pipeline_layout, &skybox.instance_sets);
+                commands.bind_pipeline(skybox.pipeline);
+                commands.draw(3, 1);
+
+                bound_pipeline = skybox.pipeline;
             }
 
+            for (item, instance_data_offset) in items.iter().zip(&instance_data_offsets) {
+                draw(
+                    &mut commands,
+                    item,
+                    item.pipeline,
+                    item.pipeline_layout,
+                    *instance_data_offset,
+                    Some((item.instance, &item.instance_sets)),
+                    &mut bound_pipeline,
+                    &mut bound_meshes,
+                    &mut bound_instance,
+                );
+            }
+
+            self.execute_draws(
+                device,
+                render_target,
+                (render_target_handle, package_index, DrawPass::Deferred),
+                commands,
+            )?;
+
+            render_target.start_composition_pass_in(device, area, scissor)?;
+
             unsafe {
-                device.cmd_draw_indexed_indirect(
+                device.cmd_bind_pipeline(
                     cmd_buffer,
-                    indirect_buffer.buffer(),
-                    indirect_offset,
-                    count,
-                    size_of::<vk::DrawIndexedIndirectCommand>() as u32,
+                    vk::PipelineBindPoint::GRAPHICS,
+                    composition.pipeline,
                 )
+            };
+
+            let bind_info = [
+                self.input_attachments.binding_info(),
+                self.uniform_buffers.binding_info(),
+            ];
+
+            let gbuffer = render_target.composition_attachments();
+            let attachment_offsets = composition
+                .inputs
+                .iter()
+                .map(|input| self.add_input_attachment(gbuffer[*input]).1)
+                .collect::<Vec<_>>();
+
+            let set_count = composition.set_count;
+            let offsets = [
+                attachment_offsets.first().copied().unwrap_or(0),
+                scene_ubo_offset,
+                light_ubo_offset,
+            ];
+
+            if set_count > 0 {
+                unsafe {
+                    db_device_ext.cmd_bind_descriptor_buffers(cmd_buffer, &bind_info);
+
+                    db_device_ext.cmd_set_descriptor_buffer_offsets(
+                        cmd_buffer,
+                        vk::PipelineBindPoint::GRAPHICS,
+                        composition.layout,
+                        0,
+                        &[0, 1, 1][..set_count],
+                        &offsets[..set_count],
+                    );
+                }
+            }
+
+            unsafe { device.cmd_draw(cmd_buffer, 6, 1, 0, 0) }
+
+            if !selection_draws.is_empty() {
+                render_target.begin_selection_mask_pass(device, area, scissor);
+
+                let mask_pipeline = self.selection_mask.pipeline();
+                let mut commands = new_recording();
+                let mut bound_pipeline = vk::Pipeline::null();
+                let mut bound_meshes = None;
+                let mut bound_instance = None;
+
+                for (item, instance_data_offset, push_constants) in &selection_draws {
+                    let bytes = unsafe {
+                        std::slice::from_raw_parts(
+                            (&raw const *push_constants).cast::<u8>(),
+                            size_of::<SelectionMaskPushConstants>(),
+                        )
+                    };
+
+                    commands.push_constants(
+                        mask_pipeline.layout,
+                        vk::ShaderStageFlags::VERTEX,
+                        bytes,
+                    );
+
+                    draw(
+                        &mut commands,
+                        item,
+                        mask_pipeline.pipeline,
+                        mask_pipeline.layout,
+                        *instance_data_offset,
+                        None,
+                        &mut bound_pipeline,
+                        &mut bound_meshes,
+                        &mut bound_instance,
+                    );
+                }
+
+                commands.replay(device, cmd_buffer);
+
+                render_target.start_selection_outline_pass(device, area, scissor);
+                self.draw_selection_outline(device, render_target)?;
             }
+
+            if !sprites.is_empty() {
+                render_target.start_overlay_pass(device, area, scissor);
+                self.draw_sprites(device, textures, render_target, sprites, area)?;
+            }
+
+            render_target.end_rendering(device);
+        }
+
+        if temporal_antialiasing {
+            self.resolve_temporal(device, render_target)?;
+        }
+
+        render_target.prepare_output(device);
+        render_target.submit_render(device)?;
+
+        let fence = render_target.in_flight_fence();
+        assets.mesh_pool.submitted(device, fence);
+
+        self.next_frame();
+
+        for scene_handle in synced_scenes {
+            assets.scenes.get_mut(&scene_handle).unwrap().next_frame();
         }
 
-        render_target.start_composition_pass(device)?;
+        Ok(())
+    }
+
+    /// Fills the previous view-projection of the camera and jitters its projection
+    /// when the target is antialiased temporally, `jitter_resolution` is the
+    /// resolution the jitter stays within a pixel of
+    fn with_camera_history(
+        &mut self,
+        camera: (RenderTargetHandle, usize),
+        uniform: SceneUniform,
+        jitter_resolution: Option<UVec2>,
+    ) -> SceneUniform {
+        let view_projection = uniform.projection * uniform.view;
+
+        let history = self
+            .camera_history
+            .entry(camera)
+            .or_insert_with(|| CameraHistory {
+                view_projection,
+                jitter: Vec2::zeros(),
+                frame_index: 0,
+            });
+
+        let jitter = jitter_resolution
+            .map(|resolution| taa_jitter(history.frame_index, resolution))
+            .unwrap_or_default();
+
+        let uniform = uniform.with_history(history.view_projection, jitter, history.jitter);
+
+        history.view_projection = view_projection;
+        history.jitter = jitter;
+        history.frame_index = history.frame_index.wrapping_add(1);
+
+        uniform
+    }
+
+    /// Blends the composed frame of `render_target` with its history, see
+    /// [`Antialiasing::Taa`]
+    fn resolve_temporal(
+        &mut self,
+        device: &LogicalDevice,
+        render_target: &mut SwapchainRenderTarget,
+    ) -> RenderResult<()> {
+        let sampler = get_sampler(&SamplerDesc::linear(vk::SamplerAddressMode::CLAMP_TO_EDGE))?;
+
+        render_target.begin_temporal_resolve(device)?;
+
+        let Some(inputs) = render_target.temporal_resolve_inputs() else {
+            render_target.end_temporal_resolve(device);
+            return Ok(());
+        };
+
+        let offsets = inputs.map(|image| self.add_texture(image, sampler));
+
+        let cmd_buffer = render_target.cmd_buffer();
+        let pipeline = self.taa_resolve.pipeline();
+        let db_device_ext = descriptor_buffer::device_ext();
 
         unsafe {
+            db_device_ext.cmd_bind_descriptor_buffers(cmd_buffer, &[self.textures.binding_info()]);
+
+            for (set, offset) in offsets.into_iter().enumerate() {
+                db_device_ext.cmd_set_descriptor_buffer_offsets(
+                    cmd_buffer,
+                    vk::PipelineBindPoint::GRAPHICS,
+                    pipeline.layout,
+                    set as u32,
+                    &[0],
+                    &[offset],
+                );
+            }
+
             device.cmd_bind_pipeline(
                 cmd_buffer,
                 vk::PipelineBindPoint::GRAPHICS,
-                self.basic_composition.pipeline().pipeline,
-            )
-        };
+                pipeline.pipeline,
+            );
+            device.cmd_draw(cmd_buffer, 6, 1, 0, 0);
+        }
 
-        let bind_info = [self.input_attachments.binding_info()];
+        render_target.end_temporal_resolve(device);
 
-        let device_ext = descriptor_buffer::device_ext();
+        Ok(())
+    }
 
-        let attachment_offsets = render_target
-            .composition_attachments()
-            .into_iter()
-            .map(|image| self.add_input_attachment(image).1)
-            .collect::<Vec<_>>();
+    /// Blends the outline around the selection mask of `render_target` over its output
+    fn draw_selection_outline(
+        &mut self,
+        device: &LogicalDevice,
+        render_target: &SwapchainRenderTarget,
+    ) -> RenderResult<()> {
+        let sampler = get_sampler(&SamplerDesc::nearest(vk::SamplerAddressMode::CLAMP_TO_EDGE))?;
+        let mask_offset = self.add_texture(render_target.selection_mask(), sampler);
+
+        let cmd_buffer = render_target.cmd_buffer();
+        let pipeline = self.selection_outline_material.pipeline();
+        let db_device_ext = descriptor_buffer::device_ext();
+
+        let push_constants = SelectionOutlinePushConstants {
+            color: self.selection_outline.color.into(),
+            width: self.selection_outline.width as i32,
+        };
 
         unsafe {
-            device_ext.cmd_bind_descriptor_buffers(cmd_buffer, &bind_info);
+            db_device_ext.cmd_bind_descriptor_buffers(cmd_buffer, &[self.textures.binding_info()]);
 
-            device_ext.cmd_set_descriptor_buffer_offsets(
+            db_device_ext.cmd_set_descriptor_buffer_offsets(
                 cmd_buffer,
                 vk::PipelineBindPoint::GRAPHICS,
-                self.basic_composition.pipeline().layout,
+                pipeline.layout,
                 0,
                 &[0],
-                &[attachment_offsets[0]],
+                &[mask_offset],
+            );
+
+            let bytes = std::slice::from_raw_parts(
+                (&raw const push_constants).cast::<u8>(),
+                size_of::<SelectionOutlinePushConstants>(),
+            );
+
+            device.cmd_push_constants(
+                cmd_buffer,
+                pipeline.layout,
+                vk::ShaderStageFlags::FRAGMENT,
+                0,
+                bytes,
             );
+
+            device.cmd_bind_pipeline(
+                cmd_buffer,
+                vk::PipelineBindPoint::GRAPHICS,
+                pipeline.pipeline,
+            );
+            device.cmd_draw(cmd_buffer, 6, 1, 0, 0);
         }
 
-        unsafe { device.cmd_draw(cmd_buffer, 6, 1, 0, 0) }
+        Ok(())
+    }
+
+    /// Blends the sprite batches over the output of `render_target` inside of `area`.
+    /// Sprites past [`MAX_SPRITES_PER_FRAME`] are skipped
+    fn draw_sprites(
+        &mut self,
+        device: &LogicalDevice,
+        textures: &DenseAssetStore<Texture>,
+        render_target: &SwapchainRenderTarget,
+        batches: &[SpriteBatch],
+        area: vk::Rect2D,
+    ) -> RenderResult<()> {
+        const WHITE: [u8; 4] = [255; 4];
 
-        render_target.end_rendering(device);
-        render_target.prepare_transfer(device);
-        render_target.submit_render(device)?;
+        if let Entry::Vacant(entry) = self.fallback_textures.entry(WHITE) {
+            entry.insert(Texture::solid(WHITE)?);
+        }
 
-        self.next_frame();
-        assets.scenes.get_mut(&scene_handle).unwrap().next_frame();
+        let (pipeline, layout) = {
+            let pipeline = self.sprite_material.pipeline();
+            (pipeline.pipeline, pipeline.layout)
+        };
+
+        let cmd_buffer = render_target.cmd_buffer();
+        let db_device_ext = descriptor_buffer::device_ext();
+
+        let buffer_size = self.sprite_buffers[self.current_frame].size();
+        let index = self.current_frame * UNIFORM_DESCRIPTOR_BUFFER_LEN + self.curr_uniform_index;
+        let sprites_offset = unsafe {
+            self.uniform_buffers.set_storage_buffer_unchecked(
+                &self.sprite_buffers[self.current_frame],
+                0,
+                buffer_size,
+                index,
+            )
+        };
+        self.curr_uniform_index += 1;
+
+        let push_constants = SpritePushConstants {
+            viewport_size: [area.extent.width as f32, area.extent.height as f32],
+        };
+
+        unsafe {
+            db_device_ext.cmd_bind_descriptor_buffers(
+                cmd_buffer,
+                &[
+                    self.uniform_buffers.binding_info(),
+                    self.textures.binding_info(),
+                ],
+            );
+
+            db_device_ext.cmd_set_descriptor_buffer_offsets(
+                cmd_buffer,
+                vk::PipelineBindPoint::GRAPHICS,
+                layout,
+                0,
+                &[UNIFORM_BUFFER_INDEX],
+                &[sprites_offset],
+            );
+
+            let bytes = std::slice::from_raw_parts(
+                (&raw const push_constants).cast::<u8>(),
+                size_of::<SpritePushConstants>(),
+            );
+
+            device.cmd_push_constants(cmd_buffer, layout, vk::ShaderStageFlags::VERTEX, 0, bytes);
+            device.cmd_bind_pipeline(cmd_buffer, vk::PipelineBindPoint::GRAPHICS, pipeline);
+        }
+
+        // Consecutive batches are drawn at once while their textures fit into the
+        // texture array of the sprite material
+        let mut batches = batches;
+
+        while !batches.is_empty() && self.curr_sprite_index < MAX_SPRITES_PER_FRAME {
+            let mut run_textures = Vec::<Option<TextureHandle>>::new();
+
+            let run_len = batches
+                .iter()
+                .take_while(|batch| {
+                    if run_textures.contains(&batch.texture) {
+                        return true;
+                    }
+
+                    if run_textures.len() == MAX_SPRITE_TEXTURES {
+                        return false;
+                    }
+
+                    run_textures.push(batch.texture);
+                    true
+                })
+                .count();
+
+            let (run, rest) = batches.split_at(run_len);
+            batches = rest;
+
+            let elements = self.textures.array_elements(MAX_SPRITE_TEXTURES);
+
+            if self.curr_texture_index + elements > TEXTURE_DESCRIPTOR_BUFFER_LEN {
+                core_warn!("Skipping sprites: out of texture descriptors");
+                break;
+            }
+
+            // Unused elements of the array sample white
+            let images = (0..MAX_SPRITE_TEXTURES)
+                .map(|index| {
+                    let texture = run_textures
+                        .get(index)
+                        .copied()
+                        .flatten()
+                        .and_then(|handle| textures.get(&handle))
+                        .unwrap_or(&self.fallback_textures[&WHITE]);
+
+                    Ok((texture.image(), get_sampler(texture.sampler())?))
+                })
+                .collect::<RenderResult<Vec<_>>>()?;
+
+            let index =
+                self.current_frame * TEXTURE_DESCRIPTOR_BUFFER_LEN + self.curr_texture_index;
+            let texture_offset =
+                unsafe { self.textures.set_texture_array_unchecked(&images, index) };
+            self.curr_texture_index += elements;
+
+            let first_sprite = self.curr_sprite_index;
+
+            for batch in run {
+                let count = batch
+                    .sprites
+                    .len()
+                    .min(MAX_SPRITES_PER_FRAME - self.curr_sprite_index);
+
+                if count < batch.sprites.len() {
+                    core_warn!(
+                        "Skipping {} sprites: more than {MAX_SPRITES_PER_FRAME} in a frame",
+                        batch.sprites.len() - count
+                    );
+                }
+
+                if count == 0 {
+                    break;
+                }
+
+                let texture_index = run_textures
+                    .iter()
+                    .position(|texture| *texture == batch.texture)
+                    .unwrap() as u32;
+
+                let buffer = &mut self.sprite_buffers[self.current_frame];
+                let mut mapped = buffer
+                    .map_as_slice::<Sprite>(self.curr_sprite_index * size_of::<Sprite>(), count)?;
+
+                for (mapped, sprite) in mapped.iter_mut().zip(&batch.sprites) {
+                    *mapped = sprite.with_texture_index(texture_index);
+                }
+
+                drop(mapped);
+
+                self.curr_sprite_index += count;
+            }
+
+            let count = self.curr_sprite_index - first_sprite;
+
+            if count == 0 {
+                break;
+            }
+
+            self.sprite_buffers[self.current_frame].flush_range(
+                (first_sprite * size_of::<Sprite>()) as vk::DeviceSize,
+                (count * size_of::<Sprite>()) as vk::DeviceSize,
+            )?;
+
+            unsafe {
+                db_device_ext.cmd_set_descriptor_buffer_offsets(
+                    cmd_buffer,
+                    vk::PipelineBindPoint::GRAPHICS,
+                    layout,
+                    1,
+                    &[TEXTURE_BUFFER_INDEX],
+                    &[texture_offset],
+                );
+
+                device.cmd_draw(cmd_buffer, 6, count as u32, 0, first_sprite as u32);
+            }
+        }
 
         Ok(())
     }
 
+    /// Writes the lights of `scene` into the light uniform buffer of the current
+    /// frame and returns its descriptor offset
+    fn add_light_uniform(
+        &mut self,
+        camera_index: usize,
+        scene: &Scene,
+    ) -> RenderResult<vk::DeviceSize> {
+        let buffer = &mut self.light_uniforms[self.current_frame];
+        let buffer_offset = camera_index * LIGHT_UNIFORM_STRIDE;
+
+        let mut mapped = buffer.map_memory::<LightUniform>(buffer_offset)?;
+
+        *mapped = LightUniform::new(scene.ambient_light(), scene.lights());
+
+        drop(mapped);
+
+        buffer.flush_range(
+            buffer_offset as vk::DeviceSize,
+            size_of::<LightUniform>() as vk::DeviceSize,
+        )?;
+
+        let index = self.current_frame * UNIFORM_DESCRIPTOR_BUFFER_LEN + self.curr_uniform_index;
+
+        let offset = unsafe {
+            self.uniform_buffers.set_uniform_buffer_unchecked(
+                &self.light_uniforms[self.current_frame],
+                buffer_offset as vk::DeviceSize,
+                size_of::<LightUniform>() as vk::DeviceSize,
+                index,
+            )
+        };
+
+        self.curr_uniform_index += 1;
+
+        Ok(offset)
+    }
+
+    /// Writes `uniform` into the camera uniform buffer of the current frame and
+    /// returns its descriptor offset
+    fn add_camera_uniform(
+        &mut self,
+        camera_index: usize,
+        uniform: SceneUniform,
+    ) -> RenderResult<vk::DeviceSize> {
+        let buffer = &mut self.camera_uniforms[self.current_frame];
+        let buffer_offset = camera_index * CAMERA_UNIFORM_STRIDE;
+
+        let mut mapped = buffer.map_memory::<SceneUniform>(buffer_offset)?;
+
+        *mapped = uniform;
+
+        drop(mapped);
+
+        buffer.flush_range(
+            buffer_offset as vk::DeviceSize,
+            size_of::<SceneUniform>() as vk::DeviceSize,
+        )?;
+
+        let index = self.current_frame * UNIFORM_DESCRIPTOR_BUFFER_LEN + self.curr_uniform_index;
+
+        let offset = unsafe {
+            self.uniform_buffers.set_uniform_buffer_unchecked(
+                &self.camera_uniforms[self.current_frame],
+                buffer_offset as vk::DeviceSize,
+                size_of::<SceneUniform>() as vk::DeviceSize,
+                index,
+            )
+        };
+
+        self.curr_uniform_index += 1;
+
+        Ok(offset)
+    }
+
     #[allow(unused)]
     #[inline]
     fn add_uniform(
@@ -362,22 +1771,294 @@ impl VulkanRenderer {
         (index, offset)
     }
 
-    #[allow(unused)]
+    /// Storage buffer descriptors share the uniform descriptor buffer
     #[inline]
-    fn add_texture(
+    fn add_storage(
         &mut self,
-        texture: &VulkanImage,
-        sampler: vk::Sampler,
+        buffer: &GpuBuffer,
+        buffer_offset: vk::DeviceSize,
+        buffer_range: vk::DeviceSize,
     ) -> (usize, vk::DeviceSize) {
-        let index = self.current_frame * TEXTURE_DESCRIPTOR_BUFFER_LEN + self.curr_texture_index;
+        let index = self.current_frame * UNIFORM_DESCRIPTOR_BUFFER_LEN + self.curr_uniform_index;
 
-        let offset = unsafe { self.textures.set_texture_unchecked(texture, sampler, index) };
+        let offset = unsafe {
+            self.uniform_buffers.set_storage_buffer_unchecked(
+                buffer,
+                buffer_offset,
+                buffer_range,
+                index,
+            )
+        };
 
-        self.curr_texture_index += 1;
+        self.curr_uniform_index += 1;
 
         (index, offset)
     }
 
+    /// Writes descriptors of the textures and buffers bound to `instance`. Returns
+    /// `None` when the batch can't be drawn: a buffer or a render texture is unset,
+    /// the render texture is the output of `render_target` itself or wasn't
+    /// rendered yet
+    fn add_instance_sets(
+        &mut self,
+        assets: &RenderAssets,
+        render_target: RenderTargetHandle,
+        material: &Material,
+        instance: &MaterialInstance,
+    ) -> Option<Vec<InstanceSet>> {
+        let buffers = instance
+            .buffers()
+            .into_iter()
+            .map(|(set, binding)| {
+                let (buffer, storage) = match binding {
+                    InstanceBinding::UniformBuffer(Some(buffer)) => (buffer, false),
+                    InstanceBinding::StorageBuffer(Some(buffer)) => (buffer, true),
+                    _ => {
+                        core_warn!(
+                            "Skipping a batch of {:?}: no buffer bound to set {set}",
+                            instance.material_handle()
+                        );
+                        return None;
+                    }
+                };
+
+                let (_, offset) = if storage {
+                    self.add_storage(buffer, 0, buffer.size())
+                } else {
+                    self.add_uniform(buffer, 0, buffer.size())
+                };
+
+                Some(InstanceSet {
+                    set,
+                    buffer_index: UNIFORM_BUFFER_INDEX,
+                    offset,
+                })
+            })
+            .collect::<Option<Vec<_>>>()?;
+
+        let textures = instance.textures();
+
+        let is_uploaded = |texture: &Option<TextureHandle>| {
+            texture.is_some_and(|handle| assets.textures.get(&handle).is_some())
+        };
+
+        for (set, binding) in textures.iter() {
+            let uploaded = match binding {
+                InstanceBinding::Texture(texture) => is_uploaded(texture),
+                InstanceBinding::TextureArray(textures) => textures.iter().all(is_uploaded),
+                _ => true,
+            };
+
+            if uploaded {
+                continue;
+            }
+
+            let rgba = material.fallback_texture(*set);
+
+            if let Entry::Vacant(entry) = self.fallback_textures.entry(rgba) {
+                let texture = Texture::solid(rgba)
+                    .inspect_err(|err| core_warn!("Failed to create a fallback texture: {err}"))
+                    .ok()?;
+
+                entry.insert(texture);
+            }
+        }
+
+        let textures = textures
+            .into_iter()
+            .map(|(set, binding)| {
+                let texture_or_fallback = |texture: &Option<TextureHandle>| {
+                    let texture = texture
+                        .and_then(|handle| assets.textures.get(&handle))
+                        .unwrap_or_else(|| {
+                            &self.fallback_textures[&material.fallback_texture(set)]
+                        });
+
+                    (texture.image(), texture.sampler())
+                };
+
+                let images = match binding {
+                    InstanceBinding::Texture(texture) => vec![texture_or_fallback(texture)],
+                    InstanceBinding::TextureArray(textures) => {
+                        textures.iter().map(texture_or_fallback).collect()
+                    }
+                    InstanceBinding::RenderTexture(texture) => vec![Self::render_texture_image(
+                        assets,
+                        render_target,
+                        instance,
+                        set,
+                        *texture,
+                    )?],
+                    _ => return None,
+                };
+
+                let images = images
+                    .into_iter()
+                    .map(|(image, sampler)| {
+                        let sampler = get_sampler(sampler)
+                            .inspect_err(|err| {
+                                core_warn!("Failed to create a texture sampler: {err}")
+                            })
+                            .ok()?;
+
+                        Some((image, sampler))
+                    })
+                    .collect::<Option<Vec<_>>>()?;
+
+                let index =
+                    self.current_frame * TEXTURE_DESCRIPTOR_BUFFER_LEN + self.curr_texture_index;
+
+                let offset = match (binding, images.as_slice()) {
+                    (InstanceBinding::TextureArray(_), images) => {
+                        let elements = self.textures.array_elements(images.len());
+
+                        if self.curr_texture_index + elements > TEXTURE_DESCRIPTOR_BUFFER_LEN {
+                            core_warn!(
+                                "Skipping a batch of {:?}: out of texture descriptors",
+                                instance.material_handle()
+                            );
+                            return None;
+                        }
+
+                        self.curr_texture_index += elements;
+                        unsafe { self.textures.set_texture_array_unchecked(images, index) }
+                    }
+                    (_, [(image, sampler)]) => {
+                        self.curr_texture_index += 1;
+                        unsafe { self.textures.set_texture_unchecked(image, *sampler, index) }
+                    }
+                    _ => return None,
+                };
+
+                Some(InstanceSet {
+                    set,
+                    buffer_index: TEXTURE_BUFFER_INDEX,
+                    offset,
+                })
+            })
+            .collect::<Option<Vec<_>>>()?;
+
+        Some([buffers, textures].concat())
+    }
+
+    #[inline]
+    fn add_texture(&mut self, image: &VulkanImage, sampler: vk::Sampler) -> vk::DeviceSize {
+        let index = self.current_frame * TEXTURE_DESCRIPTOR_BUFFER_LEN + self.curr_texture_index;
+        let offset = unsafe { self.textures.set_texture_unchecked(image, sampler, index) };
+        self.curr_texture_index += 1;
+
+        offset
+    }
+
+    fn render_texture_image<'a>(
+        assets: &'a RenderAssets,
+        render_target: RenderTargetHandle,
+        instance: &MaterialInstance,
+        set: u32,
+        texture: Option<RenderTextureHandle>,
+    ) -> Option<(&'a VulkanImage, &'a SamplerDesc)> {
+        let Some(texture) = texture.and_then(|handle| assets.render_textures.get(&handle)) else {
+            core_warn!(
+                "Skipping a batch of {:?}: no render texture bound to set {set}",
+                instance.material_handle()
+            );
+            return None;
+        };
+
+        if texture.render_target() == render_target {
+            core_warn!(
+                "Skipping a batch of {:?}: render target {render_target:?} can't sample its own output",
+                instance.material_handle()
+            );
+            return None;
+        }
+
+        let image = assets
+            .render_targets
+            .get(&texture.render_target())?
+            .sampled_image();
+
+        if image.image_layout != vk::ImageLayout::SHADER_READ_ONLY_OPTIMAL {
+            core_trace!(
+                "Skipping a batch of {:?}: render target {:?} has no sampled output yet",
+                instance.material_handle(),
+                texture.render_target()
+            );
+            return None;
+        }
+
+        Some((image, texture.sampler()))
+    }
+
+    /// Composition pipeline of a render target with `material`. Falls back to the
+    /// built-in one if the material doesn't exist or reads input attachments the
+    /// composition pass doesn't have
+    fn composition_pass(
+        &mut self,
+        materials: &DenseAssetStore<Material>,
+        render_target: RenderTargetHandle,
+        material: Option<MaterialHandle>,
+    ) -> CompositionPass {
+        // Falls back silently once the rejection of a material was warned about
+        let first_rejection = |rejected: &mut HashMap<_, _>, handle: MaterialHandle| {
+            rejected.insert(render_target, handle) != Some(handle)
+        };
+
+        let rejected = &mut self.rejected_compositions;
+
+        let custom = material.and_then(|handle| {
+            let Some(material) = materials.get(&handle) else {
+                if first_rejection(rejected, handle) {
+                    core_warn!(
+                        "Composition material {handle:?} doesn't exist, using the built-in one"
+                    );
+                }
+                return None;
+            };
+
+            let pipeline = material.pipeline();
+
+            let unsupported = pipeline.input_attachments.iter().find(|input| {
+                input.set != 0
+                    || input.descriptor_count != 1
+                    || input
+                        .input_attachment_index
+                        .is_none_or(|index| index as usize >= COMPOSITION_INPUT_COUNT)
+            });
+
+            if let Some(input) = unsupported {
+                if first_rejection(rejected, handle) {
+                    core_warn!(
+                        "Composition material {handle:?} reads an unsupported input attachment at set {}, binding {}, using the built-in one",
+                        input.set,
+                        input.binding
+                    );
+                }
+                return None;
+            }
+
+            Some(pipeline)
+        });
+
+        if custom.is_some() || material.is_none() {
+            rejected.remove(&render_target);
+        }
+
+        let pipeline = custom.unwrap_or(self.basic_composition.pipeline());
+
+        CompositionPass {
+            pipeline: pipeline.pipeline,
+            layout: pipeline.layout,
+            set_count: pipeline.set_layouts.len().min(COMPOSITION_SET_COUNT),
+            inputs: pipeline
+                .input_attachments
+                .iter()
+                .filter_map(|input| input.input_attachment_index)
+                .map(|index| index as usize)
+                .collect(),
+        }
+    }
+
     #[allow(unused)]
     #[inline]
     fn add_input_attachment(&mut self, texture: &VulkanImage) -> (usize, vk::DeviceSize) {
@@ -407,6 +2088,17 @@ impl VulkanRenderer {
         present_target: PresentTargetHandle,
         render_target: RenderTargetHandle,
     ) -> PresentResult<()> {
+        assets.record_presentation(render_target, present_target);
+
+        let visible = assets
+            .present_targets
+            .get(&present_target)
+            .is_some_and(|target| target.is_visible() && target.size().min() > 0);
+
+        if !visible {
+            return Err(PresentError::PresentSkipped);
+        }
+
         let device = get_device();
 
         unsafe { device.device_wait_idle()? }
@@ -421,7 +2113,11 @@ impl VulkanRenderer {
             image_ready,
             image_index: index,
             image_ready_fence,
-        } = present_target.record_present(device, render_target.output_image())?;
+        } = present_target.record_present(
+            device,
+            render_target.output_image(),
+            render_target.render_extent(),
+        )?;
 
         let swapchains = [swapchain];
         let indices = [index];
@@ -467,8 +2163,22 @@ impl VulkanRenderer {
 
 impl Drop for VulkanRenderer {
     fn drop(&mut self) {
+        let device = get_device();
+
         unsafe {
-            get_device().device_wait_idle();
+            device.device_wait_idle();
+        }
+
+        self.camera_uniforms
+            .iter_mut()
+            .chain(self.light_uniforms.iter_mut())
+            .chain(self.sprite_buffers.iter_mut())
+            .for_each(|buffer| buffer.destroy(device));
+
+        if let Some(gpu_culling) = &mut self.gpu_culling {
+            gpu_culling.destroy(device);
         }
+
+        self.destroy_secondary_draws(device);
     }
 }
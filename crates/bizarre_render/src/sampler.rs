@@ -0,0 +1,157 @@
+use std::collections::HashMap;
+
+use ash::vk;
+use bizarre_config::{get_config_section, ConfigSection};
+use serde::Deserialize;
+
+use crate::vulkan_context::get_device;
+
+/// `[sampler]` section of the engine config
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default)]
+pub struct SamplerConfig {
+    /// Upper bound of the anisotropy of every sampler, `1` disables anisotropic filtering
+    pub max_anisotropy: f32,
+}
+
+impl Default for SamplerConfig {
+    fn default() -> Self {
+        Self {
+            max_anisotropy: 16.0,
+        }
+    }
+}
+
+impl ConfigSection for SamplerConfig {
+    fn section_name() -> &'static str {
+        "sampler"
+    }
+}
+
+/// Filtering and addressing of a sampler
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct SamplerDesc {
+    pub mag_filter: vk::Filter,
+    pub min_filter: vk::Filter,
+    pub mipmap_mode: vk::SamplerMipmapMode,
+    pub address_mode_u: vk::SamplerAddressMode,
+    pub address_mode_v: vk::SamplerAddressMode,
+    pub address_mode_w: vk::SamplerAddressMode,
+    /// Requested anisotropy, capped by the config and the device limit.
+    /// `1` disables anisotropic filtering
+    pub anisotropy: u32,
+}
+
+impl Default for SamplerDesc {
+    fn default() -> Self {
+        Self::linear(vk::SamplerAddressMode::REPEAT)
+    }
+}
+
+impl SamplerDesc {
+    /// Trilinear filtering with the maximum allowed anisotropy
+    pub fn linear(address_mode: vk::SamplerAddressMode) -> Self {
+        Self {
+            mag_filter: vk::Filter::LINEAR,
+            min_filter: vk::Filter::LINEAR,
+            mipmap_mode: vk::SamplerMipmapMode::LINEAR,
+            address_mode_u: address_mode,
+            address_mode_v: address_mode,
+            address_mode_w: address_mode,
+            anisotropy: 16,
+        }
+    }
+
+    pub fn nearest(address_mode: vk::SamplerAddressMode) -> Self {
+        Self {
+            mag_filter: vk::Filter::NEAREST,
+            min_filter: vk::Filter::NEAREST,
+            mipmap_mode: vk::SamplerMipmapMode::NEAREST,
+            address_mode_u: address_mode,
+            address_mode_v: address_mode,
+            address_mode_w: address_mode,
+            anisotropy: 1,
+        }
+    }
+
+    pub fn with_anisotropy(self, anisotropy: u32) -> Self {
+        Self {
+            anisotropy: anisotropy.max(1),
+            ..self
+        }
+    }
+}
+
+/// Samplers shared by every texture with the same [`SamplerDesc`].
+///
+/// Samplers live as long as the device, they are cheap and there are only a few
+/// distinct ones
+pub struct SamplerCache {
+    samplers: HashMap<SamplerDesc, vk::Sampler>,
+    /// Smallest of the config and device limits, `1` if the device doesn't
+    /// support anisotropic filtering
+    max_anisotropy: f32,
+}
+
+impl SamplerCache {
+    /// `device_max_anisotropy` is `None` if the feature isn't enabled on the device
+    pub(crate) fn new(device_max_anisotropy: Option<f32>) -> Self {
+        let config = get_config_section::<SamplerConfig>().unwrap_or_default();
+
+        let max_anisotropy = device_max_anisotropy
+            .map(|limit| config.max_anisotropy.clamp(1.0, limit))
+            .unwrap_or(1.0);
+
+        Self {
+            samplers: Default::default(),
+            max_anisotropy,
+        }
+    }
+
+    pub fn max_anisotropy(&self) -> f32 {
+        self.max_anisotropy
+    }
+
+    /// Returns a sampler matching `desc`, creating it if there's no matching one yet
+    pub(crate) fn acquire(
+        &mut self,
+        device: &ash::Device,
+        desc: &SamplerDesc,
+    ) -> Result<vk::Sampler, vk::Result> {
+        if let Some(sampler) = self.samplers.get(desc) {
+            return Ok(*sampler);
+        }
+
+        let anisotropy = (desc.anisotropy as f32).min(self.max_anisotropy);
+
+        let create_info = vk::SamplerCreateInfo::default()
+            .mag_filter(desc.mag_filter)
+            .min_filter(desc.min_filter)
+            .mipmap_mode(desc.mipmap_mode)
+            .address_mode_u(desc.address_mode_u)
+            .address_mode_v(desc.address_mode_v)
+            .address_mode_w(desc.address_mode_w)
+            .anisotropy_enable(anisotropy > 1.0)
+            .max_anisotropy(anisotropy)
+            .min_lod(0.0)
+            .max_lod(vk::LOD_CLAMP_NONE);
+
+        let sampler = unsafe { device.create_sampler(&create_info, None)? };
+        self.samplers.insert(*desc, sampler);
+
+        Ok(sampler)
+    }
+
+    pub(crate) fn destroy(&mut self, device: &ash::Device) {
+        self.samplers
+            .drain()
+            .for_each(|(_, sampler)| unsafe { device.destroy_sampler(sampler, None) });
+    }
+}
+
+/// Shared sampler for `desc` on the current device
+pub fn get_sampler(desc: &SamplerDesc) -> Result<vk::Sampler, vk::Result> {
+    let device = get_device();
+
+    device.samplers.lock().unwrap().acquire(device, desc)
+}
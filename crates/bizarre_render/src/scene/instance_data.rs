@@ -0,0 +1,53 @@
+use std::any::{type_name, TypeId};
+
+pub use bizarre_render_proc_macro::InstanceData;
+
+/// Per-object data a scene uploads to the GPU for every instance.
+///
+/// Use `#[derive(InstanceData)]` to implement it, the derive checks that the
+/// type is `#[repr(C, align(16))]` at compile time.
+///
+/// # Safety
+///
+/// The type must be `#[repr(C)]` with an alignment that is a multiple of 16 and
+/// `FIELDS` must describe its actual fields
+pub unsafe trait GpuInstanceData: Clone + 'static {
+    /// `(name, offset, size)` of every field of the type
+    const FIELDS: &'static [(&'static str, usize, usize)];
+
+    fn instance_layout() -> InstanceLayout {
+        InstanceLayout {
+            type_id: TypeId::of::<Self>(),
+            type_name: type_name::<Self>(),
+            size: size_of::<Self>(),
+            align: align_of::<Self>(),
+            fields: Self::FIELDS,
+        }
+    }
+}
+
+/// Describes memory layout of a [`GpuInstanceData`] type
+#[derive(Clone, Copy, Debug)]
+pub struct InstanceLayout {
+    pub type_id: TypeId,
+    pub type_name: &'static str,
+    pub size: usize,
+    pub align: usize,
+    pub fields: &'static [(&'static str, usize, usize)],
+}
+
+impl PartialEq for InstanceLayout {
+    fn eq(&self, other: &Self) -> bool {
+        self.type_id == other.type_id
+    }
+}
+
+impl InstanceLayout {
+    /// Offset and size of the field called `name`
+    pub fn field(&self, name: &str) -> Option<(usize, usize)> {
+        self.fields
+            .iter()
+            .find(|(field, ..)| *field == name)
+            .map(|(_, offset, size)| (*offset, *size))
+    }
+}
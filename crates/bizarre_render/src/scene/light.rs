@@ -1,13 +1,20 @@
-use nalgebra_glm::Vec3;
+use nalgebra_glm::{Vec3, Vec4};
+
+/// Max amount of lights of a scene the composition pass shades with, the rest
+/// is ignored
+pub const MAX_LIGHTS: usize = 32;
 
 #[repr(C)]
+#[derive(Clone, Copy, Debug)]
 pub struct PointLight {
     pub position: Vec3,
     pub color: Vec3,
-    pub intesity: f32,
+    /// Radiant intensity, falls off with the square of the distance
+    pub intensity: f32,
 }
 
 #[repr(C)]
+#[derive(Clone, Copy, Debug)]
 pub struct DirectionalLight {
     pub position: Vec3,
     pub direction: Vec3,
@@ -16,7 +23,63 @@ pub struct DirectionalLight {
     pub fov: f32,
 }
 
+#[derive(Clone, Copy, Debug)]
 pub enum Light {
     Point(PointLight),
     Directional(DirectionalLight),
 }
+
+/// Light as the composition shader reads it
+#[repr(C, align(16))]
+#[derive(Clone, Copy, Debug, Default)]
+pub(crate) struct GpuLight {
+    /// `w` is `1.0` for point lights and `0.0` for directional ones
+    position: Vec4,
+    /// Direction the light travels in, unused by point lights
+    direction: Vec4,
+    /// Color premultiplied by the intensity
+    radiance: Vec4,
+}
+
+impl From<&Light> for GpuLight {
+    fn from(value: &Light) -> Self {
+        match value {
+            Light::Point(light) => Self {
+                position: light.position.push(1.0),
+                direction: Vec4::zeros(),
+                radiance: (light.color * light.intensity).push(0.0),
+            },
+            Light::Directional(light) => Self {
+                position: light.position.push(0.0),
+                direction: light.direction.normalize().push(0.0),
+                radiance: (light.color * light.intensity).push(0.0),
+            },
+        }
+    }
+}
+
+/// Lights of a scene at `set = 2, binding = 0` of the composition pass
+#[repr(C, align(16))]
+#[derive(Clone, Copy, Debug)]
+pub(crate) struct LightUniform {
+    /// Ambient light, `w` is the amount of lights
+    ambient: Vec4,
+    lights: [GpuLight; MAX_LIGHTS],
+}
+
+impl LightUniform {
+    pub(crate) fn new(ambient: Vec3, lights: &[Light]) -> Self {
+        let mut uniform = Self {
+            ambient: ambient.push(0.0),
+            lights: [GpuLight::default(); MAX_LIGHTS],
+        };
+
+        for (gpu_light, light) in uniform.lights.iter_mut().zip(lights) {
+            *gpu_light = light.into();
+        }
+
+        uniform.ambient.w = lights.len().min(MAX_LIGHTS) as f32;
+
+        uniform
+    }
+}
@@ -1,5 +1,4 @@
 use std::{
-    any::type_name,
     collections::{BTreeMap, BTreeSet, VecDeque},
     time::Duration,
 };
@@ -9,7 +8,9 @@ use bitflags::bitflags;
 use bizarre_core::{handle::HandleStrategy, Handle};
 use bizarre_ecs::prelude::Component;
 use bizarre_log::{core_info, core_trace};
-use nalgebra_glm::Mat4;
+use instance_data::{GpuInstanceData, InstanceLayout};
+use light::Light;
+use nalgebra_glm::{translation, Mat4, UVec2, Vec2, Vec3, Vec4};
 use render_batch::RenderBatch;
 use render_object::{RenderObject, RenderObjectMaterials};
 use scene_frame::SceneFrameData;
@@ -19,11 +20,14 @@ use bizarre_ecs::prelude::*;
 
 use crate::{
     buffer::{BufferError, GpuBuffer},
-    mesh::{Mesh, MeshHandle},
+    device::LogicalDevice,
+    mesh::{Mesh, MeshHandle, MeshUsage},
+    mesh_pool::MeshPool,
     render_assets::{AssetStore, DenseAssetStore},
     vertex::Vertex,
 };
 
+pub mod instance_data;
 pub mod light;
 pub mod object_pass;
 pub mod render_batch;
@@ -38,11 +42,21 @@ pub enum SceneError {
     BufferError(#[from] BufferError),
     #[error(transparent)]
     VkError(#[from] vk::Result),
+    #[error("There is no render object with id {0}")]
+    NoSuchObject(usize),
+    #[error(
+        "Render object {object} was created with `{expected}` instance data, but got `{found}`"
+    )]
+    InstanceDataMismatch {
+        object: usize,
+        expected: &'static str,
+        found: &'static str,
+    },
 }
 
 pub type SceneResult<T> = Result<T, SceneError>;
 
-#[derive(Clone, Copy, Debug, Component)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Component)]
 pub struct RenderObjectId(usize);
 
 impl RenderObjectId {
@@ -51,22 +65,115 @@ impl RenderObjectId {
     }
 }
 
-const INITIAL_VERTEX_LEN: usize = 10_000;
-const INITIAL_INDEX_LEN: usize = 50_000;
 const INITIAL_INSTANCE_LEN: usize = 2000;
 const INITIAL_INDIRECT_LEN: usize = 1024;
 
-#[repr(C, align(4))]
+/// Per-view data available to every material at `set = 0, binding = 0`.
+///
+/// Create it with [`SceneUniform::new`], time and resolution are filled in by
+/// the renderer right before the uniform is uploaded
+#[repr(C, align(16))]
 #[derive(Clone, Debug)]
 pub struct SceneUniform {
     pub view: Mat4,
     pub projection: Mat4,
+    /// World space position of the camera, `w` is always `1.0`
+    pub camera_position: Vec4,
+    /// `(width, height, 1 / width, 1 / height)` of the rendered area in pixels
+    pub resolution: Vec4,
+    /// Seconds since the renderer was created
+    pub time: f32,
+    /// Seconds since the previous render into the same target
+    pub delta_time: f32,
+    pub near: f32,
+    pub far: f32,
+    /// Unjittered view-projection of the same camera in the previous frame, for
+    /// motion vectors
+    pub previous_view_projection: Mat4,
+    /// `xy` is the offset in NDC `projection` is jittered by, `zw` is the offset
+    /// of the previous frame
+    pub jitter: Vec4,
+}
+
+impl SceneUniform {
+    /// Camera position and clip planes are taken from `view` and `projection`.
+    ///
+    /// Planes are extracted for perspective and orthographic projections with
+    /// the `[-1, 1]` depth range, as built by `nalgebra_glm::perspective` and `ortho`
+    pub fn new(view: Mat4, projection: Mat4) -> Self {
+        let camera_position = view
+            .try_inverse()
+            .map(|inverse| inverse.column(3).into_owned())
+            .unwrap_or(Vec4::new(0.0, 0.0, 0.0, 1.0));
+
+        let (near, far) = clip_planes(&projection);
+
+        Self {
+            view,
+            projection,
+            camera_position,
+            resolution: Vec4::zeros(),
+            time: 0.0,
+            delta_time: 0.0,
+            near,
+            far,
+            previous_view_projection: projection * view,
+            jitter: Vec4::zeros(),
+        }
+    }
+
+    pub(crate) fn with_frame_info(mut self, resolution: UVec2, time: f32, delta_time: f32) -> Self {
+        let (width, height) = (resolution.x as f32, resolution.y as f32);
+
+        self.resolution = Vec4::new(width, height, 1.0 / width, 1.0 / height);
+        self.time = time;
+        self.delta_time = delta_time;
+        self
+    }
+
+    /// Jitters the projection by `jitter` in NDC, `previous_view_projection` and
+    /// `previous_jitter` are what the camera rendered with in the previous frame
+    pub(crate) fn with_history(
+        mut self,
+        previous_view_projection: Mat4,
+        jitter: Vec2,
+        previous_jitter: Vec2,
+    ) -> Self {
+        self.projection = translation(&Vec3::new(jitter.x, jitter.y, 0.0)) * self.projection;
+        self.previous_view_projection = previous_view_projection;
+        self.jitter = Vec4::new(jitter.x, jitter.y, previous_jitter.x, previous_jitter.y);
+        self
+    }
+}
+
+fn clip_planes(projection: &Mat4) -> (f32, f32) {
+    let a = projection[(2, 2)];
+    let b = projection[(2, 3)];
+
+    if projection[(3, 3)] == 0.0 {
+        (b / (a - 1.0), b / (a + 1.0))
+    } else {
+        ((b + 1.0) / a, (b - 1.0) / a)
+    }
 }
 
 #[repr(C, align(16))]
-#[derive(Debug, Clone, Default)]
+#[derive(Debug, Clone, Default, instance_data::InstanceData)]
 pub struct InstanceData {
     pub transform: Mat4,
+    /// Transform the object was rendered with in the previous frame, written by
+    /// the scene before every render for motion vectors. Any instance data type
+    /// with a `previous_transform` field gets it filled in the same way
+    pub previous_transform: Mat4,
+}
+
+impl InstanceData {
+    pub fn new(transform: Mat4) -> Self {
+        Self {
+            transform,
+            previous_transform: transform,
+        }
+    }
 }
 
 #[derive(Debug)]
@@ -76,8 +183,15 @@ pub struct Scene {
 
     next_id: usize,
     id_recycling: VecDeque<usize>,
+    /// Instance data layout of every object, indexed by `RenderObjectId`
+    object_layouts: Vec<Option<InstanceLayout>>,
+
+    lights: Vec<Light>,
+    ambient_light: Vec3,
 
     frames: Vec<SceneFrameData>,
+    /// Transform every object was last rendered with, indexed by `RenderObjectId`
+    rendered_transforms: Vec<Option<Mat4>>,
 }
 
 macro_rules! trace_sleep {
@@ -97,11 +211,47 @@ impl Scene {
             max_frames_in_flight,
             next_id: 0,
             id_recycling: Default::default(),
+            object_layouts: Vec::new(),
+            lights: Vec::new(),
+            ambient_light: Vec3::repeat(0.03),
             current_frame: 0,
             frames,
+            rendered_transforms: Vec::new(),
         })
     }
 
+    /// Destroys GPU buffers of every frame, see [`SceneFrameData::release_gpu_resources`]
+    pub(crate) fn release_gpu_resources(&mut self, device: &LogicalDevice) {
+        self.frames
+            .iter_mut()
+            .for_each(|frame| frame.release_gpu_resources(device));
+    }
+
+    /// Recreates GPU buffers of every frame on the current device
+    pub(crate) fn restore_gpu_resources(&mut self) -> SceneResult<()> {
+        self.frames
+            .iter_mut()
+            .try_for_each(|frame| frame.restore_gpu_resources())
+    }
+
+    /// Lights shading the scene in the composition pass, only the first
+    /// [`MAX_LIGHTS`](light::MAX_LIGHTS) are used. Without lights the scene is drawn unlit
+    pub fn lights(&self) -> &[Light] {
+        &self.lights
+    }
+
+    pub fn lights_mut(&mut self) -> &mut Vec<Light> {
+        &mut self.lights
+    }
+
+    pub fn ambient_light(&self) -> Vec3 {
+        self.ambient_light
+    }
+
+    pub fn set_ambient_light(&mut self, ambient_light: Vec3) {
+        self.ambient_light = ambient_light;
+    }
+
     pub fn scene_ubo(&self) -> &GpuBuffer {
         &self.frames[self.current_frame].scene_uniform_buffer
     }
@@ -110,20 +260,18 @@ impl Scene {
         &self.frames[self.current_frame].instance_data_ubo
     }
 
-    pub fn vertex_buffer(&self) -> vk::Buffer {
-        self.frames[self.current_frame].vertex_buffer.buffer()
+    pub(crate) fn frame(&self) -> &SceneFrameData {
+        &self.frames[self.current_frame]
     }
 
-    pub fn index_buffer(&self) -> vk::Buffer {
-        self.frames[self.current_frame].index_buffer.buffer()
-    }
-
-    pub fn sync_frame_data<S, A>(&mut self, mesh_store: &A)
+    /// Applies pending changes to the current frame, meshes of new batches are
+    /// uploaded into `mesh_pool` if they aren't there yet
+    pub fn sync_frame_data<S, A>(&mut self, mesh_pool: &mut MeshPool, mesh_store: &A)
     where
         S: HandleStrategy<Mesh>,
         A: AssetStore<Mesh, S>,
     {
-        self.frames[self.current_frame].sync_frame_data(mesh_store)
+        self.frames[self.current_frame].sync_frame_data(mesh_pool, mesh_store)
     }
 
     pub fn next_frame(&mut self) {
@@ -135,22 +283,49 @@ impl Scene {
             .iter_mut()
             .for_each(|frame| frame.remove_object(object_id));
 
+        if let Some(layout) = self.object_layouts.get_mut(object_id.0) {
+            *layout = None;
+        }
+
+        if let Some(transform) = self.rendered_transforms.get_mut(object_id.0) {
+            *transform = None;
+        }
+
         self.id_recycling.push_back(object_id.0)
     }
 
-    #[track_caller]
-    pub fn update_object<T: Clone>(&mut self, object_id: RenderObjectId, instance_data: T) {
-        assert_ubo_alignemnt::<T>();
+    /// Replaces instance data of an object, `T` must be the type the object
+    /// was created with
+    pub fn update_object<T: GpuInstanceData>(
+        &mut self,
+        object_id: RenderObjectId,
+        instance_data: T,
+    ) -> SceneResult<()> {
+        let layout = self
+            .object_layouts
+            .get(object_id.0)
+            .copied()
+            .flatten()
+            .ok_or(SceneError::NoSuchObject(object_id.0))?;
+
+        let new_layout = T::instance_layout();
+
+        if layout != new_layout {
+            return Err(SceneError::InstanceDataMismatch {
+                object: object_id.0,
+                expected: layout.type_name,
+                found: new_layout.type_name,
+            });
+        }
 
         self.frames
             .iter_mut()
             .for_each(|frame| frame.update_object(object_id, instance_data.clone()));
-    }
 
-    #[track_caller]
-    pub fn add_object<T: Clone>(&mut self, object: RenderObject<T>) -> RenderObjectId {
-        assert_ubo_alignemnt::<T>();
+        Ok(())
+    }
 
+    pub fn add_object<T: GpuInstanceData>(&mut self, object: RenderObject<T>) -> RenderObjectId {
         let id = if let Some(id) = self.id_recycling.pop_front() {
             id
         } else {
@@ -161,6 +336,11 @@ impl Scene {
 
         let id = RenderObjectId(id);
 
+        if id.0 >= self.object_layouts.len() {
+            self.object_layouts.resize(id.0 + 1, None);
+        }
+        self.object_layouts[id.0] = Some(T::instance_layout());
+
         self.frames
             .iter_mut()
             .for_each(|frame| frame.add_object(id, object.clone()));
@@ -168,17 +348,77 @@ impl Scene {
         id
     }
 
+    /// Replaces materials of an existing object for the passes set in `overrides`
+    /// without recreating the object
+    pub fn override_object_materials(
+        &mut self,
+        object_id: RenderObjectId,
+        overrides: RenderObjectMaterials,
+    ) {
+        self.frames
+            .iter_mut()
+            .for_each(|frame| frame.override_materials(object_id, overrides.clone()));
+    }
+
+    /// Selected objects are drawn into the selection mask of the render target and
+    /// outlined, see [`VulkanRenderer::set_selection_outline`](crate::renderer::VulkanRenderer::set_selection_outline)
+    pub fn set_object_selected(&mut self, object_id: RenderObjectId, selected: bool) {
+        self.frames
+            .iter_mut()
+            .for_each(|frame| frame.set_selected(object_id, selected));
+    }
+
+    /// Scene uniform last synced into the current frame
+    pub fn scene_uniform(&self) -> Option<&SceneUniform> {
+        self.frames[self.current_frame].scene_uniform.as_ref()
+    }
+
+    /// View of the packages rendering the scene without a camera.
+    ///
+    /// Queued into every frame and applied the next time the frame is synced, so
+    /// a camera moved with it may be drawn with stale matrices. Moving cameras
+    /// should be passed with [`RenderPackage::with_camera`](crate::submitter::RenderPackage::with_camera)
     pub fn update_scene_uniform(&mut self, uniform: SceneUniform) {
         self.frames
             .iter_mut()
             .for_each(|frame| frame.update_scene_uniform(uniform.clone()));
     }
 
+    /// Refreshes the location of `mesh` after it was replaced in the mesh store
+    /// and released from the [`MeshPool`].
+    ///
+    /// Every frame picks up the new location the next time it is synced
+    pub fn mesh_changed(&mut self, mesh: MeshHandle) {
+        self.frames
+            .iter_mut()
+            .for_each(|frame| frame.mesh_changed(mesh));
+    }
+
+    /// Marks the start of a simulation step. Objects updated after it are drawn
+    /// between their previous and new transforms with the interpolation factor of
+    /// the [`RenderPackage`](crate::submitter::RenderPackage), objects that are not
+    /// updated in the step stay still
+    pub fn begin_tick(&mut self) {
+        self.frames.iter_mut().for_each(SceneFrameData::begin_tick);
+    }
+
+    /// See [`SceneFrameData::interpolate_transforms`]
+    pub(crate) fn interpolate_transforms(&mut self, factor: f32) {
+        self.frames[self.current_frame].interpolate_transforms(factor)
+    }
+
+    /// See [`SceneFrameData::write_previous_transforms`]. A scene rendered into
+    /// several targets in one frame has no object motion in all but the first one
+    pub(crate) fn write_previous_transforms(&mut self) {
+        self.frames[self.current_frame].write_previous_transforms(&mut self.rendered_transforms)
+    }
+
     pub fn indirect_draw_iterator(&self) -> (&GpuBuffer, SceneIndirectDrawIterator) {
         let iter = SceneIndirectDrawIterator {
             scene: self,
             batch_offset: 0,
             indirect_offset: 0,
+            culled_offset: 0,
             helper_offset: 0,
             frame_index: self.current_frame,
         };
@@ -191,6 +431,7 @@ impl Scene {
 pub struct SceneIndirectDrawIterator<'a> {
     scene: &'a Scene,
     indirect_offset: vk::DeviceSize,
+    culled_offset: vk::DeviceSize,
     helper_offset: usize,
     frame_index: usize,
     batch_offset: usize,
@@ -202,24 +443,39 @@ pub struct IndirectIterItem<'a> {
     pub batch_offset: vk::DeviceSize,
     pub batch_range: vk::DeviceSize,
     pub count: u32,
+    /// Offset of the batch's commands in the culled indirect buffer
+    pub culled_offset: vk::DeviceSize,
+    /// Offset of the batch's draw count in the draw count buffer
+    pub draw_count_offset: vk::DeviceSize,
+    /// Instance slots of the batch, max amount of culled commands
+    pub max_count: u32,
+    /// Mesh pool buffers the batch is drawn from, `None` if its mesh isn't uploaded
+    pub mesh_usage: Option<MeshUsage>,
+    /// Objects of the batch are outlined
+    pub selected: bool,
+    /// Size of the instance data of one object
+    pub instance_stride: usize,
+    pub instance_layout: InstanceLayout,
 }
 
 impl<'a> Iterator for SceneIndirectDrawIterator<'a> {
     type Item = IndirectIterItem<'a>;
 
     fn next(&mut self) -> Option<Self::Item> {
-        let batch = self.scene.frames[self.frame_index]
-            .batches
-            .get(self.batch_offset)?;
-
-        let helper = self.scene.frames[self.frame_index]
-            .indirect_helpers
-            .get(self.helper_offset)?;
+        let frame = &self.scene.frames[self.frame_index];
+        let batch = frame.batches.get(self.batch_offset)?;
+        let helper = frame.indirect_helpers.get(self.helper_offset)?;
 
         let offset = self.indirect_offset;
         self.indirect_offset +=
             (*helper as usize * size_of::<vk::DrawIndexedIndirectCommand>()) as vk::DeviceSize;
 
+        let culled_offset = self.culled_offset;
+        self.culled_offset +=
+            (batch.count * size_of::<vk::DrawIndexedIndirectCommand>()) as vk::DeviceSize;
+
+        let draw_count_offset = (self.batch_offset * size_of::<u32>()) as vk::DeviceSize;
+
         self.batch_offset += 1;
         self.helper_offset += 1;
 
@@ -229,23 +485,13 @@ impl<'a> Iterator for SceneIndirectDrawIterator<'a> {
             batch_offset: batch.offset as u64,
             batch_range: (batch.count * batch.instance_data_stride) as u64,
             count: *helper,
+            culled_offset,
+            draw_count_offset,
+            max_count: batch.count as u32,
+            mesh_usage: frame.mesh_map.get(&batch.mesh).map(|mapping| mapping.usage),
+            selected: batch.selected,
+            instance_stride: batch.instance_data_stride,
+            instance_layout: batch.instance_layout,
         })
     }
 }
-
-#[derive(Debug, Clone)]
-pub struct MeshMapping {
-    index_offset: u32,
-    index_count: u32,
-    vertex_offset: u32,
-}
-
-#[track_caller]
-fn assert_ubo_alignemnt<T>() {
-    debug_assert!(
-        align_of::<T>() == 16,
-        "Trying to use `{}` as a instance data for rendering, but it has align of {} (align of 16 is required)",
-        type_name::<T>(),
-        align_of::<T>()
-    );
-}
@@ -13,6 +13,16 @@ pub enum SceneObjectPass {
     Deferred = 0,
     Forward,
     Lighting,
+    Shadow,
+}
+
+impl SceneObjectPass {
+    pub const ALL: [SceneObjectPass; variant_count::<SceneObjectPass>()] = [
+        SceneObjectPass::Deferred,
+        SceneObjectPass::Forward,
+        SceneObjectPass::Lighting,
+        SceneObjectPass::Shadow,
+    ];
 }
 
 impl From<RenderObjectFlags> for Vec<SceneObjectPass> {
@@ -23,6 +33,7 @@ impl From<RenderObjectFlags> for Vec<SceneObjectPass> {
                 RenderObjectFlags::DEFERRED_PASS => Some(SceneObjectPass::Deferred),
                 RenderObjectFlags::FORWARD_PASS => Some(SceneObjectPass::Forward),
                 RenderObjectFlags::LIGHTING_PASS => Some(SceneObjectPass::Lighting),
+                RenderObjectFlags::SHADOW_PASS => Some(SceneObjectPass::Shadow),
                 _ => None,
             })
             .collect()
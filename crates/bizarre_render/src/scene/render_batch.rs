@@ -3,17 +3,44 @@ use std::{alloc::Layout, any::TypeId, collections::VecDeque, ops::Range};
 
 use bizarre_core::erased_buffer::ErasedSparseArray;
 
+use nalgebra_glm::Mat4;
+
 use crate::mesh::MeshHandle;
 
-use super::render_object::{RenderObjectMaterials, RenderObjectMeta};
+use super::{
+    instance_data::InstanceLayout,
+    render_object::{RenderObjectFlags, RenderObjectMaterials, RenderObjectMeta},
+};
+
+/// Identifies the batch an object is drawn in, objects sharing the mesh, the
+/// material instances of every pass and the selection are drawn together
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct BatchKey {
+    pub mesh: MeshHandle,
+    pub materials: RenderObjectMaterials,
+    pub selected: bool,
+}
+
+impl From<&RenderObjectMeta> for BatchKey {
+    fn from(meta: &RenderObjectMeta) -> Self {
+        Self {
+            mesh: meta.mesh,
+            materials: meta.materials.clone(),
+            selected: meta.flags.contains(RenderObjectFlags::SELECTED),
+        }
+    }
+}
 
 #[derive(Debug)]
 pub struct RenderBatch {
     pub mesh: MeshHandle,
     pub materials: RenderObjectMaterials,
+    /// Objects of the batch are outlined
+    pub selected: bool,
     pub offset: usize,
     pub count: usize,
     pub instance_data_stride: usize,
+    pub instance_layout: InstanceLayout,
     pub instance_data: ErasedSparseArray,
     pub holes: VecDeque<usize>,
 }
@@ -22,18 +49,23 @@ impl RenderBatch {
     pub fn new(
         offset: usize,
         render_object_meta: &RenderObjectMeta,
-        instance_data_layout: Layout,
+        instance_layout: InstanceLayout,
     ) -> Self {
-        let instance_data = unsafe { ErasedSparseArray::from_layout(instance_data_layout) };
+        let layout = Layout::from_size_align(instance_layout.size, instance_layout.align).unwrap();
+        let instance_data = unsafe { ErasedSparseArray::from_layout(layout) };
         let instance_data_stride = instance_data.stride();
 
         Self {
             mesh: render_object_meta.mesh,
             materials: render_object_meta.materials.clone(),
+            selected: render_object_meta
+                .flags
+                .contains(RenderObjectFlags::SELECTED),
             count: 0,
             holes: Default::default(),
             instance_data,
             instance_data_stride,
+            instance_layout,
             offset,
         }
     }
@@ -55,6 +87,54 @@ impl RenderBatch {
         self.instance_data.insert_bytes(at, data);
     }
 
+    /// Meta every object of the batch shares
+    pub fn meta(&self) -> RenderObjectMeta {
+        let mut flags = RenderObjectFlags::empty();
+        flags.set(RenderObjectFlags::SELECTED, self.selected);
+
+        RenderObjectMeta {
+            flags,
+            materials: self.materials.clone(),
+            mesh: self.mesh,
+        }
+    }
+
+    /// Raw instance data of the object at `at`
+    pub fn instance_bytes(&self, at: usize) -> Option<&[u8]> {
+        if !self.instance_data.contains(at) {
+            return None;
+        }
+
+        unsafe {
+            let ptr = self
+                .instance_data
+                .as_ptr::<u8>()
+                .add(at * self.instance_data_stride);
+
+            Some(std::slice::from_raw_parts(ptr, self.instance_data_stride))
+        }
+    }
+
+    /// `transform` field of the object at `at`, if its instance data has one
+    pub fn instance_transform(&self, at: usize) -> Option<Mat4> {
+        let (offset, size) = self.instance_layout.field("transform")?;
+
+        if size != size_of::<Mat4>() {
+            return None;
+        }
+
+        let bytes = self.instance_bytes(at)?;
+
+        unsafe {
+            Some(
+                bytes[offset..offset + size]
+                    .as_ptr()
+                    .cast::<Mat4>()
+                    .read_unaligned(),
+            )
+        }
+    }
+
     pub fn empty(&self) {
         self.holes.len() == self.count;
     }
@@ -18,6 +18,9 @@ bitflags! {
         const DEFERRED_PASS = 0b0000_0001;
         const FORWARD_PASS = 0b0000_0010;
         const LIGHTING_PASS = 0b000_0100;
+        const SHADOW_PASS = 0b0000_1000;
+        /// Drawn into the selection mask and outlined, see [`Scene::set_object_selected`](super::Scene::set_object_selected)
+        const SELECTED = 0b0001_0000;
     }
 }
 
@@ -55,16 +58,65 @@ impl<T: Clone> RenderObject<T> {
     }
 }
 
-#[derive(Debug, Clone, Default)]
+#[derive(Debug, Clone, Default, PartialEq, Eq, Hash)]
 pub struct RenderObjectMaterials {
     pub inner: [Option<MaterialInstanceHandle>; variant_count::<SceneObjectPass>()],
 }
 
 impl RenderObjectMaterials {
     pub fn new(deferred_material: MaterialInstanceHandle) -> Self {
-        Self {
-            inner: [Some(deferred_material), None, None],
-        }
+        Self::builder().deferred(deferred_material).build()
+    }
+
+    pub fn builder() -> RenderObjectMaterialsBuilder {
+        RenderObjectMaterialsBuilder::default()
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = (SceneObjectPass, MaterialInstanceHandle)> + '_ {
+        SceneObjectPass::ALL
+            .into_iter()
+            .filter_map(|pass| Some((pass, self[pass]?)))
+    }
+
+    /// Replaces materials of the passes that are set in `overrides`, passes that are
+    /// `None` in `overrides` are left untouched
+    pub fn apply_overrides(&mut self, overrides: &RenderObjectMaterials) {
+        overrides
+            .iter()
+            .for_each(|(pass, material)| self[pass] = Some(material));
+    }
+}
+
+#[derive(Debug, Clone, Default)]
+#[must_use]
+pub struct RenderObjectMaterialsBuilder {
+    materials: RenderObjectMaterials,
+}
+
+impl RenderObjectMaterialsBuilder {
+    pub fn pass(mut self, pass: SceneObjectPass, material: MaterialInstanceHandle) -> Self {
+        self.materials[pass] = Some(material);
+        self
+    }
+
+    pub fn deferred(self, material: MaterialInstanceHandle) -> Self {
+        self.pass(SceneObjectPass::Deferred, material)
+    }
+
+    pub fn forward(self, material: MaterialInstanceHandle) -> Self {
+        self.pass(SceneObjectPass::Forward, material)
+    }
+
+    pub fn lighting(self, material: MaterialInstanceHandle) -> Self {
+        self.pass(SceneObjectPass::Lighting, material)
+    }
+
+    pub fn shadow(self, material: MaterialInstanceHandle) -> Self {
+        self.pass(SceneObjectPass::Shadow, material)
+    }
+
+    pub fn build(self) -> RenderObjectMaterials {
+        self.materials
     }
 }
 
@@ -81,14 +133,3 @@ impl IndexMut<SceneObjectPass> for RenderObjectMaterials {
         &mut self.inner[index as usize]
     }
 }
-
-impl PartialEq for RenderObjectMaterials {
-    fn eq(&self, other: &Self) -> bool {
-        self.inner
-            .iter()
-            .zip(other.inner.iter())
-            .all(|(a, b)| a == b)
-    }
-}
-
-impl Eq for RenderObjectMaterials {}
@@ -1,23 +1,34 @@
-use std::{alloc::Layout, collections::BTreeMap, ops::Deref};
+use std::{
+    collections::{hash_map::Entry, BTreeMap, HashMap},
+    ops::Deref,
+};
 
 use bitflags::bitflags;
 
 use ash::vk;
 use bizarre_core::handle::HandleStrategy;
-use bizarre_log::core_trace;
+use bizarre_log::{core_error, core_trace, core_warn};
+use nalgebra_glm::{
+    mat3_to_quat, quat_slerp, quat_to_mat4, scaling, translation as translation_matrix, Mat3, Mat4,
+    Quat, Vec3, Vec4,
+};
 
 use crate::{
-    buffer::GpuBuffer,
+    buffer::{GpuBuffer, TypedGpuBuffer},
+    culling::CullObject,
+    device::LogicalDevice,
     mesh::{Mesh, MeshHandle},
+    mesh_pool::{MeshMapping, MeshPool},
     render_assets::AssetStore,
-    vertex::Vertex,
+    vulkan_context::get_device,
 };
 
 use super::{
-    render_batch::RenderBatch,
-    render_object::{RenderObject, RenderObjectMeta},
-    InstanceData, MeshMapping, RenderObjectId, SceneResult, SceneUniform, INITIAL_INDEX_LEN,
-    INITIAL_INDIRECT_LEN, INITIAL_INSTANCE_LEN, INITIAL_VERTEX_LEN,
+    instance_data::{GpuInstanceData, InstanceLayout},
+    render_batch::{BatchKey, RenderBatch},
+    render_object::{RenderObject, RenderObjectFlags, RenderObjectMaterials, RenderObjectMeta},
+    InstanceData, RenderObjectId, SceneResult, SceneUniform, INITIAL_INDIRECT_LEN,
+    INITIAL_INSTANCE_LEN,
 };
 
 bitflags! {
@@ -27,87 +38,168 @@ bitflags! {
         const NEED_INSTANCE_DATA_SYNC           = 0b0000_0010;
         const NEED_MESH_REBUILD                 = 0b0000_0100;
         const NEED_INDIRECT_REBUILD             = 0b0000_1000;
+        const NEED_CULL_OBJECTS_REBUILD         = 0b0001_0000;
     }
 }
 
 #[derive(Clone, Debug)]
 pub enum SceneChange {
-    AddObject(RenderObjectId, RenderObjectMeta, Layout, Vec<u8>),
+    AddObject(RenderObjectId, RenderObjectMeta, InstanceLayout, Vec<u8>),
     UpdateObject(RenderObjectId, Vec<u8>),
     RemoveObject(RenderObjectId),
+    OverrideMaterials(RenderObjectId, RenderObjectMaterials),
+    SetSelected(RenderObjectId, bool),
     UpdateSceneUniform(SceneUniform),
+    MeshChanged(MeshHandle),
+    BeginTick,
 }
 
 #[derive(Debug)]
 pub struct SceneFrameData {
     pub(crate) flags: SceneFrameFlags,
     pub(crate) batches: Vec<RenderBatch>,
-    pub(crate) vertex_buffer: GpuBuffer,
-    pub(crate) index_buffer: GpuBuffer,
-    pub(crate) scene_uniform_buffer: GpuBuffer,
+    /// Index into `batches` of every batch key
+    pub(crate) batch_lookup: HashMap<BatchKey, usize>,
+    pub(crate) scene_uniform_buffer: TypedGpuBuffer<SceneUniform>,
+    /// Location of the mesh of every batch in the [`MeshPool`]
     pub(crate) mesh_map: BTreeMap<MeshHandle, MeshMapping>,
     /// Maps RenderObjectId (throug this vec index) to a (batch_id, index_into_batch) pair
     pub(crate) instance_mapping: Vec<Option<(usize, usize)>>,
     pub(crate) pending_changes: Vec<SceneChange>,
     pub(crate) instance_data_ubo: GpuBuffer,
-    pub(crate) indirect_buffer: GpuBuffer,
+    pub(crate) indirect_buffer: TypedGpuBuffer<vk::DrawIndexedIndirectCommand>,
     pub(crate) indirect_helpers: Vec<u32>,
+    /// Bounds and draw parameters of every object for GPU culling
+    pub(crate) cull_object_buffer: TypedGpuBuffer<CullObject>,
+    pub(crate) cull_object_count: u32,
+    /// One command per instance slot of every batch, written by the culling shader
+    pub(crate) culled_indirect_buffer: TypedGpuBuffer<vk::DrawIndexedIndirectCommand>,
+    /// Visible command count of every batch, written by the culling shader
+    pub(crate) draw_count_buffer: TypedGpuBuffer<u32>,
+    /// Last uploaded scene uniform, written again when the buffers are restored
+    pub(crate) scene_uniform: Option<SceneUniform>,
+    /// Transforms objects had before their first update in the current tick,
+    /// keyed by `(batch_id, index_into_batch)`. Tracked after the first tick begins
+    pub(crate) previous_transforms: HashMap<(usize, usize), Mat4>,
+    pub(crate) tracks_previous: bool,
 }
 
 impl SceneFrameData {
     pub fn new() -> SceneResult<Self> {
-        let vertex_buffer = GpuBuffer::new(
-            (size_of::<Vertex>() * INITIAL_VERTEX_LEN) as vk::DeviceSize,
-            vk::BufferUsageFlags::VERTEX_BUFFER | vk::BufferUsageFlags::TRANSFER_DST,
-            vma::MemoryUsage::Auto,
-            vma::AllocationCreateFlags::HOST_ACCESS_SEQUENTIAL_WRITE,
-        )?;
-        let index_buffer = GpuBuffer::new(
-            (size_of::<u32>() * INITIAL_INDEX_LEN) as vk::DeviceSize,
-            vk::BufferUsageFlags::INDEX_BUFFER | vk::BufferUsageFlags::TRANSFER_DST,
-            vma::MemoryUsage::Auto,
-            vma::AllocationCreateFlags::HOST_ACCESS_SEQUENTIAL_WRITE,
-        )?;
-
         let instance_data_ubo = GpuBuffer::new(
             (size_of::<InstanceData>() * INITIAL_INSTANCE_LEN) as vk::DeviceSize,
-            vk::BufferUsageFlags::UNIFORM_BUFFER | vk::BufferUsageFlags::SHADER_DEVICE_ADDRESS,
+            vk::BufferUsageFlags::UNIFORM_BUFFER
+                | vk::BufferUsageFlags::STORAGE_BUFFER
+                | vk::BufferUsageFlags::SHADER_DEVICE_ADDRESS,
             vma::MemoryUsage::Auto,
             vma::AllocationCreateFlags::HOST_ACCESS_RANDOM,
         )?;
 
-        let scene_uniform_buffer = GpuBuffer::new(
-            size_of::<SceneUniform>() as vk::DeviceSize,
+        let scene_uniform_buffer = TypedGpuBuffer::new(
+            1,
             vk::BufferUsageFlags::UNIFORM_BUFFER | vk::BufferUsageFlags::SHADER_DEVICE_ADDRESS,
             vma::MemoryUsage::Auto,
             vma::AllocationCreateFlags::HOST_ACCESS_RANDOM,
         )?;
 
-        let indirect_buffer = GpuBuffer::new(
-            (size_of::<vk::DrawIndexedIndirectCommand>() * INITIAL_INDIRECT_LEN) as vk::DeviceSize,
+        let indirect_buffer = TypedGpuBuffer::new(
+            INITIAL_INDIRECT_LEN,
             vk::BufferUsageFlags::INDIRECT_BUFFER,
             vma::MemoryUsage::Auto,
             vma::AllocationCreateFlags::HOST_ACCESS_SEQUENTIAL_WRITE,
         )?;
 
+        let cull_object_buffer = TypedGpuBuffer::new(
+            INITIAL_INSTANCE_LEN,
+            vk::BufferUsageFlags::STORAGE_BUFFER | vk::BufferUsageFlags::SHADER_DEVICE_ADDRESS,
+            vma::MemoryUsage::Auto,
+            vma::AllocationCreateFlags::HOST_ACCESS_SEQUENTIAL_WRITE,
+        )?;
+
+        let culled_indirect_buffer = TypedGpuBuffer::new(
+            INITIAL_INSTANCE_LEN,
+            vk::BufferUsageFlags::INDIRECT_BUFFER
+                | vk::BufferUsageFlags::STORAGE_BUFFER
+                | vk::BufferUsageFlags::SHADER_DEVICE_ADDRESS
+                | vk::BufferUsageFlags::TRANSFER_DST,
+            vma::MemoryUsage::AutoPreferDevice,
+            vma::AllocationCreateFlags::empty(),
+        )?;
+
+        let draw_count_buffer = TypedGpuBuffer::new(
+            INITIAL_INDIRECT_LEN,
+            vk::BufferUsageFlags::INDIRECT_BUFFER
+                | vk::BufferUsageFlags::STORAGE_BUFFER
+                | vk::BufferUsageFlags::SHADER_DEVICE_ADDRESS
+                | vk::BufferUsageFlags::TRANSFER_DST,
+            vma::MemoryUsage::AutoPreferDevice,
+            vma::AllocationCreateFlags::empty(),
+        )?;
+
         let frame = Self {
             scene_uniform_buffer,
             batches: Vec::default(),
+            batch_lookup: Default::default(),
             flags: SceneFrameFlags::empty(),
-            vertex_buffer,
-            index_buffer,
             indirect_buffer,
             indirect_helpers: Default::default(),
+            cull_object_buffer,
+            cull_object_count: 0,
+            culled_indirect_buffer,
+            draw_count_buffer,
             instance_data_ubo,
             instance_mapping: Default::default(),
             mesh_map: Default::default(),
             pending_changes: Default::default(),
+            scene_uniform: None,
+            previous_transforms: Default::default(),
+            tracks_previous: false,
         };
 
         Ok(frame)
     }
 
-    pub fn sync_frame_data<S, A>(&mut self, mesh_store: &A)
+    /// Destroys the GPU buffers of the frame. CPU-side data stays, so the
+    /// buffers can be rebuilt with [`restore_gpu_resources`](Self::restore_gpu_resources)
+    pub(crate) fn release_gpu_resources(&mut self, device: &LogicalDevice) {
+        self.scene_uniform_buffer.destroy(device);
+        self.instance_data_ubo.destroy(device);
+        self.indirect_buffer.destroy(device);
+        self.cull_object_buffer.destroy(device);
+        self.culled_indirect_buffer.destroy(device);
+        self.draw_count_buffer.destroy(device);
+    }
+
+    /// Recreates the GPU buffers on the current device and reuploads everything
+    /// on the next sync
+    pub(crate) fn restore_gpu_resources(&mut self) -> SceneResult<()> {
+        let Self {
+            scene_uniform_buffer,
+            instance_data_ubo,
+            indirect_buffer,
+            cull_object_buffer,
+            culled_indirect_buffer,
+            draw_count_buffer,
+            ..
+        } = Self::new()?;
+
+        self.scene_uniform_buffer = scene_uniform_buffer;
+        self.instance_data_ubo = instance_data_ubo;
+        self.indirect_buffer = indirect_buffer;
+        self.cull_object_buffer = cull_object_buffer;
+        self.culled_indirect_buffer = culled_indirect_buffer;
+        self.draw_count_buffer = draw_count_buffer;
+
+        self.flags.insert(SceneFrameFlags::all());
+
+        if let Some(uniform) = self.scene_uniform.take() {
+            self.handle_update_scene_uniform(uniform);
+        }
+
+        Ok(())
+    }
+
+    pub fn sync_frame_data<S, A>(&mut self, mesh_pool: &mut MeshPool, mesh_store: &A)
     where
         S: HandleStrategy<Mesh>,
         A: AssetStore<Mesh, S>,
@@ -120,21 +212,29 @@ impl SceneFrameData {
                 SceneChange::AddObject(
                     render_object_id,
                     render_object_meta,
-                    instance_data_layout,
+                    instance_layout,
                     instance_data,
                 ) => self.handle_add(
                     render_object_id,
                     render_object_meta,
-                    instance_data_layout,
+                    instance_layout,
                     instance_data,
                 ),
                 SceneChange::UpdateObject(render_object_id, instance_data) => {
                     self.handle_update(render_object_id, instance_data)
                 }
                 SceneChange::RemoveObject(render_object_id) => self.handle_remove(render_object_id),
+                SceneChange::OverrideMaterials(render_object_id, overrides) => {
+                    self.handle_override_materials(render_object_id, overrides)
+                }
+                SceneChange::SetSelected(render_object_id, selected) => {
+                    self.handle_set_selected(render_object_id, selected)
+                }
                 SceneChange::UpdateSceneUniform(uniform) => {
                     self.handle_update_scene_uniform(uniform)
                 }
+                SceneChange::MeshChanged(mesh) => self.handle_mesh_changed(mesh),
+                SceneChange::BeginTick => self.handle_begin_tick(),
             });
 
         let flags = self.flags;
@@ -143,12 +243,17 @@ impl SceneFrameData {
             SceneFrameFlags::NEED_INDIRECT_REBUILD => self.rebuild_indirects(),
             SceneFrameFlags::NEED_INSTANCE_DATA_SYNC => self.sync_instance_data(),
             SceneFrameFlags::NEED_INSTANCE_DATA_REBUILD => self.rebuild_instance_data(),
-            SceneFrameFlags::NEED_MESH_REBUILD => self.rebuild_mesh_data(mesh_store),
+            SceneFrameFlags::NEED_MESH_REBUILD => self.rebuild_mesh_data(mesh_pool, mesh_store),
+            SceneFrameFlags::NEED_CULL_OBJECTS_REBUILD => self.rebuild_cull_objects(),
             _ => (),
         })
     }
 
-    pub fn add_object<T: Clone>(&mut self, object_id: RenderObjectId, object: RenderObject<T>) {
+    pub fn add_object<T: GpuInstanceData>(
+        &mut self,
+        object_id: RenderObjectId,
+        object: RenderObject<T>,
+    ) {
         let instance_data = unsafe {
             let ptr = (&raw const object.instance_data).cast::<u8>();
             std::slice::from_raw_parts(ptr, size_of::<T>()).to_vec()
@@ -157,7 +262,7 @@ impl SceneFrameData {
         self.pending_changes.push(SceneChange::AddObject(
             object_id,
             object.meta,
-            Layout::new::<T>(),
+            T::instance_layout(),
             instance_data,
         ));
     }
@@ -177,17 +282,165 @@ impl SceneFrameData {
             .push(SceneChange::RemoveObject(object_id))
     }
 
+    pub fn override_materials(
+        &mut self,
+        object_id: RenderObjectId,
+        overrides: RenderObjectMaterials,
+    ) {
+        self.pending_changes
+            .push(SceneChange::OverrideMaterials(object_id, overrides))
+    }
+
+    pub fn set_selected(&mut self, object_id: RenderObjectId, selected: bool) {
+        self.pending_changes
+            .push(SceneChange::SetSelected(object_id, selected))
+    }
+
     pub fn update_scene_uniform(&mut self, uniform: SceneUniform) {
         self.pending_changes
             .push(SceneChange::UpdateSceneUniform(uniform));
     }
 
+    pub fn mesh_changed(&mut self, mesh: MeshHandle) {
+        self.pending_changes.push(SceneChange::MeshChanged(mesh));
+    }
+
+    pub fn begin_tick(&mut self) {
+        self.pending_changes.push(SceneChange::BeginTick);
+    }
+
+    /// Writes transforms between the previous and the current ones into the
+    /// instance buffer, `factor` of `0.0` is the previous tick and `1.0` the current one.
+    ///
+    /// Has to be called after [`sync_frame_data`](Self::sync_frame_data), only objects
+    /// updated in the current tick are written
+    pub fn interpolate_transforms(&mut self, factor: f32) {
+        if self.previous_transforms.is_empty() {
+            return;
+        }
+
+        let factor = factor.clamp(0.0, 1.0);
+
+        let writes = self
+            .previous_transforms
+            .iter()
+            .filter_map(|((batch_id, index), previous)| {
+                let batch = self.batches.get(*batch_id)?;
+                let (field_offset, _) = batch.instance_layout.field("transform")?;
+                let current = batch.instance_transform(*index)?;
+
+                let offset = batch.offset + index * batch.instance_data_stride + field_offset;
+
+                Some((offset, interpolate_transform(previous, &current, factor)))
+            })
+            .collect::<Vec<_>>();
+
+        let Some(len) = writes
+            .iter()
+            .map(|(offset, _)| offset + size_of::<Mat4>())
+            .max()
+        else {
+            return;
+        };
+
+        let mut mapped_slice = self.instance_data_ubo.map_as_slice::<u8>(0, len).unwrap();
+
+        for (offset, transform) in writes {
+            unsafe {
+                mapped_slice
+                    .as_mut_ptr()
+                    .add(offset)
+                    .cast::<Mat4>()
+                    .write_unaligned(transform);
+            }
+        }
+
+        drop(mapped_slice);
+
+        self.instance_data_ubo
+            .flush_range(0, len as vk::DeviceSize)
+            .unwrap();
+    }
+
+    /// Writes the transform every object was rendered with last time into its
+    /// `previous_transform` field and stores the current one in `rendered`,
+    /// indexed by `RenderObjectId`. Objects rendered for the first time don't move.
+    ///
+    /// Has to be called after [`interpolate_transforms`](Self::interpolate_transforms),
+    /// instance data types without `transform` and `previous_transform` fields are skipped
+    pub fn write_previous_transforms(&mut self, rendered: &mut Vec<Option<Mat4>>) {
+        let writes = self
+            .instance_mapping
+            .iter()
+            .enumerate()
+            .filter_map(|(object_id, mapping)| {
+                let (batch_id, index) = (*mapping)?;
+                let batch = self.batches.get(batch_id)?;
+
+                let (current_offset, current_size) = batch.instance_layout.field("transform")?;
+                let (previous_offset, previous_size) =
+                    batch.instance_layout.field("previous_transform")?;
+
+                if current_size != size_of::<Mat4>() || previous_size != size_of::<Mat4>() {
+                    return None;
+                }
+
+                let offset = batch.offset + index * batch.instance_data_stride;
+
+                Some((object_id, offset + current_offset, offset + previous_offset))
+            })
+            .collect::<Vec<_>>();
+
+        let Some(len) = writes
+            .iter()
+            .map(|(_, current, previous)| current.max(previous) + size_of::<Mat4>())
+            .max()
+        else {
+            return;
+        };
+
+        if rendered.len() < self.instance_mapping.len() {
+            rendered.resize(self.instance_mapping.len(), None);
+        }
+
+        let mut mapped_slice = self.instance_data_ubo.map_as_slice::<u8>(0, len).unwrap();
+        let ptr = mapped_slice.as_mut_ptr();
+
+        for (object_id, current_offset, previous_offset) in writes {
+            unsafe {
+                let current = ptr.add(current_offset).cast::<Mat4>().read_unaligned();
+                let previous = rendered[object_id].replace(current).unwrap_or(current);
+
+                ptr.add(previous_offset)
+                    .cast::<Mat4>()
+                    .write_unaligned(previous);
+            }
+        }
+
+        drop(mapped_slice);
+
+        self.instance_data_ubo
+            .flush_range(0, len as vk::DeviceSize)
+            .unwrap();
+    }
+
+    #[inline]
+    fn handle_mesh_changed(&mut self, mesh: MeshHandle) {
+        if self.batches.iter().any(|batch| batch.mesh == mesh) {
+            self.flags.insert(
+                SceneFrameFlags::NEED_MESH_REBUILD
+                    | SceneFrameFlags::NEED_INDIRECT_REBUILD
+                    | SceneFrameFlags::NEED_CULL_OBJECTS_REBUILD,
+            );
+        }
+    }
+
     #[inline]
     fn handle_add(
         &mut self,
         render_object_id: RenderObjectId,
         render_object_meta: RenderObjectMeta,
-        instance_data_layout: Layout,
+        instance_layout: InstanceLayout,
         instance_data: Vec<u8>,
     ) {
         if render_object_id.0 >= self.instance_mapping.len() {
@@ -197,9 +450,8 @@ impl SceneFrameData {
             self.instance_mapping.extend(additional.into_iter());
         }
 
-        let batch_id = self.batches.iter().position(|batch| {
-            batch.mesh == render_object_meta.mesh && batch.materials == render_object_meta.materials
-        });
+        let key = BatchKey::from(&render_object_meta);
+        let batch_id = self.batch_lookup.get(&key).copied();
 
         if let Some(batch_id) = batch_id {
             let batch = &mut self.batches[batch_id];
@@ -235,7 +487,7 @@ impl SceneFrameData {
                 None => 0,
             };
 
-            let mut batch = RenderBatch::new(offset, &render_object_meta, instance_data_layout);
+            let mut batch = RenderBatch::new(offset, &render_object_meta, instance_layout);
 
             unsafe {
                 let _ = batch.insert_bytes(0, &instance_data);
@@ -243,6 +495,7 @@ impl SceneFrameData {
 
             batch.count += 1;
             self.batches.push(batch);
+            self.batch_lookup.insert(key, batch_id);
 
             self.flags
                 .insert(SceneFrameFlags::NEED_INSTANCE_DATA_REBUILD);
@@ -254,7 +507,9 @@ impl SceneFrameData {
             }
         }
 
-        self.flags.insert(SceneFrameFlags::NEED_INDIRECT_REBUILD);
+        self.flags.insert(
+            SceneFrameFlags::NEED_INDIRECT_REBUILD | SceneFrameFlags::NEED_CULL_OBJECTS_REBUILD,
+        );
     }
 
     #[inline]
@@ -268,11 +523,21 @@ impl SceneFrameData {
             return;
         };
 
+        if self.tracks_previous {
+            if let Entry::Vacant(entry) = self.previous_transforms.entry((batch_id, object_idx)) {
+                if let Some(transform) = batch.instance_transform(object_idx) {
+                    entry.insert(transform);
+                }
+            }
+        }
+
         unsafe {
             batch.insert_bytes(object_idx, &instance_data);
         }
 
-        self.flags.insert(SceneFrameFlags::NEED_INSTANCE_DATA_SYNC);
+        self.flags.insert(
+            SceneFrameFlags::NEED_INSTANCE_DATA_SYNC | SceneFrameFlags::NEED_CULL_OBJECTS_REBUILD,
+        );
     }
 
     #[inline]
@@ -288,25 +553,85 @@ impl SceneFrameData {
         let Some(batch) = batch else { return };
 
         batch.holes.push_back(*object_id);
+        self.previous_transforms.remove(&(*batch_id, *object_id));
         *mapping = None;
 
-        self.flags.insert(SceneFrameFlags::NEED_INDIRECT_REBUILD);
+        self.flags.insert(
+            SceneFrameFlags::NEED_INDIRECT_REBUILD | SceneFrameFlags::NEED_CULL_OBJECTS_REBUILD,
+        );
     }
 
     #[inline]
-    fn handle_update_scene_uniform(&mut self, uniform: SceneUniform) {
-        let mut mapped = self
-            .scene_uniform_buffer
-            .map_memory::<SceneUniform>(0)
-            .unwrap();
+    fn handle_override_materials(
+        &mut self,
+        render_object_id: RenderObjectId,
+        overrides: RenderObjectMaterials,
+    ) {
+        self.move_to_batch(render_object_id, |meta| {
+            meta.materials.apply_overrides(&overrides)
+        });
+    }
 
-        *mapped = uniform;
+    #[inline]
+    fn handle_set_selected(&mut self, render_object_id: RenderObjectId, selected: bool) {
+        self.move_to_batch(render_object_id, |meta| {
+            meta.flags.set(RenderObjectFlags::SELECTED, selected)
+        });
+    }
 
-        drop(mapped);
+    /// Moves the object into the batch matching its meta after `change`, keeping
+    /// its instance data
+    fn move_to_batch(
+        &mut self,
+        render_object_id: RenderObjectId,
+        change: impl FnOnce(&mut RenderObjectMeta),
+    ) {
+        let Some(Some((batch_id, object_idx))) =
+            self.instance_mapping.get(render_object_id.0).cloned()
+        else {
+            return;
+        };
 
-        self.scene_uniform_buffer
-            .flush_range(0, size_of::<SceneUniform>() as vk::DeviceSize)
-            .unwrap();
+        let Some(batch) = self.batches.get(batch_id) else {
+            return;
+        };
+
+        let mut meta = batch.meta();
+        change(&mut meta);
+
+        if BatchKey::from(&meta) == BatchKey::from(&batch.meta()) {
+            return;
+        }
+
+        let Some(instance_data) = batch.instance_bytes(object_idx).map(<[u8]>::to_vec) else {
+            return;
+        };
+
+        let instance_layout = batch.instance_layout;
+
+        self.handle_remove(render_object_id);
+        self.handle_add(render_object_id, meta, instance_layout, instance_data);
+    }
+
+    /// Objects not updated in the new tick stay where they are, the interpolated
+    /// transforms of the last tick are replaced with the current ones
+    #[inline]
+    fn handle_begin_tick(&mut self) {
+        if !self.previous_transforms.is_empty() {
+            self.previous_transforms.clear();
+            self.flags.insert(SceneFrameFlags::NEED_INSTANCE_DATA_SYNC);
+        }
+
+        self.tracks_previous = true;
+    }
+
+    #[inline]
+    fn handle_update_scene_uniform(&mut self, uniform: SceneUniform) {
+        if let Err(err) = self.scene_uniform_buffer.write_at(0, &uniform) {
+            core_error!("Failed to write the scene uniform: {err}");
+        }
+
+        self.scene_uniform = Some(uniform);
     }
 
     #[inline]
@@ -341,24 +666,72 @@ impl SceneFrameData {
             },
         );
 
-        {
-            {
-                let mut mapped_slice = self
-                    .indirect_buffer
-                    .map_as_slice::<vk::DrawIndexedIndirectCommand>(0, indirects.len())
-                    .unwrap();
+        let written = self
+            .indirect_buffer
+            .reserve(get_device(), indirects.len().next_power_of_two())
+            .and_then(|_| self.indirect_buffer.write(&indirects));
 
-                mapped_slice.clone_from_slice(&indirects);
-            }
-            self.indirect_buffer
-                .flush_range(0, indirects.len() as vk::DeviceSize)
-                .unwrap();
+        if let Err(err) = written {
+            core_error!("Failed to write the indirect commands of the scene: {err}");
         }
 
         self.indirect_helpers = helpers;
         self.flags.remove(SceneFrameFlags::NEED_INDIRECT_REBUILD);
     }
 
+    /// Writes bounds of every object for the culling shader. Objects without a
+    /// `transform` in their instance data are culled with the bounds of their mesh
+    #[inline]
+    fn rebuild_cull_objects(&mut self) {
+        let capacity = self.culled_indirect_buffer.capacity();
+
+        let mut command_offset = 0;
+        let mut objects = Vec::new();
+
+        for (batch_index, batch) in self.batches.iter().enumerate() {
+            let batch_commands = command_offset;
+            command_offset += batch.count;
+
+            let Some(mesh_mapping) = self.mesh_map.get(&batch.mesh) else {
+                continue;
+            };
+
+            if command_offset > capacity {
+                core_warn!(
+                    "Scene has more instances than GPU culling supports ({capacity}), some of them won't be drawn"
+                );
+                break;
+            }
+
+            let local_sphere = mesh_mapping.bounds;
+
+            for instance in batch.instance_ranges().into_iter().flatten() {
+                let transform = batch
+                    .instance_transform(instance)
+                    .unwrap_or_else(Mat4::identity);
+                let sphere = transform_sphere(&transform, local_sphere);
+
+                objects.push(CullObject {
+                    sphere,
+                    first_index: mesh_mapping.index_offset,
+                    index_count: mesh_mapping.index_count,
+                    vertex_offset: mesh_mapping.vertex_offset as i32,
+                    first_instance: instance as u32,
+                    command_offset: batch_commands as u32,
+                    batch: batch_index as u32,
+                });
+            }
+        }
+
+        if let Err(err) = self.cull_object_buffer.write(&objects) {
+            core_error!("Failed to write the cull objects of the scene: {err}");
+        }
+
+        self.cull_object_count = objects.len() as u32;
+        self.flags
+            .remove(SceneFrameFlags::NEED_CULL_OBJECTS_REBUILD);
+    }
+
     #[inline]
     fn sync_instance_data(&mut self) {
         let instance_data_len = self
@@ -401,42 +774,70 @@ impl SceneFrameData {
             .remove(SceneFrameFlags::NEED_INSTANCE_DATA_REBUILD);
     }
 
+    /// Looks up the meshes of all batches in the pool, nothing is copied when
+    /// they are already there
     #[inline]
-    fn rebuild_mesh_data<S, A>(&mut self, mesh_store: &A)
+    fn rebuild_mesh_data<S, A>(&mut self, mesh_pool: &mut MeshPool, mesh_store: &A)
     where
         S: HandleStrategy<Mesh>,
         A: AssetStore<Mesh, S>,
     {
-        let (vertices, indices, mappings) = self.batches.iter().fold(
-            (Vec::new(), Vec::new(), BTreeMap::new()),
-            |(mut vertices, mut indices, mut mappings), batch| {
-                let mesh = mesh_store.get(&batch.mesh).unwrap();
-                let mapping = MeshMapping {
-                    index_offset: indices.len() as u32,
-                    index_count: mesh.indices.len() as u32,
-                    vertex_offset: vertices.len() as u32,
-                };
-
-                vertices.extend_from_slice(&mesh.vertices);
-                indices.extend_from_slice(&mesh.indices);
-                mappings.insert(batch.mesh, mapping);
+        self.mesh_map = self
+            .batches
+            .iter()
+            .filter_map(|batch| {
+                mesh_pool
+                    .acquire(batch.mesh, mesh_store)
+                    .map(|mapping| (batch.mesh, mapping))
+            })
+            .collect();
 
-                (vertices, indices, mappings)
-            },
-        );
+        self.flags.remove(SceneFrameFlags::NEED_MESH_REBUILD);
+    }
+}
 
-        self.mesh_map = mappings;
+/// Interpolates translation and scale linearly and rotation spherically, transforms
+/// with a degenerate scale are interpolated component-wise
+fn interpolate_transform(previous: &Mat4, current: &Mat4, factor: f32) -> Mat4 {
+    let (
+        Some((from_translation, from_rotation, from_scale)),
+        Some((to_translation, to_rotation, to_scale)),
+    ) = (decompose_transform(previous), decompose_transform(current))
+    else {
+        return previous * (1.0 - factor) + current * factor;
+    };
+
+    let translation = from_translation.lerp(&to_translation, factor);
+    let rotation = quat_slerp(&from_rotation, &to_rotation, factor);
+    let scale = from_scale.lerp(&to_scale, factor);
+
+    translation_matrix(&translation) * quat_to_mat4(&rotation) * scaling(&scale)
+}
 
-        {
-            let mut mapped_slice = self.vertex_buffer.map_as_slice(0, vertices.len()).unwrap();
-            mapped_slice.clone_from_slice(&vertices);
-        }
+/// Translation, rotation and scale of an affine transform without shear
+fn decompose_transform(transform: &Mat4) -> Option<(Vec3, Quat, Vec3)> {
+    let translation = transform.column(3).xyz();
 
-        {
-            let mut mapped_slice = self.index_buffer.map_as_slice(0, indices.len()).unwrap();
-            mapped_slice.clone_from_slice(&indices);
-        }
+    let axes = [0, 1, 2].map(|axis| transform.column(axis).xyz());
+    let scale = Vec3::new(axes[0].norm(), axes[1].norm(), axes[2].norm());
 
-        self.flags.remove(SceneFrameFlags::NEED_MESH_REBUILD);
+    if scale.min() <= f32::EPSILON {
+        return None;
     }
+
+    let rotation = Mat3::from_columns(&[axes[0] / scale.x, axes[1] / scale.y, axes[2] / scale.z]);
+
+    Some((translation, mat3_to_quat(&rotation), scale))
+}
+
+/// Moves a bounding sphere into the space of `transform`, the radius is scaled
+/// by the largest axis scale
+fn transform_sphere(transform: &Mat4, sphere: Vec4) -> Vec4 {
+    let center = transform * Vec4::new(sphere.x, sphere.y, sphere.z, 1.0);
+
+    let scale = (0..3)
+        .map(|axis| transform.column(axis).xyz().norm())
+        .fold(0.0, f32::max);
+
+    Vec4::new(center.x, center.y, center.z, sphere.w * scale)
 }
@@ -43,6 +43,7 @@ const CACHE_SHADER_PREFIX: &'static str = "cache/shaders";
 pub enum ShaderStage {
     Vertex = vk::ShaderStageFlags::VERTEX.as_raw(),
     Fragment = vk::ShaderStageFlags::FRAGMENT.as_raw(),
+    Compute = vk::ShaderStageFlags::COMPUTE.as_raw(),
 }
 
 impl From<ShaderStage> for shaderc::ShaderKind {
@@ -50,6 +51,7 @@ impl From<ShaderStage> for shaderc::ShaderKind {
         match value {
             ShaderStage::Vertex => shaderc::ShaderKind::Vertex,
             ShaderStage::Fragment => shaderc::ShaderKind::Fragment,
+            ShaderStage::Compute => shaderc::ShaderKind::Compute,
         }
     }
 }
@@ -59,6 +61,7 @@ impl From<ShaderStage> for vk::ShaderStageFlags {
         match value {
             ShaderStage::Vertex => vk::ShaderStageFlags::VERTEX,
             ShaderStage::Fragment => vk::ShaderStageFlags::FRAGMENT,
+            ShaderStage::Compute => vk::ShaderStageFlags::COMPUTE,
         }
     }
 }
@@ -98,6 +101,7 @@ bitflags! {
     pub struct ShaderStageFlags: u32 {
         const VERTEX = vk::ShaderStageFlags::VERTEX.as_raw();
         const FRAGMENT = vk::ShaderStageFlags::FRAGMENT.as_raw();
+        const COMPUTE = vk::ShaderStageFlags::COMPUTE.as_raw();
     }
 }
 
@@ -106,6 +110,7 @@ impl From<ShaderStage> for ShaderStageFlags {
         match value {
             ShaderStage::Vertex => ShaderStageFlags::VERTEX,
             ShaderStage::Fragment => ShaderStageFlags::FRAGMENT,
+            ShaderStage::Compute => ShaderStageFlags::COMPUTE,
         }
     }
 }
@@ -121,6 +126,7 @@ impl From<ShaderStageFlags> for ShaderStage {
         match value {
             ShaderStageFlags::VERTEX => ShaderStage::Vertex,
             ShaderStageFlags::FRAGMENT => ShaderStage::Fragment,
+            ShaderStageFlags::COMPUTE => ShaderStage::Compute,
             _ => panic!("cannot convert `ShaderStageFlags` into `ShaderStage` when there are more than one flag set")
         }
     }
@@ -0,0 +1,802 @@
+use std::collections::{BTreeMap, HashMap};
+
+use ash::vk;
+use thiserror::Error;
+
+use crate::{
+    material::{
+        material_binding::{MaterialBinding, MaterialBindingRate},
+        pipeline::VulkanPipelineRequirements,
+    },
+    shader::{ShaderStage, ShaderStageFlags},
+};
+
+#[derive(Error, Debug)]
+pub enum ReflectionError {
+    #[error("Spir-V module is too short to have a header")]
+    MissingHeader,
+    #[error("Spir-V instruction at word {0} is truncated")]
+    TruncatedInstruction(usize),
+    #[error("Shader uses `set = {set}, binding = {binding}` ({stage:?}) which is not in the material bindings")]
+    MissingBinding {
+        set: u32,
+        binding: u32,
+        stage: ShaderStage,
+    },
+    #[error("`set = {set}, binding = {binding}` is declared as `{declared:?}` while the shader expects `{reflected:?}`")]
+    BindingTypeMismatch {
+        set: u32,
+        binding: u32,
+        declared: vk::DescriptorType,
+        reflected: vk::DescriptorType,
+    },
+    #[error("`set = {set}, binding = {binding}` is declared with {declared} descriptors while the shader expects {reflected}")]
+    BindingCountMismatch {
+        set: u32,
+        binding: u32,
+        declared: u32,
+        reflected: u32,
+    },
+    #[error("`set = {set}, binding = {binding}` is not visible to the {stage:?} stage using it")]
+    BindingStageMismatch {
+        set: u32,
+        binding: u32,
+        stage: ShaderStage,
+    },
+    #[error("Vertex shader reads `location = {0}` which has no vertex attribute")]
+    MissingVertexAttribute(u32),
+    #[error("Vertex attribute at `location = {location}` is `{declared:?}` while the shader expects `{reflected:?}`")]
+    VertexFormatMismatch {
+        location: u32,
+        declared: vk::Format,
+        reflected: vk::Format,
+    },
+    #[error(
+        "Shader reads `input_attachment_index = {0}` which is not mapped to any color attachment"
+    )]
+    UnmappedInputAttachment(u32),
+}
+
+pub type ReflectionResult<T> = Result<T, ReflectionError>;
+
+const SPIRV_HEADER_LEN: usize = 5;
+
+mod op {
+    pub const DECORATE: u16 = 71;
+    pub const MEMBER_DECORATE: u16 = 72;
+    pub const TYPE_BOOL: u16 = 20;
+    pub const TYPE_INT: u16 = 21;
+    pub const TYPE_FLOAT: u16 = 22;
+    pub const TYPE_VECTOR: u16 = 23;
+    pub const TYPE_MATRIX: u16 = 24;
+    pub const TYPE_IMAGE: u16 = 25;
+    pub const TYPE_SAMPLER: u16 = 26;
+    pub const TYPE_SAMPLED_IMAGE: u16 = 27;
+    pub const TYPE_ARRAY: u16 = 28;
+    pub const TYPE_RUNTIME_ARRAY: u16 = 29;
+    pub const TYPE_STRUCT: u16 = 30;
+    pub const TYPE_POINTER: u16 = 32;
+    pub const CONSTANT: u16 = 43;
+    pub const VARIABLE: u16 = 59;
+    pub const TYPE_ACCELERATION_STRUCTURE: u16 = 5341;
+}
+
+mod decoration {
+    pub const BLOCK: u32 = 2;
+    pub const BUFFER_BLOCK: u32 = 3;
+    pub const ARRAY_STRIDE: u32 = 6;
+    pub const MATRIX_STRIDE: u32 = 7;
+    pub const BUILT_IN: u32 = 11;
+    pub const LOCATION: u32 = 30;
+    pub const BINDING: u32 = 33;
+    pub const DESCRIPTOR_SET: u32 = 34;
+    pub const OFFSET: u32 = 35;
+    pub const INPUT_ATTACHMENT_INDEX: u32 = 43;
+}
+
+mod storage_class {
+    pub const UNIFORM_CONSTANT: u32 = 0;
+    pub const INPUT: u32 = 1;
+    pub const UNIFORM: u32 = 2;
+    pub const PUSH_CONSTANT: u32 = 9;
+    pub const STORAGE_BUFFER: u32 = 12;
+}
+
+const DIM_BUFFER: u32 = 5;
+const DIM_SUBPASS_DATA: u32 = 6;
+
+/// Descriptor a shader stage declares
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ReflectedBinding {
+    pub set: u32,
+    pub binding: u32,
+    pub descriptor_type: vk::DescriptorType,
+    /// `0` for runtime sized arrays
+    pub descriptor_count: u32,
+    pub input_attachment_index: Option<u32>,
+}
+
+/// User defined input of a vertex shader
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ReflectedInput {
+    pub location: u32,
+    pub format: vk::Format,
+}
+
+/// Interface of a single shader stage read from its Spir-V
+#[derive(Debug, Clone)]
+pub struct ShaderReflection {
+    pub stage: ShaderStage,
+    pub bindings: Vec<ReflectedBinding>,
+    /// Only filled for the vertex stage, sorted by location
+    pub inputs: Vec<ReflectedInput>,
+    /// Size of the push constant block, `0` if there is none
+    pub push_constant_size: u32,
+}
+
+#[derive(Debug, Clone)]
+enum SpirvType {
+    Scalar {
+        float: bool,
+        signed: bool,
+        width: u32,
+    },
+    Vector {
+        component: u32,
+        count: u32,
+    },
+    Matrix {
+        column: u32,
+        count: u32,
+    },
+    Image {
+        dim: u32,
+        sampled: u32,
+    },
+    Sampler,
+    SampledImage,
+    AccelerationStructure,
+    Array {
+        element: u32,
+        length: u32,
+    },
+    RuntimeArray {
+        element: u32,
+    },
+    Struct {
+        members: Vec<u32>,
+    },
+    Pointer {
+        pointee: u32,
+    },
+}
+
+#[derive(Default)]
+struct Decorations {
+    set: Option<u32>,
+    binding: Option<u32>,
+    location: Option<u32>,
+    input_attachment_index: Option<u32>,
+    array_stride: Option<u32>,
+    built_in: bool,
+    block: bool,
+    buffer_block: bool,
+}
+
+#[derive(Default)]
+struct MemberDecorations {
+    offset: Option<u32>,
+    matrix_stride: Option<u32>,
+}
+
+#[derive(Default)]
+struct Module {
+    types: HashMap<u32, SpirvType>,
+    constants: HashMap<u32, u32>,
+    decorations: HashMap<u32, Decorations>,
+    member_decorations: HashMap<(u32, u32), MemberDecorations>,
+    /// `(result type, id, storage class)`
+    variables: Vec<(u32, u32, u32)>,
+}
+
+impl ShaderReflection {
+    pub fn from_spirv(code: &[u32], stage: ShaderStage) -> ReflectionResult<Self> {
+        let module = Module::parse(code)?;
+
+        let mut bindings = Vec::new();
+        let mut inputs = Vec::new();
+        let mut push_constant_size = 0;
+
+        for &(pointer_type, id, storage) in module.variables.iter() {
+            let Some(SpirvType::Pointer { pointee }) = module.types.get(&pointer_type) else {
+                continue;
+            };
+
+            let decorations = module.decorations.get(&id);
+
+            match storage {
+                storage_class::UNIFORM_CONSTANT
+                | storage_class::UNIFORM
+                | storage_class::STORAGE_BUFFER => {
+                    let Some(decorations) = decorations else {
+                        continue;
+                    };
+
+                    let (element, descriptor_count) = module.unwrap_array(*pointee);
+
+                    let Some(descriptor_type) = module.descriptor_type(element, storage) else {
+                        continue;
+                    };
+
+                    bindings.push(ReflectedBinding {
+                        set: decorations.set.unwrap_or(0),
+                        binding: decorations.binding.unwrap_or(0),
+                        descriptor_type,
+                        descriptor_count,
+                        input_attachment_index: decorations.input_attachment_index,
+                    });
+                }
+                storage_class::INPUT if stage == ShaderStage::Vertex => {
+                    let Some(location) = decorations
+                        .filter(|decorations| !decorations.built_in)
+                        .and_then(|decorations| decorations.location)
+                    else {
+                        continue;
+                    };
+
+                    // Matrices take a location for every column
+                    let (format, locations) = match module.types.get(pointee) {
+                        Some(SpirvType::Matrix { column, count }) => {
+                            (module.vertex_format(*column), *count)
+                        }
+                        _ => (module.vertex_format(*pointee), 1),
+                    };
+
+                    inputs.extend((0..locations).map(|i| ReflectedInput {
+                        location: location + i,
+                        format,
+                    }));
+                }
+                storage_class::PUSH_CONSTANT => {
+                    push_constant_size = push_constant_size.max(module.size_of(*pointee, None));
+                }
+                _ => (),
+            }
+        }
+
+        bindings.sort_by_key(|binding| (binding.set, binding.binding));
+        inputs.sort_by_key(|input| input.location);
+
+        Ok(Self {
+            stage,
+            bindings,
+            inputs,
+            push_constant_size,
+        })
+    }
+}
+
+impl Module {
+    fn parse(code: &[u32]) -> ReflectionResult<Self> {
+        if code.len() < SPIRV_HEADER_LEN {
+            return Err(ReflectionError::MissingHeader);
+        }
+
+        let mut module = Self::default();
+        let mut cursor = SPIRV_HEADER_LEN;
+
+        while cursor < code.len() {
+            let word_count = (code[cursor] >> 16) as usize;
+            let opcode = (code[cursor] & 0xffff) as u16;
+
+            if word_count == 0 || cursor + word_count > code.len() {
+                return Err(ReflectionError::TruncatedInstruction(cursor));
+            }
+
+            module.parse_instruction(opcode, &code[cursor + 1..cursor + word_count]);
+            cursor += word_count;
+        }
+
+        Ok(module)
+    }
+
+    fn parse_instruction(&mut self, opcode: u16, operands: &[u32]) {
+        let operand = |i: usize| operands.get(i).copied().unwrap_or(0);
+
+        let ty = match opcode {
+            op::DECORATE => {
+                let decorations = self.decorations.entry(operand(0)).or_default();
+                let value = operands.get(2).copied();
+
+                match operand(1) {
+                    decoration::BLOCK => decorations.block = true,
+                    decoration::BUFFER_BLOCK => decorations.buffer_block = true,
+                    decoration::ARRAY_STRIDE => decorations.array_stride = value,
+                    decoration::BUILT_IN => decorations.built_in = true,
+                    decoration::LOCATION => decorations.location = value,
+                    decoration::BINDING => decorations.binding = value,
+                    decoration::DESCRIPTOR_SET => decorations.set = value,
+                    decoration::INPUT_ATTACHMENT_INDEX => {
+                        decorations.input_attachment_index = value
+                    }
+                    _ => (),
+                }
+
+                return;
+            }
+            op::MEMBER_DECORATE => {
+                let decorations = self
+                    .member_decorations
+                    .entry((operand(0), operand(1)))
+                    .or_default();
+                let value = operands.get(3).copied();
+
+                match operand(2) {
+                    decoration::OFFSET => decorations.offset = value,
+                    decoration::MATRIX_STRIDE => decorations.matrix_stride = value,
+                    _ => (),
+                }
+
+                return;
+            }
+            op::CONSTANT => {
+                self.constants.insert(operand(1), operand(2));
+                return;
+            }
+            op::VARIABLE => {
+                self.variables.push((operand(0), operand(1), operand(2)));
+                return;
+            }
+            op::TYPE_BOOL => SpirvType::Scalar {
+                float: false,
+                signed: false,
+                width: 32,
+            },
+            op::TYPE_INT => SpirvType::Scalar {
+                float: false,
+                signed: operand(2) != 0,
+                width: operand(1),
+            },
+            op::TYPE_FLOAT => SpirvType::Scalar {
+                float: true,
+                signed: true,
+                width: operand(1),
+            },
+            op::TYPE_VECTOR => SpirvType::Vector {
+                component: operand(1),
+                count: operand(2),
+            },
+            op::TYPE_MATRIX => SpirvType::Matrix {
+                column: operand(1),
+                count: operand(2),
+            },
+            op::TYPE_IMAGE => SpirvType::Image {
+                dim: operand(2),
+                sampled: operand(6),
+            },
+            op::TYPE_SAMPLER => SpirvType::Sampler,
+            op::TYPE_SAMPLED_IMAGE => SpirvType::SampledImage,
+            op::TYPE_ACCELERATION_STRUCTURE => SpirvType::AccelerationStructure,
+            op::TYPE_ARRAY => SpirvType::Array {
+                element: operand(1),
+                length: operand(2),
+            },
+            op::TYPE_RUNTIME_ARRAY => SpirvType::RuntimeArray {
+                element: operand(1),
+            },
+            op::TYPE_STRUCT => SpirvType::Struct {
+                members: operands.get(1..).unwrap_or_default().to_vec(),
+            },
+            op::TYPE_POINTER => SpirvType::Pointer {
+                pointee: operand(2),
+            },
+            _ => return,
+        };
+
+        self.types.insert(operand(0), ty);
+    }
+
+    /// Element type and descriptor count of a possibly arrayed binding
+    fn unwrap_array(&self, ty: u32) -> (u32, u32) {
+        match self.types.get(&ty) {
+            Some(SpirvType::Array { element, length }) => {
+                (*element, self.constants.get(length).copied().unwrap_or(1))
+            }
+            Some(SpirvType::RuntimeArray { element }) => (*element, 0),
+            _ => (ty, 1),
+        }
+    }
+
+    fn descriptor_type(&self, ty: u32, storage: u32) -> Option<vk::DescriptorType> {
+        let decorations = self.decorations.get(&ty);
+        let has = |f: fn(&Decorations) -> bool| decorations.is_some_and(f);
+
+        let descriptor_type = match (self.types.get(&ty)?, storage) {
+            (SpirvType::Struct { .. }, storage_class::STORAGE_BUFFER) => {
+                vk::DescriptorType::STORAGE_BUFFER
+            }
+            (SpirvType::Struct { .. }, _) if has(|d| d.buffer_block) => {
+                vk::DescriptorType::STORAGE_BUFFER
+            }
+            (SpirvType::Struct { .. }, _) if has(|d| d.block) => vk::DescriptorType::UNIFORM_BUFFER,
+            (SpirvType::Image { dim, .. }, _) if *dim == DIM_SUBPASS_DATA => {
+                vk::DescriptorType::INPUT_ATTACHMENT
+            }
+            (SpirvType::Image { dim, sampled }, _) if *dim == DIM_BUFFER => {
+                if *sampled == 2 {
+                    vk::DescriptorType::STORAGE_TEXEL_BUFFER
+                } else {
+                    vk::DescriptorType::UNIFORM_TEXEL_BUFFER
+                }
+            }
+            (SpirvType::Image { sampled: 2, .. }, _) => vk::DescriptorType::STORAGE_IMAGE,
+            (SpirvType::Image { .. }, _) => vk::DescriptorType::SAMPLED_IMAGE,
+            (SpirvType::Sampler, _) => vk::DescriptorType::SAMPLER,
+            (SpirvType::SampledImage, _) => vk::DescriptorType::COMBINED_IMAGE_SAMPLER,
+            (SpirvType::AccelerationStructure, _) => vk::DescriptorType::ACCELERATION_STRUCTURE_KHR,
+            _ => return None,
+        };
+
+        Some(descriptor_type)
+    }
+
+    fn vertex_format(&self, ty: u32) -> vk::Format {
+        let (scalar, count) = match self.types.get(&ty) {
+            Some(SpirvType::Vector { component, count }) => (self.types.get(component), *count),
+            scalar => (scalar, 1),
+        };
+
+        let Some(&SpirvType::Scalar {
+            float,
+            signed,
+            width,
+        }) = scalar
+        else {
+            return vk::Format::UNDEFINED;
+        };
+
+        use vk::Format as F;
+
+        let formats = match (float, signed, width) {
+            (true, _, 32) => [
+                F::R32_SFLOAT,
+                F::R32G32_SFLOAT,
+                F::R32G32B32_SFLOAT,
+                F::R32G32B32A32_SFLOAT,
+            ],
+            (true, _, 64) => [
+                F::R64_SFLOAT,
+                F::R64G64_SFLOAT,
+                F::R64G64B64_SFLOAT,
+                F::R64G64B64A64_SFLOAT,
+            ],
+            (true, _, 16) => [
+                F::R16_SFLOAT,
+                F::R16G16_SFLOAT,
+                F::R16G16B16_SFLOAT,
+                F::R16G16B16A16_SFLOAT,
+            ],
+            (false, true, 32) => [
+                F::R32_SINT,
+                F::R32G32_SINT,
+                F::R32G32B32_SINT,
+                F::R32G32B32A32_SINT,
+            ],
+            (false, false, 32) => [
+                F::R32_UINT,
+                F::R32G32_UINT,
+                F::R32G32B32_UINT,
+                F::R32G32B32A32_UINT,
+            ],
+            _ => return vk::Format::UNDEFINED,
+        };
+
+        formats
+            .get(count.saturating_sub(1) as usize)
+            .copied()
+            .unwrap_or(vk::Format::UNDEFINED)
+    }
+
+    /// Size of `ty` laid out in a block, `matrix_stride` comes from the member
+    /// decorations of the enclosing struct
+    fn size_of(&self, ty: u32, matrix_stride: Option<u32>) -> u32 {
+        let Some(spirv_type) = self.types.get(&ty) else {
+            return 0;
+        };
+
+        match spirv_type {
+            SpirvType::Scalar { width, .. } => width / 8,
+            SpirvType::Vector { component, count } => self.size_of(*component, None) * count,
+            SpirvType::Matrix { column, count } => {
+                matrix_stride.unwrap_or_else(|| self.size_of(*column, None)) * count
+            }
+            SpirvType::Array { element, length } => {
+                let length = self.constants.get(length).copied().unwrap_or(0);
+                let stride = self
+                    .decorations
+                    .get(&ty)
+                    .and_then(|decorations| decorations.array_stride)
+                    .unwrap_or_else(|| self.size_of(*element, matrix_stride));
+
+                stride * length
+            }
+            SpirvType::Struct { members } => members
+                .iter()
+                .enumerate()
+                .map(|(index, member)| {
+                    let decorations = self.member_decorations.get(&(ty, index as u32));
+                    let offset = decorations.and_then(|d| d.offset).unwrap_or(0);
+                    let matrix_stride = decorations.and_then(|d| d.matrix_stride);
+
+                    offset + self.size_of(*member, matrix_stride)
+                })
+                .max()
+                .unwrap_or(0),
+            // Physical storage buffer references are 64 bit addresses
+            SpirvType::Pointer { .. } => 8,
+            _ => 0,
+        }
+    }
+}
+
+/// Interface of all stages of a pipeline
+#[derive(Debug, Clone, Default)]
+pub struct PipelineReflection {
+    bindings: BTreeMap<(u32, u32), (ReflectedBinding, ShaderStageFlags)>,
+    inputs: Vec<ReflectedInput>,
+    push_constants: Option<(u32, ShaderStageFlags)>,
+}
+
+impl PipelineReflection {
+    pub fn new(stages: &[ShaderReflection]) -> Self {
+        let mut reflection = Self::default();
+
+        for stage in stages {
+            let stage_flags = ShaderStageFlags::from(stage.stage);
+
+            for binding in stage.bindings.iter() {
+                reflection
+                    .bindings
+                    .entry((binding.set, binding.binding))
+                    .and_modify(|(_, flags)| *flags |= stage_flags)
+                    .or_insert_with(|| (binding.clone(), stage_flags));
+            }
+
+            if stage.stage == ShaderStage::Vertex {
+                reflection.inputs = stage.inputs.clone();
+            }
+
+            if stage.push_constant_size > 0 {
+                let (size, flags) = reflection
+                    .push_constants
+                    .get_or_insert((0, ShaderStageFlags::empty()));
+
+                *size = (*size).max(stage.push_constant_size);
+                *flags |= stage_flags;
+            }
+        }
+
+        reflection
+    }
+
+    /// Material bindings matching the shaders. Binding rates can't be
+    /// reflected, every binding is `PerFrame`
+    pub fn bindings(&self) -> Vec<MaterialBinding> {
+        self.bindings
+            .values()
+            .map(|(binding, stage_flags)| MaterialBinding {
+                set: binding.set,
+                binding: binding.binding,
+                descriptor_type: binding.descriptor_type,
+                descriptor_count: binding.descriptor_count.max(1),
+                binding_rate: MaterialBindingRate::PerFrame,
+                shader_stage_flags: *stage_flags,
+            })
+            .collect()
+    }
+
+    /// Input attachments the shaders read, ordered by set and binding
+    pub fn input_attachments(&self) -> Vec<ReflectedBinding> {
+        self.bindings
+            .values()
+            .filter(|(binding, _)| binding.input_attachment_index.is_some())
+            .map(|(binding, _)| binding.clone())
+            .collect()
+    }
+
+    /// Vertex attributes of the vertex shader, tightly packed into `binding` in
+    /// location order, and the stride of the binding
+    pub fn vertex_attributes(
+        &self,
+        binding: u32,
+    ) -> (Vec<vk::VertexInputAttributeDescription>, u32) {
+        let mut offset = 0;
+
+        let attributes = self
+            .inputs
+            .iter()
+            .map(|input| {
+                let attribute = vk::VertexInputAttributeDescription {
+                    binding,
+                    location: input.location,
+                    format: input.format,
+                    offset,
+                };
+
+                offset += format_size(input.format);
+
+                attribute
+            })
+            .collect();
+
+        (attributes, offset)
+    }
+
+    /// A single range covering the push constants of all stages
+    pub fn push_constant_ranges(&self) -> Vec<vk::PushConstantRange> {
+        self.push_constants
+            .iter()
+            .map(|(size, stage_flags)| vk::PushConstantRange {
+                stage_flags: (*stage_flags).into(),
+                offset: 0,
+                size: *size,
+            })
+            .collect()
+    }
+
+    /// Fills the parts of `requirements` left empty from the shaders and
+    /// validates the rest against them.
+    ///
+    /// Empty `bindings` are replaced with [`bindings`](Self::bindings), empty vertex
+    /// bindings and attributes with a single tightly packed binding and empty
+    /// `input_attachment_indices` map color attachment `i` to `input_attachment_index = i`
+    pub fn complete<'a>(
+        &self,
+        requirements: &VulkanPipelineRequirements<'a>,
+    ) -> ReflectionResult<VulkanPipelineRequirements<'a>> {
+        let mut requirements = requirements.clone();
+
+        if requirements.bindings.is_empty() {
+            requirements.bindings = self.bindings();
+        }
+
+        if requirements.vertex_attributes.is_empty()
+            && requirements.vertex_bindings.is_empty()
+            && !self.inputs.is_empty()
+        {
+            let (attributes, stride) = self.vertex_attributes(0);
+
+            requirements.vertex_attributes = attributes;
+            requirements.vertex_bindings = vec![vk::VertexInputBindingDescription {
+                binding: 0,
+                stride,
+                input_rate: vk::VertexInputRate::VERTEX,
+            }];
+        }
+
+        if requirements.input_attachment_indices.is_empty() {
+            let used = self
+                .bindings
+                .values()
+                .filter_map(|(binding, _)| binding.input_attachment_index)
+                .collect::<Vec<_>>();
+
+            requirements.input_attachment_indices = (0
+                ..requirements.color_attachment_formats.len() as u32)
+                .map(|i| {
+                    if used.contains(&i) {
+                        i
+                    } else {
+                        vk::ATTACHMENT_UNUSED
+                    }
+                })
+                .collect();
+        }
+
+        self.validate(&requirements)?;
+
+        Ok(requirements)
+    }
+
+    /// Checks that `requirements` provide everything the shaders use
+    pub fn validate(&self, requirements: &VulkanPipelineRequirements) -> ReflectionResult<()> {
+        for (reflected, stage_flags) in self.bindings.values() {
+            let (set, binding) = (reflected.set, reflected.binding);
+
+            let Some(declared) = requirements
+                .bindings
+                .iter()
+                .find(|declared| declared.set == set && declared.binding == binding)
+            else {
+                return Err(ReflectionError::MissingBinding {
+                    set,
+                    binding,
+                    stage: first_stage(*stage_flags),
+                });
+            };
+
+            if declared.descriptor_type != reflected.descriptor_type {
+                return Err(ReflectionError::BindingTypeMismatch {
+                    set,
+                    binding,
+                    declared: declared.descriptor_type,
+                    reflected: reflected.descriptor_type,
+                });
+            }
+
+            if reflected.descriptor_count != 0
+                && declared.descriptor_count != reflected.descriptor_count
+            {
+                return Err(ReflectionError::BindingCountMismatch {
+                    set,
+                    binding,
+                    declared: declared.descriptor_count,
+                    reflected: reflected.descriptor_count,
+                });
+            }
+
+            let missing_stages = stage_flags.difference(declared.shader_stage_flags);
+
+            if !missing_stages.is_empty() {
+                return Err(ReflectionError::BindingStageMismatch {
+                    set,
+                    binding,
+                    stage: first_stage(missing_stages),
+                });
+            }
+
+            if let Some(index) = reflected.input_attachment_index {
+                if !requirements.input_attachment_indices.contains(&index) {
+                    return Err(ReflectionError::UnmappedInputAttachment(index));
+                }
+            }
+        }
+
+        for input in self.inputs.iter() {
+            let Some(declared) = requirements
+                .vertex_attributes
+                .iter()
+                .find(|attribute| attribute.location == input.location)
+            else {
+                return Err(ReflectionError::MissingVertexAttribute(input.location));
+            };
+
+            if input.format != vk::Format::UNDEFINED && declared.format != input.format {
+                return Err(ReflectionError::VertexFormatMismatch {
+                    location: input.location,
+                    declared: declared.format,
+                    reflected: input.format,
+                });
+            }
+        }
+
+        Ok(())
+    }
+}
+
+fn first_stage(flags: ShaderStageFlags) -> ShaderStage {
+    let first = flags.bits() & flags.bits().wrapping_neg();
+    ShaderStage::from(ShaderStageFlags::from_bits_truncate(first))
+}
+
+fn format_size(format: vk::Format) -> u32 {
+    use vk::Format as F;
+
+    match format {
+        F::R16_SFLOAT => 2,
+        F::R16G16_SFLOAT | F::R32_SFLOAT | F::R32_SINT | F::R32_UINT => 4,
+        F::R16G16B16_SFLOAT => 6,
+        F::R16G16B16A16_SFLOAT
+        | F::R32G32_SFLOAT
+        | F::R32G32_SINT
+        | F::R32G32_UINT
+        | F::R64_SFLOAT => 8,
+        F::R32G32B32_SFLOAT | F::R32G32B32_SINT | F::R32G32B32_UINT => 12,
+        F::R32G32B32A32_SFLOAT | F::R32G32B32A32_SINT | F::R32G32B32A32_UINT | F::R64G64_SFLOAT => {
+            16
+        }
+        F::R64G64B64_SFLOAT => 24,
+        F::R64G64B64A64_SFLOAT => 32,
+        _ => 0,
+    }
+}
@@ -0,0 +1,70 @@
+use nalgebra_glm::{Vec2, Vec4};
+
+use crate::texture::TextureHandle;
+
+/// Sprites a render target fits into a frame, the rest are skipped
+pub const MAX_SPRITES_PER_FRAME: usize = 16384;
+
+/// Size of the texture array in `sprite.frag`, consecutive batches with up to
+/// this many different textures are drawn at once
+pub const MAX_SPRITE_TEXTURES: usize = 16;
+
+/// Screen space quad, layout of a sprite in `sprite.vert`
+#[repr(C)]
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Sprite {
+    /// Top-left corner and size in pixels of the package viewport
+    pub rect: Vec4,
+    /// Top-left and bottom-right texture coordinates
+    pub uv: Vec4,
+    /// Multiplied with the texture
+    pub color: Vec4,
+    /// Index of the batch texture in the texture array, set by the renderer
+    texture_index: u32,
+    _padding: [u32; 3],
+}
+
+impl Sprite {
+    pub fn new(position: Vec2, size: Vec2) -> Self {
+        Self {
+            rect: Vec4::new(position.x, position.y, size.x, size.y),
+            uv: Vec4::new(0.0, 0.0, 1.0, 1.0),
+            color: Vec4::repeat(1.0),
+            texture_index: 0,
+            _padding: [0; 3],
+        }
+    }
+
+    pub fn with_uv(mut self, min: Vec2, max: Vec2) -> Self {
+        self.uv = Vec4::new(min.x, min.y, max.x, max.y);
+        self
+    }
+
+    pub fn with_color(mut self, color: Vec4) -> Self {
+        self.color = color;
+        self
+    }
+
+    pub(crate) fn with_texture_index(mut self, texture_index: u32) -> Self {
+        self.texture_index = texture_index;
+        self
+    }
+}
+
+/// Sprites sharing a texture, drawn in order over the scene of a
+/// [`RenderPackage`](crate::submitter::RenderPackage)
+#[derive(Clone, Debug, Default)]
+pub struct SpriteBatch {
+    /// Solid white if `None`, so the sprites are filled with their color
+    pub texture: Option<TextureHandle>,
+    pub sprites: Vec<Sprite>,
+}
+
+impl SpriteBatch {
+    pub fn new(texture: Option<TextureHandle>) -> Self {
+        Self {
+            texture,
+            sprites: Vec::new(),
+        }
+    }
+}
@@ -1,11 +1,91 @@
 use bizarre_ecs::prelude::*;
-use nalgebra_glm::Mat4;
+use nalgebra_glm::{Mat4, Vec4};
 
-use crate::scene::SceneHandle;
+use crate::{
+    camera::CameraViewport,
+    material::material_instance::MaterialInstanceHandle,
+    scene::{SceneHandle, SceneUniform},
+    sprite::SpriteBatch,
+};
+
+/// How a package fills its area before the scene is drawn
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum ClearMode {
+    Color(Vec4),
+    /// Draws a full screen triangle with the material instance behind the scene,
+    /// see [`gradient_skybox`](crate::material::builtin::gradient_skybox)
+    Skybox(MaterialInstanceHandle),
+    /// Keeps what earlier packages of the frame have drawn in the area
+    None,
+}
+
+impl Default for ClearMode {
+    fn default() -> Self {
+        Self::Color(Vec4::zeros())
+    }
+}
 
 pub struct RenderPackage {
     pub scene: SceneHandle,
     pub pov: Mat4,
+    /// View and projection written into the uniform of the current frame when the
+    /// package is recorded, the scene uniform is used if `None`
+    pub camera: Option<SceneUniform>,
+    pub viewport: CameraViewport,
+    /// Clips the render inside of the viewport, nothing is clipped if `None`
+    pub scissor: Option<CameraViewport>,
+    pub clear: ClearMode,
+    /// Depth the area is cleared to, `None` keeps the depth of earlier packages
+    pub clear_depth: Option<f32>,
+    /// Position between the previous and the current simulation tick the scene
+    /// objects are drawn at, see [`Scene::begin_tick`](crate::scene::Scene::begin_tick).
+    /// Packages drawing the same scene into a target use the factor of the first one
+    pub interpolation: f32,
+    /// Drawn in order over the scene, inside of the viewport
+    pub sprites: Vec<SpriteBatch>,
+}
+
+impl RenderPackage {
+    pub fn new(scene: SceneHandle) -> Self {
+        Self {
+            scene,
+            pov: Mat4::identity(),
+            camera: None,
+            viewport: CameraViewport::FULL,
+            scissor: None,
+            clear: ClearMode::default(),
+            clear_depth: Some(1.0),
+            interpolation: 1.0,
+            sprites: Vec::new(),
+        }
+    }
+
+    /// The supported way to move a camera, the matrices can't lag behind the
+    /// frame they are drawn in
+    pub fn with_camera(mut self, camera: SceneUniform) -> Self {
+        self.camera = Some(camera);
+        self
+    }
+
+    pub fn with_clear(mut self, clear: ClearMode) -> Self {
+        self.clear = clear;
+        self
+    }
+
+    pub fn with_clear_depth(mut self, clear_depth: Option<f32>) -> Self {
+        self.clear_depth = clear_depth;
+        self
+    }
+
+    pub fn with_sprites(mut self, sprites: Vec<SpriteBatch>) -> Self {
+        self.sprites = sprites;
+        self
+    }
+
+    pub fn with_interpolation(mut self, interpolation: f32) -> Self {
+        self.interpolation = interpolation;
+        self
+    }
 }
 
 #[derive(Resource)]
@@ -0,0 +1,112 @@
+use std::ffi::CStr;
+
+use ash::{
+    prelude::VkResult,
+    vk::{self, Handle},
+};
+use bizarre_log::core_info;
+use bizarre_sdl::window::{
+    native_window, windowing_backend, NativeWindow, Window, WindowingBackend,
+};
+
+use crate::instance::{SurfaceBackend, VulkanInstance};
+
+/// Resolves [`SurfaceBackend::Auto`] and returns the instance extensions
+/// `backend` needs, as `(backend, required, optional)`.
+///
+/// When the windowing backend is unknown the backend stays `Auto`, only
+/// `VK_KHR_surface` is required and SDL creates the surfaces with whatever
+/// platform extension is available
+pub(crate) fn surface_extensions(
+    backend: SurfaceBackend,
+) -> (
+    SurfaceBackend,
+    &'static [&'static CStr],
+    &'static [&'static CStr],
+) {
+    let backend = match backend {
+        SurfaceBackend::Auto => {
+            let windowing = windowing_backend();
+            core_info!("Windowing backend: {windowing:?}");
+
+            match windowing {
+                WindowingBackend::Wayland => SurfaceBackend::Wayland,
+                WindowingBackend::X11 => SurfaceBackend::Xlib,
+                WindowingBackend::Win32 => SurfaceBackend::Win32,
+                WindowingBackend::Other => SurfaceBackend::Auto,
+            }
+        }
+        backend => backend,
+    };
+
+    let (required, optional): (&[&CStr], &[&CStr]) = match backend {
+        SurfaceBackend::Headless => (&[], &[]),
+        SurfaceBackend::Wayland => (
+            &[ash::khr::surface::NAME, ash::khr::wayland_surface::NAME],
+            &[],
+        ),
+        SurfaceBackend::Xcb => (&[ash::khr::surface::NAME, ash::khr::xcb_surface::NAME], &[]),
+        SurfaceBackend::Xlib => (
+            &[ash::khr::surface::NAME, ash::khr::xlib_surface::NAME],
+            &[],
+        ),
+        SurfaceBackend::Win32 => (
+            &[ash::khr::surface::NAME, ash::khr::win32_surface::NAME],
+            &[],
+        ),
+        SurfaceBackend::Auto => (
+            &[ash::khr::surface::NAME],
+            &[
+                ash::khr::wayland_surface::NAME,
+                ash::khr::xlib_surface::NAME,
+                ash::khr::xcb_surface::NAME,
+                ash::khr::win32_surface::NAME,
+            ],
+        ),
+    };
+
+    (backend, required, optional)
+}
+
+/// Creates a surface for `window` on its windowing backend, windows of other
+/// backends (including Win32) and windows whose surface extension isn't enabled
+/// on the instance get their surfaces from SDL. Headless instances can't create
+/// surfaces at all
+pub(crate) fn create_surface(
+    instance: &VulkanInstance,
+    window: &Window,
+) -> VkResult<vk::SurfaceKHR> {
+    if instance.is_headless() {
+        return Err(vk::Result::ERROR_EXTENSION_NOT_PRESENT);
+    }
+
+    match native_window(window) {
+        NativeWindow::Wayland { display, surface }
+            if instance.has_extension(ash::khr::wayland_surface::NAME) =>
+        {
+            let loader =
+                ash::khr::wayland_surface::Instance::new(&instance.entry, &instance.instance);
+
+            let create_info = vk::WaylandSurfaceCreateInfoKHR::default()
+                .display(display)
+                .surface(surface);
+
+            unsafe { loader.create_wayland_surface(&create_info, None) }
+        }
+        NativeWindow::X11 { display, window }
+            if instance.has_extension(ash::khr::xlib_surface::NAME) =>
+        {
+            let loader = ash::khr::xlib_surface::Instance::new(&instance.entry, &instance.instance);
+
+            let create_info = vk::XlibSurfaceCreateInfoKHR::default()
+                .dpy(display.cast())
+                .window(window as vk::Window);
+
+            unsafe { loader.create_xlib_surface(&create_info, None) }
+        }
+        _ => window
+            .vulkan_create_surface(instance.handle().as_raw() as usize)
+            .map(vk::SurfaceKHR::from_raw)
+            .map_err(|_| vk::Result::ERROR_INITIALIZATION_FAILED),
+    }
+}
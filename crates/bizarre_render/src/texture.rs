@@ -0,0 +1,418 @@
+use std::path::{Path, PathBuf};
+
+use ash::vk::{self, Handle as _};
+use bizarre_core::Handle;
+use bizarre_log::core_warn;
+use nalgebra_glm::UVec2;
+use thiserror::Error;
+
+use crate::{
+    bcn::{self, compressed_len},
+    buffer::{BufferError, GpuBuffer},
+    image::{mip_level_count, VulkanImage},
+    ktx2::Ktx2Image,
+    memory_stats::{track_compressed_texture, track_compressed_texture_free},
+    sampler::SamplerDesc,
+    vulkan_context::get_device,
+};
+
+pub type TextureHandle = Handle<Texture>;
+
+#[derive(Debug, Error)]
+pub enum TextureError {
+    #[error("Got {len} bytes of pixels for a {size:?} texture, expected {expected}")]
+    WrongPixelCount {
+        len: usize,
+        expected: usize,
+        size: UVec2,
+    },
+    #[error("Texture size must not be zero")]
+    ZeroSize,
+    #[error("Invalid KTX2 file: {0}")]
+    InvalidKtx2(&'static str),
+    #[error("Basis Universal textures are not supported, there is no transcoder for them")]
+    BasisUniversal,
+    #[error("KTX2 supercompression scheme {0} is not supported")]
+    UnsupportedSupercompression(u32),
+    #[error("Texture format {0:?} is not supported")]
+    UnsupportedFormat(vk::Format),
+    #[error("Failed to read `{0}`: {1}")]
+    Io(PathBuf, std::io::Error),
+    #[error(transparent)]
+    VulkanError(#[from] vk::Result),
+    #[error(transparent)]
+    BufferError(#[from] BufferError),
+}
+
+pub type TextureResult<T> = Result<T, TextureError>;
+
+/// Pixels a texture is uploaded from
+enum TextureData {
+    /// Level 0 in 8-bit RGBA, the rest of the chain is blitted from it
+    Rgba8(Vec<u8>),
+    /// Every level of a block-compressed image, from the largest
+    Compressed(Vec<Vec<u8>>),
+}
+
+/// Sampled image with a mip chain, uploaded from 8-bit RGBA pixels or from
+/// block-compressed ones, see [`Texture::from_ktx2`].
+///
+/// The pixels are kept on the CPU to rebuild the image after a device loss
+pub struct Texture {
+    image: VulkanImage,
+    data: TextureData,
+    size: UVec2,
+    format: vk::Format,
+    sampler: SamplerDesc,
+}
+
+impl Texture {
+    /// `format` has to be a 4 byte RGBA format, `R8G8B8A8_SRGB` for colors and
+    /// `R8G8B8A8_UNORM` for data like normal maps
+    pub fn from_rgba8(
+        size: UVec2,
+        format: vk::Format,
+        pixels: Vec<u8>,
+        sampler: SamplerDesc,
+    ) -> TextureResult<Self> {
+        if size.x == 0 || size.y == 0 {
+            return Err(TextureError::ZeroSize);
+        }
+
+        let expected = (size.x * size.y * 4) as usize;
+        if pixels.len() != expected {
+            return Err(TextureError::WrongPixelCount {
+                len: pixels.len(),
+                expected,
+                size,
+            });
+        }
+
+        let image = upload_image(size, format, &pixels)?;
+
+        Ok(Self {
+            image,
+            data: TextureData::Rgba8(pixels),
+            size,
+            format,
+            sampler,
+        })
+    }
+
+    /// Reads a KTX2 file holding a BC7, BC5, BC4 or 8-bit RGBA image.
+    ///
+    /// Block-compressed images are uploaded with all of their levels when the
+    /// device can sample BC formats and decoded to RGBA otherwise. Basis Universal
+    /// files can't be transcoded and are rejected
+    pub fn from_ktx2(bytes: &[u8], sampler: SamplerDesc) -> TextureResult<Self> {
+        let ktx2 = Ktx2Image::parse(bytes)?;
+        let size = ktx2.size;
+
+        type Decode = fn(UVec2, &[u8]) -> Vec<u8>;
+
+        let (block_size, decode, rgba_format): (usize, Decode, vk::Format) = match ktx2.format {
+            vk::Format::R8G8B8A8_UNORM | vk::Format::R8G8B8A8_SRGB => {
+                return Self::from_rgba8(size, ktx2.format, ktx2.levels[0].to_vec(), sampler);
+            }
+            vk::Format::BC7_UNORM_BLOCK => (16, bcn::decode_bc7, vk::Format::R8G8B8A8_UNORM),
+            vk::Format::BC7_SRGB_BLOCK => (16, bcn::decode_bc7, vk::Format::R8G8B8A8_SRGB),
+            vk::Format::BC5_UNORM_BLOCK => (16, bcn::decode_bc5, vk::Format::R8G8B8A8_UNORM),
+            vk::Format::BC4_UNORM_BLOCK => (8, bcn::decode_bc4, vk::Format::R8G8B8A8_UNORM),
+            format => return Err(TextureError::UnsupportedFormat(format)),
+        };
+
+        if ktx2.levels.len() as u32 > mip_level_count(size) {
+            return Err(TextureError::InvalidKtx2(
+                "more levels than the image size allows",
+            ));
+        }
+
+        let levels = ktx2
+            .levels
+            .iter()
+            .enumerate()
+            .map(|(level, data)| {
+                let size = mip_size(size, level as u32);
+                let expected = compressed_len(size, block_size);
+
+                if data.len() != expected {
+                    return Err(TextureError::WrongPixelCount {
+                        len: data.len(),
+                        expected,
+                        size,
+                    });
+                }
+
+                Ok(data.to_vec())
+            })
+            .collect::<TextureResult<Vec<_>>>()?;
+
+        if !get_device().texture_compression_bc {
+            core_warn!(
+                "The device can't sample BC textures, decoding a {:?} texture to RGBA",
+                ktx2.format
+            );
+
+            return Self::from_rgba8(size, rgba_format, decode(size, &levels[0]), sampler);
+        }
+
+        let image = upload_compressed_image(size, ktx2.format, &levels)?;
+
+        let texture = Self {
+            image,
+            data: TextureData::Compressed(levels),
+            size,
+            format: ktx2.format,
+            sampler,
+        };
+
+        texture.track_compression();
+
+        Ok(texture)
+    }
+
+    /// Reads and uploads a KTX2 file, see [`from_ktx2`](Self::from_ktx2)
+    pub fn load_ktx2(path: impl AsRef<Path>, sampler: SamplerDesc) -> TextureResult<Self> {
+        let path = path.as_ref();
+        let bytes = std::fs::read(path).map_err(|err| TextureError::Io(path.to_path_buf(), err))?;
+
+        Self::from_ktx2(&bytes, sampler)
+    }
+
+    /// 1x1 linear texture of a single color, used in place of unbound textures
+    pub fn solid(rgba: [u8; 4]) -> TextureResult<Self> {
+        Self::from_rgba8(
+            UVec2::new(1, 1),
+            vk::Format::R8G8B8A8_UNORM,
+            rgba.to_vec(),
+            SamplerDesc::default(),
+        )
+    }
+
+    pub fn size(&self) -> UVec2 {
+        self.size
+    }
+
+    pub fn format(&self) -> vk::Format {
+        self.format
+    }
+
+    pub fn sampler(&self) -> &SamplerDesc {
+        &self.sampler
+    }
+
+    pub(crate) fn image(&self) -> &VulkanImage {
+        &self.image
+    }
+
+    /// Destroys the image, the texture can't be sampled until it's restored
+    pub(crate) fn release(&mut self) {
+        self.destroy_image();
+    }
+
+    /// Uploads the pixels again on the current device
+    pub(crate) fn restore(&mut self) -> TextureResult<()> {
+        match &self.data {
+            TextureData::Rgba8(pixels) => {
+                self.image = upload_image(self.size, self.format, pixels)?;
+            }
+            TextureData::Compressed(levels) => {
+                self.image = upload_compressed_image(self.size, self.format, levels)?;
+                self.track_compression();
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Bytes of the compressed levels and what they would take as 8-bit RGBA,
+    /// `None` for uncompressed textures
+    fn compressed_bytes(&self) -> Option<(u64, u64)> {
+        let TextureData::Compressed(levels) = &self.data else {
+            return None;
+        };
+
+        let compressed = levels.iter().map(|level| level.len() as u64).sum();
+        let uncompressed = (0..levels.len() as u32)
+            .map(|level| mip_size(self.size, level))
+            .map(|size| size.x as u64 * size.y as u64 * 4)
+            .sum();
+
+        Some((compressed, uncompressed))
+    }
+
+    fn track_compression(&self) {
+        if let Some((compressed, uncompressed)) = self.compressed_bytes() {
+            track_compressed_texture(compressed, uncompressed);
+        }
+    }
+
+    fn destroy_image(&mut self) {
+        if self.image.image.is_null() {
+            return;
+        }
+
+        if let Some((compressed, uncompressed)) = self.compressed_bytes() {
+            track_compressed_texture_free(compressed, uncompressed);
+        }
+
+        self.image.destroy();
+    }
+}
+
+impl Drop for Texture {
+    fn drop(&mut self) {
+        self.destroy_image();
+    }
+}
+
+/// Size of the `level` mip of a `size` image
+fn mip_size(size: UVec2, level: u32) -> UVec2 {
+    size.map(|side| (side >> level).max(1))
+}
+
+/// Copies `pixels` into a new texture image and fills its mips on the graphics
+/// queue, blits aren't guaranteed on transfer queues. Blocks until it's done
+fn upload_image(size: UVec2, format: vk::Format, pixels: &[u8]) -> TextureResult<VulkanImage> {
+    let mut image = VulkanImage::texture_image(size, format)?;
+
+    submit_upload(&mut image, &[pixels], |image, cmd_buffer| {
+        image.generate_mipmaps(cmd_buffer)
+    })?;
+
+    Ok(image)
+}
+
+/// Copies every level of a block-compressed image into a new texture image,
+/// blocks until it's done
+fn upload_compressed_image(
+    size: UVec2,
+    format: vk::Format,
+    levels: &[Vec<u8>],
+) -> TextureResult<VulkanImage> {
+    let mut image = VulkanImage::compressed_texture_image(size, format, levels.len() as u32)?;
+    let levels = levels.iter().map(Vec::as_slice).collect::<Vec<_>>();
+
+    submit_upload(&mut image, &levels, |image, cmd_buffer| unsafe {
+        let barrier = image.image_barrier(
+            vk::PipelineStageFlags2::TRANSFER,
+            vk::AccessFlags2::TRANSFER_WRITE,
+            vk::PipelineStageFlags2::FRAGMENT_SHADER,
+            vk::AccessFlags2::SHADER_READ,
+            vk::ImageLayout::SHADER_READ_ONLY_OPTIMAL,
+        );
+
+        get_device().cmd_pipeline_barrier2(
+            cmd_buffer,
+            &vk::DependencyInfo::default().image_memory_barriers(&[barrier]),
+        );
+    })?;
+
+    Ok(image)
+}
+
+/// Copies `levels` into the mips of `image` from the largest one through a
+/// staging buffer and records `finish` with the image in `TRANSFER_DST_OPTIMAL`.
+/// Submitted to the graphics queue, blocks until it's done
+fn submit_upload(
+    image: &mut VulkanImage,
+    levels: &[&[u8]],
+    finish: impl FnOnce(&mut VulkanImage, vk::CommandBuffer),
+) -> TextureResult<()> {
+    let device = get_device();
+
+    let len = levels.iter().map(|level| level.len()).sum::<usize>();
+    let mut staging = GpuBuffer::staging_buffer(device, len as vk::DeviceSize)?;
+
+    let result = (|| -> TextureResult<()> {
+        let mut offset = 0;
+
+        let regions = levels
+            .iter()
+            .enumerate()
+            .map(|(level, data)| {
+                let size = mip_size(image.size, level as u32);
+                let region = vk::BufferImageCopy::default()
+                    .buffer_offset(offset as vk::DeviceSize)
+                    .image_subresource(vk::ImageSubresourceLayers {
+                        aspect_mask: vk::ImageAspectFlags::COLOR,
+                        mip_level: level as u32,
+                        base_array_layer: 0,
+                        layer_count: 1,
+                    })
+                    .image_extent(vk::Extent3D {
+                        width: size.x,
+                        height: size.y,
+                        depth: 1,
+                    });
+
+                offset += data.len();
+                region
+            })
+            .collect::<Vec<_>>();
+
+        staging
+            .map_as_slice::<u8>(0, len)?
+            .copy_from_slice(&levels.concat());
+        staging.flush_range(0, len as vk::DeviceSize)?;
+
+        unsafe {
+            let allocate_info = vk::CommandBufferAllocateInfo::default()
+                .command_pool(device.cmd_pool)
+                .level(vk::CommandBufferLevel::PRIMARY)
+                .command_buffer_count(1);
+
+            let cmd_buffer = device.allocate_command_buffers(&allocate_info)?[0];
+
+            let begin_info = vk::CommandBufferBeginInfo::default()
+                .flags(vk::CommandBufferUsageFlags::ONE_TIME_SUBMIT);
+
+            device.begin_command_buffer(cmd_buffer, &begin_info)?;
+
+            let barrier = image.image_barrier(
+                vk::PipelineStageFlags2::NONE,
+                vk::AccessFlags2::NONE,
+                vk::PipelineStageFlags2::TRANSFER,
+                vk::AccessFlags2::TRANSFER_WRITE,
+                vk::ImageLayout::TRANSFER_DST_OPTIMAL,
+            );
+
+            device.cmd_pipeline_barrier2(
+                cmd_buffer,
+                &vk::DependencyInfo::default().image_memory_barriers(&[barrier]),
+            );
+
+            device.cmd_copy_buffer_to_image(
+                cmd_buffer,
+                staging.buffer(),
+                image.image,
+                vk::ImageLayout::TRANSFER_DST_OPTIMAL,
+                &regions,
+            );
+
+            finish(image, cmd_buffer);
+
+            device.end_command_buffer(cmd_buffer)?;
+
+            let cmd_buffers = [cmd_buffer];
+            let submits = [vk::SubmitInfo::default().command_buffers(&cmd_buffers)];
+
+            let fence = device.create_fence(&vk::FenceCreateInfo::default(), None)?;
+
+            let submitted = device
+                .queue_submit(device.graphics_queue, &submits, fence)
+                .and_then(|_| device.wait_for_fences(&[fence], true, u64::MAX));
+
+            device.destroy_fence(fence, None);
+            device.free_command_buffers(device.cmd_pool, &cmd_buffers);
+
+            submitted?;
+        }
+
+        Ok(())
+    })();
+
+    staging.destroy(device);
+
+    result
+}
@@ -1,14 +1,17 @@
 use std::mem::offset_of;
 
 use ash::vk;
-use nalgebra_glm::Vec3;
+use nalgebra_glm::{Vec2, Vec3, Vec4};
 
 #[repr(C, align(4))]
-#[derive(Clone, Debug, Default)]
+#[derive(Clone, Copy, Debug, Default)]
 pub struct Vertex {
     pub position: Vec3,
     pub _pad0: f32,
     pub normal: Vec3,
+    pub uv: Vec2,
+    /// Tangent along `+U`, `w` is the sign of the bitangent `cross(normal, tangent.xyz) * w`
+    pub tangent: Vec4,
 }
 
 impl Vertex {
@@ -35,6 +38,18 @@ impl Vertex {
                 format: vk::Format::R32G32B32_SFLOAT,
                 offset: offset_of!(Vertex, normal) as u32,
             },
+            vk::VertexInputAttributeDescription {
+                binding: 0,
+                location: 2,
+                format: vk::Format::R32G32_SFLOAT,
+                offset: offset_of!(Vertex, uv) as u32,
+            },
+            vk::VertexInputAttributeDescription {
+                binding: 0,
+                location: 3,
+                format: vk::Format::R32G32B32A32_SFLOAT,
+                offset: offset_of!(Vertex, tangent) as u32,
+            },
         ]
     }
 }
@@ -1,36 +1,47 @@
-use std::sync::{LazyLock, OnceLock};
+use std::sync::{
+    atomic::{AtomicPtr, Ordering},
+    LazyLock, OnceLock,
+};
 
 use ash::vk::{
     self, native::StdVideoAV1TransferCharacteristics_STD_VIDEO_AV1_TRANSFER_CHARACTERISTICS_INVALID,
 };
 use bizarre_log::core_fatal;
 
-use crate::{device::LogicalDevice, instance::VulkanInstance};
+use crate::{
+    device::{logical_device::DeviceResult, LogicalDevice},
+    instance::VulkanInstance,
+    renderer::RendererCreateError,
+};
 
 static CTX: LazyLock<VulkanContext> = LazyLock::new(|| match VulkanContext::new() {
     Ok(ctx) => ctx,
     Err(err) => {
-        let message = format!("Failed to init Vulkan context {err:?}");
+        let message = format!("Failed to init Vulkan context: {err}");
         core_fatal!("{}", message);
         panic!("{}", message);
     }
 });
 
 pub struct VulkanContext {
-    device: LogicalDevice,
+    /// Boxed so a lost device can be swapped out with [`recreate_device`]
+    device: AtomicPtr<LogicalDevice>,
     instance: VulkanInstance,
 }
 
 impl VulkanContext {
-    pub fn new() -> Result<Self, vk::Result> {
-        let instance = VulkanInstance::new();
-        let device = LogicalDevice::new(&instance).unwrap();
+    pub fn new() -> Result<Self, RendererCreateError> {
+        let instance = VulkanInstance::new()?;
+        let device = LogicalDevice::new(&instance)?;
+        let device = AtomicPtr::new(Box::into_raw(Box::new(device)));
 
         Ok(Self { device, instance })
     }
 
     pub fn device(&self) -> &LogicalDevice {
-        &self.device
+        // SAFETY: the pointer always comes from `Box::into_raw` and is freed only by
+        // `recreate_device`, whose caller guarantees that no references to it are alive
+        unsafe { &*self.device.load(Ordering::Acquire) }
     }
 
     pub fn instance(&self) -> &VulkanInstance {
@@ -42,10 +53,34 @@ pub fn get_context() -> &'static VulkanContext {
     &CTX
 }
 
+impl Drop for VulkanContext {
+    fn drop(&mut self) {
+        drop(unsafe { Box::from_raw(*self.device.get_mut()) });
+    }
+}
+
 pub fn get_device() -> &'static LogicalDevice {
-    &CTX.device
+    CTX.device()
 }
 
 pub fn get_instance() -> &'static VulkanInstance {
     &CTX.instance
 }
+
+/// Replaces the current (lost) logical device with a new one created on the
+/// same instance. Surfaces are instance objects and stay valid
+///
+/// # Safety
+///
+/// Every object created with the current device must already be destroyed and
+/// no reference returned by [`get_device`] may be used after this call
+pub(crate) unsafe fn recreate_device() -> DeviceResult<()> {
+    let device = LogicalDevice::new(&CTX.instance)?;
+    let old = CTX
+        .device
+        .swap(Box::into_raw(Box::new(device)), Ordering::AcqRel);
+
+    drop(Box::from_raw(old));
+
+    Ok(())
+}
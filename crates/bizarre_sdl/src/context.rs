@@ -1,11 +1,18 @@
 use std::{
     cell::{LazyCell, OnceCell},
+    env,
     sync::{LazyLock, OnceLock},
     thread::{self, ThreadId},
 };
 
 use sdl::{EventSubsystem, Sdl, VideoSubsystem};
 
+use crate::window::{PlatformWindowError, PlatformWindowResult};
+
+/// Environment variable naming the SDL video driver to use, like `wayland` or
+/// `x11`. Without it the driver is picked from the display servers that are running
+pub const WINDOW_BACKEND_ENV: &str = "BE_WINDOW_BACKEND";
+
 static INIT_THREAD_ID: OnceLock<ThreadId> = OnceLock::new();
 
 thread_local! {
@@ -24,14 +31,33 @@ pub fn with_sdl_context<R, F: FnOnce(&Sdl) -> R>(f: F) -> R {
     })
 }
 
+/// Panics if no video driver could be initialized, see [`try_init_video`]
 pub fn with_sdl_video<R, F: FnOnce(&VideoSubsystem) -> R>(f: F) -> R {
     SDL_VIDEO.with(|cell| {
-        let ctx = cell.get_or_init(init_video);
+        let ctx = cell.get_or_init(|| {
+            init_video()
+                .unwrap_or_else(|err| panic!("Failed to initialize SDL video subsystem: {err}"))
+        });
         panic_on_wrong_thread();
         f(ctx)
     })
 }
 
+/// Initializes the video subsystem if it isn't yet, so a missing display server
+/// is reported instead of panicking
+pub fn try_init_video() -> PlatformWindowResult<()> {
+    if is_video_initialized() {
+        return Ok(());
+    }
+
+    let video = init_video()?;
+    SDL_VIDEO.with(|cell| {
+        let _ = cell.set(video);
+    });
+
+    Ok(())
+}
+
 pub fn with_sdl_events<R, F: FnOnce(&EventSubsystem) -> R>(f: F) -> R {
     SDL_EVENTS.with(|cell| {
         let ctx = cell.get_or_init(init_events);
@@ -40,6 +66,11 @@ pub fn with_sdl_events<R, F: FnOnce(&EventSubsystem) -> R>(f: F) -> R {
     })
 }
 
+/// Whether the video subsystem has already read its environment, see [`with_sdl_video`]
+pub(crate) fn is_video_initialized() -> bool {
+    SDL_VIDEO.with(|cell| cell.get().is_some())
+}
+
 fn panic_on_wrong_thread() {
     let Some(thread_id) = INIT_THREAD_ID.get().copied() else {
         panic!("SDL is not initialized");
@@ -61,9 +92,56 @@ fn init_sdl() -> sdl::Sdl {
     sdl::init().unwrap_or_else(|err| panic!("Could not initialize SDL context: {err}"))
 }
 
-fn init_video() -> sdl::VideoSubsystem {
-    with_sdl_context(|sdl| sdl.video())
-        .unwrap_or_else(|err| panic!("Failed to initialize SDL video subsystem: {err}"))
+/// Tries the drivers from [`video_drivers`] in order and keeps the first one that
+/// connects to its display server
+fn init_video() -> PlatformWindowResult<sdl::VideoSubsystem> {
+    let drivers = video_drivers()?;
+
+    if drivers.is_empty() {
+        return with_sdl_context(|sdl| sdl.video()).map_err(PlatformWindowError::NoDisplayServer);
+    }
+
+    let mut errors = Vec::new();
+
+    for driver in drivers {
+        sdl::hint::set("SDL_VIDEODRIVER", &driver);
+
+        match with_sdl_context(|sdl| sdl.video()) {
+            Ok(video) => return Ok(video),
+            Err(err) => errors.push(format!("`{driver}`: {err}")),
+        }
+    }
+
+    Err(PlatformWindowError::NoDisplayServer(errors.join(", ")))
+}
+
+/// Video drivers to try, empty if the choice is left to SDL.
+///
+/// [`WINDOW_BACKEND_ENV`] and then `SDL_VIDEODRIVER` take precedence, on Linux
+/// Wayland is preferred over X11 when both are running
+fn video_drivers() -> PlatformWindowResult<Vec<String>> {
+    if let Ok(backend) = env::var(WINDOW_BACKEND_ENV) {
+        return Ok(vec![backend.to_lowercase()]);
+    }
+
+    if !cfg!(target_os = "linux") || env::var_os("SDL_VIDEODRIVER").is_some() {
+        return Ok(Vec::new());
+    }
+
+    let drivers = [("WAYLAND_DISPLAY", "wayland"), ("DISPLAY", "x11")]
+        .into_iter()
+        .filter(|(display, _)| env::var_os(display).is_some_and(|display| !display.is_empty()))
+        .map(|(_, driver)| driver.to_string())
+        .collect::<Vec<_>>();
+
+    if drivers.is_empty() {
+        return Err(PlatformWindowError::NoDisplayServer(format!(
+            "neither `WAYLAND_DISPLAY` nor `DISPLAY` is set, set `{WINDOW_BACKEND_ENV}` to pick \
+             a video driver"
+        )));
+    }
+
+    Ok(drivers)
 }
 
 fn init_events() -> sdl::EventSubsystem {
@@ -1,8 +1,14 @@
+use std::time::Duration;
+
 use nalgebra_glm::IVec2;
 use nalgebra_glm::Vec2;
 
+use super::current_mouse_mode;
+use super::keyboard_focus;
+use super::mouse_focus;
 use super::Keymod;
 use super::MouseButton;
+use super::MouseMode;
 use super::Scancode;
 use crate::window::WindowHandle;
 
@@ -14,11 +20,17 @@ pub enum InputEvent {
         window: WindowHandle,
         scancode: Scancode,
         keymod: Keymod,
+        /// The key is held down and this is a repeated press
+        repeat: bool,
+        /// Time since SDL initialization
+        timestamp: Duration,
     },
     KeyReleased {
         window: WindowHandle,
         scancode: Scancode,
         keymod: Keymod,
+        /// Time since SDL initialization
+        timestamp: Duration,
     },
     MouseButtonPressed {
         window: WindowHandle,
@@ -37,38 +49,75 @@ pub enum InputEvent {
     },
     MouseMoved {
         window: WindowHandle,
+        /// Meaningless in [`MouseMode::Relative`], some drivers report zeros
         pos: IVec2,
+        /// Relative motion, keeps being reported while the mouse is grabbed
+        delta: IVec2,
     },
     MouseScrolled {
         window: WindowHandle,
         scroll_delta: Vec2,
     },
+    /// The mouse was grabbed or released, sent to the window with the mouse focus
+    MouseModeChanged {
+        window: WindowHandle,
+        mode: MouseMode,
+    },
 }
 
 impl InputEvent {
+    /// Window the event was sent to
+    pub fn window(&self) -> WindowHandle {
+        match self {
+            InputEvent::KeyPressed { window, .. }
+            | InputEvent::KeyReleased { window, .. }
+            | InputEvent::MouseButtonPressed { window, .. }
+            | InputEvent::MouseDoubleClick { window, .. }
+            | InputEvent::MouseButtonReleased { window, .. }
+            | InputEvent::MouseMoved { window, .. }
+            | InputEvent::MouseScrolled { window, .. }
+            | InputEvent::MouseModeChanged { window, .. } => *window,
+        }
+    }
+
+    /// [`InputEvent::MouseModeChanged`] if SDL is not in the `previous` mouse mode anymore
+    pub fn mouse_mode_change(previous: MouseMode) -> Option<InputEvent> {
+        let mode = current_mouse_mode();
+
+        (mode != previous).then(|| InputEvent::MouseModeChanged {
+            window: mouse_target(0),
+            mode,
+        })
+    }
+
     pub fn try_from_sdl(event: &SdlEvent) -> Option<InputEvent> {
         match event {
             SdlEvent::KeyDown {
+                timestamp,
                 window_id,
                 scancode,
                 keymod,
                 repeat,
                 ..
-            } if !repeat => Some(InputEvent::KeyPressed {
-                window: WindowHandle::from_raw(*window_id as usize),
+            } => Some(InputEvent::KeyPressed {
+                window: keyboard_target(*window_id),
                 scancode: *scancode.as_ref()?,
                 keymod: *keymod,
+                repeat: *repeat,
+                timestamp: Duration::from_millis(*timestamp as u64),
             }),
             SdlEvent::KeyUp {
+                timestamp,
                 window_id,
                 scancode,
                 keymod,
                 repeat,
                 ..
             } if !repeat => Some(InputEvent::KeyReleased {
-                window: WindowHandle::from_raw(*window_id as usize),
+                window: keyboard_target(*window_id),
                 scancode: *scancode.as_ref()?,
                 keymod: *keymod,
+                timestamp: Duration::from_millis(*timestamp as u64),
             }),
             SdlEvent::MouseButtonDown {
                 window_id,
@@ -77,7 +126,7 @@ impl InputEvent {
                 y,
                 ..
             } => Some(InputEvent::MouseButtonPressed {
-                window: WindowHandle::from_raw(*window_id as usize),
+                window: mouse_target(*window_id),
                 button: *mouse_btn,
                 pos: IVec2::new(*x, *y),
             }),
@@ -88,15 +137,21 @@ impl InputEvent {
                 y,
                 ..
             } => Some(InputEvent::MouseButtonReleased {
-                window: WindowHandle::from_raw(*window_id as usize),
+                window: mouse_target(*window_id),
                 button: *mouse_btn,
                 pos: IVec2::new(*x, *y),
             }),
             SdlEvent::MouseMotion {
-                window_id, x, y, ..
+                window_id,
+                x,
+                y,
+                xrel,
+                yrel,
+                ..
             } => Some(InputEvent::MouseMoved {
                 pos: IVec2::new(*x, *y),
-                window: WindowHandle::from_raw(*window_id as usize),
+                delta: IVec2::new(*xrel, *yrel),
+                window: mouse_target(*window_id),
             }),
             SdlEvent::MouseWheel {
                 window_id,
@@ -104,10 +159,29 @@ impl InputEvent {
                 precise_y,
                 ..
             } => Some(InputEvent::MouseScrolled {
-                window: WindowHandle::from_raw(*window_id as usize),
+                window: mouse_target(*window_id),
                 scroll_delta: Vec2::new(*precise_x, *precise_y),
             }),
             _ => None,
         }
     }
 }
+
+// Some video drivers send input without a window id, e.g. Wayland while the mouse is grabbed,
+// such events go to the focused window instead
+
+fn keyboard_target(window_id: u32) -> WindowHandle {
+    match window_id {
+        0 => keyboard_focus().unwrap_or(WindowHandle::from_raw(0usize)),
+        id => WindowHandle::from_raw(id as usize),
+    }
+}
+
+fn mouse_target(window_id: u32) -> WindowHandle {
+    match window_id {
+        0 => mouse_focus()
+            .or_else(keyboard_focus)
+            .unwrap_or(WindowHandle::from_raw(0usize)),
+        id => WindowHandle::from_raw(id as usize),
+    }
+}
@@ -0,0 +1,141 @@
+use std::time::Duration;
+
+use bizarre_ecs::prelude::*;
+
+use crate::window::WindowHandle;
+
+use super::{InputEvent, Keymod, Scancode};
+
+/// Timing of synthesized key repeats
+#[derive(Clone, Copy, Debug)]
+pub struct KeyRepeat {
+    /// Time a key has to be held before it starts repeating
+    pub delay: Duration,
+    /// Time between two repeats
+    pub interval: Duration,
+}
+
+impl Default for KeyRepeat {
+    fn default() -> Self {
+        Self {
+            delay: Duration::from_millis(500),
+            interval: Duration::from_millis(33),
+        }
+    }
+}
+
+struct HeldKey {
+    window: WindowHandle,
+    scancode: Scancode,
+    keymod: Keymod,
+    next_repeat: Duration,
+}
+
+/// Generates repeated `KeyPressed` events for held keys on backends that don't deliver
+/// repeats. Synthesis stops as soon as the backend sends a repeat on its own
+#[derive(Resource, Default)]
+pub struct KeyRepeatSynthesizer {
+    key_repeat: Option<KeyRepeat>,
+    held_keys: Vec<HeldKey>,
+    native_repeats: bool,
+}
+
+impl KeyRepeatSynthesizer {
+    /// Nothing is synthesized while `key_repeat` is `None`
+    pub fn new(key_repeat: Option<KeyRepeat>) -> Self {
+        Self {
+            key_repeat,
+            held_keys: Vec::new(),
+            native_repeats: false,
+        }
+    }
+
+    pub fn key_repeat(&self) -> Option<KeyRepeat> {
+        self.key_repeat
+    }
+
+    pub fn set_key_repeat(&mut self, key_repeat: Option<KeyRepeat>) {
+        self.key_repeat = key_repeat;
+        self.held_keys.clear();
+    }
+
+    /// Whether the backend was seen delivering repeats, nothing is synthesized then
+    pub fn has_native_repeats(&self) -> bool {
+        self.native_repeats
+    }
+
+    /// Tracks held keys from the events received from the backend
+    pub fn observe(&mut self, event: &InputEvent) {
+        match event {
+            InputEvent::KeyPressed { repeat: true, .. } => {
+                self.native_repeats = true;
+                self.held_keys.clear();
+            }
+            InputEvent::KeyPressed {
+                window,
+                scancode,
+                keymod,
+                timestamp,
+                ..
+            } if !self.native_repeats => {
+                let Some(key_repeat) = self.key_repeat else {
+                    return;
+                };
+
+                self.held_keys.retain(|key| key.scancode != *scancode);
+                self.held_keys.push(HeldKey {
+                    window: *window,
+                    scancode: *scancode,
+                    keymod: *keymod,
+                    next_repeat: *timestamp + key_repeat.delay,
+                });
+            }
+            InputEvent::KeyReleased { scancode, .. } => {
+                self.held_keys.retain(|key| key.scancode != *scancode)
+            }
+            _ => (),
+        }
+    }
+
+    /// Repeats of the held keys that are due at `now`, the time since SDL initialization
+    pub fn synthesize(&mut self, now: Duration) -> Vec<InputEvent> {
+        let Some(key_repeat) = self.key_repeat else {
+            return Vec::new();
+        };
+
+        let mut events = Vec::new();
+        // Zero interval would never catch up
+        let interval = key_repeat.interval.max(Duration::from_millis(1));
+
+        for key in &mut self.held_keys {
+            while key.next_repeat <= now {
+                events.push(InputEvent::KeyPressed {
+                    window: key.window,
+                    scancode: key.scancode,
+                    keymod: key.keymod,
+                    repeat: true,
+                    timestamp: key.next_repeat,
+                });
+
+                key.next_repeat += interval;
+            }
+        }
+
+        events.sort_by_key(|event| match event {
+            InputEvent::KeyPressed { timestamp, .. } => *timestamp,
+            _ => Duration::ZERO,
+        });
+
+        events
+    }
+
+    /// Forgets held keys, e.g. when the keyboard focus was lost
+    pub fn reset(&mut self) {
+        self.held_keys.clear();
+    }
+}
+
+/// Time since SDL initialization in the clock used by event timestamps
+pub fn ticks() -> Duration {
+    Duration::from_millis(unsafe { sdl::sys::SDL_GetTicks64() })
+}
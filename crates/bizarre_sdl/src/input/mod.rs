@@ -1,3 +1,5 @@
+use std::collections::BTreeMap;
+
 use nalgebra_glm::IVec2;
 use nalgebra_glm::Vec2;
 
@@ -5,13 +7,44 @@ use bizarre_core::bit_buffer::BitBuffer;
 use bizarre_ecs::prelude::*;
 
 pub use input_event::InputEvent;
+pub use key_repeat::{ticks, KeyRepeat, KeyRepeatSynthesizer};
 pub use sdl::keyboard::Mod as Keymod;
 pub use sdl::keyboard::Scancode;
 pub use sdl::mouse::MouseButton;
 
-use crate::context::with_sdl_context;
+use crate::context::{with_sdl_context, with_sdl_video};
+use crate::window::{WindowEvent, WindowHandle};
 
 mod input_event;
+mod key_repeat;
+
+/// How mouse movement is reported
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum MouseMode {
+    /// The cursor moves freely, positions follow it
+    #[default]
+    Absolute,
+    /// The mouse is grabbed and only reports movement, positions stay at the
+    /// last absolute one
+    Relative,
+}
+
+/// Mouse mode SDL is in, relative while a window grabs the mouse
+pub fn current_mouse_mode() -> MouseMode {
+    if with_sdl_context(|sdl| sdl.mouse().relative_mouse_mode()) {
+        MouseMode::Relative
+    } else {
+        MouseMode::Absolute
+    }
+}
+
+/// Mouse state of a single window, positions are relative to the window
+#[derive(Default, Clone, Copy)]
+struct WindowMouseState {
+    position: IVec2,
+    delta: IVec2,
+    scroll_delta: Vec2,
+}
 
 #[derive(Resource)]
 pub struct InputState {
@@ -21,8 +54,12 @@ pub struct InputState {
     prev_mouse_state: BitBuffer,
     mouse_state: BitBuffer,
     mouse_position: IVec2,
-    prev_mouse_position: IVec2,
+    mouse_delta: IVec2,
     mouse_scroll_delta: Vec2,
+    mouse_mode: MouseMode,
+    keyboard_focus: Option<WindowHandle>,
+    mouse_focus: Option<WindowHandle>,
+    window_mouse: BTreeMap<WindowHandle, WindowMouseState>,
 }
 
 impl InputState {
@@ -60,11 +97,32 @@ impl InputState {
             prev_mouse_state: BitBuffer::new(mouse_state.width()),
             mouse_state,
             mouse_position,
-            prev_mouse_position: mouse_position,
+            mouse_delta: IVec2::zeros(),
             mouse_scroll_delta: Vec2::zeros(),
+            mouse_mode: current_mouse_mode(),
+            keyboard_focus: keyboard_focus(),
+            mouse_focus: mouse_focus(),
+            window_mouse: BTreeMap::new(),
+        }
+    }
+
+    /// Input as seen by `handle`, keys and buttons are only reported while the window has focus
+    pub fn for_window(&self, handle: WindowHandle) -> WindowInputState<'_> {
+        WindowInputState {
+            input: self,
+            handle,
+            mouse: self.window_mouse.get(&handle).copied().unwrap_or_default(),
         }
     }
 
+    pub fn keyboard_focus(&self) -> Option<WindowHandle> {
+        self.keyboard_focus
+    }
+
+    pub fn mouse_focus(&self) -> Option<WindowHandle> {
+        self.mouse_focus
+    }
+
     pub fn was_key_pressed(&self, scancode: Scancode) -> bool {
         self.prev_keyboard_state.get(scancode as usize).unwrap()
     }
@@ -96,21 +154,36 @@ impl InputState {
         self.keymod
     }
 
+    /// Last known absolute mouse position, kept in place while the mouse is in
+    /// [`MouseMode::Relative`]
     pub fn mouse_position(&self) -> IVec2 {
         self.mouse_position
     }
 
+    /// Mouse movement since the last frame, reported in both mouse modes
     pub fn mouse_delta(&self) -> IVec2 {
-        self.mouse_position - self.prev_mouse_position
+        self.mouse_delta
+    }
+
+    pub fn mouse_mode(&self) -> MouseMode {
+        self.mouse_mode
+    }
+
+    /// Mouse wheel movement since the last frame
+    pub fn mouse_scroll_delta(&self) -> Vec2 {
+        self.mouse_scroll_delta
     }
 
     pub fn process_event(&mut self, event: InputEvent) {
+        let window = event.window();
+
         match event {
             InputEvent::KeyPressed {
                 scancode, keymod, ..
             } => {
                 self.keyboard_state.set(scancode as usize, true);
                 self.keymod = keymod;
+                self.keyboard_focus = Some(window);
             }
             InputEvent::KeyReleased {
                 scancode, keymod, ..
@@ -118,15 +191,64 @@ impl InputState {
                 self.keyboard_state.set(scancode as usize, false);
                 self.keymod = keymod;
             }
-            InputEvent::MouseButtonPressed { button, .. } => {
-                self.mouse_state.set(button as usize, true)
+            InputEvent::MouseButtonPressed { button, pos, .. } => {
+                self.mouse_state.set(button as usize, true);
+                self.mouse_focus = Some(window);
+                self.set_window_mouse_position(window, pos);
+            }
+            InputEvent::MouseButtonReleased { button, pos, .. } => {
+                self.mouse_state.set(button as usize, false);
+                self.set_window_mouse_position(window, pos);
             }
-            InputEvent::MouseButtonReleased { button, .. } => {
-                self.mouse_state.set(button as usize, false)
+            InputEvent::MouseMoved { pos, delta, .. } => {
+                if self.mouse_mode == MouseMode::Absolute {
+                    self.mouse_position = pos;
+                }
+                self.mouse_delta += delta;
+
+                self.set_window_mouse_position(window, pos);
+                self.window_mouse.entry(window).or_default().delta += delta;
             }
-            InputEvent::MouseMoved { pos, .. } => self.mouse_position = pos,
+            InputEvent::MouseModeChanged { mode, .. } => self.mouse_mode = mode,
             InputEvent::MouseScrolled { scroll_delta, .. } => {
-                self.mouse_scroll_delta += scroll_delta
+                self.mouse_scroll_delta += scroll_delta;
+                self.window_mouse.entry(window).or_default().scroll_delta += scroll_delta;
+            }
+            _ => (),
+        }
+    }
+
+    /// Positions reported in [`MouseMode::Relative`] are not where the cursor is
+    /// and are dropped
+    fn set_window_mouse_position(&mut self, window: WindowHandle, pos: IVec2) {
+        if self.mouse_mode == MouseMode::Absolute {
+            self.window_mouse.entry(window).or_default().position = pos;
+        }
+    }
+
+    /// Tracks focus changes and forgets closed windows
+    pub fn process_window_event(&mut self, event: &WindowEvent) {
+        match event {
+            WindowEvent::KeyboardFocusGained(handle) => self.keyboard_focus = Some(*handle),
+            WindowEvent::KeyboardFocusLost(handle) => {
+                if self.keyboard_focus == Some(*handle) {
+                    self.keyboard_focus = None
+                }
+            }
+            WindowEvent::MouseEnter(handle) => self.mouse_focus = Some(*handle),
+            WindowEvent::MouseLeave(handle) => {
+                if self.mouse_focus == Some(*handle) {
+                    self.mouse_focus = None
+                }
+            }
+            WindowEvent::WindowMustClose(handle) | WindowEvent::MainWindowMustClose(handle) => {
+                self.window_mouse.remove(handle);
+                if self.keyboard_focus == Some(*handle) {
+                    self.keyboard_focus = None
+                }
+                if self.mouse_focus == Some(*handle) {
+                    self.mouse_focus = None
+                }
             }
             _ => (),
         }
@@ -135,7 +257,105 @@ impl InputState {
     pub fn swap_frames(&mut self) {
         self.prev_keyboard_state.copy_from(&self.keyboard_state);
         self.prev_mouse_state.copy_from(&self.mouse_state);
-        self.prev_mouse_position = self.mouse_position;
+        self.mouse_delta = IVec2::zeros();
         self.mouse_scroll_delta = Vec2::zeros();
+
+        for mouse in self.window_mouse.values_mut() {
+            mouse.delta = IVec2::zeros();
+            mouse.scroll_delta = Vec2::zeros();
+        }
+    }
+}
+
+/// View of [`InputState`] limited to a single window
+pub struct WindowInputState<'a> {
+    input: &'a InputState,
+    handle: WindowHandle,
+    mouse: WindowMouseState,
+}
+
+impl WindowInputState<'_> {
+    pub fn handle(&self) -> WindowHandle {
+        self.handle
+    }
+
+    pub fn has_keyboard_focus(&self) -> bool {
+        self.input.keyboard_focus == Some(self.handle)
+    }
+
+    pub fn has_mouse_focus(&self) -> bool {
+        self.input.mouse_focus == Some(self.handle)
+    }
+
+    pub fn was_key_pressed(&self, scancode: Scancode) -> bool {
+        self.has_keyboard_focus() && self.input.was_key_pressed(scancode)
+    }
+
+    pub fn was_key_just_pressed(&self, scancode: Scancode) -> bool {
+        self.has_keyboard_focus() && self.input.was_key_just_pressed(scancode)
+    }
+
+    pub fn is_key_pressed(&self, scancode: Scancode) -> bool {
+        self.has_keyboard_focus() && self.input.is_key_pressed(scancode)
+    }
+
+    pub fn pressed_keys(&self) -> impl Iterator<Item = Scancode> + '_ {
+        self.input
+            .pressed_keys()
+            .filter(|_| self.has_keyboard_focus())
+    }
+
+    pub fn is_mouse_pressed(&self, button: MouseButton) -> bool {
+        self.has_mouse_focus() && self.input.is_mouse_pressed(button)
+    }
+
+    pub fn was_mouse_pressed(&self, button: MouseButton) -> bool {
+        self.has_mouse_focus() && self.input.was_mouse_pressed(button)
+    }
+
+    pub fn keymod(&self) -> Keymod {
+        if self.has_keyboard_focus() {
+            self.input.keymod
+        } else {
+            Keymod::empty()
+        }
+    }
+
+    /// Last known absolute mouse position inside the window
+    pub fn mouse_position(&self) -> IVec2 {
+        self.mouse.position
+    }
+
+    pub fn mouse_mode(&self) -> MouseMode {
+        self.input.mouse_mode
+    }
+
+    pub fn mouse_delta(&self) -> IVec2 {
+        self.mouse.delta
+    }
+
+    pub fn mouse_scroll_delta(&self) -> Vec2 {
+        self.mouse.scroll_delta
+    }
+}
+
+/// Window with the keyboard focus according to SDL
+pub(crate) fn keyboard_focus() -> Option<WindowHandle> {
+    with_sdl_video(|_| unsafe { focused_window(sdl::sys::SDL_GetKeyboardFocus()) })
+}
+
+/// Window with the mouse focus according to SDL
+pub(crate) fn mouse_focus() -> Option<WindowHandle> {
+    with_sdl_video(|_| unsafe { focused_window(sdl::sys::SDL_GetMouseFocus()) })
+}
+
+unsafe fn focused_window(window: *mut sdl::sys::SDL_Window) -> Option<WindowHandle> {
+    if window.is_null() {
+        return None;
+    }
+
+    match unsafe { sdl::sys::SDL_GetWindowID(window) } {
+        0 => None,
+        id => Some(WindowHandle::from_raw(id as usize)),
     }
 }
@@ -1,4 +1,6 @@
 pub mod input;
+pub mod raw_event;
+pub mod replay;
 pub mod window;
 
 pub extern crate sdl2 as sdl;
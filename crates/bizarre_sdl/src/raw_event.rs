@@ -0,0 +1,76 @@
+use std::sync::mpsc::{self, Receiver, Sender};
+
+use bizarre_ecs::{prelude::*, world::World};
+
+use crate::sdl::event::Event as SdlEvent;
+
+type RawEventFilter = Box<dyn Fn(&SdlEvent) -> bool>;
+type RawEventCallback = Box<dyn FnMut(&SdlEvent)>;
+/// Returns `false` once the subscription is gone
+type RawEventSubscription = Box<dyn FnMut(&SdlEvent) -> bool>;
+
+/// Raw SDL events for the events the sdl module doesn't convert, like joystick
+/// devices or custom user events.
+///
+/// Filters run first and drop the event for everyone, callbacks and subscriptions
+/// see every event that passed the filters before it's converted
+#[derive(Resource, Default)]
+pub struct SdlEventHooks {
+    filters: Vec<RawEventFilter>,
+    callbacks: Vec<RawEventCallback>,
+    subscriptions: Vec<RawEventSubscription>,
+}
+
+impl SdlEventHooks {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Hooks of the world, inserted if the sdl module wasn't applied yet
+    pub fn of_world(world: &mut World) -> &mut Self {
+        if world.resource::<Self>().is_none() {
+            world.insert_resource(Self::new());
+        }
+
+        world.resource_mut::<Self>().unwrap()
+    }
+
+    /// Drops the events `filter` returns `false` for
+    pub fn add_filter(&mut self, filter: impl Fn(&SdlEvent) -> bool + 'static) {
+        self.filters.push(Box::new(filter));
+    }
+
+    pub fn add_callback(&mut self, callback: impl FnMut(&SdlEvent) + 'static) {
+        self.callbacks.push(Box::new(callback));
+    }
+
+    /// Sends the events `map` converts into the returned channel, the subscription
+    /// is removed when the receiver is dropped
+    pub fn subscribe<E: Send + 'static>(
+        &mut self,
+        mut map: impl FnMut(&SdlEvent) -> Option<E> + 'static,
+    ) -> Receiver<E> {
+        let (sender, receiver): (Sender<E>, _) = mpsc::channel();
+
+        self.subscriptions
+            .push(Box::new(move |event| match map(event) {
+                Some(event) => sender.send(event).is_ok(),
+                None => true,
+            }));
+
+        receiver
+    }
+
+    pub fn is_allowed(&self, event: &SdlEvent) -> bool {
+        self.filters.iter().all(|filter| filter(event))
+    }
+
+    /// Runs the callbacks and subscriptions for `event`
+    pub fn dispatch(&mut self, event: &SdlEvent) {
+        self.callbacks
+            .iter_mut()
+            .for_each(|callback| callback(event));
+        self.subscriptions
+            .retain_mut(|subscription| subscription(event));
+    }
+}
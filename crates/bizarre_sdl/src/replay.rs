@@ -0,0 +1,413 @@
+use std::{
+    collections::VecDeque,
+    fs::File,
+    io::{self, BufRead, BufReader, BufWriter, Write},
+    path::Path,
+    str::{FromStr, SplitWhitespace},
+    time::Duration,
+};
+
+use bizarre_ecs::prelude::*;
+use nalgebra_glm::{IVec2, UVec2, Vec2};
+use thiserror::Error;
+
+use crate::{
+    input::{InputEvent, Keymod, MouseButton, MouseMode, Scancode},
+    window::{WindowEvent, WindowHandle},
+};
+
+#[derive(Debug, Error)]
+pub enum ReplayError {
+    #[error(transparent)]
+    Io(#[from] io::Error),
+    #[error("Malformed event at line {line}: {reason}")]
+    Malformed { line: usize, reason: String },
+}
+
+pub type ReplayResult<T> = Result<T, ReplayError>;
+
+/// An event that goes into a recording
+#[derive(Clone, Debug)]
+pub enum RecordedEvent {
+    Input(InputEvent),
+    Window(WindowEvent),
+}
+
+/// Writes events with their frame indices into a file, one event per line
+pub struct EventRecorder {
+    writer: BufWriter<File>,
+}
+
+impl EventRecorder {
+    pub fn create(path: impl AsRef<Path>) -> io::Result<Self> {
+        Ok(Self {
+            writer: BufWriter::new(File::create(path)?),
+        })
+    }
+
+    pub fn record(&mut self, frame: u64, event: &RecordedEvent) -> io::Result<()> {
+        writeln!(self.writer, "{frame} {}", encode_event(event))
+    }
+
+    pub fn flush(&mut self) -> io::Result<()> {
+        self.writer.flush()
+    }
+}
+
+/// Events of a recording, handed out frame by frame
+pub struct EventReplay {
+    events: VecDeque<(u64, RecordedEvent)>,
+}
+
+impl EventReplay {
+    pub fn open(path: impl AsRef<Path>) -> ReplayResult<Self> {
+        let reader = BufReader::new(File::open(path)?);
+        let mut events = VecDeque::new();
+
+        for (index, line) in reader.lines().enumerate() {
+            let line = line?;
+
+            if line.trim().is_empty() {
+                continue;
+            }
+
+            let event = decode_line(&line).map_err(|reason| ReplayError::Malformed {
+                line: index + 1,
+                reason,
+            })?;
+
+            events.push_back(event);
+        }
+
+        Ok(Self { events })
+    }
+
+    /// Takes the events recorded up to and including `frame`
+    pub fn take_frame(&mut self, frame: u64) -> Vec<RecordedEvent> {
+        let count = self
+            .events
+            .iter()
+            .take_while(|(event_frame, _)| *event_frame <= frame)
+            .count();
+
+        self.events.drain(..count).map(|(_, event)| event).collect()
+    }
+
+    pub fn is_finished(&self) -> bool {
+        self.events.is_empty()
+    }
+}
+
+#[derive(Default)]
+enum CaptureMode {
+    #[default]
+    Off,
+    Record(EventRecorder),
+    Replay(EventReplay),
+}
+
+/// Records the events of every frame or replays a recording instead of the live events
+#[derive(Resource, Default)]
+pub struct EventCapture {
+    frame: u64,
+    mode: CaptureMode,
+}
+
+impl EventCapture {
+    pub fn recording(recorder: EventRecorder) -> Self {
+        Self {
+            frame: 0,
+            mode: CaptureMode::Record(recorder),
+        }
+    }
+
+    pub fn replaying(replay: EventReplay) -> Self {
+        Self {
+            frame: 0,
+            mode: CaptureMode::Replay(replay),
+        }
+    }
+
+    pub fn frame(&self) -> u64 {
+        self.frame
+    }
+
+    pub fn is_recording(&self) -> bool {
+        matches!(self.mode, CaptureMode::Record(_))
+    }
+
+    pub fn is_replaying(&self) -> bool {
+        matches!(self.mode, CaptureMode::Replay(_))
+    }
+
+    /// Records `event` for the current frame when recording
+    pub fn record(&mut self, event: &RecordedEvent) -> io::Result<()> {
+        match &mut self.mode {
+            CaptureMode::Record(recorder) => recorder.record(self.frame, event),
+            _ => Ok(()),
+        }
+    }
+
+    /// Recorded events of the current frame when replaying
+    pub fn replayed_events(&mut self) -> Vec<RecordedEvent> {
+        match &mut self.mode {
+            CaptureMode::Replay(replay) => replay.take_frame(self.frame),
+            _ => Vec::new(),
+        }
+    }
+
+    pub fn next_frame(&mut self) -> io::Result<()> {
+        self.frame += 1;
+
+        match &mut self.mode {
+            CaptureMode::Record(recorder) => recorder.flush(),
+            _ => Ok(()),
+        }
+    }
+}
+
+fn encode_event(event: &RecordedEvent) -> String {
+    let handle = |handle: &WindowHandle| handle.as_raw();
+
+    match event {
+        RecordedEvent::Input(event) => match event {
+            InputEvent::KeyPressed {
+                window,
+                scancode,
+                keymod,
+                repeat,
+                timestamp,
+            } => format!(
+                "key_pressed {} {} {} {} {}",
+                handle(window),
+                *scancode as i32,
+                keymod.bits(),
+                *repeat as u8,
+                timestamp.as_millis()
+            ),
+            InputEvent::KeyReleased {
+                window,
+                scancode,
+                keymod,
+                timestamp,
+            } => format!(
+                "key_released {} {} {} {}",
+                handle(window),
+                *scancode as i32,
+                keymod.bits(),
+                timestamp.as_millis()
+            ),
+            InputEvent::MouseButtonPressed {
+                window,
+                button,
+                pos,
+            } => format!(
+                "mouse_pressed {} {} {} {}",
+                handle(window),
+                *button as u8,
+                pos.x,
+                pos.y
+            ),
+            InputEvent::MouseDoubleClick {
+                window,
+                button,
+                pos,
+            } => format!(
+                "mouse_double_click {} {} {} {}",
+                handle(window),
+                *button as u8,
+                pos.x,
+                pos.y
+            ),
+            InputEvent::MouseButtonReleased {
+                window,
+                button,
+                pos,
+            } => format!(
+                "mouse_released {} {} {} {}",
+                handle(window),
+                *button as u8,
+                pos.x,
+                pos.y
+            ),
+            InputEvent::MouseMoved { window, pos, delta } => format!(
+                "mouse_moved {} {} {} {} {}",
+                handle(window),
+                pos.x,
+                pos.y,
+                delta.x,
+                delta.y
+            ),
+            InputEvent::MouseScrolled {
+                window,
+                scroll_delta,
+            } => format!(
+                "mouse_scrolled {} {} {}",
+                handle(window),
+                scroll_delta.x,
+                scroll_delta.y
+            ),
+            InputEvent::MouseModeChanged { window, mode } => format!(
+                "mouse_mode {} {}",
+                handle(window),
+                (*mode == MouseMode::Relative) as u8
+            ),
+        },
+        RecordedEvent::Window(event) => {
+            let window = handle(&event.window_handle());
+
+            match event {
+                WindowEvent::Shown(_) => format!("shown {window}"),
+                WindowEvent::Hidden(_) => format!("hidden {window}"),
+                WindowEvent::Exposed(_) => format!("exposed {window}"),
+                WindowEvent::CloseRequested(_) => format!("close_requested {window}"),
+                WindowEvent::WindowMustClose(_) => format!("must_close {window}"),
+                WindowEvent::MainWindowCloseRequested(_) => {
+                    format!("main_close_requested {window}")
+                }
+                WindowEvent::MainWindowMustClose(_) => format!("main_must_close {window}"),
+                WindowEvent::Moved { pos, .. } => format!("moved {window} {} {}", pos.x, pos.y),
+                WindowEvent::Resized { size, .. } => {
+                    format!("resized {window} {} {}", size.x, size.y)
+                }
+                WindowEvent::Minimized(_) => format!("minimized {window}"),
+                WindowEvent::Maximized(_) => format!("maximized {window}"),
+                WindowEvent::Restored(_) => format!("restored {window}"),
+                WindowEvent::MouseEnter(_) => format!("mouse_enter {window}"),
+                WindowEvent::MouseLeave(_) => format!("mouse_leave {window}"),
+                WindowEvent::KeyboardFocusGained(_) => format!("focus_gained {window}"),
+                WindowEvent::KeyboardFocusLost(_) => format!("focus_lost {window}"),
+            }
+        }
+    }
+}
+
+struct Fields<'a>(SplitWhitespace<'a>);
+
+impl Fields<'_> {
+    fn next<T: FromStr>(&mut self, name: &str) -> Result<T, String> {
+        let field = self.0.next().ok_or_else(|| format!("missing `{name}`"))?;
+
+        field
+            .parse()
+            .map_err(|_| format!("invalid `{name}`: `{field}`"))
+    }
+
+    fn window(&mut self) -> Result<WindowHandle, String> {
+        self.next::<usize>("window").map(WindowHandle::from_raw)
+    }
+
+    fn scancode(&mut self) -> Result<Scancode, String> {
+        let raw = self.next::<i32>("scancode")?;
+        Scancode::from_i32(raw).ok_or_else(|| format!("unknown scancode `{raw}`"))
+    }
+
+    fn keymod(&mut self) -> Result<Keymod, String> {
+        self.next::<u16>("keymod").map(Keymod::from_bits_truncate)
+    }
+
+    fn button(&mut self) -> Result<MouseButton, String> {
+        self.next::<u8>("button").map(MouseButton::from_ll)
+    }
+
+    /// Parses a field that older recordings don't have, `default` is used when it's missing
+    fn optional<T: FromStr>(&mut self, name: &str, default: T) -> Result<T, String> {
+        match self.0.next() {
+            Some(field) => field
+                .parse()
+                .map_err(|_| format!("invalid `{name}`: `{field}`")),
+            None => Ok(default),
+        }
+    }
+
+    fn timestamp(&mut self) -> Result<Duration, String> {
+        self.optional::<u64>("timestamp", 0)
+            .map(Duration::from_millis)
+    }
+
+    fn ivec2(&mut self) -> Result<IVec2, String> {
+        Ok(IVec2::new(self.next("x")?, self.next("y")?))
+    }
+}
+
+fn decode_line(line: &str) -> Result<(u64, RecordedEvent), String> {
+    let mut fields = Fields(line.split_whitespace());
+
+    let frame = fields.next::<u64>("frame")?;
+    let kind = fields.next::<String>("event")?;
+
+    let input = |event| Ok(RecordedEvent::Input(event));
+    let window = |event| Ok(RecordedEvent::Window(event));
+
+    let event = match kind.as_str() {
+        "key_pressed" => input(InputEvent::KeyPressed {
+            window: fields.window()?,
+            scancode: fields.scancode()?,
+            keymod: fields.keymod()?,
+            repeat: fields.optional::<u8>("repeat", 0)? != 0,
+            timestamp: fields.timestamp()?,
+        }),
+        "key_released" => input(InputEvent::KeyReleased {
+            window: fields.window()?,
+            scancode: fields.scancode()?,
+            keymod: fields.keymod()?,
+            timestamp: fields.timestamp()?,
+        }),
+        "mouse_pressed" => input(InputEvent::MouseButtonPressed {
+            window: fields.window()?,
+            button: fields.button()?,
+            pos: fields.ivec2()?,
+        }),
+        "mouse_double_click" => input(InputEvent::MouseDoubleClick {
+            window: fields.window()?,
+            button: fields.button()?,
+            pos: fields.ivec2()?,
+        }),
+        "mouse_released" => input(InputEvent::MouseButtonReleased {
+            window: fields.window()?,
+            button: fields.button()?,
+            pos: fields.ivec2()?,
+        }),
+        "mouse_moved" => input(InputEvent::MouseMoved {
+            window: fields.window()?,
+            pos: fields.ivec2()?,
+            delta: IVec2::new(fields.next("dx")?, fields.next("dy")?),
+        }),
+        "mouse_scrolled" => input(InputEvent::MouseScrolled {
+            window: fields.window()?,
+            scroll_delta: Vec2::new(fields.next("dx")?, fields.next("dy")?),
+        }),
+        "mouse_mode" => input(InputEvent::MouseModeChanged {
+            window: fields.window()?,
+            mode: match fields.next::<u8>("mode")? {
+                0 => MouseMode::Absolute,
+                _ => MouseMode::Relative,
+            },
+        }),
+        "shown" => window(WindowEvent::Shown(fields.window()?)),
+        "hidden" => window(WindowEvent::Hidden(fields.window()?)),
+        "exposed" => window(WindowEvent::Exposed(fields.window()?)),
+        "close_requested" => window(WindowEvent::CloseRequested(fields.window()?)),
+        "must_close" => window(WindowEvent::WindowMustClose(fields.window()?)),
+        "main_close_requested" => window(WindowEvent::MainWindowCloseRequested(fields.window()?)),
+        "main_must_close" => window(WindowEvent::MainWindowMustClose(fields.window()?)),
+        "moved" => window(WindowEvent::Moved {
+            handle: fields.window()?,
+            pos: fields.ivec2()?,
+        }),
+        "resized" => window(WindowEvent::Resized {
+            handle: fields.window()?,
+            size: UVec2::new(fields.next("width")?, fields.next("height")?),
+        }),
+        "minimized" => window(WindowEvent::Minimized(fields.window()?)),
+        "maximized" => window(WindowEvent::Maximized(fields.window()?)),
+        "restored" => window(WindowEvent::Restored(fields.window()?)),
+        "mouse_enter" => window(WindowEvent::MouseEnter(fields.window()?)),
+        "mouse_leave" => window(WindowEvent::MouseLeave(fields.window()?)),
+        "focus_gained" => window(WindowEvent::KeyboardFocusGained(fields.window()?)),
+        "focus_lost" => window(WindowEvent::KeyboardFocusLost(fields.window()?)),
+        _ => Err(format!("unknown event `{kind}`")),
+    }?;
+
+    Ok((frame, event))
+}
@@ -11,6 +11,22 @@ pub enum WindowPosition {
     Positioned(IVec2),
 }
 
+/// `WM_CLASS` of X11 windows, Wayland uses `class` as the app id
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct WmClass {
+    pub instance: String,
+    pub class: String,
+}
+
+impl WmClass {
+    pub fn new(instance: impl Into<String>, class: impl Into<String>) -> Self {
+        Self {
+            instance: instance.into(),
+            class: class.into(),
+        }
+    }
+}
+
 pub struct WindowCreateInfo {
     pub title: String,
     pub size: UVec2,
@@ -19,6 +35,15 @@ pub struct WindowCreateInfo {
     pub borderless: bool,
     pub resizable: bool,
     pub vulkan_enabled: bool,
+    /// Defaults to the executable name
+    pub wm_class: Option<WmClass>,
+    pub min_size: Option<UVec2>,
+    pub max_size: Option<UVec2>,
+    /// Width to height ratio kept while resizing, e.g. `16:9`
+    pub aspect_ratio: Option<UVec2>,
+    /// Presents are synchronized to the display refresh, kept by [`Windows`](super::Windows)
+    /// for the renderer to pick a present mode
+    pub vsync: bool,
 }
 
 impl WindowCreateInfo {
@@ -31,6 +56,11 @@ impl WindowCreateInfo {
             borderless: false,
             resizable: true,
             vulkan_enabled: true,
+            wm_class: None,
+            min_size: None,
+            max_size: None,
+            aspect_ratio: None,
+            vsync: true,
         }
     }
 
@@ -49,6 +79,31 @@ impl WindowCreateInfo {
         }
     }
 
+    pub fn with_wm_class(mut self, wm_class: WmClass) -> Self {
+        self.wm_class = Some(wm_class);
+        self
+    }
+
+    pub fn with_min_size(mut self, min_size: UVec2) -> Self {
+        self.min_size = Some(min_size);
+        self
+    }
+
+    pub fn with_max_size(mut self, max_size: UVec2) -> Self {
+        self.max_size = Some(max_size);
+        self
+    }
+
+    pub fn with_aspect_ratio(mut self, aspect_ratio: UVec2) -> Self {
+        self.aspect_ratio = Some(aspect_ratio);
+        self
+    }
+
+    pub fn with_vsync(mut self, vsync: bool) -> Self {
+        self.vsync = vsync;
+        self
+    }
+
     pub(crate) fn builder(&self, video: &sdl::VideoSubsystem) -> WindowBuilder {
         let WindowCreateInfo {
             title,
@@ -1,19 +1,26 @@
-use std::collections::BTreeMap;
+use std::collections::{BTreeMap, BTreeSet};
 
 use bizarre_core::Handle;
 use bizarre_ecs::prelude::Resource;
 use nalgebra_glm::IVec2;
 use nalgebra_glm::UVec2;
 
-use crate::context::with_sdl_video;
+use crate::context::{is_video_initialized, try_init_video, with_sdl_video};
 
 pub mod create_info;
+pub mod native;
+pub mod platform;
+pub mod script;
 pub mod window_event;
+mod x11;
 
 pub use sdl::video::Window;
 
 pub use create_info::WindowCreateInfo;
 pub use create_info::WindowPosition;
+pub use create_info::WmClass;
+pub use native::{native_window, windowing_backend, NativeWindow, WindowingBackend};
+pub use platform::{CursorShape, PlatformWindow, PlatformWindowError, PlatformWindowResult};
 pub use window_event::WindowEvent;
 
 pub type WindowHandle = Handle<Window>;
@@ -22,6 +29,15 @@ pub type WindowHandle = Handle<Window>;
 pub struct Windows {
     windows: BTreeMap<WindowHandle, Window>,
     main_window: Option<WindowHandle>,
+    aspect_ratios: BTreeMap<WindowHandle, UVec2>,
+    /// Windows created without vsync
+    no_vsync: BTreeSet<WindowHandle>,
+    /// Window events of the current frame by the window they were sent to
+    routed_events: BTreeMap<WindowHandle, Vec<WindowEvent>>,
+    /// Closed with [`Windows::close_window`], told to close on the next frame
+    closing: Vec<WindowHandle>,
+    /// Told to close on the current frame, destroyed on the next one
+    destroying: Vec<WindowHandle>,
 }
 
 impl Windows {
@@ -29,14 +45,31 @@ impl Windows {
         Self::default()
     }
 
+    /// Panics if the window can't be created, see [`Windows::try_create_window`]
     pub fn create_window(&mut self, create_info: &WindowCreateInfo) -> WindowHandle {
-        let window = with_sdl_video(|video| create_info.builder(video).build()).unwrap();
+        self.try_create_window(create_info)
+            .unwrap_or_else(|err| panic!("{err}"))
+    }
+
+    pub fn try_create_window(
+        &mut self,
+        create_info: &WindowCreateInfo,
+    ) -> PlatformWindowResult<WindowHandle> {
+        let window = create_platform_window(create_info)?;
 
         let handle = WindowHandle::from_raw(window.id() as usize);
 
         self.windows.insert(handle, window);
 
-        handle
+        if !create_info.vsync {
+            self.no_vsync.insert(handle);
+        }
+
+        if let Some(aspect_ratio) = create_info.aspect_ratio {
+            self.set_aspect_ratio(handle, Some(aspect_ratio))?;
+        }
+
+        Ok(handle)
     }
 
     pub fn window(&self, handle: &WindowHandle) -> Option<&Window> {
@@ -48,16 +81,191 @@ impl Windows {
     }
 
     pub fn remove_window(&mut self, handle: &WindowHandle) -> Option<Window> {
+        self.aspect_ratios.remove(handle);
+        self.no_vsync.remove(handle);
+        self.routed_events.remove(handle);
+        self.closing.retain(|closing| closing != handle);
+        self.destroying.retain(|destroying| destroying != handle);
+        if self.main_window == Some(*handle) {
+            self.main_window = None;
+        }
         self.windows.remove(handle)
     }
 
+    /// Destroys the window in two frames: on the next one
+    /// [`WindowEvent::WindowMustClose`] (or [`WindowEvent::MainWindowMustClose`])
+    /// is sent so its resources can be released, on the one after it's destroyed
+    pub fn close_window(&mut self, handle: WindowHandle) {
+        if self.windows.contains_key(&handle)
+            && !self.closing.contains(&handle)
+            && !self.destroying.contains(&handle)
+        {
+            self.closing.push(handle);
+        }
+    }
+
+    /// The window was closed with [`Windows::close_window`] and is about to be destroyed
+    pub fn is_closing(&self, handle: &WindowHandle) -> bool {
+        self.closing.contains(handle) || self.destroying.contains(handle)
+    }
+
+    /// Starts a frame of the event pump: forgets the events routed on the last
+    /// one, destroys the windows told to close on it and returns the events
+    /// telling the windows closed since to close
+    pub fn begin_frame(&mut self) -> Vec<WindowEvent> {
+        self.routed_events.clear();
+
+        for handle in std::mem::take(&mut self.destroying) {
+            self.remove_window(&handle);
+        }
+
+        self.destroying = std::mem::take(&mut self.closing);
+
+        self.destroying
+            .iter()
+            .map(|handle| {
+                if self.main_window == Some(*handle) {
+                    WindowEvent::MainWindowMustClose(*handle)
+                } else {
+                    WindowEvent::WindowMustClose(*handle)
+                }
+            })
+            .collect()
+    }
+
+    /// Stores `event` for [`Windows::window_events`] of the window it was sent to
+    pub fn route_event(&mut self, event: &WindowEvent) {
+        self.routed_events
+            .entry(event.window_handle())
+            .or_default()
+            .push(event.clone());
+    }
+
+    /// Window events sent to `handle` on the current frame
+    pub fn window_events(&self, handle: &WindowHandle) -> &[WindowEvent] {
+        self.routed_events
+            .get(handle)
+            .map(Vec::as_slice)
+            .unwrap_or_default()
+    }
+
+    /// Keeps the width to height ratio of the window while it's being resized
+    ///
+    /// X11 window managers get it through `WM_NORMAL_HINTS`, Wayland has no
+    /// aspect ratio protocol so the window is resized back in
+    /// [`Windows::constrain_aspect_ratio`]
+    pub fn set_aspect_ratio(
+        &mut self,
+        handle: WindowHandle,
+        aspect_ratio: Option<UVec2>,
+    ) -> PlatformWindowResult<()> {
+        let Some(window) = self.windows.get(&handle) else {
+            return Ok(());
+        };
+
+        if let NativeWindow::X11 { display, window } = native_window(window) {
+            x11::set_aspect_hint(display, window, aspect_ratio)?;
+        }
+
+        match aspect_ratio {
+            Some(aspect_ratio) => self.aspect_ratios.insert(handle, aspect_ratio),
+            None => self.aspect_ratios.remove(&handle),
+        };
+
+        Ok(())
+    }
+
+    pub fn aspect_ratio(&self, handle: &WindowHandle) -> Option<UVec2> {
+        self.aspect_ratios.get(handle).copied()
+    }
+
+    /// Whether presents to the window should wait for the display refresh, set
+    /// from [`WindowCreateInfo::vsync`]
+    pub fn vsync(&self, handle: &WindowHandle) -> bool {
+        !self.no_vsync.contains(handle)
+    }
+
+    pub fn set_vsync(&mut self, handle: WindowHandle, vsync: bool) {
+        if vsync {
+            self.no_vsync.remove(&handle);
+        } else if self.windows.contains_key(&handle) {
+            self.no_vsync.insert(handle);
+        }
+    }
+
+    /// Resizes the window back to its aspect ratio after it was resized to `size`,
+    /// keeps the width
+    pub fn constrain_aspect_ratio(&mut self, handle: WindowHandle, size: UVec2) {
+        let (Some(aspect_ratio), Some(window)) = (
+            self.aspect_ratios.get(&handle),
+            self.windows.get_mut(&handle),
+        ) else {
+            return;
+        };
+
+        let height = (size.x as f32 * aspect_ratio.y as f32 / aspect_ratio.x as f32).round() as u32;
+
+        // Window managers round the hinted sizes too
+        if height.abs_diff(size.y) > 1 {
+            let _ = window.set_size(size.x, height.max(1));
+        }
+    }
+
     pub fn set_main_window(&mut self, handle: WindowHandle) {
         self.main_window = Some(handle)
     }
 
+    pub fn main_window_handle(&self) -> Option<WindowHandle> {
+        self.main_window
+    }
+
     pub fn get_main_window(&self) -> Option<&Window> {
         self.windows.get(self.main_window.as_ref()?)
     }
+
+    pub fn get_main_window_mut(&mut self) -> Option<&mut Window> {
+        self.windows.get_mut(self.main_window.as_ref()?)
+    }
+}
+
+/// Creates a window on the video driver picked at runtime, reports a missing
+/// display server instead of panicking
+pub fn create_platform_window(create_info: &WindowCreateInfo) -> PlatformWindowResult<Window> {
+    if let Some(wm_class) = &create_info.wm_class {
+        if !is_video_initialized() {
+            // SDL reads the class once on video initialization, it's the
+            // only way to set the Wayland app id
+            unsafe {
+                std::env::set_var("SDL_VIDEO_X11_WMCLASS", &wm_class.class);
+                std::env::set_var("SDL_VIDEO_WAYLAND_WMCLASS", &wm_class.class);
+            }
+        }
+    }
+
+    try_init_video()?;
+
+    let mut window = with_sdl_video(|video| create_info.builder(video).build())
+        .map_err(|err| PlatformWindowError::WindowCreation(err.to_string()))?;
+
+    if let Some(min_size) = create_info.min_size {
+        window
+            .set_minimum_size(min_size.x, min_size.y)
+            .map_err(|err| PlatformWindowError::WindowCreation(err.to_string()))?;
+    }
+
+    if let Some(max_size) = create_info.max_size {
+        window
+            .set_maximum_size(max_size.x, max_size.y)
+            .map_err(|err| PlatformWindowError::WindowCreation(err.to_string()))?;
+    }
+
+    if let Some(wm_class) = &create_info.wm_class {
+        if windowing_backend() == WindowingBackend::X11 {
+            window.set_wm_class(wm_class)?;
+        }
+    }
+
+    Ok(window)
 }
 
 pub fn try_handle_sdl_event(windows: &Windows, event: &sdl::event::Event) -> Option<WindowEvent> {
@@ -0,0 +1,71 @@
+use std::{ffi::c_void, mem::MaybeUninit};
+
+use sdl::sys;
+
+use crate::context::with_sdl_video;
+
+use super::Window;
+
+/// Windowing system SDL's video subsystem is running on
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum WindowingBackend {
+    Wayland,
+    X11,
+    Win32,
+    Other,
+}
+
+/// Returns the backend of the active SDL video driver
+pub fn windowing_backend() -> WindowingBackend {
+    with_sdl_video(|video| match video.current_video_driver() {
+        "wayland" => WindowingBackend::Wayland,
+        "x11" => WindowingBackend::X11,
+        "windows" => WindowingBackend::Win32,
+        _ => WindowingBackend::Other,
+    })
+}
+
+/// Native handles of a window, used to create graphics surfaces for it
+#[derive(Clone, Copy, Debug)]
+pub enum NativeWindow {
+    Wayland {
+        display: *mut c_void,
+        surface: *mut c_void,
+    },
+    X11 {
+        display: *mut c_void,
+        window: u64,
+    },
+    /// The window belongs to a backend without native handle support, Win32
+    /// handles aren't exposed by the SDL bindings
+    Other,
+}
+
+/// Queries native handles of `window` from SDL
+pub fn native_window(window: &Window) -> NativeWindow {
+    let mut info = MaybeUninit::<sys::SDL_SysWMinfo>::zeroed();
+
+    let info = unsafe {
+        sys::SDL_GetVersion(&raw mut (*info.as_mut_ptr()).version);
+
+        if sys::SDL_GetWindowWMInfo(window.raw(), info.as_mut_ptr()) != sys::SDL_bool::SDL_TRUE {
+            return NativeWindow::Other;
+        }
+
+        info.assume_init()
+    };
+
+    unsafe {
+        match info.subsystem {
+            sys::SDL_SYSWM_TYPE::SDL_SYSWM_WAYLAND => NativeWindow::Wayland {
+                display: info.info.wl.display.cast(),
+                surface: info.info.wl.surface.cast(),
+            },
+            sys::SDL_SYSWM_TYPE::SDL_SYSWM_X11 => NativeWindow::X11 {
+                display: info.info.x11.display.cast(),
+                window: info.info.x11.window as u64,
+            },
+            _ => NativeWindow::Other,
+        }
+    }
+}
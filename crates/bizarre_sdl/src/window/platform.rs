@@ -0,0 +1,200 @@
+use std::cell::RefCell;
+
+use nalgebra_glm::{IVec2, UVec2};
+use sdl::{
+    mouse::{Cursor, SystemCursor},
+    pixels::PixelFormatEnum,
+    surface::Surface,
+};
+use thiserror::Error;
+
+use crate::context::{with_sdl_context, with_sdl_video};
+
+use super::{
+    native_window, windowing_backend, x11, NativeWindow, Window, WindowingBackend, WmClass,
+};
+
+thread_local! {
+    /// SDL keeps a pointer to the active cursor, so it has to outlive its use
+    static ACTIVE_CURSOR: RefCell<Option<Cursor>> = const { RefCell::new(None) };
+}
+
+#[derive(Debug, Error)]
+pub enum PlatformWindowError {
+    #[error("Cursor image of size {size} needs {expected} bytes of RGBA pixels, got {actual}")]
+    CursorImageSize {
+        size: UVec2,
+        expected: usize,
+        actual: usize,
+    },
+    #[error("Failed to create cursor: {0}")]
+    Cursor(String),
+    #[error("Clipboard is not available: {0}")]
+    Clipboard(String),
+    #[error("Xlib call failed: {0}")]
+    X11(String),
+    #[error("Not supported on the {0:?} windowing backend")]
+    Unsupported(WindowingBackend),
+    #[error("No display server is reachable: {0}")]
+    NoDisplayServer(String),
+    #[error("Failed to create window: {0}")]
+    WindowCreation(String),
+}
+
+pub type PlatformWindowResult<T> = Result<T, PlatformWindowError>;
+
+/// Shapes of the system cursor, named after the cursor-shape-v1 protocol
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+pub enum CursorShape {
+    #[default]
+    Default,
+    Pointer,
+    Text,
+    Crosshair,
+    Wait,
+    Progress,
+    Move,
+    NotAllowed,
+    EwResize,
+    NsResize,
+    NeswResize,
+    NwseResize,
+}
+
+impl CursorShape {
+    fn system_cursor(self) -> SystemCursor {
+        match self {
+            CursorShape::Default => SystemCursor::Arrow,
+            CursorShape::Pointer => SystemCursor::Hand,
+            CursorShape::Text => SystemCursor::IBeam,
+            CursorShape::Crosshair => SystemCursor::Crosshair,
+            CursorShape::Wait => SystemCursor::Wait,
+            CursorShape::Progress => SystemCursor::WaitArrow,
+            CursorShape::Move => SystemCursor::SizeAll,
+            CursorShape::NotAllowed => SystemCursor::No,
+            CursorShape::EwResize => SystemCursor::SizeWE,
+            CursorShape::NsResize => SystemCursor::SizeNS,
+            CursorShape::NeswResize => SystemCursor::SizeNESW,
+            CursorShape::NwseResize => SystemCursor::SizeNWSE,
+        }
+    }
+}
+
+/// Pointer, cursor and clipboard features of the windowing system
+///
+/// On Wayland SDL maps these onto the compositor protocols: mouse grab locks the
+/// pointer through `zwp_pointer_constraints_v1` and reads motion from
+/// `zwp_relative_pointer_v1`, cursor shapes go through `wp_cursor_shape_v1` when
+/// the compositor supports it and the cursor theme otherwise, clipboard goes
+/// through `wl_data_device`. X11 and Win32 get the native equivalents.
+pub trait PlatformWindow {
+    /// Hides the cursor, locks it inside the window and reports relative motion
+    /// only, movement is available through `InputEvent::MouseMoved::delta`
+    fn set_mouse_grab(&mut self, grabbed: bool);
+
+    fn is_mouse_grabbed(&self) -> bool;
+
+    /// Sets one of the system cursor shapes, falls back to
+    /// [`CursorShape::Default`] when the platform lacks `shape`
+    fn set_cursor_shape(&self, shape: CursorShape) -> PlatformWindowResult<()>;
+
+    /// Sets a cursor from tightly packed RGBA8 `pixels`, `hotspot` is the
+    /// clicking point relative to the top left corner of the image
+    fn set_custom_cursor(
+        &self,
+        pixels: &[u8],
+        size: UVec2,
+        hotspot: IVec2,
+    ) -> PlatformWindowResult<()>;
+
+    fn set_cursor_visible(&self, visible: bool);
+
+    fn clipboard_text(&self) -> PlatformWindowResult<String>;
+
+    fn set_clipboard_text(&self, text: &str) -> PlatformWindowResult<()>;
+
+    /// Changes `WM_CLASS` of the window, Wayland only takes the app id from
+    /// [`WindowCreateInfo::wm_class`](super::WindowCreateInfo::wm_class) of the first window
+    fn set_wm_class(&self, wm_class: &WmClass) -> PlatformWindowResult<()>;
+}
+
+impl PlatformWindow for Window {
+    fn set_mouse_grab(&mut self, grabbed: bool) {
+        Window::set_mouse_grab(self, grabbed);
+        with_sdl_context(|sdl| sdl.mouse().set_relative_mouse_mode(grabbed));
+    }
+
+    fn is_mouse_grabbed(&self) -> bool {
+        with_sdl_context(|sdl| sdl.mouse().relative_mouse_mode())
+    }
+
+    fn set_cursor_shape(&self, shape: CursorShape) -> PlatformWindowResult<()> {
+        let cursor = Cursor::from_system(shape.system_cursor())
+            .or_else(|_| Cursor::from_system(SystemCursor::Arrow))
+            .map_err(PlatformWindowError::Cursor)?;
+
+        set_active_cursor(cursor);
+        Ok(())
+    }
+
+    fn set_custom_cursor(
+        &self,
+        pixels: &[u8],
+        size: UVec2,
+        hotspot: IVec2,
+    ) -> PlatformWindowResult<()> {
+        let expected = size.x as usize * size.y as usize * 4;
+        if pixels.len() != expected {
+            return Err(PlatformWindowError::CursorImageSize {
+                size,
+                expected,
+                actual: pixels.len(),
+            });
+        }
+
+        // SDL copies the pixels while creating the cursor
+        let mut pixels = pixels.to_vec();
+        let surface = Surface::from_data(
+            &mut pixels,
+            size.x,
+            size.y,
+            size.x * 4,
+            PixelFormatEnum::RGBA32,
+        )
+        .map_err(PlatformWindowError::Cursor)?;
+
+        let cursor = Cursor::from_surface(surface, hotspot.x, hotspot.y)
+            .map_err(PlatformWindowError::Cursor)?;
+
+        set_active_cursor(cursor);
+        Ok(())
+    }
+
+    fn set_cursor_visible(&self, visible: bool) {
+        with_sdl_context(|sdl| sdl.mouse().show_cursor(visible));
+    }
+
+    fn clipboard_text(&self) -> PlatformWindowResult<String> {
+        with_sdl_video(|video| video.clipboard().clipboard_text())
+            .map_err(PlatformWindowError::Clipboard)
+    }
+
+    fn set_clipboard_text(&self, text: &str) -> PlatformWindowResult<()> {
+        with_sdl_video(|video| video.clipboard().set_clipboard_text(text))
+            .map_err(PlatformWindowError::Clipboard)
+    }
+
+    fn set_wm_class(&self, wm_class: &WmClass) -> PlatformWindowResult<()> {
+        match native_window(self) {
+            NativeWindow::X11 { display, window } => {
+                x11::set_class_hint(display, window, &wm_class.instance, &wm_class.class)
+            }
+            _ => Err(PlatformWindowError::Unsupported(windowing_backend())),
+        }
+    }
+}
+
+fn set_active_cursor(cursor: Cursor) {
+    cursor.set();
+    ACTIVE_CURSOR.with_borrow_mut(|active| *active = Some(cursor));
+}
@@ -0,0 +1,220 @@
+use std::{
+    fmt::Display,
+    time::{Duration, Instant},
+};
+
+use nalgebra_glm::UVec2;
+use sdl::video::FullscreenType;
+
+use crate::context::with_sdl_context;
+
+use super::{try_handle_sdl_event, Window, WindowCreateInfo, WindowEvent, Windows};
+
+/// How long a step waits for the backend to report the events caused by it
+pub const DEFAULT_SETTLE_TIME: Duration = Duration::from_millis(250);
+
+pub enum WindowOp {
+    Create(WindowCreateInfo),
+    Resize(UVec2),
+    SetMode(FullscreenType),
+    Maximize,
+    Minimize,
+    Restore,
+    SetTitle(String),
+}
+
+impl Display for WindowOp {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            WindowOp::Create(info) => write!(f, "create({}x{})", info.size.x, info.size.y),
+            WindowOp::Resize(size) => write!(f, "resize({}x{})", size.x, size.y),
+            WindowOp::SetMode(mode) => write!(f, "set_mode({mode:?})"),
+            WindowOp::Maximize => write!(f, "maximize"),
+            WindowOp::Minimize => write!(f, "minimize"),
+            WindowOp::Restore => write!(f, "restore"),
+            WindowOp::SetTitle(title) => write!(f, "set_title({title})"),
+        }
+    }
+}
+
+/// Sequence of window operations run against the current SDL video backend,
+/// recording the [`WindowEvent`]s every operation caused.
+///
+/// Used to check that different backends (x11, wayland, offscreen) report the
+/// same things for the same actions
+pub struct WindowScript {
+    ops: Vec<WindowOp>,
+    settle_time: Duration,
+}
+
+impl WindowScript {
+    pub fn new(create_info: WindowCreateInfo) -> Self {
+        Self {
+            ops: vec![WindowOp::Create(create_info)],
+            settle_time: DEFAULT_SETTLE_TIME,
+        }
+    }
+
+    pub fn with_settle_time(mut self, settle_time: Duration) -> Self {
+        self.settle_time = settle_time;
+        self
+    }
+
+    pub fn then(mut self, op: WindowOp) -> Self {
+        self.ops.push(op);
+        self
+    }
+
+    pub fn resize(self, size: UVec2) -> Self {
+        self.then(WindowOp::Resize(size))
+    }
+
+    pub fn set_mode(self, mode: FullscreenType) -> Self {
+        self.then(WindowOp::SetMode(mode))
+    }
+
+    pub fn maximize(self) -> Self {
+        self.then(WindowOp::Maximize)
+    }
+
+    pub fn minimize(self) -> Self {
+        self.then(WindowOp::Minimize)
+    }
+
+    pub fn restore(self) -> Self {
+        self.then(WindowOp::Restore)
+    }
+
+    pub fn set_title(self, title: impl Into<String>) -> Self {
+        self.then(WindowOp::SetTitle(title.into()))
+    }
+
+    /// Runs the script, must be called on the thread SDL was initialized on
+    pub fn run(self, windows: &mut Windows) -> Result<ScriptReport, String> {
+        let mut event_pump = with_sdl_context(|sdl| sdl.event_pump())?;
+        let mut handle = None;
+        let mut steps = Vec::with_capacity(self.ops.len());
+
+        for op in self.ops {
+            match &op {
+                WindowOp::Create(create_info) => {
+                    handle = Some(windows.create_window(create_info));
+                }
+                op => {
+                    let window = handle
+                        .and_then(|handle| windows.window_mut(&handle))
+                        .ok_or("The script window does not exist")?;
+
+                    apply_op(window, op)?;
+                }
+            }
+
+            let handle = handle.ok_or("The script window does not exist")?;
+
+            let deadline = Instant::now() + self.settle_time;
+            let mut events = Vec::new();
+
+            while Instant::now() < deadline {
+                events.extend(
+                    event_pump
+                        .poll_iter()
+                        .filter_map(|event| try_handle_sdl_event(windows, &event))
+                        .filter(|event| event.window_handle() == handle),
+                );
+
+                std::thread::sleep(Duration::from_millis(5));
+            }
+
+            let window = windows.window(&handle).unwrap();
+            let (width, height) = window.size();
+
+            steps.push(StepReport {
+                op: op.to_string(),
+                events,
+                title: window.title().to_string(),
+                size: UVec2::new(width, height),
+            });
+        }
+
+        Ok(ScriptReport { steps })
+    }
+}
+
+fn apply_op(window: &mut Window, op: &WindowOp) -> Result<(), String> {
+    match op {
+        WindowOp::Create(_) => unreachable!(),
+        WindowOp::Resize(size) => window
+            .set_size(size.x, size.y)
+            .map_err(|err| err.to_string()),
+        WindowOp::SetMode(mode) => window.set_fullscreen(*mode),
+        WindowOp::Maximize => {
+            window.maximize();
+            Ok(())
+        }
+        WindowOp::Minimize => {
+            window.minimize();
+            Ok(())
+        }
+        WindowOp::Restore => {
+            window.restore();
+            Ok(())
+        }
+        WindowOp::SetTitle(title) => window.set_title(title).map_err(|err| err.to_string()),
+    }
+}
+
+/// Events and window state recorded after a single [`WindowOp`]
+#[derive(Debug)]
+pub struct StepReport {
+    pub op: String,
+    pub events: Vec<WindowEvent>,
+    pub title: String,
+    pub size: UVec2,
+}
+
+impl StepReport {
+    /// Backend independent description of the recorded events, window handles
+    /// are left out since they differ between runs
+    pub fn event_names(&self) -> Vec<String> {
+        self.events.iter().map(event_name).collect()
+    }
+}
+
+#[derive(Debug)]
+pub struct ScriptReport {
+    pub steps: Vec<StepReport>,
+}
+
+impl ScriptReport {
+    /// One `op: events | title | size` line per step. Reports of two backends
+    /// behaving the same are equal line by line
+    pub fn to_lines(&self) -> Vec<String> {
+        self.steps
+            .iter()
+            .map(|step| {
+                format!(
+                    "{}: [{}] | {} | {}x{}",
+                    step.op,
+                    step.event_names().join(", "),
+                    step.title,
+                    step.size.x,
+                    step.size.y
+                )
+            })
+            .collect()
+    }
+}
+
+fn event_name(event: &WindowEvent) -> String {
+    match event {
+        WindowEvent::Moved { .. } => "Moved".into(),
+        WindowEvent::Resized { size, .. } => format!("Resized({}x{})", size.x, size.y),
+        event => {
+            let debug = format!("{event:?}");
+            debug
+                .split_once('(')
+                .map(|(name, _)| name.to_string())
+                .unwrap_or(debug)
+        }
+    }
+}
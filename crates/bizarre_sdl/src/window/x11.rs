@@ -0,0 +1,151 @@
+//! The few Xlib calls SDL doesn't expose, resolved from the `libX11` SDL itself loads
+
+use std::{
+    ffi::{c_char, c_int, c_long, c_ulong, c_void, CString},
+    sync::OnceLock,
+};
+
+use libloading::Library;
+use nalgebra_glm::UVec2;
+
+use super::platform::{PlatformWindowError, PlatformWindowResult};
+
+/// `PAspect` flag of `XSizeHints::flags`
+const P_ASPECT: c_long = 1 << 7;
+
+#[repr(C)]
+struct XClassHint {
+    res_name: *mut c_char,
+    res_class: *mut c_char,
+}
+
+#[repr(C)]
+#[derive(Default)]
+struct XAspect {
+    x: c_int,
+    y: c_int,
+}
+
+#[repr(C)]
+#[derive(Default)]
+struct XSizeHints {
+    flags: c_long,
+    x: c_int,
+    y: c_int,
+    width: c_int,
+    height: c_int,
+    min_width: c_int,
+    min_height: c_int,
+    max_width: c_int,
+    max_height: c_int,
+    width_inc: c_int,
+    height_inc: c_int,
+    min_aspect: XAspect,
+    max_aspect: XAspect,
+    base_width: c_int,
+    base_height: c_int,
+    win_gravity: c_int,
+}
+
+type XSetClassHint = unsafe extern "C" fn(*mut c_void, c_ulong, *mut XClassHint) -> c_int;
+type XGetWMNormalHints =
+    unsafe extern "C" fn(*mut c_void, c_ulong, *mut XSizeHints, *mut c_long) -> c_int;
+type XSetWMNormalHints = unsafe extern "C" fn(*mut c_void, c_ulong, *mut XSizeHints) -> c_int;
+type XFlush = unsafe extern "C" fn(*mut c_void) -> c_int;
+
+struct Xlib {
+    set_class_hint: XSetClassHint,
+    get_wm_normal_hints: XGetWMNormalHints,
+    set_wm_normal_hints: XSetWMNormalHints,
+    flush: XFlush,
+    _library: Library,
+}
+
+impl Xlib {
+    fn load() -> Result<Self, libloading::Error> {
+        unsafe {
+            let library = Library::new("libX11.so.6")?;
+
+            Ok(Self {
+                set_class_hint: *library.get(b"XSetClassHint\0")?,
+                get_wm_normal_hints: *library.get(b"XGetWMNormalHints\0")?,
+                set_wm_normal_hints: *library.get(b"XSetWMNormalHints\0")?,
+                flush: *library.get(b"XFlush\0")?,
+                _library: library,
+            })
+        }
+    }
+}
+
+fn xlib() -> PlatformWindowResult<&'static Xlib> {
+    static XLIB: OnceLock<Result<Xlib, String>> = OnceLock::new();
+
+    XLIB.get_or_init(|| Xlib::load().map_err(|err| err.to_string()))
+        .as_ref()
+        .map_err(|err| PlatformWindowError::X11(err.clone()))
+}
+
+/// Sets `WM_CLASS` of an X11 window
+pub(crate) fn set_class_hint(
+    display: *mut c_void,
+    window: u64,
+    instance: &str,
+    class: &str,
+) -> PlatformWindowResult<()> {
+    let xlib = xlib()?;
+
+    let instance =
+        CString::new(instance).map_err(|err| PlatformWindowError::X11(err.to_string()))?;
+    let class = CString::new(class).map_err(|err| PlatformWindowError::X11(err.to_string()))?;
+
+    // Xlib copies the strings into the property
+    let mut hint = XClassHint {
+        res_name: instance.as_ptr().cast_mut(),
+        res_class: class.as_ptr().cast_mut(),
+    };
+
+    unsafe {
+        (xlib.set_class_hint)(display, window as c_ulong, &mut hint);
+        (xlib.flush)(display);
+    }
+
+    Ok(())
+}
+
+/// Sets or clears the fixed aspect ratio in `WM_NORMAL_HINTS`, keeping the
+/// hints SDL has set
+pub(crate) fn set_aspect_hint(
+    display: *mut c_void,
+    window: u64,
+    ratio: Option<UVec2>,
+) -> PlatformWindowResult<()> {
+    let xlib = xlib()?;
+
+    let mut hints = XSizeHints::default();
+    let mut supplied: c_long = 0;
+
+    unsafe {
+        (xlib.get_wm_normal_hints)(display, window as c_ulong, &mut hints, &mut supplied);
+    }
+
+    match ratio {
+        Some(ratio) => {
+            let aspect = || XAspect {
+                x: ratio.x as c_int,
+                y: ratio.y as c_int,
+            };
+
+            hints.flags |= P_ASPECT;
+            hints.min_aspect = aspect();
+            hints.max_aspect = aspect();
+        }
+        None => hints.flags &= !P_ASPECT,
+    }
+
+    unsafe {
+        (xlib.set_wm_normal_hints)(display, window as c_ulong, &mut hints);
+        (xlib.flush)(display);
+    }
+
+    Ok(())
+}
@@ -0,0 +1,127 @@
+//! Runs the same window script against every SDL video driver listed in
+//! `BIZARRE_PARITY_DRIVERS` (comma separated, `offscreen` by default) and checks
+//! that all of them report the same window events.
+//!
+//! Every driver runs in its own process since SDL can be initialized only once.
+
+use std::{
+    env,
+    process::{exit, Command},
+};
+
+use bizarre_sdl::{
+    context::with_sdl_video,
+    window::{create_info::FullscreenType, script::WindowScript, WindowCreateInfo, Windows},
+};
+use nalgebra_glm::UVec2;
+
+const DRIVER_ENV: &str = "BIZARRE_PARITY_DRIVER";
+const DRIVERS_ENV: &str = "BIZARRE_PARITY_DRIVERS";
+const UNAVAILABLE_EXIT_CODE: i32 = 2;
+
+fn script() -> WindowScript {
+    let create_info = WindowCreateInfo {
+        vulkan_enabled: false,
+        ..WindowCreateInfo::normal_window("Parity".into(), UVec2::new(640, 480), Default::default())
+    };
+
+    WindowScript::new(create_info)
+        .resize(UVec2::new(800, 600))
+        .set_title("Parity renamed")
+        .maximize()
+        .restore()
+        .minimize()
+        .restore()
+        .set_mode(FullscreenType::Desktop)
+        .set_mode(FullscreenType::Off)
+}
+
+/// Lines every backend must produce no matter how it implements window management
+fn required_lines() -> Vec<(&'static str, &'static str)> {
+    vec![
+        ("resize(800x600)", "Resized(800x600)"),
+        ("set_title(Parity renamed)", "| Parity renamed |"),
+    ]
+}
+
+fn run_driver(driver: &str) {
+    // SAFETY: called before any other thread is spawned
+    unsafe { env::set_var("SDL_VIDEODRIVER", driver) };
+
+    if std::panic::catch_unwind(|| with_sdl_video(|_| ())).is_err() {
+        exit(UNAVAILABLE_EXIT_CODE);
+    }
+
+    let mut windows = Windows::new();
+    let report = script()
+        .run(&mut windows)
+        .unwrap_or_else(|err| panic!("Failed to run window script on `{driver}`: {err}"));
+
+    for line in report.to_lines() {
+        println!("{line}");
+    }
+}
+
+fn main() {
+    if let Ok(driver) = env::var(DRIVER_ENV) {
+        run_driver(&driver);
+        return;
+    }
+
+    let drivers = env::var(DRIVERS_ENV).unwrap_or_else(|_| "offscreen".into());
+    let exe = env::current_exe().unwrap();
+
+    let mut reports = Vec::new();
+
+    for driver in drivers.split(',').map(str::trim).filter(|d| !d.is_empty()) {
+        let output = Command::new(&exe)
+            .env(DRIVER_ENV, driver)
+            .output()
+            .unwrap_or_else(|err| panic!("Failed to spawn parity run for `{driver}`: {err}"));
+
+        match output.status.code() {
+            Some(0) => {}
+            Some(UNAVAILABLE_EXIT_CODE) => {
+                println!("window_parity: `{driver}` video driver is unavailable, skipping");
+                continue;
+            }
+            _ => panic!(
+                "Parity run for `{driver}` failed:\n{}",
+                String::from_utf8_lossy(&output.stderr)
+            ),
+        }
+
+        let lines = String::from_utf8_lossy(&output.stdout)
+            .lines()
+            .map(str::to_string)
+            .collect::<Vec<_>>();
+
+        for (op, expected) in required_lines() {
+            let line = lines
+                .iter()
+                .find(|line| line.starts_with(op))
+                .unwrap_or_else(|| panic!("`{driver}` has no report for `{op}`"));
+
+            assert!(
+                line.contains(expected),
+                "`{driver}`: expected `{expected}` after `{op}`, got `{line}`"
+            );
+        }
+
+        reports.push((driver.to_string(), lines));
+    }
+
+    if let Some(((first_driver, first), rest)) = reports.split_first() {
+        for (driver, lines) in rest {
+            for (expected, actual) in first.iter().zip(lines) {
+                assert_eq!(
+                    expected, actual,
+                    "`{driver}` diverges from `{first_driver}`"
+                );
+            }
+            assert_eq!(first.len(), lines.len());
+        }
+    }
+
+    println!("window_parity: {} driver(s) checked", reports.len());
+}
@@ -0,0 +1,122 @@
+use std::collections::HashMap;
+
+use bizarre_ecs::prelude::*;
+use bizarre_render::texture::TextureHandle;
+use nalgebra_glm::{UVec2, Vec2};
+
+/// Area of the atlas texture in pixels
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct AtlasRegion {
+    pub position: UVec2,
+    pub size: UVec2,
+}
+
+impl AtlasRegion {
+    pub fn new(position: UVec2, size: UVec2) -> Self {
+        Self { position, size }
+    }
+}
+
+/// Monospace bitmap font laid out in a grid of equally sized glyphs, starting
+/// at `origin` with `first_char` and going row by row
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct FontAtlas {
+    pub origin: UVec2,
+    pub glyph_size: UVec2,
+    pub columns: u32,
+    pub first_char: char,
+    pub glyph_count: u32,
+}
+
+impl FontAtlas {
+    pub fn glyph(&self, c: char) -> Option<AtlasRegion> {
+        let index = (c as u32).checked_sub(self.first_char as u32)?;
+
+        if index >= self.glyph_count || self.columns == 0 {
+            return None;
+        }
+
+        let cell = UVec2::new(index % self.columns, index / self.columns);
+
+        Some(AtlasRegion::new(
+            self.origin + cell.component_mul(&self.glyph_size),
+            self.glyph_size,
+        ))
+    }
+
+    /// Horizontal advance of a glyph drawn `size` pixels high
+    pub fn advance(&self, size: f32) -> f32 {
+        self.glyph_size.x as f32 * size / self.glyph_size.y.max(1) as f32
+    }
+}
+
+/// Texture with the images and the font of the UI, so most of it is drawn in
+/// a single batch
+#[derive(Resource, Clone, Debug, Default)]
+pub struct UiAtlas {
+    texture: Option<TextureHandle>,
+    size: UVec2,
+    regions: HashMap<String, AtlasRegion>,
+    font: Option<FontAtlas>,
+}
+
+impl UiAtlas {
+    pub fn new(texture: TextureHandle, size: UVec2) -> Self {
+        Self {
+            texture: Some(texture),
+            size,
+            ..Default::default()
+        }
+    }
+
+    /// Atlas without a texture, images and text are drawn as solid quads
+    pub fn untextured() -> Self {
+        Self::default()
+    }
+
+    pub fn with_region(mut self, name: impl Into<String>, region: AtlasRegion) -> Self {
+        self.regions.insert(name.into(), region);
+        self
+    }
+
+    pub fn with_font(mut self, font: FontAtlas) -> Self {
+        self.font = Some(font);
+        self
+    }
+
+    pub fn texture(&self) -> Option<TextureHandle> {
+        self.texture
+    }
+
+    pub fn region(&self, name: &str) -> Option<AtlasRegion> {
+        self.regions.get(name).copied()
+    }
+
+    pub fn font(&self) -> Option<&FontAtlas> {
+        self.font.as_ref()
+    }
+
+    /// Top-left and bottom-right texture coordinates of the region
+    pub fn uv(&self, region: AtlasRegion) -> (Vec2, Vec2) {
+        let size = self.size.map(|v| v.max(1) as f32);
+        let min = region.position.cast::<f32>().component_div(&size);
+        let max = (region.position + region.size)
+            .cast::<f32>()
+            .component_div(&size);
+
+        (min, max)
+    }
+
+    /// Size of the text drawn `size` pixels high, lines are split by `\n`
+    pub fn text_size(&self, text: &str, size: f32) -> Vec2 {
+        let advance = self.font.map_or(size * 0.5, |font| font.advance(size));
+        let columns = text
+            .lines()
+            .map(|line| line.chars().count())
+            .max()
+            .unwrap_or(0);
+        let lines = text.lines().count().max(1);
+
+        Vec2::new(columns as f32 * advance, lines as f32 * size)
+    }
+}
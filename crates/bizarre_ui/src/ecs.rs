@@ -0,0 +1,64 @@
+use bizarre_ecs::prelude::*;
+use bizarre_event::EventQueue;
+use bizarre_render::{
+    render_assets::{AssetStore, RenderAssets},
+    renderer::VulkanRenderer,
+};
+use bizarre_sdl::input::{InputState, MouseButton, MouseMode};
+use nalgebra_glm::Vec2;
+
+use crate::{atlas::UiAtlas, tree::UiTree};
+
+/// Runs [`update_ui`], the layout is queued by [`UiQueueSet`] after it
+#[derive(SystemSet)]
+pub struct UiLayoutSet;
+
+/// Runs [`queue_ui`], has to run before [`RenderSet`](bizarre_render::ecs::RenderSet)
+#[derive(SystemSet)]
+pub struct UiQueueSet;
+
+/// Lays the [`UiTree`] out over its render target and updates the hovered and
+/// pressed nodes from the mouse of its window
+pub fn update_ui(
+    mut tree: ResMut<UiTree>,
+    atlas: Res<UiAtlas>,
+    assets: Res<RenderAssets>,
+    input: Res<InputState>,
+    mut events: ResMut<EventQueue>,
+) {
+    let Some(extent) = tree
+        .render_target()
+        .and_then(|target| assets.render_targets.get(&target))
+        .map(|target| target.extent())
+    else {
+        return;
+    };
+
+    tree.layout(extent.cast(), &atlas);
+
+    if let Some(window) = tree.window() {
+        let input = input.for_window(window);
+        // A grabbed mouse has no cursor to hover or click with
+        let mouse = if input.has_mouse_focus() && input.mouse_mode() == MouseMode::Absolute {
+            input.mouse_position().cast()
+        } else {
+            Vec2::repeat(f32::NEG_INFINITY)
+        };
+
+        tree.update_input(
+            mouse,
+            input.is_mouse_pressed(MouseButton::Left),
+            &mut events,
+        );
+    }
+}
+
+/// Queues the quads of the [`UiTree`] into the sprite pass of its render target,
+/// has to run before [`render_cameras`](bizarre_render::ecs::render_cameras)
+pub fn queue_ui(tree: Res<UiTree>, atlas: Res<UiAtlas>, mut renderer: ResMut<VulkanRenderer>) {
+    let Some(render_target) = tree.render_target() else {
+        return;
+    };
+
+    renderer.queue_sprites(render_target, tree.build_batches(&atlas));
+}
@@ -0,0 +1,188 @@
+use nalgebra_glm::Vec2;
+
+/// Size of a node along one axis
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub enum Dimension {
+    /// Fits the content of the node
+    #[default]
+    Auto,
+    Px(f32),
+    /// Part of the inner size of the parent, `1.0` is all of it
+    Percent(f32),
+}
+
+impl Dimension {
+    /// Size of the node given the inner size of the parent and the size of the content
+    pub fn resolve(&self, parent: f32, content: f32) -> f32 {
+        match self {
+            Dimension::Auto => content,
+            Dimension::Px(px) => *px,
+            Dimension::Percent(percent) => parent * percent,
+        }
+    }
+}
+
+/// Axis the children of a node are laid out along
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum FlexDirection {
+    Row,
+    #[default]
+    Column,
+}
+
+/// Placement of the children along the main axis when they don't fill it
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum Justify {
+    #[default]
+    Start,
+    Center,
+    End,
+    SpaceBetween,
+}
+
+/// Placement of the children along the cross axis
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum Align {
+    Start,
+    Center,
+    End,
+    /// Children with an `Auto` cross size fill the parent
+    #[default]
+    Stretch,
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct Edges {
+    pub left: f32,
+    pub top: f32,
+    pub right: f32,
+    pub bottom: f32,
+}
+
+impl Edges {
+    pub fn all(value: f32) -> Self {
+        Self::symmetric(value, value)
+    }
+
+    pub fn symmetric(horizontal: f32, vertical: f32) -> Self {
+        Self {
+            left: horizontal,
+            top: vertical,
+            right: horizontal,
+            bottom: vertical,
+        }
+    }
+
+    /// Sum of the left and the right, and the top and the bottom edges
+    pub fn size(&self) -> Vec2 {
+        Vec2::new(self.left + self.right, self.top + self.bottom)
+    }
+
+    pub fn top_left(&self) -> Vec2 {
+        Vec2::new(self.left, self.top)
+    }
+}
+
+/// Rectangle in pixels, `position` is the top-left corner
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct Rect {
+    pub position: Vec2,
+    pub size: Vec2,
+}
+
+impl Rect {
+    pub fn new(position: Vec2, size: Vec2) -> Self {
+        Self { position, size }
+    }
+
+    pub fn contains(&self, point: Vec2) -> bool {
+        let max = self.position + self.size;
+        point.x >= self.position.x
+            && point.y >= self.position.y
+            && point.x < max.x
+            && point.y < max.y
+    }
+
+    /// Rectangle inside of the edges
+    pub fn shrink(&self, edges: &Edges) -> Rect {
+        Rect {
+            position: self.position + edges.top_left(),
+            size: (self.size - edges.size()).sup(&Vec2::zeros()),
+        }
+    }
+}
+
+/// Flexbox-like layout of a node and its children
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct Style {
+    pub direction: FlexDirection,
+    pub width: Dimension,
+    pub height: Dimension,
+    pub padding: Edges,
+    pub margin: Edges,
+    /// Space between the children along the main axis
+    pub gap: f32,
+    pub justify: Justify,
+    pub align: Align,
+    /// Share of the free space of the parent along its main axis
+    pub grow: f32,
+}
+
+impl Style {
+    pub fn with_direction(mut self, direction: FlexDirection) -> Self {
+        self.direction = direction;
+        self
+    }
+
+    pub fn with_size(mut self, width: Dimension, height: Dimension) -> Self {
+        self.width = width;
+        self.height = height;
+        self
+    }
+
+    pub fn with_padding(mut self, padding: Edges) -> Self {
+        self.padding = padding;
+        self
+    }
+
+    pub fn with_margin(mut self, margin: Edges) -> Self {
+        self.margin = margin;
+        self
+    }
+
+    pub fn with_gap(mut self, gap: f32) -> Self {
+        self.gap = gap;
+        self
+    }
+
+    pub fn with_justify(mut self, justify: Justify) -> Self {
+        self.justify = justify;
+        self
+    }
+
+    pub fn with_align(mut self, align: Align) -> Self {
+        self.align = align;
+        self
+    }
+
+    pub fn with_grow(mut self, grow: f32) -> Self {
+        self.grow = grow;
+        self
+    }
+}
+
+/// Main and cross axis components of `v` for the direction
+pub(crate) fn split(direction: FlexDirection, v: Vec2) -> (f32, f32) {
+    match direction {
+        FlexDirection::Row => (v.x, v.y),
+        FlexDirection::Column => (v.y, v.x),
+    }
+}
+
+/// Inverse of [`split`]
+pub(crate) fn join(direction: FlexDirection, main: f32, cross: f32) -> Vec2 {
+    match direction {
+        FlexDirection::Row => Vec2::new(main, cross),
+        FlexDirection::Column => Vec2::new(cross, main),
+    }
+}
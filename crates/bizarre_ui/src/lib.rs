@@ -0,0 +1,12 @@
+pub mod atlas;
+pub mod ecs;
+pub mod layout;
+pub mod node;
+pub mod tree;
+
+pub use {
+    atlas::{AtlasRegion, FontAtlas, UiAtlas},
+    layout::{Align, Dimension, Edges, FlexDirection, Justify, Rect, Style},
+    node::{UiNode, UiNodeKind},
+    tree::{UiEvent, UiNodeId, UiTree},
+};
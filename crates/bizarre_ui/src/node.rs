@@ -0,0 +1,83 @@
+use nalgebra_glm::Vec4;
+
+use crate::layout::Style;
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum UiNodeKind {
+    /// Quad filled with the color of the node
+    Panel,
+    /// Region of the [`UiAtlas`](crate::UiAtlas) tinted with the color of the node
+    Image { region: String },
+    /// Text drawn with the font of the [`UiAtlas`](crate::UiAtlas), `size` is the
+    /// line height in pixels
+    Text { text: String, size: f32 },
+    /// Panel that changes its color under the mouse and reports
+    /// [`UiEvent::Clicked`](crate::UiEvent::Clicked)
+    Button { hovered: Vec4, pressed: Vec4 },
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct UiNode {
+    pub kind: UiNodeKind,
+    pub style: Style,
+    pub color: Vec4,
+    /// Hidden nodes and their children are not laid out, drawn or hit
+    pub visible: bool,
+}
+
+impl UiNode {
+    pub fn new(kind: UiNodeKind) -> Self {
+        Self {
+            kind,
+            style: Style::default(),
+            color: Vec4::repeat(1.0),
+            visible: true,
+        }
+    }
+
+    pub fn panel(color: Vec4) -> Self {
+        Self::new(UiNodeKind::Panel).with_color(color)
+    }
+
+    pub fn image(region: impl Into<String>) -> Self {
+        Self::new(UiNodeKind::Image {
+            region: region.into(),
+        })
+    }
+
+    pub fn text(text: impl Into<String>, size: f32) -> Self {
+        Self::new(UiNodeKind::Text {
+            text: text.into(),
+            size,
+        })
+    }
+
+    /// Hovered and pressed buttons are drawn lighter and darker
+    pub fn button(color: Vec4) -> Self {
+        let shade = |factor: f32| {
+            let rgb = color.xyz() * factor;
+            Vec4::new(rgb.x.min(1.0), rgb.y.min(1.0), rgb.z.min(1.0), color.w)
+        };
+
+        Self::new(UiNodeKind::Button {
+            hovered: shade(1.2),
+            pressed: shade(0.8),
+        })
+        .with_color(color)
+    }
+
+    pub fn with_style(mut self, style: Style) -> Self {
+        self.style = style;
+        self
+    }
+
+    pub fn with_color(mut self, color: Vec4) -> Self {
+        self.color = color;
+        self
+    }
+
+    pub fn with_visible(mut self, visible: bool) -> Self {
+        self.visible = visible;
+        self
+    }
+}
@@ -0,0 +1,586 @@
+use bizarre_ecs::prelude::*;
+use bizarre_event::EventQueue;
+use bizarre_render::{
+    render_target::RenderTargetHandle,
+    sprite::{Sprite, SpriteBatch},
+    texture::TextureHandle,
+};
+use bizarre_sdl::window::WindowHandle;
+use nalgebra_glm::{Vec2, Vec4};
+
+use crate::{
+    atlas::UiAtlas,
+    layout::{join, split, Align, Dimension, FlexDirection, Justify, Rect},
+    node::{UiNode, UiNodeKind},
+};
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct UiNodeId(usize);
+
+/// Pushed into the [`EventQueue`] by [`UiTree::update_input`]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum UiEvent {
+    /// Left mouse button was pressed and released over the button
+    Clicked(UiNodeId),
+    HoverStarted(UiNodeId),
+    HoverEnded(UiNodeId),
+}
+
+struct NodeEntry {
+    node: UiNode,
+    parent: Option<UiNodeId>,
+    children: Vec<UiNodeId>,
+    rect: Rect,
+}
+
+/// Retained tree of UI nodes drawn over a render target.
+///
+/// The root fills the whole target, nodes are laid out again only after the
+/// tree or the size of the target changed
+#[derive(Resource)]
+pub struct UiTree {
+    nodes: Vec<Option<NodeEntry>>,
+    free: Vec<usize>,
+    render_target: Option<RenderTargetHandle>,
+    window: Option<WindowHandle>,
+    viewport: Vec2,
+    dirty: bool,
+    hovered: Option<UiNodeId>,
+    pressed: Option<UiNodeId>,
+}
+
+impl Default for UiTree {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl UiTree {
+    pub fn new() -> Self {
+        let root = NodeEntry {
+            node: UiNode::panel(Vec4::zeros()),
+            parent: None,
+            children: Vec::new(),
+            rect: Rect::default(),
+        };
+
+        Self {
+            nodes: vec![Some(root)],
+            free: Vec::new(),
+            render_target: None,
+            window: None,
+            viewport: Vec2::zeros(),
+            dirty: true,
+            hovered: None,
+            pressed: None,
+        }
+    }
+
+    /// Draws the tree over `render_target` and takes the mouse input of `window`
+    pub fn with_target(mut self, render_target: RenderTargetHandle, window: WindowHandle) -> Self {
+        self.set_target(render_target, window);
+        self
+    }
+
+    pub fn set_target(&mut self, render_target: RenderTargetHandle, window: WindowHandle) {
+        self.render_target = Some(render_target);
+        self.window = Some(window);
+    }
+
+    pub fn render_target(&self) -> Option<RenderTargetHandle> {
+        self.render_target
+    }
+
+    pub fn window(&self) -> Option<WindowHandle> {
+        self.window
+    }
+
+    pub fn root(&self) -> UiNodeId {
+        UiNodeId(0)
+    }
+
+    /// Appends the node to the children of `parent`
+    ///
+    /// # Panics
+    ///
+    /// Panics if `parent` was removed
+    pub fn add(&mut self, parent: UiNodeId, node: UiNode) -> UiNodeId {
+        assert!(
+            self.contains(parent),
+            "Parent node {parent:?} does not exist"
+        );
+
+        let entry = NodeEntry {
+            node,
+            parent: Some(parent),
+            children: Vec::new(),
+            rect: Rect::default(),
+        };
+
+        let id = match self.free.pop() {
+            Some(index) => {
+                self.nodes[index] = Some(entry);
+                UiNodeId(index)
+            }
+            None => {
+                self.nodes.push(Some(entry));
+                UiNodeId(self.nodes.len() - 1)
+            }
+        };
+
+        self.entry_mut(parent).unwrap().children.push(id);
+        self.dirty = true;
+        id
+    }
+
+    /// Removes the node with its children, the root is only cleared
+    pub fn remove(&mut self, id: UiNodeId) {
+        let Some(entry) = self.entry_mut(id) else {
+            return;
+        };
+
+        let children = std::mem::take(&mut entry.children);
+        let parent = entry.parent;
+        children.into_iter().for_each(|child| self.remove(child));
+
+        if let Some(parent) = parent {
+            self.entry_mut(parent)
+                .unwrap()
+                .children
+                .retain(|child| *child != id);
+            self.nodes[id.0] = None;
+            self.free.push(id.0);
+
+            if self.hovered == Some(id) {
+                self.hovered = None;
+            }
+            if self.pressed == Some(id) {
+                self.pressed = None;
+            }
+        }
+
+        self.dirty = true;
+    }
+
+    pub fn contains(&self, id: UiNodeId) -> bool {
+        self.entry(id).is_some()
+    }
+
+    pub fn node(&self, id: UiNodeId) -> Option<&UiNode> {
+        self.entry(id).map(|entry| &entry.node)
+    }
+
+    /// Marks the tree for layout
+    pub fn node_mut(&mut self, id: UiNodeId) -> Option<&mut UiNode> {
+        self.dirty = true;
+        self.entry_mut(id).map(|entry| &mut entry.node)
+    }
+
+    pub fn parent(&self, id: UiNodeId) -> Option<UiNodeId> {
+        self.entry(id)?.parent
+    }
+
+    pub fn children(&self, id: UiNodeId) -> &[UiNodeId] {
+        self.entry(id).map_or(&[], |entry| &entry.children)
+    }
+
+    /// Rectangle of the node after the last layout
+    pub fn rect(&self, id: UiNodeId) -> Option<Rect> {
+        self.entry(id).map(|entry| entry.rect)
+    }
+
+    pub fn hovered(&self) -> Option<UiNodeId> {
+        self.hovered
+    }
+
+    /// Button the left mouse button was pressed over and is still held
+    pub fn pressed(&self) -> Option<UiNodeId> {
+        self.pressed
+    }
+
+    /// Lays the tree out over a viewport of the size if anything changed
+    pub fn layout(&mut self, viewport: Vec2, atlas: &UiAtlas) {
+        if !self.dirty && self.viewport == viewport {
+            return;
+        }
+
+        self.viewport = viewport;
+        self.dirty = false;
+        self.place(self.root(), Rect::new(Vec2::zeros(), viewport), atlas);
+    }
+
+    /// Topmost visible node under the point
+    pub fn hit_test(&self, point: Vec2) -> Option<UiNodeId> {
+        self.hit_test_node(self.root(), point)
+            .filter(|id| *id != self.root())
+    }
+
+    /// Updates the hovered and pressed nodes and pushes [`UiEvent`]s
+    pub fn update_input(&mut self, mouse: Vec2, mouse_pressed: bool, events: &mut EventQueue) {
+        let hit = self.hit_test(mouse);
+
+        if hit != self.hovered {
+            if let Some(id) = self.hovered {
+                events.push_event(UiEvent::HoverEnded(id));
+            }
+            if let Some(id) = hit {
+                events.push_event(UiEvent::HoverStarted(id));
+            }
+            self.hovered = hit;
+        }
+
+        let hit_button = hit.filter(|id| {
+            matches!(
+                self.node(*id).map(|node| &node.kind),
+                Some(UiNodeKind::Button { .. })
+            )
+        });
+
+        match (mouse_pressed, self.pressed) {
+            (true, None) => self.pressed = hit_button,
+            (false, Some(pressed)) => {
+                if hit_button == Some(pressed) {
+                    events.push_event(UiEvent::Clicked(pressed));
+                }
+                self.pressed = None;
+            }
+            _ => (),
+        }
+    }
+
+    /// Quads of the visible nodes in drawing order, a new batch starts every time
+    /// the texture changes
+    pub fn build_batches(&self, atlas: &UiAtlas) -> Vec<SpriteBatch> {
+        let mut batches = Vec::new();
+        self.collect_sprites(self.root(), atlas, &mut batches);
+        batches
+    }
+
+    fn entry(&self, id: UiNodeId) -> Option<&NodeEntry> {
+        self.nodes.get(id.0)?.as_ref()
+    }
+
+    fn entry_mut(&mut self, id: UiNodeId) -> Option<&mut NodeEntry> {
+        self.nodes.get_mut(id.0)?.as_mut()
+    }
+
+    fn visible_children(&self, id: UiNodeId) -> Vec<UiNodeId> {
+        self.children(id)
+            .iter()
+            .copied()
+            .filter(|child| self.node(*child).is_some_and(|node| node.visible))
+            .collect()
+    }
+
+    /// Size of the content of the node with its padding
+    fn measure(&self, id: UiNodeId, atlas: &UiAtlas) -> Vec2 {
+        let node = self.node(id).unwrap();
+        let style = &node.style;
+
+        let content = match &node.kind {
+            UiNodeKind::Text { text, size } => atlas.text_size(text, *size),
+            UiNodeKind::Image { region } => atlas
+                .region(region)
+                .map_or(Vec2::zeros(), |region| region.size.cast()),
+            UiNodeKind::Panel | UiNodeKind::Button { .. } => {
+                let children = self.visible_children(id);
+                let gaps = style.gap * children.len().saturating_sub(1) as f32;
+
+                let (main, cross) = children
+                    .iter()
+                    .map(|child| {
+                        let child_style = &self.node(*child).unwrap().style;
+                        let size = self.outer_size(*child, atlas) + child_style.margin.size();
+                        split(style.direction, size)
+                    })
+                    .fold(
+                        (gaps, 0.0f32),
+                        |(main, cross), (child_main, child_cross)| {
+                            (main + child_main, cross.max(child_cross))
+                        },
+                    );
+
+                join(style.direction, main, cross)
+            }
+        };
+
+        content + style.padding.size()
+    }
+
+    /// Size of the node when its parent size is unknown
+    fn outer_size(&self, id: UiNodeId, atlas: &UiAtlas) -> Vec2 {
+        let style = &self.node(id).unwrap().style;
+        let content = self.measure(id, atlas);
+
+        let resolve = |dimension: Dimension, content: f32| match dimension {
+            Dimension::Px(px) => px,
+            Dimension::Auto | Dimension::Percent(_) => content,
+        };
+
+        Vec2::new(
+            resolve(style.width, content.x),
+            resolve(style.height, content.y),
+        )
+    }
+
+    fn place(&mut self, id: UiNodeId, rect: Rect, atlas: &UiAtlas) {
+        self.entry_mut(id).unwrap().rect = rect;
+
+        let style = self.node(id).unwrap().style;
+        let inner = rect.shrink(&style.padding);
+        let (inner_main, inner_cross) = split(style.direction, inner.size);
+
+        let children = self.visible_children(id);
+        if children.is_empty() {
+            return;
+        }
+
+        let mut sizes = children
+            .iter()
+            .map(|child| {
+                let child_style = &self.node(*child).unwrap().style;
+                let content = self.measure(*child, atlas);
+                Vec2::new(
+                    child_style.width.resolve(inner.size.x, content.x),
+                    child_style.height.resolve(inner.size.y, content.y),
+                )
+            })
+            .collect::<Vec<_>>();
+
+        let styles = children
+            .iter()
+            .map(|child| self.node(*child).unwrap().style)
+            .collect::<Vec<_>>();
+
+        let used = sizes
+            .iter()
+            .zip(&styles)
+            .map(|(size, child)| split(style.direction, size + child.margin.size()).0)
+            .sum::<f32>()
+            + style.gap * (children.len() - 1) as f32;
+        let mut free = inner_main - used;
+
+        let grow = styles.iter().map(|child| child.grow.max(0.0)).sum::<f32>();
+        if free > 0.0 && grow > 0.0 {
+            for (size, child) in sizes.iter_mut().zip(&styles) {
+                let (main, cross) = split(style.direction, *size);
+                let main = main + free * child.grow.max(0.0) / grow;
+                *size = join(style.direction, main, cross);
+            }
+            free = 0.0;
+        }
+
+        let free = free.max(0.0);
+        let (mut cursor, spacing) = match style.justify {
+            Justify::Start => (0.0, 0.0),
+            Justify::Center => (free / 2.0, 0.0),
+            Justify::End => (free, 0.0),
+            Justify::SpaceBetween if children.len() > 1 => {
+                (0.0, free / (children.len() - 1) as f32)
+            }
+            Justify::SpaceBetween => (0.0, 0.0),
+        };
+
+        for ((child, size), child_style) in children.into_iter().zip(sizes).zip(styles) {
+            let (margin_start, margin_cross_start) =
+                split(style.direction, child_style.margin.top_left());
+            let (margin_main, margin_cross) = split(style.direction, child_style.margin.size());
+            let (main, mut cross) = split(style.direction, size);
+
+            let cross_dimension = match style.direction {
+                FlexDirection::Row => child_style.height,
+                FlexDirection::Column => child_style.width,
+            };
+
+            let free_cross = inner_cross - margin_cross;
+            let cross_offset = match style.align {
+                Align::Stretch if cross_dimension == Dimension::Auto => {
+                    cross = free_cross.max(0.0);
+                    margin_cross_start
+                }
+                Align::Start | Align::Stretch => margin_cross_start,
+                Align::Center => margin_cross_start + (free_cross - cross) / 2.0,
+                Align::End => margin_cross_start + free_cross - cross,
+            };
+
+            let position =
+                inner.position + join(style.direction, cursor + margin_start, cross_offset);
+            let size = join(style.direction, main, cross);
+            self.place(child, Rect::new(position, size), atlas);
+
+            cursor += main + margin_main + style.gap + spacing;
+        }
+    }
+
+    fn hit_test_node(&self, id: UiNodeId, point: Vec2) -> Option<UiNodeId> {
+        let entry = self.entry(id)?;
+
+        if !entry.node.visible || !entry.rect.contains(point) {
+            return None;
+        }
+
+        entry
+            .children
+            .iter()
+            .rev()
+            .find_map(|child| self.hit_test_node(*child, point))
+            .or(Some(id))
+    }
+
+    fn collect_sprites(&self, id: UiNodeId, atlas: &UiAtlas, batches: &mut Vec<SpriteBatch>) {
+        let Some(entry) = self.entry(id) else {
+            return;
+        };
+
+        let node = &entry.node;
+        if !node.visible {
+            return;
+        }
+
+        let rect = entry.rect;
+
+        match &node.kind {
+            UiNodeKind::Panel => {
+                if node.color.w > 0.0 {
+                    push_sprite(
+                        batches,
+                        None,
+                        Sprite::new(rect.position, rect.size).with_color(node.color),
+                    );
+                }
+            }
+            UiNodeKind::Button { hovered, pressed } => {
+                let color = if self.pressed == Some(id) {
+                    *pressed
+                } else if self.hovered == Some(id) {
+                    *hovered
+                } else {
+                    node.color
+                };
+
+                push_sprite(
+                    batches,
+                    None,
+                    Sprite::new(rect.position, rect.size).with_color(color),
+                );
+            }
+            UiNodeKind::Image { region } => {
+                let mut sprite = Sprite::new(rect.position, rect.size).with_color(node.color);
+                if let Some(region) = atlas.region(region) {
+                    let (min, max) = atlas.uv(region);
+                    sprite = sprite.with_uv(min, max);
+                }
+                push_sprite(batches, atlas.texture(), sprite);
+            }
+            UiNodeKind::Text { text, size } => {
+                if let Some(font) = atlas.font() {
+                    let advance = font.advance(*size);
+                    let origin = rect.shrink(&node.style.padding).position;
+
+                    for (line, text) in text.lines().enumerate() {
+                        for (column, c) in text.chars().enumerate() {
+                            let Some(glyph) = font.glyph(c) else {
+                                continue;
+                            };
+
+                            let (min, max) = atlas.uv(glyph);
+                            let position =
+                                origin + Vec2::new(column as f32 * advance, line as f32 * size);
+
+                            push_sprite(
+                                batches,
+                                atlas.texture(),
+                                Sprite::new(position, Vec2::new(advance, *size))
+                                    .with_uv(min, max)
+                                    .with_color(node.color),
+                            );
+                        }
+                    }
+                }
+            }
+        }
+
+        for child in &entry.children {
+            self.collect_sprites(*child, atlas, batches);
+        }
+    }
+}
+
+fn push_sprite(batches: &mut Vec<SpriteBatch>, texture: Option<TextureHandle>, sprite: Sprite) {
+    match batches.last_mut() {
+        Some(batch) if batch.texture == texture => batch.sprites.push(sprite),
+        _ => {
+            let mut batch = SpriteBatch::new(texture);
+            batch.sprites.push(sprite);
+            batches.push(batch);
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use bizarre_event::EventQueue;
+    use nalgebra_glm::{Vec2, Vec4};
+
+    use crate::{
+        layout::{Dimension, Edges, FlexDirection, Justify, Style},
+        UiAtlas, UiEvent, UiNode, UiTree,
+    };
+
+    #[test]
+    fn should_lay_out_row_and_hit_test_buttons() {
+        let atlas = UiAtlas::untextured();
+        let mut tree = UiTree::new();
+
+        let bar = tree.add(
+            tree.root(),
+            UiNode::panel(Vec4::repeat(1.0)).with_style(
+                Style::default()
+                    .with_direction(FlexDirection::Row)
+                    .with_size(Dimension::Auto, Dimension::Px(40.0))
+                    .with_padding(Edges::all(10.0))
+                    .with_gap(20.0)
+                    .with_justify(Justify::End),
+            ),
+        );
+
+        let button_style = Style::default().with_size(Dimension::Px(50.0), Dimension::Auto);
+        let first = tree.add(
+            bar,
+            UiNode::button(Vec4::repeat(0.5)).with_style(button_style),
+        );
+        let second = tree.add(
+            bar,
+            UiNode::button(Vec4::repeat(0.5)).with_style(button_style.with_grow(1.0)),
+        );
+
+        tree.layout(Vec2::new(400.0, 300.0), &atlas);
+
+        assert_eq!(tree.rect(bar).unwrap().size, Vec2::new(400.0, 40.0));
+
+        let first_rect = tree.rect(first).unwrap();
+        assert_eq!(first_rect.position, Vec2::new(10.0, 10.0));
+        assert_eq!(first_rect.size, Vec2::new(50.0, 20.0));
+
+        let second_rect = tree.rect(second).unwrap();
+        assert_eq!(second_rect.position, Vec2::new(80.0, 10.0));
+        assert_eq!(second_rect.size, Vec2::new(310.0, 20.0));
+
+        assert_eq!(tree.hit_test(Vec2::new(15.0, 15.0)), Some(first));
+        assert_eq!(tree.hit_test(Vec2::new(70.0, 15.0)), Some(bar));
+        assert_eq!(tree.hit_test(Vec2::new(200.0, 200.0)), None);
+
+        let mut events = EventQueue::new();
+        let reader = events.create_reader();
+        events.register_reader::<UiEvent>(reader).unwrap();
+
+        tree.update_input(Vec2::new(100.0, 15.0), true, &mut events);
+        tree.update_input(Vec2::new(100.0, 15.0), false, &mut events);
+        events.change_frames();
+
+        assert_eq!(
+            events.pull_events::<UiEvent>(&reader),
+            vec![UiEvent::HoverStarted(second), UiEvent::Clicked(second)]
+        );
+    }
+}
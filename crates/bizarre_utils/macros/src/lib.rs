@@ -1,39 +1,105 @@
 use proc_macro::TokenStream;
 use quote::{format_ident, quote};
-use syn::{parse::Parse, token::Comma, Ident, LitInt};
+use syn::{
+    parse::{Parse, ParseStream},
+    token::{Comma, Semi},
+    Ident, LitInt, Token,
+};
 
 struct MassImplScaffoldInput {
     macro_ident: Ident,
+    start: usize,
     len: usize,
     idents: Vec<Ident>,
+    fixed: Vec<Ident>,
+    doc_hidden: bool,
 }
 
 impl Parse for MassImplScaffoldInput {
-    fn parse(input: syn::parse::ParseStream) -> syn::Result<Self> {
+    fn parse(input: ParseStream) -> syn::Result<Self> {
         let macro_ident = input.parse::<Ident>()?;
         input.parse::<Comma>()?;
-        let len = input.parse::<LitInt>()?.base10_parse()?;
+
+        let first = input.parse::<LitInt>()?;
+        let (start, len) = if input.parse::<Option<Token![..=]>>()?.is_some() {
+            let last = input.parse::<LitInt>()?;
+            (first.base10_parse()?, last.base10_parse()?)
+        } else {
+            (1, first.base10_parse()?)
+        };
+
+        if start == 0 || start > len {
+            return Err(syn::Error::new(
+                first.span(),
+                format!("invalid arity range `{start}..={len}`, expected `1 <= start <= len`"),
+            ));
+        }
 
         let mut idents = vec![];
 
-        while input.parse::<Comma>().is_ok() {
+        while input.parse::<Option<Comma>>()?.is_some() {
             idents.push(input.parse::<Ident>()?);
         }
 
+        let mut fixed = vec![];
+        let mut doc_hidden = false;
+
+        while input.parse::<Option<Semi>>()?.is_some() {
+            let option = input.parse::<Ident>()?;
+
+            match option.to_string().as_str() {
+                "fixed" => {
+                    input.parse::<Token![=]>()?;
+                    fixed.push(input.parse::<Ident>()?);
+                    while input.peek(Comma) {
+                        input.parse::<Comma>()?;
+                        fixed.push(input.parse::<Ident>()?);
+                    }
+                }
+                "doc_hidden" => doc_hidden = true,
+                _ => {
+                    return Err(syn::Error::new(
+                        option.span(),
+                        format!("unknown option `{option}`, expected `fixed` or `doc_hidden`"),
+                    ))
+                }
+            }
+        }
+
         Ok(MassImplScaffoldInput {
             macro_ident,
+            start,
             len,
             idents,
+            fixed,
+            doc_hidden,
         })
     }
 }
 
+/// Invokes a `macro_rules!` macro once for every tuple arity, e.g.
+/// `mass_impl!(impl_x, 3, T)` expands to `impl_x!(T0); impl_x!(T0, T1); impl_x!(T0, T1, T2);`.
+///
+/// Passing several idents makes every element a tuple of them:
+/// `mass_impl!(impl_x, 2, C, M)` expands to `impl_x!((C0, M0)); impl_x!((C0, M0), (C1, M1));`.
+///
+/// The arity can be given as a range (`2..=16`) to skip the smallest tuples. Options can follow the
+/// idents, each after a `;`:
+/// - `fixed = A, B` passes the `A, B` idents to every invocation
+/// - `doc_hidden` passes `#[doc(hidden)]` to every invocation but the first one, so the docs only
+///   show the smallest impl
+///
+/// With any option set the invocations become `impl_x!(#[doc(hidden)] A, B; T0, T1)`, so the
+/// macro should match `($(#[$meta:meta])* $($fixed:ident),*; $($param:tt),+)`.
 #[proc_macro]
 pub fn mass_impl(input: TokenStream) -> TokenStream {
     let MassImplScaffoldInput {
         macro_ident,
+        start,
         len,
         idents,
+        fixed,
+        doc_hidden,
     } = syn::parse_macro_input!(input as MassImplScaffoldInput);
 
     let input_idents = idents;
@@ -54,11 +120,21 @@ pub fn mass_impl(input: TokenStream) -> TokenStream {
         }
     }
 
-    let invocations = (1..=len).map(|i| {
+    let has_options = doc_hidden || !fixed.is_empty();
+
+    let invocations = (start..=len).map(|i| {
         let tuples = &tuples[..i];
 
+        if !has_options {
+            return quote! {
+                #macro_ident!(#(#tuples),*);
+            };
+        }
+
+        let attrs = (doc_hidden && i > start).then(|| quote! { #[doc(hidden)] });
+
         quote! {
-            #macro_ident!(#(#tuples),*);
+            #macro_ident!(#attrs #(#fixed),*; #(#tuples),*);
         }
     });
 
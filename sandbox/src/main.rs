@@ -4,20 +4,35 @@ use anyhow::Result;
 
 use bizarre_engine::{
     app::AppBuilder,
-    ecs::{system::schedule::Schedule, world::ecs_module::EcsModule},
-    ecs_modules::sdl_module::SdlModule,
-    event::Events,
+    ecs::{
+        system::{schedule::Schedule, system_config::IntoSystemConfigs},
+        world::ecs_module::EcsModule,
+    },
+    ecs_modules::{
+        console_module::ConsoleModule, sdl_module::SdlModule, window_module::WindowConfig,
+    },
+    event::EventReader,
     prelude::{Res, ResMut, *},
     render::{
-        material::builtin::basic_deferred,
+        ecs::{
+            release_closed_present_targets, teardown_render, update_gpu_memory_stats,
+            update_present_target_visibility, RenderSet,
+        },
+        material::builtin::pbr_deferred,
+        memory_stats::GpuMemoryStats,
         present_target::{PresentError, PresentTargetHandle},
         render_assets::{AssetStore, RenderAssets},
         render_target::RenderTargetHandle,
         renderer::{RenderError, VulkanRenderer},
-        scene::{SceneHandle, SceneUniform},
+        sampler::SamplerConfig,
+        scene::{
+            light::{DirectionalLight, Light},
+            SceneHandle, SceneUniform,
+        },
         submitter::RenderPackage,
+        InstanceConfig,
     },
-    sdl::window::{WindowCreateInfo, WindowEvent, WindowPosition, Windows},
+    sdl::window::{WindowEvent, Windows},
 };
 
 use nalgebra_glm::{look_at, perspective, Mat4, UVec2, Vec3};
@@ -36,21 +51,27 @@ struct MainRenderTarget(pub RenderTargetHandle);
 #[derive(Resource)]
 struct MainScene(pub SceneHandle);
 
+/// Submitted with every render package, so the camera is never a frame behind
+#[derive(Resource)]
+struct MainCamera(pub SceneUniform);
+
 impl EcsModule for RenderModule {
     fn apply(self, world: &mut bizarre_engine::ecs::world::World) {
         let renderer = VulkanRenderer::new().unwrap();
-        let main_window = world
-            .resource_mut::<Windows>()
-            .unwrap()
-            .get_main_window()
-            .unwrap();
+        let windows = world.resource_mut::<Windows>().unwrap();
+        let main_window = windows.get_main_window().unwrap();
+        let vsync = windows.vsync(&windows.main_window_handle().unwrap());
 
         let mut assets = RenderAssets::new();
 
         let present_target_handle =
             assets.create_present_target2(&main_window, renderer.image_count());
 
-        let (width, height) = main_window.size();
+        assets
+            .present_target_mut(&present_target_handle)
+            .unwrap()
+            .set_vsync(vsync)
+            .unwrap();
 
         let image_count = renderer.image_count();
 
@@ -64,30 +85,40 @@ impl EcsModule for RenderModule {
 
         let mesh = assets.load_mesh("assets/meshes/cube.obj");
 
-        let material = assets.insert_material(basic_deferred());
+        let material = assets.insert_material(pbr_deferred());
         let (instance_handle, _) = assets.create_material_instance(material).unwrap();
 
         let scene_handle = assets.create_scene(image_count);
         let scene = assets.scene_mut(&scene_handle).unwrap();
 
-        let view = default_view();
-
-        let projection = perspective(
-            width as f32 / height as f32,
-            90.0f32.to_radians(),
-            0.1,
-            1000.0,
-        );
-
-        scene.update_scene_uniform(SceneUniform { view, projection });
+        scene
+            .lights_mut()
+            .push(Light::Directional(DirectionalLight {
+                position: Vec3::zeros(),
+                direction: Vec3::new(-0.4, -1.0, -0.3),
+                color: Vec3::new(1.0, 0.96, 0.9),
+                intensity: 3.0,
+                fov: 0.0,
+            }));
 
         world.insert_resource(MainPresentTarget(present_target_handle));
         world.insert_resource(MainRenderTarget(render_target));
         world.insert_resource(MainScene(scene_handle));
+        world.insert_resource(MainCamera(main_camera(extent)));
         world.insert_resource(renderer);
         world.insert_resource(assets);
-
-        world.add_systems(Schedule::Update, render);
+        world.insert_resource(GpuMemoryStats::default());
+
+        world.add_systems(
+            Schedule::Update,
+            (
+                update_present_target_visibility,
+                release_closed_present_targets,
+            ),
+        );
+        world.add_systems(Schedule::Update, render.in_set(RenderSet));
+        world.add_systems(Schedule::Update, update_gpu_memory_stats);
+        world.add_teardown(teardown_render);
     }
 }
 
@@ -99,6 +130,17 @@ fn default_view() -> Mat4 {
     )
 }
 
+fn main_camera(size: UVec2) -> SceneUniform {
+    let projection = perspective(
+        size.x as f32 / size.y as f32,
+        90.0f32.to_radians(),
+        0.1,
+        1000.0,
+    );
+
+    SceneUniform::new(default_view(), projection)
+}
+
 fn render(
     mut renderer: ResMut<VulkanRenderer>,
     mut assets: ResMut<RenderAssets>,
@@ -106,8 +148,8 @@ fn render(
     present_target: Res<MainPresentTarget>,
     render_target: Res<MainRenderTarget>,
     scene_handle: Res<MainScene>,
-    window_events: Events<WindowEvent>,
-    mut skip_render: Local<bool>,
+    mut camera: ResMut<MainCamera>,
+    window_events: EventReader<WindowEvent>,
 ) {
     let elapsed = last_render.elapsed();
     let target = Duration::from_millis(16);
@@ -119,7 +161,7 @@ fn render(
 
     for event in window_events {
         match event {
-            WindowEvent::Resized { size, .. } if size.x == 0 || size.y == 0 => *skip_render = true,
+            WindowEvent::Resized { size, .. } if size.x == 0 || size.y == 0 => (),
             WindowEvent::Resized { handle, size } => {
                 let handle = PresentTargetHandle::from_raw(handle.as_raw());
                 let present_target = assets.present_target_mut(&handle).unwrap();
@@ -129,39 +171,16 @@ fn render(
                     .render_targets
                     .get_mut(&render_target.0)
                     .unwrap()
-                    .resize(size)
+                    .resize(*size)
                     .unwrap();
 
-                let view = default_view();
-
-                let projection = perspective(
-                    size.x as f32 / size.y as f32,
-                    90.0f32.to_radians(),
-                    0.1,
-                    1000.0,
-                );
-
-                assets
-                    .scene_mut(&scene_handle.0)
-                    .unwrap()
-                    .update_scene_uniform(SceneUniform { view, projection });
-
-                *skip_render = false
+                camera.0 = main_camera(*size);
             }
-            WindowEvent::Exposed(..) => *skip_render = false,
-            WindowEvent::Hidden(..) => *skip_render = true,
             _ => (),
         }
     }
 
-    if *skip_render {
-        return;
-    }
-
-    let render_package = RenderPackage {
-        pov: Mat4::default(),
-        scene: SceneHandle::from_raw(0usize),
-    };
+    let render_package = RenderPackage::new(scene_handle.0).with_camera(camera.0.clone());
 
     let render_extent = {
         assets
@@ -177,11 +196,13 @@ fn render(
     let present_result = match render_result {
         Ok(()) => renderer.present_to_target(&mut assets, present_target.0, render_target.0),
         Err(RenderError::RenderSkipped) => Err(PresentError::PresentSkipped),
+        Err(RenderError::DeviceLost) => Err(PresentError::DeviceLost),
         Err(err) => panic!("Failed render: {err:?}"),
     };
 
     match present_result {
         Ok(()) | Err(PresentError::PresentSkipped) => {}
+        Err(PresentError::DeviceLost) => renderer.recover_device(&mut assets).unwrap(),
         Err(err) => panic!("Failed present: {err:?}"),
     }
 }
@@ -189,15 +210,13 @@ fn render(
 fn main() -> Result<()> {
     AppBuilder::default()
         .with_name("Bizarre Engine")
-        .with_module(
-            SdlModule::new().with_main_window(WindowCreateInfo::normal_window(
-                "Bizarre Window".into(),
-                UVec2::new(800, 600),
-                WindowPosition::Undefined,
-            )),
-        )
+        .with_config_section::<InstanceConfig>()
+        .with_config_section::<SamplerConfig>()
+        .with_config_section::<WindowConfig>()
+        .with_module(SdlModule::new())
         .with_module(RenderModule)
         .with_module(SandboxModule)
+        .with_module(ConsoleModule::new())
         .build()
         .run()
 }
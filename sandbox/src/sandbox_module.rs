@@ -13,13 +13,13 @@ use bizarre_engine::{
         mesh::MeshHandle,
         render_assets::RenderAssets,
         scene::{
+            instance_data::{GpuInstanceData, InstanceData},
             render_object::{
                 RenderObject, RenderObjectFlags, RenderObjectMaterials, RenderObjectMeta,
             },
             InstanceData, RenderObjectId,
         },
         shader::ShaderStage,
-        uniform_block_def,
     },
     sdl::input::{InputEvent, InputState, Scancode},
 };
@@ -86,7 +86,7 @@ fn setup_cubes(mut assets: ResMut<RenderAssets>, scene_handle: Res<MainScene>, m
     });
 
     let material_handle = assets.insert_material(material);
-    let (instance_handle, ..) = assets.create_material_instance(material_handle).unwrap();
+    let instance_handle = assets.shared_material_instance(material_handle).unwrap();
 
     let scene = assets.scene_mut(&scene_handle.0).unwrap();
 
@@ -122,9 +122,7 @@ fn setup_cubes(mut assets: ResMut<RenderAssets>, scene_handle: Res<MainScene>, m
                     mesh: MeshHandle::from_raw(0usize),
                 };
 
-                let instance_data = InstanceData {
-                    transform: transform.get_transform(),
-                };
+                let instance_data = InstanceData::new(transform.get_transform());
 
                 let render_object = RenderObject::new(meta, instance_data);
                 (scene.add_object(render_object), false)
@@ -153,29 +151,28 @@ fn update_cubes(
     for (transform, id, is_colored) in cubes {
         transform.rotation.y += ROTATION_SPEED_DEG * elapsed.as_secs_f32();
         if is_colored.0 {
-            scene.update_object(
-                *id,
-                CubeInstanceData {
-                    transform: transform.get_transform(),
-                    color: COLORS[(id.inner() * 2) % 3],
-                },
-            );
+            scene
+                .update_object(
+                    *id,
+                    CubeInstanceData {
+                        transform: transform.get_transform(),
+                        color: COLORS[(id.inner() * 2) % 3],
+                    },
+                )
+                .unwrap();
         } else {
-            scene.update_object(
-                *id,
-                InstanceData {
-                    transform: transform.get_transform(),
-                },
-            );
+            scene
+                .update_object(*id, InstanceData::new(transform.get_transform()))
+                .unwrap();
         }
     }
 }
 
-uniform_block_def! {
-    struct CubeInstanceData {
-        transform: Mat4,
-        color: Vec3,
-    }
+#[repr(C, align(16))]
+#[derive(Clone, InstanceData)]
+struct CubeInstanceData {
+    transform: Mat4,
+    color: Vec3,
 }
 
 const COLORS: [Vec3; 3] = [